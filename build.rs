@@ -13,6 +13,11 @@ use std::fs::File;
 use std::io::Write;
 use std::path::PathBuf;
 
+#[path = "build/logo.rs"]
+mod logo;
+#[path = "build/png.rs"]
+mod png;
+
 fn main() {
     // Put `memory.x` in our output directory and ensure it's
     // on the linker search path.
@@ -28,4 +33,7 @@ fn main() {
     // here, we ensure the build script is only re-run when
     // `memory.x` is changed.
     println!("cargo:rerun-if-changed=memory.x");
+
+    // 开机 logo：assets/logo.png 或者 logo.pbm 转成显存格式，见 build/logo.rs
+    logo::generate(out);
 }
@@ -0,0 +1,178 @@
+//! 开机 logo：把 assets/logo.png(或者 assets/logo.pbm)转成屏幕显存的按页格式，生成 `$OUT_DIR/logo.rs`
+//!
+//! 显存的格式(见 src/framebuffer.rs)：一页是 8 行，一页里每一列一个字节，最低位在最上面，页从上往下排。
+//! 生成的文件里是 `LOGO: Option<Logo>`，固件里的 src/logo.rs 把它 `include!` 进去；两个文件都没有就是 None。
+//!
+//! - PNG：亮度(0..=255)不低于阈值、而且不透明(alpha >= 128)的像素点亮。阈值默认 128，
+//!   环境变量 `LOGO_THRESHOLD` 可以改，和 tools/sprite_preview.py 的 `--threshold` 一样
+//! - PBM(P1 文本或者 P4 二进制)：1(黑)的像素点亮，也就是纸上画黑的地方在屏幕上是亮的
+//!
+//! 尺寸最大是屏幕大小：128x64，打开 `panel-128x32` 的话 128x32。高度不是 8 的倍数的话下面补暗的行补齐一页。
+
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use crate::png;
+
+/// 按顺序找，先找到哪个用哪个
+const CANDIDATES: [&str; 2] = ["assets/logo.png", "assets/logo.pbm"];
+
+const MAX_WIDTH: u32 = 128;
+
+/// 解出来的单色图，一行一行
+struct Bitmap {
+    width: u32,
+    height: u32,
+    lit: Vec<bool>,
+}
+
+impl Bitmap {
+    /// 按页打包：每页 `width` 个字节
+    fn pack_pages(&self) -> Vec<u8> {
+        let pages = self.height.div_ceil(8);
+        let mut bytes = vec![0u8; (pages * self.width) as usize];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if self.lit[(y * self.width + x) as usize] {
+                    bytes[((y / 8) * self.width + x) as usize] |= 1 << (y % 8);
+                }
+            }
+        }
+        bytes
+    }
+}
+
+/// 生成 `$OUT_DIR/logo.rs`。图不对(格式坏了、太大)直接让构建失败，免得烧进去才发现
+pub fn generate(out: &Path) {
+    println!("cargo:rerun-if-changed=assets");
+    println!("cargo:rerun-if-env-changed=LOGO_THRESHOLD");
+    let max_height = if std::env::var_os("CARGO_FEATURE_PANEL_128X32").is_some() {
+        32
+    } else {
+        64
+    };
+    let threshold = match std::env::var("LOGO_THRESHOLD") {
+        Ok(value) => value
+            .parse::<u8>()
+            .unwrap_or_else(|_| panic!("LOGO_THRESHOLD must be 0..=255, got {value:?}")),
+        Err(_) => 128,
+    };
+
+    let bitmap = CANDIDATES.iter().find_map(|path| {
+        let data = fs::read(path).ok()?;
+        let decoded = if path.ends_with(".png") {
+            read_png(&data, threshold)
+        } else {
+            read_pbm(&data)
+        };
+        Some(decoded.unwrap_or_else(|err| panic!("{path}: {err}")))
+    });
+
+    let mut code = String::from("// build.rs 生成的，见 build/logo.rs\n");
+    match bitmap {
+        Some(bitmap) => {
+            if bitmap.width == 0 || bitmap.height == 0 {
+                panic!("logo is empty");
+            }
+            if bitmap.width > MAX_WIDTH || bitmap.height > max_height {
+                panic!(
+                    "logo is {}x{}, the panel is {MAX_WIDTH}x{max_height}",
+                    bitmap.width, bitmap.height
+                );
+            }
+            let bytes = bitmap.pack_pages();
+            let _ = writeln!(code, "pub const LOGO: Option<Logo> = Some(Logo {{");
+            let _ = writeln!(code, "    width: {},", bitmap.width);
+            let _ = writeln!(code, "    height: {},", bitmap.height);
+            let _ = writeln!(code, "    pages: &[");
+            for page in bytes.chunks(bitmap.width as usize) {
+                let line: Vec<String> = page.iter().map(|byte| format!("0x{byte:02X}")).collect();
+                let _ = writeln!(code, "        {},", line.join(", "));
+            }
+            let _ = writeln!(code, "    ],");
+            let _ = writeln!(code, "}});");
+        }
+        None => code.push_str("pub const LOGO: Option<Logo> = None;\n"),
+    }
+    fs::write(out.join("logo.rs"), code).unwrap();
+}
+
+fn read_png(data: &[u8], threshold: u8) -> Result<Bitmap, String> {
+    let image = png::decode(data)?;
+    Ok(Bitmap {
+        width: image.width,
+        height: image.height,
+        lit: image
+            .pixels
+            .iter()
+            .map(|&(luma, alpha)| alpha >= 128 && luma >= threshold)
+            .collect(),
+    })
+}
+
+/// P1(文本)和 P4(二进制)，`#` 开头的注释跳过
+fn read_pbm(data: &[u8]) -> Result<Bitmap, String> {
+    let mut pos = 0;
+    // 跳过空白和注释，读一个词
+    let token = |pos: &mut usize| -> Result<String, String> {
+        loop {
+            match data.get(*pos) {
+                Some(b'#') => {
+                    while data.get(*pos).is_some_and(|&c| c != b'\n') {
+                        *pos += 1;
+                    }
+                }
+                Some(c) if c.is_ascii_whitespace() => *pos += 1,
+                Some(_) => break,
+                None => return Err("unexpected end of file".into()),
+            }
+        }
+        let start = *pos;
+        while data.get(*pos).is_some_and(|c| !c.is_ascii_whitespace()) {
+            *pos += 1;
+        }
+        Ok(String::from_utf8_lossy(&data[start..*pos]).into_owned())
+    };
+    let magic = token(&mut pos)?;
+    let number = |text: String| {
+        text.parse::<u32>()
+            .map_err(|_| format!("bad number {text:?}"))
+    };
+    let width = number(token(&mut pos)?)?;
+    let height = number(token(&mut pos)?)?;
+    let count = (width * height) as usize;
+    let lit = match magic.as_str() {
+        "P1" => {
+            // 像素之间可以没有空白，一个字一个像素
+            let mut lit = Vec::with_capacity(count);
+            for &c in &data[pos..] {
+                match c {
+                    b'0' | b'1' if lit.len() < count => lit.push(c == b'1'),
+                    _ => {}
+                }
+            }
+            lit
+        }
+        "P4" => {
+            // 尺寸后面正好一个空白，然后每行补齐到整字节，高位在左
+            let body = data.get(pos + 1..).unwrap_or_default();
+            let stride = width.div_ceil(8) as usize;
+            if body.len() < stride * height as usize {
+                return Err("not enough pixel data".into());
+            }
+            (0..count)
+                .map(|i| {
+                    let (x, y) = (i % width as usize, i / width as usize);
+                    body.get(y * stride + x / 8)
+                        .is_some_and(|byte| byte & (0x80 >> (x % 8)) != 0)
+                })
+                .collect()
+        }
+        other => return Err(format!("unsupported PBM type {other:?}, use P1 or P4")),
+    };
+    if lit.len() != count {
+        return Err("not enough pixel data".into());
+    }
+    Ok(Bitmap { width, height, lit })
+}
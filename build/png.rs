@@ -0,0 +1,396 @@
+//! 构建脚本用的最小 PNG 解码：只解出每个像素的亮度和透明度，够开机 logo 用
+//!
+//! 构建环境不一定拉得到 crates.io，所以不依赖 png 这些库，自己解：zlib(deflate)解压、按行反滤波、按颜色类型取像素。
+//! 支持所有非隔行的颜色类型和位深度(灰度、RGB、调色板、带透明度的)，16 位的只取高 8 位；隔行扫描的不支持，
+//! 图片软件导出的时候把 interlace 关掉就行。
+
+/// 解出来的图：每个像素 (亮度 0..=255, 不透明度 0..=255)，一行一行
+pub struct Decoded {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<(u8, u8)>,
+}
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+pub fn decode(data: &[u8]) -> Result<Decoded, String> {
+    if !data.starts_with(&SIGNATURE) {
+        return Err("not a PNG file".into());
+    }
+    let mut pos = SIGNATURE.len();
+    let mut header = None;
+    let mut palette: Vec<[u8; 3]> = Vec::new();
+    let mut palette_alpha: Vec<u8> = Vec::new();
+    let mut compressed = Vec::new();
+    while pos + 8 <= data.len() {
+        let len = u32::from_be_bytes(data[pos..pos + 4].try_into().unwrap()) as usize;
+        let kind = &data[pos + 4..pos + 8];
+        let body = data.get(pos + 8..pos + 8 + len).ok_or("truncated chunk")?;
+        match kind {
+            b"IHDR" => header = Some(Header::parse(body)?),
+            b"PLTE" => {
+                palette = body
+                    .chunks_exact(3)
+                    .map(|rgb| [rgb[0], rgb[1], rgb[2]])
+                    .collect()
+            }
+            b"tRNS" => palette_alpha = body.to_vec(),
+            b"IDAT" => compressed.extend_from_slice(body),
+            b"IEND" => break,
+            _ => {}
+        }
+        // 长度、类型、数据、CRC
+        pos += 12 + len;
+    }
+    let header = header.ok_or("missing IHDR")?;
+    if header.interlace != 0 {
+        return Err("interlaced PNG is not supported, export it without interlacing".into());
+    }
+    let raw = inflate_zlib(&compressed)?;
+    let rows = unfilter(&raw, &header)?;
+
+    let mut pixels = Vec::with_capacity((header.width * header.height) as usize);
+    let max = (1u32 << header.depth.min(8)) - 1;
+    for row in &rows {
+        for x in 0..header.width as usize {
+            let sample = |channel: usize| header.sample(row, x, channel);
+            // 灰度按位深度拉到 0..=255，调色板是下标不拉
+            let scale = |value: u32| (value * 255 / max) as u8;
+            let pixel = match header.color {
+                0 => (scale(sample(0)), 255),
+                4 => (scale(sample(0)), scale(sample(1))),
+                2 => (luma(sample(0), sample(1), sample(2)), 255),
+                6 => (luma(sample(0), sample(1), sample(2)), sample(3) as u8),
+                3 => {
+                    let index = sample(0) as usize;
+                    let [r, g, b] = *palette.get(index).ok_or("palette index out of range")?;
+                    let alpha = palette_alpha.get(index).copied().unwrap_or(255);
+                    (luma(r as u32, g as u32, b as u32), alpha)
+                }
+                color => return Err(format!("unknown color type {color}")),
+            };
+            pixels.push(pixel);
+        }
+    }
+    Ok(Decoded {
+        width: header.width,
+        height: header.height,
+        pixels,
+    })
+}
+
+/// 和 tools/sprite_preview.py 一样的亮度公式
+fn luma(r: u32, g: u32, b: u32) -> u8 {
+    ((r * 299 + g * 587 + b * 114) / 1000) as u8
+}
+
+struct Header {
+    width: u32,
+    height: u32,
+    depth: u32,
+    color: u8,
+    interlace: u8,
+}
+
+impl Header {
+    fn parse(body: &[u8]) -> Result<Self, String> {
+        if body.len() != 13 {
+            return Err("bad IHDR".into());
+        }
+        Ok(Self {
+            width: u32::from_be_bytes(body[0..4].try_into().unwrap()),
+            height: u32::from_be_bytes(body[4..8].try_into().unwrap()),
+            depth: body[8] as u32,
+            color: body[9],
+            interlace: body[12],
+        })
+    }
+
+    fn channels(&self) -> u32 {
+        match self.color {
+            2 => 3,
+            4 => 2,
+            6 => 4,
+            _ => 1,
+        }
+    }
+
+    /// 一个像素几位
+    fn pixel_bits(&self) -> u32 {
+        self.channels() * self.depth
+    }
+
+    /// 一行几个字节(不算行首的滤波类型)
+    fn row_bytes(&self) -> usize {
+        (self.width * self.pixel_bits()).div_ceil(8) as usize
+    }
+
+    /// 第 `x` 个像素的第 `channel` 个通道，16 位的取高 8 位
+    fn sample(&self, row: &[u8], x: usize, channel: usize) -> u32 {
+        let bit = (x * self.channels() as usize + channel) * self.depth as usize;
+        match self.depth {
+            16 => row[bit / 8] as u32,
+            8 => row[bit / 8] as u32,
+            depth => {
+                let shift = 8 - depth as usize - bit % 8;
+                (row[bit / 8] as u32 >> shift) & ((1 << depth) - 1)
+            }
+        }
+    }
+}
+
+/// 每行开头一个字节的滤波类型，反过来算出原始的字节
+fn unfilter(raw: &[u8], header: &Header) -> Result<Vec<Vec<u8>>, String> {
+    let stride = header.row_bytes();
+    // 滤波按字节算"左边那个像素"，不到一个字节的按一个字节
+    let left = (header.pixel_bits() as usize).div_ceil(8);
+    let mut rows: Vec<Vec<u8>> = Vec::with_capacity(header.height as usize);
+    for y in 0..header.height as usize {
+        let start = y * (stride + 1);
+        let line = raw
+            .get(start..start + stride + 1)
+            .ok_or("image data too short")?;
+        let (filter, line) = (line[0], &line[1..]);
+        let mut row = vec![0u8; stride];
+        for i in 0..stride {
+            let a = if i >= left { row[i - left] as i16 } else { 0 };
+            let b = rows.last().map_or(0, |up| up[i] as i16);
+            let c = match rows.last() {
+                Some(up) if i >= left => up[i - left] as i16,
+                _ => 0,
+            };
+            let predicted = match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => (a + b) / 2,
+                4 => {
+                    let p = a + b - c;
+                    let (pa, pb, pc) = ((p - a).abs(), (p - b).abs(), (p - c).abs());
+                    if pa <= pb && pa <= pc {
+                        a
+                    } else if pb <= pc {
+                        b
+                    } else {
+                        c
+                    }
+                }
+                filter => return Err(format!("unknown filter type {filter}")),
+            };
+            row[i] = line[i].wrapping_add(predicted as u8);
+        }
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// 按位读，低位在前(deflate 的顺序)
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit: u32,
+}
+
+impl Bits<'_> {
+    fn bit(&mut self) -> Result<u32, String> {
+        let byte = *self.data.get(self.pos).ok_or("compressed data too short")?;
+        let value = (byte as u32 >> self.bit) & 1;
+        self.bit += 1;
+        if self.bit == 8 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+        Ok(value)
+    }
+
+    fn bits(&mut self, count: u32) -> Result<u32, String> {
+        let mut value = 0;
+        for i in 0..count {
+            value |= self.bit()? << i;
+        }
+        Ok(value)
+    }
+
+    /// 跳到下一个整字节
+    fn align(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+    }
+}
+
+/// 规范 Huffman 码表：每种长度有几个码，按码排好的符号
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0u16; 16];
+        for len in 1..16 {
+            offsets[len] = offsets[len - 1] + counts[len - 1];
+        }
+        let mut symbols = vec![0u16; lengths.len()];
+        for (symbol, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbols[offsets[len as usize] as usize] = symbol as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+        Self { counts, symbols }
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..16 {
+            code |= bits.bit()? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("bad Huffman code".into())
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// 动态码表里码长的码长的顺序
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+fn inflate_zlib(data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() < 2 || data[0] & 0x0F != 8 {
+        return Err("not zlib/deflate data".into());
+    }
+    let mut bits = Bits {
+        data: &data[2..],
+        pos: 0,
+        bit: 0,
+    };
+    let mut out = Vec::new();
+    loop {
+        let last = bits.bit()? == 1;
+        match bits.bits(2)? {
+            0 => {
+                bits.align();
+                let rest = &bits.data[bits.pos..];
+                if rest.len() < 4 {
+                    return Err("stored block too short".into());
+                }
+                let len = u16::from_le_bytes([rest[0], rest[1]]) as usize;
+                let block = rest.get(4..4 + len).ok_or("stored block too short")?;
+                out.extend_from_slice(block);
+                bits.pos += 4 + len;
+            }
+            1 => {
+                let mut lengths = [0u8; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+                let literals = Huffman::new(&lengths);
+                let distances = Huffman::new(&[5; 30]);
+                inflate_block(&mut bits, &mut out, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_tables(&mut bits)?;
+                inflate_block(&mut bits, &mut out, &literals, &distances)?;
+            }
+            _ => return Err("bad deflate block type".into()),
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+fn dynamic_tables(bits: &mut Bits) -> Result<(Huffman, Huffman), String> {
+    let literal_count = bits.bits(5)? as usize + 257;
+    let distance_count = bits.bits(5)? as usize + 1;
+    let code_count = bits.bits(4)? as usize + 4;
+    let mut code_lengths = [0u8; 19];
+    for &index in &CODE_LENGTH_ORDER[..code_count] {
+        code_lengths[index] = bits.bits(3)? as u8;
+    }
+    let codes = Huffman::new(&code_lengths);
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let (value, repeat) = match codes.decode(bits)? {
+            symbol @ 0..=15 => (symbol as u8, 1),
+            16 => (
+                *lengths.last().ok_or("repeat with no previous length")?,
+                3 + bits.bits(2)?,
+            ),
+            17 => (0, 3 + bits.bits(3)?),
+            _ => (0, 11 + bits.bits(7)?),
+        };
+        lengths.extend(std::iter::repeat_n(value, repeat as usize));
+    }
+    if lengths.len() != literal_count + distance_count {
+        return Err("code lengths overflow".into());
+    }
+    let (literal, distance) = lengths.split_at(literal_count);
+    Ok((Huffman::new(literal), Huffman::new(distance)))
+}
+
+fn inflate_block(
+    bits: &mut Bits,
+    out: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<(), String> {
+    loop {
+        let symbol = literals.decode(bits)? as usize;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let index = symbol - 257;
+                if index >= LENGTH_BASE.len() {
+                    return Err("bad length code".into());
+                }
+                let len =
+                    LENGTH_BASE[index] as usize + bits.bits(LENGTH_EXTRA[index] as u32)? as usize;
+                let code = distances.decode(bits)? as usize;
+                if code >= DISTANCE_BASE.len() {
+                    return Err("bad distance code".into());
+                }
+                let distance =
+                    DISTANCE_BASE[code] as usize + bits.bits(DISTANCE_EXTRA[code] as u32)? as usize;
+                if distance > out.len() {
+                    return Err("distance too far back".into());
+                }
+                // 可能和正在写的部分重叠，只能一个字节一个字节拷
+                for _ in 0..len {
+                    out.push(out[out.len() - distance]);
+                }
+            }
+        }
+    }
+}
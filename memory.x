@@ -1,6 +1,7 @@
 MEMORY {
     BOOT2 : ORIGIN = 0x10000000, LENGTH = 0x100
-    FLASH : ORIGIN = 0x10000100, LENGTH = 2048K - 0x100
+    /* 最后一个 4K 扇区留给用户设置，再往前 64K 给数据记录、16K 给图片槽、4K 给脉冲计数(见 src/flash.rs)，不放程序 */
+    FLASH : ORIGIN = 0x10000100, LENGTH = 2048K - 0x100 - 4K - 64K - 16K - 4K
     RAM   : ORIGIN = 0x20000000, LENGTH = 256K
 }
 
@@ -0,0 +1,213 @@
+//! 几路 ADC 轮流采：一次 `poll` 只管一路，不在转换上干等
+//!
+//! `Sampler` 每次读数都是阻塞的(开始转换，等 2µs 转完)，只有两三路的时候无所谓。
+//! 模拟输入多了以后用 `AdcScanner`：给它一张要采的输入表(GP26..GP29 和片内温度传感器)，
+//! 主循环每一圈调一次 `poll`：上一次开始的转换转完了就把结果存起来，再开始下一路，然后立刻返回。
+//! 每一路最新的读数随时可以取，来源多的条形图、曲线直接从这里拿，不用一路一路去读。
+//!
+//! - GPIO 只能是 GP26..GP29(ADC0..ADC3)，别的引脚 `new` 的时候就报错。
+//!   这里只记通道号，引脚本身还要调用方用 `AdcPin::new` 切到模拟输入(关掉数字输入)并且一直拿着
+//! - 温度传感器是 ADC 的 4 号通道，不在 GPIO 上：`new` 的时候打开它，读数用 `temp_centi` 换算成 0.01°C
+//!
+//! 扫描器拿着 `Adc` 的所有权，所以不会有别人在中间改 AINSEL、把结果读错；不用了 `free` 还回来。
+//! 转换出错(CS.ERR)的那一次不存，读数保持上一次的。
+
+use core::fmt;
+
+use heapless::Vec;
+use rp2040_hal::adc::Adc;
+use rp2040_hal::pac;
+
+use crate::sampler::{millivolts_from_raw, temp_centi_from_raw};
+
+/// ADC 一共几个通道(4 个 GPIO + 温度传感器)
+pub const ADC_CHANNELS: usize = 5;
+
+/// 第一个能接 ADC 的 GPIO
+const FIRST_ADC_GPIO: u8 = 26;
+
+/// 温度传感器的通道号
+const TEMP_CHANNEL: u8 = 4;
+
+/// 一路模拟输入
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum AdcInput {
+    /// GPn，只能是 26..=29
+    Gpio(u8),
+    /// 片内温度传感器
+    Temperature,
+}
+
+impl AdcInput {
+    /// ADC 的通道号(AINSEL)，不是 ADC 引脚的 GPIO 返回 None
+    pub fn channel(self) -> Option<u8> {
+        match self {
+            AdcInput::Gpio(pin @ 26..=29) => Some(pin - FIRST_ADC_GPIO),
+            AdcInput::Gpio(_) => None,
+            AdcInput::Temperature => Some(TEMP_CHANNEL),
+        }
+    }
+}
+
+impl fmt::Display for AdcInput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdcInput::Gpio(pin) => write!(f, "GP{}", pin),
+            AdcInput::Temperature => f.write_str("temp"),
+        }
+    }
+}
+
+/// 输入表不对
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum ScanError {
+    /// 表是空的
+    Empty,
+    /// 这个 GPIO 不能接 ADC
+    NotAnAdcPin(u8),
+    /// 同一路写了两次
+    Duplicate(AdcInput),
+    /// 温度传感器已经被别人打开了(比如 `Sampler`)
+    TempSensorTaken,
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScanError::Empty => f.write_str("no adc inputs"),
+            ScanError::NotAnAdcPin(pin) => write!(f, "GP{} is not an adc pin (GP26..GP29)", pin),
+            ScanError::Duplicate(input) => write!(f, "{} listed twice", input),
+            ScanError::TempSensorTaken => f.write_str("temperature sensor already in use"),
+        }
+    }
+}
+
+/// 一次 `poll` 发生了什么
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum ScanPoll {
+    /// 这一路刚转完，值是原始读数(12 位)
+    Sampled(AdcInput, u16),
+    /// 上一次的转换还没完
+    Busy,
+    /// 刚开始第一次转换，还没有结果
+    Started,
+}
+
+/// 轮流采几路 ADC，记着每一路最新的读数
+pub struct AdcScanner {
+    adc: Adc,
+    inputs: Vec<AdcInput, ADC_CHANNELS>,
+    /// 按通道号存的最新原始读数
+    latest: [Option<u16>; ADC_CHANNELS],
+    /// 正在转换的是表里第几路，None 表示还没开始
+    converting: Option<usize>,
+}
+
+impl AdcScanner {
+    /// `inputs` 是采样的顺序。有温度传感器的话在这里打开
+    pub fn new(mut adc: Adc, inputs: &[AdcInput]) -> Result<Self, (Adc, ScanError)> {
+        match Self::check(inputs) {
+            Ok(list) => {
+                if list.contains(&AdcInput::Temperature) {
+                    // 打开以后一直开着，扫描器自己按通道号读，不用 TempSense 这个凭证
+                    if adc.take_temp_sensor().is_none() {
+                        return Err((adc, ScanError::TempSensorTaken));
+                    }
+                }
+                Ok(Self {
+                    adc,
+                    inputs: list,
+                    latest: [None; ADC_CHANNELS],
+                    converting: None,
+                })
+            }
+            Err(err) => Err((adc, err)),
+        }
+    }
+
+    fn check(inputs: &[AdcInput]) -> Result<Vec<AdcInput, ADC_CHANNELS>, ScanError> {
+        if inputs.is_empty() {
+            return Err(ScanError::Empty);
+        }
+        let mut list = Vec::new();
+        for &input in inputs {
+            if let (AdcInput::Gpio(pin), None) = (input, input.channel()) {
+                return Err(ScanError::NotAnAdcPin(pin));
+            }
+            if list.contains(&input) {
+                return Err(ScanError::Duplicate(input));
+            }
+            // 通道号不重复的话最多正好 ADC_CHANNELS 个，放得下
+            let _ = list.push(input);
+        }
+        Ok(list)
+    }
+
+    /// 要采的输入，按采样顺序
+    pub fn inputs(&self) -> &[AdcInput] {
+        &self.inputs
+    }
+
+    /// 往前走一步，不会等：上一路转完了就存下来，再开始下一路
+    pub fn poll(&mut self) -> ScanPoll {
+        // 安全性：Adc 在我们手里，别人碰不到 ADC 的寄存器；只读 CS/RESULT、写 AINSEL/START_ONCE
+        let regs = unsafe { &*pac::ADC::ptr() };
+        let cs = regs.cs().read();
+        let result = match self.converting {
+            Some(_) if cs.ready().bit_is_clear() => return ScanPoll::Busy,
+            Some(index) => {
+                let input = self.inputs[index];
+                let raw = regs.result().read().result().bits();
+                if cs.err().bit_is_clear() {
+                    if let Some(channel) = input.channel() {
+                        self.latest[channel as usize] = Some(raw);
+                    }
+                }
+                self.converting = Some((index + 1) % self.inputs.len());
+                ScanPoll::Sampled(input, raw)
+            }
+            None => {
+                self.converting = Some(0);
+                ScanPoll::Started
+            }
+        };
+
+        if let Some(channel) = self
+            .converting
+            .and_then(|index| self.inputs[index].channel())
+        {
+            regs.cs()
+                .modify(|_, w| unsafe { w.ainsel().bits(channel).start_once().set_bit() });
+        }
+        result
+    }
+
+    /// 最新的原始读数(12 位)，不在表里或者还没采到返回 None
+    pub fn raw(&self, input: AdcInput) -> Option<u16> {
+        if !self.inputs.contains(&input) {
+            return None;
+        }
+        self.latest[input.channel()? as usize]
+    }
+
+    /// 最新读数换算成引脚上的电压(mV)
+    pub fn millivolts(&self, input: AdcInput) -> Option<u16> {
+        self.raw(input).map(millivolts_from_raw)
+    }
+
+    /// 片内温度，单位 0.01°C，表里没有温度传感器返回 None
+    pub fn temp_centi(&self) -> Option<i16> {
+        self.raw(AdcInput::Temperature).map(temp_centi_from_raw)
+    }
+
+    /// 每一路和它最新的读数，按采样顺序
+    pub fn readings(&self) -> impl Iterator<Item = (AdcInput, Option<u16>)> + '_ {
+        self.inputs.iter().map(|&input| (input, self.raw(input)))
+    }
+
+    /// 等正在进行的转换结束，把 ADC 还回去(温度传感器还开着)
+    pub fn free(self) -> Adc {
+        self.adc.wait_ready();
+        self.adc
+    }
+}
@@ -0,0 +1,94 @@
+//! AHT20 温湿度传感器(奥松)
+//!
+//! 上电之后读一次状态字节，校准位(bit3)没置上的话要先发初始化命令 0xBE 0x08 0x00 等它校准好，
+//! 这一步 `probe` 里做(会阻塞几十毫秒，只在开机的时候)。之后每次测量发 0xAC 0x33 0x00，
+//! 等 80ms 读 7 字节：状态、20 位湿度、20 位温度、CRC-8。状态里的忙位(bit7)还置着说明没测完，下次再读。
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+
+use crate::crc::crc8;
+use crate::humidity::{HumidityError, HumidityReading, HumiditySensor};
+
+/// AHT20 的地址是固定的
+pub const ADDRESS: u8 = 0x38;
+
+/// 上电到能通信至少要 40ms
+const POWER_ON_MS: u32 = 40;
+
+/// 初始化(校准)命令和之后要等的时间
+const CMD_INIT: [u8; 3] = [0xBE, 0x08, 0x00];
+const INIT_MS: u32 = 10;
+
+/// 触发测量
+const CMD_MEASURE: [u8; 3] = [0xAC, 0x33, 0x00];
+
+/// 手册说等 80ms
+const MEASUREMENT_MS: u32 = 80;
+
+/// 状态字节：忙、已校准
+const STATUS_BUSY: u8 = 1 << 7;
+const STATUS_CALIBRATED: u8 = 1 << 3;
+
+/// 一个 AHT20
+#[derive(Debug, Clone, Copy)]
+pub struct Aht20 {
+    address: u8,
+}
+
+impl Aht20 {
+    /// 读状态字节看看在不在，没校准就初始化一次。初始化了还没校准好返回 None
+    pub fn probe<I: I2c, T: DelayNs>(i2c: &mut I, timer: &mut T) -> Option<Self> {
+        timer.delay_ms(POWER_ON_MS);
+        let status = read_status(i2c)?;
+        if status & STATUS_CALIBRATED == 0 {
+            i2c.write(ADDRESS, &CMD_INIT).ok()?;
+            timer.delay_ms(INIT_MS);
+            if read_status(i2c)? & STATUS_CALIBRATED == 0 {
+                defmt::warn!("AHT20 did not calibrate");
+                return None;
+            }
+        }
+        Some(Self { address: ADDRESS })
+    }
+}
+
+fn read_status<I: I2c>(i2c: &mut I) -> Option<u8> {
+    let mut status = [0u8];
+    i2c.read(ADDRESS, &mut status).ok()?;
+    Some(status[0])
+}
+
+impl HumiditySensor for Aht20 {
+    fn name(&self) -> &'static str {
+        "AHT20"
+    }
+
+    fn measurement_ms(&self) -> u32 {
+        MEASUREMENT_MS
+    }
+
+    fn start<I: I2c>(&mut self, i2c: &mut I) -> Result<(), HumidityError> {
+        i2c.write(self.address, &CMD_MEASURE)
+            .map_err(|_| HumidityError::Bus)
+    }
+
+    fn read<I: I2c>(&mut self, i2c: &mut I) -> Result<HumidityReading, HumidityError> {
+        let mut data = [0u8; 7];
+        i2c.read(self.address, &mut data)
+            .map_err(|_| HumidityError::Bus)?;
+        if data[0] & STATUS_BUSY != 0 {
+            return Err(HumidityError::Busy);
+        }
+        if crc8(&data[..6]) != data[6] {
+            return Err(HumidityError::Crc);
+        }
+        let raw_rh = ((data[1] as i64) << 12) | ((data[2] as i64) << 4) | (data[3] as i64 >> 4);
+        let raw_t = (((data[3] & 0x0F) as i64) << 16) | ((data[4] as i64) << 8) | data[5] as i64;
+        // 手册 6.1：RH = raw / 2^20 * 100%，T = raw / 2^20 * 200 - 50
+        Ok(HumidityReading {
+            temp_centi: ((raw_t * 20000) >> 20) as i32 - 5000,
+            humidity_centi: ((raw_rh * 10000) >> 20) as i32,
+        })
+    }
+}
@@ -0,0 +1,318 @@
+//! 告警页面：列出所有告警规则和哪些正在触发，也在这里编辑规则
+//!
+//! 从诊断页面按 Up 进来，设置菜单里也有。列表每一行是一条规则的条件，右边 "FIRE" 表示正在触发；最后一行是 "add"(满了就没有)：
+//!
+//! - Up/Down 选行，Select 开始编辑：依次改 通道 -> 方向 -> 阈值 -> 回差 -> 动作，Up/Down 调数值，
+//!   Select 进下一项，改完动作就保存；编辑的时候按 Back 放弃这次修改
+//! - 编辑通道的时候长按 Select 删掉这条规则
+//! - Back 回去
+//!
+//! 保存是交给主循环做的(`Action::SaveAlarmRules`)。哪些规则在触发由主循环通过 `Event::Alarms` 广播过来。
+
+use core::convert::Infallible;
+use core::fmt::Write;
+
+use embedded_graphics::geometry::Point;
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::text::Text;
+use embedded_graphics::Drawable;
+use heapless::{String, Vec};
+
+use crate::alarms::{Actions, AlarmRules, Comparison, Rule, MAX_RULES};
+use crate::app::{Action, Canvas, Event, Page, Transition};
+use crate::input::{Button, ButtonEvent};
+use crate::sensors::{ChannelId, ChannelInfo, MAX_CHANNELS, UNKNOWN_CHANNEL};
+use crate::widgets::draw_menu;
+
+/// 标题行基线
+const TITLE_Y: i32 = 8;
+
+/// 菜单从哪一行开始
+const MENU_TOP: i32 = 12;
+
+/// 阈值每次调多少(1°C 或者 0.1V)
+const THRESHOLD_STEP: i16 = 100;
+
+/// 回差每次调多少(0.5°C 或者 0.05V)
+const HYSTERESIS_STEP: u16 = 50;
+
+/// 回差最大多少
+const HYSTERESIS_MAX: u16 = 1000;
+
+/// 正在改哪一项，顺序就是编辑的顺序
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Source,
+    Comparison,
+    Threshold,
+    Hysteresis,
+    Actions,
+}
+
+impl Field {
+    const ALL: [Field; 5] = [
+        Field::Source,
+        Field::Comparison,
+        Field::Threshold,
+        Field::Hysteresis,
+        Field::Actions,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Field::Source => "source",
+            Field::Comparison => "when",
+            Field::Threshold => "limit",
+            Field::Hysteresis => "hyst",
+            Field::Actions => "do",
+        }
+    }
+
+    fn next(self) -> Option<Field> {
+        Self::ALL
+            .iter()
+            .position(|&field| field == self)
+            .and_then(|i| Self::ALL.get(i + 1))
+            .copied()
+    }
+}
+
+/// 告警页面
+#[derive(Debug)]
+pub struct AlarmPage {
+    rules: AlarmRules,
+    /// 注册表里的通道，规则的通道在这里面选
+    channels: Vec<ChannelInfo, MAX_CHANNELS>,
+    /// 正在触发的规则，第 i 位是第 i 条
+    firing: u8,
+    selected: usize,
+    /// 正在编辑的规则和正在改的字段
+    editing: Option<(Rule, Field)>,
+    pending: Option<Action>,
+}
+
+impl AlarmPage {
+    /// `rules` 是设置里存的规则表，`channels` 是传感器注册表里的通道(`SensorRegistry::infos`)
+    pub fn new(rules: AlarmRules, channels: Vec<ChannelInfo, MAX_CHANNELS>) -> Self {
+        Self {
+            rules,
+            channels,
+            firing: 0,
+            selected: 0,
+            editing: None,
+            pending: None,
+        }
+    }
+
+    fn channel(&self, id: ChannelId) -> &ChannelInfo {
+        self.channels.get(id.0 as usize).unwrap_or(&UNKNOWN_CHANNEL)
+    }
+
+    /// 一共几行(最后可能有一行 "add")
+    fn rows(&self) -> usize {
+        let len = self.rules.rules().len();
+        if len < MAX_RULES {
+            len + 1
+        } else {
+            len
+        }
+    }
+
+    fn save(&mut self) {
+        self.selected = self.selected.min(self.rows() - 1);
+        self.pending = Some(Action::SaveAlarmRules(self.rules));
+    }
+
+    fn on_edit(&mut self, button: Button, mut rule: Rule, field: Field) {
+        let up = match button {
+            Button::Up => true,
+            Button::Down => false,
+            Button::Back => {
+                self.editing = None;
+                return;
+            }
+            Button::Select => {
+                self.editing = field.next().map(|next| (rule, next));
+                if self.editing.is_none() {
+                    self.rules.set(self.selected, rule);
+                    self.save();
+                }
+                return;
+            }
+        };
+        match field {
+            Field::Source => {
+                let count = self.channels.len().max(1);
+                let index = (rule.source.0 as usize).min(count - 1);
+                let index = if up {
+                    (index + 1) % count
+                } else {
+                    (index + count - 1) % count
+                };
+                rule.source = ChannelId(index as u8);
+            }
+            Field::Comparison => {
+                rule.comparison = match rule.comparison {
+                    Comparison::Above => Comparison::Below,
+                    Comparison::Below => Comparison::Above,
+                };
+            }
+            Field::Threshold => {
+                rule.threshold = if up {
+                    rule.threshold.saturating_add(THRESHOLD_STEP)
+                } else {
+                    rule.threshold.saturating_sub(THRESHOLD_STEP)
+                };
+            }
+            Field::Hysteresis => {
+                rule.hysteresis = if up {
+                    (rule.hysteresis + HYSTERESIS_STEP).min(HYSTERESIS_MAX)
+                } else {
+                    rule.hysteresis.saturating_sub(HYSTERESIS_STEP)
+                };
+            }
+            Field::Actions => {
+                // 四个动作的 16 种组合轮着选
+                let bits = rule.actions.bits();
+                let count = Actions::ALL.bits() + 1;
+                let bits = if up {
+                    (bits + 1) % count
+                } else {
+                    (bits + count - 1) % count
+                };
+                rule.actions = Actions::from_bits(bits).unwrap_or(Actions::NONE);
+            }
+        }
+        self.editing = Some((rule, field));
+    }
+
+    /// 编辑界面：每个字段一行，正在改的那一行反色
+    fn render_editor(
+        &self,
+        canvas: &mut Canvas,
+        rule: &Rule,
+        field: Field,
+    ) -> Result<(), Infallible> {
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        let mut title: String<8> = String::new();
+        let _ = write!(title, "rule {}", self.selected + 1);
+        Text::new(&title, Point::new(0, TITLE_Y), style).draw(canvas)?;
+
+        let channel = self.channel(rule.source);
+        let mut values: Vec<String<20>, 5> = Vec::new();
+        for item in Field::ALL {
+            let mut value = String::new();
+            let _ = match item {
+                Field::Source => value.write_str(channel.name),
+                Field::Comparison => value.write_str(match rule.comparison {
+                    Comparison::Above => "above",
+                    Comparison::Below => "below",
+                }),
+                Field::Threshold => channel.write_value(Some(rule.threshold as i32), &mut value),
+                Field::Hysteresis => channel.write_value(Some(rule.hysteresis as i32), &mut value),
+                Field::Actions => rule.actions.write_names(&mut value),
+            };
+            let _ = values.push(value);
+        }
+        let selected = Field::ALL
+            .iter()
+            .position(|&item| item == field)
+            .unwrap_or(0);
+        draw_menu(
+            canvas,
+            MENU_TOP,
+            Field::ALL
+                .iter()
+                .zip(values.iter())
+                .map(|(item, value)| (item.label(), value.as_str())),
+            selected,
+        )
+    }
+}
+
+impl Page for AlarmPage {
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        let button = match event {
+            Event::Alarms(firing) => {
+                self.firing = *firing;
+                return Transition::None;
+            }
+            Event::Button(ButtonEvent::Pressed(button)) => *button,
+            // 按下的那一下已经开始编辑通道了，长按就是放弃编辑、删掉这一条
+            Event::Button(ButtonEvent::LongPress(Button::Select)) => {
+                if !matches!(self.editing, Some((_, Field::Source))) {
+                    return Transition::None;
+                }
+                self.editing = None;
+                if self.selected < self.rules.rules().len() {
+                    self.rules.remove(self.selected);
+                    self.save();
+                }
+                return Transition::None;
+            }
+            _ => return Transition::None,
+        };
+        if let Some((rule, field)) = self.editing {
+            self.on_edit(button, rule, field);
+            return Transition::None;
+        }
+        match button {
+            Button::Back => return Transition::Pop,
+            Button::Up => self.selected = (self.selected + self.rows() - 1) % self.rows(),
+            Button::Down => self.selected = (self.selected + 1) % self.rows(),
+            Button::Select => {
+                let rule = self
+                    .rules
+                    .rules()
+                    .get(self.selected)
+                    .copied()
+                    .unwrap_or(Rule::DEFAULT);
+                self.editing = Some((rule, Field::Source));
+            }
+        }
+        Transition::None
+    }
+
+    fn take_action(&mut self) -> Option<Action> {
+        self.pending.take()
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<(), Infallible> {
+        if let Some((rule, field)) = self.editing {
+            return self.render_editor(canvas, &rule, field);
+        }
+
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        Text::new("alarms", Point::new(0, TITLE_Y), style).draw(canvas)?;
+
+        let mut labels: Vec<String<16>, { MAX_RULES + 1 }> = Vec::new();
+        for rule in self.rules.rules() {
+            let mut label = String::new();
+            let _ = rule.write_condition(self.channel(rule.source), &mut label);
+            let _ = labels.push(label);
+        }
+        if labels.len() < self.rows() {
+            let mut add = String::new();
+            let _ = add.push_str("add");
+            let _ = labels.push(add);
+        }
+        let firing = self.firing;
+        draw_menu(
+            canvas,
+            MENU_TOP,
+            labels.iter().enumerate().map(|(i, label)| {
+                let state = if i >= self.rules.rules().len() {
+                    ""
+                } else if firing & (1 << i) != 0 {
+                    "FIRE"
+                } else {
+                    "ok"
+                };
+                (label.as_str(), state)
+            }),
+            self.selected,
+        )
+    }
+}
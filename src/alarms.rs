@@ -0,0 +1,621 @@
+//! 阈值告警：一张规则表，每条规则盯着一个传感器通道(`sensors` 注册表里的)，超过(或者低于)阈值就触发设好的动作
+//!
+//! 以前"温度高了闪屏"这种逻辑是写死在各个页面里的，现在统一放在这里：
+//!
+//! - 规则 = 通道 + 比较方向 + 阈值 + 回差 + 动作，最多 `MAX_RULES` 条，存在设置里(见 `settings`)
+//! - 主循环每秒拿注册表里的最新读数调 `AlarmEngine::evaluate`，返回这一次新触发、新解除的规则。
+//!   通道不可用的时候规则保持原来的状态
+//! - 回差：高于阈值触发的规则，要降到 `阈值 - 回差` 以下才解除(低于阈值的反过来)，
+//!   读数在阈值附近抖的时候不会一直触发、解除、触发
+//!
+//! 判断本身是 `ThresholdAlarm`：上限、下限各一个(可以只设一个)，共用一个回差，`update` 喂读数返回现在在哪个区间，
+//! 一条规则就是只设了一边的 `ThresholdAlarm`。
+//!
+//! 动作用的都是现成的告警输出：`FLASH` 报一个 `Warning` 级别的告警(屏幕反色闪烁，有灯带的话灯带也亮，
+//! 告警升级的时候本来就会响一下)；`BEEP` 触发的时候响一下；`SERIAL` 触发和解除的时候往串口打一行；
+//! `LED` 触发期间板载 LED 按 `blink_code::ALARM` 闪，解除了才停。
+//!
+//! 规则在告警页面里编辑，见 `alarm_page`。
+
+use core::fmt::{self, Write};
+
+use crate::sensors::{ChannelId, ChannelInfo, MAX_CHANNELS};
+
+/// 规则表最多几条
+pub const MAX_RULES: usize = 4;
+
+/// 一条规则编码之后多少字节：通道、方向、阈值(2 字节)、回差(2 字节)、动作
+const RULE_LEN: usize = 7;
+
+/// 编码之后多少字节：条数 + 每条规则
+pub const ENCODED_LEN: usize = 1 + MAX_RULES * RULE_LEN;
+
+/// 比较方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Comparison {
+    /// 高于阈值触发
+    Above,
+    /// 低于阈值触发
+    Below,
+}
+
+impl Comparison {
+    pub fn symbol(self) -> char {
+        match self {
+            Comparison::Above => '>',
+            Comparison::Below => '<',
+        }
+    }
+}
+
+/// 触发之后做什么，几个动作可以同时选
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, defmt::Format)]
+pub struct Actions(u8);
+
+impl Actions {
+    pub const NONE: Actions = Actions(0);
+    /// 报 `Warning` 告警：屏幕反色闪烁、灯带
+    pub const FLASH: Actions = Actions(1 << 0);
+    /// 蜂鸣器响一下
+    pub const BEEP: Actions = Actions(1 << 1);
+    /// 串口打一行
+    pub const SERIAL: Actions = Actions(1 << 2);
+    /// 板载 LED 一直闪到解除
+    pub const LED: Actions = Actions(1 << 3);
+    pub const ALL: Actions = Actions(0b1111);
+
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    pub fn from_bits(bits: u8) -> Option<Self> {
+        (bits & !Self::ALL.0 == 0).then_some(Self(bits))
+    }
+
+    pub fn contains(self, other: Actions) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn union(self, other: Actions) -> Actions {
+        Actions(self.0 | other.0)
+    }
+
+    /// 写成 "flash beep tx led" 这样，一个都没有就是 "none"
+    pub fn write_names<W: Write>(self, out: &mut W) -> fmt::Result {
+        if self.0 == 0 {
+            return out.write_str("none");
+        }
+        let names = [
+            (Actions::FLASH, "flash"),
+            (Actions::BEEP, "beep"),
+            (Actions::SERIAL, "tx"),
+            (Actions::LED, "led"),
+        ];
+        let mut first = true;
+        for (action, name) in names {
+            if self.contains(action) {
+                if !first {
+                    out.write_char(' ')?;
+                }
+                out.write_str(name)?;
+                first = false;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 读数在哪个区间
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum AlarmState {
+    Normal,
+    /// 超过了上限
+    High,
+    /// 低于下限
+    Low,
+}
+
+/// 带回差的上下限告警
+///
+/// 超过 `high` 进 `High`，要降到 `high - hysteresis` 以下才出来；`Low` 反过来。
+/// 正好等于上限(下限)不算超，出来以后要重新超过上限(下限)才会再进去
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct ThresholdAlarm {
+    /// 上限，None 是不管高
+    pub high: Option<i32>,
+    /// 下限，None 是不管低
+    pub low: Option<i32>,
+    pub hysteresis: i32,
+    state: AlarmState,
+}
+
+impl ThresholdAlarm {
+    pub const fn new(high: Option<i32>, low: Option<i32>, hysteresis: i32) -> Self {
+        Self {
+            high,
+            low,
+            hysteresis,
+            state: AlarmState::Normal,
+        }
+    }
+
+    pub fn state(&self) -> AlarmState {
+        self.state
+    }
+
+    /// 喂一个新读数，返回更新以后的状态
+    pub fn update(&mut self, value: i32) -> AlarmState {
+        let above = |margin: i32| self.high.is_some_and(|high| value > high - margin);
+        let below = |margin: i32| self.low.is_some_and(|low| value < low + margin);
+        // 还在回差带里就保持，出来了再按没触发的时候那样判断(从高直接掉到低于下限也行)
+        self.state = match self.state {
+            AlarmState::High if above(self.hysteresis + 1) => AlarmState::High,
+            AlarmState::Low if below(self.hysteresis + 1) => AlarmState::Low,
+            _ if above(0) => AlarmState::High,
+            _ if below(0) => AlarmState::Low,
+            _ => AlarmState::Normal,
+        };
+        self.state
+    }
+}
+
+/// 一条告警规则
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Rule {
+    /// 盯着哪个通道
+    pub source: ChannelId,
+    pub comparison: Comparison,
+    /// 阈值，和通道的读数一样是整数，比例跟着通道走(见 `ChannelInfo::scale`)
+    pub threshold: i16,
+    /// 回差，单位同上
+    pub hysteresis: u16,
+    pub actions: Actions,
+}
+
+impl Rule {
+    /// 新加规则时的默认值：0 号通道(片内温度)高于 50°C 闪屏
+    pub const DEFAULT: Rule = Rule {
+        source: ChannelId(0),
+        comparison: Comparison::Above,
+        threshold: 5000,
+        hysteresis: 100,
+        actions: Actions::FLASH,
+    };
+
+    /// 读数 `value` 下这条规则是不是触发状态，`firing` 是上一次的状态
+    pub fn check(&self, value: i32, firing: bool) -> bool {
+        let threshold = Some(self.threshold as i32);
+        let (high, low, state) = match self.comparison {
+            Comparison::Above => (threshold, None, AlarmState::High),
+            Comparison::Below => (None, threshold, AlarmState::Low),
+        };
+        let mut alarm = ThresholdAlarm::new(high, low, self.hysteresis as i32);
+        if firing {
+            alarm.state = state;
+        }
+        alarm.update(value) != AlarmState::Normal
+    }
+
+    /// 写成 "temp>50.00C" 这样，`channel` 是 `source` 这个通道的描述
+    pub fn write_condition<W: Write>(&self, channel: &ChannelInfo, out: &mut W) -> fmt::Result {
+        write!(out, "{}{}", channel.name, self.comparison.symbol())?;
+        channel.write_value(Some(self.threshold as i32), out)
+    }
+
+    fn encode(&self, out: &mut [u8]) {
+        out[0] = self.source.0;
+        out[1] = match self.comparison {
+            Comparison::Above => 0,
+            Comparison::Below => 1,
+        };
+        out[2..4].copy_from_slice(&self.threshold.to_le_bytes());
+        out[4..6].copy_from_slice(&self.hysteresis.to_le_bytes());
+        out[6] = self.actions.bits();
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        if bytes[0] as usize >= MAX_CHANNELS {
+            return None;
+        }
+        Some(Self {
+            source: ChannelId(bytes[0]),
+            comparison: match bytes[1] {
+                0 => Comparison::Above,
+                1 => Comparison::Below,
+                _ => return None,
+            },
+            threshold: i16::from_le_bytes([bytes[2], bytes[3]]),
+            hysteresis: u16::from_le_bytes([bytes[4], bytes[5]]),
+            actions: Actions::from_bits(bytes[6])?,
+        })
+    }
+}
+
+/// 规则表
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct AlarmRules {
+    rules: [Rule; MAX_RULES],
+    len: u8,
+}
+
+impl Default for AlarmRules {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AlarmRules {
+    /// 空表，什么都不盯
+    pub const fn new() -> Self {
+        Self {
+            rules: [Rule::DEFAULT; MAX_RULES],
+            len: 0,
+        }
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules[..self.len as usize]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// 加一条，满了返回 false
+    pub fn push(&mut self, rule: Rule) -> bool {
+        if self.len as usize >= MAX_RULES {
+            return false;
+        }
+        self.rules[self.len as usize] = rule;
+        self.len += 1;
+        true
+    }
+
+    /// 替换第 `index` 条，超出范围的就加在后面
+    pub fn set(&mut self, index: usize, rule: Rule) -> bool {
+        match self.rules[..self.len as usize].get_mut(index) {
+            Some(slot) => {
+                *slot = rule;
+                true
+            }
+            None => self.push(rule),
+        }
+    }
+
+    /// 删掉第 `index` 条
+    pub fn remove(&mut self, index: usize) {
+        if index >= self.len as usize {
+            return;
+        }
+        self.rules.copy_within(index + 1..self.len as usize, index);
+        self.len -= 1;
+    }
+
+    /// 这些规则里正在触发的那几条的动作合起来
+    pub fn active_actions(&self, firing: u8) -> Actions {
+        self.rules()
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| firing & (1 << i) != 0)
+            .fold(Actions::NONE, |all, (_, rule)| all.union(rule.actions))
+    }
+
+    pub fn encode(&self) -> [u8; ENCODED_LEN] {
+        let mut out = [0u8; ENCODED_LEN];
+        out[0] = self.len;
+        for (i, rule) in self.rules().iter().enumerate() {
+            rule.encode(&mut out[1 + i * RULE_LEN..1 + (i + 1) * RULE_LEN]);
+        }
+        out
+    }
+
+    /// 解码。条数或者字段超出范围返回 None
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != ENCODED_LEN || bytes[0] as usize > MAX_RULES {
+            return None;
+        }
+        let mut rules = Self::new();
+        for i in 0..bytes[0] as usize {
+            rules.push(Rule::decode(
+                &bytes[1 + i * RULE_LEN..1 + (i + 1) * RULE_LEN],
+            )?);
+        }
+        Some(rules)
+    }
+}
+
+/// 一次评估的结果，每一位对应一条规则
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, defmt::Format)]
+pub struct AlarmChanges {
+    /// 这一次新触发的
+    pub raised: u8,
+    /// 这一次解除的
+    pub cleared: u8,
+}
+
+impl AlarmChanges {
+    pub fn is_empty(&self) -> bool {
+        self.raised == 0 && self.cleared == 0
+    }
+}
+
+/// 告警引擎：记着哪些规则正在触发
+#[derive(Debug, Default)]
+pub struct AlarmEngine {
+    firing: u8,
+}
+
+impl AlarmEngine {
+    pub const fn new() -> Self {
+        Self { firing: 0 }
+    }
+
+    /// 正在触发的规则，第 i 位是第 i 条
+    pub fn firing(&self) -> u8 {
+        self.firing
+    }
+
+    pub fn is_firing(&self, index: usize) -> bool {
+        self.firing & (1 << index) != 0
+    }
+
+    /// 规则表改过了，全部当成没触发重新来。返回之前在触发的那些，调用方要把它们的动作收掉
+    pub fn reset(&mut self) -> u8 {
+        core::mem::take(&mut self.firing)
+    }
+
+    /// 用最新读数评估所有规则。`read` 返回某个通道现在的读数，读不到的返回 None，
+    /// 这条规则保持原来的状态
+    pub fn evaluate(
+        &mut self,
+        rules: &AlarmRules,
+        read: impl Fn(ChannelId) -> Option<i32>,
+    ) -> AlarmChanges {
+        let mut firing = 0u8;
+        for (i, rule) in rules.rules().iter().enumerate() {
+            let was = self.is_firing(i);
+            let now = read(rule.source).map_or(was, |value| rule.check(value, was));
+            if now {
+                firing |= 1 << i;
+            }
+        }
+        let changes = AlarmChanges {
+            raised: firing & !self.firing,
+            cleared: self.firing & !firing,
+        };
+        self.firing = firing;
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 按顺序喂读数，返回每一步的状态
+    fn run(alarm: &mut ThresholdAlarm, values: &[i32]) -> std::vec::Vec<AlarmState> {
+        values.iter().map(|&value| alarm.update(value)).collect()
+    }
+
+    #[test]
+    fn high_edges() {
+        use AlarmState::*;
+        let mut alarm = ThresholdAlarm::new(Some(100), None, 10);
+        // 等于上限不算超；进去以后等于 上限 - 回差 还在里面，再低一点才出来
+        assert_eq!(
+            run(&mut alarm, &[100, 101, 95, 90, 89, 100, 101]),
+            [Normal, High, High, High, Normal, Normal, High]
+        );
+    }
+
+    #[test]
+    fn low_edges() {
+        use AlarmState::*;
+        let mut alarm = ThresholdAlarm::new(None, Some(-20), 5);
+        assert_eq!(
+            run(&mut alarm, &[-20, -21, -16, -15, -14, -20, -21]),
+            [Normal, Low, Low, Low, Normal, Normal, Low]
+        );
+    }
+
+    #[test]
+    fn zero_hysteresis() {
+        use AlarmState::*;
+        let mut alarm = ThresholdAlarm::new(Some(0), None, 0);
+        assert_eq!(run(&mut alarm, &[1, 0, 1, -1]), [High, High, High, Normal]);
+    }
+
+    #[test]
+    fn jumps_between_limits() {
+        use AlarmState::*;
+        let mut alarm = ThresholdAlarm::new(Some(100), Some(0), 10);
+        // 从高直接掉到低于下限，不经过 Normal
+        assert_eq!(
+            run(&mut alarm, &[150, -5, 8, 10, 11, 200]),
+            [High, Low, Low, Low, Normal, High]
+        );
+        assert_eq!(alarm.state(), High);
+    }
+
+    #[test]
+    fn no_limits_never_fire() {
+        let mut alarm = ThresholdAlarm::new(None, None, 10);
+        for value in [i32::MIN, -1, 0, 1, i32::MAX] {
+            assert_eq!(alarm.update(value), AlarmState::Normal);
+        }
+    }
+
+    #[test]
+    fn rule_check_matches_alarm() {
+        let above = Rule {
+            threshold: 5000,
+            hysteresis: 100,
+            ..Rule::DEFAULT
+        };
+        assert!(!above.check(5000, false));
+        assert!(above.check(5001, false));
+        assert!(above.check(4900, true));
+        assert!(!above.check(4899, true));
+        // 没在触发的时候回差不算
+        assert!(!above.check(4950, false));
+
+        let below = Rule {
+            comparison: Comparison::Below,
+            threshold: 1000,
+            hysteresis: 50,
+            ..Rule::DEFAULT
+        };
+        assert!(!below.check(1000, false));
+        assert!(below.check(999, false));
+        assert!(below.check(1050, true));
+        assert!(!below.check(1051, true));
+    }
+
+    fn rules(list: &[Rule]) -> AlarmRules {
+        let mut rules = AlarmRules::new();
+        for &rule in list {
+            assert!(rules.push(rule));
+        }
+        rules
+    }
+
+    #[test]
+    fn engine_reports_edges_only() {
+        let rules = rules(&[
+            Rule::DEFAULT,
+            Rule {
+                source: ChannelId(1),
+                comparison: Comparison::Below,
+                threshold: 10,
+                hysteresis: 0,
+                actions: Actions::BEEP,
+            },
+        ]);
+        let mut engine = AlarmEngine::new();
+        let read = |temp: i32, other: Option<i32>| {
+            move |id: ChannelId| if id.0 == 0 { Some(temp) } else { other }
+        };
+        let changes = engine.evaluate(&rules, read(6000, Some(20)));
+        assert_eq!(
+            changes,
+            AlarmChanges {
+                raised: 0b01,
+                cleared: 0
+            }
+        );
+        assert!(engine.evaluate(&rules, read(6000, Some(20))).is_empty());
+        let changes = engine.evaluate(&rules, read(4950, Some(5)));
+        assert_eq!(
+            changes,
+            AlarmChanges {
+                raised: 0b10,
+                cleared: 0
+            }
+        );
+        assert_eq!(
+            rules.active_actions(engine.firing()),
+            Actions::FLASH.union(Actions::BEEP)
+        );
+        // 读不到的通道保持原来的状态
+        let changes = engine.evaluate(&rules, read(4000, None));
+        assert_eq!(
+            changes,
+            AlarmChanges {
+                raised: 0,
+                cleared: 0b01
+            }
+        );
+        assert!(engine.is_firing(1));
+        assert_eq!(engine.reset(), 0b10);
+        assert_eq!(engine.firing(), 0);
+    }
+
+    #[test]
+    fn rules_round_trip() {
+        let table = rules(&[
+            Rule::DEFAULT,
+            Rule {
+                source: ChannelId(3),
+                comparison: Comparison::Below,
+                threshold: -1234,
+                hysteresis: 65535,
+                actions: Actions::ALL,
+            },
+        ]);
+        let bytes = table.encode();
+        assert_eq!(bytes[0], 2);
+        assert_eq!(AlarmRules::decode(&bytes), Some(table));
+        assert_eq!(
+            AlarmRules::decode(&AlarmRules::new().encode()),
+            Some(AlarmRules::new())
+        );
+    }
+
+    #[test]
+    fn decode_rejects_bad_fields() {
+        let good = rules(&[Rule::DEFAULT]).encode();
+        let mut bad = good;
+        bad[0] = MAX_RULES as u8 + 1;
+        assert_eq!(AlarmRules::decode(&bad), None);
+        let mut bad = good;
+        bad[1] = MAX_CHANNELS as u8;
+        assert_eq!(AlarmRules::decode(&bad), None);
+        let mut bad = good;
+        bad[2] = 2;
+        assert_eq!(AlarmRules::decode(&bad), None);
+        let mut bad = good;
+        bad[7] = 1 << 4;
+        assert_eq!(AlarmRules::decode(&bad), None);
+        assert_eq!(AlarmRules::decode(&good[..ENCODED_LEN - 1]), None);
+    }
+
+    #[test]
+    fn table_edits() {
+        let mut table = AlarmRules::new();
+        for i in 0..MAX_RULES {
+            assert!(table.push(Rule {
+                threshold: i as i16,
+                ..Rule::DEFAULT
+            }));
+        }
+        assert!(!table.push(Rule::DEFAULT));
+        table.remove(1);
+        let thresholds: std::vec::Vec<i16> =
+            table.rules().iter().map(|rule| rule.threshold).collect();
+        assert_eq!(thresholds, [0, 2, 3]);
+        assert!(table.set(
+            0,
+            Rule {
+                threshold: 9,
+                ..Rule::DEFAULT
+            }
+        ));
+        assert!(table.set(
+            10,
+            Rule {
+                threshold: 8,
+                ..Rule::DEFAULT
+            }
+        ));
+        let thresholds: std::vec::Vec<i16> =
+            table.rules().iter().map(|rule| rule.threshold).collect();
+        assert_eq!(thresholds, [9, 2, 3, 8]);
+    }
+
+    #[test]
+    fn action_names() {
+        let mut out = std::string::String::new();
+        Actions::NONE.write_names(&mut out).unwrap();
+        assert_eq!(out, "none");
+        out.clear();
+        Actions::ALL.write_names(&mut out).unwrap();
+        assert_eq!(out, "flash beep tx led");
+        out.clear();
+        Actions::BEEP
+            .union(Actions::LED)
+            .write_names(&mut out)
+            .unwrap();
+        assert_eq!(out, "beep led");
+        assert_eq!(Actions::from_bits(0x10), None);
+    }
+}
@@ -0,0 +1,83 @@
+//! 告警级别：界面发现问题时在这里登记，屏幕反色闪烁、灯带之类的提醒都从这里读
+//!
+//! 谁来 `raise` 由各个功能自己决定(比如 main.rs 在 I2C 刷新失败时报 `Critical`)，
+//! 谁来显示也各管各的，互相不用知道对方存在。同一时间只记一个级别，高的盖住低的。
+
+/// 告警级别，从低到高
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, defmt::Format)]
+pub enum AlertLevel {
+    /// 没有告警
+    None,
+    /// 提示，比如倒计时结束，过一会儿自己消失
+    Notice,
+    /// 警告，比如温度超过阈值
+    Warning,
+    /// 严重，比如屏幕的 I2C 通信失败
+    Critical,
+}
+
+/// 提示级别的告警显示多久(毫秒)
+pub const NOTICE_DURATION_MS: u64 = 3000;
+
+/// 屏幕反色闪烁的半周期(毫秒)
+const FLASH_HALF_PERIOD_MS: u64 = 500;
+
+/// 当前的告警状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Alerts {
+    level: AlertLevel,
+    /// 当前级别是什么时候开始的，动画从这个时间算起
+    since_ms: u64,
+}
+
+impl Default for Alerts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Alerts {
+    pub const fn new() -> Self {
+        Self {
+            level: AlertLevel::None,
+            since_ms: 0,
+        }
+    }
+
+    /// 报告一个告警。比当前级别低的会被忽略；同级别重复报告不会重新开始计时(动画不会跳)
+    pub fn raise(&mut self, level: AlertLevel, now_ms: u64) {
+        if level > self.level {
+            self.level = level;
+            self.since_ms = now_ms;
+        } else if level == AlertLevel::Notice && self.level == AlertLevel::Notice {
+            // 提示重复出现就顺延
+            self.since_ms = now_ms;
+        }
+    }
+
+    /// 问题解决了：当前是这个级别就清掉，否则不动(别把更严重的告警顺手清了)
+    pub fn clear(&mut self, level: AlertLevel) {
+        if self.level == level {
+            self.level = AlertLevel::None;
+        }
+    }
+
+    /// 当前级别，到时间的提示会在这里自动清掉
+    pub fn level(&mut self, now_ms: u64) -> AlertLevel {
+        if self.level == AlertLevel::Notice && self.elapsed_ms(now_ms) >= NOTICE_DURATION_MS {
+            self.level = AlertLevel::None;
+        }
+        self.level
+    }
+
+    /// 当前级别已经持续了多久
+    pub fn elapsed_ms(&self, now_ms: u64) -> u64 {
+        now_ms.saturating_sub(self.since_ms)
+    }
+
+    /// 屏幕此刻要不要反色：有告警的时候按 1Hz 闪烁，第一下先反色
+    pub fn display_inverted(&mut self, now_ms: u64) -> bool {
+        self.level(now_ms) != AlertLevel::None
+            && (self.elapsed_ms(now_ms) / FLASH_HALF_PERIOD_MS).is_multiple_of(2)
+    }
+}
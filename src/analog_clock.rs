@@ -0,0 +1,140 @@
+//! 指针式时钟：圆形表盘、12 个刻度，时针、分针、秒针每秒走一格
+//!
+//! 时间和床头钟一样来自 `WallClock`(串口 `CLOCK SET` 对时以后广播 `Event::ClockSet` 过来)，
+//! 没对过时从开机那一刻的 00:00 开始走。指针的角度按一整圈 65536 算(见 `trig`)：
+//! 秒针 60 秒一圈，分针和时针也带上秒，所以是平滑走的，不会到整分、整点才跳一下。
+//!
+//! 表盘画在屏幕中间，半径取宽高里短的那一边，转了 90/270 度(竖屏)一样是圆的、在中间。
+//! 秒数变了才重画，调度器每次都先清屏，所以每秒整个表盘重画一遍。
+
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::{OriginDimensions, Point};
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::primitives::{Circle, Line, Primitive, PrimitiveStyle};
+use embedded_graphics::Drawable;
+
+use crate::app::Event;
+use crate::screen::back_pops;
+use crate::sleep_clock::WallClock;
+use crate::trig::{cos_q15, mul_q15, sin_q15, FULL_TURN};
+
+/// 表盘离屏幕边留几个像素
+const MARGIN: i32 = 1;
+
+/// 刻度从半径的多少(百分比)画到外圈，3、6、9、12 点的长一点
+const TICK_INNER_PERCENT: i32 = 85;
+const MAJOR_TICK_INNER_PERCENT: i32 = 75;
+
+/// 三根针的长度(半径的百分比)和粗细
+const HOUR_HAND: (i32, u32) = (50, 3);
+const MINUTE_HAND: (i32, u32) = (75, 2);
+const SECOND_HAND: (i32, u32) = (88, 1);
+
+/// 中间的轴
+const HUB_DIAMETER: u32 = 5;
+
+/// 一圈的 `part / whole`，换成 1/65536 圈
+fn turn(part: u32, whole: u32) -> u16 {
+    (part as u64 * FULL_TURN as u64 / whole as u64) as u16
+}
+
+/// (时, 分, 秒) 三根针的角度，0 是 12 点方向，顺时针增加
+pub fn hand_angles(hour: u8, minute: u8, second: u8) -> (u16, u16, u16) {
+    let (hour, minute, second) = (hour as u32 % 12, minute as u32, second as u32);
+    (
+        turn(hour * 3600 + minute * 60 + second, 12 * 3600),
+        turn(minute * 60 + second, 3600),
+        turn(second, 60),
+    )
+}
+
+/// 从 `center` 往 `angle` 方向走 `length` 像素。屏幕的 y 往下，所以 12 点方向是 -y
+fn polar(center: Point, angle: u16, length: i32) -> Point {
+    center
+        + Point::new(
+            mul_q15(length, sin_q15(angle)),
+            -mul_q15(length, cos_q15(angle)),
+        )
+}
+
+/// 表盘的状态：时间和屏幕上画的是第几秒
+#[derive(Debug, Default)]
+pub struct AnalogClock {
+    clock: WallClock,
+    /// None 表示还没画过(或者要重画)
+    shown: Option<(u8, u8, u8)>,
+}
+
+impl AnalogClock {
+    pub const fn new(clock: WallClock) -> Self {
+        Self { clock, shown: None }
+    }
+
+    /// 画整个表盘，指针指着 (时, 分, 秒)
+    pub fn draw<D>(&self, display: &mut D, time: (u8, u8, u8)) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = BinaryColor> + OriginDimensions,
+    {
+        let size = display.size();
+        let center = Point::new(size.width as i32 / 2, size.height as i32 / 2);
+        let radius = size.width.min(size.height) as i32 / 2 - MARGIN;
+        let thin = PrimitiveStyle::with_stroke(BinaryColor::On, 1);
+
+        Circle::with_center(center, (radius * 2 + 1) as u32)
+            .into_styled(thin)
+            .draw(display)?;
+        for hour in 0..12 {
+            let angle = turn(hour, 12);
+            let inner = if hour % 3 == 0 {
+                MAJOR_TICK_INNER_PERCENT
+            } else {
+                TICK_INNER_PERCENT
+            };
+            Line::new(
+                polar(center, angle, radius * inner / 100),
+                polar(center, angle, radius),
+            )
+            .into_styled(thin)
+            .draw(display)?;
+        }
+
+        let (hour, minute, second) = hand_angles(time.0, time.1, time.2);
+        for (angle, (length, width)) in [
+            (hour, HOUR_HAND),
+            (minute, MINUTE_HAND),
+            (second, SECOND_HAND),
+        ] {
+            Line::new(center, polar(center, angle, radius * length / 100))
+                .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, width))
+                .draw(display)?;
+        }
+        Circle::with_center(center, HUB_DIAMETER)
+            .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+            .draw(display)
+    }
+}
+
+crate::screen! {
+    /// 指针式时钟
+    pub screen AnalogClockPage: AnalogClock {
+        name: "analog",
+        fps: 4,
+        event(face, event, _now_ms) {
+            if let Event::ClockSet(clock) = event {
+                face.clock = *clock;
+                face.shown = None;
+            }
+            back_pops(event)
+        }
+        update(face, now_ms) {
+            let time = face.clock.time(now_ms);
+            let changed = face.shown != Some(time);
+            face.shown = Some(time);
+            changed
+        }
+        render(face, canvas, now_ms) {
+            let time = face.shown.unwrap_or_else(|| face.clock.time(now_ms));
+            face.draw(canvas, time)
+        }
+    }
+}
@@ -0,0 +1,876 @@
+//! 页面框架：所有"一整屏"的功能都实现 `Page`，由 `Scheduler` 统一调度
+//!
+//! 以前每加一个页面都要改 main.rs 的主循环(什么时候画、按键给谁、什么时候 flush)，
+//! 现在这些都归 `Scheduler` 管：
+//!
+//! - 页面栈：最上面的页面收按键事件；菜单、弹窗这种临时页面 push 上去，关掉的时候 pop
+//! - 定时：每一帧给栈里的页面调一次 `tick`，任何一个说要重画才会重画。
+//!   帧率由当前显示的页面自己定(`Page::desired_fps`)，静态页面帧率低，不浪费 I2C 带宽
+//! - 逻辑 tick：动画、游戏按固定的节奏走(`Page::step`，每秒 `timestep::TICK_HZ` 次)，画得慢了也不会跟着变慢，
+//!   落后了先补逻辑、这一帧不画(见 `timestep`)
+//! - 重画：一帧最多画一次，画完由调用方 flush 一次。画面分成几个窗口的页面(`Page::redraws_partially`，见 `compositor`)
+//!   单独显示的时候不清屏，只重画变了的窗口；画布在两帧之间被别人动过(换页、提示条消失、别处直接画了显存)就还是整页重画
+//! - 帧同步：SSD1306 不给 vsync，flush 的时候屏幕正扫到一半就会看到上下两半不是同一帧(撕裂)。
+//!   打开以后(`set_frame_pacing`)两次 flush 之间隔整数个屏幕刷新周期(按初始化参数算的，见 `command::REFRESH_PERIOD_US`)，
+//!   撕开的那条线停在同一个地方，不会一帧一帧地往下跑。只是估的：振荡频率每片都不一样，主循环也只有毫秒精度，
+//!   跑久了相位还是会慢慢漂，只能减轻，消不掉
+//! - 提示条(toast)：画在所有页面最上面，到时间自己消失
+//! - 告警图标：有告警规则在触发的时候画在右上角(`set_alarm_indicator`)
+//! - 低电量图标：电压低的时候画在告警图标左边，有设置等着存再多一个小方块(`set_battery_indicator`，见 `low_voltage`)
+//! - 网络图标：Pico W 上配了 Wi-Fi 的话画在低电量图标左边，在连、拿到 IP、有人连着(`set_network_indicator`，见 `net_console`)
+//! - 轮播进度：自动轮播的时候底边画一条细线，表示离换页还有多久(`set_carousel_progress`，见 `carousel`)
+//! - 动作(`Action`)：页面要存 flash 这种自己做不了的事，交给主循环去做。全局按键也是映射成动作交给主循环的(见 `input_map`)
+//! - 耗时统计：给了 `set_perf` 的话每个页面的 `render` 和每一帧都会计时(见 `perf`)
+//! - 换页动画：换了整页(不算小窗口)的时候记下该用哪种动画，主循环 `take_transition` 取走去播(见 `transition`)
+//! - 大字模式：页面改画大字版(`Page::render_large`)，没有的就放大两倍看四分之一(`set_large_text`，见 `large_text`)
+//! - 确认框：页面返回 `Transition::Confirm`，调度器在它上面弹一个 yes/no 的框，选完了用 `Event::Confirmed` 告诉它(见 `confirm`)。
+//!   主循环也能自己弹一个(`ask`)，选 yes 的动作从 `take_action` 交回去
+//! - 主题：进度条、提示条、确认框这些控件是什么样式(`set_theme`，见 `theme`)，换了整屏重画
+//! - 旋转：运行的时候换了方向(串口导入设置)，90/270 度宽高是对调的，页面缓存的布局(切好的行、滚动位置、
+//!   球在哪)都按旧尺寸算的。`rotation_changed` 让栈里栈外的每个页面按新尺寸重算(`Page::on_rotation_changed`)，
+//!   放大的那一块回到左上角，没播的换页动画丢掉(截的是旧方向的画面)，整屏重画
+//!
+//! 打开 `overlay-layer` 的话提示条、告警图标、轮播进度、确认框画在叠加层上(见 `overlay_layer`)，
+//! 它们出现、消失只重画叠加层，页面不用重画。
+//!
+//! 页面画在 `Canvas`(也就是 `FrameBuffer`)上，而不是泛型的 `DrawTarget`：
+//! `DrawTarget` 有泛型方法，做不成 trait object，页面栈里又必须放 `dyn Page`。
+//!
+//! 只有"状态 + 走一步 + 画出来"的简单演示页面可以用 `screen!` 宏定义，不用手写 `Page` 的实现(见 screen.rs)。
+
+use core::cell::RefCell;
+use core::convert::Infallible;
+
+use embedded_graphics::geometry::{Point, Size};
+use heapless::Vec;
+
+use crate::alarms::AlarmRules;
+use crate::calibration::CalibrationInput;
+use crate::carousel::CarouselConfig;
+use crate::confirm::ConfirmDialog;
+use crate::dimming::DimSchedule;
+use crate::ds3231::Time;
+use crate::framebuffer::FrameBuffer;
+use crate::gesture::Gesture;
+use crate::host_status::StatusPacket;
+use crate::i2c_lines::BusReport;
+use crate::input::{Button, ButtonEvent};
+use crate::large_text::{draw_zoomed, QUADRANTS};
+use crate::net_console::NetState;
+use crate::perf::{self, PerfTable};
+use crate::popup::PopupText;
+use crate::reaction::TimerMessage;
+use crate::remap::{InputBindings, Role};
+use crate::remote_page::RemoteText;
+use crate::sleep_clock::WallClock;
+use crate::theme::{self, PatternTarget};
+use crate::timestep::FixedStep;
+use crate::timezone::TimeZone;
+use crate::transition::TransitionEffect;
+use crate::wake_alarm::{Ringing, WakeAlarmConfig};
+use crate::watch_face::WatchFaceConfig;
+use crate::widgets::{
+    draw_alarm_icon, draw_carousel_progress, draw_low_battery_indicator, draw_network_indicator,
+    Toast,
+};
+
+/// 页面画图的目标
+pub type Canvas = FrameBuffer;
+
+/// 页面栈最多几层
+pub const STACK_DEPTH: usize = 8;
+
+/// 页面没说要多少帧率的时候用这个，10 帧每秒对滚动字幕来说足够顺滑了
+pub const DEFAULT_FPS: u16 = 10;
+
+/// 页面编号，就是页面在 `Scheduler::new` 传进去的数组里的下标
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct PageId(pub u8);
+
+/// 发给页面的事件
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// 按键(已经消抖过了)
+    Button(ButtonEvent),
+    /// 按键手势(短按、长按、双击)，和 `Button` 是同一次按键的两种说法，见 gesture.rs
+    Gesture(Gesture),
+    /// 电脑端发来的状态(USB HID)
+    Host(StatusPacket),
+    /// 对过时了，带着对好的墙上时间(广播)
+    ClockSet(WallClock),
+    /// 从 DS3231 读到(或者 `CLOCK SET` 写进去)的 UTC 时间(广播)，见 `timezone`
+    RtcTime(Time),
+    /// 串口命令改了时区或者导入了设置(广播)
+    TimeZone(TimeZone),
+    /// 正在触发的告警规则变了，第 i 位是第 i 条(广播)
+    Alarms(u8),
+    /// 串口命令 `TEXT` 发来的文字(广播)
+    RemoteText(RemoteText),
+    /// 串口命令 `CLEAR`(广播)
+    RemoteClear,
+    /// 串口命令 `IMG SHOW`：第几个图片槽(从 0 数)、左上角放在哪(广播)
+    RemoteImage { slot: usize, top_left: Point },
+    /// 弹一个告警框(广播)
+    Popup(PopupText),
+    /// `Action::ScanBus` 扫完了(广播)
+    BusReport(BusReport),
+    /// 串口命令改了表盘设置(广播)
+    WatchFace(WatchFaceConfig),
+    /// 串口命令开关了大字模式(广播)
+    LargeText(bool),
+    /// 串口命令换了显示方案或者导入了设置(广播)，值是方案编号
+    DisplayProfile(Option<u8>),
+    /// 反应游戏计时核(core1)发来的消息，见 `reaction`
+    Reaction(TimerMessage),
+    /// 闹钟开始响了，None 是不响了(广播)，见 `wake_alarm`
+    WakeAlarm(Option<Ringing>),
+    /// 确认框选完了，只发给弹框的那个页面，`yes` 是选了 "yes"
+    Confirmed { request: Confirm, yes: bool },
+    /// 按键重映射改了、恢复默认了或者等按键超时了(广播)，见 `remap`
+    InputBindings(InputBindings),
+}
+
+/// 页面想让主循环做的事，一般是要用到 flash、外设这些页面拿不到的东西
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Action {
+    /// 保存新的亮度时间表
+    SaveDimSchedule(DimSchedule),
+    /// 保存新的告警规则表
+    SaveAlarmRules(AlarmRules),
+    /// 保存新的海平面气压(Pa)，算海拔用
+    SaveSeaLevel(u32),
+    /// 保存新的轮播设置
+    SaveCarousel(CarouselConfig),
+    /// 扫一遍 I2C 总线、诊断 SCL 线，结果用 `Event::BusReport` 广播回来
+    ScanBus,
+    /// 校准页面做完一步，按现在的校准值算好保存
+    Calibrate(CalibrationInput),
+    /// 恢复成不校准
+    ResetCalibration,
+    /// 保存表盘设置
+    SaveWatchFace(WatchFaceConfig),
+    /// 关机，见 `power_off`
+    PowerOff,
+    /// 保存开机画面用哪个图片槽，None 是不用
+    SaveSplash(Option<u8>),
+    /// 保存大字模式开关，见 `large_text`
+    SaveLargeText(bool),
+    /// 换显示方案并保存，值是 `display_profile::PROFILES` 里的编号
+    SaveDisplayProfile(u8),
+    /// 换主题并保存，值是 `theme::THEMES` 里的编号
+    SaveTheme(u8),
+    /// 让 core1 开一局反应游戏，倒计时多少毫秒，见 `reaction`
+    StartReaction { countdown_ms: u16 },
+    /// 保存闹钟设置，见 `wake_alarm`
+    SaveWakeAlarms(WakeAlarmConfig),
+    /// 正在响的闹钟贪睡
+    SnoozeWakeAlarm,
+    /// 关掉正在响的闹钟
+    DismissWakeAlarm,
+    /// 脉冲计数清零，见 `pulse_counter`
+    ResetPulseCount,
+    /// 问一下要不要关机，选 yes 再 `PowerOff`(默认是按满 3 秒 Select，见 `gesture::HoldGate`)
+    AskPowerOff,
+    /// 进床头钟模式(默认是长按 Back，见 `input_map`)
+    EnterSleepClock,
+    /// 关掉所有页面，打开演示菜单(默认是双击 Back)
+    OpenDemoMenu,
+    /// 等用户按一个输入给这个角色，按了(或者超时了)用 `Event::InputBindings` 广播回来，见 `remap`
+    CaptureInput(Role),
+    /// 按键重映射恢复默认
+    ResetInputBindings,
+    /// 保存时区并按新时区重新对时，见 `timezone`
+    SaveTimeZone(TimeZone),
+}
+
+/// 要先问一下才做的操作，见 `confirm`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Confirm {
+    /// 框里的问题，一行 18 个字以内
+    pub prompt: &'static str,
+    /// 选了 "yes" 以后页面交给主循环的动作
+    pub action: Action,
+}
+
+/// 页面处理完事件之后想做的页面切换
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Transition {
+    /// 不切换
+    None,
+    /// 在当前页面上面再打开一个页面(弹窗、菜单)
+    Push(PageId),
+    /// 关掉当前页面，回到下面那个。最底下的页面不能关
+    Pop,
+    /// 换掉当前页面
+    Replace(PageId),
+    /// 在当前页面上面弹一个确认框，页面不换
+    Confirm(Confirm),
+}
+
+/// 一个页面
+pub trait Page {
+    /// 处理事件。按键事件只发给最上面的页面，电脑状态这种广播事件所有页面都会收到
+    fn on_event(&mut self, event: &Event, now_ms: u64) -> Transition {
+        let _ = (event, now_ms);
+        Transition::None
+    }
+
+    /// 每一帧调用一次，返回 true 表示需要重画(比如新数据来了、时间变了)
+    fn tick(&mut self, now_ms: u64) -> bool {
+        let _ = now_ms;
+        false
+    }
+
+    /// 逻辑 tick，每 `timestep::TICK_MS` 毫秒一次，和帧率无关。动画、游戏在这里往前走，
+    /// 返回 true 表示变了，下一帧要重画。`tick` 是第几个 tick，每几个 tick 走一步就拿它取模
+    fn step(&mut self, tick: u32) -> bool {
+        let _ = tick;
+        false
+    }
+
+    /// 屏幕换了方向，`size` 是新方向下的宽高。按旧尺寸算好存着的布局在这里重算或者扔掉，
+    /// 滚动位置这种要收回新的范围里。不在最上面的页面也会调，之后整屏重画
+    fn on_rotation_changed(&mut self, size: Size) {
+        let _ = size;
+    }
+
+    /// 取走页面攒下来的动作，主循环每一圈都会问一次最上面的页面
+    fn take_action(&mut self) -> Option<Action> {
+        None
+    }
+
+    /// 画页面。画之前画布已经清空了
+    fn render(&mut self, canvas: &mut Canvas, now_ms: u64) -> Result<(), Infallible>;
+
+    /// 大字模式下画页面：只画一两个最要紧的读数(见 `large_text`)。返回 false 是没有大字版，
+    /// 这时候什么都不要画，调度器会把 `render` 放大两倍显示
+    fn render_large(&mut self, canvas: &mut Canvas, now_ms: u64) -> Result<bool, Infallible> {
+        let _ = (canvas, now_ms);
+        Ok(false)
+    }
+
+    /// 是不是只重画变了的窗口(见 `compositor`)。是的话这一页单独显示的时候调度器不清屏，改调 `render_dirty`
+    fn redraws_partially(&self) -> bool {
+        false
+    }
+
+    /// 只重画变了的窗口，画布上还是这一页上一帧画完的样子。整页重画走的还是清屏再 `render`
+    fn render_dirty(&mut self, canvas: &mut Canvas, now_ms: u64) -> Result<(), Infallible> {
+        self.render(canvas, now_ms)
+    }
+
+    /// 希望每秒 tick 几次。按键这类事件不受这个限制，会马上重画
+    fn desired_fps(&self) -> u16 {
+        DEFAULT_FPS
+    }
+
+    /// 是不是叠在别的页面上面的小窗口。是的话下面的页面也会画出来，否则只画这个页面
+    fn is_overlay(&self) -> bool {
+        false
+    }
+
+    /// 自动轮播的时候在这一页停多久(毫秒)，None 是用默认的 `carousel::DEFAULT_DWELL_MS`
+    fn dwell_ms(&self) -> Option<u32> {
+        None
+    }
+
+    /// 有没有东西可显示。没有的话(比如没接对应的传感器)自动轮播会跳过这一页
+    fn has_data(&self) -> bool {
+        true
+    }
+}
+
+/// 页面调度器
+pub struct Scheduler<'a, const N: usize> {
+    pages: [&'a mut dyn Page; N],
+    stack: Vec<PageId, STACK_DEPTH>,
+    toast: Toast,
+    toast_visible: bool,
+    alarm_icon: bool,
+    /// 低电量图标：None 是不画，里面是有没有设置等着存
+    battery_icon: Option<bool>,
+    network_icon: NetState,
+    /// 轮播进度(百分比)，不在轮播是 None
+    carousel_progress: Option<u8>,
+    needs_redraw: bool,
+    /// 上一帧是哪个只重画窗口的页面单独画的，画完以后画布的 CRC。对得上下一帧才能只重画窗口
+    kept: Option<(PageId, u32)>,
+    /// 逻辑 tick 走过，到了下一帧要重画
+    stepped: bool,
+    clock: FixedStep,
+    /// 提示条、告警图标、轮播进度变了，只重画叠加层
+    #[cfg(feature = "overlay-layer")]
+    overlay_dirty: bool,
+    next_frame_ms: u64,
+    /// 帧同步，None 是不同步
+    pacing: Option<Pacing>,
+    perf: Option<&'a RefCell<PerfTable<N>>>,
+    /// 换页以后还没播的动画
+    transition: Option<TransitionEffect>,
+    transitions_enabled: bool,
+    /// 大字模式，见 `large_text`
+    large_text: bool,
+    /// 没有大字版的页面放大以后看的是哪一块
+    zoom_quadrant: u8,
+    /// 上一帧最底下那一页是不是放大显示的，是的话长按 Up/Down 换块
+    zoomed: bool,
+    /// 正在问的确认框
+    modal: Option<Modal>,
+    /// 主循环弹的确认框选了 "yes"，动作等着 `take_action` 取走
+    confirmed: Option<Action>,
+}
+
+/// 帧同步的状态：第一次 flush 的时间当成刷新的起点，以后只在起点往后整数个周期的时候 flush
+#[derive(Debug, Clone, Copy)]
+struct Pacing {
+    period_us: u64,
+    anchor_us: Option<u64>,
+    /// 下一次最早什么时候能画
+    next_us: u64,
+}
+
+/// 挂在某个页面上面的确认框：那个页面在栈里的第 `depth` 层(从 1 数)，在最上面的时候才显示。
+/// 主循环自己弹的(`ask`)不挂在页面上，`owner` 是 None，一直显示
+#[derive(Debug, Clone, Copy)]
+struct Modal {
+    dialog: ConfirmDialog,
+    owner: Option<PageId>,
+    depth: usize,
+}
+
+impl<'a, const N: usize> Scheduler<'a, N> {
+    /// `pages` 是所有页面，`root` 是最底下那一页
+    pub fn new(pages: [&'a mut dyn Page; N], root: PageId) -> Self {
+        let mut stack = Vec::new();
+        let _ = stack.push(root);
+        Self {
+            pages,
+            stack,
+            toast: Toast::new(),
+            toast_visible: false,
+            alarm_icon: false,
+            battery_icon: None,
+            network_icon: NetState::Off,
+            carousel_progress: None,
+            needs_redraw: true,
+            kept: None,
+            stepped: false,
+            clock: FixedStep::new(),
+            #[cfg(feature = "overlay-layer")]
+            overlay_dirty: true,
+            next_frame_ms: 0,
+            pacing: None,
+            perf: None,
+            transition: None,
+            transitions_enabled: true,
+            large_text: false,
+            zoom_quadrant: 0,
+            zoomed: false,
+            modal: None,
+            confirmed: None,
+        }
+    }
+
+    /// 以后每一帧都往 `perf` 里记耗时
+    pub fn set_perf(&mut self, perf: &'a RefCell<PerfTable<N>>) {
+        self.perf = Some(perf);
+    }
+
+    /// 当前最上面的页面
+    pub fn current(&self) -> PageId {
+        // 栈里至少有一个页面(Pop 不会把最后一个弹出去)
+        self.stack[self.stack.len() - 1]
+    }
+
+    /// 页面栈有几层
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// 页面自己说的轮播停留时间，编号不对也是 None
+    pub fn page_dwell_ms(&self, id: PageId) -> Option<u32> {
+        self.pages.get(id.0 as usize)?.dwell_ms()
+    }
+
+    /// 页面有没有东西可显示，编号不对的当作没有
+    pub fn page_has_data(&self, id: PageId) -> bool {
+        self.pages
+            .get(id.0 as usize)
+            .is_some_and(|page| page.has_data())
+    }
+
+    fn page(&mut self, id: PageId) -> &mut dyn Page {
+        &mut *self.pages[id.0 as usize]
+    }
+
+    /// 最下面一个要画出来的页面在栈里的位置：从最上面往下找到第一个不是小窗口的页面
+    fn base(&self) -> usize {
+        (0..self.stack.len())
+            .rev()
+            .find(|&i| !self.pages[self.stack[i].0 as usize].is_overlay())
+            .unwrap_or(0)
+    }
+
+    /// 现在的帧间隔(毫秒)：画出来的页面里谁要的帧率最高就按谁的来
+    pub fn frame_interval_ms(&self) -> u64 {
+        let fps = self.stack[self.base()..]
+            .iter()
+            .map(|id| self.pages[id.0 as usize].desired_fps())
+            .max()
+            .unwrap_or(DEFAULT_FPS);
+        1000 / fps.max(1) as u64
+    }
+
+    /// 显示着的确认框：问它的页面在最上面才算，被别的页面盖住了就先不显示
+    fn shown_modal(&self) -> Option<&Modal> {
+        self.modal
+            .as_ref()
+            .filter(|modal| modal.owner.is_none() || modal.depth == self.stack.len())
+    }
+
+    /// 有没有确认框在问
+    pub fn has_modal(&self) -> bool {
+        self.shown_modal().is_some()
+    }
+
+    /// 确认框显示着的时候按键都归它，按下以外的按键事件和手势也不交给页面。
+    /// 选完了关掉框，把结果发给问的页面，页面这时候要的切换照样生效
+    fn dispatch_modal(&mut self, event: &Event, now_ms: u64) -> bool {
+        if self.shown_modal().is_none() {
+            return false;
+        }
+        let button = match event {
+            Event::Button(ButtonEvent::Pressed(button)) => *button,
+            Event::Button(_) | Event::Gesture(_) => return true,
+            _ => return false,
+        };
+        let Some(modal) = self.modal.as_mut() else {
+            return false;
+        };
+        let Some(yes) = modal.dialog.on_press(button) else {
+            self.overlay_changed();
+            return true;
+        };
+        let (request, owner) = (modal.dialog.request(), modal.owner);
+        self.modal = None;
+        self.overlay_changed();
+        let Some(owner) = owner else {
+            self.confirmed = yes.then_some(request.action);
+            return true;
+        };
+        let transition = self
+            .page(owner)
+            .on_event(&Event::Confirmed { request, yes }, now_ms);
+        self.apply(transition);
+        true
+    }
+
+    /// 主循环自己弹一个确认框(比如长按关机)，不挂在哪个页面上，换页也不关。
+    /// 选了 "yes" 的话动作从 `take_action` 交回主循环。已经有框在问的话不弹
+    pub fn ask(&mut self, request: Confirm) {
+        if self.modal.is_some() {
+            return;
+        }
+        self.modal = Some(Modal {
+            dialog: ConfirmDialog::new(request),
+            owner: None,
+            depth: self.stack.len(),
+        });
+        self.overlay_changed();
+    }
+
+    /// 问的页面不在栈里原来的位置了(关掉了、换掉了)，框也不要了
+    fn drop_stale_modal(&mut self) {
+        if let Some(modal) = self.modal {
+            let stale = modal
+                .owner
+                .is_some_and(|owner| self.stack.get(modal.depth - 1) != Some(&owner));
+            if stale {
+                self.modal = None;
+            }
+            // 框可能刚被盖住或者又露出来了
+            self.overlay_changed();
+        }
+    }
+
+    /// 按键之类的事件，只发给最上面的页面
+    ///
+    /// 大字模式下页面是放大显示的话，长按 Up/Down 被拿来换块，不发给页面。确认框显示着的话按键先给它
+    pub fn dispatch(&mut self, event: Event, now_ms: u64) {
+        if self.dispatch_modal(&event, now_ms) {
+            return;
+        }
+        if self.pan(&event) {
+            self.needs_redraw = true;
+            return;
+        }
+        let transition = self.page(self.current()).on_event(&event, now_ms);
+        self.apply(transition);
+        self.needs_redraw = true;
+    }
+
+    /// 取走主循环弹的确认框选出来的动作，没有的话取最上面页面的动作
+    pub fn take_action(&mut self) -> Option<Action> {
+        self.confirmed
+            .take()
+            .or_else(|| self.page(self.current()).take_action())
+    }
+
+    /// 广播事件，所有页面都会收到(不管在不在栈里)，只有最上面页面的切换请求会生效
+    pub fn broadcast(&mut self, event: Event, now_ms: u64) {
+        let current = self.current().0 as usize;
+        let mut transition = Transition::None;
+        for (i, page) in self.pages.iter_mut().enumerate() {
+            let requested = page.on_event(&event, now_ms);
+            if i == current {
+                transition = requested;
+            }
+        }
+        self.apply(transition);
+        self.needs_redraw = true;
+    }
+
+    /// 打开帧同步，`period_us` 是屏幕的刷新周期；None 关掉。见模块文档
+    pub fn set_frame_pacing(&mut self, period_us: Option<u32>) {
+        self.pacing = period_us.filter(|&us| us > 0).map(|us| Pacing {
+            period_us: us as u64,
+            anchor_us: None,
+            next_us: 0,
+        });
+    }
+
+    /// 换页的时候要不要播动画，默认要
+    pub fn set_transitions(&mut self, enabled: bool) {
+        self.transitions_enabled = enabled;
+        if !enabled {
+            self.transition = None;
+        }
+    }
+
+    /// 打开或者关掉大字模式，下一帧就按新的模式画
+    pub fn set_large_text(&mut self, enabled: bool) {
+        self.large_text = enabled;
+        self.zoom_quadrant = 0;
+        self.invalidate();
+    }
+
+    /// 换主题(`theme::THEMES` 里的编号)，下一帧整屏按新主题画，见 `theme`
+    pub fn set_theme(&mut self, index: u8) {
+        theme::set_active(index);
+        self.invalidate();
+    }
+
+    /// 屏幕换了方向(`size` 是新的宽高)：所有页面重算布局，下一帧整屏重画，见模块文档
+    pub fn rotation_changed(&mut self, size: Size) {
+        for page in self.pages.iter_mut() {
+            page.on_rotation_changed(size);
+        }
+        self.zoom_quadrant = 0;
+        self.transition = None;
+        self.invalidate();
+    }
+
+    /// 放大显示的时候长按 Up/Down 换到上一块/下一块，换了返回 true
+    fn pan(&mut self, event: &Event) -> bool {
+        if !self.large_text || !self.zoomed {
+            return false;
+        }
+        let step = match event {
+            Event::Gesture(Gesture::Long(Button::Up)) => QUADRANTS - 1,
+            Event::Gesture(Gesture::Long(Button::Down)) => 1,
+            _ => return false,
+        };
+        self.zoom_quadrant = (self.zoom_quadrant + step) % QUADRANTS;
+        true
+    }
+
+    /// 取走上一次换页要播的动画
+    pub fn take_transition(&mut self) -> Option<TransitionEffect> {
+        self.transition.take()
+    }
+
+    /// 执行页面切换
+    pub fn apply(&mut self, transition: Transition) {
+        let before = self.current();
+        let effect = match transition {
+            Transition::Pop => TransitionEffect::WipeDown,
+            _ => TransitionEffect::SlideLeft,
+        };
+        match transition {
+            Transition::None => return,
+            Transition::Confirm(request) => {
+                let dialog = ConfirmDialog::new(request);
+                let (owner, depth) = (Some(before), self.stack.len());
+                self.modal = Some(Modal {
+                    dialog,
+                    owner,
+                    depth,
+                });
+                self.overlay_changed();
+                return;
+            }
+            Transition::Push(id) => {
+                if self.stack.push(id).is_err() {
+                    defmt::warn!("page stack full, dropping {}", id);
+                }
+            }
+            Transition::Pop => {
+                if self.stack.len() > 1 {
+                    self.stack.pop();
+                }
+            }
+            Transition::Replace(id) => {
+                self.stack.pop();
+                let _ = self.stack.push(id);
+            }
+        }
+        self.drop_stale_modal();
+        // 换了页面帧率可能也变了，马上按新页面的节奏重新开始
+        self.next_frame_ms = 0;
+        self.needs_redraw = true;
+        // 小窗口弹出来、关掉的时候下面的页面还在，不用动画
+        let after = self.current();
+        if after != before {
+            self.zoom_quadrant = 0;
+        }
+        let overlay = |id: PageId| self.pages[id.0 as usize].is_overlay();
+        if self.transitions_enabled && after != before && !overlay(before) && !overlay(after) {
+            self.transition = Some(effect);
+        }
+    }
+
+    /// 弹到只剩最底下那一页
+    pub fn pop_to_root(&mut self) {
+        if self.stack.len() > 1 {
+            self.stack.truncate(1);
+            self.drop_stale_modal();
+            self.next_frame_ms = 0;
+            self.needs_redraw = true;
+        }
+    }
+
+    /// 在页面最上面显示一条提示
+    pub fn show_toast(&mut self, message: &str, now_ms: u64) {
+        self.toast.show(message, now_ms);
+        self.overlay_changed();
+    }
+
+    /// 右上角的告警图标开还是关
+    pub fn set_alarm_indicator(&mut self, on: bool) {
+        if self.alarm_icon != on {
+            self.alarm_icon = on;
+            self.overlay_changed();
+        }
+    }
+
+    /// 低电量图标开还是关，`save_pending` 是有没有设置等着存
+    pub fn set_battery_indicator(&mut self, low: bool, save_pending: bool) {
+        let icon = low.then_some(save_pending);
+        if self.battery_icon != icon {
+            self.battery_icon = icon;
+            self.overlay_changed();
+        }
+    }
+
+    /// 网络图标，`Off` 是不画。只在变了的时候重画
+    pub fn set_network_indicator(&mut self, state: NetState) {
+        if self.network_icon != state {
+            self.network_icon = state;
+            self.overlay_changed();
+        }
+    }
+
+    /// 底边的轮播进度条，0..=100，None 是不画。只在变了的时候重画
+    pub fn set_carousel_progress(&mut self, percent: Option<u8>) {
+        if self.carousel_progress != percent {
+            self.carousel_progress = percent;
+            self.overlay_changed();
+        }
+    }
+
+    /// 强制下一帧重画，比如屏幕旋转之后
+    pub fn invalidate(&mut self) {
+        self.needs_redraw = true;
+        self.kept = None;
+        self.overlay_changed();
+    }
+
+    /// 盖在页面上面的东西变了。有叠加层的时候只重画叠加层，没有的话整页重画
+    fn overlay_changed(&mut self) {
+        #[cfg(feature = "overlay-layer")]
+        {
+            self.overlay_dirty = true;
+        }
+        #[cfg(not(feature = "overlay-layer"))]
+        {
+            // 盖在页面上的东西画在画布里，要清屏才擦得掉
+            self.needs_redraw = true;
+            self.kept = None;
+        }
+    }
+
+    /// 画确认框、告警图标、低电量图标、网络图标、轮播进度、提示条
+    fn draw_overlays<D>(&self, target: &mut D, now_ms: u64)
+    where
+        D: PatternTarget<Error = Infallible>,
+    {
+        let theme = theme::active();
+        if let Some(modal) = self.shown_modal() {
+            let Ok(()) = modal.dialog.draw_styled(target, &theme.dialog);
+        }
+        if self.alarm_icon {
+            let Ok(()) = draw_alarm_icon(target);
+        }
+        // 大字模式下只留告警图标和提示条
+        if let Some(save_pending) = self.battery_icon.filter(|_| !self.large_text) {
+            let Ok(()) = draw_low_battery_indicator(target, save_pending);
+        }
+        if !self.large_text {
+            let Ok(()) = draw_network_indicator(target, self.network_icon);
+        }
+        if let Some(percent) = self.carousel_progress.filter(|_| !self.large_text) {
+            let Ok(()) = draw_carousel_progress(target, percent);
+        }
+        let Ok(()) = self.toast.draw_styled(target, now_ms, &theme.toast);
+    }
+
+    /// 画一个页面。大字模式下先试大字版，没有的话最底下那一页放大画，小窗口照原样画
+    fn render_page(&mut self, id: PageId, is_base: bool, canvas: &mut Canvas, now_ms: u64) {
+        let (large_text, quadrant) = (self.large_text, self.zoom_quadrant);
+        let page = self.page(id);
+        if !large_text {
+            let Ok(()) = page.render(canvas, now_ms);
+            return;
+        }
+        let Ok(drawn) = page.render_large(canvas, now_ms);
+        let zoom = !drawn && is_base;
+        if zoom {
+            let mut source = FrameBuffer::new(canvas.rotation());
+            let Ok(()) = page.render(&mut source, now_ms);
+            draw_zoomed(&source, quadrant, canvas);
+        } else if !drawn {
+            let Ok(()) = page.render(canvas, now_ms);
+        }
+        if is_base {
+            self.zoomed = zoom;
+        }
+    }
+
+    /// 推进一帧：先把到点的逻辑 tick 走完，到时间了再 tick 所有栈里的页面，需要的话重画。
+    /// 返回 true 表示画过了，调用方要 flush
+    ///
+    /// 事件触发的重画不用等到下一帧，调用的时候就画。逻辑落后了这一次不画，状态留到下一次
+    pub fn frame(&mut self, canvas: &mut Canvas, now_ms: u64) -> bool {
+        let steps = self.clock.advance(now_ms);
+        for tick in steps.ticks() {
+            for i in 0..self.stack.len() {
+                let id = self.stack[i];
+                if self.page(id).step(tick) {
+                    self.stepped = true;
+                }
+            }
+        }
+        if now_ms >= self.next_frame_ms {
+            self.next_frame_ms = now_ms + self.frame_interval_ms();
+            for i in 0..self.stack.len() {
+                let id = self.stack[i];
+                if self.page(id).tick(now_ms) {
+                    self.needs_redraw = true;
+                }
+            }
+            if core::mem::take(&mut self.stepped) {
+                self.needs_redraw = true;
+            }
+        }
+        // 有东西要画但是这一次让给逻辑了才算跳过一帧
+        let skipped = steps.skip_render && self.needs_redraw;
+        if let Some(perf) = self.perf {
+            perf.borrow_mut()
+                .record_steps(steps.count, steps.dropped, skipped);
+        }
+        if steps.skip_render {
+            return false;
+        }
+        // 还没到下一个刷新周期，要画的东西留到下一次
+        let now_us = now_ms * 1000;
+        if self.pacing.is_some_and(|pacing| now_us < pacing.next_us) {
+            return false;
+        }
+        let toast_visible = self.toast.is_visible(now_ms);
+        if toast_visible != self.toast_visible {
+            self.toast_visible = toast_visible;
+            self.overlay_changed();
+        }
+        #[cfg(feature = "overlay-layer")]
+        let redraw_overlay = core::mem::take(&mut self.overlay_dirty);
+        #[cfg(not(feature = "overlay-layer"))]
+        let redraw_overlay = self.needs_redraw;
+        if !self.needs_redraw && !redraw_overlay {
+            return false;
+        }
+        if let Some(pacing) = self.pacing.as_mut() {
+            let anchor = *pacing.anchor_us.get_or_insert(now_us);
+            let periods = (now_us - anchor) / pacing.period_us + 1;
+            pacing.next_us = anchor + periods * pacing.period_us;
+        }
+
+        let frame_start = perf::ticks();
+        let mut keep = None;
+        if core::mem::take(&mut self.needs_redraw) {
+            let base = self.base();
+            let id = self.stack[base];
+            let partial = base + 1 == self.stack.len()
+                && !self.large_text
+                && self.pages[id.0 as usize].redraws_partially();
+            // 画布还是这一页上一帧画完的样子才能只重画窗口
+            if partial && self.kept.take() == Some((id, canvas.checksum())) {
+                let start = perf::ticks();
+                let Ok(()) = self.page(id).render_dirty(canvas, now_ms);
+                let elapsed = perf::ticks().wrapping_sub(start);
+                if let Some(perf) = self.perf {
+                    perf.borrow_mut().record_render(id.0 as usize, elapsed);
+                }
+            } else {
+                canvas.clear();
+                for i in base..self.stack.len() {
+                    let id = self.stack[i];
+                    let start = perf::ticks();
+                    self.render_page(id, i == base, canvas, now_ms);
+                    let elapsed = perf::ticks().wrapping_sub(start);
+                    if let Some(perf) = self.perf {
+                        perf.borrow_mut().record_render(id.0 as usize, elapsed);
+                    }
+                }
+            }
+            self.kept = None;
+            keep = partial.then_some(id);
+        }
+        if redraw_overlay {
+            #[cfg(feature = "overlay-layer")]
+            let target = {
+                let overlay = canvas.overlay_mut();
+                overlay.clear();
+                overlay
+            };
+            #[cfg(not(feature = "overlay-layer"))]
+            let target = &mut *canvas;
+            self.draw_overlays(target, now_ms);
+        }
+        if let Some(id) = keep {
+            self.kept = Some((id, canvas.checksum()));
+        }
+        let elapsed = perf::ticks().wrapping_sub(frame_start);
+        if let Some(perf) = self.perf {
+            let mut perf = perf.borrow_mut();
+            if perf.record_frame(elapsed) {
+                defmt::warn!(
+                    "frame took {} us (budget {} us), top page {}",
+                    elapsed,
+                    perf.budget_us(),
+                    self.current()
+                );
+            }
+        }
+        true
+    }
+}
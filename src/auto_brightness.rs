@@ -0,0 +1,256 @@
+//! 跟着环境光自动调亮度：ADC0(GP26)上接一个光敏电阻分压，越亮电压越高，换算成屏幕亮度
+//!
+//! 接法：光敏电阻一头接 3V3、一头接 GP26，GP26 再接一个 10K 电阻到 AGND。光越强光敏电阻越小，GP26 上的电压越高。
+//! 常见的 5528 光敏电阻在室内灯光下大约 10K 上下(GP26 约 1.6V)，手捂住几百 K(零点几伏)，太阳底下接近 3.3V。
+//! 读的是传感器通道 "adc0"(校准过的 mV，见 `calibration`)，所以 ADC0 就不能再接别的了；打开 `mic-vu` 的时候 ADC0 是话筒，
+//! 这个功能读到的是话筒的直流偏置，没有意义。
+//!
+//! 换算曲线(`AutoBrightnessConfig::level_for`)：
+//!
+//! - 低于 `dark_mv` 一律是 `min_level`，高于 `bright_mv` 一律是 `max_level`
+//! - 中间按 t² 走(t 是电压在两头之间的比例)：眼睛对暗处的亮度变化更敏感，暗的这一头升得慢一点，不会稍微一亮就晃眼
+//! - `min_level` 最小是 1，怎么暗屏幕都不会全黑
+//!
+//! 读数先过一个 `WINDOW` 个的滑动平均(每 `UPDATE_MS` 加一个，大约 4 秒)，手从上面晃过去、灯闪一下都不会跟着闪；
+//! 算出来的亮度和现在差不到 `HYSTERESIS` 就不改，到了两头的极限值除外。
+//! 打开以后按时间调亮度(见 `dimming`)不生效，关掉以后回到那边。设置存在设置里，串口是 `SETTINGS AUTOBRIGHT`。
+
+use crate::filter::MovingAverage;
+
+/// 编码之后多少字节：暗端、亮端电压各 2 字节(mV)，最暗、最亮亮度各 1 字节
+pub const ENCODED_LEN: usize = 6;
+
+/// 多久加一个读数(毫秒)
+pub const UPDATE_MS: u64 = 250;
+
+/// 滑动平均攒多少个读数
+pub const WINDOW: usize = 16;
+
+/// 亮度变化不到这么多就不改，免得在两档之间来回跳
+pub const HYSTERESIS: u8 = 4;
+
+/// ADC 最高能读到多少(mV)
+pub const MAX_MV: u16 = 3300;
+
+/// 只给了两头电压的时候用的最暗、最亮
+pub const DEFAULT_MIN_LEVEL: u8 = 16;
+pub const DEFAULT_MAX_LEVEL: u8 = 255;
+
+/// 自动亮度的设置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct AutoBrightnessConfig {
+    /// 低于这个电压(mV)是最暗
+    pub dark_mv: u16,
+    /// 高于这个电压(mV)是最亮，0 是关掉
+    pub bright_mv: u16,
+    pub min_level: u8,
+    pub max_level: u8,
+}
+
+impl Default for AutoBrightnessConfig {
+    fn default() -> Self {
+        Self::OFF
+    }
+}
+
+impl AutoBrightnessConfig {
+    /// 关掉
+    pub const OFF: Self = Self {
+        dark_mv: 0,
+        bright_mv: 0,
+        min_level: 0,
+        max_level: 0,
+    };
+
+    pub fn is_enabled(&self) -> bool {
+        self.bright_mv > 0
+    }
+
+    /// 合不合理：关掉的是全 0，开着的要暗端 < 亮端 <= `MAX_MV`、1 <= 最暗 < 最亮
+    pub fn is_valid(&self) -> bool {
+        *self == Self::OFF
+            || (self.dark_mv < self.bright_mv
+                && self.bright_mv <= MAX_MV
+                && 0 < self.min_level
+                && self.min_level < self.max_level)
+    }
+
+    /// 电压(mV)换算成亮度，见模块文档
+    pub fn level_for(&self, mv: i32) -> u8 {
+        let span = (self.bright_mv - self.dark_mv) as u64;
+        let x = (mv - self.dark_mv as i32).clamp(0, span as i32) as u64;
+        let range = (self.max_level - self.min_level) as u64;
+        self.min_level + (range * x * x / (span * span)) as u8
+    }
+
+    pub fn encode(&self) -> [u8; ENCODED_LEN] {
+        let mut out = [0u8; ENCODED_LEN];
+        out[0..2].copy_from_slice(&self.dark_mv.to_le_bytes());
+        out[2..4].copy_from_slice(&self.bright_mv.to_le_bytes());
+        out[4] = self.min_level;
+        out[5] = self.max_level;
+        out
+    }
+
+    /// 解码，不合理返回 None
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let &[dark_lo, dark_hi, bright_lo, bright_hi, min_level, max_level] = bytes else {
+            return None;
+        };
+        let config = Self {
+            dark_mv: u16::from_le_bytes([dark_lo, dark_hi]),
+            bright_mv: u16::from_le_bytes([bright_lo, bright_hi]),
+            min_level,
+            max_level,
+        };
+        config.is_valid().then_some(config)
+    }
+}
+
+/// 自动亮度：主循环每 `UPDATE_MS` 调一次 `update`，返回 Some 的时候把这个亮度发给屏幕
+#[derive(Debug, Clone, Default)]
+pub struct AutoBrightness {
+    filter: MovingAverage<WINDOW>,
+    /// 上一次给出去的亮度，不知道的话是 None
+    level: Option<u8>,
+}
+
+impl AutoBrightness {
+    pub const fn new() -> Self {
+        Self {
+            filter: MovingAverage::new(),
+            level: None,
+        }
+    }
+
+    /// 亮度被别的地方改过了(比如导入设置、换显示方案)，下一次 `update` 不管差多少都给一次
+    pub fn invalidate(&mut self) {
+        self.level = None;
+    }
+
+    /// `mv` 是光敏电阻分压的读数，读不到传 None(亮度不动)。关着的时候永远是 None
+    pub fn update(&mut self, config: &AutoBrightnessConfig, mv: Option<i32>) -> Option<u8> {
+        if !config.is_enabled() {
+            self.filter.clear();
+            self.level = None;
+            return None;
+        }
+        let average = self.filter.push(mv?);
+        let target = config.level_for(average);
+        let at_limit = target == config.min_level || target == config.max_level;
+        if let Some(level) = self.level {
+            if level == target || (level.abs_diff(target) < HYSTERESIS && !at_limit) {
+                return None;
+            }
+        }
+        self.level = Some(target);
+        Some(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: AutoBrightnessConfig = AutoBrightnessConfig {
+        dark_mv: 200,
+        bright_mv: 2200,
+        min_level: 16,
+        max_level: 255,
+    };
+
+    #[test]
+    fn curve_endpoints_and_clamping() {
+        assert_eq!(CONFIG.level_for(200), 16);
+        assert_eq!(CONFIG.level_for(2200), 255);
+        assert_eq!(CONFIG.level_for(0), 16);
+        assert_eq!(CONFIG.level_for(-50), 16);
+        assert_eq!(CONFIG.level_for(3300), 255);
+        assert_eq!(CONFIG.level_for(i32::MAX), 255);
+    }
+
+    #[test]
+    fn curve_is_quadratic_and_monotonic() {
+        // 一半的电压是四分之一的亮度范围
+        assert_eq!(CONFIG.level_for(1200), 16 + 239 / 4);
+        let mut last = 0;
+        for mv in (0..=3300).step_by(10) {
+            let level = CONFIG.level_for(mv);
+            assert!(level >= last, "{mv} mV");
+            last = level;
+        }
+    }
+
+    #[test]
+    fn encode_decode_round_trip() {
+        assert_eq!(AutoBrightnessConfig::decode(&CONFIG.encode()), Some(CONFIG));
+        let off = AutoBrightnessConfig::OFF;
+        assert_eq!(AutoBrightnessConfig::decode(&off.encode()), Some(off));
+        let inverted = AutoBrightnessConfig {
+            min_level: 200,
+            max_level: 100,
+            ..CONFIG
+        };
+        assert_eq!(AutoBrightnessConfig::decode(&inverted.encode()), None);
+        assert_eq!(AutoBrightnessConfig::decode(&CONFIG.encode()[..5]), None);
+    }
+
+    /// 窗口攒满同一个读数，滤波器的平均就是它
+    fn settle(auto: &mut AutoBrightness, mv: i32) -> Option<u8> {
+        let mut last = None;
+        for _ in 0..WINDOW {
+            last = auto.update(&CONFIG, Some(mv)).or(last);
+        }
+        last
+    }
+
+    #[test]
+    fn small_changes_are_held_back() {
+        let mut auto = AutoBrightness::new();
+        assert_eq!(auto.update(&CONFIG, Some(1200)), Some(75));
+        // 同一个亮度不重复给
+        assert_eq!(auto.update(&CONFIG, Some(1200)), None);
+        // 差不到 HYSTERESIS 不改
+        assert_eq!(CONFIG.level_for(1215), 77);
+        assert_eq!(settle(&mut auto, 1215), None);
+        // 差得多了跟着走，停下来的地方离目标不到 HYSTERESIS
+        let level = settle(&mut auto, 1300).unwrap();
+        assert!(level > 77 && level.abs_diff(CONFIG.level_for(1300)) < HYSTERESIS);
+    }
+
+    #[test]
+    fn limits_are_reached_inside_the_hysteresis() {
+        let mut auto = AutoBrightness::new();
+        // 离最亮差 1，但是到了极限
+        let near_top = (200..2200)
+            .rev()
+            .find(|&mv| CONFIG.level_for(mv) == 254)
+            .unwrap();
+        assert_eq!(settle(&mut auto, near_top), Some(254));
+        assert_eq!(settle(&mut auto, 2500), Some(255));
+
+        let near_bottom = (200..2200).find(|&mv| CONFIG.level_for(mv) == 17).unwrap();
+        let mut auto = AutoBrightness::new();
+        assert_eq!(settle(&mut auto, near_bottom), Some(17));
+        assert_eq!(settle(&mut auto, 0), Some(16));
+    }
+
+    #[test]
+    fn invalidate_forces_the_next_level_out() {
+        let mut auto = AutoBrightness::new();
+        assert_eq!(auto.update(&CONFIG, Some(1200)), Some(75));
+        auto.invalidate();
+        assert_eq!(auto.update(&CONFIG, Some(1200)), Some(75));
+    }
+
+    #[test]
+    fn missing_readings_and_disabled_config_give_nothing() {
+        let mut auto = AutoBrightness::new();
+        assert_eq!(auto.update(&CONFIG, None), None);
+        assert_eq!(auto.update(&AutoBrightnessConfig::OFF, Some(1200)), None);
+        auto.update(&CONFIG, Some(3000));
+        // 关掉的时候窗口也清了，重新打开从新读数开始
+        auto.update(&AutoBrightnessConfig::OFF, None);
+        assert_eq!(auto.update(&CONFIG, Some(200)), Some(16));
+    }
+}
@@ -0,0 +1,190 @@
+//! 开机横幅：显示固件名和版本号
+//!
+//! 名字和版本号是编译的时候由 cargo 通过 `env!` 塞进来的(也就是 Cargo.toml 里的 name/version)，
+//! 所以屏幕上看到的一定是当前烧进去的这个版本，不会出现改了代码忘了改版本字符串的情况。
+//!
+//! 设置里选了开机画面(见 `splash_page`)的话，换成图片槽里的图(`show_splash`)；没选但是编译的时候放了 logo 的话
+//! 显示 logo(`show_logo`，见 `logo`)。
+//! 版本号画面最下面一行的启动模式是一个字一个字打出来的(`type_subtitle`，见 `typewriter`)。
+
+use embedded_graphics::geometry::Point;
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_hal::delay::DelayNs;
+
+use crate::display::BufferedDisplay;
+use crate::image_slots::StoredImage;
+use crate::logo::Logo;
+use crate::text::{centered_x, draw_centered};
+use crate::typewriter::type_text;
+
+/// 固件名，来自 Cargo.toml 的 `package.name`
+pub const FIRMWARE_NAME: &str = env!("CARGO_PKG_NAME");
+
+/// 固件版本，来自 Cargo.toml 的 `package.version`
+pub const FIRMWARE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// 屏幕上显示的版本行，比如 "v0.1.0"。no_std 下没有 format!，所以在编译期用 concat! 拼好
+const VERSION_LINE: &str = concat!("v", env!("CARGO_PKG_VERSION"));
+
+/// 横幅默认停留的时间(毫秒)
+pub const DEFAULT_BANNER_DURATION_MS: u32 = 2000;
+
+/// 打副标题的时候每个字隔多久(毫秒)
+const SUBTITLE_CHAR_MS: u32 = 50;
+
+/// 横幅停留的时候隔多久看一次要不要跳过(毫秒)
+const SKIP_POLL_MS: u32 = 10;
+
+/// 固件名所在行的基线 y 坐标
+const NAME_Y: i32 = 26;
+
+/// 版本号所在行的基线 y 坐标
+const VERSION_Y: i32 = 42;
+
+/// 副标题(比如启动模式)所在行的基线 y 坐标
+const SUBTITLE_Y: i32 = 58;
+
+/// 画开机横幅并立刻显示出来，不等待。想在横幅停留期间做别的事(比如淡入)的时候用这个
+///
+/// `subtitle` 画在最下面一行，用来显示启动模式之类的附加信息。
+/// 名字或版本号太长放不下一行的时候会被截断并补上省略号，不会画出屏幕
+pub fn show_version_banner<D: BufferedDisplay>(
+    display: &mut D,
+    subtitle: Option<&str>,
+) -> Result<(), D::Error> {
+    let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+
+    display.clear_buffer();
+    draw_centered(display, FIRMWARE_NAME, NAME_Y, style)?;
+    draw_centered(display, VERSION_LINE, VERSION_Y, style)?;
+    if let Some(subtitle) = subtitle {
+        draw_centered(display, subtitle, SUBTITLE_Y, style)?;
+    }
+    display.flush()
+}
+
+/// 在已经显示出来的版本号画面(`show_version_banner` 不带副标题)最下面一行把 `subtitle` 一个字一个字打出来
+///
+/// `skip` 返回 true 就一次画完，返回是不是被跳过的，和 `hold_banner` 一样
+pub fn type_subtitle<D, T>(
+    display: &mut D,
+    subtitle: &str,
+    timer: &mut T,
+    skip: impl FnMut() -> bool,
+) -> Result<bool, D::Error>
+where
+    D: BufferedDisplay,
+    T: DelayNs,
+{
+    let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+    let width = display.bounding_box().size.width;
+    let origin = Point::new(
+        centered_x(subtitle, &FONT_6X10, width),
+        SUBTITLE_Y - FONT_6X10.baseline as i32,
+    );
+    let typed = type_text(
+        display,
+        subtitle,
+        style,
+        origin,
+        SUBTITLE_CHAR_MS,
+        timer,
+        skip,
+    )?;
+    Ok(typed.skipped)
+}
+
+/// 开机画面：图片槽里的图居中显示出来，不等待，和 `show_version_banner` 一样接着用 `hold_banner`
+///
+/// 图片下面空得出一行字的时候 `subtitle` 画在最下面一行，图太高就不画了，不去盖住图
+pub fn show_splash<D: BufferedDisplay>(
+    display: &mut D,
+    image: &StoredImage,
+    subtitle: Option<&str>,
+) -> Result<(), D::Error> {
+    let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+    let size = display.bounding_box().size;
+    let top_left = Point::new(
+        (size.width as i32 - image.width as i32) / 2,
+        (size.height as i32 - image.height as i32) / 2,
+    );
+
+    display.clear_buffer();
+    image.draw(display, top_left)?;
+    let bottom = top_left.y + image.height as i32;
+    let subtitle_top = SUBTITLE_Y - FONT_6X10.baseline as i32;
+    if let Some(subtitle) = subtitle.filter(|_| bottom <= subtitle_top) {
+        draw_centered(display, subtitle, SUBTITLE_Y, style)?;
+    }
+    display.flush()
+}
+
+/// 编译进去的 logo 放在屏幕中间，和 `show_splash` 一样下面放得下的话写上副标题
+pub fn show_logo<D: BufferedDisplay>(
+    display: &mut D,
+    logo: &Logo,
+    subtitle: Option<&str>,
+) -> Result<(), D::Error> {
+    let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+    let size = display.bounding_box().size;
+    let top_left = Point::new(
+        (size.width as i32 - logo.width as i32) / 2,
+        (size.height as i32 - logo.height as i32) / 2,
+    );
+
+    display.clear_buffer();
+    logo.draw(display, top_left)?;
+    let bottom = top_left.y + logo.height as i32;
+    let subtitle_top = SUBTITLE_Y - FONT_6X10.baseline as i32;
+    if let Some(subtitle) = subtitle.filter(|_| bottom <= subtitle_top) {
+        draw_centered(display, subtitle, SUBTITLE_Y, style)?;
+    }
+    display.flush()
+}
+
+/// 画开机横幅并停留 `duration_ms` 毫秒，结束时清空缓冲区，方便后面直接画主界面
+pub fn draw_version_banner<D, T>(
+    display: &mut D,
+    timer: &mut T,
+    duration_ms: u32,
+) -> Result<(), D::Error>
+where
+    D: BufferedDisplay,
+    T: DelayNs,
+{
+    show_version_banner(display, None)?;
+
+    timer.delay_ms(duration_ms);
+
+    display.clear_buffer();
+    Ok(())
+}
+
+/// 让已经显示出来的横幅停留最多 `duration_ms` 毫秒，期间每 10ms 问一次 `skip`，返回 true 就提前结束
+///
+/// 提前结束的时候马上清屏并 flush，横幅不会留到主界面画第一帧的时候才消失。返回是不是提前结束的
+pub fn hold_banner<D, T>(
+    display: &mut D,
+    timer: &mut T,
+    duration_ms: u32,
+    mut skip: impl FnMut() -> bool,
+) -> Result<bool, D::Error>
+where
+    D: BufferedDisplay,
+    T: DelayNs,
+{
+    let mut waited = 0;
+    while waited < duration_ms {
+        if skip() {
+            display.clear_buffer();
+            display.flush()?;
+            return Ok(true);
+        }
+        let step = SKIP_POLL_MS.min(duration_ms - waited);
+        timer.delay_ms(step);
+        waited += step;
+    }
+    Ok(false)
+}
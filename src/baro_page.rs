@@ -0,0 +1,264 @@
+//! 气压计页面：BMP280 的气压、温度、海拔和 3 小时气压趋势，也在这里调海平面气压
+//!
+//! 从仪表盘按 Back 进来。箭头是气压趋势：朝上在升(多半要转晴)、朝下在降(多半要变天)、朝右是平稳，
+//! 算法见 `bmp280::pressure_trend`，刚开机历史不够 3 小时就用有多少算多少。
+//!
+//! - Select 开始调海平面气压，Up/Down 每次 0.1hPa，长按每次 1hPa，再按 Select 保存，Back 放弃
+//! - 不在调的时候 Up/Down 切到前一个/后一个传感器页面(温湿度、距离)
+//! - Back 回去
+//!
+//! 调的时候海拔跟着按新的海平面气压重算，保存是交给主循环做的(`Action::SaveSeaLevel`)。
+//! 没接 BMP280 的时候只显示一行提示。大字模式下只显示气压和温度，调的时候只显示海平面气压。
+
+use core::cell::RefCell;
+use core::convert::Infallible;
+
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::Point;
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::primitives::{Primitive, PrimitiveStyle, Triangle};
+use embedded_graphics::Drawable;
+use heapless::String;
+
+use crate::app::{Action, Canvas, Event, Page, PageId, Transition};
+use crate::bmp280::{altitude_dm, pressure_trend, Trend, SEA_LEVEL_MAX_PA, SEA_LEVEL_MIN_PA};
+use crate::input::{Button, ButtonEvent};
+use crate::large_text::draw_large_readings;
+use crate::sensors::{ChannelId, ChannelInfo, SensorRegistry};
+use crate::text::draw_centered;
+use crate::widgets::{draw_menu_row, MENU_ROW_HEIGHT};
+
+/// 标题行基线
+const TITLE_Y: i32 = 8;
+
+/// 第一行的顶边
+const FIRST_ROW_TOP: i32 = 12;
+
+/// 趋势箭头的左边和边长
+const ARROW_X: i32 = 34;
+const ARROW_SIZE: i32 = 8;
+
+/// 海平面气压按一下调多少、长按调多少(Pa)
+const SEA_LEVEL_STEP_PA: u32 = 10;
+const SEA_LEVEL_LONG_STEP_PA: u32 = 100;
+
+/// 海拔的显示格式，读数单位 0.1m
+const ALTITUDE: ChannelInfo = ChannelInfo::new("alt", "m", 10);
+
+/// BMP280 注册到传感器表里的通道
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BaroChannels {
+    pub pressure: ChannelId,
+    pub temperature: ChannelId,
+}
+
+/// 气压计页面
+pub struct BaroPage<'a, S> {
+    sensors: &'a RefCell<SensorRegistry<S>>,
+    /// 没接 BMP280 就是 None
+    channels: Option<BaroChannels>,
+    /// Up/Down 切到哪两个页面
+    prev: PageId,
+    next: PageId,
+    /// 保存过的海平面气压
+    sea_level_pa: u32,
+    /// 正在调的海平面气压
+    editing: Option<u32>,
+    pending: Option<Action>,
+    /// 上次画的时候注册表的 `generation`
+    shown_generation: Option<u32>,
+}
+
+impl<'a, S> BaroPage<'a, S> {
+    /// `sea_level_pa` 是设置里存的海平面气压
+    pub const fn new(
+        sensors: &'a RefCell<SensorRegistry<S>>,
+        channels: Option<BaroChannels>,
+        prev: PageId,
+        next: PageId,
+        sea_level_pa: u32,
+    ) -> Self {
+        Self {
+            sensors,
+            channels,
+            prev,
+            next,
+            sea_level_pa,
+            editing: None,
+            pending: None,
+            shown_generation: None,
+        }
+    }
+
+    fn adjust(&mut self, up: bool, step: u32) {
+        if let Some(pa) = self.editing.as_mut() {
+            *pa = if up {
+                *pa + step
+            } else {
+                pa.saturating_sub(step)
+            }
+            .clamp(SEA_LEVEL_MIN_PA, SEA_LEVEL_MAX_PA);
+        }
+    }
+}
+
+/// 第 `row` 行的顶边
+const fn row_top(row: i32) -> i32 {
+    FIRST_ROW_TOP + row * MENU_ROW_HEIGHT
+}
+
+/// 趋势箭头，`top` 是这一行的顶边
+fn draw_trend<D>(display: &mut D, top: i32, trend: Trend) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let (x, y, s) = (ARROW_X, top + 2, ARROW_SIZE);
+    let triangle = match trend {
+        Trend::Rising => Triangle::new(
+            Point::new(x, y + s),
+            Point::new(x + s, y + s),
+            Point::new(x + s / 2, y),
+        ),
+        Trend::Falling => Triangle::new(
+            Point::new(x, y),
+            Point::new(x + s, y),
+            Point::new(x + s / 2, y + s),
+        ),
+        Trend::Steady => Triangle::new(
+            Point::new(x, y),
+            Point::new(x, y + s),
+            Point::new(x + s, y + s / 2),
+        ),
+    };
+    triangle
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(display)
+}
+
+impl<S> Page for BaroPage<'_, S> {
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        let (button, long) = match event {
+            Event::Button(ButtonEvent::Pressed(button)) => (*button, false),
+            Event::Button(ButtonEvent::LongPress(button)) => (*button, true),
+            _ => return Transition::None,
+        };
+        let step = if long {
+            SEA_LEVEL_LONG_STEP_PA
+        } else {
+            SEA_LEVEL_STEP_PA
+        };
+        match (button, self.editing) {
+            (Button::Back, None) if !long => return Transition::Pop,
+            (Button::Back, Some(_)) => self.editing = None,
+            (Button::Select, None) if !long && self.channels.is_some() => {
+                self.editing = Some(self.sea_level_pa);
+            }
+            (Button::Select, Some(pa)) if !long => {
+                self.editing = None;
+                self.sea_level_pa = pa;
+                self.pending = Some(Action::SaveSeaLevel(pa));
+            }
+            (Button::Up, None) if !long => return Transition::Replace(self.prev),
+            (Button::Down, None) if !long => return Transition::Replace(self.next),
+            (Button::Up, Some(_)) => self.adjust(true, step),
+            (Button::Down, Some(_)) => self.adjust(false, step),
+            _ => {}
+        }
+        Transition::None
+    }
+
+    fn desired_fps(&self) -> u16 {
+        2
+    }
+
+    /// 没接传感器的话轮播跳过这一页
+    fn has_data(&self) -> bool {
+        self.channels.is_some()
+    }
+
+    fn tick(&mut self, _now_ms: u64) -> bool {
+        let generation = self.sensors.borrow().generation();
+        let changed = self.shown_generation != Some(generation);
+        self.shown_generation = Some(generation);
+        changed
+    }
+
+    fn take_action(&mut self) -> Option<Action> {
+        self.pending.take()
+    }
+
+    /// 大字版：气压和温度；调海平面气压的时候只显示正在调的值
+    fn render_large(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<bool, Infallible> {
+        let Some(channels) = self.channels else {
+            return Ok(false);
+        };
+        let sensors = self.sensors.borrow();
+        let Some(pressure) = sensors.get(channels.pressure) else {
+            return Ok(false);
+        };
+        let mut first: String<16> = String::new();
+        if let Some(sea_level_pa) = self.editing {
+            let _ = pressure
+                .info()
+                .write_value(Some(sea_level_pa as i32), &mut first);
+            draw_large_readings(canvas, &[("sea level", &first)])?;
+            return Ok(true);
+        }
+        let _ = pressure.info().write_value(pressure.value(), &mut first);
+        let mut second: String<16> = String::new();
+        if let Some(temperature) = sensors.get(channels.temperature) {
+            let _ = temperature
+                .info()
+                .write_value(temperature.value(), &mut second);
+        }
+        draw_large_readings(canvas, &[("P", &first), ("temp", &second)])?;
+        Ok(true)
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<(), Infallible> {
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        draw_centered(canvas, "barometer", TITLE_Y, style)?;
+        let Some(channels) = self.channels else {
+            return draw_centered(canvas, "no BMP280", 36, style);
+        };
+
+        let sensors = self.sensors.borrow();
+        let pressure = sensors.get(channels.pressure);
+        let temperature = sensors.get(channels.temperature);
+        let sea_level_pa = self.editing.unwrap_or(self.sea_level_pa);
+        let mut value: String<24> = String::new();
+
+        if let Some(pressure) = pressure {
+            let _ = pressure.info().write_value(pressure.value(), &mut value);
+            draw_menu_row(canvas, row_top(0), "press", &value, false)?;
+            if let Some(trend) = pressure_trend(pressure.history()) {
+                draw_trend(canvas, FIRST_ROW_TOP, trend)?;
+            }
+        }
+        if let Some(temperature) = temperature {
+            value.clear();
+            let _ = temperature
+                .info()
+                .write_value(temperature.value(), &mut value);
+            draw_menu_row(canvas, row_top(1), "temp", &value, false)?;
+        }
+
+        value.clear();
+        let altitude = pressure
+            .and_then(|p| p.value())
+            .and_then(|pa| altitude_dm(pa as u32, sea_level_pa));
+        let _ = ALTITUDE.write_value(altitude, &mut value);
+        draw_menu_row(canvas, row_top(2), "alt", &value, false)?;
+
+        // 海平面气压和气压用同一种格式(hPa)
+        value.clear();
+        if let Some(pressure) = pressure {
+            let _ = pressure
+                .info()
+                .write_value(Some(sea_level_pa as i32), &mut value);
+        }
+        draw_menu_row(canvas, row_top(3), "sea", &value, self.editing.is_some())
+    }
+}
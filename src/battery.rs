@@ -0,0 +1,83 @@
+//! 电池电量估算：VSYS 电压 -> 剩余百分比
+//!
+//! 假设 VSYS 上直接接的是**一节锂聚合物/锂离子电池**(标称 3.7V，充满 4.2V)，中间没有升压板。
+//! 放电曲线是常见单节 LiPo 在小电流(0.2C 左右)放电、静置一会儿以后的典型值，中间那段很平，
+//! 所以 30%~70% 之间差几十毫伏就差十几个百分点，只能看个大概。负载大的时候电压会被拉低，显示得比实际少。
+//!
+//! 其他情况读数没有意义：
+//!
+//! - 插着 USB 的时候 VSYS 是 5V 减二极管压降，超过 4.2V，一直显示 100%
+//! - 磷酸铁锂(3.2V 标称)、三节镍氢这些曲线完全不一样，要换 `DISCHARGE_CURVE`
+//!
+//! 曲线之间线性插值，低于第一个点算 0%，高于最后一个点算 100%。
+
+/// 放电曲线：(电压 mV, 剩余 %)，电压从低到高
+pub const DISCHARGE_CURVE: [(u16, u8); 12] = [
+    (3300, 0),
+    (3500, 5),
+    (3600, 10),
+    (3700, 20),
+    (3750, 30),
+    (3790, 40),
+    (3830, 50),
+    (3870, 60),
+    (3920, 70),
+    (3980, 80),
+    (4060, 90),
+    (4200, 100),
+];
+
+/// 按 `DISCHARGE_CURVE` 估算剩余电量，0..=100
+pub fn estimate_battery_percent(voltage_mv: u16) -> u8 {
+    let (first_mv, first_percent) = DISCHARGE_CURVE[0];
+    if voltage_mv <= first_mv {
+        return first_percent;
+    }
+    for pair in DISCHARGE_CURVE.windows(2) {
+        let ((lo_mv, lo_percent), (hi_mv, hi_percent)) = (pair[0], pair[1]);
+        if voltage_mv <= hi_mv {
+            let offset = (voltage_mv - lo_mv) as u32 * (hi_percent - lo_percent) as u32;
+            return lo_percent + (offset / (hi_mv - lo_mv) as u32) as u8;
+        }
+    }
+    DISCHARGE_CURVE[DISCHARGE_CURVE.len() - 1].1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clamps_outside_the_curve() {
+        for mv in [0, 3000, 3299, 3300] {
+            assert_eq!(estimate_battery_percent(mv), 0, "{mv} mV");
+        }
+        // 插着 USB 的时候 VSYS 在 4.7V 左右
+        for mv in [4200, 4201, 4700, u16::MAX] {
+            assert_eq!(estimate_battery_percent(mv), 100, "{mv} mV");
+        }
+    }
+
+    #[test]
+    fn exact_at_the_breakpoints() {
+        for (mv, percent) in DISCHARGE_CURVE {
+            assert_eq!(estimate_battery_percent(mv), percent, "{mv} mV");
+        }
+    }
+
+    #[test]
+    fn interpolates_between_breakpoints() {
+        // 中点，除不尽的往下取
+        assert_eq!(estimate_battery_percent(3400), 2);
+        assert_eq!(estimate_battery_percent(3550), 7);
+        assert_eq!(estimate_battery_percent(3650), 15);
+        assert_eq!(estimate_battery_percent(3725), 25);
+        assert_eq!(estimate_battery_percent(3810), 45);
+        assert_eq!(estimate_battery_percent(4130), 95);
+        // 离下一个点还差 1 mV 的时候还没到
+        assert_eq!(estimate_battery_percent(4199), 99);
+        // 电压越高电量不会越少
+        let percents: std::vec::Vec<u8> = (3200..=4300).map(estimate_battery_percent).collect();
+        assert!(percents.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+}
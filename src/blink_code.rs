@@ -0,0 +1,48 @@
+//! 板载 LED 的闪烁码：没有屏幕的时候只能靠它看出板子是什么状态
+//!
+//! 一个闪烁码就是"快闪 n 下，停一会儿"，一直重复，数闪了几下就知道是哪种情况。
+//! 这里只算某个时刻灯该亮还是该灭，引脚由 main.rs 在主循环里按结果设置(引脚见 board.rs 的 `status_led_pin!`)。
+
+/// 每一下亮多久(毫秒)
+const PULSE_ON_MS: u64 = 150;
+
+/// 每一下从亮到下一下亮隔多久(毫秒)
+const PULSE_PERIOD_MS: u64 = 400;
+
+/// 一组闪完停多久(毫秒)
+const PAUSE_MS: u64 = 1200;
+
+/// 屏幕没接上：闪 3 下
+pub const NO_DISPLAY: BlinkCode = BlinkCode::new(3);
+
+/// 带 `led` 动作的告警规则在触发：闪 1 下(屏幕掉线的时候先报屏幕)
+pub const ALARM: BlinkCode = BlinkCode::new(1);
+
+/// 闹钟在响(见 `wake_alarm`)：闪 2 下
+pub const WAKE_ALARM: BlinkCode = BlinkCode::new(2);
+
+/// 反复重启进了恢复模式(见 `boot_loop`)：闪 5 下
+pub const RECOVERY: BlinkCode = BlinkCode::new(5);
+
+/// 闪烁码
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlinkCode {
+    pulses: u8,
+}
+
+impl BlinkCode {
+    pub const fn new(pulses: u8) -> Self {
+        Self { pulses }
+    }
+
+    pub fn pulses(&self) -> u8 {
+        self.pulses
+    }
+
+    /// `now_ms` 这个时刻灯该不该亮
+    pub fn level(&self, now_ms: u64) -> bool {
+        let pulses = self.pulses as u64 * PULSE_PERIOD_MS;
+        let t = now_ms % (pulses + PAUSE_MS);
+        t < pulses && t % PULSE_PERIOD_MS < PULSE_ON_MS
+    }
+}
@@ -0,0 +1,288 @@
+//! BMP280 气压/温度传感器，和屏幕挂在同一条 I2C 总线上
+//!
+//! 上电的时候在 0x76、0x77 两个地址上找芯片，认出来以后读一遍出厂校准系数，设成连续测量模式
+//! (温度 2 倍过采样、气压 16 倍过采样、IIR 滤波 16，每 0.5 秒出一个结果)。之后只要隔一会读一次结果寄存器，
+//! 用手册 3.11.3 节的整数公式(温度 32 位、气压 64 位那一版)换算成 0.01°C 和 Pa。
+//!
+//! 总线是屏幕的 DMA 和这里轮流用的，主循环只在 DMA 空闲的时候调 `update`，所以读一次不会打断一帧。
+//! 找不到芯片 `probe` 返回 None，主循环就不注册这几个通道；运行中拔掉的话 `update` 失败，
+//! 通道按注册表的规则变成不可用。BMP180 的寄存器和公式完全不一样，认出来会打一行日志，但不支持。
+//!
+//! 海拔是按国际标准大气从气压和海平面气压算出来的，海平面气压要按当地天气预报里的数调准，不然会差几十米。
+
+use embedded_hal::i2c::I2c;
+
+/// 芯片可能的地址，SDO 接地是 0x76，接 VDDIO 是 0x77
+pub const ADDRESSES: [u8; 2] = [0x76, 0x77];
+
+/// 芯片 ID 寄存器和几种芯片的 ID。BME280 的温度、气压部分和 BMP280 一样，也能用
+const REG_CHIP_ID: u8 = 0xD0;
+const CHIP_ID_BMP280: u8 = 0x58;
+const CHIP_ID_BME280: u8 = 0x60;
+const CHIP_ID_BMP180: u8 = 0x55;
+
+/// 校准系数从 0x88 开始，一共 24 字节，全是小端
+const REG_CALIBRATION: u8 = 0x88;
+const CALIBRATION_LEN: usize = 24;
+
+/// ctrl_meas：osrs_t = x2，osrs_p = x16，连续测量模式
+const REG_CTRL_MEAS: u8 = 0xF4;
+const CTRL_MEAS: u8 = (0b010 << 5) | (0b101 << 2) | 0b11;
+
+/// config：两次测量之间等 500ms，IIR 滤波系数 16
+const REG_CONFIG: u8 = 0xF5;
+const CONFIG: u8 = (0b100 << 5) | (0b100 << 2);
+
+/// 结果寄存器：气压 3 字节、温度 3 字节，一次连着读出来，保证是同一次测量的
+const REG_DATA: u8 = 0xF7;
+
+/// 这个通道没测(过采样设成跳过或者还没测完第一次)的时候读出来是这个值
+const ADC_SKIPPED: i32 = 0x80000;
+
+/// 标准海平面气压(Pa)
+pub const DEFAULT_SEA_LEVEL_PA: u32 = 101_325;
+
+/// 海平面气压能设的范围(Pa)，有记录的最低和最高差不多就在这两头
+pub const SEA_LEVEL_MIN_PA: u32 = 85_000;
+pub const SEA_LEVEL_MAX_PA: u32 = 110_000;
+
+/// 气压趋势看最近多久
+pub const TREND_WINDOW_MS: u32 = 3 * 3600 * 1000;
+
+/// 气压趋势的窗口里变了多少 Pa 才算在升/降，天气预报一般用 3 小时 1hPa 当分界
+const TREND_THRESHOLD_PA: i32 = 100;
+
+/// 出厂校准系数，名字和手册表 17 一样
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Calibration {
+    t1: u16,
+    t2: i16,
+    t3: i16,
+    p1: u16,
+    p2: i16,
+    p3: i16,
+    p4: i16,
+    p5: i16,
+    p6: i16,
+    p7: i16,
+    p8: i16,
+    p9: i16,
+}
+
+impl Calibration {
+    /// 从 0x88..0xA0 读出来的 24 字节解出来
+    pub fn from_bytes(bytes: &[u8; CALIBRATION_LEN]) -> Self {
+        let u = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+        let s = |i: usize| i16::from_le_bytes([bytes[i], bytes[i + 1]]);
+        Self {
+            t1: u(0),
+            t2: s(2),
+            t3: s(4),
+            p1: u(6),
+            p2: s(8),
+            p3: s(10),
+            p4: s(12),
+            p5: s(14),
+            p6: s(16),
+            p7: s(18),
+            p8: s(20),
+            p9: s(22),
+        }
+    }
+
+    /// 温度原始值 -> (0.01°C, t_fine)，t_fine 算气压的时候要用
+    pub fn compensate_temperature(&self, adc_t: i32) -> (i32, i32) {
+        let t1 = self.t1 as i32;
+        let var1 = (((adc_t >> 3) - (t1 << 1)) * self.t2 as i32) >> 11;
+        let var2 = (((((adc_t >> 4) - t1) * ((adc_t >> 4) - t1)) >> 12) * self.t3 as i32) >> 14;
+        let t_fine = var1 + var2;
+        ((t_fine * 5 + 128) >> 8, t_fine)
+    }
+
+    /// 气压原始值 -> Pa。校准系数不对(比如读到的全是 0)的时候返回 None，免得除以 0
+    pub fn compensate_pressure(&self, adc_p: i32, t_fine: i32) -> Option<u32> {
+        let mut var1 = t_fine as i64 - 128_000;
+        let mut var2 = var1 * var1 * self.p6 as i64;
+        var2 += (var1 * self.p5 as i64) << 17;
+        var2 += (self.p4 as i64) << 35;
+        var1 = ((var1 * var1 * self.p3 as i64) >> 8) + ((var1 * self.p2 as i64) << 12);
+        var1 = (((1i64 << 47) + var1) * self.p1 as i64) >> 33;
+        if var1 == 0 {
+            return None;
+        }
+        let mut p = 1_048_576 - adc_p as i64;
+        p = (((p << 31) - var2) * 3125) / var1;
+        let var1 = (self.p9 as i64 * (p >> 13) * (p >> 13)) >> 25;
+        let var2 = (self.p8 as i64 * p) >> 19;
+        // 算出来是 Q24.8 格式的 Pa，小数部分不要了
+        let q24_8 = ((p + var1 + var2) >> 8) + ((self.p7 as i64) << 4);
+        u32::try_from(q24_8 >> 8).ok()
+    }
+}
+
+/// 一次测量的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Measurement {
+    /// 温度，单位 0.01°C
+    pub temp_centi: i32,
+    /// 气压，单位 Pa
+    pub pressure_pa: u32,
+}
+
+/// 找到的 BMP280 和它最近一次的读数
+#[derive(Debug, Clone, Copy)]
+pub struct Bmp280 {
+    address: u8,
+    calibration: Calibration,
+    latest: Option<Measurement>,
+    sea_level_pa: u32,
+}
+
+impl Bmp280 {
+    /// 在 `ADDRESSES` 上找芯片，找到了就读校准系数、开始连续测量。哪都没找到返回 None
+    pub fn probe<I: I2c>(i2c: &mut I, sea_level_pa: u32) -> Option<Self> {
+        ADDRESSES
+            .iter()
+            .find_map(|&address| Self::init(i2c, address, sea_level_pa))
+    }
+
+    fn init<I: I2c>(i2c: &mut I, address: u8, sea_level_pa: u32) -> Option<Self> {
+        let mut id = [0u8];
+        i2c.write_read(address, &[REG_CHIP_ID], &mut id).ok()?;
+        match id[0] {
+            CHIP_ID_BMP280 | CHIP_ID_BME280 => {}
+            CHIP_ID_BMP180 => {
+                defmt::warn!("BMP180 at {=u8:#x} is not supported", address);
+                return None;
+            }
+            other => {
+                defmt::debug!("unknown chip id {=u8:#x} at {=u8:#x}", other, address);
+                return None;
+            }
+        }
+
+        let mut bytes = [0u8; CALIBRATION_LEN];
+        i2c.write_read(address, &[REG_CALIBRATION], &mut bytes)
+            .ok()?;
+        // config 要在休眠模式下写才一定生效，所以先写 config 再写 ctrl_meas 切到连续测量
+        i2c.write(address, &[REG_CONFIG, CONFIG]).ok()?;
+        i2c.write(address, &[REG_CTRL_MEAS, CTRL_MEAS]).ok()?;
+        defmt::info!("BMP280 found at {=u8:#x}", address);
+        Some(Self {
+            address,
+            calibration: Calibration::from_bytes(&bytes),
+            latest: None,
+            sea_level_pa,
+        })
+    }
+
+    /// 读一次最新的测量结果，返回成功没有。失败的话之前的读数也作废
+    pub fn update<I: I2c>(&mut self, i2c: &mut I) -> bool {
+        let mut data = [0u8; 6];
+        self.latest = i2c
+            .write_read(self.address, &[REG_DATA], &mut data)
+            .ok()
+            .and_then(|()| self.convert(&data));
+        self.latest.is_some()
+    }
+
+    fn convert(&self, data: &[u8; 6]) -> Option<Measurement> {
+        let raw = |i: usize| {
+            ((data[i] as i32) << 12) | ((data[i + 1] as i32) << 4) | (data[i + 2] as i32 >> 4)
+        };
+        let (adc_p, adc_t) = (raw(0), raw(3));
+        if adc_t == ADC_SKIPPED || adc_p == ADC_SKIPPED {
+            return None;
+        }
+        let (temp_centi, t_fine) = self.calibration.compensate_temperature(adc_t);
+        let pressure_pa = self.calibration.compensate_pressure(adc_p, t_fine)?;
+        Some(Measurement {
+            temp_centi,
+            pressure_pa,
+        })
+    }
+
+    pub fn latest(&self) -> Option<Measurement> {
+        self.latest
+    }
+
+    /// 温度，单位 0.01°C
+    pub fn temp_centi(&self) -> Option<i32> {
+        self.latest.map(|m| m.temp_centi)
+    }
+
+    /// 气压，单位 Pa
+    pub fn pressure_pa(&self) -> Option<i32> {
+        self.latest.map(|m| m.pressure_pa as i32)
+    }
+
+    /// 按现在设的海平面气压算出来的海拔，单位 0.1m
+    pub fn altitude_dm(&self) -> Option<i32> {
+        self.latest
+            .and_then(|m| altitude_dm(m.pressure_pa, self.sea_level_pa))
+    }
+
+    pub fn sea_level_pa(&self) -> u32 {
+        self.sea_level_pa
+    }
+
+    pub fn set_sea_level(&mut self, pa: u32) {
+        self.sea_level_pa = pa;
+    }
+}
+
+/// 国际标准大气的海拔公式 `h = 44330 * (1 - (p / p0) ^ (1 / 5.255))`，单位 0.1m。
+/// core 里没有 `powf`，这里用 `exp(ln(x) / 5.255)` 的级数展开，海拔十公里以内误差不到一分米
+pub fn altitude_dm(pressure_pa: u32, sea_level_pa: u32) -> Option<i32> {
+    if pressure_pa == 0 || sea_level_pa == 0 {
+        return None;
+    }
+    let ratio = pressure_pa as f32 / sea_level_pa as f32;
+    let scaled = exp(ln(ratio) / 5.255);
+    Some((443_300.0 * (1.0 - scaled)) as i32)
+}
+
+/// ln(x) = 2 * atanh((x - 1) / (x + 1))，x 在 0.2..2 之间的时候几项就够了
+fn ln(x: f32) -> f32 {
+    let z = (x - 1.0) / (x + 1.0);
+    let z2 = z * z;
+    let mut term = z;
+    let mut sum = 0.0;
+    for k in 0..8 {
+        sum += term / (2 * k + 1) as f32;
+        term *= z2;
+    }
+    2.0 * sum
+}
+
+/// exp(x) 的泰勒展开，这里 x 的绝对值不会超过 0.3
+fn exp(x: f32) -> f32 {
+    let mut term = 1.0;
+    let mut sum = 1.0;
+    for k in 1..8 {
+        term *= x / k as f32;
+        sum += term;
+    }
+    sum
+}
+
+/// 气压趋势
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Trend {
+    Rising,
+    Falling,
+    Steady,
+}
+
+/// 从最老到最新的气压历史(Pa)算趋势，历史一般要盖住 `TREND_WINDOW_MS`，刚开机不够长就用有多少算多少。
+/// 少于两个点返回 None
+pub fn pressure_trend(history: impl Iterator<Item = i32>) -> Option<Trend> {
+    let mut history = history;
+    let oldest = history.next()?;
+    let newest = history.last()?;
+    Some(match newest - oldest {
+        delta if delta >= TREND_THRESHOLD_PA => Trend::Rising,
+        delta if delta <= -TREND_THRESHOLD_PA => Trend::Falling,
+        _ => Trend::Steady,
+    })
+}
@@ -0,0 +1,303 @@
+//! 板子相关的配置：哪个功能接在哪个引脚上
+//!
+//! 换板子或者改接线的时候只改这个文件。rp2040-hal 里每个引脚都是一个单独的类型，没法写成常量，
+//! 所以这里用宏从 `Pins` 里把引脚取出来。同一个引脚被取两次编译器会报 "use of moved value"，
+//! 所以这里配的引脚不可能和屏幕的 I2C 引脚撞车，改错了编译都过不了。
+//!
+//! 不同型号的板子(Pico、Pico W、自己画的板子)用 Cargo feature 选，见 boards.rs。
+//! 这里和型号有关的宏按同样的 feature 取引脚，数字要和 `boards::BOARD` 对得上。
+
+use rp2040_hal::gpio::{
+    bank0, Function, OutputDriveStrength, OutputSlewRate, Pin, PinId, PullType,
+};
+use rp2040_hal::pac;
+
+use crate::boot_mode::BootMode;
+
+/// 屏幕用的 I2C 块和引脚的类型。改了这里，下面的 `oled_i2c!` 要跟着改
+#[cfg(not(feature = "board-custom"))]
+pub type OledI2cBlock = pac::I2C0;
+#[cfg(not(feature = "board-custom"))]
+pub type OledSda = bank0::Gpio4;
+#[cfg(not(feature = "board-custom"))]
+pub type OledScl = bank0::Gpio5;
+#[cfg(feature = "board-custom")]
+pub type OledI2cBlock = pac::I2C1;
+#[cfg(feature = "board-custom")]
+pub type OledSda = bank0::Gpio6;
+#[cfg(feature = "board-custom")]
+pub type OledScl = bank0::Gpio7;
+
+/// 屏幕 I2C 引脚的上拉：默认开片内上拉，打开 `external-i2c-pullups` 就不开，全靠板子上的上拉电阻
+///
+/// 片内上拉只有 50~80kΩ，总线电容大一点(线长、接了好几个设备)上升沿就慢得不像样，400kHz 下经常读写失败：
+/// 面包板上好好的，换了 PCB 或者长杜邦线就不行。正经做法是 SDA、SCL 各接一个 2.2k~4.7kΩ 上拉到 3.3V，
+/// 再打开这个 feature。不要两个都有也不要两个都没有：都没有的话总线一直是低，什么设备都扫不到
+#[cfg(not(feature = "external-i2c-pullups"))]
+pub type OledI2cPull = rp2040_hal::gpio::PullUp;
+#[cfg(feature = "external-i2c-pullups")]
+pub type OledI2cPull = rp2040_hal::gpio::PullNone;
+
+/// 屏幕 I2C 是不是靠片内上拉
+pub const I2C_INTERNAL_PULLUPS: bool = cfg!(not(feature = "external-i2c-pullups"));
+
+/// 靠片内上拉的时候总线超过这个频率开机打警告
+pub const INTERNAL_PULLUP_MAX_HZ: u32 = 100_000;
+
+/// I2C 引脚的焊盘设置，不靠上电默认值：打开施密特触发(慢上升沿不会被读成好几个边沿)，
+/// 压摆率调慢(下降沿少点过冲和振铃)，驱动 4mA(I2C 是开漏，只管往下拉，够拉低 2.2kΩ 上拉了)
+pub fn configure_i2c_pad<I: PinId, F: Function, P: PullType>(pin: &mut Pin<I, F, P>) {
+    pin.set_schmitt_enabled(true);
+    pin.set_slew_rate(OutputSlewRate::Slow);
+    pin.set_drive_strength(OutputDriveStrength::FourMilliAmps);
+}
+
+/// 屏幕的 I2C：返回 (I2C 块, SDA, SCL)，引脚配成 I2C 功能，上拉按 `OledI2cPull`。Pico 是 I2C0(GP4/GP5)
+#[cfg(not(feature = "board-custom"))]
+#[macro_export]
+macro_rules! oled_i2c {
+    ($pac:ident, $pins:ident) => {
+        (
+            $pac.I2C0,
+            $pins
+                .gpio4
+                .reconfigure::<rp2040_hal::gpio::FunctionI2C, $crate::board::OledI2cPull>(),
+            $pins
+                .gpio5
+                .reconfigure::<rp2040_hal::gpio::FunctionI2C, $crate::board::OledI2cPull>(),
+        )
+    };
+}
+
+/// 自己画的板子：I2C1(GP6/GP7)
+#[cfg(feature = "board-custom")]
+#[macro_export]
+macro_rules! oled_i2c {
+    ($pac:ident, $pins:ident) => {
+        (
+            $pac.I2C1,
+            $pins
+                .gpio6
+                .reconfigure::<rp2040_hal::gpio::FunctionI2C, $crate::board::OledI2cPull>(),
+            $pins
+                .gpio7
+                .reconfigure::<rp2040_hal::gpio::FunctionI2C, $crate::board::OledI2cPull>(),
+        )
+    };
+}
+
+/// 启动模式跳线：返回一个数组，每个元素是配置成上拉输入的引脚，跳线把引脚接地就算"插上"
+///
+/// 默认两根：GP22 是副屏模式，GP21 是产线测试模式(见 fixture.rs)。Pico 上这两根旁边都是 GND，一个跳线帽就能短接，
+/// 测试架上用一根顶针把 GP21 接地就行。要再加的话往数组里加一个，然后改下面的 `boot_mode_for`
+#[macro_export]
+macro_rules! boot_strap_pins {
+    ($pins:ident) => {
+        [
+            $pins.gpio22.into_pull_up_input().into_dyn_pin(),
+            $pins.gpio21.into_pull_up_input().into_dyn_pin(),
+        ]
+    };
+}
+
+/// 产线测试的结果引脚，默认 GP17：全过一直是高电平，没过高低交替(见 fixture.rs)。没进测试模式的时候不碰
+#[macro_export]
+macro_rules! fixture_result_pin {
+    ($pins:ident) => {
+        $pins.gpio17.into_push_pull_output().into_dyn_pin()
+    };
+}
+
+/// 产线测试的时候一定要在的 I2C 传感器，可以写 "baro"、"humidity"、"distance"、"rtc"，缺了哪个哪项就不过。
+/// 默认一个都不要求，只有屏幕必须在
+pub const FIXTURE_SENSORS: &[&str] = &[];
+
+/// 按键：返回 (按键, 引脚) 的数组，引脚配成上拉输入，按键另一端接地
+///
+/// 默认接在 GP12..GP15，没接按键的引脚被上拉住，读出来一直是"松开"，不影响使用
+#[macro_export]
+macro_rules! button_pins {
+    ($pins:ident) => {
+        [
+            (
+                $crate::input::Button::Up,
+                $pins.gpio12.into_pull_up_input().into_dyn_pin(),
+            ),
+            (
+                $crate::input::Button::Down,
+                $pins.gpio13.into_pull_up_input().into_dyn_pin(),
+            ),
+            (
+                $crate::input::Button::Select,
+                $pins.gpio14.into_pull_up_input().into_dyn_pin(),
+            ),
+            (
+                $crate::input::Button::Back,
+                $pins.gpio15.into_pull_up_input().into_dyn_pin(),
+            ),
+        ]
+    };
+}
+
+/// 输出控制菜单里的引脚：返回 (名字, 引脚) 的数组，引脚配成推挽输出，上电是低电平
+///
+/// 默认 GP6..GP9，这几个脚在 Pico 左边一排，紧挨着屏幕的 I2C 引脚，接线方便。
+/// 名字就是菜单里显示的文字，改成实际接的东西就行(比如 "fan")
+#[cfg(not(feature = "board-custom"))]
+#[macro_export]
+macro_rules! output_pins {
+    ($pins:ident) => {
+        [
+            (
+                "relay 1",
+                $pins.gpio6.into_push_pull_output().into_dyn_pin(),
+            ),
+            (
+                "relay 2",
+                $pins.gpio7.into_push_pull_output().into_dyn_pin(),
+            ),
+            ("led 1", $pins.gpio8.into_push_pull_output().into_dyn_pin()),
+            ("led 2", $pins.gpio9.into_push_pull_output().into_dyn_pin()),
+        ]
+    };
+}
+
+/// 自己画的板子上 GP6/GP7 给了屏幕，输出挪到 GP8..GP11
+#[cfg(feature = "board-custom")]
+#[macro_export]
+macro_rules! output_pins {
+    ($pins:ident) => {
+        [
+            (
+                "relay 1",
+                $pins.gpio8.into_push_pull_output().into_dyn_pin(),
+            ),
+            (
+                "relay 2",
+                $pins.gpio9.into_push_pull_output().into_dyn_pin(),
+            ),
+            ("led 1", $pins.gpio10.into_push_pull_output().into_dyn_pin()),
+            ("led 2", $pins.gpio11.into_push_pull_output().into_dyn_pin()),
+        ]
+    };
+}
+
+/// 板载 LED：返回 `Option`，Pico 上固定是 GP25，配成推挽输出。没接屏幕的时候用它闪错误码(见 blink_code.rs)
+#[cfg(any(not(feature = "board-pico-w"), feature = "board-custom"))]
+#[macro_export]
+macro_rules! status_led_pin {
+    ($pins:ident) => {
+        Some($pins.gpio25.into_push_pull_output().into_dyn_pin())
+    };
+}
+
+/// Pico W 的板载 LED 接在无线芯片上，GP25 是无线芯片的片选，不能动。打开 `pico-w` 的话起无线芯片的驱动去点它，
+/// GP23(电源)、GP25(片选)、GP24(数据)给驱动，时钟 GP29 和 VSYS 共用(见 wireless.rs)。起不来是 None
+#[cfg(all(feature = "pico-w", not(feature = "board-custom")))]
+#[macro_export]
+macro_rules! status_led_pin {
+    ($pins:ident) => {
+        $crate::wireless::Cyw43Led::start(
+            $pins.gpio23.into_push_pull_output().into_dyn_pin(),
+            $pins.gpio25.into_push_pull_output().into_dyn_pin(),
+            $pins.gpio24.into_push_pull_output().into_dyn_pin(),
+        )
+    };
+}
+
+/// 只开 `board-pico-w` 不开 `pico-w`：不带无线芯片的驱动，没有状态灯
+#[cfg(all(feature = "board-pico-w", not(feature = "pico-w"), not(feature = "board-custom")))]
+#[macro_export]
+macro_rules! status_led_pin {
+    ($pins:ident) => {
+        None::<$crate::status_led::BoardLed>
+    };
+}
+
+/// WS2812 告警灯带有几颗灯珠
+#[cfg(feature = "ws2812")]
+pub const LED_STRIP_LEN: usize = 8;
+
+/// WS2812 灯带的数据引脚，默认 GP16。引脚功能由驱动切换成 PIO，这里只要把引脚取出来
+///
+/// 灯带用 5V 供电的话，3.3V 的数据线大多数时候也能用，不稳定就在中间加一个电平转换
+#[cfg(feature = "ws2812")]
+#[macro_export]
+macro_rules! led_strip_pin {
+    ($pins:ident) => {
+        $pins.gpio16
+    };
+}
+
+/// 无源蜂鸣器：默认接在 GP18，对应 PWM1 的 A 通道。返回配好输出引脚的 PWM slice
+///
+/// 换引脚的时候 slice 和通道要跟着换：GPn 对应 PWM(n/2 % 8)，n 是偶数用 A 通道，奇数用 B 通道
+#[macro_export]
+macro_rules! buzzer_pwm {
+    ($slices:ident, $pins:ident) => {{
+        let mut slice = $slices.pwm1;
+        slice.channel_a.output_to($pins.gpio18);
+        slice
+    }};
+}
+
+/// 舵机信号线：默认接在 GP20，对应 PWM2 的 A 通道。返回配好输出引脚的 PWM slice
+///
+/// 舵机只用 A 通道(`servo::ServoOutput`)，换引脚的时候要换成偶数号的 GPIO，slice 跟着换
+#[macro_export]
+macro_rules! servo_pwm {
+    ($slices:ident, $pins:ident) => {{
+        let mut slice = $slices.pwm2;
+        slice.channel_a.output_to($pins.gpio20);
+        slice
+    }};
+}
+
+/// 脉冲计数的输入：默认 GP19，配成上拉输入，传感器把它拉到地算一个脉冲(见 pulse_counter.rs)
+///
+/// 边沿中断是 `pulse_counter::start` 按 GPIO 号直接配的，换引脚只改这里和 boards.rs 里的 `SHARED_PINS`
+#[macro_export]
+macro_rules! pulse_input_pin {
+    ($pins:ident) => {
+        $pins.gpio19.into_pull_up_input()
+    };
+}
+
+/// 数据记录用的外部 ADC 引脚，默认 GP26(ADC0)。不接东西的话读数是悬空的，只看温度就行
+#[macro_export]
+macro_rules! sampler_adc_pin {
+    ($pins:ident) => {
+        $crate::sampler::AdcPin::new($pins.gpio26.into_floating_input()).unwrap()
+    };
+}
+
+/// 测 VSYS 的引脚：Pico 上固定是 GP29(ADC3)，板子上已经做好了 3:1 分压
+#[macro_export]
+macro_rules! vsys_adc_pin {
+    ($pins:ident) => {
+        $crate::sampler::AdcPin::new($pins.gpio29.into_floating_input()).unwrap()
+    };
+}
+
+/// I2C 线诊断的 SCL 分压点：默认 GP27(ADC1)，接法见 i2c_lines.rs
+#[macro_export]
+macro_rules! scl_tap_adc_pin {
+    ($pins:ident) => {
+        $crate::sampler::AdcPin::new($pins.gpio27.into_floating_input()).unwrap()
+    };
+}
+
+/// `scl_tap_adc_pin!` 取出来的引脚类型，要放进结构体里所以写出来
+pub type SclTapPin = crate::sampler::AdcPin<
+    Pin<bank0::Gpio27, rp2040_hal::gpio::FunctionSioInput, rp2040_hal::gpio::PullNone>,
+>;
+
+/// 跳线组合对应的启动模式。`jumpers` 的第 i 位是 1 表示第 i 根跳线插上了(引脚被拉低)
+pub fn boot_mode_for(jumpers: u8) -> BootMode {
+    match jumpers {
+        0b1 => BootMode::SmartDisplay,
+        // 测试架只管 GP21，GP22 插没插都进测试模式
+        0b10 | 0b11 => BootMode::Fixture,
+        _ => BootMode::Dashboard,
+    }
+}
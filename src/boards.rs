@@ -0,0 +1,134 @@
+//! 板子型号：晶振频率、板载 LED、屏幕的 I2C 块和引脚、按键和输出引脚
+//!
+//! 同一份代码要跑在几种板子上，用 Cargo feature 选，不用改源码：
+//!
+//! - 不开 feature：树莓派 Pico，12MHz 晶振，LED 在 GP25，屏幕在 I2C0(GP4/GP5)
+//! - `board-pico-w`：Pico W，晶振和引脚都和 Pico 一样，但是板载 LED 接在无线芯片上，GP25 是无线芯片的片选，不能当 LED 用。
+//!   板载 LED 要打开 `pico-w` 走无线芯片去点(见 wireless.rs)，所以这里的 `status_led` 是 None
+//! - `board-custom`：自己画的 RP2040 板子，16MHz 晶振，屏幕在 I2C1(GP6/GP7)，输出控制挪到 GP8..GP11
+//!
+//! 同时开了几个(比如 `--all-features`)按 `board-custom`、`board-pico-w` 的顺序取第一个。
+//!
+//! 这里的数字是给人看、给编译期检查用的；rp2040-hal 里每个引脚是一个类型，真正取引脚的是 board.rs 里的宏，
+//! 它们按同样的 feature 选引脚，main.rs 启动的时候再核对一遍两边是不是一致。
+//!
+//! 编译期检查两件事，不对就编译失败并说明原因：
+//!
+//! - 屏幕的 SDA/SCL 在选的 I2C 块上能不能用(RP2040 上 GPn 属于 I2C((n/2)%2)，n 是偶数是 SDA，奇数是 SCL)
+//! - 配的引脚有没有撞车，包括所有板子共用的跳线、蜂鸣器、灯带、舵机、ADC、脉冲计数引脚
+//!
+//! 16MHz 晶振的时候系统时钟 PLL 凑不出整 125MHz，`init_clocks_and_plls` 会配成 124MHz，实际频率从 `clocks` 里取，不影响 I2C、USB。
+
+/// 一种板子的配置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoardConfig {
+    pub name: &'static str,
+    /// 外部晶振频率(Hz)，传给 `init_clocks_and_plls`
+    pub xosc_hz: u32,
+    /// 板载 LED 的 GPIO，没有(或者不在 GPIO 上)是 None
+    pub status_led: Option<u8>,
+    /// 屏幕用哪个 I2C 块，0 或者 1
+    pub i2c_block: u8,
+    pub i2c_sda: u8,
+    pub i2c_scl: u8,
+    /// Up、Down、Select、Back 四个按键
+    pub buttons: [u8; 4],
+    /// 输出控制菜单的四路输出
+    pub outputs: [u8; 4],
+}
+
+/// 所有板子都一样的引脚：启动跳线 GP22 和 GP21、蜂鸣器 GP18、灯带 GP16、ADC0 GP26、VSYS GP29、舵机 GP20、SCL 分压点 GP27、
+/// 脉冲计数 GP19、产线测试结果 GP17
+pub const SHARED_PINS: [u8; 10] = [22, 21, 18, 16, 26, 29, 20, 27, 19, 17];
+
+pub const PICO: BoardConfig = BoardConfig {
+    name: "pico",
+    xosc_hz: 12_000_000,
+    status_led: Some(25),
+    i2c_block: 0,
+    i2c_sda: 4,
+    i2c_scl: 5,
+    buttons: [12, 13, 14, 15],
+    outputs: [6, 7, 8, 9],
+};
+
+pub const PICO_W: BoardConfig = BoardConfig {
+    name: "pico-w",
+    status_led: None,
+    ..PICO
+};
+
+pub const CUSTOM: BoardConfig = BoardConfig {
+    name: "custom",
+    xosc_hz: 16_000_000,
+    status_led: Some(25),
+    i2c_block: 1,
+    i2c_sda: 6,
+    i2c_scl: 7,
+    buttons: [12, 13, 14, 15],
+    outputs: [8, 9, 10, 11],
+};
+
+/// 这次编译选的板子
+#[cfg(feature = "board-custom")]
+pub const BOARD: BoardConfig = CUSTOM;
+#[cfg(all(feature = "board-pico-w", not(feature = "board-custom")))]
+pub const BOARD: BoardConfig = PICO_W;
+#[cfg(not(any(feature = "board-pico-w", feature = "board-custom")))]
+pub const BOARD: BoardConfig = PICO;
+
+const _: () = assert!(
+    i2c_pin_valid(BOARD.i2c_block, BOARD.i2c_sda, true),
+    "display SDA pin is not an SDA pin of the selected I2C block (GPn: block (n/2)%2, SDA if n is even)"
+);
+const _: () = assert!(
+    i2c_pin_valid(BOARD.i2c_block, BOARD.i2c_scl, false),
+    "display SCL pin is not an SCL pin of the selected I2C block (GPn: block (n/2)%2, SCL if n is odd)"
+);
+const _: () = assert!(
+    pins_distinct(&BOARD),
+    "two functions in the board configuration share the same GPIO"
+);
+
+/// `gpio` 能不能当 `block` 的 SDA(`sda` 是 true)或者 SCL
+pub const fn i2c_pin_valid(block: u8, gpio: u8, sda: bool) -> bool {
+    gpio < 30 && (gpio / 2) % 2 == block && gpio.is_multiple_of(2) == sda
+}
+
+/// 配置里用到的引脚加上共用引脚，有没有两个是同一个
+pub const fn pins_distinct(board: &BoardConfig) -> bool {
+    let mut pins = [0u8; 24];
+    let mut len = 0;
+    let mut i = 0;
+    while i < SHARED_PINS.len() {
+        pins[len] = SHARED_PINS[i];
+        len += 1;
+        i += 1;
+    }
+    let mut i = 0;
+    while i < 4 {
+        pins[len] = board.buttons[i];
+        pins[len + 1] = board.outputs[i];
+        len += 2;
+        i += 1;
+    }
+    pins[len] = board.i2c_sda;
+    pins[len + 1] = board.i2c_scl;
+    len += 2;
+    if let Some(led) = board.status_led {
+        pins[len] = led;
+        len += 1;
+    }
+    let mut i = 0;
+    while i < len {
+        let mut j = i + 1;
+        while j < len {
+            if pins[i] == pins[j] {
+                return false;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    true
+}
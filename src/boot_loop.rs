@@ -0,0 +1,88 @@
+//! 反复重启检测：开机一早在看门狗的 scratch 寄存器里记一次"又启动了"，稳定跑够 `HEALTHY_AFTER_MS` 就清零
+//!
+//! 刚开机就出错(比如存的某个设置把程序带崩了)的话，板子会一直重启，外面看不出发生了什么。
+//! 计数超过 `MAX_BOOTS` 就进恢复模式(见 main.rs 的 `recovery_mode`)：不读 flash 里的设置，
+//! 屏幕上显示上次复位的原因和启动了几次，串口只认 `SETTINGS RESET`(恢复默认设置、计数清零、重启)，
+//! 板载 LED 闪 `blink_code::RECOVERY`。断电再上电 scratch 寄存器会清零，也就回到正常启动了。
+//!
+//! 计数要靠看门狗把板子复位才加得上去：main.rs 时钟一配好就启动看门狗，panic 以后画完死机画面停在 HardFault 里，
+//! 没人喂了就复位。是不是恢复模式在屏幕初始化、读设置之前就决定了，这两步出错也能进恢复模式。
+//!
+//! 只用 `SCRATCH0`：bootrom 的看门狗重启(`watchdog_reboot` 跳到指定地址那种)用的是 SCRATCH4-7，不会打架。
+//! 高 16 位是 `MAGIC`，对不上(上电复位以后是 0、或者被别的东西写过)就当成 0 次。
+
+use core::fmt::Write;
+
+use embedded_graphics::geometry::Point;
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::text::Text;
+use embedded_graphics::Drawable;
+use heapless::String;
+use rp2040_hal::watchdog::{ScratchRegister, Watchdog};
+
+use crate::display::BufferedDisplay;
+use crate::widgets::draw_panel;
+
+/// 计数放在哪个 scratch 寄存器
+const SCRATCH: ScratchRegister = ScratchRegister::Scratch0;
+
+/// 高 16 位的标记
+const MAGIC: u32 = 0xB007;
+
+/// 连着启动超过这么多次还没稳定下来就进恢复模式
+pub const MAX_BOOTS: u16 = 3;
+
+/// 开机后稳定跑了这么久(毫秒)算正常，计数清零
+pub const HEALTHY_AFTER_MS: u64 = 30_000;
+
+/// scratch 里存的值是连着启动了几次
+pub fn decode(scratch: u32) -> u16 {
+    if scratch >> 16 == MAGIC {
+        scratch as u16
+    } else {
+        0
+    }
+}
+
+pub fn encode(boots: u16) -> u32 {
+    MAGIC << 16 | boots as u32
+}
+
+/// 这一次算不算反复重启
+pub fn is_boot_loop(boots: u16) -> bool {
+    boots > MAX_BOOTS
+}
+
+/// 开机尽早调用：计数加一，返回算上这一次连着启动了几次
+pub fn record_boot(watchdog: &mut Watchdog) -> u16 {
+    let boots = decode(watchdog.read_scratch(SCRATCH)).saturating_add(1);
+    watchdog.write_scratch(SCRATCH, encode(boots));
+    boots
+}
+
+/// 稳定下来了，计数清零
+pub fn mark_healthy(watchdog: &mut Watchdog) {
+    watchdog.write_scratch(SCRATCH, encode(0));
+}
+
+/// 恢复模式的画面，画完立刻 flush：标题栏 "RECOVERY"，下面是复位原因、第几次启动和怎么出去
+pub fn draw_recovery_screen<D: BufferedDisplay>(
+    display: &mut D,
+    reset_cause: &str,
+    boots: u16,
+) -> Result<(), D::Error> {
+    display.clear_buffer();
+    let area = display.bounding_box();
+    let content = draw_panel(display, area, "RECOVERY")?;
+    let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+    let mut status: String<24> = String::new();
+    let _ = write!(status, "reset {}, boot {}", reset_cause, boots);
+    // 32 行的屏只放得下这两行
+    for (row, line) in [status.as_str(), "send SETTINGS RESET"].iter().enumerate() {
+        let y = content.top_left.y + 9 + row as i32 * 10;
+        Text::new(line, Point::new(content.top_left.x + 2, y), style).draw(display)?;
+    }
+    display.flush()
+}
@@ -0,0 +1,92 @@
+//! 启动模式：上电时读跳线，决定这次跑哪种模式
+//!
+//! 同一个固件可以当"电脑副屏"用，也可以脱离电脑自己跑仪表盘，插不插跳线帽决定。
+//! 跳线只在上电那一刻读一次，运行中拔插没有效果，要按复位键重新读。
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::InputPin;
+use rp2040_hal::gpio::{DynPinId, FunctionSioInput, Pin, PullUp};
+
+use crate::board::boot_mode_for;
+
+/// 跳线引脚，统一成动态引脚类型，这样几根引脚可以放进一个数组
+pub type StrapPin = Pin<DynPinId, FunctionSioInput, PullUp>;
+
+/// 连续多少次读到一样的结果才算稳定
+const STABLE_SAMPLES: u32 = 5;
+
+/// 两次采样之间的间隔(毫秒)
+const SAMPLE_INTERVAL_MS: u32 = 2;
+
+/// 最多采样多少次。一直抖个不停(比如跳线接触不良)就用最后一次的结果
+const MAX_SAMPLES: u32 = 50;
+
+/// 启动模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum BootMode {
+    /// 脱离电脑运行，显示本地的仪表盘
+    Dashboard,
+    /// 电脑副屏：USB 串口命令行 + HID 电脑状态
+    SmartDisplay,
+    /// 产线测试：自动跑一遍自检，结果从引脚、串口、屏幕报出来(见 fixture.rs)
+    Fixture,
+}
+
+impl BootMode {
+    /// 开机横幅上显示的名字
+    pub fn name(self) -> &'static str {
+        match self {
+            BootMode::Dashboard => "dashboard",
+            BootMode::SmartDisplay => "smart display",
+            BootMode::Fixture => "fixture",
+        }
+    }
+}
+
+/// 跳线读取器
+pub struct BootStrap<const N: usize> {
+    pins: [StrapPin; N],
+}
+
+impl<const N: usize> BootStrap<N> {
+    /// 引脚用 `boot_strap_pins!` 取，`Pins::new` 之后尽早调用，给上拉电阻留点时间把电平拉稳
+    pub fn new(pins: [StrapPin; N]) -> Self {
+        Self { pins }
+    }
+
+    /// 读一次所有跳线，第 i 位是 1 表示第 i 根引脚是低电平(跳线插上了)
+    fn sample(&mut self) -> u8 {
+        let mut jumpers = 0;
+        for (i, pin) in self.pins.iter_mut().enumerate() {
+            if pin.is_low().unwrap_or(false) {
+                jumpers |= 1 << i;
+            }
+        }
+        jumpers
+    }
+
+    /// 消抖：连续 `STABLE_SAMPLES` 次读数一样才采用
+    pub fn read<T: DelayNs>(&mut self, timer: &mut T) -> u8 {
+        let mut last = self.sample();
+        let mut stable = 1;
+        for _ in 1..MAX_SAMPLES {
+            if stable >= STABLE_SAMPLES {
+                break;
+            }
+            timer.delay_ms(SAMPLE_INTERVAL_MS);
+            let now = self.sample();
+            if now == last {
+                stable += 1;
+            } else {
+                last = now;
+                stable = 1;
+            }
+        }
+        last
+    }
+
+    /// 读跳线并换算成启动模式
+    pub fn detect<T: DelayNs>(&mut self, timer: &mut T) -> BootMode {
+        boot_mode_for(self.read(timer))
+    }
+}
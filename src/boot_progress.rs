@@ -0,0 +1,77 @@
+//! 开机进度：初始化分好几步，慢的几步(打开数据记录要扫一遍 flash)加起来要好一会，
+//! 屏幕一直停在横幅上看起来像死机了，所以每做完一步就把进度条往前推一格
+//!
+//! 一共几步在 `new` 的时候定，每做完一步调一次 `advance`，传这一步的名字，立刻画出来并 flush。
+//! 画面是固件名、进度条、刚做完的那一步的名字。
+
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::primitives::Rectangle;
+
+use crate::banner::FIRMWARE_NAME;
+use crate::display::BufferedDisplay;
+use crate::text::draw_centered;
+use crate::widgets::draw_progress_bar;
+
+/// 固件名所在行的基线
+const NAME_Y: i32 = 20;
+
+/// 进度条的位置和大小
+const BAR_TOP: i32 = 30;
+const BAR_MARGIN: i32 = 8;
+const BAR_HEIGHT: u32 = 9;
+
+/// 步骤名所在行的基线
+const STAGE_Y: i32 = 54;
+
+/// 开机进度
+#[derive(Debug, Clone, Copy)]
+pub struct BootProgress {
+    /// 一共几步
+    stages: u8,
+    /// 做完了几步
+    done: u8,
+}
+
+impl BootProgress {
+    /// 一共 `stages` 步(0 按 1 算)
+    pub const fn new(stages: u8) -> Self {
+        Self {
+            stages: if stages == 0 { 1 } else { stages },
+            done: 0,
+        }
+    }
+
+    /// 做完了百分之几
+    pub fn percent(&self) -> u8 {
+        (self.done.min(self.stages) as u32 * 100 / self.stages as u32) as u8
+    }
+
+    /// 做完了一步，`name` 是这一步的名字。多调了也不会超过 100%
+    pub fn advance<D: BufferedDisplay>(
+        &mut self,
+        name: &str,
+        display: &mut D,
+    ) -> Result<(), D::Error> {
+        self.done = self.done.saturating_add(1).min(self.stages);
+        defmt::debug!("boot stage {=str} done ({}%)", name, self.percent());
+        self.show(name, display)
+    }
+
+    fn show<D: BufferedDisplay>(&self, name: &str, display: &mut D) -> Result<(), D::Error> {
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        let width = display.bounding_box().size.width;
+
+        display.clear_buffer();
+        draw_centered(display, FIRMWARE_NAME, NAME_Y, style)?;
+        let bar = Rectangle::new(
+            Point::new(BAR_MARGIN, BAR_TOP),
+            Size::new(width.saturating_sub(2 * BAR_MARGIN as u32), BAR_HEIGHT),
+        );
+        draw_progress_bar(display, bar, self.percent())?;
+        draw_centered(display, name, STAGE_Y, style)?;
+        display.flush()
+    }
+}
@@ -0,0 +1,139 @@
+//! 弹球演示：一个球在屏幕里来回弹，每帧整屏清空再重画
+//!
+//! 主要是调试用的：每一帧都是"清屏 + 画图 + flush"，能直观看出刷新跟不跟得上。
+//! 球每个逻辑 tick 走一步(见 `timestep`)，刷新跟不上的时候球是跳着走的，但不会变慢。
+//! 从诊断页面按 Select 进来，再按 Select 换成星空屏保，按 Back 回去。
+
+use core::convert::Infallible;
+
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::{OriginDimensions, Point, Size};
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::primitives::{Circle, Primitive, PrimitiveStyle};
+use embedded_graphics::Drawable;
+
+use crate::app::{Canvas, Event, Page, PageId, Transition};
+use crate::framebuffer::{HEIGHT, WIDTH};
+use crate::input::{Button, ButtonEvent};
+
+/// 球的半径
+pub const BALL_RADIUS: i32 = 4;
+
+/// 球：圆心位置和每个逻辑 tick 移动多少像素
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ball {
+    pub x: i32,
+    pub y: i32,
+    pub vx: i32,
+    pub vy: i32,
+}
+
+impl Ball {
+    pub const fn new(x: i32, y: i32, vx: i32, vy: i32) -> Self {
+        Self { x, y, vx, vy }
+    }
+
+    /// 走一步，碰到边就反弹。`area` 是屏幕在当前方向下的宽高，旋转 90 度的时候宽高是反过来的
+    pub fn step(&mut self, area: Size) {
+        let (x, vx) = bounce(self.x + self.vx, self.vx, area.width as i32);
+        let (y, vy) = bounce(self.y + self.vy, self.vy, area.height as i32);
+        *self = Self { x, y, vx, vy };
+    }
+
+    /// 挪回 `area` 里面，速度不变。屏幕转了方向以后球可能在新的边界外面
+    pub fn keep_inside(&mut self, area: Size) {
+        let max_x = (area.width as i32 - 1 - BALL_RADIUS).max(BALL_RADIUS);
+        let max_y = (area.height as i32 - 1 - BALL_RADIUS).max(BALL_RADIUS);
+        self.x = self.x.clamp(BALL_RADIUS, max_x);
+        self.y = self.y.clamp(BALL_RADIUS, max_y);
+    }
+}
+
+/// 一个方向上的反弹：越过边界多少就往回弹多少，速度反向
+fn bounce(pos: i32, velocity: i32, extent: i32) -> (i32, i32) {
+    let min = BALL_RADIUS;
+    let max = (extent - 1 - BALL_RADIUS).max(min);
+    if pos < min {
+        ((2 * min - pos).min(max), -velocity)
+    } else if pos > max {
+        ((2 * max - pos).max(min), -velocity)
+    } else {
+        (pos, velocity)
+    }
+}
+
+/// 清屏，把球画上去。flush 由调用方负责
+pub fn draw_ball<D>(display: &mut D, ball: &Ball) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    display.clear(BinaryColor::Off)?;
+    // Circle 的参数是外接正方形的左上角和直径
+    Circle::new(
+        Point::new(ball.x - BALL_RADIUS, ball.y - BALL_RADIUS),
+        (2 * BALL_RADIUS + 1) as u32,
+    )
+    .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+    .draw(display)
+}
+
+/// 走一步再画，画一帧走一步的简单循环用。页面里不用它：球按逻辑 tick 走(`Page::step`)，跟帧率无关
+pub fn step_and_draw<D>(display: &mut D, ball: &mut Ball) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor> + OriginDimensions,
+{
+    ball.step(display.size());
+    draw_ball(display, ball)
+}
+
+/// 弹球页面，每一帧都重画
+#[derive(Debug)]
+pub struct BouncingBallPage {
+    ball: Ball,
+    /// 上一次画的时候屏幕多大，球在这里面弹
+    area: Size,
+    /// 按 Select 切去哪个演示
+    other_demo: PageId,
+}
+
+impl BouncingBallPage {
+    pub const fn new(other_demo: PageId) -> Self {
+        Self {
+            ball: Ball::new(20, 12, 2, 1),
+            area: Size::new(WIDTH as u32, HEIGHT as u32),
+            other_demo,
+        }
+    }
+}
+
+impl Page for BouncingBallPage {
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        match event {
+            Event::Button(ButtonEvent::Pressed(Button::Back)) => Transition::Pop,
+            Event::Button(ButtonEvent::Pressed(Button::Select)) => {
+                Transition::Replace(self.other_demo)
+            }
+            _ => Transition::None,
+        }
+    }
+
+    /// 整屏刷新一次大概 25ms，30 帧每秒差不多是 400kHz I2C 的上限
+    fn desired_fps(&self) -> u16 {
+        30
+    }
+
+    fn step(&mut self, _tick: u32) -> bool {
+        self.ball.step(self.area);
+        true
+    }
+
+    fn on_rotation_changed(&mut self, size: Size) {
+        self.area = size;
+        self.ball.keep_inside(size);
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<(), Infallible> {
+        self.area = canvas.size();
+        draw_ball(canvas, &self.ball)
+    }
+}
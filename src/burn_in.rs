@@ -0,0 +1,147 @@
+//! 防烧屏：OLED 长时间显示同样的东西，常亮的像素会比别的老得快，时间长了留下残影
+//!
+//! 两种办法，按设置里的间隔定时做一次，默认关着(`SETTINGS BURNIN`，见 `console`)：
+//!
+//! - 像素平移(`PixelShift`)：整个画面在 (0,0)、(1,0)、(1,1)、(0,1) 四个偏移之间轮流挪一个像素，
+//!   同一个像素不会一直亮着。代价是最右一列、最下一行的内容会被挤出屏幕，贴边画的东西偶尔少一条边；
+//!   每挪一次整屏重画重发一遍，看着几乎察觉不到
+//! - 反色闪一下(`InvertFlash`)：整屏反色半秒再恢复，让一直暗着的像素也亮一会儿，老化得平均一点。
+//!   不丢内容，但是闪那一下很显眼，适合没人一直盯着看的场合
+//!
+//! 平移是改显存的绘制原点(`FrameBuffer::set_shift`)，反色和告警闪烁叠在一起算，都在主循环里做。
+
+/// 反色闪多久(毫秒)
+pub const INVERT_FLASH_MS: u64 = 500;
+
+/// 默认多久做一次(分钟)
+pub const DEFAULT_INTERVAL_MIN: u16 = 5;
+
+/// 编码之后多少字节：办法 1 字节 + 间隔(分钟)2 字节
+pub const ENCODED_LEN: usize = 3;
+
+/// 平移的时候轮流用的偏移
+const SHIFT_PATTERN: [(i32, i32); 4] = [(0, 0), (1, 0), (1, 1), (0, 1)];
+
+/// 办法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, defmt::Format)]
+pub enum BurnInStrategy {
+    /// 关掉
+    #[default]
+    Off,
+    /// 整个画面挪一个像素
+    PixelShift,
+    /// 反色闪一下
+    InvertFlash,
+}
+
+/// 防烧屏的设置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct BurnInConfig {
+    pub strategy: BurnInStrategy,
+    /// 多久做一次(分钟)，至少 1 分钟
+    pub interval_min: u16,
+}
+
+impl Default for BurnInConfig {
+    fn default() -> Self {
+        Self {
+            strategy: BurnInStrategy::Off,
+            interval_min: DEFAULT_INTERVAL_MIN,
+        }
+    }
+}
+
+impl BurnInConfig {
+    pub fn encode(&self) -> [u8; ENCODED_LEN] {
+        let [lo, hi] = self.interval_min.to_le_bytes();
+        let strategy = match self.strategy {
+            BurnInStrategy::Off => 0,
+            BurnInStrategy::PixelShift => 1,
+            BurnInStrategy::InvertFlash => 2,
+        };
+        [strategy, lo, hi]
+    }
+
+    /// 解码。不认识的办法或者间隔是 0 返回 None
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let &[strategy, lo, hi] = bytes else {
+            return None;
+        };
+        let strategy = match strategy {
+            0 => BurnInStrategy::Off,
+            1 => BurnInStrategy::PixelShift,
+            2 => BurnInStrategy::InvertFlash,
+            _ => return None,
+        };
+        let interval_min = u16::from_le_bytes([lo, hi]);
+        (interval_min > 0).then_some(Self {
+            strategy,
+            interval_min,
+        })
+    }
+
+    fn interval_ms(&self) -> u64 {
+        self.interval_min as u64 * 60_000
+    }
+}
+
+/// 防烧屏的节奏：主循环里每一圈调 `update`
+#[derive(Debug, Clone, Copy)]
+pub struct AntiBurnIn {
+    config: BurnInConfig,
+    /// 下次什么时候做
+    next_ms: u64,
+    /// 现在用的是 `SHIFT_PATTERN` 的第几个
+    step: usize,
+    /// 反色闪到什么时候
+    flash_until_ms: u64,
+}
+
+impl AntiBurnIn {
+    pub fn new(config: BurnInConfig, now_ms: u64) -> Self {
+        Self {
+            config,
+            next_ms: now_ms + config.interval_ms(),
+            step: 0,
+            flash_until_ms: 0,
+        }
+    }
+
+    pub fn config(&self) -> BurnInConfig {
+        self.config
+    }
+
+    /// 换设置，从头开始计时，画面回到原位
+    pub fn set_config(&mut self, config: BurnInConfig, now_ms: u64) {
+        *self = Self::new(config, now_ms);
+    }
+
+    /// 到时间了就做一次。返回 true 表示平移的偏移变了，调用方要把 `offset` 设给显存并整屏重画
+    pub fn update(&mut self, now_ms: u64) -> bool {
+        if self.config.strategy == BurnInStrategy::Off || now_ms < self.next_ms {
+            return false;
+        }
+        self.next_ms = now_ms + self.config.interval_ms();
+        match self.config.strategy {
+            BurnInStrategy::PixelShift => {
+                self.step = (self.step + 1) % SHIFT_PATTERN.len();
+                true
+            }
+            BurnInStrategy::InvertFlash => {
+                self.flash_until_ms = now_ms + INVERT_FLASH_MS;
+                false
+            }
+            BurnInStrategy::Off => false,
+        }
+    }
+
+    /// 画面现在要挪多少像素(右、下)
+    pub fn offset(&self) -> (i32, i32) {
+        SHIFT_PATTERN[self.step]
+    }
+
+    /// 现在是不是在反色闪
+    pub fn inverted(&self, now_ms: u64) -> bool {
+        now_ms < self.flash_until_ms
+    }
+}
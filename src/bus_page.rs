@@ -0,0 +1,137 @@
+//! 总线页面：I2C 扫描结果和 SCL 线诊断(见 `i2c_lines`)
+//!
+//! 从设置菜单进来，进来的时候扫一遍，Select 再扫一遍，Back 回去。扫描和诊断要用到总线和 ADC，
+//! 页面自己拿不到，发 `Action::ScanBus` 让主循环去做，做完广播 `Event::BusReport` 回来。
+//!
+//! 上面是应答了的地址(放不下的写 `+N`)，下面是 SCL 的结论、空闲电压和发数据的时候最高到多少、中间电平占多少。
+//! 串口命令 `SCAN` 看到的是同样的东西。
+
+use core::convert::Infallible;
+use core::fmt::Write;
+
+use embedded_graphics::geometry::Point;
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::text::Text;
+use embedded_graphics::Drawable;
+use heapless::String;
+
+use crate::app::{Action, Canvas, Event, Page, Transition};
+use crate::i2c_lines::BusReport;
+use crate::input::{Button, ButtonEvent};
+use crate::text::draw_centered;
+
+/// 行间距(基线到基线)
+const LINE_HEIGHT: i32 = 10;
+
+/// 第一行基线
+const TOP_Y: i32 = 8;
+
+/// 一行放几个地址(`0x3c ` 五个字符)
+const ADDRESSES_PER_ROW: usize = 4;
+
+/// 地址最多占几行
+const ADDRESS_ROWS: usize = 2;
+
+/// 电压按 0.01V 写，比如 `3.28V`
+fn write_volts<W: Write>(out: &mut W, mv: u16) -> core::fmt::Result {
+    write!(out, "{}.{:02}V", mv / 1000, mv % 1000 / 10)
+}
+
+/// 总线页面
+#[derive(Debug, Default)]
+pub struct BusPage {
+    report: Option<BusReport>,
+    /// 等着主循环去扫
+    pending: bool,
+}
+
+impl BusPage {
+    /// 第一次进来就扫
+    pub const fn new() -> Self {
+        Self {
+            report: None,
+            pending: true,
+        }
+    }
+}
+
+impl Page for BusPage {
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        match event {
+            Event::Button(ButtonEvent::Pressed(Button::Back)) => {
+                // 下次进来重新扫
+                self.pending = true;
+                return Transition::Pop;
+            }
+            Event::Button(ButtonEvent::Pressed(Button::Select)) => self.pending = true,
+            Event::BusReport(report) => self.report = Some(report.clone()),
+            _ => {}
+        }
+        Transition::None
+    }
+
+    fn take_action(&mut self) -> Option<Action> {
+        core::mem::take(&mut self.pending).then_some(Action::ScanBus)
+    }
+
+    fn desired_fps(&self) -> u16 {
+        1
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<(), Infallible> {
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        let Some(report) = &self.report else {
+            return draw_centered(canvas, "scanning...", 32, style);
+        };
+
+        let mut line: String<24> = String::new();
+        let _ = write!(line, "i2c: {} found", report.addresses.len());
+        Text::new(&line, Point::new(0, TOP_Y), style).draw(canvas)?;
+        let mut y = TOP_Y;
+        let shown = ADDRESSES_PER_ROW * ADDRESS_ROWS;
+        for (row, chunk) in report.addresses[..report.addresses.len().min(shown)]
+            .chunks(ADDRESSES_PER_ROW)
+            .enumerate()
+        {
+            let mut line: String<24> = String::new();
+            for address in chunk {
+                let _ = write!(line, "{:#04x} ", address);
+            }
+            if row == ADDRESS_ROWS - 1 && report.addresses.len() > shown {
+                let _ = write!(line, "+{}", report.addresses.len() - shown);
+            }
+            y += LINE_HEIGHT;
+            Text::new(&line, Point::new(0, y), style).draw(canvas)?;
+        }
+
+        y = TOP_Y + LINE_HEIGHT * (ADDRESS_ROWS as i32 + 1) + 2;
+        let Some(lines) = report.lines else {
+            return Text::new("SCL: n/a", Point::new(0, y), style)
+                .draw(canvas)
+                .map(|_| ());
+        };
+        let mut line: String<24> = String::new();
+        let _ = write!(line, "SCL: {}", lines.verdict.name());
+        Text::new(&line, Point::new(0, y), style).draw(canvas)?;
+
+        let mut line: String<24> = String::new();
+        let _ = line.push_str("idle ");
+        let _ = write_volts(&mut line, lines.idle_mv);
+        if let Some(high) = lines.high_mv {
+            let _ = line.push_str(" hi ");
+            let _ = write_volts(&mut line, high);
+        }
+        y += LINE_HEIGHT;
+        Text::new(&line, Point::new(0, y), style).draw(canvas)?;
+
+        if let Some(mid) = lines.mid_percent {
+            let mut line: String<24> = String::new();
+            let _ = write!(line, "mid {}%", mid);
+            y += LINE_HEIGHT;
+            Text::new(&line, Point::new(0, y), style).draw(canvas)?;
+        }
+        Ok(())
+    }
+}
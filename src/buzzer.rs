@@ -0,0 +1,85 @@
+//! PWM 驱动的无源蜂鸣器
+//!
+//! 无源蜂鸣器要喂方波才会响，方波频率就是音调。PWM 的输出频率是
+//! `系统时钟 / (分频 * (TOP + 1))`，每换一个音都要重新算分频和 TOP：
+//! 分频尽量小(TOP 越大，频率越准)，但 TOP 不能超过 16 位。占空比固定 50%，声音最响。
+//!
+//! 蜂鸣器接在哪个引脚、用哪个 PWM slice 见 board.rs 的 `buzzer_pwm!`。
+//! 一定要无源的：有源蜂鸣器自己带振荡，通电就是固定的一个音，喂方波只会响得发毛。
+//!
+//! 主循环里放声音走 `ToneEngine`(见 tone.rs)，它是不阻塞的 `beep`；还没进主循环、或者不在乎卡一下的地方直接用 `beep`。
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::pwm::SetDutyCycle;
+use rp2040_hal::pwm::{FreeRunning, Slice, SliceId, ValidSliceMode};
+
+use crate::tone::ToneOutput;
+
+/// 分频寄存器是 8.4 定点数，最大 255 + 15/16
+const MAX_DIV_16THS: u32 = 255 * 16 + 15;
+
+/// 蜂鸣器：一个 PWM slice，两个通道输出一样的波形(只有接了蜂鸣器的那个引脚有用)
+pub struct PwmBuzzer<S>
+where
+    S: SliceId,
+    FreeRunning: ValidSliceMode<S>,
+{
+    slice: Slice<S, FreeRunning>,
+    system_clock_hz: u32,
+}
+
+impl<S> PwmBuzzer<S>
+where
+    S: SliceId,
+    FreeRunning: ValidSliceMode<S>,
+{
+    /// `system_clock_hz` 用来算分频，要传实际的系统时钟
+    pub fn new(mut slice: Slice<S, FreeRunning>, system_clock_hz: u32) -> Self {
+        slice.disable();
+        Self {
+            slice,
+            system_clock_hz,
+        }
+    }
+}
+
+impl<S> ToneOutput for PwmBuzzer<S>
+where
+    S: SliceId,
+    FreeRunning: ValidSliceMode<S>,
+{
+    fn set_tone(&mut self, freq_hz: Option<u32>) {
+        let Some(freq) = freq_hz.filter(|&f| f > 0) else {
+            self.slice.disable();
+            return;
+        };
+
+        // 分频以 1/16 为单位：先取能让 TOP 放进 16 位的最小分频，再按分频算 TOP
+        let clock_16ths = self.system_clock_hz as u64 * 16;
+        let div = (clock_16ths / (freq as u64 * 65536) + 1).clamp(16, MAX_DIV_16THS as u64);
+        let top = (clock_16ths / (div * freq as u64)).clamp(2, 65536) - 1;
+
+        self.slice.disable();
+        self.slice.set_div_int((div >> 4) as u8);
+        self.slice.set_div_frac((div & 0xF) as u8);
+        self.slice.set_top(top as u16);
+        // 计数器可能比新的 TOP 大，不清零的话要先数到 65535 才会回绕，第一个周期就错了
+        self.slice.set_counter(0);
+        let duty = (top / 2) as u16;
+        let _ = self.slice.channel_a.set_duty_cycle(duty);
+        let _ = self.slice.channel_b.set_duty_cycle(duty);
+        self.slice.enable();
+    }
+}
+
+/// 阻塞地响一声：按 `freq_hz` 发 `duration_ms`，然后静音。这期间什么都干不了，只适合很短的提示音
+pub fn beep<T: ToneOutput, D: DelayNs>(
+    output: &mut T,
+    freq_hz: u32,
+    duration_ms: u32,
+    timer: &mut D,
+) {
+    output.set_tone(Some(freq_hz));
+    timer.delay_ms(duration_ms);
+    output.set_tone(None);
+}
@@ -0,0 +1,129 @@
+//! 每块板子自己的校准：片内温度的偏移、ADC 的比例
+//!
+//! 片内温度传感器每块芯片差好几度，ADC 的参考电压(Pico 上就是 3.3V 电源)也有百分之一两的偏差。
+//! 校准在设置菜单的 "calibrate" 里做(见 calibration_page.rs)：
+//!
+//! - 温度：输入现在的真实室温，偏移 = 真实温度 - 原始读数，之后温度通道的读数都加上这个偏移
+//! - ADC：往 ADC0 上接一个知道是多少伏的电压(比如用万用表量过的电池)，输入这个电压，比例 = 真实电压 / 原始读数，
+//!   之后 ADC0 和 VSYS(电池电量也是按 VSYS 算的)都乘这个比例。这一步可以跳过
+//!
+//! 结果存在设置里，修正挂在传感器通道上(`SensorRegistry::set_correction`)，仪表盘、记录、告警、遥测拿到的都是修正过的值。
+//! 诊断页面并排显示原始值和修正后的值。设置菜单的 "reset cal" 恢复成不修正。
+
+use crate::sensors::{Correction, GAIN_ONE};
+
+/// 编码之后多少字节：温度偏移 2 字节 + ADC 比例 2 字节
+pub const ENCODED_LEN: usize = 4;
+
+/// 温度偏移最多修多少(0.01°C)，再多多半是输错了
+pub const MAX_TEMP_OFFSET_CENTI: i16 = 2000;
+
+/// ADC 比例的范围(`GAIN_ONE` 是 1 倍)，参考电压偏差不会超过两成
+pub const MIN_ADC_GAIN: u16 = 8_000;
+pub const MAX_ADC_GAIN: u16 = 12_000;
+
+/// ADC0 上的电压低于这个(mV)不能用来校准，读数的相对误差太大，多半是没接
+pub const MIN_REFERENCE_MV: i32 = 300;
+
+/// 校准页面做完的一步，交给主循环按现在的校准值算好存起来(`Action::Calibrate`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum CalibrationInput {
+    /// 温度：没修正的读数和输入的真实温度(0.01°C)
+    Temperature { raw_centi: i32, true_centi: i32 },
+    /// ADC0：没修正的读数和输入的真实电压(mV)
+    AdcReference { raw_mv: i32, true_mv: i32 },
+}
+
+impl CalibrationInput {
+    /// 用在 `calibration` 上，另一项不变。超出范围返回 None
+    pub fn apply(self, calibration: Calibration) -> Option<Calibration> {
+        match self {
+            CalibrationInput::Temperature {
+                raw_centi,
+                true_centi,
+            } => calibration.with_temperature(raw_centi, true_centi),
+            CalibrationInput::AdcReference { raw_mv, true_mv } => {
+                calibration.with_adc_reference(raw_mv, true_mv)
+            }
+        }
+    }
+}
+
+/// 校准值
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Calibration {
+    /// 温度加多少(0.01°C)
+    pub temp_offset_centi: i16,
+    /// 电压乘多少，`GAIN_ONE` 是 1 倍
+    pub adc_gain: u16,
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self {
+            temp_offset_centi: 0,
+            adc_gain: GAIN_ONE as u16,
+        }
+    }
+}
+
+impl Calibration {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// 按真实室温算温度偏移，`raw_centi` 是没修正的读数。偏得太多返回 None
+    pub fn with_temperature(self, raw_centi: i32, true_centi: i32) -> Option<Self> {
+        let offset = true_centi - raw_centi;
+        (offset.unsigned_abs() <= MAX_TEMP_OFFSET_CENTI as u32).then_some(Self {
+            temp_offset_centi: offset as i16,
+            ..self
+        })
+    }
+
+    /// 按 ADC0 上已知的电压算比例，`raw_mv` 是没修正的读数。读数太小或者比例超出范围返回 None
+    pub fn with_adc_reference(self, raw_mv: i32, true_mv: i32) -> Option<Self> {
+        if raw_mv < MIN_REFERENCE_MV {
+            return None;
+        }
+        let gain = (true_mv as i64 * GAIN_ONE as i64 + raw_mv as i64 / 2) / raw_mv as i64;
+        (MIN_ADC_GAIN as i64..=MAX_ADC_GAIN as i64)
+            .contains(&gain)
+            .then_some(Self {
+                adc_gain: gain as u16,
+                ..self
+            })
+    }
+
+    /// 温度通道的修正
+    pub fn temperature(&self) -> Correction {
+        Correction::offset(self.temp_offset_centi as i32)
+    }
+
+    /// 电压通道(ADC0、VSYS)的修正
+    pub fn voltage(&self) -> Correction {
+        Correction::gain(self.adc_gain as i32)
+    }
+
+    pub fn encode(&self) -> [u8; ENCODED_LEN] {
+        let mut out = [0u8; ENCODED_LEN];
+        out[0..2].copy_from_slice(&self.temp_offset_centi.to_le_bytes());
+        out[2..4].copy_from_slice(&self.adc_gain.to_le_bytes());
+        out
+    }
+
+    /// 解码。超出范围返回 None
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let &[offset_lo, offset_hi, gain_lo, gain_hi] = bytes else {
+            return None;
+        };
+        let temp_offset_centi = i16::from_le_bytes([offset_lo, offset_hi]);
+        let adc_gain = u16::from_le_bytes([gain_lo, gain_hi]);
+        let valid = temp_offset_centi.unsigned_abs() <= MAX_TEMP_OFFSET_CENTI as u16
+            && (MIN_ADC_GAIN..=MAX_ADC_GAIN).contains(&adc_gain);
+        valid.then_some(Self {
+            temp_offset_centi,
+            adc_gain,
+        })
+    }
+}
@@ -0,0 +1,307 @@
+//! 校准页面：输入真实室温和 ADC0 上的已知电压，算出这块板子的修正(见 `calibration`)
+//!
+//! 从设置菜单的 "calibrate" 进来，分两步：
+//!
+//! 1. 温度：上面一行是传感器现在的读数(修正之前)，下面一行是要输入的真实温度，从现在显示的温度开始调。
+//!    Up/Down 每次 0.1°C，长按每次 1°C，Select 确定，Back 放弃
+//! 2. 电压：ADC0 上接好已知电压，同样调到万用表量到的值，每次 10mV，长按 100mV，Select 确定，Back 跳过这一步
+//!
+//! 每一步确定的时候交给主循环保存(`Action::Calibrate`)，做完第一步就退出也算数。读数太离谱(比如 ADC0 没接)
+//! 这一步不让确定，下面提示 "out of range"。两步走完显示 "saved"，按 Back 或者 Select 回去。
+//!
+//! 设置菜单的 "reset cal" 是 `CalibrationResetPage`，Select 确认一下就恢复成不修正。
+//! 动作是主循环问最上面的页面要的，所以确定了以后两个页面都先留在屏幕上，不马上关。
+
+use core::cell::RefCell;
+use core::convert::Infallible;
+
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use heapless::String;
+
+use crate::app::{Action, Canvas, Event, Page, Transition};
+use crate::calibration::{Calibration, CalibrationInput};
+use crate::input::{Button, ButtonEvent};
+use crate::sensors::{ChannelId, SensorRegistry};
+use crate::text::draw_centered;
+use crate::widgets::{draw_menu_row, MENU_ROW_HEIGHT};
+
+/// 标题行基线
+const TITLE_Y: i32 = 8;
+
+/// 第一行的顶边
+const FIRST_ROW_TOP: i32 = 12;
+
+/// 提示行基线
+const HINT_Y: i32 = 50;
+const ERROR_Y: i32 = 61;
+
+/// 温度按一下调多少、长按调多少(0.01°C)
+const TEMP_STEP: i32 = 10;
+const TEMP_LONG_STEP: i32 = 100;
+
+/// 电压按一下调多少、长按调多少(mV)
+const VOLT_STEP: i32 = 10;
+const VOLT_LONG_STEP: i32 = 100;
+
+/// 在做哪一步
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Temperature,
+    Voltage,
+    /// 做完了，等着回去
+    Done,
+}
+
+/// 校准页面
+pub struct CalibrationPage<'a, S> {
+    sensors: &'a RefCell<SensorRegistry<S>>,
+    temp: ChannelId,
+    adc0: ChannelId,
+    step: Step,
+    /// 输入的真实值，还没调过是 None(显示现在的读数)
+    entry: Option<i32>,
+    /// 上次确定的时候读数不对
+    rejected: bool,
+    pending: Option<Action>,
+    shown_generation: Option<u32>,
+}
+
+impl<'a, S> CalibrationPage<'a, S> {
+    /// `temp`、`adc0` 是片内温度和 ADC0 在注册表里的通道
+    pub const fn new(
+        sensors: &'a RefCell<SensorRegistry<S>>,
+        temp: ChannelId,
+        adc0: ChannelId,
+    ) -> Self {
+        Self {
+            sensors,
+            temp,
+            adc0,
+            step: Step::Temperature,
+            entry: None,
+            rejected: false,
+            pending: None,
+            shown_generation: None,
+        }
+    }
+
+    /// 这一步读哪个通道，做完了是 None
+    fn channel(&self) -> Option<ChannelId> {
+        match self.step {
+            Step::Temperature => Some(self.temp),
+            Step::Voltage => Some(self.adc0),
+            Step::Done => None,
+        }
+    }
+
+    /// 这一步的通道现在的 (修正之前, 修正之后) 读数
+    fn readings(&self) -> Option<(i32, i32)> {
+        let sensors = self.sensors.borrow();
+        let channel = sensors.get(self.channel()?)?;
+        Some((channel.raw_value()?, channel.value()?))
+    }
+
+    fn go_to(&mut self, step: Step) {
+        self.step = step;
+        self.entry = None;
+        self.rejected = false;
+    }
+
+    /// 回去，下次进来从第一步开始
+    fn exit(&mut self) -> Transition {
+        self.go_to(Step::Temperature);
+        Transition::Pop
+    }
+
+    fn confirm(&mut self) {
+        let Some((raw, value)) = self.readings() else {
+            self.rejected = true;
+            return;
+        };
+        let entry = self.entry.unwrap_or(value);
+        let (input, next) = match self.step {
+            Step::Temperature => (
+                CalibrationInput::Temperature {
+                    raw_centi: raw,
+                    true_centi: entry,
+                },
+                Step::Voltage,
+            ),
+            _ => (
+                CalibrationInput::AdcReference {
+                    raw_mv: raw,
+                    true_mv: entry,
+                },
+                Step::Done,
+            ),
+        };
+        // 范围和现在的校准值无关，这里先查一遍，不对就留在这一步
+        if input.apply(Calibration::default()).is_none() {
+            self.rejected = true;
+            return;
+        }
+        self.pending = Some(Action::Calibrate(input));
+        self.go_to(next);
+    }
+
+    fn adjust(&mut self, up: bool, long: bool) {
+        let Some((_, value)) = self.readings() else {
+            return;
+        };
+        let step = match (self.step, long) {
+            (Step::Temperature, false) => TEMP_STEP,
+            (Step::Temperature, true) => TEMP_LONG_STEP,
+            (_, false) => VOLT_STEP,
+            (_, true) => VOLT_LONG_STEP,
+        };
+        let entry = self.entry.unwrap_or(value);
+        let entry = if up { entry + step } else { entry - step };
+        // 电压不会是负的
+        self.entry = Some(match self.step {
+            Step::Temperature => entry,
+            _ => entry.max(0),
+        });
+        self.rejected = false;
+    }
+}
+
+impl<S> Page for CalibrationPage<'_, S> {
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        let (button, long) = match event {
+            Event::Button(ButtonEvent::Pressed(button)) => (*button, false),
+            Event::Button(ButtonEvent::LongPress(button)) => (*button, true),
+            _ => return Transition::None,
+        };
+        match (self.step, button, long) {
+            (Step::Done, Button::Back | Button::Select, false) => self.exit(),
+            (Step::Temperature, Button::Back, false) => self.exit(),
+            // 跳过电压这一步
+            (Step::Voltage, Button::Back, false) => {
+                self.go_to(Step::Done);
+                Transition::None
+            }
+            (_, Button::Select, false) => {
+                self.confirm();
+                Transition::None
+            }
+            (_, Button::Up, _) => {
+                self.adjust(true, long);
+                Transition::None
+            }
+            (_, Button::Down, _) => {
+                self.adjust(false, long);
+                Transition::None
+            }
+            _ => Transition::None,
+        }
+    }
+
+    fn desired_fps(&self) -> u16 {
+        2
+    }
+
+    fn tick(&mut self, _now_ms: u64) -> bool {
+        let generation = self.sensors.borrow().generation();
+        let changed = self.shown_generation != Some(generation);
+        self.shown_generation = Some(generation);
+        changed
+    }
+
+    fn take_action(&mut self) -> Option<Action> {
+        self.pending.take()
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<(), Infallible> {
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        let (title, hint) = match self.step {
+            Step::Temperature => ("calibrate 1/2: temp", "Sel=ok Back=cancel"),
+            Step::Voltage => ("calibrate 2/2: adc0", "Sel=ok Back=skip"),
+            Step::Done => {
+                draw_centered(canvas, "calibration", TITLE_Y, style)?;
+                return draw_centered(canvas, "saved", 36, style);
+            }
+        };
+        draw_centered(canvas, title, TITLE_Y, style)?;
+
+        let sensors = self.sensors.borrow();
+        let channel = self.channel().and_then(|id| sensors.get(id));
+        let info = channel.map(|channel| *channel.info());
+        let raw = channel.and_then(|channel| channel.raw_value());
+        let value = channel.and_then(|channel| channel.value());
+        let mut text: String<16> = String::new();
+        if let Some(info) = info {
+            let _ = info.write_value(raw, &mut text);
+        }
+        draw_menu_row(canvas, FIRST_ROW_TOP, "sensor", &text, false)?;
+        text.clear();
+        if let Some(info) = info {
+            let _ = info.write_value(self.entry.or(value), &mut text);
+        }
+        draw_menu_row(
+            canvas,
+            FIRST_ROW_TOP + MENU_ROW_HEIGHT,
+            "actual",
+            &text,
+            true,
+        )?;
+
+        draw_centered(canvas, hint, HINT_Y, style)?;
+        if self.rejected {
+            draw_centered(canvas, "out of range", ERROR_Y, style)?;
+        }
+        Ok(())
+    }
+}
+
+/// "reset cal"：Select 恢复成不修正，Back 回去
+#[derive(Debug, Default)]
+pub struct CalibrationResetPage {
+    pending: Option<Action>,
+    /// 已经恢复过了
+    done: bool,
+}
+
+impl CalibrationResetPage {
+    pub const fn new() -> Self {
+        Self {
+            pending: None,
+            done: false,
+        }
+    }
+}
+
+impl Page for CalibrationResetPage {
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        match event {
+            Event::Button(ButtonEvent::Pressed(Button::Back)) => {
+                self.done = false;
+                Transition::Pop
+            }
+            Event::Button(ButtonEvent::Pressed(Button::Select)) if !self.done => {
+                self.pending = Some(Action::ResetCalibration);
+                self.done = true;
+                Transition::None
+            }
+            _ => Transition::None,
+        }
+    }
+
+    fn desired_fps(&self) -> u16 {
+        1
+    }
+
+    fn take_action(&mut self) -> Option<Action> {
+        self.pending.take()
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<(), Infallible> {
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        if self.done {
+            return draw_centered(canvas, "calibration reset", 32, style);
+        }
+        draw_centered(canvas, "reset calibration?", 24, style)?;
+        draw_centered(canvas, "Sel=yes Back=no", 44, style)
+    }
+}
@@ -0,0 +1,180 @@
+//! 编译进来了什么：一块来路不明的板子，看一眼就知道这个固件是怎么编译的、支持什么
+//!
+//! 所有的 cargo feature 都在这里登记一次(`FEATURES`)，值是编译的时候的 `cfg!(feature = ...)`，
+//! 加了新 feature 记得在这里也加一行。另外还有几样不是开关的：版本号、屏幕接口(现在只有 I2C，没有 SPI)、
+//! 板子型号、屏幕型号，也都由 feature 决定。
+//!
+//! 看的地方有三个，内容一样：
+//!
+//! - 设置菜单里的 "features" 页面(`CapabilitiesPage`、`render_capabilities`)：第一行版本、接口、板子、屏幕，
+//!   下面三列，`+` 是编译进来了，`-` 是没有
+//! - 开机的时候 defmt 打一块(`log`)
+//! - 串口 `FEATURES`：第一行 `FEATURES` 加上版本这些，每个 feature 一行 `+ 名字` 或者 `- 名字`，最后一行 `OK`(`write_line`)
+
+use core::fmt::{self, Write};
+
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::Point;
+use embedded_graphics::mono_font::ascii::FONT_4X6;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::text::Text;
+use embedded_graphics::Drawable;
+use heapless::String;
+
+use crate::banner::FIRMWARE_VERSION;
+use crate::boards::BOARD;
+use crate::display::BufferedDisplay;
+use crate::panel::PANEL;
+
+/// 屏幕接口，这个固件只支持 I2C
+pub const DISPLAY_BUS: &str = "i2c";
+
+/// 一个 feature 开没开
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capability {
+    /// Cargo.toml 里的名字，串口和 defmt 用
+    pub feature: &'static str,
+    /// 屏幕上的短名字，一列放得下
+    pub label: &'static str,
+    pub enabled: bool,
+}
+
+const fn capability(feature: &'static str, label: &'static str, enabled: bool) -> Capability {
+    Capability {
+        feature,
+        label,
+        enabled,
+    }
+}
+
+/// 不开的时候才有的东西说成"开"，比如 `no-xosc` 说成有没有晶振、USB
+pub const FEATURES: [Capability; 12] = [
+    capability("alloc", "alloc", cfg!(feature = "alloc")),
+    capability("ws2812", "ws2812", cfg!(feature = "ws2812")),
+    capability("profiling", "profiling", cfg!(feature = "profiling")),
+    capability("xosc", "xosc", !cfg!(feature = "no-xosc")),
+    capability("usb-serial", "usb", !cfg!(feature = "no-xosc")),
+    capability(
+        "external-i2c-pullups",
+        "ext-pull",
+        cfg!(feature = "external-i2c-pullups"),
+    ),
+    capability("overlay-layer", "overlay", cfg!(feature = "overlay-layer")),
+    capability("mic-vu", "mic-vu", cfg!(feature = "mic-vu")),
+    capability(
+        "panic-restore",
+        "panic-rst",
+        cfg!(feature = "panic-restore"),
+    ),
+    capability("trace_i2c", "trace-i2c", cfg!(feature = "trace_i2c")),
+    capability("devtools", "devtools", cfg!(feature = "devtools")),
+    capability("pico-w", "cyw43", cfg!(feature = "pico-w")),
+];
+
+/// 串口一共几行(不算最后的 `OK`)：第一行版本这些，然后每个 feature 一行
+pub const LINES: usize = 1 + FEATURES.len();
+
+/// 串口一行最长多少字节(带 `\r\n`)
+pub const LINE_MAX: usize = 64;
+
+/// 屏幕上分几列
+const COLUMNS: usize = 3;
+
+/// 屏幕上的行距，4x6 的字 128x32 的屏上也放得下五行
+const ROW_PITCH: i32 = 6;
+
+/// 一列多宽(像素)
+const COLUMN_WIDTH: i32 = 42;
+
+fn marker(enabled: bool) -> char {
+    if enabled {
+        '+'
+    } else {
+        '-'
+    }
+}
+
+/// 第一行：版本、接口、板子、屏幕
+fn summary() -> String<40> {
+    let mut text = String::new();
+    let _ = write!(
+        text,
+        "v{} {} {} {}",
+        FIRMWARE_VERSION, DISPLAY_BUS, BOARD.name, PANEL.name
+    );
+    text
+}
+
+/// 串口的第 `index` 行(带 `\r\n`)
+pub fn write_line<W: fmt::Write>(index: usize, out: &mut W) -> fmt::Result {
+    match index {
+        0 => write!(
+            out,
+            "FEATURES version={} bus={} board={} panel={}\r\n",
+            FIRMWARE_VERSION, DISPLAY_BUS, BOARD.name, PANEL.name
+        ),
+        _ => match FEATURES.get(index - 1) {
+            Some(capability) => write!(
+                out,
+                "{} {}\r\n",
+                marker(capability.enabled),
+                capability.feature
+            ),
+            None => Ok(()),
+        },
+    }
+}
+
+/// 用 defmt 打一整块，开机的时候调
+pub fn log() {
+    defmt::info!(
+        "build: v{=str} bus={=str} board={=str} panel={=str}",
+        FIRMWARE_VERSION,
+        DISPLAY_BUS,
+        BOARD.name,
+        PANEL.name
+    );
+    for capability in &FEATURES {
+        defmt::info!(
+            "  {=char} {=str}",
+            marker(capability.enabled),
+            capability.feature
+        );
+    }
+}
+
+/// 把第一行和所有 feature 画出来，不清屏、不 flush
+pub fn draw_capabilities<D>(display: &mut D) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let style = MonoTextStyle::new(&FONT_4X6, BinaryColor::On);
+    Text::new(&summary(), Point::new(0, 5), style).draw(display)?;
+    for (index, capability) in FEATURES.iter().enumerate() {
+        let mut text: String<12> = String::new();
+        let _ = write!(text, "{}{}", marker(capability.enabled), capability.label);
+        let x = (index % COLUMNS) as i32 * COLUMN_WIDTH;
+        let y = 5 + (1 + index / COLUMNS) as i32 * ROW_PITCH;
+        Text::new(&text, Point::new(x, y), style).draw(display)?;
+    }
+    Ok(())
+}
+
+/// 整屏画编译进来了什么，画完立刻 flush
+pub fn render_capabilities<D: BufferedDisplay>(display: &mut D) -> Result<(), D::Error> {
+    display.clear_buffer();
+    draw_capabilities(display)?;
+    display.flush()
+}
+
+crate::screen! {
+    /// 设置菜单里的 "features" 页面，内容不会变
+    pub screen CapabilitiesPage: () {
+        name: "features",
+        fps: 1,
+        render(_state, canvas, _now_ms) {
+            draw_capabilities(canvas)
+        }
+    }
+}
@@ -0,0 +1,178 @@
+//! 自动轮播：没人看着的时候几个页面轮流显示
+//!
+//! 哪些页面参加、什么顺序、按键之后停多久都存在设置里，在设置菜单的 "carousel" 页面改
+//! (见 `carousel_page`)。每个页面停多久由页面自己定(`Page::dwell_ms`)，没说的用 `DEFAULT_DWELL_MS`；
+//! 说自己没数据的页面(`Page::has_data`，比如没接传感器)直接跳过。
+//!
+//! 只在栈里只有一层的时候轮播，换页用的是 `Transition::Replace`，所以打开菜单、弹窗的时候不会被切走。
+//! 任何按键都会让轮播停一段时间(`hold_s`)，停着的时候底边的进度条不画。
+
+use crate::app::{PageId, Scheduler, Transition};
+
+/// 最多几个页面参加轮播
+pub const MAX_SLOTS: usize = 8;
+
+/// 编码之后多少字节：开关 1 字节 + 停多久(秒)2 字节 + 页面个数 1 字节 + 页面编号 `MAX_SLOTS` 字节
+pub const ENCODED_LEN: usize = 4 + MAX_SLOTS;
+
+/// 页面没说停多久的时候停 10 秒
+pub const DEFAULT_DWELL_MS: u32 = 10_000;
+
+/// 按键之后默认停多久(秒)
+pub const DEFAULT_HOLD_S: u16 = 60;
+
+/// 轮播的设置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct CarouselConfig {
+    pub enabled: bool,
+    /// 按键之后多久(秒)再开始轮播
+    pub hold_s: u16,
+    order: [PageId; MAX_SLOTS],
+    len: u8,
+}
+
+impl Default for CarouselConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            hold_s: DEFAULT_HOLD_S,
+            order: [PageId(0); MAX_SLOTS],
+            len: 0,
+        }
+    }
+}
+
+impl CarouselConfig {
+    /// 参加轮播的页面，按轮播的顺序
+    pub fn order(&self) -> &[PageId] {
+        &self.order[..self.len as usize]
+    }
+
+    /// `page` 排在第几个，不参加是 None
+    pub fn position(&self, page: PageId) -> Option<usize> {
+        self.order().iter().position(|&id| id == page)
+    }
+
+    /// 排到最后面。已经在里面或者满了返回 false
+    pub fn push(&mut self, page: PageId) -> bool {
+        if self.position(page).is_some() || self.len as usize == MAX_SLOTS {
+            return false;
+        }
+        self.order[self.len as usize] = page;
+        self.len += 1;
+        true
+    }
+
+    /// 不参加了，后面的往前挪
+    pub fn remove(&mut self, page: PageId) {
+        let Some(index) = self.position(page) else {
+            return;
+        };
+        self.order.copy_within(index + 1..self.len as usize, index);
+        self.len -= 1;
+    }
+
+    pub fn encode(&self) -> [u8; ENCODED_LEN] {
+        let mut out = [0u8; ENCODED_LEN];
+        out[0] = self.enabled as u8;
+        out[1..3].copy_from_slice(&self.hold_s.to_le_bytes());
+        out[3] = self.len;
+        for (slot, id) in out[4..].iter_mut().zip(self.order()) {
+            *slot = id.0;
+        }
+        out
+    }
+
+    /// 解码。开关不是 0/1、页面个数超了或者有重复的页面返回 None
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != ENCODED_LEN || bytes[0] > 1 || bytes[3] as usize > MAX_SLOTS {
+            return None;
+        }
+        let mut config = Self {
+            enabled: bytes[0] == 1,
+            hold_s: u16::from_le_bytes([bytes[1], bytes[2]]),
+            ..Self::default()
+        };
+        for &id in &bytes[4..4 + bytes[3] as usize] {
+            if !config.push(PageId(id)) {
+                return None;
+            }
+        }
+        Some(config)
+    }
+}
+
+/// 轮播的节奏：主循环里每一圈调 `update`，每次按键调 `hold`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Carousel {
+    /// 当前页面从什么时候开始算
+    shown_since_ms: u64,
+    /// 停到什么时候
+    hold_until_ms: u64,
+}
+
+impl Carousel {
+    pub const fn new() -> Self {
+        Self {
+            shown_since_ms: 0,
+            hold_until_ms: 0,
+        }
+    }
+
+    /// 有人按键了，先停一会儿
+    pub fn hold(&mut self, config: &CarouselConfig, now_ms: u64) {
+        self.hold_until_ms = now_ms + config.hold_s as u64 * 1000;
+    }
+
+    /// 到时间了就换到下一个有数据的页面，顺便更新底边的进度条
+    pub fn update<const N: usize>(
+        &mut self,
+        config: &CarouselConfig,
+        scheduler: &mut Scheduler<'_, N>,
+        now_ms: u64,
+    ) {
+        let running = config.enabled
+            && !config.order().is_empty()
+            && scheduler.depth() == 1
+            && now_ms >= self.hold_until_ms;
+        if !running {
+            // 停着的时候不算时间，恢复之后当前页面重新停满一轮
+            self.shown_since_ms = now_ms;
+            scheduler.set_carousel_progress(None);
+            return;
+        }
+
+        let current = scheduler.current();
+        let dwell_ms = scheduler
+            .page_dwell_ms(current)
+            .unwrap_or(DEFAULT_DWELL_MS)
+            .max(1) as u64;
+        let elapsed = now_ms - self.shown_since_ms;
+        // 当前页面没数据(比如传感器拔了)就不用停满
+        if elapsed < dwell_ms && scheduler.page_has_data(current) {
+            scheduler.set_carousel_progress(Some((elapsed * 100 / dwell_ms) as u8));
+            return;
+        }
+
+        self.shown_since_ms = now_ms;
+        scheduler.set_carousel_progress(Some(0));
+        if let Some(next) = next_page(config, current, |id| scheduler.page_has_data(id)) {
+            if next != current {
+                scheduler.apply(Transition::Replace(next));
+            }
+        }
+    }
+}
+
+/// `current` 后面第一个有数据的页面(转一圈)。`current` 不参加轮播的话从第一个开始找
+fn next_page(
+    config: &CarouselConfig,
+    current: PageId,
+    has_data: impl Fn(PageId) -> bool,
+) -> Option<PageId> {
+    let order = config.order();
+    let start = config.position(current).map_or(0, |index| index + 1);
+    (0..order.len())
+        .map(|offset| order[(start + offset) % order.len()])
+        .find(|&id| has_data(id))
+}
@@ -0,0 +1,142 @@
+//! 自动轮播的设置页面
+//!
+//! 从设置菜单进来。前两行是开关和按键之后停多久，后面每行一个可以参加轮播的页面，值是它排第几(`-` 是不参加)：
+//!
+//! - Up/Down 选行，Back 回去
+//! - 开关那一行按 Select 开/关，停多久那一行按 Select 在几个常用值之间换
+//! - 页面那一行按 Select 加进轮播(排到最后)或者拿出来；想换顺序就拿出来再按顺序加回去
+//!
+//! 每改一下就保存(`Action::SaveCarousel`)。
+
+use core::convert::Infallible;
+use core::fmt::Write;
+
+use embedded_graphics::geometry::Point;
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::text::Text;
+use embedded_graphics::Drawable;
+use heapless::String;
+
+use crate::app::{Action, Canvas, Event, Page, PageId, Transition};
+use crate::carousel::CarouselConfig;
+use crate::input::{Button, ButtonEvent};
+use crate::widgets::draw_menu;
+
+/// 标题行基线
+const TITLE_Y: i32 = 8;
+
+/// 菜单从哪一行开始
+const MENU_TOP: i32 = 12;
+
+/// 页面前面的两行：开关、停多久
+const FIXED_ROWS: usize = 2;
+
+/// 停多久可以选的值(秒)
+const HOLD_CHOICES: [u16; 5] = [15, 30, 60, 120, 300];
+
+/// 轮播设置页面，`N` 是可以参加轮播的页面个数
+#[derive(Debug)]
+pub struct CarouselPage<const N: usize> {
+    config: CarouselConfig,
+    /// 可以参加轮播的页面和显示的名字
+    candidates: [(&'static str, PageId); N],
+    selected: usize,
+    pending: Option<Action>,
+}
+
+impl<const N: usize> CarouselPage<N> {
+    /// `config` 是设置里存的轮播设置
+    pub const fn new(config: CarouselConfig, candidates: [(&'static str, PageId); N]) -> Self {
+        Self {
+            config,
+            candidates,
+            selected: 0,
+            pending: None,
+        }
+    }
+
+    fn rows(&self) -> usize {
+        FIXED_ROWS + N
+    }
+
+    fn select(&mut self) {
+        match self.selected {
+            0 => self.config.enabled = !self.config.enabled,
+            1 => {
+                // 不在表里的值(串口导进来的)从头开始换
+                let next = HOLD_CHOICES
+                    .iter()
+                    .position(|&s| s == self.config.hold_s)
+                    .map_or(0, |i| (i + 1) % HOLD_CHOICES.len());
+                self.config.hold_s = HOLD_CHOICES[next];
+            }
+            row => {
+                let (_, page) = self.candidates[row - FIXED_ROWS];
+                if self.config.position(page).is_some() {
+                    self.config.remove(page);
+                } else if !self.config.push(page) {
+                    return;
+                }
+            }
+        }
+        self.pending = Some(Action::SaveCarousel(self.config));
+    }
+}
+
+impl<const N: usize> Page for CarouselPage<N> {
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        let Event::Button(ButtonEvent::Pressed(button)) = event else {
+            return Transition::None;
+        };
+        match button {
+            Button::Back => return Transition::Pop,
+            Button::Up => self.selected = (self.selected + self.rows() - 1) % self.rows(),
+            Button::Down => self.selected = (self.selected + 1) % self.rows(),
+            Button::Select => self.select(),
+        }
+        Transition::None
+    }
+
+    fn take_action(&mut self) -> Option<Action> {
+        self.pending.take()
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<(), Infallible> {
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        Text::new("carousel", Point::new(0, TITLE_Y), style).draw(canvas)?;
+
+        // 先把每一行的值拼好，菜单只借用字符串
+        let mut hold: String<8> = String::new();
+        let _ = write!(hold, "{}s", self.config.hold_s);
+        let positions: [String<4>; N] = core::array::from_fn(|i| {
+            let mut value = String::new();
+            match self.config.position(self.candidates[i].1) {
+                Some(index) => {
+                    let _ = write!(value, "#{}", index + 1);
+                }
+                None => {
+                    let _ = value.push('-');
+                }
+            }
+            value
+        });
+
+        let fixed = [
+            ("rotate", if self.config.enabled { "on" } else { "off" }),
+            ("hold", hold.as_str()),
+        ];
+        let pages = self
+            .candidates
+            .iter()
+            .zip(&positions)
+            .map(|(&(name, _), value)| (name, value.as_str()));
+        draw_menu(
+            canvas,
+            MENU_TOP,
+            fixed.into_iter().chain(pages),
+            self.selected,
+        )
+    }
+}
@@ -0,0 +1,109 @@
+//! 裁剪：画到某块矩形外面的像素直接丢掉
+//!
+//! 长字符串、曲线、滚动列表偶尔会画出自己的地盘，压到旁边的东西(比如右上角的告警图标、底下的提示条)。
+//! 用 `with_clip` 把一段绘制包起来，外面就一个像素都不会被改。
+//!
+//! 底下用的是 embedded-graphics 的 `Clipped`：裁剪区域建的时候就和目标的 `bounding_box` 取交集，
+//! 所以可以嵌套，里面那层只会更小，不会比外面那层大；里面的代码看到的 `bounding_box` 也是裁剪后的区域。
+//! 面积是 0 或者完全在屏幕外的矩形，交集是空的，什么都画不上去，不会出错。
+
+use embedded_graphics::draw_target::{Clipped, DrawTarget, DrawTargetExt};
+use embedded_graphics::primitives::Rectangle;
+
+/// 在 `area` 里画：`draw` 拿到的目标只接受 `area` 以内(再和 `target` 自己的范围取交集)的像素
+pub fn with_clip<D, R>(
+    target: &mut D,
+    area: Rectangle,
+    draw: impl FnOnce(&mut Clipped<'_, D>) -> R,
+) -> R
+where
+    D: DrawTarget,
+{
+    draw(&mut target.clipped(&area))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_graphics::geometry::{Dimensions, Point, Size};
+    use embedded_graphics::mock_display::MockDisplay;
+    use embedded_graphics::pixelcolor::BinaryColor;
+    use embedded_graphics::primitives::{Primitive, PrimitiveStyle};
+    use embedded_graphics::Drawable;
+
+    fn fill<D: DrawTarget<Color = BinaryColor>>(target: &mut D, area: Rectangle) {
+        let _ = area
+            .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+            .draw(target);
+    }
+
+    /// 只画 `area` 的参考结果
+    fn filled(area: Rectangle) -> MockDisplay<BinaryColor> {
+        let mut display = MockDisplay::new();
+        fill(&mut display, area);
+        display
+    }
+
+    const CLIP: Rectangle = Rectangle::new(Point::new(10, 20), Size::new(30, 16));
+
+    #[test]
+    fn drawing_across_each_edge_is_cut_at_the_edge() {
+        let cases = [
+            // 左边、右边、上边、下边各跨出去一截
+            Rectangle::new(Point::new(4, 24), Size::new(12, 4)),
+            Rectangle::new(Point::new(34, 24), Size::new(12, 4)),
+            Rectangle::new(Point::new(14, 16), Size::new(4, 8)),
+            Rectangle::new(Point::new(14, 32), Size::new(4, 8)),
+            // 四面都出去
+            Rectangle::new(Point::new(0, 0), Size::new(64, 64)),
+        ];
+        for area in cases {
+            let mut display = MockDisplay::new();
+            with_clip(&mut display, CLIP, |clipped| fill(clipped, area));
+            display.assert_eq(&filled(area.intersection(&CLIP)));
+        }
+    }
+
+    #[test]
+    fn drawing_inside_is_untouched() {
+        let area = Rectangle::new(Point::new(12, 22), Size::new(5, 5));
+        let mut display = MockDisplay::new();
+        with_clip(&mut display, CLIP, |clipped| fill(clipped, area));
+        display.assert_eq(&filled(area));
+    }
+
+    #[test]
+    fn nested_clips_intersect() {
+        let inner = Rectangle::new(Point::new(30, 10), Size::new(20, 20));
+        let mut display = MockDisplay::new();
+        let bounds = with_clip(&mut display, CLIP, |outer| {
+            with_clip(outer, inner, |clipped| {
+                fill(clipped, Rectangle::new(Point::zero(), Size::new(64, 64)));
+                clipped.bounding_box()
+            })
+        });
+        let expected = Rectangle::new(Point::new(30, 20), Size::new(10, 10));
+        assert_eq!(bounds, expected);
+        display.assert_eq(&filled(expected));
+    }
+
+    #[test]
+    fn empty_intersection_draws_nothing() {
+        let mut display = MockDisplay::<BinaryColor>::new();
+        let disjoint = Rectangle::new(Point::new(50, 50), Size::new(5, 5));
+        with_clip(&mut display, CLIP, |outer| {
+            with_clip(outer, disjoint, |clipped| {
+                assert_eq!(clipped.bounding_box().size, Size::zero());
+                fill(clipped, Rectangle::new(Point::zero(), Size::new(64, 64)));
+            })
+        });
+        with_clip(
+            &mut display,
+            Rectangle::new(Point::new(5, 5), Size::zero()),
+            |clipped| {
+                fill(clipped, Rectangle::new(Point::zero(), Size::new(64, 64)));
+            },
+        );
+        display.assert_eq(&MockDisplay::new());
+    }
+}
@@ -0,0 +1,165 @@
+//! 温湿度页面：SHT31/AHT20 的温度、湿度、露点和一句体感
+//!
+//! 气压计、温湿度、距离几个页面之间用 Up/Down 切换，Back 回仪表盘。
+//! 露点是按 `humidity::dew_point_centi` 的简化公式算的，体感按湿度粗分：30% 以下干、60% 以上潮。
+//! 没接温湿度传感器的时候只显示一行提示。大字模式下只显示温度和湿度。
+
+use core::cell::RefCell;
+use core::convert::Infallible;
+
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use heapless::String;
+
+use crate::app::{Canvas, Event, Page, PageId, Transition};
+use crate::humidity::dew_point_centi;
+use crate::input::{Button, ButtonEvent};
+use crate::large_text::draw_large_readings;
+use crate::sensors::{ChannelId, ChannelInfo, SensorRegistry};
+use crate::text::draw_centered;
+use crate::widgets::{draw_menu_row, MENU_ROW_HEIGHT};
+
+/// 标题行基线
+const TITLE_Y: i32 = 8;
+
+/// 第一行的顶边
+const FIRST_ROW_TOP: i32 = 12;
+
+/// 湿度低于这个算干、高于这个算潮(0.01%)
+const DRY_BELOW: i32 = 3000;
+const HUMID_ABOVE: i32 = 6000;
+
+/// 露点的显示格式，和温度一样是 0.01°C
+const DEW_POINT: ChannelInfo = ChannelInfo::new("dew", "C", 100);
+
+/// 温湿度传感器注册到传感器表里的通道
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ComfortChannels {
+    /// 芯片型号，标题上显示
+    pub name: &'static str,
+    pub temperature: ChannelId,
+    pub humidity: ChannelId,
+}
+
+/// 温湿度页面
+pub struct ComfortPage<'a, S> {
+    sensors: &'a RefCell<SensorRegistry<S>>,
+    /// 没接温湿度传感器就是 None
+    channels: Option<ComfortChannels>,
+    /// Up/Down 切到哪两个页面
+    prev: PageId,
+    next: PageId,
+    /// 上次画的时候注册表的 `generation`
+    shown_generation: Option<u32>,
+}
+
+impl<'a, S> ComfortPage<'a, S> {
+    pub const fn new(
+        sensors: &'a RefCell<SensorRegistry<S>>,
+        channels: Option<ComfortChannels>,
+        prev: PageId,
+        next: PageId,
+    ) -> Self {
+        Self {
+            sensors,
+            channels,
+            prev,
+            next,
+            shown_generation: None,
+        }
+    }
+}
+
+/// 第 `row` 行的顶边
+const fn row_top(row: i32) -> i32 {
+    FIRST_ROW_TOP + row * MENU_ROW_HEIGHT
+}
+
+impl<S> Page for ComfortPage<'_, S> {
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        match event {
+            Event::Button(ButtonEvent::Pressed(Button::Back)) => Transition::Pop,
+            Event::Button(ButtonEvent::Pressed(Button::Up)) => Transition::Replace(self.prev),
+            Event::Button(ButtonEvent::Pressed(Button::Down)) => Transition::Replace(self.next),
+            _ => Transition::None,
+        }
+    }
+
+    fn desired_fps(&self) -> u16 {
+        2
+    }
+
+    /// 没接传感器的话轮播跳过这一页
+    fn has_data(&self) -> bool {
+        self.channels.is_some()
+    }
+
+    fn tick(&mut self, _now_ms: u64) -> bool {
+        let generation = self.sensors.borrow().generation();
+        let changed = self.shown_generation != Some(generation);
+        self.shown_generation = Some(generation);
+        changed
+    }
+
+    /// 大字版：温度和湿度，没接传感器就放大原来的提示
+    fn render_large(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<bool, Infallible> {
+        let Some(channels) = self.channels else {
+            return Ok(false);
+        };
+        let sensors = self.sensors.borrow();
+        let mut temperature: String<16> = String::new();
+        let mut humidity: String<16> = String::new();
+        if let Some(channel) = sensors.get(channels.temperature) {
+            let _ = channel
+                .info()
+                .write_value(channel.value(), &mut temperature);
+        }
+        if let Some(channel) = sensors.get(channels.humidity) {
+            let _ = channel.info().write_value(channel.value(), &mut humidity);
+        }
+        draw_large_readings(canvas, &[("temp", &temperature), ("hum", &humidity)])?;
+        Ok(true)
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<(), Infallible> {
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        let Some(channels) = self.channels else {
+            draw_centered(canvas, "comfort", TITLE_Y, style)?;
+            return draw_centered(canvas, "no SHT31/AHT20", 36, style);
+        };
+        draw_centered(canvas, channels.name, TITLE_Y, style)?;
+
+        let sensors = self.sensors.borrow();
+        let temperature = sensors.get(channels.temperature);
+        let humidity = sensors.get(channels.humidity);
+        let temp_centi = temperature.and_then(|c| c.value());
+        let humidity_centi = humidity.and_then(|c| c.value());
+        let mut value: String<24> = String::new();
+
+        if let Some(temperature) = temperature {
+            let _ = temperature.info().write_value(temp_centi, &mut value);
+            draw_menu_row(canvas, row_top(0), "temp", &value, false)?;
+        }
+        if let Some(humidity) = humidity {
+            value.clear();
+            let _ = humidity.info().write_value(humidity_centi, &mut value);
+            draw_menu_row(canvas, row_top(1), "hum", &value, false)?;
+        }
+
+        value.clear();
+        let dew_point = temp_centi
+            .zip(humidity_centi)
+            .map(|(t, rh)| dew_point_centi(t, rh));
+        let _ = DEW_POINT.write_value(dew_point, &mut value);
+        draw_menu_row(canvas, row_top(2), "dew", &value, false)?;
+
+        let feel = match humidity_centi {
+            None => "--",
+            Some(rh) if rh < DRY_BELOW => "dry",
+            Some(rh) if rh > HUMID_ABOVE => "humid",
+            Some(_) => "ok",
+        };
+        draw_menu_row(canvas, row_top(3), "feel", feel, false)
+    }
+}
@@ -0,0 +1,334 @@
+//! SSD1306 命令编码：只负责把命令变成字节，不碰总线
+//!
+//! 每个函数都是纯函数，返回的字节和手册(SSD1306 datasheet 第 9、10 章)里的命令表一一对应，
+//! 发送交给 `Display`(通过 `WriteOnlyDataCommand`)。这样命令格式对不对只看这个文件就行，
+//! 和 I2C、DMA 这些传输细节没有关系。
+//!
+//! 多条命令可以拼在一起一次发出去：I2C 上控制字节 0x00(Co=0, D/C#=0)后面跟的所有字节都会被当作命令。
+
+use ssd1306::prelude::DisplayRotation;
+
+use crate::framebuffer::{DirtyRegion, HEIGHT};
+use crate::panel::PANEL;
+
+/// 初始化序列的长度
+pub const INIT_SEQUENCE_LEN: usize = 26;
+
+/// 时钟分频(0xD5)的参数：高 4 位振荡频率档位 8，低 4 位分频比减一(0 就是不分频)
+pub const CLOCK_DIVIDE: u8 = 0x80;
+
+/// 预充电周期(0xD9)的参数：低 4 位 phase1、高 4 位 phase2，单位 DCLK
+pub const PRECHARGE: u8 = 0x21;
+
+/// 振荡频率档位 8 的典型频率(Hz)。手册只给了这一档的典型值，不同的片子差 ±10% 左右，
+/// 改了 `CLOCK_DIVIDE` 的高 4 位就要跟着改这个
+const OSC_HZ: u64 = 370_000;
+
+/// 屏幕刷一整屏的周期(微秒)，手册 10.1.15：帧率 = Fosc / (分频比 * 每行 DCLK * 复用率)，
+/// 每行 DCLK = phase1 + phase2 + 50。128x64 上大约 9.2ms(109Hz)，128x32 上减半
+pub const fn refresh_period_us(clock_divide: u8, precharge: u8, rows: u32) -> u32 {
+    let divide = (clock_divide & 0x0F) as u64 + 1;
+    let dclks = (precharge & 0x0F) as u64 + (precharge >> 4) as u64 + 50;
+    (divide * dclks * rows as u64 * 1_000_000 / OSC_HZ) as u32
+}
+
+/// 按初始化序列里的参数算出来的刷新周期，帧同步用(见 `app::Scheduler::set_frame_pacing`)
+pub const REFRESH_PERIOD_US: u32 = refresh_period_us(CLOCK_DIVIDE, PRECHARGE, HEIGHT as u32);
+
+/// 水平滚动的方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum ScrollDirection {
+    /// 内容往右移(0x26)
+    Right = 0x26,
+    /// 内容往左移(0x27)
+    Left = 0x27,
+}
+
+/// 滚动速度：每隔几帧移动一列。注意编码不是按大小顺序排的(手册 10.2.1)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum ScrollSpeed {
+    Frames2 = 0b111,
+    Frames3 = 0b100,
+    Frames4 = 0b101,
+    Frames5 = 0b000,
+    Frames25 = 0b110,
+    Frames64 = 0b001,
+    Frames128 = 0b010,
+    Frames256 = 0b011,
+}
+
+/// 对比度(0x81)
+pub const fn contrast(level: u8) -> [u8; 2] {
+    [0x81, level]
+}
+
+/// 开关显示(0xAE 关 / 0xAF 开)，关掉的时候屏幕内部显存保留
+pub const fn display_on(on: bool) -> [u8; 1] {
+    [0xAE | on as u8]
+}
+
+/// 电荷泵(0x8D, 0x14 开 / 0x10 关)。关机的时候和 `display_on(false)` 一起关，屏幕几乎不耗电
+pub const fn charge_pump(on: bool) -> [u8; 2] {
+    [0x8D, 0x10 | (on as u8) << 2]
+}
+
+/// 反色显示(0xA6 正常 / 0xA7 反色)，只影响显示，不改显存
+pub const fn invert(inverted: bool) -> [u8; 1] {
+    [0xA6 | inverted as u8]
+}
+
+/// 列地址范围(0x21)，水平寻址模式下接下来的数据从 `start` 列开始写
+pub const fn column_address(start: u8, end: u8) -> [u8; 3] {
+    [0x21, start, end]
+}
+
+/// 页地址范围(0x22)
+pub const fn page_address(start: u8, end: u8) -> [u8; 3] {
+    [0x22, start & 0x07, end & 0x07]
+}
+
+/// 列 + 页地址，一次设置好 flush 要写的窗口
+pub const fn window(region: DirtyRegion) -> [u8; 6] {
+    let [c0, c1, c2] = column_address(region.first_col, region.last_col);
+    let [p0, p1, p2] = page_address(region.first_page, region.last_page);
+    [c0, c1, c2, p0, p1, p2]
+}
+
+/// 屏幕方向：段重映射(0xA0/0xA1) + COM 扫描方向(0xC0/0xC8)
+///
+/// 90/270 度在硬件上只是翻转，行列互换是 `FrameBuffer` 在软件里做的，和 ssd1306 crate 的做法一样
+pub const fn rotation(rotation: DisplayRotation) -> [u8; 2] {
+    let (segment_remap, reverse_com) = match rotation {
+        DisplayRotation::Rotate0 => (true, true),
+        DisplayRotation::Rotate90 => (false, true),
+        DisplayRotation::Rotate180 => (false, false),
+        DisplayRotation::Rotate270 => (true, false),
+    };
+    [0xA0 | segment_remap as u8, 0xC0 | (reverse_com as u8) << 3]
+}
+
+/// 在 `rotation` 的基础上再镜像：还是那两条命令，只是把对应的位反过来
+///
+/// `horizontal`/`vertical` 是按转完之后看到的画面说的左右、上下。90/270 度的时候行列是软件互换的，
+/// 看到的左右对应的是硬件的 COM 扫描方向，所以两个位要跟着换一下
+pub const fn mirrored(rotation_: DisplayRotation, horizontal: bool, vertical: bool) -> [u8; 2] {
+    let (flip_segments, flip_com) = match rotation_ {
+        DisplayRotation::Rotate0 | DisplayRotation::Rotate180 => (horizontal, vertical),
+        DisplayRotation::Rotate90 | DisplayRotation::Rotate270 => (vertical, horizontal),
+    };
+    let [remap, scan] = rotation(rotation_);
+    [remap ^ flip_segments as u8, scan ^ (flip_com as u8) << 3]
+}
+
+/// 设置水平滚动(0x26/0x27)，`start_page`..=`end_page` 这几页一起滚。设置之后要 `activate_scroll(true)` 才开始
+///
+/// 手册要求设置之前先停掉滚动，这个函数不管，由调用方保证
+pub const fn horizontal_scroll(
+    direction: ScrollDirection,
+    start_page: u8,
+    end_page: u8,
+    speed: ScrollSpeed,
+) -> [u8; 7] {
+    [
+        direction as u8,
+        0x00, // 固定的空字节
+        start_page & 0x07,
+        speed as u8,
+        end_page & 0x07,
+        0x00, // 固定的空字节
+        0xFF, // 固定的 0xFF
+    ]
+}
+
+/// 开始(0x2F)/停止(0x2E)滚动。停止之后显存要重写一遍，滚动会把显存内容搞乱
+pub const fn activate_scroll(active: bool) -> [u8; 1] {
+    [0x2E | active as u8]
+}
+
+/// 完整初始化序列，水平寻址模式，和 ssd1306 crate 的 `init_with_addr_mode` 发的一样。复用率和 COM 引脚配置按 `PANEL` 选
+pub const fn init_sequence(rotation_: DisplayRotation, contrast_: u8) -> [u8; INIT_SEQUENCE_LEN] {
+    let [r0, r1] = rotation(rotation_);
+    [
+        0xAE, // 先关显示
+        0xD5,
+        CLOCK_DIVIDE, // 时钟分频：振荡频率 8，分频 1
+        0xA8,
+        HEIGHT as u8 - 1, // 复用率 = 行数 - 1
+        0xD3,
+        0x00, // 显示偏移 0
+        0x40, // 起始行 0
+        0x8D,
+        0x14, // 打开电荷泵(模块上没有外部升压电路，必须开)
+        0x20,
+        0x00, // 水平寻址模式
+        0xDA,
+        PANEL.com_pins, // COM 引脚配置：64 行用"交替"模式，32 行用"顺序"模式
+        r0,
+        r1, // 方向
+        0xD9,
+        PRECHARGE, // 预充电周期：phase1 = 1，phase2 = 2
+        0x81,
+        contrast_, // 对比度
+        0xDB,
+        0x40, // VCOMH 电压：自动
+        0xA4, // 按显存内容显示(不是全亮)
+        0xA6, // 不反色
+        0x2E, // 停止滚动
+        0xAF, // 打开显示
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use display_interface::{DataFormat, DisplayError, WriteOnlyDataCommand};
+    use ssd1306::command::{AddrMode, Command, HScrollDir, NFrames, Page};
+    use ssd1306::Ssd1306;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::vec::Vec;
+
+    use crate::display::PanelSize;
+
+    /// 把 ssd1306 crate 发出来的命令字节记下来，数据不管。`Ssd1306` 不把接口还回来，所以字节放在共享的 Vec 里
+    #[derive(Default, Clone)]
+    struct Recorder(Rc<RefCell<Vec<u8>>>);
+
+    impl Recorder {
+        fn bytes(&self) -> Vec<u8> {
+            self.0.borrow().clone()
+        }
+    }
+
+    impl WriteOnlyDataCommand for Recorder {
+        fn send_commands(&mut self, cmd: DataFormat<'_>) -> Result<(), DisplayError> {
+            match cmd {
+                DataFormat::U8(bytes) => self.0.borrow_mut().extend_from_slice(bytes),
+                _ => return Err(DisplayError::DataFormatNotImplemented),
+            }
+            Ok(())
+        }
+
+        fn send_data(&mut self, _buf: DataFormat<'_>) -> Result<(), DisplayError> {
+            Ok(())
+        }
+    }
+
+    fn reference(command: Command) -> Vec<u8> {
+        let mut recorder = Recorder::default();
+        command.send(&mut recorder).unwrap();
+        recorder.bytes()
+    }
+
+    const ROTATIONS: [DisplayRotation; 4] = [
+        DisplayRotation::Rotate0,
+        DisplayRotation::Rotate90,
+        DisplayRotation::Rotate180,
+        DisplayRotation::Rotate270,
+    ];
+
+    #[test]
+    fn single_commands_match_ssd1306() {
+        for level in [0, 0x5F, 0xFF] {
+            assert_eq!(contrast(level)[..], reference(Command::Contrast(level)));
+        }
+        for on in [false, true] {
+            assert_eq!(display_on(on)[..], reference(Command::DisplayOn(on)));
+            assert_eq!(charge_pump(on)[..], reference(Command::ChargePump(on)));
+            assert_eq!(invert(on)[..], reference(Command::Invert(on)));
+            assert_eq!(
+                activate_scroll(on)[..],
+                reference(Command::EnableScroll(on))
+            );
+        }
+        assert_eq!(
+            column_address(3, 120)[..],
+            reference(Command::ColumnAddress(3, 120))
+        );
+        for (start, end) in [(0, 7), (2, 5), (7, 7)] {
+            assert_eq!(
+                page_address(start, end)[..],
+                reference(Command::PageAddress(
+                    Page::from(start * 8),
+                    Page::from(end * 8)
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn window_is_column_then_page_address() {
+        let region = DirtyRegion {
+            first_col: 10,
+            last_col: 100,
+            first_page: 1,
+            last_page: 6,
+        };
+        let mut expected = reference(Command::ColumnAddress(10, 100));
+        expected.extend(reference(Command::PageAddress(Page::Page1, Page::Page6)));
+        assert_eq!(window(region)[..], expected);
+    }
+
+    #[test]
+    fn rotation_matches_set_rotation() {
+        for rot in ROTATIONS {
+            let recorder = Recorder::default();
+            let mut display = Ssd1306::new(recorder.clone(), PanelSize {}, rot);
+            display.set_rotation(rot).unwrap();
+            assert_eq!(rotation(rot)[..], recorder.bytes(), "{rot:?}");
+        }
+    }
+
+    #[test]
+    fn unmirrored_is_plain_rotation() {
+        for rot in ROTATIONS {
+            assert_eq!(mirrored(rot, false, false), rotation(rot));
+        }
+        // 0 度镜像左右就是不做段重映射
+        assert_eq!(
+            mirrored(DisplayRotation::Rotate0, true, false)[..],
+            [
+                reference(Command::SegmentRemap(false)),
+                reference(Command::ReverseComDir(true))
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn horizontal_scroll_matches_ssd1306() {
+        let cases = [
+            (ScrollDirection::Right, HScrollDir::LeftToRight),
+            (ScrollDirection::Left, HScrollDir::RightToLeft),
+        ];
+        let speeds = [
+            (ScrollSpeed::Frames2, NFrames::F2),
+            (ScrollSpeed::Frames3, NFrames::F3),
+            (ScrollSpeed::Frames4, NFrames::F4),
+            (ScrollSpeed::Frames5, NFrames::F5),
+            (ScrollSpeed::Frames25, NFrames::F25),
+            (ScrollSpeed::Frames64, NFrames::F64),
+            (ScrollSpeed::Frames128, NFrames::F128),
+            (ScrollSpeed::Frames256, NFrames::F256),
+        ];
+        for (direction, dir) in cases {
+            for (speed, frames) in speeds {
+                assert_eq!(
+                    horizontal_scroll(direction, 0, 7, speed)[..],
+                    reference(Command::HScrollSetup(dir, Page::Page0, Page::Page7, frames))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn init_sequence_matches_init_with_addr_mode() {
+        for rot in ROTATIONS {
+            let recorder = Recorder::default();
+            let mut display = Ssd1306::new(recorder.clone(), PanelSize {}, rot);
+            display.init_with_addr_mode(AddrMode::Horizontal).unwrap();
+            // ssd1306 crate 默认的亮度是 Brightness::NORMAL，对比度 0x5F
+            assert_eq!(init_sequence(rot, 0x5F)[..], recorder.bytes(), "{rot:?}");
+        }
+    }
+}
@@ -0,0 +1,380 @@
+//! 窗口合成：屏幕分成几块互不重叠的矩形，每块(窗口)自己决定要不要重画，只重画、只发送变了的那几块
+//!
+//! 适合一屏上有好几个小部件、各自数据更新频率不一样的情况(比如仪表盘上面的开机时长每秒变、
+//! 下面每个传感器通道有新读数才变，见 dashboard.rs)。
+//!
+//! ## 坐标
+//!
+//! - 窗口的位置 `area` 用的是逻辑坐标，也就是和页面画图一样、考虑了屏幕旋转之后的坐标。
+//!   屏幕转了方向要用 `set_areas` 换一套位置
+//! - 画窗口内容的时候拿到的是 `ClippedTarget` 画布，原点在窗口左上角，超出窗口的部分自动裁掉，
+//!   所以同一个部件放在哪个窗口里都不用改代码。embedded-graphics 自带的 `Cropped` 只平移不裁剪，
+//!   画出界会画到相邻窗口上，所以不能用
+//! - flush 的时候把所有脏窗口的并集换算成物理坐标(90/270 度旋转时宽高互换)，再扩到整页(8 行)，
+//!   因为 SSD1306 最小只能按页写。窗口上下边不在 8 的倍数上的话，会顺带重发相邻窗口的几行，
+//!   那几行显存本来就是对的，所以不影响显示，只是多发几个字节
+//!
+//! ## 两种用法
+//!
+//! - 在页面里(`Page::render_dirty`)：`compose` 只把脏窗口画进显存，调度器照常 flush，
+//!   显存记下的脏区域落在这几个窗口的并集里
+//! - 自己管整个屏幕：`flush` 画完马上把并集发出去(阻塞)
+//!
+//! ## 限制
+//!
+//! - 窗口不能重叠，`Compositor::new` 在 debug 编译下会检查。重叠的话后画的窗口会把先画的盖掉一部分，
+//!   而先画的窗口不知道自己被盖了
+//! - 用合成器的时候窗口覆盖的地方都归它管，不要再在别的地方往那里画：没标脏的窗口不会重画，画上去的东西一直留着
+
+use core::convert::Infallible;
+
+use display_interface::{DisplayError, WriteOnlyDataCommand};
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::{OriginDimensions, Point, Size};
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::Pixel;
+
+use crate::display::Display;
+use crate::framebuffer::FrameBuffer;
+use crate::theme::PatternTarget;
+
+/// 一个窗口：一块矩形区域 + 要不要重画
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Window {
+    area: Rectangle,
+    dirty: bool,
+}
+
+impl Window {
+    /// 新建的窗口是脏的，第一次合成的时候一定会画
+    pub const fn new(area: Rectangle) -> Self {
+        Self { area, dirty: true }
+    }
+
+    /// 窗口在屏幕上的位置(逻辑坐标)
+    pub fn area(&self) -> Rectangle {
+        self.area
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// 标记为要重画
+    pub fn invalidate(&mut self) {
+        self.dirty = true;
+    }
+}
+
+/// 画一个窗口用的画布：原点平移到窗口左上角，窗口以外的点丢掉
+#[derive(Debug)]
+pub struct ClippedTarget<'a, D> {
+    parent: &'a mut D,
+    area: Rectangle,
+}
+
+impl<'a, D: DrawTarget<Color = BinaryColor>> ClippedTarget<'a, D> {
+    /// `area` 是窗口在 `parent` 上的位置，超出 `parent` 的部分也裁掉
+    pub fn new(parent: &'a mut D, area: Rectangle) -> Self {
+        let area = area.intersection(&parent.bounding_box());
+        Self { parent, area }
+    }
+
+    /// 窗口坐标下的一块矩形换算成 `parent` 上的坐标，裁到窗口里面
+    fn to_parent(&self, area: &Rectangle) -> Rectangle {
+        Rectangle::new(area.top_left + self.area.top_left, area.size).intersection(&self.area)
+    }
+}
+
+impl<D: DrawTarget<Color = BinaryColor>> OriginDimensions for ClippedTarget<'_, D> {
+    fn size(&self) -> Size {
+        self.area.size
+    }
+}
+
+impl<D: DrawTarget<Color = BinaryColor>> DrawTarget for ClippedTarget<'_, D> {
+    type Color = BinaryColor;
+    type Error = D::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<BinaryColor>>,
+    {
+        let area = self.area;
+        self.parent.draw_iter(
+            pixels
+                .into_iter()
+                .map(|Pixel(point, color)| Pixel(point + area.top_left, color))
+                .filter(|Pixel(point, _)| area.contains(*point)),
+        )
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: BinaryColor) -> Result<(), Self::Error> {
+        let area = self.to_parent(area);
+        self.parent.fill_solid(&area, color)
+    }
+}
+
+/// 花纹跟着屏幕对齐，所以不能按窗口坐标交给 `parent` 去填，一个点一个点画
+impl<D: PatternTarget> PatternTarget for ClippedTarget<'_, D> {}
+
+/// 窗口合成器，管着 `N` 个窗口
+#[derive(Debug)]
+pub struct Compositor<const N: usize> {
+    windows: [Window; N],
+}
+
+impl<const N: usize> Compositor<N> {
+    /// `areas` 是每个窗口的位置，后面用下标来指窗口。窗口不能重叠，面积是 0 的窗口不画
+    pub fn new(areas: [Rectangle; N]) -> Self {
+        check_no_overlap(&areas);
+        Self {
+            windows: areas.map(Window::new),
+        }
+    }
+
+    /// 换一套位置(比如屏幕转了方向)，全部标脏
+    pub fn set_areas(&mut self, areas: [Rectangle; N]) {
+        check_no_overlap(&areas);
+        self.windows = areas.map(Window::new);
+    }
+
+    pub fn window(&self, index: usize) -> &Window {
+        &self.windows[index]
+    }
+
+    /// 第 `index` 个窗口的数据变了，下一次合成重画它
+    pub fn invalidate(&mut self, index: usize) {
+        if let Some(window) = self.windows.get_mut(index) {
+            window.invalidate();
+        }
+    }
+
+    /// 全部重画，比如整屏清过了之后
+    pub fn invalidate_all(&mut self) {
+        self.windows.iter_mut().for_each(Window::invalidate);
+    }
+
+    /// 有没有窗口要重画
+    pub fn is_dirty(&self) -> bool {
+        self.windows.iter().any(Window::is_dirty)
+    }
+
+    /// 所有脏窗口的并集(包住它们的最小矩形)，没有脏窗口是 None
+    pub fn dirty_area(&self) -> Option<Rectangle> {
+        self.windows
+            .iter()
+            .filter(|window| window.dirty)
+            .map(Window::area)
+            .fold(None, |union, area| Some(union_of(union, area)))
+    }
+
+    /// 重画所有脏窗口，返回重画了的那几块的并集。`draw(下标, 画布)` 负责画一个窗口的内容，
+    /// 调用之前窗口已经清空了，画布的原点在窗口左上角
+    pub fn compose<D, F>(
+        &mut self,
+        target: &mut D,
+        mut draw: F,
+    ) -> Result<Option<Rectangle>, D::Error>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+        F: FnMut(usize, &mut ClippedTarget<'_, D>) -> Result<(), D::Error>,
+    {
+        let redrawn = self.dirty_area();
+        for (index, window) in self.windows.iter_mut().enumerate() {
+            if !window.dirty {
+                continue;
+            }
+            window.dirty = false;
+            if window.area.is_zero_sized() {
+                continue;
+            }
+            target.fill_solid(&window.area, BinaryColor::Off)?;
+            draw(index, &mut ClippedTarget::new(target, window.area))?;
+        }
+        Ok(redrawn)
+    }
+
+    /// 重画所有脏窗口并且马上把它们的并集 flush 出去(阻塞)，`draw` 和 `compose` 一样
+    ///
+    /// 显存里累计的脏区域一起清掉：窗口以外的地方归别人管的话，要在这之前自己 flush
+    pub fn flush<DI, F>(&mut self, display: &mut Display<DI>, draw: F) -> Result<(), DisplayError>
+    where
+        DI: WriteOnlyDataCommand,
+        F: FnMut(usize, &mut ClippedTarget<'_, FrameBuffer>) -> Result<(), Infallible>,
+    {
+        let Ok(redrawn) = self.compose(display.framebuffer_mut(), draw);
+        let region = redrawn.and_then(|area| display.framebuffer().region_for(area));
+        let _ = display.framebuffer_mut().take_dirty();
+        let Some(region) = region else {
+            return Ok(());
+        };
+        let result = display.flush_region(region);
+        if result.is_err() {
+            // 和 `Display::flush` 一样，发失败了下次整屏重发
+            display.framebuffer_mut().mark_all_dirty();
+        }
+        result
+    }
+}
+
+/// 两块矩形(第一块可以没有)的并集
+fn union_of(union: Option<Rectangle>, area: Rectangle) -> Rectangle {
+    let Some(bottom_right) = area.bottom_right() else {
+        return union.unwrap_or(area);
+    };
+    let Some((top_left, other)) = union.and_then(|u| Some((u.top_left, u.bottom_right()?))) else {
+        return area;
+    };
+    Rectangle::with_corners(
+        Point::new(
+            top_left.x.min(area.top_left.x),
+            top_left.y.min(area.top_left.y),
+        ),
+        Point::new(other.x.max(bottom_right.x), other.y.max(bottom_right.y)),
+    )
+}
+
+fn check_no_overlap(areas: &[Rectangle]) {
+    debug_assert!(
+        (0..areas.len()).all(|i| (i + 1..areas.len())
+            .all(|j| areas[i].intersection(&areas[j]).is_zero_sized())),
+        "compositor windows overlap"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use display_interface::DataFormat;
+    use embedded_graphics::geometry::Size;
+    use embedded_graphics::primitives::{Primitive, PrimitiveStyle};
+    use embedded_graphics::Drawable;
+    use ssd1306::prelude::DisplayRotation;
+
+    use crate::framebuffer::DirtyRegion;
+
+    /// 只数发了多少数据字节
+    #[derive(Default)]
+    struct Sink {
+        data: usize,
+    }
+
+    impl WriteOnlyDataCommand for Sink {
+        fn send_commands(&mut self, _cmd: DataFormat<'_>) -> Result<(), DisplayError> {
+            Ok(())
+        }
+
+        fn send_data(&mut self, buf: DataFormat<'_>) -> Result<(), DisplayError> {
+            if let DataFormat::U8(bytes) = buf {
+                self.data += bytes.len();
+            }
+            Ok(())
+        }
+    }
+
+    const LEFT: Rectangle = Rectangle::new(Point::new(0, 0), Size::new(32, 16));
+    const RIGHT: Rectangle = Rectangle::new(Point::new(96, 0), Size::new(32, 16));
+    const BOTTOM: Rectangle = Rectangle::new(Point::new(0, 40), Size::new(128, 8));
+
+    /// 每个窗口画一个满格的框，记下画了哪几个
+    fn fill_all<D: DrawTarget<Color = BinaryColor>>(
+        drawn: &mut std::vec::Vec<usize>,
+    ) -> impl FnMut(usize, &mut ClippedTarget<'_, D>) -> Result<(), D::Error> + '_ {
+        move |index, canvas| {
+            drawn.push(index);
+            Rectangle::new(Point::zero(), Size::new(200, 200))
+                .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+                .draw(canvas)
+        }
+    }
+
+    #[test]
+    fn redraws_only_dirty_windows_clipped_to_their_area() {
+        let mut fb = FrameBuffer::new(DisplayRotation::Rotate0);
+        let mut windows = Compositor::new([LEFT, RIGHT, BOTTOM]);
+        let mut drawn = std::vec::Vec::new();
+        let area = windows.compose(&mut fb, fill_all(&mut drawn)).unwrap();
+        assert_eq!(drawn, [0, 1, 2]);
+        assert_eq!(
+            area,
+            Some(Rectangle::new(Point::zero(), Size::new(128, 48)))
+        );
+        // 画出界的部分裁掉了
+        assert!(fb.pixel(31, 15) && !fb.pixel(32, 15) && !fb.pixel(0, 16));
+        assert!(!windows.is_dirty());
+
+        let _ = fb.take_dirty();
+        drawn.clear();
+        windows.invalidate(1);
+        let area = windows.compose(&mut fb, fill_all(&mut drawn)).unwrap();
+        assert_eq!((drawn.as_slice(), area), (&[1][..], Some(RIGHT)));
+        // 显存记下的脏区域没超出这个窗口
+        assert_eq!(fb.take_dirty(), fb.region_for(RIGHT));
+        assert_eq!(
+            windows.compose(&mut fb, fill_all(&mut drawn)).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn dirty_area_is_the_union() {
+        let mut windows = Compositor::new([LEFT, RIGHT, BOTTOM]);
+        let _ = windows.compose(&mut FrameBuffer::new(DisplayRotation::Rotate0), |_, _| {
+            Ok(())
+        });
+        assert_eq!(windows.dirty_area(), None);
+        windows.invalidate(0);
+        windows.invalidate(2);
+        assert_eq!(
+            windows.dirty_area(),
+            Some(Rectangle::new(Point::zero(), Size::new(128, 48)))
+        );
+        // 面积是 0 的窗口不算，也不画
+        let mut windows = Compositor::new([LEFT, Rectangle::zero()]);
+        assert_eq!(windows.dirty_area(), Some(LEFT));
+        let mut drawn = std::vec::Vec::new();
+        let mut fb = FrameBuffer::new(DisplayRotation::Rotate0);
+        windows.compose(&mut fb, fill_all(&mut drawn)).unwrap();
+        assert_eq!(drawn, [0]);
+    }
+
+    #[test]
+    fn flush_sends_the_pages_of_the_union() {
+        let mut display = Display::new(Sink::default(), DisplayRotation::Rotate0);
+        let mut windows = Compositor::new([LEFT, RIGHT, BOTTOM]);
+        let mut drawn = std::vec::Vec::new();
+        windows.flush(&mut display, fill_all(&mut drawn)).unwrap();
+        // 0..48 行是 6 页，整行宽
+        assert_eq!(display.interface_mut().data, 6 * 128);
+        assert_eq!(display.framebuffer().dirty(), None);
+
+        windows.invalidate(0);
+        windows.invalidate(1);
+        windows.flush(&mut display, fill_all(&mut drawn)).unwrap();
+        // 左右两块的并集：两页，中间没变的几列也带上
+        assert_eq!(display.interface_mut().data, 6 * 128 + 2 * 128);
+    }
+
+    #[test]
+    fn region_follows_rotation() {
+        let fb = FrameBuffer::new(DisplayRotation::Rotate90);
+        // 转 90 度以后逻辑上的一行是物理上的一列
+        let region = fb.region_for(Rectangle::new(Point::new(0, 3), Size::new(9, 1)));
+        assert_eq!(
+            region,
+            Some(DirtyRegion {
+                first_col: 3,
+                last_col: 3,
+                first_page: 0,
+                last_page: 1,
+            })
+        );
+        assert_eq!(
+            fb.region_for(Rectangle::new(Point::new(500, 0), Size::new(4, 4))),
+            None
+        );
+    }
+}
@@ -0,0 +1,120 @@
+//! 确认框：清零、擦除这种做了就回不去的操作，先在屏幕中间弹一个框问一下
+//!
+//! 页面返回 `Transition::Confirm`(带着问题和确定以后要做的 `Action`)，调度器把框挂在这个页面上面，
+//! 按键先交给框：Up/Down 在 "no"、"yes" 之间换，默认选着 "no"，Select 确定，Back 等于选 "no"。
+//! 选完了框就关掉，结果用 `Event::Confirmed` 发回给问的那个页面，选了 "yes" 的话由页面自己交出动作。
+//!
+//! 框不是单独的页面，只在问它的那个页面在最上面的时候显示：闹钟响了、告警弹窗这种盖上来的页面关掉以后，
+//! 框还在原来的地方等着。问的页面被关掉了(比如回到了首页)框就跟着没了，当作选了 "no"。
+//! 打开 `overlay-layer` 的话框画在叠加层上，弹出、关掉都不用重画下面的页面；不开的话关掉以后整屏重画。
+
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::text::{Baseline, Text};
+use embedded_graphics::Drawable;
+
+use crate::app::Confirm;
+use crate::input::Button;
+use crate::text::{draw_centered, text_pixel_width};
+use crate::theme::{self, Fill, PatternTarget, Style};
+
+/// 框离屏幕左右边多远
+const MARGIN: u32 = 8;
+
+/// 框的高度：问题一行、选项一行
+const HEIGHT: u32 = 36;
+
+/// 两个选项，下标 0 是默认的
+const CHOICES: [&str; 2] = ["no", "yes"];
+
+/// 选项的宽度(高亮块)
+const CHOICE_WIDTH: u32 = 30;
+
+/// 正在问的确认框
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConfirmDialog {
+    request: Confirm,
+    /// 选着 "yes"
+    yes: bool,
+}
+
+impl ConfirmDialog {
+    pub const fn new(request: Confirm) -> Self {
+        Self {
+            request,
+            yes: false,
+        }
+    }
+
+    pub fn request(&self) -> Confirm {
+        self.request
+    }
+
+    /// 处理一次按下，选完了返回选的是不是 "yes"
+    pub fn on_press(&mut self, button: Button) -> Option<bool> {
+        match button {
+            Button::Up | Button::Down => {
+                self.yes = !self.yes;
+                None
+            }
+            Button::Select => Some(self.yes),
+            Button::Back => Some(false),
+        }
+    }
+
+    /// 画在屏幕中间：黑底白框，上面一行问题，下面两个选项，选着的那个反色
+    pub fn draw<D>(&self, display: &mut D) -> Result<(), D::Error>
+    where
+        D: PatternTarget,
+    {
+        self.draw_styled(display, &theme::DEFAULT.dialog)
+    }
+
+    /// 按 `style` 画(见 `theme`)，选着的那个选项按填充方式涂
+    pub fn draw_styled<D>(&self, display: &mut D, style: &Style) -> Result<(), D::Error>
+    where
+        D: PatternTarget,
+    {
+        let size = display.bounding_box().size;
+        let width = size.width.saturating_sub(2 * MARGIN);
+        let top = (size.height.saturating_sub(HEIGHT) / 2) as i32;
+        let area = Rectangle::new(Point::new(MARGIN as i32, top), Size::new(width, HEIGHT));
+        style.draw_frame(display, area, true)?;
+
+        let font = &FONT_6X10;
+        let text = MonoTextStyle::new(font, style.ink());
+        draw_centered(display, self.request.prompt, top + 13, text)?;
+
+        // 两个选项在框里左右各占一半，各自居中
+        let half = width / 2;
+        let choice_top = top + 20;
+        for (index, choice) in CHOICES.iter().enumerate() {
+            let selected = self.yes == (index == 1);
+            let left = MARGIN as i32 + (index as u32 * half + (half - CHOICE_WIDTH) / 2) as i32;
+            let color = if selected {
+                let highlight =
+                    Rectangle::new(Point::new(left, choice_top), Size::new(CHOICE_WIDTH, 12));
+                style.fill_area(display, highlight)?;
+                // 不涂的话只有一圈边，字还是原来的颜色
+                if style.fill == Fill::None {
+                    style.ink()
+                } else {
+                    style.paper()
+                }
+            } else {
+                style.ink()
+            };
+            let x = left + (CHOICE_WIDTH.saturating_sub(text_pixel_width(choice, font)) / 2) as i32;
+            Text::with_baseline(
+                choice,
+                Point::new(x, choice_top + 1),
+                MonoTextStyle::new(font, color),
+                Baseline::Top,
+            )
+            .draw(display)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,1579 @@
+//! USB 串口上的文本命令行
+//!
+//! 电脑端用任意串口终端(比如 `picocom /dev/ttyACM0`)连上来，一行一条命令，回车结束。
+//! Pico W 上连了 Wi-Fi 的话 TCP 端口上也是这一套(见 net_console.rs)。
+//! 这里只负责把字节流切成行、把行解析成命令；命令具体怎么执行由 main.rs 决定，
+//! 因为执行的时候要用到屏幕、flash 这些外设。退格删一个字，Ctrl-U 清掉这一行重新打。
+//!
+//! 所有命令都登记在 `COMMANDS` 表里：名字、每个参数的类型(`ArgSpec`)、写法、一句话说明。
+//! 拆参数、检查范围都在这一层做完，每条命令拿到的是类型对的参数(`Args`)，
+//! 参数不对统一回复 `ERR usage: <写法>`，不认识的命令提示开头最像的那条。`HELP` 按表列出所有命令，
+//! `HELP SETTINGS` 这样带主题的只列这几个词开头的。加命令 = 在 `ConsoleCommand` 加一个变体 + 表里加一条。
+//!
+//! 除了 `SETTINGS ...` 这些管理命令，还有几条直接控制屏幕的(`TEXT`、`CLEAR`、`BRIGHT`、`INVERT`、`IMG`)，
+//! 电脑端脚本可以把板子当成一块遥控小屏用。
+
+use core::fmt;
+
+use heapless::Vec;
+
+use crate::auto_brightness::{self, AutoBrightnessConfig};
+use crate::burn_in::{BurnInConfig, BurnInStrategy};
+use crate::demo_auto::DemoAutoConfig;
+use crate::display_profile;
+use crate::flash::IMAGE_SLOTS;
+use crate::low_voltage::LowVoltageConfig;
+use crate::pulse_counter::{self, PulseConfig, MAX_MIN_WIDTH_MS};
+use crate::settings::BASE64_CAPACITY;
+use crate::theme;
+use crate::timezone::{self, DstRule, TimeZone};
+use crate::vl53l0x::MAX_RANGE_MM;
+use crate::watch_face::{Layout, WidgetKind, SLOTS};
+
+use crate::sleep_clock::parse_hh_mm;
+
+/// 一行最长多少字节，超过的部分直接丢掉，这一整行作废
+pub const LINE_CAPACITY: usize = 192;
+
+// `SETTINGS LOAD` 要能一行放下整个设置
+const _: () = assert!("SETTINGS LOAD ".len() + BASE64_CAPACITY <= LINE_CAPACITY);
+
+/// 解析出来的命令
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsoleCommand<'a> {
+    /// `SETTINGS DUMP`：把当前设置用 base64 打印出来
+    SettingsDump,
+    /// `SETTINGS LOAD <base64>`：导入设置
+    SettingsLoad(&'a str),
+    /// `SETTINGS RESET`：恢复默认设置(校准值留着)，恢复模式里也能用
+    SettingsReset,
+    /// `SETTINGS MUTE ON|OFF`：蜂鸣器静音开关
+    SettingsMute(bool),
+    /// `SETTINGS LOG <秒>` 打开数据记录并设置间隔，`SETTINGS LOG OFF` 关掉(值是 None)
+    SettingsLog(Option<u16>),
+    /// `SETTINGS BURNIN SHIFT|INVERT <分钟>` 打开防烧屏，`SETTINGS BURNIN OFF` 关掉
+    SettingsBurnIn(BurnInConfig),
+    /// `SETTINGS SCREENOFF <分钟>` 多久没操作自动关屏，`SETTINGS SCREENOFF OFF` 不关(值是 None)
+    SettingsScreenOff(Option<u16>),
+    /// `SETTINGS WAKE <mm>` 关屏的时候手靠近多近亮屏，`SETTINGS WAKE OFF` 不用(值是 None)
+    SettingsWake(Option<u16>),
+    /// `SETTINGS DEMO <秒> [每个几秒]` 没人按键多久开始自动轮换演示，`SETTINGS DEMO OFF` 关掉
+    SettingsDemo(DemoAutoConfig),
+    /// `SETTINGS DERATE ON|OFF`：片内温度一直偏高的时候自动调暗
+    SettingsDerate(bool),
+    /// `SETTINGS PACING ON|OFF`：flush 对齐屏幕的刷新周期
+    SettingsPacing(bool),
+    /// `SETTINGS BATTLOW <警告 mV> <严重 mV>` 低电压保护的阈值，`SETTINGS BATTLOW OFF` 关掉
+    SettingsBattLow(LowVoltageConfig),
+    /// `SETTINGS AUTOBRIGHT <暗 mV> <亮 mV> [最暗] [最亮]` 跟着光敏电阻自动调亮度，`SETTINGS AUTOBRIGHT OFF` 关掉
+    SettingsAutoBright(AutoBrightnessConfig),
+    /// `SETTINGS TZ <±HH:MM> [OFF|ON|EU|US]`：时区和夏令时(见 timezone.rs)，不写夏令时就是不用
+    SettingsTimeZone(TimeZone),
+    /// `SETTINGS LARGE ON|OFF`：大字模式
+    SettingsLarge(bool),
+    /// `SETTINGS PROFILE INDOOR|SUNLIGHT|NIGHT`：换显示方案，值是方案的编号(见 display_profile.rs)
+    SettingsProfile(u8),
+    /// `SETTINGS THEME DEFAULT|HIGH-CONTRAST`：换主题，值是主题的编号(见 theme.rs)
+    SettingsTheme(u8),
+    /// `SETTINGS WIFI <名字> [密码]`：连这个 Wi-Fi 并存起来，`SETTINGS WIFI OFF` 断开不连(值是 None)，见 wifi_config.rs
+    #[cfg(feature = "pico-w")]
+    SettingsWifi(Option<(&'a str, &'a str)>),
+    /// `SETTINGS PULSE <每个脉冲多少> <单位> [最短脉宽 ms]`：脉冲计数的换算(见 pulse_counter.rs)，不写脉宽用默认的
+    SettingsPulse(PulseConfig),
+    /// `IMG BEGIN <槽> <宽> <高>`：开始往一个图片槽传图，槽从 1 数，这里已经减成从 0 数了
+    ImageBegin {
+        slot: usize,
+        width: u16,
+        height: u16,
+    },
+    /// `IMG DATA <base64>`：一块图片数据
+    ImageData(&'a str),
+    /// `IMG END <crc32 十六进制>`：传完了，校验通过写进 flash
+    ImageEnd(u32),
+    /// `IMG SHOW <槽> [x y]`：在遥控显示页面上画出一个槽里的图，不写位置就画在左上角
+    ImageShow { slot: usize, top_left: (i32, i32) },
+    /// `IMG LIST`：每个槽一行，空的、坏的、宽x高
+    ImageList,
+    /// `PREVIEW <宽> <高> <base64>`：一块预览图的数据，收齐了马上显示，不存 flash(见 devtools.rs)
+    #[cfg(feature = "devtools")]
+    Preview {
+        width: u16,
+        height: u16,
+        data: &'a str,
+    },
+    /// `PREVIEW AT <x> <y>`：预览图的左上角放在哪
+    #[cfg(feature = "devtools")]
+    PreviewAt(i32, i32),
+    /// `PREVIEW OFF`：关掉预览，回到原来的页面
+    #[cfg(feature = "devtools")]
+    PreviewOff,
+    /// `GRID ON|OFF`：预览上面叠 8 像素网格
+    #[cfg(feature = "devtools")]
+    Grid(bool),
+    /// `LOG DUMP`：把数据记录按 CSV 格式导出
+    LogDump,
+    /// `TELEM ON <每秒几行>` 打开串口遥测，`TELEM OFF` 关掉(值是 None)
+    Telemetry(Option<u16>),
+    /// `TELEM FIELDS temp,vsys`：遥测输出哪几列。列名要查传感器注册表，由 main.rs 解析(见 `telemetry::Fields::parse`)
+    TelemetryFields(&'a str),
+    /// `PULSE`：打印脉冲计数的总量和速率
+    Pulse,
+    /// `PULSE RESET`：脉冲计数清零
+    PulseReset,
+    /// `STATUS`：打一行状态(见 status.rs)
+    Status,
+    /// `REC START`：开始录输入(见 input_replay.rs)
+    RecordStart,
+    /// `REC STOP`：停止录或者放
+    RecordStop,
+    /// `REC PLAY [LOOP]`：把录下来的输入放一遍，带 LOOP 是一直放(值是 true)
+    RecordPlay(bool),
+    /// `STATUS ON <秒>` 每隔几秒打一行状态，`STATUS OFF` 停(值是 None)
+    StatusEvery(Option<u16>),
+    /// `CLOCK SET HH:MM`：对时(床头钟模式用)
+    ClockSet { hour: u8, minute: u8 },
+    /// `PERF`：打印每个页面的渲染耗时统计
+    Perf,
+    /// `PERF RESET`：耗时统计清零
+    PerfReset,
+    /// `PERF BUDGET <ms>`：一帧超过多少毫秒打警告，不存 flash
+    PerfBudget(u16),
+    /// `EVENTS`：打印事件日志，新的在前(见 event_log.rs)
+    Events,
+    /// `FACE`：打印表盘设置
+    Face,
+    /// `FACE LAYOUT GRID|BIG|FULL|SIX`：换表盘布局
+    FaceLayout(Layout),
+    /// `FACE <1-6> EMPTY|CLOCK|BATT|ALARMS`、`FACE <1-6> VALUE|BAR <通道>`：第几个格子放什么。
+    /// `slot` 从 0 开始，通道名要查传感器注册表，由 main.rs 解析
+    FaceSlot {
+        slot: usize,
+        kind: WidgetKind,
+        channel: Option<&'a str>,
+    },
+    /// `SCAN`：扫描 I2C 总线，列出应答了的地址(没接屏幕的时候用来查接线)
+    Scan,
+    /// `SYSINFO`：打印开机硬件小结(时钟、I2C、屏幕、传感器、USB、内存，见 system_info.rs)
+    SysInfo,
+    /// `FEATURES`：打印编译进来了哪些 feature、屏幕接口、板子、屏幕型号(见 capabilities.rs)
+    Features,
+    /// `FB`：把显存用十六进制从串口导出来，格式见 snapshot.rs
+    FrameDump,
+    /// `FB DEFMT`：同上，打到 defmt 日志里
+    FrameDumpDefmt,
+    /// `TEXT <文字>`：在遥控页面上显示这段文字(空格原样保留)
+    Text(&'a str),
+    /// `CLEAR`：清掉遥控页面上的文字
+    Clear,
+    /// `BRIGHT <0-255>`：临时调亮度，不存 flash
+    Bright(u8),
+    /// `INVERT 0|1`：反色显示，不存 flash
+    Invert(bool),
+    /// `HELP [命令]`：列出命令，带主题的话只列这几个词开头的(见 `Help`)
+    Help(Option<&'static str>),
+    /// 认识的命令但参数不对，带着用法说明
+    Usage(&'static str),
+    /// 太长被截断的行
+    TooLong,
+    /// 不认识的命令，带着最像的那条命令名(见 `closest_command`)
+    Unknown(Option<&'static str>),
+}
+
+impl<'a> ConsoleCommand<'a> {
+    /// 解析一行：在命令表里找名字对得上的词最多的那条，再按它的参数说明拆参数。
+    /// 命令名、关键字不区分大小写，别的参数原样保留
+    pub fn parse(line: &'a str) -> Self {
+        let line = line.trim();
+        let Some((spec, rest)) = find_command(line) else {
+            // 只打了 "IMG" 这样的前半截当成 HELP IMG，真不认识的在里面找最像的
+            return help_topic(Some(line));
+        };
+        spec.parse_args(rest)
+            .and_then(|args| (spec.build)(&args))
+            .unwrap_or(ConsoleCommand::Usage(spec.usage))
+    }
+
+    /// 回复太长、要在之后几圈按发送缓冲区的空分批打完的命令。main.rs 按这个记下后面几批往哪边打
+    pub fn streams(&self) -> bool {
+        matches!(
+            self,
+            ConsoleCommand::Help(_)
+                | ConsoleCommand::LogDump
+                | ConsoleCommand::Perf
+                | ConsoleCommand::Events
+                | ConsoleCommand::SysInfo
+                | ConsoleCommand::Features
+                | ConsoleCommand::FrameDump
+        )
+    }
+}
+
+/// 一条命令最多几个参数
+const MAX_ARGS: usize = 4;
+
+/// 一个参数是什么类型的，拆参数的时候就按类型检查好
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgKind {
+    /// 一个词，原样给出
+    Word,
+    /// `min..=max` 之间的十进制数
+    Number { min: u32, max: u32 },
+    /// 带符号的十进制数
+    Int,
+    /// 十六进制数，可以带 `0x`
+    Hex,
+    /// `ON` 或者 `OFF`
+    OnOff,
+    /// `OFF`，或者 `1..=max` 的数
+    OffOr { max: u16 },
+    /// 几个关键字里的一个，给出是第几个
+    Choice(&'static [&'static str]),
+    /// `HH:MM`
+    Time,
+    /// 这一行剩下的全部(空格原样保留)，只能放在最后
+    Rest,
+}
+
+impl ArgKind {
+    fn parse(self, word: &str) -> Option<Value<'_>> {
+        let number = |word: &str, min: u32, max: u32| {
+            word.parse::<u32>()
+                .ok()
+                .filter(|n| (min..=max).contains(n))
+                .map(Value::Number)
+        };
+        match self {
+            ArgKind::Word | ArgKind::Rest => Some(Value::Word(word)),
+            ArgKind::Number { min, max } => number(word, min, max),
+            ArgKind::Int => word.parse().ok().map(Value::Int),
+            ArgKind::Hex => {
+                let digits = word.trim_start_matches("0x").trim_start_matches("0X");
+                u32::from_str_radix(digits, 16).ok().map(Value::Number)
+            }
+            ArgKind::OnOff => ArgKind::Choice(&["OFF", "ON"])
+                .parse(word)
+                .map(|on| Value::Flag(on == Value::Choice(1))),
+            ArgKind::OffOr { .. } if word.eq_ignore_ascii_case("OFF") => Some(Value::Off),
+            ArgKind::OffOr { max } => number(word, 1, max as u32),
+            ArgKind::Choice(names) => names
+                .iter()
+                .position(|name| name.eq_ignore_ascii_case(word))
+                .map(|index| Value::Choice(index as u8)),
+            ArgKind::Time => parse_hh_mm(word).map(|(hour, minute)| Value::Time(hour, minute)),
+        }
+    }
+}
+
+/// 参数说明：类型 + 能不能不写。能不写的参数只能放在后面
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArgSpec {
+    pub kind: ArgKind,
+    pub optional: bool,
+}
+
+const fn required(kind: ArgKind) -> ArgSpec {
+    ArgSpec {
+        kind,
+        optional: false,
+    }
+}
+
+const fn optional(kind: ArgKind) -> ArgSpec {
+    ArgSpec {
+        kind,
+        optional: true,
+    }
+}
+
+/// 拆好的一个参数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Value<'a> {
+    /// 能不写的参数没写
+    Missing,
+    Word(&'a str),
+    Number(u32),
+    Int(i32),
+    Flag(bool),
+    Off,
+    Choice(u8),
+    Time(u8, u8),
+}
+
+/// 按 `ArgSpec` 检查过的参数，第几个参数就是第几个，类型不对的取不出来
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Args<'a> {
+    values: [Value<'a>; MAX_ARGS],
+}
+
+impl<'a> Args<'a> {
+    pub fn is_given(&self, index: usize) -> bool {
+        self.values[index] != Value::Missing
+    }
+
+    pub fn word(&self, index: usize) -> Option<&'a str> {
+        match self.values[index] {
+            Value::Word(word) => Some(word),
+            _ => None,
+        }
+    }
+
+    /// `Number` 和 `Hex`，以及 `OffOr` 给了数的时候
+    pub fn number(&self, index: usize) -> Option<u32> {
+        match self.values[index] {
+            Value::Number(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    pub fn int(&self, index: usize) -> Option<i32> {
+        match self.values[index] {
+            Value::Int(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    pub fn flag(&self, index: usize) -> Option<bool> {
+        match self.values[index] {
+            Value::Flag(on) => Some(on),
+            _ => None,
+        }
+    }
+
+    /// `OffOr`：`OFF` 是 Some(None)
+    pub fn off_or(&self, index: usize) -> Option<Option<u16>> {
+        match self.values[index] {
+            Value::Off => Some(None),
+            Value::Number(n) => Some(Some(n as u16)),
+            _ => None,
+        }
+    }
+
+    pub fn choice(&self, index: usize) -> Option<usize> {
+        match self.values[index] {
+            Value::Choice(index) => Some(index as usize),
+            _ => None,
+        }
+    }
+
+    pub fn time(&self, index: usize) -> Option<(u8, u8)> {
+        match self.values[index] {
+            Value::Time(hour, minute) => Some((hour, minute)),
+            _ => None,
+        }
+    }
+}
+
+/// 参数都检查过以后怎么变成命令，返回 None 是参数之间对不上(比如只给了 x 没给 y)，回复用法
+type Build = for<'a> fn(&Args<'a>) -> Option<ConsoleCommand<'a>>;
+
+/// 命令表里的一条
+pub struct CommandSpec {
+    /// 命令名，几个词的用一个空格隔开，比如 "SETTINGS MUTE"
+    pub name: &'static str,
+    args: &'static [ArgSpec],
+    /// 完整的写法，参数不对的时候回复这个(`ERR usage: ...`)
+    pub usage: &'static str,
+    /// 一句话说明，`HELP` 列出来
+    pub help: &'static str,
+    build: Build,
+}
+
+impl CommandSpec {
+    /// 按参数说明拆 `rest`，个数、类型不对返回 None
+    fn parse_args<'a>(&self, rest: &'a str) -> Option<Args<'a>> {
+        let mut values = [Value::Missing; MAX_ARGS];
+        let mut rest = rest.trim_start();
+        for (value, spec) in values.iter_mut().zip(self.args) {
+            if rest.is_empty() {
+                if spec.optional {
+                    continue;
+                }
+                return None;
+            }
+            if spec.kind == ArgKind::Rest {
+                *value = Value::Word(rest);
+                rest = "";
+                continue;
+            }
+            let (word, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            *value = spec.kind.parse(word)?;
+            rest = tail.trim_start();
+        }
+        rest.is_empty().then_some(Args { values })
+    }
+}
+
+/// 开关类的 `SETTINGS` 命令用的参数
+const ON_OFF: &[ArgSpec] = &[required(ArgKind::OnOff)];
+
+const NO_ARGS: &[ArgSpec] = &[];
+
+const ANY_U16: ArgKind = ArgKind::Number {
+    min: 0,
+    max: u16::MAX as u32,
+};
+
+const POSITIVE_U16: ArgKind = ArgKind::Number {
+    min: 1,
+    max: u16::MAX as u32,
+};
+
+/// 屏幕亮度，0 不让设，免得全黑
+const LEVEL: ArgKind = ArgKind::Number { min: 1, max: 255 };
+
+const IMAGE_SLOT: ArgKind = ArgKind::Number {
+    min: 1,
+    max: IMAGE_SLOTS as u32,
+};
+
+/// 命令表里有几条，`devtools` 多 4 条，`pico-w` 多 1 条
+const COMMAND_COUNT: usize = 52
+    + if cfg!(feature = "devtools") { 4 } else { 0 }
+    + if cfg!(feature = "pico-w") { 1 } else { 0 };
+
+/// 命令表，`HELP` 按这个顺序列
+pub static COMMANDS: [CommandSpec; COMMAND_COUNT] = [
+    CommandSpec {
+        name: "HELP",
+        args: &[optional(ArgKind::Rest)],
+        usage: "HELP [command]",
+        help: "list commands, or show usage of one",
+        build: |args| Some(help_topic(args.word(0))),
+    },
+    CommandSpec {
+        name: "SETTINGS DUMP",
+        args: NO_ARGS,
+        usage: "SETTINGS DUMP",
+        help: "print settings as base64",
+        build: |_| Some(ConsoleCommand::SettingsDump),
+    },
+    CommandSpec {
+        name: "SETTINGS LOAD",
+        args: &[required(ArgKind::Word)],
+        usage: "SETTINGS LOAD <base64>",
+        help: "import settings from SETTINGS DUMP",
+        build: |args| args.word(0).map(ConsoleCommand::SettingsLoad),
+    },
+    CommandSpec {
+        name: "SETTINGS RESET",
+        args: NO_ARGS,
+        usage: "SETTINGS RESET",
+        help: "restore default settings",
+        build: |_| Some(ConsoleCommand::SettingsReset),
+    },
+    CommandSpec {
+        name: "SETTINGS MUTE",
+        args: ON_OFF,
+        usage: "SETTINGS MUTE ON|OFF",
+        help: "mute the buzzer",
+        build: |args| args.flag(0).map(ConsoleCommand::SettingsMute),
+    },
+    CommandSpec {
+        name: "SETTINGS LOG",
+        args: &[required(ArgKind::OffOr { max: u16::MAX })],
+        usage: "SETTINGS LOG <seconds>|OFF",
+        help: "data logging interval",
+        build: |args| args.off_or(0).map(ConsoleCommand::SettingsLog),
+    },
+    CommandSpec {
+        name: "SETTINGS BURNIN",
+        args: &[
+            required(ArgKind::Choice(&["OFF", "SHIFT", "INVERT"])),
+            optional(POSITIVE_U16),
+        ],
+        usage: "SETTINGS BURNIN SHIFT|INVERT [minutes]|OFF",
+        help: "burn-in protection",
+        build: |args| {
+            let strategy = match args.choice(0)? {
+                0 => {
+                    return (!args.is_given(1))
+                        .then(|| ConsoleCommand::SettingsBurnIn(BurnInConfig::default()))
+                }
+                1 => BurnInStrategy::PixelShift,
+                _ => BurnInStrategy::InvertFlash,
+            };
+            let interval_min = args
+                .number(1)
+                .map_or(BurnInConfig::default().interval_min, |minutes| {
+                    minutes as u16
+                });
+            Some(ConsoleCommand::SettingsBurnIn(BurnInConfig {
+                strategy,
+                interval_min,
+            }))
+        },
+    },
+    CommandSpec {
+        name: "SETTINGS SCREENOFF",
+        args: &[required(ArgKind::OffOr { max: u16::MAX })],
+        usage: "SETTINGS SCREENOFF <minutes>|OFF",
+        help: "turn the screen off when idle",
+        build: |args| args.off_or(0).map(ConsoleCommand::SettingsScreenOff),
+    },
+    CommandSpec {
+        name: "SETTINGS WAKE",
+        args: &[required(ArgKind::OffOr { max: MAX_RANGE_MM })],
+        usage: "SETTINGS WAKE <mm>|OFF",
+        help: "wake the screen by proximity",
+        build: |args| args.off_or(0).map(ConsoleCommand::SettingsWake),
+    },
+    CommandSpec {
+        name: "SETTINGS DEMO",
+        args: &[
+            required(ArgKind::OffOr { max: u16::MAX }),
+            optional(ArgKind::OffOr { max: u16::MAX }),
+        ],
+        usage: "SETTINGS DEMO <seconds> [each seconds]|OFF",
+        help: "auto-cycle demos when idle",
+        build: |args| {
+            // OFF 不带时间，不写每个演示放多久就用默认的
+            let config = match (args.off_or(0)?, args.off_or(1)) {
+                (None, None) => DemoAutoConfig::default(),
+                (Some(idle_s), None) => DemoAutoConfig {
+                    idle_s,
+                    dwell_s: DemoAutoConfig::default().dwell_s,
+                },
+                (Some(idle_s), Some(Some(dwell_s))) => DemoAutoConfig { idle_s, dwell_s },
+                _ => return None,
+            };
+            Some(ConsoleCommand::SettingsDemo(config))
+        },
+    },
+    CommandSpec {
+        name: "SETTINGS DERATE",
+        args: ON_OFF,
+        usage: "SETTINGS DERATE ON|OFF",
+        help: "dim when the chip runs hot",
+        build: |args| args.flag(0).map(ConsoleCommand::SettingsDerate),
+    },
+    CommandSpec {
+        name: "SETTINGS PACING",
+        args: ON_OFF,
+        usage: "SETTINGS PACING ON|OFF",
+        help: "pace flushes to the panel refresh",
+        build: |args| args.flag(0).map(ConsoleCommand::SettingsPacing),
+    },
+    CommandSpec {
+        name: "SETTINGS LARGE",
+        args: ON_OFF,
+        usage: "SETTINGS LARGE ON|OFF",
+        help: "large-text mode",
+        build: |args| args.flag(0).map(ConsoleCommand::SettingsLarge),
+    },
+    CommandSpec {
+        name: "SETTINGS PROFILE",
+        args: &[required(ArgKind::Word)],
+        usage: "SETTINGS PROFILE INDOOR|SUNLIGHT|NIGHT",
+        help: "brightness, invert and burn-in preset",
+        build: |args| display_profile::find(args.word(0)?).map(ConsoleCommand::SettingsProfile),
+    },
+    CommandSpec {
+        name: "SETTINGS THEME",
+        args: &[required(ArgKind::Word)],
+        usage: "SETTINGS THEME DEFAULT|HIGH-CONTRAST",
+        help: "widget borders, fills and colours",
+        build: |args| theme::find(args.word(0)?).map(ConsoleCommand::SettingsTheme),
+    },
+    CommandSpec {
+        name: "SETTINGS PULSE",
+        args: &[
+            required(ArgKind::Word),
+            required(ArgKind::Word),
+            optional(ArgKind::Number {
+                min: 0,
+                max: MAX_MIN_WIDTH_MS as u32,
+            }),
+        ],
+        usage: "SETTINGS PULSE <per pulse> <unit> [min ms]",
+        help: "pulse counter scale, unit, debounce",
+        build: |args| {
+            let config = PulseConfig {
+                milli_per_pulse: pulse_counter::parse_milli(args.word(0)?)?,
+                unit: pulse_counter::find_unit(args.word(1)?)?,
+                min_width_ms: args
+                    .number(2)
+                    .map_or(PulseConfig::DEFAULT.min_width_ms, |ms| ms as u16),
+            };
+            config
+                .is_valid()
+                .then_some(ConsoleCommand::SettingsPulse(config))
+        },
+    },
+    CommandSpec {
+        name: "SETTINGS BATTLOW",
+        args: &[
+            required(ArgKind::OffOr { max: u16::MAX }),
+            optional(ANY_U16),
+        ],
+        usage: "SETTINGS BATTLOW <warn mV> <critical mV>|OFF",
+        help: "low-voltage protection",
+        build: |args| {
+            let Some(warn_mv) = args.off_or(0)? else {
+                return (!args.is_given(1))
+                    .then_some(ConsoleCommand::SettingsBattLow(LowVoltageConfig::OFF));
+            };
+            let config = LowVoltageConfig {
+                warn_mv,
+                critical_mv: args.number(1)? as u16,
+            };
+            (config.is_enabled() && config.is_valid())
+                .then_some(ConsoleCommand::SettingsBattLow(config))
+        },
+    },
+    CommandSpec {
+        name: "SETTINGS AUTOBRIGHT",
+        args: &[
+            required(ArgKind::OffOr {
+                max: auto_brightness::MAX_MV,
+            }),
+            optional(ANY_U16),
+            optional(LEVEL),
+            optional(LEVEL),
+        ],
+        usage: "SETTINGS AUTOBRIGHT <dark mV> <bright mV> [min] [max]|OFF",
+        help: "brightness from an LDR on ADC0",
+        build: |args| {
+            let Some(dark_mv) = args.off_or(0)? else {
+                return (!args.is_given(1)).then_some(ConsoleCommand::SettingsAutoBright(
+                    AutoBrightnessConfig::OFF,
+                ));
+            };
+            let level = |index, default| args.number(index).map_or(default, |n| n as u8);
+            let config = AutoBrightnessConfig {
+                dark_mv,
+                bright_mv: args.number(1)? as u16,
+                min_level: level(2, auto_brightness::DEFAULT_MIN_LEVEL),
+                max_level: level(3, auto_brightness::DEFAULT_MAX_LEVEL),
+            };
+            (config.is_enabled() && config.is_valid())
+                .then_some(ConsoleCommand::SettingsAutoBright(config))
+        },
+    },
+    CommandSpec {
+        name: "SETTINGS TZ",
+        args: &[required(ArgKind::Word), optional(ArgKind::Word)],
+        usage: "SETTINGS TZ <+HH:MM> [OFF|ON|EU|US]",
+        help: "time zone and daylight saving, the RTC keeps UTC",
+        build: |args| {
+            let offset = timezone::parse_offset(args.word(0)?)?;
+            let dst = match args.word(1) {
+                Some(rule) => DstRule::parse(rule)?,
+                None => DstRule::Off,
+            };
+            TimeZone::new(offset, dst).map(ConsoleCommand::SettingsTimeZone)
+        },
+    },
+    #[cfg(feature = "pico-w")]
+    CommandSpec {
+        name: "SETTINGS WIFI",
+        args: &[required(ArgKind::Word), optional(ArgKind::Rest)],
+        usage: "SETTINGS WIFI <ssid> [password]|OFF",
+        help: "join a Wi-Fi network, or forget it",
+        build: |args| {
+            let ssid = args.word(0)?;
+            let password = args.word(1).unwrap_or("");
+            if ssid.eq_ignore_ascii_case("OFF") && !args.is_given(1) {
+                return Some(ConsoleCommand::SettingsWifi(None));
+            }
+            crate::wifi_config::WifiCredentials::new(ssid, password)
+                .map(|_| ConsoleCommand::SettingsWifi(Some((ssid, password))))
+        },
+    },
+    CommandSpec {
+        name: "IMG BEGIN",
+        args: &[required(IMAGE_SLOT), required(ANY_U16), required(ANY_U16)],
+        usage: "IMG BEGIN <1-4> <w> <h>",
+        help: "start uploading an image",
+        build: |args| {
+            Some(ConsoleCommand::ImageBegin {
+                slot: args.number(0)? as usize - 1,
+                width: args.number(1)? as u16,
+                height: args.number(2)? as u16,
+            })
+        },
+    },
+    CommandSpec {
+        name: "IMG DATA",
+        args: &[required(ArgKind::Word)],
+        usage: "IMG DATA <base64>",
+        help: "one chunk of image data",
+        build: |args| args.word(0).map(ConsoleCommand::ImageData),
+    },
+    CommandSpec {
+        name: "IMG END",
+        args: &[required(ArgKind::Hex)],
+        usage: "IMG END <crc32 hex>",
+        help: "finish the upload and save it",
+        build: |args| args.number(0).map(ConsoleCommand::ImageEnd),
+    },
+    CommandSpec {
+        name: "IMG SHOW",
+        args: &[
+            required(IMAGE_SLOT),
+            optional(ArgKind::Int),
+            optional(ArgKind::Int),
+        ],
+        usage: "IMG SHOW <1-4> [x y]",
+        help: "show a stored image",
+        build: |args| {
+            let top_left = match (args.int(1), args.int(2)) {
+                (None, None) => (0, 0),
+                (Some(x), Some(y)) => (x, y),
+                _ => return None,
+            };
+            Some(ConsoleCommand::ImageShow {
+                slot: args.number(0)? as usize - 1,
+                top_left,
+            })
+        },
+    },
+    CommandSpec {
+        name: "IMG LIST",
+        args: NO_ARGS,
+        usage: "IMG LIST",
+        help: "list image slots",
+        build: |_| Some(ConsoleCommand::ImageList),
+    },
+    #[cfg(feature = "devtools")]
+    CommandSpec {
+        name: "PREVIEW",
+        args: &[
+            required(ANY_U16),
+            required(ANY_U16),
+            required(ArgKind::Word),
+        ],
+        usage: "PREVIEW <w> <h> <base64>",
+        help: "show a sprite without storing it",
+        build: |args| {
+            Some(ConsoleCommand::Preview {
+                width: args.number(0)? as u16,
+                height: args.number(1)? as u16,
+                data: args.word(2)?,
+            })
+        },
+    },
+    #[cfg(feature = "devtools")]
+    CommandSpec {
+        name: "PREVIEW AT",
+        args: &[required(ArgKind::Int), required(ArgKind::Int)],
+        usage: "PREVIEW AT <x> <y>",
+        help: "move the preview",
+        build: |args| Some(ConsoleCommand::PreviewAt(args.int(0)?, args.int(1)?)),
+    },
+    #[cfg(feature = "devtools")]
+    CommandSpec {
+        name: "PREVIEW OFF",
+        args: NO_ARGS,
+        usage: "PREVIEW OFF",
+        help: "close the preview",
+        build: |_| Some(ConsoleCommand::PreviewOff),
+    },
+    #[cfg(feature = "devtools")]
+    CommandSpec {
+        name: "GRID",
+        args: ON_OFF,
+        usage: "GRID ON|OFF",
+        help: "8 pixel grid over the preview",
+        build: |args| args.flag(0).map(ConsoleCommand::Grid),
+    },
+    CommandSpec {
+        name: "LOG DUMP",
+        args: NO_ARGS,
+        usage: "LOG DUMP",
+        help: "export the data log as CSV",
+        build: |_| Some(ConsoleCommand::LogDump),
+    },
+    CommandSpec {
+        name: "CLOCK SET",
+        args: &[required(ArgKind::Time)],
+        usage: "CLOCK SET HH:MM",
+        help: "set the wall clock",
+        build: |args| {
+            let (hour, minute) = args.time(0)?;
+            Some(ConsoleCommand::ClockSet { hour, minute })
+        },
+    },
+    CommandSpec {
+        name: "TELEM ON",
+        args: &[required(POSITIVE_U16)],
+        usage: "TELEM ON <lines per second>",
+        help: "stream readings as CSV",
+        build: |args| Some(ConsoleCommand::Telemetry(Some(args.number(0)? as u16))),
+    },
+    CommandSpec {
+        name: "TELEM OFF",
+        args: NO_ARGS,
+        usage: "TELEM OFF",
+        help: "stop streaming",
+        build: |_| Some(ConsoleCommand::Telemetry(None)),
+    },
+    CommandSpec {
+        name: "TELEM FIELDS",
+        args: &[required(ArgKind::Word)],
+        usage: "TELEM FIELDS <name,name,...>",
+        help: "pick telemetry columns",
+        build: |args| args.word(0).map(ConsoleCommand::TelemetryFields),
+    },
+    CommandSpec {
+        name: "PULSE",
+        args: NO_ARGS,
+        usage: "PULSE",
+        help: "pulse counter total and rates",
+        build: |_| Some(ConsoleCommand::Pulse),
+    },
+    CommandSpec {
+        name: "PULSE RESET",
+        args: NO_ARGS,
+        usage: "PULSE RESET",
+        help: "zero the pulse counter",
+        build: |_| Some(ConsoleCommand::PulseReset),
+    },
+    CommandSpec {
+        name: "REC START",
+        args: NO_ARGS,
+        usage: "REC START",
+        help: "start recording button input",
+        build: |_| Some(ConsoleCommand::RecordStart),
+    },
+    CommandSpec {
+        name: "REC STOP",
+        args: NO_ARGS,
+        usage: "REC STOP",
+        help: "stop recording or replay",
+        build: |_| Some(ConsoleCommand::RecordStop),
+    },
+    CommandSpec {
+        name: "REC PLAY",
+        args: &[optional(ArgKind::Choice(&["LOOP"]))],
+        usage: "REC PLAY [LOOP]",
+        help: "replay the recorded input",
+        build: |args| Some(ConsoleCommand::RecordPlay(args.choice(0).is_some())),
+    },
+    CommandSpec {
+        name: "STATUS",
+        args: NO_ARGS,
+        usage: "STATUS",
+        help: "print one status line",
+        build: |_| Some(ConsoleCommand::Status),
+    },
+    CommandSpec {
+        name: "STATUS ON",
+        args: &[required(POSITIVE_U16)],
+        usage: "STATUS ON <seconds>",
+        help: "print status periodically",
+        build: |args| Some(ConsoleCommand::StatusEvery(Some(args.number(0)? as u16))),
+    },
+    CommandSpec {
+        name: "STATUS OFF",
+        args: NO_ARGS,
+        usage: "STATUS OFF",
+        help: "stop periodic status",
+        build: |_| Some(ConsoleCommand::StatusEvery(None)),
+    },
+    CommandSpec {
+        name: "PERF",
+        args: NO_ARGS,
+        usage: "PERF",
+        help: "render time per page",
+        build: |_| Some(ConsoleCommand::Perf),
+    },
+    CommandSpec {
+        name: "PERF RESET",
+        args: NO_ARGS,
+        usage: "PERF RESET",
+        help: "clear render statistics",
+        build: |_| Some(ConsoleCommand::PerfReset),
+    },
+    CommandSpec {
+        name: "PERF BUDGET",
+        args: &[required(POSITIVE_U16)],
+        usage: "PERF BUDGET <ms>",
+        help: "warn when a frame takes longer",
+        build: |args| Some(ConsoleCommand::PerfBudget(args.number(0)? as u16)),
+    },
+    CommandSpec {
+        name: "EVENTS",
+        args: NO_ARGS,
+        usage: "EVENTS",
+        help: "recent system events",
+        build: |_| Some(ConsoleCommand::Events),
+    },
+    CommandSpec {
+        name: "FACE",
+        args: &[
+            optional(ArgKind::Number {
+                min: 1,
+                max: SLOTS as u32,
+            }),
+            optional(ArgKind::Word),
+            optional(ArgKind::Word),
+        ],
+        usage: "FACE [<1-6> <widget> [channel]]",
+        help: "show the watch face, or set one slot",
+        build: |args| {
+            let Some(slot) = args.number(0) else {
+                return (!args.is_given(1)).then_some(ConsoleCommand::Face);
+            };
+            // 读数、进度条要带通道名，别的不能带
+            let kind = WidgetKind::from_name(args.word(1)?)?;
+            let channel = args.word(2);
+            (kind.needs_channel() == channel.is_some()).then_some(ConsoleCommand::FaceSlot {
+                slot: slot as usize - 1,
+                kind,
+                channel,
+            })
+        },
+    },
+    CommandSpec {
+        name: "FACE LAYOUT",
+        args: &[required(ArgKind::Word)],
+        usage: "FACE LAYOUT GRID|BIG|FULL|SIX",
+        help: "change the watch face layout",
+        build: |args| Layout::from_name(args.word(0)?).map(ConsoleCommand::FaceLayout),
+    },
+    CommandSpec {
+        name: "SCAN",
+        args: NO_ARGS,
+        usage: "SCAN",
+        help: "scan the I2C bus",
+        build: |_| Some(ConsoleCommand::Scan),
+    },
+    CommandSpec {
+        name: "SYSINFO",
+        args: NO_ARGS,
+        usage: "SYSINFO",
+        help: "print the boot hardware summary",
+        build: |_| Some(ConsoleCommand::SysInfo),
+    },
+    CommandSpec {
+        name: "FEATURES",
+        args: NO_ARGS,
+        usage: "FEATURES",
+        help: "list the features compiled into this build",
+        build: |_| Some(ConsoleCommand::Features),
+    },
+    CommandSpec {
+        name: "FB",
+        args: NO_ARGS,
+        usage: "FB",
+        help: "dump the frame buffer",
+        build: |_| Some(ConsoleCommand::FrameDump),
+    },
+    CommandSpec {
+        name: "FB DEFMT",
+        args: NO_ARGS,
+        usage: "FB DEFMT",
+        help: "dump the frame buffer to defmt",
+        build: |_| Some(ConsoleCommand::FrameDumpDefmt),
+    },
+    CommandSpec {
+        name: "TEXT",
+        args: &[required(ArgKind::Rest)],
+        usage: "TEXT <message>",
+        help: "show text on the remote page",
+        build: |args| args.word(0).map(ConsoleCommand::Text),
+    },
+    CommandSpec {
+        name: "CLEAR",
+        args: NO_ARGS,
+        usage: "CLEAR",
+        help: "clear the remote page",
+        build: |_| Some(ConsoleCommand::Clear),
+    },
+    CommandSpec {
+        name: "BRIGHT",
+        args: &[required(ArgKind::Number { min: 0, max: 255 })],
+        usage: "BRIGHT <0-255>",
+        help: "set brightness until reboot",
+        build: |args| Some(ConsoleCommand::Bright(args.number(0)? as u8)),
+    },
+    CommandSpec {
+        name: "INVERT",
+        args: &[required(ArgKind::Choice(&["0", "1"]))],
+        usage: "INVERT 0|1",
+        help: "invert the display until reboot",
+        build: |args| Some(ConsoleCommand::Invert(args.choice(0)? == 1)),
+    },
+];
+
+/// `line` 开头的几个词是不是 `name`(不区分大小写，词之间空几个都行)，是的话返回剩下的部分
+fn strip_name<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = line;
+    for word in name.split(' ') {
+        rest = rest.trim_start();
+        let (head, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        if !head.eq_ignore_ascii_case(word) {
+            return None;
+        }
+        rest = tail;
+    }
+    Some(rest)
+}
+
+/// 命令表里对得上的词最多的那条，和去掉命令名以后剩下的参数
+fn find_command(line: &str) -> Option<(&'static CommandSpec, &str)> {
+    COMMANDS
+        .iter()
+        .filter_map(|spec| Some((spec, strip_name(line, spec.name)?)))
+        .max_by_key(|(spec, _)| spec.name.split(' ').count())
+}
+
+/// 至少几个字母对得上才提示
+const MIN_SUGGEST_PREFIX: usize = 2;
+
+/// 不认识的命令：和它开头对得上最多字母的命令名(词之间当一个空格算)，比如 "SETTINGS MUT" 是 "SETTINGS MUTE"
+pub fn closest_command(line: &str) -> Option<&'static str> {
+    let typed = || {
+        line.split_whitespace()
+            .flat_map(|word| core::iter::once(' ').chain(word.chars()))
+            .skip(1)
+    };
+    let mut best = None;
+    let mut best_len = MIN_SUGGEST_PREFIX - 1;
+    for spec in COMMANDS.iter() {
+        let common = typed()
+            .zip(spec.name.chars())
+            .take_while(|(a, b)| a.eq_ignore_ascii_case(b))
+            .count();
+        if common > best_len {
+            best = Some(spec.name);
+            best_len = common;
+        }
+    }
+    best
+}
+
+/// `HELP` 后面跟的主题：命令名开头的几个词，比如 "SETTINGS" 或者 "SETTINGS MUTE"
+fn help_topic(topic: Option<&str>) -> ConsoleCommand<'static> {
+    let Some(topic) = topic else {
+        return ConsoleCommand::Help(None);
+    };
+    // 从命令表里切出这几个词，这样主题是 'static 的，main.rs 可以分几圈慢慢打
+    let words = topic.split_whitespace().count();
+    if words == 0 {
+        return ConsoleCommand::Unknown(None);
+    }
+    COMMANDS
+        .iter()
+        .find(|spec| starts_with_words(spec.name, topic))
+        .map(|spec| {
+            let end = spec
+                .name
+                .match_indices(' ')
+                .nth(words - 1)
+                .map_or(spec.name.len(), |(at, _)| at);
+            ConsoleCommand::Help(Some(&spec.name[..end]))
+        })
+        .unwrap_or(ConsoleCommand::Unknown(closest_command(topic)))
+}
+
+/// `name` 是不是以 `words` 这几个词开头(整词，不区分大小写)
+fn starts_with_words(name: &str, words: &str) -> bool {
+    let mut name_words = name.split(' ');
+    words.split_whitespace().all(|word| {
+        name_words
+            .next()
+            .is_some_and(|name| name.eq_ignore_ascii_case(word))
+    })
+}
+
+/// `HELP` 打印出来的一行最长多少字节
+pub const HELP_LINE_MAX: usize = 96;
+
+/// `HELP` 的输出，一行一条命令，最多 `COMMANDS.len()` 行，分几圈打(见 main.rs)
+///
+/// 没有主题列出所有命令和一句话说明，有主题只列这几个词开头的命令的完整写法和说明
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Help {
+    topic: Option<&'static str>,
+}
+
+impl Help {
+    pub const LINES: usize = COMMANDS.len();
+
+    pub const fn new(topic: Option<&'static str>) -> Self {
+        Self { topic }
+    }
+
+    /// 第 `index` 条命令那一行，不在主题下的什么都不写
+    pub fn write_line<W: fmt::Write>(&self, index: usize, out: &mut W) -> fmt::Result {
+        let Some(spec) = COMMANDS.get(index) else {
+            return Ok(());
+        };
+        match self.topic {
+            None => write!(out, "{:<18}  {}\r\n", spec.name, spec.help),
+            Some(topic) if starts_with_words(spec.name, topic) => {
+                write!(out, "{:<38}  {}\r\n", spec.usage, spec.help)
+            }
+            Some(_) => Ok(()),
+        }
+    }
+}
+
+/// 终端按退格发的这两个里的一个
+const BACKSPACE: u8 = 0x08;
+const DELETE: u8 = 0x7f;
+
+/// Ctrl-U：清掉这一行
+const CTRL_U: u8 = 0x15;
+
+/// 行缓冲
+#[derive(Debug, Default)]
+pub struct Console {
+    line: Vec<u8, LINE_CAPACITY>,
+    /// 当前这一行已经超长了
+    overflow: bool,
+    /// 上一次 `feed` 交出去了一整行，下一次要先清空
+    done: bool,
+}
+
+impl Console {
+    fn clear(&mut self) {
+        self.line.clear();
+        self.overflow = false;
+        self.done = false;
+    }
+
+    pub const fn new() -> Self {
+        Self {
+            line: Vec::new(),
+            overflow: false,
+            done: false,
+        }
+    }
+
+    /// 喂一个字节，凑够一行(遇到 `\r` 或 `\n`)就返回解析好的命令，空行会被忽略
+    pub fn feed(&mut self, byte: u8) -> Option<ConsoleCommand<'_>> {
+        if self.done {
+            self.clear();
+        }
+
+        match byte {
+            BACKSPACE | DELETE => {
+                // 已经超长的行删几个字也救不回来(前面的丢掉了)，只能 Ctrl-U
+                if !self.overflow {
+                    self.line.pop();
+                }
+                return None;
+            }
+            CTRL_U => {
+                self.clear();
+                return None;
+            }
+            _ => {}
+        }
+        if byte != b'\r' && byte != b'\n' {
+            if self.line.push(byte).is_err() {
+                self.overflow = true;
+            }
+            return None;
+        }
+
+        if self.overflow {
+            self.done = true;
+            return Some(ConsoleCommand::TooLong);
+        }
+        if self.line.is_empty() {
+            return None;
+        }
+        self.done = true;
+        // 不是 UTF-8 的行也当作不认识的命令
+        Some(match core::str::from_utf8(&self.line) {
+            Ok(line) => ConsoleCommand::parse(line),
+            Err(_) => ConsoleCommand::Unknown(None),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::burn_in::DEFAULT_INTERVAL_MIN;
+    use crate::demo_auto::DEFAULT_DWELL_S;
+    use std::string::String;
+    use ConsoleCommand::*;
+
+    fn usage(name: &str) -> ConsoleCommand<'static> {
+        let spec = COMMANDS.iter().find(|spec| spec.name == name).unwrap();
+        Usage(spec.usage)
+    }
+
+    fn burn_in(strategy: BurnInStrategy, interval_min: u16) -> ConsoleCommand<'static> {
+        SettingsBurnIn(BurnInConfig {
+            strategy,
+            interval_min,
+        })
+    }
+
+    fn auto_bright(dark_mv: u16, bright_mv: u16, min: u8, max: u8) -> ConsoleCommand<'static> {
+        SettingsAutoBright(AutoBrightnessConfig {
+            dark_mv,
+            bright_mv,
+            min_level: min,
+            max_level: max,
+        })
+    }
+
+    #[test]
+    fn parses_command_lines() {
+        let cases: &[(&str, ConsoleCommand)] = &[
+            ("HELP", Help(None)),
+            ("help settings", Help(Some("SETTINGS"))),
+            ("HELP settings   mute", Help(Some("SETTINGS MUTE"))),
+            ("HELP nothing", Unknown(None)),
+            ("IMG", Help(Some("IMG"))),
+            ("SETTINGS DUMP", SettingsDump),
+            ("  settings   dump  ", SettingsDump),
+            ("SETTINGS DUMP now", usage("SETTINGS DUMP")),
+            ("SETTINGS LOAD AQID", SettingsLoad("AQID")),
+            ("SETTINGS LOAD", usage("SETTINGS LOAD")),
+            ("SETTINGS RESET", SettingsReset),
+            ("SETTINGS MUTE ON", SettingsMute(true)),
+            ("settings mute off", SettingsMute(false)),
+            ("SETTINGS MUTE 1", usage("SETTINGS MUTE")),
+            ("SETTINGS LOG 60", SettingsLog(Some(60))),
+            ("SETTINGS LOG OFF", SettingsLog(None)),
+            ("SETTINGS LOG 0", usage("SETTINGS LOG")),
+            ("SETTINGS LOG 65536", usage("SETTINGS LOG")),
+            (
+                "SETTINGS BURNIN OFF",
+                SettingsBurnIn(BurnInConfig::default()),
+            ),
+            ("SETTINGS BURNIN OFF 5", usage("SETTINGS BURNIN")),
+            (
+                "SETTINGS BURNIN SHIFT",
+                burn_in(BurnInStrategy::PixelShift, DEFAULT_INTERVAL_MIN),
+            ),
+            (
+                "SETTINGS BURNIN INVERT 30",
+                burn_in(BurnInStrategy::InvertFlash, 30),
+            ),
+            ("SETTINGS BURNIN SHIFT 0", usage("SETTINGS BURNIN")),
+            ("SETTINGS SCREENOFF 10", SettingsScreenOff(Some(10))),
+            ("SETTINGS SCREENOFF OFF", SettingsScreenOff(None)),
+            ("SETTINGS WAKE 150", SettingsWake(Some(150))),
+            ("SETTINGS WAKE 2001", usage("SETTINGS WAKE")),
+            ("SETTINGS DEMO OFF", SettingsDemo(DemoAutoConfig::default())),
+            (
+                "SETTINGS DEMO 120",
+                SettingsDemo(DemoAutoConfig {
+                    idle_s: 120,
+                    dwell_s: DEFAULT_DWELL_S,
+                }),
+            ),
+            (
+                "SETTINGS DEMO 120 10",
+                SettingsDemo(DemoAutoConfig {
+                    idle_s: 120,
+                    dwell_s: 10,
+                }),
+            ),
+            ("SETTINGS DEMO OFF 10", usage("SETTINGS DEMO")),
+            ("SETTINGS DEMO 120 OFF", usage("SETTINGS DEMO")),
+            ("SETTINGS DERATE ON", SettingsDerate(true)),
+            ("SETTINGS PACING OFF", SettingsPacing(false)),
+            ("SETTINGS LARGE ON", SettingsLarge(true)),
+            ("SETTINGS PROFILE night", SettingsProfile(2)),
+            ("SETTINGS PROFILE SUNLIGHT", SettingsProfile(1)),
+            ("SETTINGS PROFILE dusk", usage("SETTINGS PROFILE")),
+            ("SETTINGS THEME HIGH-CONTRAST", SettingsTheme(1)),
+            ("SETTINGS THEME neon", usage("SETTINGS THEME")),
+            (
+                "SETTINGS PULSE 2.5 mL",
+                SettingsPulse(PulseConfig {
+                    milli_per_pulse: 2500,
+                    unit: 1,
+                    min_width_ms: PulseConfig::DEFAULT.min_width_ms,
+                }),
+            ),
+            (
+                "SETTINGS PULSE 1 gal 20",
+                SettingsPulse(PulseConfig {
+                    milli_per_pulse: 1000,
+                    unit: 4,
+                    min_width_ms: 20,
+                }),
+            ),
+            ("SETTINGS PULSE 1 furlong", usage("SETTINGS PULSE")),
+            ("SETTINGS PULSE 1.2345 mL", usage("SETTINGS PULSE")),
+            (
+                "SETTINGS BATTLOW OFF",
+                SettingsBattLow(LowVoltageConfig::OFF),
+            ),
+            (
+                "SETTINGS BATTLOW 3600 3300",
+                SettingsBattLow(LowVoltageConfig {
+                    warn_mv: 3600,
+                    critical_mv: 3300,
+                }),
+            ),
+            ("SETTINGS BATTLOW 3300 3600", usage("SETTINGS BATTLOW")),
+            ("SETTINGS BATTLOW 3600", usage("SETTINGS BATTLOW")),
+            ("SETTINGS BATTLOW OFF 3300", usage("SETTINGS BATTLOW")),
+            (
+                "SETTINGS AUTOBRIGHT OFF",
+                SettingsAutoBright(AutoBrightnessConfig::OFF),
+            ),
+            (
+                "SETTINGS AUTOBRIGHT 200 2500",
+                auto_bright(
+                    200,
+                    2500,
+                    auto_brightness::DEFAULT_MIN_LEVEL,
+                    auto_brightness::DEFAULT_MAX_LEVEL,
+                ),
+            ),
+            (
+                "SETTINGS AUTOBRIGHT 200 2500 8 200",
+                auto_bright(200, 2500, 8, 200),
+            ),
+            ("SETTINGS AUTOBRIGHT 2500 200", usage("SETTINGS AUTOBRIGHT")),
+            (
+                "SETTINGS AUTOBRIGHT 200 2500 0",
+                usage("SETTINGS AUTOBRIGHT"),
+            ),
+            (
+                "SETTINGS AUTOBRIGHT 200 2500 100 50",
+                usage("SETTINGS AUTOBRIGHT"),
+            ),
+            ("SETTINGS AUTOBRIGHT 0 3301", usage("SETTINGS AUTOBRIGHT")),
+            (
+                "SETTINGS TZ +05:45",
+                SettingsTimeZone(TimeZone::new(23, DstRule::Off).unwrap()),
+            ),
+            (
+                "SETTINGS TZ -5 us",
+                SettingsTimeZone(TimeZone::new(-20, DstRule::Us).unwrap()),
+            ),
+            (
+                "SETTINGS TZ 0 EU",
+                SettingsTimeZone(TimeZone::UTC.with_dst(DstRule::Eu)),
+            ),
+            ("SETTINGS TZ +08:10", usage("SETTINGS TZ")),
+            ("SETTINGS TZ +15:00", usage("SETTINGS TZ")),
+            ("SETTINGS TZ +01:00 CN", usage("SETTINGS TZ")),
+            ("SETTINGS TZ", usage("SETTINGS TZ")),
+            (
+                "IMG BEGIN 1 32 16",
+                ImageBegin {
+                    slot: 0,
+                    width: 32,
+                    height: 16,
+                },
+            ),
+            ("IMG BEGIN 0 32 16", usage("IMG BEGIN")),
+            ("IMG BEGIN 5 32 16", usage("IMG BEGIN")),
+            ("IMG BEGIN 1 32", usage("IMG BEGIN")),
+            ("IMG DATA AAAA", ImageData("AAAA")),
+            ("IMG END 0xDEADBEEF", ImageEnd(0xDEAD_BEEF)),
+            ("IMG END cafe", ImageEnd(0xCAFE)),
+            ("IMG END xyz", usage("IMG END")),
+            (
+                "IMG SHOW 2",
+                ImageShow {
+                    slot: 1,
+                    top_left: (0, 0),
+                },
+            ),
+            (
+                "IMG SHOW 4 -8 12",
+                ImageShow {
+                    slot: 3,
+                    top_left: (-8, 12),
+                },
+            ),
+            ("IMG SHOW 2 5", usage("IMG SHOW")),
+            ("IMG LIST", ImageList),
+            ("LOG DUMP", LogDump),
+            (
+                "CLOCK SET 07:30",
+                ClockSet {
+                    hour: 7,
+                    minute: 30,
+                },
+            ),
+            ("CLOCK SET 24:00", usage("CLOCK SET")),
+            ("TELEM ON 5", Telemetry(Some(5))),
+            ("TELEM ON 0", usage("TELEM ON")),
+            ("TELEM OFF", Telemetry(None)),
+            ("TELEM FIELDS temp,vsys", TelemetryFields("temp,vsys")),
+            ("PULSE", Pulse),
+            ("PULSE RESET", PulseReset),
+            ("REC START", RecordStart),
+            ("REC STOP", RecordStop),
+            ("REC PLAY", RecordPlay(false)),
+            ("rec play loop", RecordPlay(true)),
+            ("REC PLAY TWICE", usage("REC PLAY")),
+            ("STATUS", Status),
+            ("STATUS ON 10", StatusEvery(Some(10))),
+            ("STATUS OFF", StatusEvery(None)),
+            ("PERF", Perf),
+            ("PERF RESET", PerfReset),
+            ("PERF BUDGET 20", PerfBudget(20)),
+            ("EVENTS", Events),
+            ("FACE", Face),
+            ("FACE LAYOUT six", FaceLayout(Layout::Six)),
+            ("FACE LAYOUT round", usage("FACE LAYOUT")),
+            (
+                "FACE 1 clock",
+                FaceSlot {
+                    slot: 0,
+                    kind: WidgetKind::Clock,
+                    channel: None,
+                },
+            ),
+            (
+                "FACE 6 value temp",
+                FaceSlot {
+                    slot: 5,
+                    kind: WidgetKind::Value,
+                    channel: Some("temp"),
+                },
+            ),
+            ("FACE 2 bar", usage("FACE")),
+            ("FACE 2 clock temp", usage("FACE")),
+            ("FACE 7 clock", usage("FACE")),
+            ("SCAN", Scan),
+            ("SYSINFO", SysInfo),
+            ("FEATURES", Features),
+            ("FB", FrameDump),
+            ("FB DEFMT", FrameDumpDefmt),
+            ("TEXT  hello   world", Text("hello   world")),
+            ("TEXT", usage("TEXT")),
+            ("CLEAR", Clear),
+            ("BRIGHT 0", Bright(0)),
+            ("BRIGHT 255", Bright(255)),
+            ("BRIGHT 256", usage("BRIGHT")),
+            ("INVERT 1", Invert(true)),
+            ("INVERT on", usage("INVERT")),
+            ("SETTINGS MUT ON", Unknown(Some("SETTINGS MUTE"))),
+            ("REBOOT", Unknown(Some("REC START"))),
+            ("WHOAMI", Unknown(None)),
+            ("X", Unknown(None)),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(ConsoleCommand::parse(line), *expected, "{line:?}");
+        }
+    }
+
+    #[cfg(feature = "pico-w")]
+    #[test]
+    fn parses_wifi_commands() {
+        let cases: &[(&str, ConsoleCommand)] = &[
+            ("SETTINGS WIFI home", SettingsWifi(Some(("home", "")))),
+            (
+                "settings wifi home correct horse",
+                SettingsWifi(Some(("home", "correct horse"))),
+            ),
+            ("SETTINGS WIFI off", SettingsWifi(None)),
+            (
+                "SETTINGS WIFI home short",
+                Usage("SETTINGS WIFI <ssid> [password]|OFF"),
+            ),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(ConsoleCommand::parse(line), *expected, "{line:?}");
+        }
+    }
+
+    #[test]
+    fn streaming_commands() {
+        assert!(ConsoleCommand::parse("LOG DUMP").streams());
+        assert!(ConsoleCommand::parse("PERF").streams());
+        assert!(ConsoleCommand::parse("HELP").streams());
+        assert!(!ConsoleCommand::parse("TELEM ON 5").streams());
+        assert!(!ConsoleCommand::parse("TEXT hi").streams());
+    }
+
+    #[cfg(feature = "devtools")]
+    #[test]
+    fn parses_devtools_commands() {
+        let cases: &[(&str, ConsoleCommand)] = &[
+            (
+                "PREVIEW 8 8 AAAA",
+                Preview {
+                    width: 8,
+                    height: 8,
+                    data: "AAAA",
+                },
+            ),
+            ("PREVIEW AT -4 10", PreviewAt(-4, 10)),
+            ("PREVIEW OFF", PreviewOff),
+            ("GRID ON", Grid(true)),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(ConsoleCommand::parse(line), *expected, "{line:?}");
+        }
+    }
+
+    #[test]
+    fn closest_command_needs_a_two_letter_prefix() {
+        assert_eq!(closest_command("SETTINGS   MU"), Some("SETTINGS MUTE"));
+        assert_eq!(closest_command("perf bud"), Some("PERF BUDGET"));
+        assert_eq!(closest_command("SE"), Some("SETTINGS DUMP"));
+        assert_eq!(closest_command("Q"), None);
+        assert_eq!(closest_command(""), None);
+    }
+
+    #[test]
+    fn every_command_is_reachable_and_documented() {
+        for (index, spec) in COMMANDS.iter().enumerate() {
+            assert!(spec.usage.starts_with(spec.name), "{}", spec.name);
+            assert!(
+                COMMANDS[..index]
+                    .iter()
+                    .all(|other| other.name != spec.name),
+                "duplicate {}",
+                spec.name
+            );
+            // 命令名本身一定解析到这条命令(可能是用法说明，但不会是别的命令的)
+            let (found, _) = find_command(spec.name).unwrap();
+            assert_eq!(found.name, spec.name);
+
+            let mut line = String::new();
+            super::Help::new(None).write_line(index, &mut line).unwrap();
+            assert!(line.len() <= HELP_LINE_MAX, "{line:?}");
+            line.clear();
+            super::Help::new(Some(spec.name))
+                .write_line(index, &mut line)
+                .unwrap();
+            assert!(
+                line.starts_with(spec.usage) && line.len() <= HELP_LINE_MAX,
+                "{line:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn help_topic_lists_only_matching_commands() {
+        let help = super::Help::new(Some("IMG"));
+        let mut out = String::new();
+        for index in 0..super::Help::LINES {
+            help.write_line(index, &mut out).unwrap();
+        }
+        assert_eq!(out.lines().count(), 5);
+        assert!(out.lines().all(|line| line.starts_with("IMG ")));
+    }
+
+    fn feed_line<'a>(console: &'a mut Console, bytes: &[u8]) -> Option<ConsoleCommand<'a>> {
+        let (last, head) = bytes.split_last().unwrap();
+        for &byte in head {
+            assert_eq!(console.feed(byte), None);
+        }
+        console.feed(*last)
+    }
+
+    #[test]
+    fn console_edits_and_splits_lines() {
+        let mut console = Console::new();
+        assert_eq!(feed_line(&mut console, b"SCAM\x08N\r"), Some(Scan));
+        assert_eq!(feed_line(&mut console, b"\n"), None);
+        assert_eq!(feed_line(&mut console, b"junk\x15PERF\n"), Some(Perf));
+        assert_eq!(feed_line(&mut console, b"TEXT \xff\r"), Some(Unknown(None)));
+
+        let mut long = [b'A'; LINE_CAPACITY + 2];
+        long[LINE_CAPACITY + 1] = b'\r';
+        assert_eq!(feed_line(&mut console, &long), Some(TooLong));
+        assert_eq!(feed_line(&mut console, b"CLEAR\r"), Some(Clear));
+    }
+}
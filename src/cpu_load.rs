@@ -0,0 +1,88 @@
+//! CPU 有多闲：每秒统计一次主循环睡着(WFI)的时间占多少
+//!
+//! 主循环每一圈干完活就睡到下一毫秒(定时器闹钟叫醒，见 main.rs)，WFI 前后各读一次定时器，
+//! 睡着的时间加起来除以这一秒，就是空闲的百分比。一圈的活超过 1ms 的话这一圈不睡，全算忙。
+//!
+//! 主循环是关着中断(PRIMASK)睡的，叫醒它的中断不进处理函数。但真正的处理函数也有：
+//! core0 上的 `IO_IRQ_BANK0`(脉冲计数，见 pulse_counter.rs)开着中断的时候随时会进，
+//! 刚睡醒、还没读第二次定时器的时候进了的话，它的耗时会被算成空闲。所以处理函数用 `isr` 包一层，
+//! 进出各读一次定时器累计耗时，`CpuMeter::idle` 把睡觉期间累计的那部分从空闲里扣掉，中断的耗时都算忙。
+//! (M0+ 没有 DWT 周期计数器，读的是 1MHz 的定时器，和空闲时间同一个单位。)
+//! core1 上的处理函数(反应测试)占的是 core1，不影响这里。
+//!
+//! 最近一秒的结果放在静态变量里，诊断页面和传感器通道 "idle" 都从 `idle_percent` 读。
+
+use core::sync::atomic::{AtomicU32, AtomicU8, Ordering};
+
+use crate::perf;
+
+/// 多长时间算一次(微秒)
+pub const WINDOW_US: u32 = 1_000_000;
+
+/// 还没算出来过
+const UNKNOWN: u8 = u8::MAX;
+
+/// 最近一秒的空闲百分比
+static IDLE_PERCENT: AtomicU8 = AtomicU8::new(UNKNOWN);
+
+/// core0 上的中断处理函数一共跑了多久(微秒，回绕)
+static ISR_US: AtomicU32 = AtomicU32::new(0);
+
+/// core0 上的中断处理函数包一层：进出各读一次定时器，耗时累计起来，从空闲里扣掉
+pub fn isr<R>(handler: impl FnOnce() -> R) -> R {
+    let start = perf::ticks();
+    let result = handler();
+    // M0+ 没有原子加法。只有处理函数里写，而且同一时间只有一个处理函数在跑，读了再写不会丢
+    let spent = perf::ticks().wrapping_sub(start);
+    ISR_US.store(
+        ISR_US.load(Ordering::Relaxed).wrapping_add(spent),
+        Ordering::Relaxed,
+    );
+    result
+}
+
+/// 最近一秒空闲的百分比，开机第一秒还没过完返回 None
+pub fn idle_percent() -> Option<u8> {
+    let percent = IDLE_PERCENT.load(Ordering::Relaxed);
+    (percent != UNKNOWN).then_some(percent)
+}
+
+/// 按一秒一段累计睡着的时间
+pub struct CpuMeter {
+    window_start: u32,
+    idle_us: u32,
+}
+
+impl CpuMeter {
+    /// `now` 是 `perf::ticks()`
+    pub const fn new(now: u32) -> Self {
+        Self {
+            window_start: now,
+            idle_us: 0,
+        }
+    }
+
+    /// 执行 `sleep`(里面是 WFI)，睡了多久记成空闲，这期间中断处理函数的耗时不算
+    pub fn idle<R>(&mut self, sleep: impl FnOnce() -> R) -> R {
+        let isr_before = ISR_US.load(Ordering::Relaxed);
+        let start = perf::ticks();
+        let result = sleep();
+        let slept = perf::ticks().wrapping_sub(start);
+        let isr = ISR_US.load(Ordering::Relaxed).wrapping_sub(isr_before);
+        self.idle_us = self.idle_us.saturating_add(slept.saturating_sub(isr));
+        result
+    }
+
+    /// 一秒过完了就算出这一秒的空闲百分比(同时更新 `idle_percent`)，没过完返回 None
+    pub fn update(&mut self, now: u32) -> Option<u8> {
+        let elapsed = now.wrapping_sub(self.window_start);
+        if elapsed < WINDOW_US {
+            return None;
+        }
+        let percent = (self.idle_us.min(elapsed) as u64 * 100 / elapsed as u64) as u8;
+        IDLE_PERCENT.store(percent, Ordering::Relaxed);
+        self.window_start = now;
+        self.idle_us = 0;
+        Some(percent)
+    }
+}
@@ -0,0 +1,82 @@
+//! CRC-32(IEEE 802.3，和 zlib 的 crc32 一样)和传感器用的 CRC-8
+//!
+//! 查表法，256 项的表编译期算好放在 flash 里(1K)，算一整屏显存也只要几十微秒。
+//! 设置、数据记录的校验和显存校验(`profiling` feature)都用这一份。
+//!
+//! CRC-8 是 SHT31 和 AHT20 的数据校验(多项式 0x31，初值 0xFF)，每次只算两三个字节，直接按位算。
+
+/// 多项式(反射形式)
+const POLYNOMIAL: u32 = 0xEDB8_8320;
+
+/// 查找表，编译期生成
+const TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ POLYNOMIAL
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// 增量计算，数据不在一块连续内存里的时候用(比如显存的一块区域)
+#[derive(Debug, Clone, Copy)]
+pub struct Crc32(u32);
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Crc32 {
+    pub const fn new() -> Self {
+        Self(!0)
+    }
+
+    pub fn update(&mut self, byte: u8) {
+        self.0 = TABLE[((self.0 ^ byte as u32) & 0xFF) as usize] ^ (self.0 >> 8);
+    }
+
+    pub fn finish(self) -> u32 {
+        !self.0
+    }
+}
+
+/// 一次算完一段数据
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    for &byte in data {
+        crc.update(byte);
+    }
+    crc.finish()
+}
+
+/// CRC-8 的多项式(x^8 + x^5 + x^4 + 1，不反射)
+const CRC8_POLYNOMIAL: u8 = 0x31;
+
+/// SHT31/AHT20 的 CRC-8
+pub fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0xFFu8;
+    for &byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ CRC8_POLYNOMIAL
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
@@ -0,0 +1,297 @@
+//! 本地仪表盘：不接电脑的时候显示的页面
+//!
+//! 显示开機时长和传感器注册表(`sensors`)里的每个通道，注册了新传感器这里自动多一行(最多放 4 行)。
+//! 有电池电量通道的话右上角画一个电池图标(见 `battery`)。大字模式下只显示前两个通道。
+//! 标题行和每个通道各是一个窗口(见 `compositor`)：秒数变了只重画标题行，有新读数只重画读数变了的那几行。
+//! 按 Select 进诊断页面，按 Down 进数据记录页面，按 Up 进输出控制页面，按 Back 进气压计页面。
+//!
+//! 注册表放在 `RefCell` 里和主循环共用：主循环在画图之外的时候读传感器，页面只在画图的时候借来看，两边不会同时借。
+
+use core::cell::RefCell;
+use core::convert::Infallible;
+use core::fmt::Write;
+
+use embedded_graphics::geometry::{OriginDimensions, Point, Size};
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::text::Text;
+use embedded_graphics::Drawable;
+use heapless::{String, Vec};
+
+use crate::app::{Canvas, Event, Page, PageId, Transition};
+use crate::compositor::Compositor;
+use crate::input::{Button, ButtonEvent};
+use crate::large_text::draw_large_readings;
+use crate::sensors::{ChannelId, SensorChannel, SensorRegistry, HISTORY_LEN};
+use crate::text::{draw_centered, text_pixel_width};
+use crate::theme::{self, PatternTarget};
+use crate::widgets::{draw_bar_chart_styled, draw_battery_icon, BATTERY_ICON_SIZE};
+
+/// 标题行(开机时长)的基线
+const TITLE_Y: i32 = 8;
+
+/// 第一个通道那一行的顶边和行高
+const FIRST_ROW_TOP: i32 = 12;
+const ROW_HEIGHT: i32 = 13;
+
+/// 读数右对齐到这个 x，右边留给柱状图
+const VALUE_RIGHT: i32 = 84;
+
+/// 历史柱状图的宽高
+const CHART_WIDTH: u32 = 40;
+const CHART_HEIGHT: u32 = 10;
+
+/// 最多放几个通道
+const MAX_ROWS: usize = 4;
+
+/// 窗口(见 `compositor`)：第 0 个是标题行(开机时长和电池图标)，后面一个通道一个
+const WINDOWS: usize = 1 + MAX_ROWS;
+const HEADER: usize = 0;
+
+/// 按屏幕尺寸分窗口，放不下的通道窗口面积是 0
+fn layout(size: Size) -> [Rectangle; WINDOWS] {
+    core::array::from_fn(|index| {
+        if index == HEADER {
+            return Rectangle::new(Point::zero(), Size::new(size.width, FIRST_ROW_TOP as u32));
+        }
+        let top = FIRST_ROW_TOP + (index - 1) as i32 * ROW_HEIGHT;
+        if top + ROW_HEIGHT > size.height as i32 {
+            return Rectangle::zero();
+        }
+        Rectangle::new(Point::new(0, top), Size::new(size.width, ROW_HEIGHT as u32))
+    })
+}
+
+/// 标题行：开机时长，`battery` 是电量(%)通道，有读数就在右上角画电池图标。`now_ms` 是开机以来的毫秒数
+fn draw_header<D, S>(
+    display: &mut D,
+    sensors: &SensorRegistry<S>,
+    battery: Option<ChannelId>,
+    now_ms: u64,
+) -> Result<(), D::Error>
+where
+    D: PatternTarget,
+{
+    let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+    let seconds = now_ms / 1000;
+    let mut line: String<16> = String::new();
+    // 最多也就 "up 99999:59:59"，不会超容量
+    let _ = write!(
+        line,
+        "up {:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    );
+    draw_centered(display, &line, TITLE_Y, style)?;
+
+    if let Some(percent) = battery.and_then(|id| sensors.value(id)) {
+        // 右边留一列给正极凸起
+        let width = display.bounding_box().size.width as i32;
+        let left = width - BATTERY_ICON_SIZE.width as i32 - 1;
+        draw_battery_icon(display, Point::new(left, 1), percent.clamp(0, 100) as u8)?;
+    }
+    Ok(())
+}
+
+/// 一个通道一行：名字、最新读数、最近读数的柱状图，读不到的显示 "--"。画在行的窗口里，原点是行的左上角
+fn draw_row<D, S>(display: &mut D, channel: &SensorChannel<S>) -> Result<(), D::Error>
+where
+    D: PatternTarget,
+{
+    let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+    let width = display.bounding_box().size.width as i32;
+    let baseline = CHART_HEIGHT as i32 - 1;
+    Text::new(channel.info().name, Point::new(0, baseline), style).draw(display)?;
+
+    let value = row_text(channel);
+    let value_x = VALUE_RIGHT - text_pixel_width(&value, &FONT_6X10) as i32;
+    Text::new(&value, Point::new(value_x, baseline), style).draw(display)?;
+
+    // 柱子的高度按这段历史里的最小值到最大值拉满
+    let (lo, hi) = channel
+        .history()
+        .fold((i32::MAX, i32::MIN), |(lo, hi), v| (lo.min(v), hi.max(v)));
+    let span = (hi - lo).max(1);
+    let chart = Rectangle::new(
+        Point::new(width - CHART_WIDTH as i32, 0),
+        Size::new(CHART_WIDTH, CHART_HEIGHT),
+    );
+    let bars: Vec<u8, HISTORY_LEN> = channel
+        .history()
+        .map(|v| (1 + (v - lo) * (CHART_HEIGHT as i32 - 1) / span) as u8)
+        .collect();
+    draw_bar_chart_styled(
+        display,
+        chart,
+        bars.iter().copied(),
+        CHART_HEIGHT as u8,
+        &theme::active().bar_chart,
+    )
+}
+
+/// 读数连单位的写法
+fn row_text<S>(channel: &SensorChannel<S>) -> String<16> {
+    let mut value = String::new();
+    let _ = channel.info().write_value(channel.value(), &mut value);
+    value
+}
+
+/// 一行画出来的样子取决于什么：读数的写法(带单位、"--")和历史的指纹
+fn row_snapshot<S>(channel: &SensorChannel<S>) -> (String<16>, u32) {
+    let history = channel
+        .history()
+        .fold(0u32, |hash, v| hash.rotate_left(5) ^ v as u32);
+    (row_text(channel), history)
+}
+
+/// 仪表盘页面，每过一秒或者有新读数的时候重画
+pub struct DashboardPage<'a, S> {
+    /// 诊断页面的编号
+    diagnostics: PageId,
+    /// 数据记录页面的编号
+    log: PageId,
+    /// 输出控制页面的编号
+    outputs: PageId,
+    /// 气压计页面的编号
+    baro: PageId,
+    /// 传感器注册表，主循环负责读，这里只看
+    sensors: &'a RefCell<SensorRegistry<S>>,
+    /// 电池电量通道，没有就不画电池图标
+    battery: Option<ChannelId>,
+    /// 上次画的时候是第几秒
+    shown_second: Option<u64>,
+    /// 上次画的时候注册表的 `generation`
+    shown_generation: Option<u32>,
+    /// 标题行一个窗口，每个通道一个窗口，只重画变了的
+    compositor: Compositor<WINDOWS>,
+    /// 每一行上次画的样子(`row_snapshot`)，没变就不重画
+    shown_rows: [Option<(String<16>, u32)>; MAX_ROWS],
+}
+
+impl<'a, S> DashboardPage<'a, S> {
+    pub fn new(
+        diagnostics: PageId,
+        log: PageId,
+        outputs: PageId,
+        baro: PageId,
+        sensors: &'a RefCell<SensorRegistry<S>>,
+        battery: Option<ChannelId>,
+    ) -> Self {
+        Self {
+            diagnostics,
+            log,
+            outputs,
+            baro,
+            sensors,
+            battery,
+            shown_second: None,
+            shown_generation: None,
+            // 画第一帧的时候按画布尺寸重新分
+            compositor: Compositor::new([Rectangle::zero(); WINDOWS]),
+            shown_rows: [const { None }; MAX_ROWS],
+        }
+    }
+
+    /// 重画脏窗口
+    fn compose(&mut self, canvas: &mut Canvas, now_ms: u64) -> Result<(), Infallible> {
+        let sensors = self.sensors.borrow();
+        let battery = self.battery;
+        self.compositor
+            .compose(canvas, |index, window| match index {
+                HEADER => draw_header(window, &sensors, battery, now_ms),
+                row => match sensors.channels().get(row - 1) {
+                    Some(channel) => draw_row(window, channel),
+                    None => Ok(()),
+                },
+            })?;
+        Ok(())
+    }
+}
+
+impl<S> Page for DashboardPage<'_, S> {
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        match event {
+            Event::Button(ButtonEvent::Pressed(Button::Select)) => {
+                Transition::Push(self.diagnostics)
+            }
+            Event::Button(ButtonEvent::Pressed(Button::Down)) => Transition::Push(self.log),
+            Event::Button(ButtonEvent::Pressed(Button::Up)) => Transition::Push(self.outputs),
+            Event::Button(ButtonEvent::Pressed(Button::Back)) => Transition::Push(self.baro),
+            _ => Transition::None,
+        }
+    }
+
+    /// 内容每秒才变一次，2 帧每秒保证秒数变了最多晚半秒显示
+    fn desired_fps(&self) -> u16 {
+        2
+    }
+
+    /// 主页面，轮播的时候多停一会儿
+    fn dwell_ms(&self) -> Option<u32> {
+        Some(15_000)
+    }
+
+    /// 秒数变了重画标题行；有新读数的时候逐行比较，只重画变了的行。电量也可能变了，标题行一起重画
+    fn tick(&mut self, now_ms: u64) -> bool {
+        let second = now_ms / 1000;
+        if self.shown_second != Some(second) {
+            self.shown_second = Some(second);
+            self.compositor.invalidate(HEADER);
+        }
+        let sensors = self.sensors.borrow();
+        let generation = sensors.generation();
+        if self.shown_generation != Some(generation) {
+            self.shown_generation = Some(generation);
+            self.compositor.invalidate(HEADER);
+            for (row, shown) in self.shown_rows.iter_mut().enumerate() {
+                let snapshot = sensors.channels().get(row).map(row_snapshot);
+                if *shown != snapshot {
+                    *shown = snapshot;
+                    self.compositor.invalidate(1 + row);
+                }
+            }
+        }
+        self.compositor.is_dirty()
+    }
+
+    fn on_rotation_changed(&mut self, size: Size) {
+        self.compositor.set_areas(layout(size));
+    }
+
+    /// 整屏清过了，按画布尺寸重新分窗口，全部重画
+    fn render(&mut self, canvas: &mut Canvas, now_ms: u64) -> Result<(), Infallible> {
+        self.compositor.set_areas(layout(canvas.size()));
+        self.compose(canvas, now_ms)
+    }
+
+    fn redraws_partially(&self) -> bool {
+        true
+    }
+
+    fn render_dirty(&mut self, canvas: &mut Canvas, now_ms: u64) -> Result<(), Infallible> {
+        self.compose(canvas, now_ms)
+    }
+
+    /// 大字版：只放前两个通道(片内温度和 ADC0)
+    fn render_large(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<bool, Infallible> {
+        let sensors = self.sensors.borrow();
+        let values: [String<16>; 2] = core::array::from_fn(|i| {
+            let mut value = String::new();
+            if let Some(channel) = sensors.channels().get(i) {
+                let _ = channel.info().write_value(channel.value(), &mut value);
+            }
+            value
+        });
+        let readings: Vec<(&str, &str), 2> = sensors
+            .channels()
+            .iter()
+            .zip(values.iter())
+            .map(|(channel, value)| (channel.info().name, value.as_str()))
+            .collect();
+        draw_large_readings(canvas, &readings)?;
+        Ok(true)
+    }
+}
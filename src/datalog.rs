@@ -0,0 +1,296 @@
+//! 数据记录：把传感器采样追加写进 flash，断电重启之后接着写
+//!
+//! 数据区是 `flash::LOG_SECTORS` 个扇区组成的环，写满了就擦掉最老的那个扇区接着写，
+//! 每个扇区轮流被擦，磨损是均匀的。
+//!
+//! 每个扇区开头 16 字节是扇区头：魔数、序号、CRC。序号每用一个新扇区加一，
+//! 重启之后扫一遍扇区头，序号最大的就是正在写的扇区，按序号排好就是从旧到新的顺序。
+//! 扇区头后面是 255 条 16 字节的记录：
+//!
+//! | 偏移 | 长度 | 内容 |
+//! |------|------|------|
+//! | 0 | 4 | 开机以来的秒数 |
+//! | 4 | 2 | 第几次开机 |
+//! | 6 | 2 | 0 号通道的读数(有符号) |
+//! | 8 | 2 | 1 号通道的读数 |
+//! | 10 | 2 | 2 号通道的读数 |
+//! | 12 | 4 | 前 12 字节的 CRC-32 |
+//!
+//! 记的是传感器注册表(`sensors`)里前 `LOG_CHANNELS` 个通道，单位和比例跟着通道走，
+//! 读不到的通道记成 `i16::MIN`。以前的格式(魔数 "LOG1"，温度 + ADC 原始值)和现在的不兼容，
+//! 升级之后旧扇区会被当成没用过，按顺序擦掉重新用。
+//!
+//! 全是 0xFF 的槽位是空的。写到一半断电的记录 CRC 对不上，读的时候跳过，写的时候也不会再用这个槽位。
+//! 擦完扇区还没写扇区头就断电的话，这个扇区被当成没用过，下次用之前会重新擦。
+
+use core::fmt;
+
+use heapless::Vec;
+
+use crate::crc::crc32;
+use crate::flash::{self, LOG_OFFSET, LOG_SECTORS, SECTOR_LEN};
+use crate::sensors::ChannelInfo;
+
+/// 扇区头的魔数，"LOG2"
+const SECTOR_MAGIC: u32 = u32::from_le_bytes(*b"LOG2");
+
+/// 每条记录记几个通道
+pub const LOG_CHANNELS: usize = 3;
+
+/// 读不到的通道存成这个值
+const MISSING: i16 = i16::MIN;
+
+/// 扇区头长度
+const SECTOR_HEADER_LEN: usize = 16;
+
+/// 一条记录的长度
+pub const RECORD_LEN: usize = 16;
+
+/// 每个扇区能放几条记录
+pub const RECORDS_PER_SECTOR: usize = (SECTOR_LEN - SECTOR_HEADER_LEN) / RECORD_LEN;
+
+/// 整个数据区最多放几条记录
+pub const CAPACITY: usize = LOG_SECTORS * RECORDS_PER_SECTOR;
+
+/// 一条记录
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Record {
+    /// 第几次开机(从 1 开始)，开机时长每次开机都会从 0 开始，靠这个区分
+    pub boot: u16,
+    /// 开机以来的秒数
+    pub uptime_s: u32,
+    /// 前 `LOG_CHANNELS` 个通道的读数，读不到的是 None
+    pub values: [Option<i16>; LOG_CHANNELS],
+}
+
+/// 一行 CSV 最长多少字节(含换行)
+pub const CSV_LINE_MAX: usize = 48;
+
+/// 写 CSV 表头(带 `\r\n`)，列和 `Record::write_csv` 对应。`channels` 是注册表里的通道
+pub fn write_csv_header<W: fmt::Write>(channels: &[ChannelInfo], out: &mut W) -> fmt::Result {
+    out.write_str("boot,uptime_s")?;
+    for channel in channels.iter().take(LOG_CHANNELS) {
+        write!(out, ",{}_{}", channel.name, channel.unit)?;
+    }
+    out.write_str("\r\n")
+}
+
+impl Record {
+    /// 写成一行 CSV(带 `\r\n`)，读不到的通道是空的
+    pub fn write_csv<W: fmt::Write>(&self, channels: &[ChannelInfo], out: &mut W) -> fmt::Result {
+        write!(out, "{},{}", self.boot, self.uptime_s)?;
+        for (channel, value) in channels.iter().zip(self.values) {
+            out.write_char(',')?;
+            if let Some(value) = value {
+                channel.write_number(value as i32, out)?;
+            }
+        }
+        out.write_str("\r\n")
+    }
+
+    fn encode(&self) -> [u8; RECORD_LEN] {
+        let mut out = [0xFFu8; RECORD_LEN];
+        out[0..4].copy_from_slice(&self.uptime_s.to_le_bytes());
+        out[4..6].copy_from_slice(&self.boot.to_le_bytes());
+        for (i, value) in self.values.iter().enumerate() {
+            let value = value.unwrap_or(MISSING);
+            out[6 + i * 2..8 + i * 2].copy_from_slice(&value.to_le_bytes());
+        }
+        let crc = crc32(&out[..12]);
+        out[12..16].copy_from_slice(&crc.to_le_bytes());
+        out
+    }
+}
+
+/// 一个槽位里是什么
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Slot {
+    Empty,
+    /// 写到一半断电了
+    Torn,
+    Valid(Record),
+}
+
+fn sector_offset(sector: usize) -> u32 {
+    LOG_OFFSET + (sector * SECTOR_LEN) as u32
+}
+
+fn slot_offset(sector: usize, slot: usize) -> u32 {
+    sector_offset(sector) + (SECTOR_HEADER_LEN + slot * RECORD_LEN) as u32
+}
+
+fn read_slot(sector: usize, slot: usize) -> Slot {
+    let bytes = flash::read(slot_offset(sector, slot), RECORD_LEN);
+    if bytes.iter().all(|&b| b == 0xFF) {
+        return Slot::Empty;
+    }
+    let word =
+        |at: usize| u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]]);
+    let half = |at: usize| u16::from_le_bytes([bytes[at], bytes[at + 1]]);
+    if word(12) != crc32(&bytes[..12]) {
+        return Slot::Torn;
+    }
+    Slot::Valid(Record {
+        boot: half(4),
+        uptime_s: word(0),
+        values: core::array::from_fn(|i| {
+            let value = half(6 + i * 2) as i16;
+            (value != MISSING).then_some(value)
+        }),
+    })
+}
+
+/// 读扇区头，返回序号；没写过或者坏了返回 None
+fn read_header(sector: usize) -> Option<u32> {
+    let bytes = flash::read(sector_offset(sector), SECTOR_HEADER_LEN);
+    let word =
+        |at: usize| u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]]);
+    (word(0) == SECTOR_MAGIC && word(8) == crc32(&bytes[..8])).then(|| word(4))
+}
+
+fn encode_header(seq: u32) -> [u8; SECTOR_HEADER_LEN] {
+    let mut out = [0xFFu8; SECTOR_HEADER_LEN];
+    out[0..4].copy_from_slice(&SECTOR_MAGIC.to_le_bytes());
+    out[4..8].copy_from_slice(&seq.to_le_bytes());
+    let crc = crc32(&out[..8]);
+    out[8..12].copy_from_slice(&crc.to_le_bytes());
+    out
+}
+
+/// 数据区的目录：哪些扇区在用、按什么顺序、最新的扇区写到哪了
+///
+/// 记录按"槽位编号"访问：0 是最老的槽位，`slots() - 1` 是最新的。坏掉的槽位也占一个编号，
+/// 读出来是 None，画曲线的时候就是一个缺口。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogIndex {
+    /// 在用的扇区，从旧到新
+    order: Vec<u8, LOG_SECTORS>,
+    /// 最新序号
+    head_seq: u32,
+    /// 最新的扇区用了几个槽位
+    head_fill: usize,
+}
+
+impl LogIndex {
+    /// 扫描 flash，重建目录。只读扇区头和最新扇区里的槽位，很快
+    pub fn scan() -> Self {
+        let mut sectors: Vec<(u32, u8), LOG_SECTORS> = Vec::new();
+        for sector in 0..LOG_SECTORS {
+            if let Some(seq) = read_header(sector) {
+                let _ = sectors.push((seq, sector as u8));
+            }
+        }
+        sectors.sort_unstable();
+
+        let mut index = Self {
+            order: sectors.iter().map(|&(_, sector)| sector).collect(),
+            head_seq: sectors.last().map_or(0, |&(seq, _)| seq),
+            head_fill: 0,
+        };
+        if let Some(&head) = index.order.last() {
+            // 最后一个不是空的槽位之后才是空位
+            index.head_fill = (0..RECORDS_PER_SECTOR)
+                .rev()
+                .find(|&slot| read_slot(head as usize, slot) != Slot::Empty)
+                .map_or(0, |slot| slot + 1);
+        }
+        index
+    }
+
+    /// 一共有多少个槽位(包括坏掉的)
+    pub fn slots(&self) -> usize {
+        match self.order.len() {
+            0 => 0,
+            n => (n - 1) * RECORDS_PER_SECTOR + self.head_fill,
+        }
+    }
+
+    /// 读第 `index` 个槽位，坏掉的、超出范围的返回 None
+    pub fn get(&self, index: usize) -> Option<Record> {
+        let sector = *self.order.get(index / RECORDS_PER_SECTOR)?;
+        if index >= self.slots() {
+            return None;
+        }
+        match read_slot(sector as usize, index % RECORDS_PER_SECTOR) {
+            Slot::Valid(record) => Some(record),
+            _ => None,
+        }
+    }
+
+    /// 从旧到新遍历所有好的记录
+    pub fn records(&self) -> impl Iterator<Item = Record> + '_ {
+        (0..self.slots()).filter_map(|index| self.get(index))
+    }
+}
+
+/// 记录器：往环里追加记录
+#[derive(Debug)]
+pub struct DataLog {
+    index: LogIndex,
+    /// 这次开机的编号
+    boot: u16,
+}
+
+impl DataLog {
+    /// 扫描 flash，找到上次写到哪了。开机编号是已有记录里最大的加一
+    pub fn open() -> Self {
+        let index = LogIndex::scan();
+        let last_boot = index.records().map(|record| record.boot).max().unwrap_or(0);
+        Self {
+            index,
+            boot: last_boot.wrapping_add(1),
+        }
+    }
+
+    pub fn index(&self) -> &LogIndex {
+        &self.index
+    }
+
+    /// 这次开机的编号
+    pub fn boot(&self) -> u16 {
+        self.boot
+    }
+
+    /// 追加一条记录。当前扇区满了会先擦一个扇区(大概 50ms)，其余情况写一页不到 1ms
+    ///
+    /// 读数超出 i16 的范围会被截到边上
+    pub fn append(&mut self, uptime_s: u64, values: [Option<i32>; LOG_CHANNELS]) {
+        let head_full = self.index.head_fill >= RECORDS_PER_SECTOR;
+        if self.index.order.is_empty() || head_full {
+            self.start_sector();
+        }
+        let Some(&head) = self.index.order.last() else {
+            return;
+        };
+        let record = Record {
+            boot: self.boot,
+            uptime_s: uptime_s as u32,
+            values: values.map(|value| {
+                value.map(|value| value.clamp(MISSING as i32 + 1, i16::MAX as i32) as i16)
+            }),
+        };
+        flash::program_bytes(
+            slot_offset(head as usize, self.index.head_fill),
+            &record.encode(),
+        );
+        self.index.head_fill += 1;
+    }
+
+    /// 换一个新扇区：环里的下一个，没用过就直接用，用过的话里面是最老的数据，丢掉
+    fn start_sector(&mut self) {
+        let next = match self.index.order.last() {
+            Some(&head) => (head as usize + 1) % LOG_SECTORS,
+            None => 0,
+        };
+        if let Some(pos) = self.index.order.iter().position(|&s| s as usize == next) {
+            self.index.order.remove(pos);
+        }
+        let seq = self.index.head_seq.wrapping_add(1);
+        flash::erase_sector(sector_offset(next));
+        flash::program_bytes(sector_offset(next), &encode_header(seq));
+        // 上面把 next 移出去了，这里一定放得下
+        let _ = self.index.order.push(next as u8);
+        self.index.head_seq = seq;
+        self.index.head_fill = 0;
+    }
+}
@@ -0,0 +1,155 @@
+//! 演示自动轮换：摆出来展示的时候，没人按键一段时间就自己一个个演示放过去
+//!
+//! 和自动轮播(`carousel`)是两回事：轮播管的是栈底那一层的几个信息页面，这里管的是演示菜单里的演示，
+//! 演示是压在菜单上面的，所以两个不会抢着换页。
+//!
+//! 最后一次按键之后 `idle_s` 秒还没人动，就开始轮换：停在演示菜单上的话进第一个演示，停在某个演示上的话
+//! 放满 `dwell_s` 秒(页面自己说了停多久(`Page::dwell_ms`)的按页面的)换下一个，没数据的演示跳过。
+//! 轮换的时候底边画和轮播一样的进度条。一按键马上停下，留在当前的演示上，等下一次闲够了 `idle_s` 再接着换。
+//! 在别的页面(仪表盘、设置菜单)上不管闲多久都不动。
+//!
+//! 两个时间用 `SETTINGS DEMO <idle_s> [dwell_s]` 设，存在设置里；`SETTINGS DEMO OFF` 关掉，默认是关的。
+
+use crate::app::{PageId, Scheduler, Transition};
+
+/// 编码之后多少字节：闲多久(秒)2 字节 + 每个演示放多久(秒)2 字节
+pub const ENCODED_LEN: usize = 4;
+
+/// 没写每个演示放多久的时候放 15 秒
+pub const DEFAULT_DWELL_S: u16 = 15;
+
+/// 演示自动轮换的设置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct DemoAutoConfig {
+    /// 最后一次按键之后多久(秒)开始轮换，0 是不轮换
+    pub idle_s: u16,
+    /// 每个演示放多久(秒)，至少 1 秒
+    pub dwell_s: u16,
+}
+
+impl Default for DemoAutoConfig {
+    fn default() -> Self {
+        Self {
+            idle_s: 0,
+            dwell_s: DEFAULT_DWELL_S,
+        }
+    }
+}
+
+impl DemoAutoConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.idle_s > 0
+    }
+
+    pub fn encode(&self) -> [u8; ENCODED_LEN] {
+        let mut out = [0u8; ENCODED_LEN];
+        out[0..2].copy_from_slice(&self.idle_s.to_le_bytes());
+        out[2..4].copy_from_slice(&self.dwell_s.to_le_bytes());
+        out
+    }
+
+    /// 解码。放多久是 0 返回 None
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let &[idle_lo, idle_hi, dwell_lo, dwell_hi] = bytes else {
+            return None;
+        };
+        let dwell_s = u16::from_le_bytes([dwell_lo, dwell_hi]);
+        (dwell_s > 0).then_some(Self {
+            idle_s: u16::from_le_bytes([idle_lo, idle_hi]),
+            dwell_s,
+        })
+    }
+}
+
+/// 轮换的节奏：主循环里每一圈调 `update`，每次按键调 `input`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DemoAuto {
+    /// 最后一次按键
+    last_input_ms: u64,
+    /// 当前演示从什么时候开始算
+    shown_since_ms: u64,
+    /// 上一圈是不是在轮换，停下来的那一下要把进度条收掉
+    running: bool,
+}
+
+impl DemoAuto {
+    pub const fn new() -> Self {
+        Self {
+            last_input_ms: 0,
+            shown_since_ms: 0,
+            running: false,
+        }
+    }
+
+    /// 有人按键了，停下来
+    pub fn input(&mut self, now_ms: u64) {
+        self.last_input_ms = now_ms;
+    }
+
+    /// 闲够了就进演示、到时间换下一个。`menu` 是演示菜单，`scenes` 是参加轮换的演示，按轮换的顺序。
+    ///
+    /// 返回现在是不是在轮换。在轮换的时候进度条归这里管，调用方这一圈就别再调 `Carousel::update` 了
+    pub fn update<const N: usize>(
+        &mut self,
+        config: &DemoAutoConfig,
+        scheduler: &mut Scheduler<'_, N>,
+        menu: PageId,
+        scenes: &[PageId],
+        now_ms: u64,
+    ) -> bool {
+        let current = scheduler.current();
+        let in_menu = current == menu;
+        let in_scene = scenes.contains(&current);
+        let idle = now_ms.saturating_sub(self.last_input_ms) >= config.idle_s as u64 * 1000;
+        // 栈底那一层归轮播管
+        let running = config.is_enabled() && idle && scheduler.depth() > 1 && (in_menu || in_scene);
+        if !running {
+            if self.running {
+                scheduler.set_carousel_progress(None);
+            }
+            self.running = false;
+            self.shown_since_ms = now_ms;
+            return false;
+        }
+        self.running = true;
+
+        if in_scene {
+            let dwell_ms = scheduler
+                .page_dwell_ms(current)
+                .map_or(config.dwell_s as u64 * 1000, u64::from)
+                .max(1);
+            let elapsed = now_ms - self.shown_since_ms;
+            if elapsed < dwell_ms && scheduler.page_has_data(current) {
+                scheduler.set_carousel_progress(Some((elapsed * 100 / dwell_ms) as u8));
+                return true;
+            }
+        }
+
+        self.shown_since_ms = now_ms;
+        scheduler.set_carousel_progress(Some(0));
+        let Some(next) = next_scene(scenes, current, |id| scheduler.page_has_data(id)) else {
+            return true;
+        };
+        if in_menu {
+            scheduler.apply(Transition::Push(next));
+        } else if next != current {
+            scheduler.apply(Transition::Replace(next));
+        }
+        true
+    }
+}
+
+/// `current` 后面第一个有数据的演示(转一圈)。`current` 不在里面的话从第一个开始找
+fn next_scene(
+    scenes: &[PageId],
+    current: PageId,
+    has_data: impl Fn(PageId) -> bool,
+) -> Option<PageId> {
+    let start = scenes
+        .iter()
+        .position(|&id| id == current)
+        .map_or(0, |index| index + 1);
+    (0..scenes.len())
+        .map(|offset| scenes[(start + offset) % scenes.len()])
+        .find(|&id| has_data(id))
+}
@@ -0,0 +1,225 @@
+//! 开发工具(feature `devtools`)：电脑上改好的精灵图直接推到屏幕上看，不用每改一笔都重新编译、烧录
+//!
+//! 电脑端是 `tools/sprite_preview.py`：把 PNG 转成精灵的字节格式(和 `sprite`、`image_slots` 一样，
+//! 一行一行、一个字节 8 个像素、高位在左)，切成一块一块用串口命令发过来：
+//!
+//! ```text
+//! PREVIEW <w> <h> <base64>      一块数据，同样宽高的接着往后拼，收齐了马上显示
+//! PREVIEW AT <x> <y>            图的左上角放在哪，默认 0 0，下一张也按这个放
+//! PREVIEW OFF                   关掉预览页面，回到原来的页面
+//! GRID ON|OFF                   在预览上面叠一层 8 像素的网格
+//! ```
+//!
+//! 一行命令放不下整张图(128x64 是 1024 字节，base64 一千多个字)，所以每行最多带 120 字节：
+//! 宽高和正在拼的那张不一样、或者上一张已经收齐了，就当作一张新图从头开始拼；拼到 `w x h` 要的字节数就换上去显示，
+//! 拼的过程中屏幕上还是上一张，不会出现画了一半的图。超过 `w x h` 的整张作废，
+//! 两块之间隔了 `CHUNK_TIMEOUT_MS` 也作废(主机那边断了)。比 128x64 大的图第一块就拒绝。
+//!
+//! 图只在内存里(收的一份、显示的一份，各 1K)，不写 flash，重启就没了。
+//! 预览是一个单独的页面，关掉的时候下面的页面整页重画，网格、预览的图都不会留在屏幕上。
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use embedded_graphics::geometry::{OriginDimensions, Point, Size};
+use embedded_graphics::image::{Image, ImageRaw};
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::Drawable;
+use heapless::Vec;
+
+use crate::framebuffer::FrameBuffer;
+use crate::image_slots::{image_len, ImageError, MAX_HEIGHT, MAX_IMAGE_BYTES, MAX_WIDTH};
+
+/// 两块数据之间最多隔多久(毫秒)，再长就当主机断了
+pub const CHUNK_TIMEOUT_MS: u64 = 2000;
+
+/// 一块最多多少字节：`PREVIEW 128 64 ` 后面一行还剩 160 个 base64 字
+pub const CHUNK_BYTES: usize = 120;
+
+/// 网格的间距，正好是一个字节的 8 个像素
+pub const GRID_STEP: i32 = 8;
+
+/// 一张图
+struct Picture {
+    width: u16,
+    height: u16,
+    data: Vec<u8, MAX_IMAGE_BYTES>,
+}
+
+impl Picture {
+    fn is_complete(&self) -> bool {
+        self.data.len() == image_len(self.width, self.height)
+    }
+}
+
+/// 收了一块以后的进度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Progress {
+    /// 还没收齐：收了多少、一共要多少
+    Partial { received: usize, expected: usize },
+    /// 收齐了，已经换上去了
+    Complete,
+}
+
+/// 预览的状态：正在拼的、正在显示的、放在哪、要不要网格
+pub struct Preview {
+    incoming: Option<Picture>,
+    last_chunk_ms: u64,
+    shown: Option<Picture>,
+    origin: Point,
+    grid: bool,
+    /// 显示的东西变一次加一，页面靠它判断要不要重画
+    generation: u32,
+}
+
+impl Default for Preview {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Preview {
+    pub const fn new() -> Self {
+        Self {
+            incoming: None,
+            last_chunk_ms: 0,
+            shown: None,
+            origin: Point::zero(),
+            grid: false,
+            generation: 0,
+        }
+    }
+
+    /// 收一块 w x h 的图的数据，见模块文档。出错的话正在拼的整张作废，显示的那张不动
+    pub fn chunk(
+        &mut self,
+        width: u16,
+        height: u16,
+        chunk: &str,
+        now_ms: u64,
+    ) -> Result<Progress, ImageError> {
+        let result = self.append(width, height, chunk, now_ms);
+        if result.is_err() {
+            self.incoming = None;
+        }
+        result
+    }
+
+    fn append(
+        &mut self,
+        width: u16,
+        height: u16,
+        chunk: &str,
+        now_ms: u64,
+    ) -> Result<Progress, ImageError> {
+        if width == 0 || height == 0 {
+            return Err(ImageError::Empty);
+        }
+        if width > MAX_WIDTH || height > MAX_HEIGHT {
+            return Err(ImageError::TooLarge);
+        }
+        let restart = self
+            .incoming
+            .as_ref()
+            .is_none_or(|picture| (picture.width, picture.height) != (width, height));
+        if restart {
+            self.incoming = Some(Picture {
+                width,
+                height,
+                data: Vec::new(),
+            });
+        }
+        let Some(picture) = self.incoming.as_mut() else {
+            return Err(ImageError::NotUploading);
+        };
+        let mut decoded = [0u8; CHUNK_BYTES];
+        let len = STANDARD
+            .decode_slice(chunk, &mut decoded)
+            .map_err(|_| ImageError::Encoding)?;
+        let expected = image_len(width, height);
+        if picture.data.len() + len > expected {
+            return Err(ImageError::Overflow);
+        }
+        // 上面比过了，expected 不会超过容量
+        let _ = picture.data.extend_from_slice(&decoded[..len]);
+        self.last_chunk_ms = now_ms;
+        if !picture.is_complete() {
+            return Ok(Progress::Partial {
+                received: picture.data.len(),
+                expected,
+            });
+        }
+        self.shown = self.incoming.take();
+        self.generation = self.generation.wrapping_add(1);
+        Ok(Progress::Complete)
+    }
+
+    /// 拼到一半太久没下一块就作废，作废了返回 true(只返回一次)
+    pub fn expire(&mut self, now_ms: u64) -> bool {
+        let stale = self.incoming.is_some()
+            && now_ms.saturating_sub(self.last_chunk_ms) >= CHUNK_TIMEOUT_MS;
+        if stale {
+            self.incoming = None;
+        }
+        stale
+    }
+
+    /// 有没有收齐过一张
+    pub fn has_picture(&self) -> bool {
+        self.shown.is_some()
+    }
+
+    /// 显示的那张多大
+    pub fn picture_size(&self) -> Option<Size> {
+        self.shown
+            .as_ref()
+            .map(|picture| Size::new(picture.width as u32, picture.height as u32))
+    }
+
+    pub fn set_origin(&mut self, origin: Point) {
+        self.origin = origin;
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    pub fn origin(&self) -> Point {
+        self.origin
+    }
+
+    pub fn set_grid(&mut self, on: bool) {
+        self.grid = on;
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// 画图，开着网格的话再叠上网格
+    pub fn draw(&self, canvas: &mut FrameBuffer) {
+        if let Some(picture) = &self.shown {
+            let raw = ImageRaw::<BinaryColor>::new(&picture.data, picture.width as u32);
+            let Ok(()) = Image::new(&raw, self.origin).draw(canvas);
+        }
+        if self.grid {
+            draw_grid(canvas, self.origin);
+        }
+    }
+}
+
+/// 8 像素的网格：竖线、横线都对齐到图的左上角，这样一格正好是图里一个字节宽。
+/// 线是隔一个点取反一个点的虚线，亮的、暗的像素上都看得见，下面的像素也还露着一半
+fn draw_grid(canvas: &mut FrameBuffer, origin: Point) {
+    let size = canvas.size();
+    let first_x = origin.x.rem_euclid(GRID_STEP);
+    let first_y = origin.y.rem_euclid(GRID_STEP);
+    for y in 0..size.height as i32 {
+        let on_row = (y - first_y).rem_euclid(GRID_STEP) == 0;
+        for x in 0..size.width as i32 {
+            let on_column = (x - first_x).rem_euclid(GRID_STEP) == 0;
+            // 交叉点只取反一次
+            if (on_column && y % 2 == 0) || (on_row && x % 2 == 0) {
+                let on = canvas.pixel(x as u32, y as u32);
+                canvas.set_pixel(x as u32, y as u32, !on);
+            }
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! 诊断页面：固件版本、堆使用情况、开机时长、遥测扔掉的行数、温湿度传感器 CRC 出错次数，调参数的时候看
+//!
+//! 标题右边的小条和百分比是最近一秒 CPU 有多闲(见 cpu_load.rs)，开机第一秒还没算出来显示 `--`。
+//!
+//! 第三行是堆用了多少(`alloc` feature，没开是 `heap off`)和栈最深用过多少 K(见 stack.rs)。
+//!
+//! 第四行最后的 `Nh` 是屏幕一共亮了多少小时(见 panel_care.rs)，重启不清零。
+//!
+//! 最下面两行是片内温度和 ADC0 校准前后的读数(`原始 > 修正后`)，校准见 calibration.rs。
+//!
+//! 开机时长是定时器数出来的，没有晶振(`no-xosc`)的时候跟着 ROSC 偏，可能差百分之几十，只能看个大概。
+//!
+//! 从仪表盘按 Select 进来，按 Back 回去；再按 Select 进弹球演示，按 Down 进设置菜单，按 Up 直接进告警规则。
+
+use core::cell::RefCell;
+use core::convert::Infallible;
+use core::fmt::Write;
+
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::text::Text;
+use embedded_graphics::Drawable;
+use heapless::String;
+
+use crate::app::{Canvas, Event, Page, PageId, Transition};
+use crate::banner::FIRMWARE_VERSION;
+use crate::cpu_load;
+use crate::heap;
+use crate::humidity;
+use crate::input::{Button, ButtonEvent};
+use crate::panel_care;
+use crate::sensors::{ChannelId, SensorRegistry};
+use crate::stack;
+use crate::telemetry;
+use crate::theme;
+use crate::widgets::draw_progress_bar_styled;
+
+/// 行间距(基线到基线)
+const LINE_HEIGHT: i32 = 10;
+
+/// 标题右边的空闲条，后面跟着百分比
+const IDLE_BAR: Rectangle = Rectangle::new(Point::new(70, 1), Size::new(24, 7));
+
+/// 诊断页面，每秒刷新一次
+pub struct DiagnosticsPage<'a, S> {
+    sensors: &'a RefCell<SensorRegistry<S>>,
+    /// 校准前后对照的两个通道：片内温度、ADC0
+    calibrated: [ChannelId; 2],
+    /// 弹球演示页面的编号
+    demo: PageId,
+    /// 设置菜单的编号
+    settings: PageId,
+    /// 告警页面的编号
+    alarms: PageId,
+    shown_second: Option<u64>,
+}
+
+impl<'a, S> DiagnosticsPage<'a, S> {
+    pub const fn new(
+        sensors: &'a RefCell<SensorRegistry<S>>,
+        calibrated: [ChannelId; 2],
+        demo: PageId,
+        settings: PageId,
+        alarms: PageId,
+    ) -> Self {
+        Self {
+            sensors,
+            calibrated,
+            demo,
+            settings,
+            alarms,
+            shown_second: None,
+        }
+    }
+}
+
+impl<S> Page for DiagnosticsPage<'_, S> {
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        match event {
+            Event::Button(ButtonEvent::Pressed(Button::Back)) => Transition::Pop,
+            Event::Button(ButtonEvent::Pressed(Button::Select)) => Transition::Push(self.demo),
+            Event::Button(ButtonEvent::Pressed(Button::Down)) => Transition::Push(self.settings),
+            Event::Button(ButtonEvent::Pressed(Button::Up)) => Transition::Push(self.alarms),
+            _ => Transition::None,
+        }
+    }
+
+    /// 内容每秒才变一次，2 帧每秒保证秒数变了最多晚半秒显示
+    fn desired_fps(&self) -> u16 {
+        2
+    }
+
+    /// 没人看的时候扫一眼就够了
+    fn dwell_ms(&self) -> Option<u32> {
+        Some(5_000)
+    }
+
+    fn tick(&mut self, now_ms: u64) -> bool {
+        let second = now_ms / 1000;
+        let changed = self.shown_second != Some(second);
+        self.shown_second = Some(second);
+        changed
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, now_ms: u64) -> Result<(), Infallible> {
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        // 每行最长也就 20 来个字符
+        let mut line: String<24> = String::new();
+
+        Text::new("diagnostics", Point::new(0, 8), style).draw(canvas)?;
+        let idle_x = IDLE_BAR.top_left.x + IDLE_BAR.size.width as i32 + 2;
+        match cpu_load::idle_percent() {
+            Some(idle) => {
+                draw_progress_bar_styled(canvas, IDLE_BAR, idle, &theme::active().progress_bar)?;
+                let _ = write!(line, "{}%", idle);
+                Text::new(&line, Point::new(idle_x, 8), style).draw(canvas)?;
+                line.clear();
+            }
+            None => {
+                Text::new("--", Point::new(idle_x, 8), style).draw(canvas)?;
+            }
+        }
+
+        let _ = write!(line, "fw v{} up {}s", FIRMWARE_VERSION, now_ms / 1000);
+        Text::new(&line, Point::new(0, 8 + LINE_HEIGHT), style).draw(canvas)?;
+
+        line.clear();
+        // 一行 21 个字，堆和栈挤在一起，栈只写最深用过多少 K
+        match heap::usage() {
+            Some(usage) => {
+                let _ = write!(line, "heap {}/{}", usage.used, usage.size);
+            }
+            None => {
+                let _ = write!(line, "heap off");
+            }
+        }
+        if let Some(usage) = stack::usage() {
+            let _ = write!(line, " stk {}K", usage.peak.div_ceil(1024));
+        }
+        Text::new(&line, Point::new(0, 8 + 2 * LINE_HEIGHT), style).draw(canvas)?;
+
+        line.clear();
+        let _ = write!(
+            line,
+            "drop {} crc {} {}h",
+            telemetry::dropped_lines(),
+            humidity::crc_errors(),
+            panel_care::lit_seconds() / 3600
+        );
+        Text::new(&line, Point::new(0, 8 + 3 * LINE_HEIGHT), style).draw(canvas)?;
+
+        let sensors = self.sensors.borrow();
+        for (row, id) in self.calibrated.iter().enumerate() {
+            let Some(channel) = sensors.get(*id) else {
+                continue;
+            };
+            let info = channel.info();
+            line.clear();
+            let _ = write!(line, "{} ", info.name);
+            let _ = info.write_value(channel.raw_value(), &mut line);
+            let _ = line.push_str(" > ");
+            let _ = info.write_value(channel.value(), &mut line);
+            let y = 8 + (4 + row as i32) * LINE_HEIGHT;
+            Text::new(&line, Point::new(0, y), style).draw(canvas)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,239 @@
+//! 亮度时间表的设置页面
+//!
+//! 从设置菜单(诊断页面按 Down)进来。每一行是一个时间点和它的亮度，最后一行是 "add"(满 4 项就没有了)：
+//!
+//! - Up/Down 选行，Select 开始编辑：依次改 时 -> 分 -> 亮度，Up/Down 调数值，Select 进下一项，
+//!   改完亮度就保存；编辑的时候按 Back 放弃这次修改
+//! - 长按 Select 删掉选中的那一项
+//! - Back 回去
+//!
+//! 保存是交给主循环做的(`Action::SaveDimSchedule`)，保存之前会整理顺序，所以改完时间之后这一项可能换了位置。
+//! 没对过时的话标题上会提示，时间表照样可以改，只是不会生效。
+
+use core::convert::Infallible;
+use core::fmt::Write;
+
+use embedded_graphics::geometry::Point;
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::text::Text;
+use embedded_graphics::Drawable;
+use heapless::{String, Vec};
+
+use crate::app::{Action, Canvas, Event, Page, Transition};
+use crate::dimming::{DimEntry, DimSchedule, MAX_ENTRIES};
+use crate::display::DEFAULT_CONTRAST;
+use crate::input::{Button, ButtonEvent};
+use crate::widgets::draw_menu;
+
+/// 标题行基线
+const TITLE_Y: i32 = 8;
+
+/// 菜单从哪一行开始
+const MENU_TOP: i32 = 12;
+
+/// 分钟每次调多少
+const MINUTE_STEP: u16 = 5;
+
+/// 亮度每次调多少
+const CONTRAST_STEP: u8 = 16;
+
+/// 正在改哪一项
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Hour,
+    Minute,
+    Contrast,
+}
+
+/// 亮度时间表页面
+#[derive(Debug)]
+pub struct DimPage {
+    schedule: DimSchedule,
+    selected: usize,
+    /// 正在编辑的那一项和正在改的字段
+    editing: Option<(DimEntry, Field)>,
+    clock_known: bool,
+    pending: Option<Action>,
+}
+
+impl DimPage {
+    /// `schedule` 是设置里存的时间表
+    pub const fn new(schedule: DimSchedule) -> Self {
+        Self {
+            schedule,
+            selected: 0,
+            editing: None,
+            clock_known: false,
+            pending: None,
+        }
+    }
+
+    /// 一共几行(最后可能有一行 "add")
+    fn rows(&self) -> usize {
+        let len = self.schedule.entries().len();
+        if len < MAX_ENTRIES {
+            len + 1
+        } else {
+            len
+        }
+    }
+
+    fn save(&mut self) {
+        self.schedule.normalize();
+        self.selected = self.selected.min(self.rows() - 1);
+        self.pending = Some(Action::SaveDimSchedule(self.schedule));
+    }
+
+    /// 编辑完了，把这一项放回时间表
+    fn commit(&mut self, entry: DimEntry) {
+        if self.selected < self.schedule.entries().len() {
+            self.schedule.remove(self.selected);
+        }
+        self.schedule.push(entry);
+        self.save();
+    }
+
+    fn on_edit(&mut self, button: Button, mut entry: DimEntry, field: Field) {
+        let up = match button {
+            Button::Up => true,
+            Button::Down => false,
+            Button::Back => {
+                self.editing = None;
+                return;
+            }
+            Button::Select => {
+                self.editing = match field {
+                    Field::Hour => Some((entry, Field::Minute)),
+                    Field::Minute => Some((entry, Field::Contrast)),
+                    Field::Contrast => {
+                        self.commit(entry);
+                        None
+                    }
+                };
+                return;
+            }
+        };
+        let (hour, minute) = (entry.minute / 60, entry.minute % 60);
+        match field {
+            Field::Hour => {
+                let hour = if up {
+                    (hour + 1) % 24
+                } else {
+                    (hour + 23) % 24
+                };
+                entry.minute = hour * 60 + minute;
+            }
+            Field::Minute => {
+                let step = if up { MINUTE_STEP } else { 60 - MINUTE_STEP };
+                // 只改分钟，不进位到小时
+                entry.minute = hour * 60 + (minute + step) % 60;
+            }
+            Field::Contrast => {
+                entry.contrast = if up {
+                    entry.contrast.saturating_add(CONTRAST_STEP)
+                } else {
+                    entry.contrast.saturating_sub(CONTRAST_STEP)
+                };
+            }
+        }
+        self.editing = Some((entry, field));
+    }
+}
+
+impl Page for DimPage {
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        let button = match event {
+            Event::ClockSet(_) => {
+                self.clock_known = true;
+                return Transition::None;
+            }
+            Event::Button(ButtonEvent::Pressed(button)) => *button,
+            // 按下的那一下已经开始编辑小时了，长按就是放弃编辑、删掉这一项
+            Event::Button(ButtonEvent::LongPress(Button::Select)) => {
+                if !matches!(self.editing, Some((_, Field::Hour))) {
+                    return Transition::None;
+                }
+                self.editing = None;
+                if self.selected < self.schedule.entries().len() {
+                    self.schedule.remove(self.selected);
+                    self.save();
+                }
+                return Transition::None;
+            }
+            _ => return Transition::None,
+        };
+        if let Some((entry, field)) = self.editing {
+            self.on_edit(button, entry, field);
+            return Transition::None;
+        }
+        match button {
+            Button::Back => return Transition::Pop,
+            Button::Up => self.selected = (self.selected + self.rows() - 1) % self.rows(),
+            Button::Down => self.selected = (self.selected + 1) % self.rows(),
+            Button::Select => {
+                let entry = self
+                    .schedule
+                    .entries()
+                    .get(self.selected)
+                    .copied()
+                    .unwrap_or(DimEntry::new(22, 0, DEFAULT_CONTRAST));
+                self.editing = Some((entry, Field::Hour));
+            }
+        }
+        Transition::None
+    }
+
+    fn take_action(&mut self) -> Option<Action> {
+        self.pending.take()
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<(), Infallible> {
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        let title = if self.clock_known {
+            "dimming"
+        } else {
+            "dimming: no clock"
+        };
+        Text::new(title, Point::new(0, TITLE_Y), style).draw(canvas)?;
+
+        // 先把每一行的文字拼好，菜单只借用字符串
+        let mut rows: Vec<(String<12>, String<8>), { MAX_ENTRIES + 1 }> = Vec::new();
+        let entries = self.schedule.entries();
+        for row in 0..self.rows() {
+            let (mut label, mut value) = (String::new(), String::new());
+            let editing = self.editing.filter(|_| row == self.selected);
+            match (editing, entries.get(row)) {
+                (Some((entry, field)), _) => {
+                    let (hour, minute) = (entry.minute / 60, entry.minute % 60);
+                    let _ = match field {
+                        Field::Hour => write!(label, "[{:02}]:{:02}", hour, minute),
+                        Field::Minute => write!(label, "{:02}:[{:02}]", hour, minute),
+                        Field::Contrast => write!(label, "{:02}:{:02}", hour, minute),
+                    };
+                    let _ = if field == Field::Contrast {
+                        write!(value, "[{}]", entry.contrast)
+                    } else {
+                        write!(value, "{}", entry.contrast)
+                    };
+                }
+                (None, Some(entry)) => {
+                    let _ = write!(label, "{:02}:{:02}", entry.minute / 60, entry.minute % 60);
+                    let _ = write!(value, "{}", entry.contrast);
+                }
+                (None, None) => {
+                    let _ = label.push_str("add");
+                }
+            }
+            let _ = rows.push((label, value));
+        }
+        draw_menu(
+            canvas,
+            MENU_TOP,
+            rows.iter()
+                .map(|(label, value)| (label.as_str(), value.as_str())),
+            self.selected,
+        )
+    }
+}
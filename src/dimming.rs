@@ -0,0 +1,237 @@
+//! 按时间自动调亮度：一天里最多 4 个时间点，每个时间点设一个亮度，比如晚上 22:00 调暗、早上 07:00 调亮
+//!
+//! 时间来自 `sleep_clock::WallClock`，没对过时(`CLOCK SET`)就不知道现在几点，这个功能自动关掉。
+//! 每分钟比较一次现在的时间和时间表，要换亮度的时候用大约 10 秒慢慢过渡，不会"啪"的一下变暗。
+//!
+//! 时间表保存在设置里(见 `settings`)，保存之前一定会 `normalize`：按时间排好序，同一个时间点只留最后设的那个，
+//! 所以运行的时候不用管乱序、重复这些情况。在设置页面里编辑，见 `dim_page`。
+
+/// 时间表最多几项
+pub const MAX_ENTRIES: usize = 4;
+
+/// 编码之后多少字节：项数 + 每项(分钟 2 字节 + 亮度 1 字节)
+pub const ENCODED_LEN: usize = 1 + MAX_ENTRIES * 3;
+
+/// 换亮度的过渡时间(毫秒)
+pub const RAMP_MS: u64 = 10_000;
+
+/// 一天多少分钟
+pub const MINUTES_PER_DAY: u16 = 24 * 60;
+
+/// 时间表里的一项：从 `minute`(一天里的第几分钟)开始用 `contrast` 这个亮度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, defmt::Format)]
+pub struct DimEntry {
+    pub minute: u16,
+    pub contrast: u8,
+}
+
+impl DimEntry {
+    pub const fn new(hour: u8, minute: u8, contrast: u8) -> Self {
+        Self {
+            minute: hour as u16 * 60 + minute as u16,
+            contrast,
+        }
+    }
+}
+
+/// 时间表
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, defmt::Format)]
+pub struct DimSchedule {
+    entries: [DimEntry; MAX_ENTRIES],
+    len: u8,
+}
+
+impl DimSchedule {
+    /// 空的时间表，亮度一直是设置里的对比度
+    pub const fn new() -> Self {
+        Self {
+            entries: [DimEntry {
+                minute: 0,
+                contrast: 0,
+            }; MAX_ENTRIES],
+            len: 0,
+        }
+    }
+
+    pub fn entries(&self) -> &[DimEntry] {
+        &self.entries[..self.len as usize]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// 加一项，满了返回 false。加完记得 `normalize`
+    pub fn push(&mut self, entry: DimEntry) -> bool {
+        if self.len as usize >= MAX_ENTRIES {
+            return false;
+        }
+        self.entries[self.len as usize] = entry;
+        self.len += 1;
+        true
+    }
+
+    /// 删掉第 `index` 项
+    pub fn remove(&mut self, index: usize) {
+        if index >= self.len as usize {
+            return;
+        }
+        self.entries
+            .copy_within(index + 1..self.len as usize, index);
+        self.len -= 1;
+    }
+
+    /// 整理：超过一天的时间取模，按时间排序，同一分钟的只留后面加的那项
+    pub fn normalize(&mut self) {
+        let len = self.len as usize;
+        for entry in &mut self.entries[..len] {
+            entry.minute %= MINUTES_PER_DAY;
+        }
+        // 稳定排序(插入排序，最多 4 项)，同一分钟的保持原来的先后
+        for i in 1..len {
+            let mut j = i;
+            while j > 0 && self.entries[j - 1].minute > self.entries[j].minute {
+                self.entries.swap(j - 1, j);
+                j -= 1;
+            }
+        }
+        let mut kept = 0;
+        for i in 0..len {
+            let entry = self.entries[i];
+            if kept > 0 && self.entries[kept - 1].minute == entry.minute {
+                self.entries[kept - 1] = entry;
+            } else {
+                self.entries[kept] = entry;
+                kept += 1;
+            }
+        }
+        self.len = kept as u8;
+    }
+
+    /// 一天里第 `minute` 分钟应该用的亮度：最后一个已经到了的时间点，都没到就是昨天的最后一项
+    pub fn target(&self, minute: u16) -> Option<u8> {
+        let entries = self.entries();
+        entries
+            .iter()
+            .rev()
+            .find(|entry| entry.minute <= minute)
+            .or(entries.last())
+            .map(|entry| entry.contrast)
+    }
+
+    pub fn encode(&self) -> [u8; ENCODED_LEN] {
+        let mut out = [0u8; ENCODED_LEN];
+        out[0] = self.len;
+        for (i, entry) in self.entries.iter().enumerate() {
+            out[1 + i * 3..3 + i * 3].copy_from_slice(&entry.minute.to_le_bytes());
+            out[3 + i * 3] = entry.contrast;
+        }
+        out
+    }
+
+    /// 解码。项数或者时间超出范围返回 None，顺序不对的会被整理好
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != ENCODED_LEN || bytes[0] as usize > MAX_ENTRIES {
+            return None;
+        }
+        let mut schedule = Self::new();
+        for i in 0..bytes[0] as usize {
+            let minute = u16::from_le_bytes([bytes[1 + i * 3], bytes[2 + i * 3]]);
+            if minute >= MINUTES_PER_DAY {
+                return None;
+            }
+            schedule.push(DimEntry {
+                minute,
+                contrast: bytes[3 + i * 3],
+            });
+        }
+        schedule.normalize();
+        Some(schedule)
+    }
+}
+
+/// 亮度过渡：`RAMP_MS` 里从 `from` 线性变到 `to`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Ramp {
+    from: u8,
+    to: u8,
+    start_ms: u64,
+}
+
+impl Ramp {
+    fn level(&self, now_ms: u64) -> u8 {
+        let elapsed = now_ms.saturating_sub(self.start_ms).min(RAMP_MS) as i32;
+        let delta = self.to as i32 - self.from as i32;
+        (self.from as i32 + delta * elapsed / RAMP_MS as i32) as u8
+    }
+}
+
+/// 调光器：主循环里每一圈调 `update`，返回 Some 的时候把这个亮度发给屏幕
+#[derive(Debug, Default)]
+pub struct Dimmer {
+    /// 上一次检查时间表是第几分钟
+    checked_minute: Option<u16>,
+    ramp: Option<Ramp>,
+    /// 屏幕现在的亮度，不知道的话是 None
+    level: Option<u8>,
+}
+
+impl Dimmer {
+    pub const fn new() -> Self {
+        Self {
+            checked_minute: None,
+            ramp: None,
+            level: None,
+        }
+    }
+
+    /// 屏幕亮度被别的地方改过了(比如导入设置、退出床头钟)，下一次 `update` 重新按时间表来
+    pub fn invalidate(&mut self) {
+        self.checked_minute = None;
+        self.ramp = None;
+        self.level = None;
+    }
+
+    /// `minute` 是一天里的第几分钟，不知道现在几点就传 None(功能关闭，亮度回到 `fallback`)。
+    /// `fallback` 是设置里的对比度，时间表为空的时候也用它
+    pub fn update(
+        &mut self,
+        schedule: &DimSchedule,
+        minute: Option<u16>,
+        fallback: u8,
+        now_ms: u64,
+    ) -> Option<u8> {
+        if self.checked_minute != minute || self.level.is_none() {
+            self.checked_minute = minute;
+            let target = minute
+                .and_then(|minute| schedule.target(minute))
+                .unwrap_or(fallback);
+            let current = self.level.unwrap_or(fallback);
+            let heading_to = self.ramp.map_or(current, |ramp| ramp.to);
+            if self.level.is_none() {
+                // 刚开始或者被打断过，不知道屏幕现在多亮，直接设
+                self.ramp = None;
+                self.level = Some(target);
+                return Some(target);
+            }
+            if heading_to != target {
+                self.ramp = Some(Ramp {
+                    from: current,
+                    to: target,
+                    start_ms: now_ms,
+                });
+            }
+        }
+
+        let ramp = self.ramp?;
+        let level = ramp.level(now_ms);
+        if level == ramp.to {
+            self.ramp = None;
+        }
+        if self.level == Some(level) {
+            return None;
+        }
+        self.level = Some(level);
+        Some(level)
+    }
+}
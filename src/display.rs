@@ -0,0 +1,471 @@
+//! 显示屏抽象
+//!
+//! embedded-graphics 的 `DrawTarget` 只管"往缓冲区画点"，但是 OLED 要 flush 之后才会真正显示，
+//! 所以这里补一个带 flush 的 trait，需要"画完立刻显示"的功能(比如开机横幅)就依赖它，
+//! 而不是写死成某个具体的 Ssd1306 类型。
+//!
+//! `Display` 是我们自己的屏幕封装：显存用 `FrameBuffer` 自己管，命令字节由 `command` 模块编码，
+//! 这里只负责把它们交给传输接口。
+//!
+//! `LazyDisplay` 包一层 `Display`，先不发初始化序列，第一次 flush 的时候才初始化，
+//! 画东西只改显存、不碰总线；`power_down` 以后屏幕关着，下一次 flush 再重新初始化。
+
+use display_interface::{DataFormat, DisplayError, WriteOnlyDataCommand};
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::{OriginDimensions, Size};
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::Pixel;
+use embedded_hal::delay::DelayNs;
+use ssd1306::mode::BufferedGraphicsMode;
+use ssd1306::prelude::DisplayRotation;
+#[cfg(feature = "panel-128x32")]
+use ssd1306::prelude::DisplaySize128x32;
+#[cfg(not(feature = "panel-128x32"))]
+use ssd1306::prelude::DisplaySize128x64;
+use ssd1306::size::DisplaySize;
+use ssd1306::Ssd1306;
+
+use crate::command;
+use crate::framebuffer::{DirtyRegion, FrameBuffer, WIDTH};
+#[cfg(feature = "panic-restore")]
+use crate::shadow_frame;
+
+/// 屏幕型号(分辨率)，ssd1306 crate 里对应的类型，和 `panel::PANEL` 一起按 feature 选
+#[cfg(feature = "panel-128x32")]
+pub type PanelSize = DisplaySize128x32;
+#[cfg(not(feature = "panel-128x32"))]
+pub type PanelSize = DisplaySize128x64;
+
+/// 上电后的默认对比度，和 ssd1306 crate 的 `Brightness::NORMAL` 一样
+pub const DEFAULT_CONTRAST: u8 = 0x5F;
+
+/// 淡入时每一步至少停这么久(毫秒)，再短肉眼也看不出区别，只是白白多发命令
+const MIN_FADE_STEP_MS: u32 = 10;
+
+/// 淡入最多分多少步
+const MAX_FADE_STEPS: u32 = 32;
+
+/// 带显存缓冲的单色屏：先画到缓冲区，再 flush 到屏幕上
+pub trait BufferedDisplay: DrawTarget<Color = BinaryColor> {
+    /// 清空缓冲区(不会立刻影响屏幕，要等下一次 flush)
+    fn clear_buffer(&mut self);
+
+    /// 把缓冲区里改动过的部分发送到屏幕
+    fn flush(&mut self) -> Result<(), Self::Error>;
+}
+
+impl<DI, SIZE> BufferedDisplay for Ssd1306<DI, SIZE, BufferedGraphicsMode<SIZE>>
+where
+    DI: WriteOnlyDataCommand,
+    SIZE: DisplaySize,
+{
+    fn clear_buffer(&mut self) {
+        self.clear();
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ssd1306::flush(self)
+    }
+}
+
+/// SSD1306 屏幕：传输接口 + 自己的显存
+pub struct Display<DI> {
+    interface: DI,
+    fb: FrameBuffer,
+    /// 设定的对比度，淡入的终点
+    contrast: u8,
+    /// 开机以来 flush 出去的显存字节数(不含命令)
+    flushed_bytes: u32,
+    /// 左右、上下镜像(见 `set_mirror`)
+    mirror: (bool, bool),
+    /// 第几次 flush，显存校验的日志里用来对应屏幕上的某一帧
+    #[cfg(feature = "profiling")]
+    frame_number: u32,
+    /// DMA 正在发的区域，发完了再抄进影子(见 `shadow_frame`)
+    #[cfg(feature = "panic-restore")]
+    in_flight: Option<DirtyRegion>,
+}
+
+impl<DI: WriteOnlyDataCommand> Display<DI> {
+    /// 只是把东西组装起来，不会往总线上发任何数据，要调用 `init` 才会初始化屏幕
+    pub fn new(interface: DI, rotation: DisplayRotation) -> Self {
+        Self {
+            interface,
+            fb: FrameBuffer::new(rotation),
+            contrast: DEFAULT_CONTRAST,
+            flushed_bytes: 0,
+            mirror: (false, false),
+            #[cfg(feature = "profiling")]
+            frame_number: 0,
+            #[cfg(feature = "panic-restore")]
+            in_flight: None,
+        }
+    }
+
+    /// 初始化屏幕：发送上电命令序列，使用水平寻址模式(flush 的时候可以一口气发一整块区域)
+    pub fn init(&mut self) -> Result<(), DisplayError> {
+        let sequence = command::init_sequence(self.fb.rotation(), self.contrast);
+        self.send_commands(&sequence)?;
+        if self.mirror != (false, false) {
+            self.send_orientation()?;
+        }
+        // 屏幕里原来的内容不可信，下一次 flush 整屏重发
+        self.fb.mark_all_dirty();
+        Ok(())
+    }
+
+    /// 显存(只读)
+    pub fn framebuffer(&self) -> &FrameBuffer {
+        &self.fb
+    }
+
+    /// 显存(可写)，直接改字节的时候用
+    pub fn framebuffer_mut(&mut self) -> &mut FrameBuffer {
+        &mut self.fb
+    }
+
+    /// 底层传输接口
+    pub fn interface_mut(&mut self) -> &mut DI {
+        &mut self.interface
+    }
+
+    /// 发送编码好的命令字节(见 `command` 模块)，可以是几条命令拼在一起
+    pub fn send_commands(&mut self, bytes: &[u8]) -> Result<(), DisplayError> {
+        self.interface.send_commands(DataFormat::U8(bytes))
+    }
+
+    /// 设置对比度(亮度)，0 最暗，255 最亮，同时记下来作为淡入的目标值
+    pub fn set_contrast(&mut self, contrast: u8) -> Result<(), DisplayError> {
+        self.contrast = contrast;
+        self.send_commands(&command::contrast(contrast))
+    }
+
+    pub fn contrast(&self) -> u8 {
+        self.contrast
+    }
+
+    /// 开机以来 flush 出去的显存字节数，看省电模式下总线跑了多少数据用
+    pub fn flushed_bytes(&self) -> u32 {
+        self.flushed_bytes
+    }
+
+    /// 记一次 flush 的字节数，每次都打一条 trace 日志
+    ///
+    /// 打开 `profiling` feature 的话还会打出整块显存和这次要发的区域的 CRC：
+    /// 屏幕花了的时候，整块的 CRC 对不上说明是显存被写坏了，对得上但屏幕不对说明是传输出了问题
+    pub(crate) fn count_flush(&mut self, region: DirtyRegion) {
+        let bytes = region.byte_count();
+        defmt::trace!("flush {=usize} bytes", bytes);
+        self.flushed_bytes = self.flushed_bytes.wrapping_add(bytes as u32);
+        #[cfg(feature = "profiling")]
+        {
+            self.frame_number = self.frame_number.wrapping_add(1);
+            defmt::debug!(
+                "frame {=u32}: buffer crc {=u32:#010x}, region {} crc {=u32:#010x}",
+                self.frame_number,
+                self.fb.checksum(),
+                region,
+                self.fb.region_checksum(region)
+            );
+        }
+    }
+
+    /// 开关屏幕。关掉的时候屏幕内部显存还在，再打开内容不变
+    pub fn set_display_on(&mut self, on: bool) -> Result<(), DisplayError> {
+        self.send_commands(&command::display_on(on))
+    }
+
+    /// 反色显示，只改显示效果，显存不变
+    pub fn set_inverted(&mut self, inverted: bool) -> Result<(), DisplayError> {
+        self.send_commands(&command::invert(inverted))
+    }
+
+    pub fn rotation(&self) -> DisplayRotation {
+        self.fb.rotation()
+    }
+
+    /// 换屏幕方向。显存里的内容要按新方向重画，调用方画完再 flush。镜像设置会保留
+    pub fn set_rotation(&mut self, rotation: DisplayRotation) -> Result<(), DisplayError> {
+        self.fb.set_rotation(rotation);
+        #[cfg(feature = "panic-restore")]
+        shadow_frame::invalidate();
+        self.send_orientation()
+    }
+
+    /// 镜像翻转，给装反了或者从背面看的面板用，和旋转是分开的：在当前旋转的基础上再左右/上下翻
+    /// (左右、上下是按转完之后看到的画面说的，见 `command::mirrored`)，之后换旋转也会保留
+    ///
+    /// 上下翻(COM 扫描方向)马上生效，左右翻(段重映射)只影响之后写进去的数据，
+    /// 所以这里把整屏标成脏的，下一次 flush 才会整个翻过来。显存本身不用重画
+    pub fn set_mirror(&mut self, horizontal: bool, vertical: bool) -> Result<(), DisplayError> {
+        self.mirror = (horizontal, vertical);
+        #[cfg(feature = "panic-restore")]
+        shadow_frame::invalidate();
+        self.send_orientation()?;
+        self.fb.mark_all_dirty();
+        Ok(())
+    }
+
+    /// 现在的左右、上下镜像
+    pub fn mirror(&self) -> (bool, bool) {
+        self.mirror
+    }
+
+    /// 按旋转和镜像发段重映射 + COM 扫描方向
+    fn send_orientation(&mut self) -> Result<(), DisplayError> {
+        let (horizontal, vertical) = self.mirror;
+        self.send_commands(&command::mirrored(self.fb.rotation(), horizontal, vertical))
+    }
+
+    /// 硬件水平滚动 `start_page..=end_page`，滚动期间不要 flush，滚动会和写进去的数据打架
+    pub fn start_horizontal_scroll(
+        &mut self,
+        direction: command::ScrollDirection,
+        start_page: u8,
+        end_page: u8,
+        speed: command::ScrollSpeed,
+    ) -> Result<(), DisplayError> {
+        self.send_commands(&command::activate_scroll(false))?;
+        self.send_commands(&command::horizontal_scroll(
+            direction, start_page, end_page, speed,
+        ))?;
+        self.send_commands(&command::activate_scroll(true))
+    }
+
+    /// 停止硬件滚动。滚动把屏幕内部显存搞乱了，下一次 flush 整屏重发
+    pub fn stop_scroll(&mut self) -> Result<(), DisplayError> {
+        self.send_commands(&command::activate_scroll(false))?;
+        self.fb.mark_all_dirty();
+        Ok(())
+    }
+
+    /// 设置屏幕接下来接收数据的窗口(水平寻址模式下，数据写满一行自动换到下一页)
+    pub(crate) fn set_window(&mut self, region: DirtyRegion) -> Result<(), DisplayError> {
+        self.send_commands(&command::window(region))
+    }
+
+    /// 阻塞式 flush：只发脏区域，发完才返回
+    pub fn flush(&mut self) -> Result<(), DisplayError> {
+        let Some(region) = self.fb.take_dirty() else {
+            return Ok(());
+        };
+        let result = self.flush_region(region);
+        if result.is_err() {
+            // 发失败了屏幕上是什么状态不确定，下次整屏重发
+            self.fb.mark_all_dirty();
+        }
+        result
+    }
+
+    /// 阻塞式 flush 指定的区域，不管脏区域记录
+    pub(crate) fn flush_region(&mut self, region: DirtyRegion) -> Result<(), DisplayError> {
+        self.count_flush(region);
+        self.set_window(region)?;
+        // 一页一页拷出来发，有叠加层的话拷的是合成以后的
+        let mut row = [0u8; WIDTH];
+        let row = &mut row[..region.columns()];
+        for page in region.first_page..=region.last_page {
+            let line = DirtyRegion {
+                first_page: page,
+                last_page: page,
+                ..region
+            };
+            for (dst, byte) in row.iter_mut().zip(self.fb.region_bytes(line)) {
+                *dst = byte;
+            }
+            self.interface.send_data(DataFormat::U8(row))?;
+        }
+        #[cfg(feature = "panic-restore")]
+        self.commit_shadow(region, self.fb.region_bytes(region));
+        Ok(())
+    }
+
+    /// 把屏幕上 `region` 这块的新内容抄进影子，连同现在的方向、镜像和亮度
+    #[cfg(feature = "panic-restore")]
+    pub(crate) fn commit_shadow(&self, region: DirtyRegion, bytes: impl Iterator<Item = u8>) {
+        shadow_frame::commit(
+            region,
+            bytes,
+            self.fb.rotation(),
+            self.mirror,
+            self.contrast,
+        );
+    }
+
+    /// 换上新的 DMA 在发的区域，返回原来的
+    #[cfg(feature = "panic-restore")]
+    pub(crate) fn stage_shadow(&mut self, region: Option<DirtyRegion>) -> Option<DirtyRegion> {
+        core::mem::replace(&mut self.in_flight, region)
+    }
+}
+
+/// 开机淡入：对比度从 0 均匀地升到屏幕设定的值(`Display::contrast`)，总共大约 `duration_ms` 毫秒
+///
+/// 调用之前先 `send_commands(&command::contrast(0))` 把屏幕压暗、画好内容再 flush，不然一开始会闪一下。
+/// 时间太短的时候步数会减少(每步至少 10ms)，但至少有一步，所以最后一定停在设定值上。
+pub fn fade_in<DI, T>(
+    display: &mut Display<DI>,
+    duration_ms: u32,
+    timer: &mut T,
+) -> Result<(), DisplayError>
+where
+    DI: WriteOnlyDataCommand,
+    T: DelayNs,
+{
+    let target = display.contrast() as u32;
+    let steps = (duration_ms / MIN_FADE_STEP_MS)
+        .clamp(1, MAX_FADE_STEPS)
+        .min(target.max(1));
+    let step_ms = duration_ms / steps;
+    for step in 1..=steps {
+        timer.delay_ms(step_ms);
+        display.send_commands(&command::contrast((target * step / steps) as u8))?;
+    }
+    Ok(())
+}
+
+/// 显存里已经画好的内容往右挪 `dx`、往下挪 `dy` 个像素(负数往左、往上)，空出来的地方是黑的，见 `FrameBuffer::scroll`
+///
+/// 只改显存，下一次 flush 才显示。和 `start_horizontal_scroll` 的硬件滚动不一样，这个任意像素、任意方向都行，
+/// 也不用担心屏幕型号支不支持
+pub fn scroll_buffer<DI: WriteOnlyDataCommand>(display: &mut Display<DI>, dx: i32, dy: i32) {
+    display.framebuffer_mut().scroll(dx, dy);
+}
+
+impl<DI> Display<DI> {
+    /// 底层传输接口(只读)
+    pub fn interface(&self) -> &DI {
+        &self.interface
+    }
+
+    /// 同时借出接口和显存，DMA 要一边读显存一边往接口里写
+    pub(crate) fn split_mut(&mut self) -> (&mut DI, &FrameBuffer) {
+        (&mut self.interface, &self.fb)
+    }
+}
+
+impl<DI> OriginDimensions for Display<DI> {
+    fn size(&self) -> Size {
+        self.fb.size()
+    }
+}
+
+impl<DI> DrawTarget for Display<DI> {
+    type Color = BinaryColor;
+    type Error = DisplayError;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let Ok(()) = self.fb.draw_iter(pixels);
+        Ok(())
+    }
+
+    /// 实心矩形和清屏交给显存的整字节填充，见 `FrameBuffer::fill_rect_fast`
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let Ok(()) = self.fb.fill_solid(area, color);
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.fb.fill_screen(color.is_on());
+        Ok(())
+    }
+}
+
+impl<DI: WriteOnlyDataCommand> BufferedDisplay for Display<DI> {
+    fn clear_buffer(&mut self) {
+        self.fb.clear();
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Display::flush(self)
+    }
+}
+
+/// 用到才初始化的屏幕：构造的时候不碰总线，第一次 flush 之前先 `Display::init`
+///
+/// 初始化失败的话这次 flush 返回错误，下次 flush 再试。没初始化过的屏幕是上电复位的状态，
+/// 本来就是关着的(不亮、电荷泵没开)，所以一直用不到就一直省着电
+pub struct LazyDisplay<DI> {
+    display: Display<DI>,
+    /// 屏幕现在是不是初始化过、开着的
+    ready: bool,
+}
+
+impl<DI: WriteOnlyDataCommand> LazyDisplay<DI> {
+    /// `display` 是还没调用过 `init` 的
+    pub fn new(display: Display<DI>) -> Self {
+        Self {
+            display,
+            ready: false,
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready
+    }
+
+    /// 还没初始化的话现在初始化，返回里面的 `Display`，要直接发命令的时候用
+    pub fn ensure_init(&mut self) -> Result<&mut Display<DI>, DisplayError> {
+        if !self.ready {
+            self.display.init()?;
+            self.ready = true;
+        }
+        Ok(&mut self.display)
+    }
+
+    /// 关屏、关电荷泵，显存留着，下一次 flush 重新初始化并整屏重发。没初始化过的什么都不发
+    pub fn power_down(&mut self) -> Result<(), DisplayError> {
+        if !self.ready {
+            return Ok(());
+        }
+        // 发失败了也当成要重新初始化，屏幕的状态说不准了
+        self.ready = false;
+        self.display.send_commands(&command::display_on(false))?;
+        self.display.send_commands(&command::charge_pump(false))
+    }
+
+    /// 拿回里面的 `Display`，还有它初始化过没有
+    pub fn into_inner(self) -> (Display<DI>, bool) {
+        (self.display, self.ready)
+    }
+}
+
+impl<DI> OriginDimensions for LazyDisplay<DI> {
+    fn size(&self) -> Size {
+        self.display.size()
+    }
+}
+
+impl<DI> DrawTarget for LazyDisplay<DI> {
+    type Color = BinaryColor;
+    type Error = DisplayError;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        self.display.draw_iter(pixels)
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        self.display.fill_solid(area, color)
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.display.clear(color)
+    }
+}
+
+impl<DI: WriteOnlyDataCommand> BufferedDisplay for LazyDisplay<DI> {
+    fn clear_buffer(&mut self) {
+        self.display.fb.clear();
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.ensure_init()?.flush()
+    }
+}
@@ -0,0 +1,92 @@
+//! 屏幕掉线和重新接上
+//!
+//! 屏幕对这个板子来说是可选的：拔掉以后传感器、遥测、记录、告警都应该照常跑，插回去接着显示。
+//! 主循环用 `DisplayLink` 记屏幕现在在不在：
+//!
+//! - 连着几次 flush 失败(`OFFLINE_AFTER_FAILURES`)就当屏幕掉线了，不再画、不再发，省得每一帧都在总线上等超时
+//! - 掉线以后每 2 秒探测一次(`health::display_present`)，有应答就重新初始化。初始化用的是
+//!   `Display` 里记着的亮度、旋转、镜像，不会变回默认值；反色、关屏这些由主循环重新发一遍
+//! - 接上以后整屏重画当前页面
+//!
+//! 掉线、恢复各记一个次数，打在 defmt 日志里，同时记进事件日志(`event_log`)。
+
+use crate::event_log::{self, SystemEvent};
+
+/// 连着几次 flush 失败算掉线。偶尔失败一次(线长、干扰)不算，下一帧会整屏重发
+pub const OFFLINE_AFTER_FAILURES: u8 = 3;
+
+/// 掉线以后多久探测一次(毫秒)
+pub const REPROBE_INTERVAL_MS: u64 = 2000;
+
+/// 屏幕在不在
+#[derive(Debug, Clone, Copy)]
+pub struct DisplayLink {
+    online: bool,
+    /// 连着失败了几次
+    failures: u8,
+    next_probe_ms: u64,
+    offline_count: u32,
+    restore_count: u32,
+}
+
+impl DisplayLink {
+    /// `online` 是开机的时候屏幕初始化成功没有
+    pub fn new(online: bool, now_ms: u64) -> Self {
+        Self {
+            online,
+            failures: 0,
+            next_probe_ms: now_ms,
+            offline_count: if online { 0 } else { 1 },
+            restore_count: 0,
+        }
+    }
+
+    pub fn is_online(&self) -> bool {
+        self.online
+    }
+
+    /// 掉过几次线(开机就没接上也算一次)
+    pub fn offline_count(&self) -> u32 {
+        self.offline_count
+    }
+
+    /// 重新接上过几次
+    pub fn restore_count(&self) -> u32 {
+        self.restore_count
+    }
+
+    pub fn flush_succeeded(&mut self) {
+        self.failures = 0;
+    }
+
+    /// 返回 true 表示这一次失败让屏幕掉线了
+    pub fn flush_failed(&mut self, now_ms: u64) -> bool {
+        self.failures = self.failures.saturating_add(1);
+        if !self.online || self.failures < OFFLINE_AFTER_FAILURES {
+            return false;
+        }
+        self.online = false;
+        self.offline_count += 1;
+        event_log::record(SystemEvent::DisplayOffline);
+        self.next_probe_ms = now_ms + REPROBE_INTERVAL_MS;
+        true
+    }
+
+    /// 掉线了而且到时间探测了
+    pub fn probe_due(&self, now_ms: u64) -> bool {
+        !self.online && now_ms >= self.next_probe_ms
+    }
+
+    /// 探测或者初始化没成功，过一会儿再试
+    pub fn probe_failed(&mut self, now_ms: u64) {
+        self.next_probe_ms = now_ms + REPROBE_INTERVAL_MS;
+    }
+
+    /// 重新初始化成功了
+    pub fn restored(&mut self) {
+        self.online = true;
+        self.failures = 0;
+        self.restore_count += 1;
+        event_log::record(SystemEvent::DisplayOnline);
+    }
+}
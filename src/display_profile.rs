@@ -0,0 +1,86 @@
+//! 显示方案：室内、太阳底下、夜里，一次把亮度、反色、防烧屏一起换掉
+//!
+//! 选一个方案就把它的亮度和防烧屏写进设置(`contrast`、`burn_in`)，和分别设的效果一样；
+//! 反色没有单独的设置，跟着方案走，所以设置里记的是选的哪个方案(`Settings::display_profile`)。
+//! 选了以后再单独改亮度或者防烧屏，方案还记着(反色还在)，只是那一项不再是方案里的值了。
+//!
+//! 在设置菜单的 "profile" 里选(见 `profile_page`)，或者串口 `SETTINGS PROFILE INDOOR|SUNLIGHT|NIGHT`。
+//! 按时间自动调亮度(`dimming`)和高温调暗照样在方案的亮度上起作用。
+
+use display_interface::{DisplayError, WriteOnlyDataCommand};
+
+use crate::burn_in::{AntiBurnIn, BurnInConfig, BurnInStrategy};
+use crate::display::{Display, DEFAULT_CONTRAST};
+
+/// 一个显示方案
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct DisplayProfile {
+    pub name: &'static str,
+    /// 对比度(亮度)，0..=255
+    pub contrast: u8,
+    /// 整屏反色(黑字白底的反过来)
+    pub inverted: bool,
+    pub burn_in: BurnInConfig,
+}
+
+/// 室内：默认亮度，一小时挪一下防烧屏
+pub const INDOOR: DisplayProfile = DisplayProfile {
+    name: "indoor",
+    contrast: DEFAULT_CONTRAST,
+    inverted: false,
+    burn_in: BurnInConfig {
+        strategy: BurnInStrategy::PixelShift,
+        interval_min: 60,
+    },
+};
+
+/// 太阳底下：最亮，反色(白底)更看得清。这么亮烧得快，防烧屏每 15 分钟挪一下
+pub const SUNLIGHT: DisplayProfile = DisplayProfile {
+    name: "sunlight",
+    contrast: 0xFF,
+    inverted: true,
+    burn_in: BurnInConfig {
+        strategy: BurnInStrategy::PixelShift,
+        interval_min: 15,
+    },
+};
+
+/// 夜里：最暗，不反色(亮的像素越少越不刺眼)，这么暗不怎么烧屏，防烧屏关掉
+pub const NIGHT: DisplayProfile = DisplayProfile {
+    name: "night",
+    contrast: 0x01,
+    inverted: false,
+    burn_in: BurnInConfig {
+        strategy: BurnInStrategy::Off,
+        interval_min: 60,
+    },
+};
+
+/// 所有方案，设置里按这个顺序存编号，所以只能往后加
+pub const PROFILES: [DisplayProfile; 3] = [INDOOR, SUNLIGHT, NIGHT];
+
+/// 按名字找(不区分大小写)，返回编号
+pub fn find(name: &str) -> Option<u8> {
+    PROFILES
+        .iter()
+        .position(|profile| profile.name.eq_ignore_ascii_case(name))
+        .map(|index| index as u8)
+}
+
+/// 第几个方案，编号不对返回 None
+pub fn get(index: u8) -> Option<&'static DisplayProfile> {
+    PROFILES.get(index as usize)
+}
+
+/// 马上换成 `profile`：亮度、反色发给屏幕，防烧屏换设置、平移回到新设置的位置(调用方要整屏重画)
+pub fn apply_profile<DI: WriteOnlyDataCommand>(
+    display: &mut Display<DI>,
+    burn_in: &mut AntiBurnIn,
+    profile: &DisplayProfile,
+    now_ms: u64,
+) -> Result<(), DisplayError> {
+    burn_in.set_config(profile.burn_in, now_ms);
+    display.framebuffer_mut().set_shift(burn_in.offset());
+    display.set_contrast(profile.contrast)?;
+    display.set_inverted(profile.inverted)
+}
@@ -0,0 +1,156 @@
+//! 距离页面：VL53L0X 测到的距离，七段大数字(mm)加一根 0~2m 的条
+//!
+//! 气压计、温湿度、距离几个页面之间用 Up/Down 切换，Back 回仪表盘。
+//! 看不到东西(太远、没有反光)的时候显示 "out of range"，不会把芯片报的 8190 当成距离显示出来。
+//! 大字模式下只剩数字，占满整屏。
+
+use core::cell::RefCell;
+use core::convert::Infallible;
+
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::text::Text;
+use embedded_graphics::Drawable;
+
+use crate::app::{Canvas, Event, Page, PageId, Transition};
+use crate::input::{Button, ButtonEvent};
+use crate::sensors::{ChannelId, SensorRegistry};
+use crate::text::draw_centered;
+use crate::theme;
+use crate::vl53l0x::MAX_RANGE_MM;
+use crate::widgets::{draw_big_digit, draw_progress_bar_styled};
+
+/// 标题行基线
+const TITLE_Y: i32 = 8;
+
+/// 大数字：四位，每位 20x30，笔画 3 像素，位与位之间空 4 像素
+const DIGIT_TOP: i32 = 12;
+const DIGIT_WIDTH: u32 = 20;
+const DIGIT_HEIGHT: u32 = 30;
+const DIGIT_STROKE: i32 = 3;
+const DIGIT_PITCH: i32 = 24;
+const DIGITS_LEFT: i32 = 8;
+
+/// 单位的位置，跟在数字右下角
+const UNIT_X: i32 = DIGITS_LEFT + 4 * DIGIT_PITCH + 4;
+const UNIT_Y: i32 = DIGIT_TOP + DIGIT_HEIGHT as i32 - 1;
+
+/// 大字版的数字：四位占满整屏，每位 26x56，笔画 5 像素
+const LARGE_DIGIT: Size = Size::new(26, 56);
+const LARGE_STROKE: i32 = 5;
+const LARGE_PITCH: i32 = 32;
+const LARGE_TOP: i32 = 4;
+
+/// 量程条
+const BAR: Rectangle = Rectangle::new(Point::new(8, 50), Size::new(112, 10));
+
+/// 四位数字里要画的那几位(第几位, 数字)：前面的 0 不画，个位总是画
+fn shown_digits(mm: i32) -> impl Iterator<Item = (usize, u8)> {
+    let digits = [mm / 1000 % 10, mm / 100 % 10, mm / 10 % 10, mm % 10];
+    let first = digits.iter().position(|&d| d != 0).unwrap_or(3);
+    digits
+        .into_iter()
+        .enumerate()
+        .skip(first)
+        .map(|(i, digit)| (i, digit as u8))
+}
+
+/// 距离页面
+pub struct DistancePage<'a, S> {
+    sensors: &'a RefCell<SensorRegistry<S>>,
+    /// 没接 VL53L0X 就是 None
+    channel: Option<ChannelId>,
+    /// Up/Down 切到哪两个页面
+    prev: PageId,
+    next: PageId,
+    /// 上次画的时候注册表的 `generation`
+    shown_generation: Option<u32>,
+}
+
+impl<'a, S> DistancePage<'a, S> {
+    pub const fn new(
+        sensors: &'a RefCell<SensorRegistry<S>>,
+        channel: Option<ChannelId>,
+        prev: PageId,
+        next: PageId,
+    ) -> Self {
+        Self {
+            sensors,
+            channel,
+            prev,
+            next,
+            shown_generation: None,
+        }
+    }
+}
+
+impl<S> Page for DistancePage<'_, S> {
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        match event {
+            Event::Button(ButtonEvent::Pressed(Button::Back)) => Transition::Pop,
+            Event::Button(ButtonEvent::Pressed(Button::Up)) => Transition::Replace(self.prev),
+            Event::Button(ButtonEvent::Pressed(Button::Down)) => Transition::Replace(self.next),
+            _ => Transition::None,
+        }
+    }
+
+    /// 手挥过去的时候要跟得上，10 帧每秒差不多是测距的频率
+    fn desired_fps(&self) -> u16 {
+        10
+    }
+
+    /// 没接 VL53L0X 的话轮播跳过这一页
+    fn has_data(&self) -> bool {
+        self.channel.is_some()
+    }
+
+    fn tick(&mut self, _now_ms: u64) -> bool {
+        let generation = self.sensors.borrow().generation();
+        let changed = self.shown_generation != Some(generation);
+        self.shown_generation = Some(generation);
+        changed
+    }
+
+    /// 大字版：只有数字(mm)，占满整屏。看不到东西、没接传感器的时候放大原来的提示
+    fn render_large(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<bool, Infallible> {
+        let Some(mm) = self.channel.and_then(|id| self.sensors.borrow().value(id)) else {
+            return Ok(false);
+        };
+        for (i, digit) in shown_digits(mm) {
+            let top_left = Point::new(i as i32 * LARGE_PITCH, LARGE_TOP);
+            draw_big_digit(
+                canvas,
+                Rectangle::new(top_left, LARGE_DIGIT),
+                LARGE_STROKE,
+                digit,
+            )?;
+        }
+        Ok(true)
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<(), Infallible> {
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        draw_centered(canvas, "distance", TITLE_Y, style)?;
+        let Some(channel) = self.channel else {
+            return draw_centered(canvas, "no VL53L0X", 36, style);
+        };
+        let Some(mm) = self.sensors.borrow().value(channel) else {
+            return draw_centered(canvas, "out of range", 36, style);
+        };
+
+        for (i, digit) in shown_digits(mm) {
+            let cell = Rectangle::new(
+                Point::new(DIGITS_LEFT + i as i32 * DIGIT_PITCH, DIGIT_TOP),
+                Size::new(DIGIT_WIDTH, DIGIT_HEIGHT),
+            );
+            draw_big_digit(canvas, cell, DIGIT_STROKE, digit)?;
+        }
+        Text::new("mm", Point::new(UNIT_X, UNIT_Y), style).draw(canvas)?;
+
+        let percent = (mm.clamp(0, MAX_RANGE_MM as i32) * 100 / MAX_RANGE_MM as i32) as u8;
+        draw_progress_bar_styled(canvas, BAR, percent, &theme::active().progress_bar)
+    }
+}
@@ -0,0 +1,333 @@
+//! DS3231 实时时钟，和屏幕挂在同一条 I2C 总线上(地址固定 0x68)
+//!
+//! 板子本身没有掉电还走的时钟(见 sleep_clock.rs)，接一块带纽扣电池的 DS3231 以后，断电再上电时间也是对的。
+//! 主循环开机的时候从这里读一次时间，之后每分钟再对一次(定时器的误差就不会越攒越大)，
+//! `CLOCK SET` 对时的时候也顺便写进去。没接的话 `probe` 返回 None，照旧用定时器走的软件时钟。
+//!
+//! 用到的寄存器(手册表 1)，时间日期都是 BCD 码，一个字节高 4 位是十位、低 4 位是个位：
+//!
+//! | 地址 | 内容 | 说明 |
+//! |------|------|------|
+//! | 0x00 | 秒 | 00-59 |
+//! | 0x01 | 分 | 00-59 |
+//! | 0x02 | 时 | bit6 是 1 的话是 12 小时制，这时 bit5 是下午，低 5 位是 01-12；是 0 的话低 6 位是 00-23 |
+//! | 0x03 | 星期 | 1-7，哪天算 1 芯片不管，这里当成星期一 |
+//! | 0x04 | 日 | 01-31 |
+//! | 0x05 | 月 | 低 5 位 01-12，bit7 是世纪位(年从 99 进到 00 的时候翻转) |
+//! | 0x06 | 年 | 00-99 |
+//! | 0x0F | 状态 | bit7 是 OSF：停过振(比如没电池的时候掉了电)，时间不可信，写一次时间把它清掉 |
+//! | 0x11 | 温度整数部分 | 有符号 |
+//! | 0x12 | 温度小数部分 | 高 2 位，单位 0.25°C |
+//!
+//! 写的时候一律按 24 小时制写，世纪位当成 2100 年以后。七个时间寄存器是一次连着读写的，
+//! 芯片在这期间锁住一份副本，不会读到秒刚进位、分还没进位的时间。
+
+use embedded_hal::i2c::I2c;
+
+/// 芯片地址，改不了
+pub const ADDRESS: u8 = 0x68;
+
+/// 时间寄存器从 0x00 开始，一共 7 个
+const REG_TIME: u8 = 0x00;
+pub const TIME_LEN: usize = 7;
+
+/// 状态寄存器和里面的停振标志
+const REG_STATUS: u8 = 0x0F;
+const STATUS_OSF: u8 = 0x80;
+
+/// 温度寄存器，两个字节
+const REG_TEMPERATURE: u8 = 0x11;
+
+/// 小时寄存器里的 12 小时制标志和下午标志
+const HOUR_12: u8 = 0x40;
+const HOUR_PM: u8 = 0x20;
+
+/// 月份寄存器里的世纪位
+const MONTH_CENTURY: u8 = 0x80;
+
+/// 芯片能表示的第一年
+const BASE_YEAR: u16 = 2000;
+
+/// 日期和时间，都是 24 小时制
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Time {
+    /// 2000..=2199
+    pub year: u16,
+    /// 1..=12
+    pub month: u8,
+    /// 1..=31
+    pub day: u8,
+    /// 1..=7，1 是星期一
+    pub weekday: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl Time {
+    /// 芯片停过振、没对过时的时候用的时间：2000-01-01 00:00:00，星期六
+    pub const EPOCH: Time = Time {
+        year: BASE_YEAR,
+        month: 1,
+        day: 1,
+        weekday: 6,
+        hour: 0,
+        minute: 0,
+        second: 0,
+    };
+
+    /// 日期不变，时分秒换成新的
+    pub fn with_time_of_day(self, hour: u8, minute: u8, second: u8) -> Self {
+        Self {
+            hour,
+            minute,
+            second,
+            ..self
+        }
+    }
+
+    /// 解析七个时间寄存器。哪一位不是合法的 BCD 或者超出范围返回 None
+    pub fn decode(regs: &[u8; TIME_LEN]) -> Option<Self> {
+        let second = bcd_to_bin(regs[0])?;
+        let minute = bcd_to_bin(regs[1])?;
+        let hour = decode_hour(regs[2])?;
+        let weekday = bcd_to_bin(regs[3])?;
+        let day = bcd_to_bin(regs[4])?;
+        let month = bcd_to_bin(regs[5] & !MONTH_CENTURY)?;
+        let century = if regs[5] & MONTH_CENTURY != 0 { 100 } else { 0 };
+        let year = BASE_YEAR + century + bcd_to_bin(regs[6])? as u16;
+        let time = Self {
+            year,
+            month,
+            day,
+            weekday,
+            hour,
+            minute,
+            second,
+        };
+        time.is_valid().then_some(time)
+    }
+
+    /// 编码成七个时间寄存器，小时用 24 小时制。超出范围的字段按芯片能存的截断，调用前自己检查 `is_valid`
+    pub fn encode(&self) -> [u8; TIME_LEN] {
+        let years = self.year.saturating_sub(BASE_YEAR).min(199);
+        let century = if years >= 100 { MONTH_CENTURY } else { 0 };
+        [
+            bin_to_bcd(self.second.min(59)),
+            bin_to_bcd(self.minute.min(59)),
+            bin_to_bcd(self.hour.min(23)),
+            bin_to_bcd(self.weekday.clamp(1, 7)),
+            bin_to_bcd(self.day.clamp(1, 31)),
+            bin_to_bcd(self.month.clamp(1, 12)) | century,
+            bin_to_bcd((years % 100) as u8),
+        ]
+    }
+
+    /// 每个字段都在范围里(日不按月份细查，31 号都算合法)
+    pub fn is_valid(&self) -> bool {
+        (BASE_YEAR..BASE_YEAR + 200).contains(&self.year)
+            && (1..=12).contains(&self.month)
+            && (1..=31).contains(&self.day)
+            && (1..=7).contains(&self.weekday)
+            && self.hour < 24
+            && self.minute < 60
+            && self.second < 60
+    }
+}
+
+/// BCD 转成二进制：0x59 -> 59。哪一位大于 9 就不是 BCD，返回 None
+pub fn bcd_to_bin(bcd: u8) -> Option<u8> {
+    let (tens, ones) = (bcd >> 4, bcd & 0x0F);
+    (tens <= 9 && ones <= 9).then_some(tens * 10 + ones)
+}
+
+/// 二进制转成 BCD：59 -> 0x59。只管 0..=99，再大的取个位和十位
+pub fn bin_to_bcd(value: u8) -> u8 {
+    ((value / 10 % 10) << 4) | (value % 10)
+}
+
+/// 小时寄存器转成 24 小时制：12 小时制的 12 AM 是 0 点，12 PM 是 12 点
+fn decode_hour(reg: u8) -> Option<u8> {
+    if reg & HOUR_12 == 0 {
+        return bcd_to_bin(reg & 0x3F);
+    }
+    let hour = bcd_to_bin(reg & 0x1F)?;
+    if !(1..=12).contains(&hour) {
+        return None;
+    }
+    let pm = if reg & HOUR_PM != 0 { 12 } else { 0 };
+    Some(hour % 12 + pm)
+}
+
+/// 温度寄存器转成 0.01°C：整数部分有符号，小数部分在第二个字节的高 2 位
+pub fn temp_centi_from_regs(msb: u8, lsb: u8) -> i32 {
+    let quarters = ((msb as i8 as i32) << 2) | (lsb >> 6) as i32;
+    quarters * 25
+}
+
+/// 一块 DS3231
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ds3231 {
+    address: u8,
+}
+
+impl Ds3231 {
+    /// 能读出状态寄存器就算在
+    pub fn probe<I: I2c>(i2c: &mut I) -> Option<Self> {
+        let mut status = [0u8];
+        i2c.write_read(ADDRESS, &[REG_STATUS], &mut status).ok()?;
+        defmt::info!("DS3231 found at {=u8:#x}", ADDRESS);
+        Some(Self { address: ADDRESS })
+    }
+
+    /// 读现在的时间。总线出错、寄存器不合法或者芯片停过振(时间不可信)都返回 None
+    pub fn read_time<I: I2c>(&mut self, i2c: &mut I) -> Option<Time> {
+        let mut status = [0u8];
+        i2c.write_read(self.address, &[REG_STATUS], &mut status)
+            .ok()?;
+        if status[0] & STATUS_OSF != 0 {
+            return None;
+        }
+        let mut regs = [0u8; TIME_LEN];
+        i2c.write_read(self.address, &[REG_TIME], &mut regs).ok()?;
+        Time::decode(&regs)
+    }
+
+    /// 写时间，写完清掉停振标志。返回成功没有，不合法的时间不写
+    pub fn set_time<I: I2c>(&mut self, i2c: &mut I, time: &Time) -> bool {
+        if !time.is_valid() {
+            return false;
+        }
+        let mut frame = [0u8; 1 + TIME_LEN];
+        frame[0] = REG_TIME;
+        frame[1..].copy_from_slice(&time.encode());
+        let mut status = [0u8];
+        i2c.write(self.address, &frame).is_ok()
+            && i2c
+                .write_read(self.address, &[REG_STATUS], &mut status)
+                .is_ok()
+            && i2c
+                .write(self.address, &[REG_STATUS, status[0] & !STATUS_OSF])
+                .is_ok()
+    }
+
+    /// 芯片里的温度(0.01°C)，精度 0.25°C，芯片自己 64 秒才测一次
+    pub fn read_temperature<I: I2c>(&mut self, i2c: &mut I) -> Option<i32> {
+        let mut regs = [0u8; 2];
+        i2c.write_read(self.address, &[REG_TEMPERATURE], &mut regs)
+            .ok()?;
+        Some(temp_centi_from_regs(regs[0], regs[1]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2026-10-14 08:30:45 星期三
+    const SAMPLE: Time = Time {
+        year: 2026,
+        month: 10,
+        day: 14,
+        weekday: 3,
+        hour: 8,
+        minute: 30,
+        second: 45,
+    };
+
+    /// `SAMPLE` 的七个寄存器，小时换成 `hour`
+    fn regs_with_hour(hour: u8) -> [u8; TIME_LEN] {
+        let mut regs = SAMPLE.encode();
+        regs[2] = hour;
+        regs
+    }
+
+    #[test]
+    fn bcd_conversions() {
+        for (bin, bcd) in [(0, 0x00), (9, 0x09), (10, 0x10), (59, 0x59), (99, 0x99)] {
+            assert_eq!(bin_to_bcd(bin), bcd);
+            assert_eq!(bcd_to_bin(bcd), Some(bin));
+        }
+        // 超过 99 只留个位和十位
+        assert_eq!(bin_to_bcd(123), 0x23);
+        for invalid in [0x0A, 0x0F, 0xA0, 0xF9, 0xFF] {
+            assert_eq!(bcd_to_bin(invalid), None, "{invalid:#04x}");
+        }
+    }
+
+    #[test]
+    fn twelve_hour_mode() {
+        let hour = |reg| Time::decode(&regs_with_hour(reg)).map(|time| time.hour);
+        assert_eq!(hour(HOUR_12 | 0x12), Some(0));
+        assert_eq!(hour(HOUR_12 | 0x01), Some(1));
+        assert_eq!(hour(HOUR_12 | 0x11), Some(11));
+        assert_eq!(hour(HOUR_12 | HOUR_PM | 0x12), Some(12));
+        assert_eq!(hour(HOUR_12 | HOUR_PM | 0x01), Some(13));
+        assert_eq!(hour(HOUR_12 | HOUR_PM | 0x11), Some(23));
+        // 12 小时制没有 0 点和 13 点
+        assert_eq!(hour(HOUR_12), None);
+        assert_eq!(hour(HOUR_12 | 0x13), None);
+        // 24 小时制
+        assert_eq!(hour(0x00), Some(0));
+        assert_eq!(hour(0x23), Some(23));
+        assert_eq!(hour(0x24), None);
+    }
+
+    #[test]
+    fn century_bit() {
+        let mut regs = SAMPLE.encode();
+        assert_eq!(regs[5] & MONTH_CENTURY, 0);
+        regs[5] |= MONTH_CENTURY;
+        assert_eq!(Time::decode(&regs).map(|time| time.year), Some(2126));
+
+        let late = Time {
+            year: 2199,
+            ..SAMPLE
+        };
+        let regs = late.encode();
+        assert_eq!((regs[5], regs[6]), (MONTH_CENTURY | 0x10, 0x99));
+        assert_eq!(Time::decode(&regs), Some(late));
+    }
+
+    #[test]
+    fn encode_decode_round_trip() {
+        let regs = SAMPLE.encode();
+        assert_eq!(regs, [0x45, 0x30, 0x08, 0x03, 0x14, 0x10, 0x26]);
+        assert_eq!(Time::decode(&regs), Some(SAMPLE));
+        for time in [
+            Time::EPOCH,
+            SAMPLE.with_time_of_day(23, 59, 59),
+            Time {
+                year: 2100,
+                month: 12,
+                day: 31,
+                weekday: 7,
+                ..SAMPLE
+            },
+        ] {
+            assert_eq!(Time::decode(&time.encode()), Some(time));
+        }
+        // 不合法的 BCD 和超出范围的字段都不认
+        let mut regs = SAMPLE.encode();
+        regs[0] = 0x5A;
+        assert_eq!(Time::decode(&regs), None);
+        let mut regs = SAMPLE.encode();
+        regs[5] = 0x13;
+        assert_eq!(Time::decode(&regs), None);
+    }
+
+    #[test]
+    fn temperature_registers() {
+        assert_eq!(temp_centi_from_regs(0x19, 0x00), 2500);
+        assert_eq!(temp_centi_from_regs(0x19, 0x40), 2525);
+        assert_eq!(temp_centi_from_regs(0x19, 0xC0), 2575);
+        assert_eq!(temp_centi_from_regs(0x00, 0x00), 0);
+        // 负数是 10 位补码：-0.25、-0.75、-25、-24.75
+        assert_eq!(temp_centi_from_regs(0xFF, 0xC0), -25);
+        assert_eq!(temp_centi_from_regs(0xFF, 0x40), -75);
+        assert_eq!(temp_centi_from_regs(0xE7, 0x00), -2500);
+        assert_eq!(temp_centi_from_regs(0xE7, 0x40), -2475);
+        // 低 6 位没用
+        assert_eq!(temp_centi_from_regs(0x19, 0x3F), 2500);
+    }
+}
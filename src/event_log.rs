@@ -0,0 +1,177 @@
+//! 事件日志：最近 `CAPACITY` 条系统事件，带时间，到现场查问题用
+//!
+//! 开机(第几次、为什么重启)、屏幕掉线/接上、告警触发、设置写进 flash、USB 连上/断开这些事情发生的时候，
+//! 各个模块自己调 `record` 记一条。满了把最老的挤掉。时间是定时器的毫秒数(开机以来)，`record` 自己读，
+//! 调用方不用传，中断里也能记：日志是一个 `Shared`(见 `shared`)，写入只是在锁里往环形队列塞一个小结构体。
+//!
+//! 看的地方有两个：设置菜单里的 "events" 页面(新的在上面，右边是多久以前，比如 `2m ago`)，
+//! 串口命令 `EVENTS`(一行一条，新的在前，最后一行 `OK`)。
+//!
+//! 只存在 RAM 里，重启就清空了。上一次为什么重启看开机那一条的原因(看门狗、RUN 脚、上电)。
+
+use core::fmt::{self, Write};
+
+use heapless::{Deque, Vec};
+use rp2040_hal::pac;
+
+use crate::shared::{self, Shared};
+
+/// 最多存几条
+pub const CAPACITY: usize = 32;
+
+/// 串口上一行最长多少字节(含换行)
+pub const LINE_MAX: usize = 48;
+
+/// 上一次是怎么重启的，开机的时候从看门狗和 CHIP_RESET 寄存器读
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum ResetCause {
+    /// 上电(或者掉电又来电)
+    PowerOn,
+    /// RUN 脚被拉低(复位按键)
+    RunPin,
+    /// 调试器通过 SWD 复位的
+    Debugger,
+    /// 看门狗超时
+    WatchdogTimeout,
+    /// 软件写看门狗的 FORCE 位主动重启的
+    WatchdogForce,
+    Unknown,
+}
+
+impl ResetCause {
+    /// 要在 `Watchdog::new` 之前读，那之后看门狗的寄存器就拿不到了
+    pub fn read(watchdog: &pac::WATCHDOG, chip: &pac::VREG_AND_CHIP_RESET) -> Self {
+        let reason = watchdog.reason().read();
+        let chip = chip.chip_reset().read();
+        if reason.timer().bit_is_set() {
+            ResetCause::WatchdogTimeout
+        } else if reason.force().bit_is_set() {
+            ResetCause::WatchdogForce
+        } else if chip.had_psm_restart().bit_is_set() {
+            ResetCause::Debugger
+        } else if chip.had_run().bit_is_set() {
+            ResetCause::RunPin
+        } else if chip.had_por().bit_is_set() {
+            ResetCause::PowerOn
+        } else {
+            ResetCause::Unknown
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ResetCause::PowerOn => "power",
+            ResetCause::RunPin => "run",
+            ResetCause::Debugger => "debug",
+            ResetCause::WatchdogTimeout => "wdog",
+            ResetCause::WatchdogForce => "forced",
+            ResetCause::Unknown => "?",
+        }
+    }
+}
+
+/// 一条事件
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum SystemEvent {
+    /// 开机，第几次(数据记录里的开机次数)和上一次为什么重启
+    Boot {
+        count: u16,
+        cause: ResetCause,
+    },
+    DisplayOffline,
+    DisplayOnline,
+    /// 第几条告警规则触发了(从 1 开始，和串口上的 `ALARM 1 ON` 一样)
+    AlarmFired(u8),
+    SettingsSaved,
+    UsbConnected,
+    UsbDisconnected,
+    /// 软关机(见 `power_off`)。日志只在 RAM 里，这一条开机以后就没了，主要是 defmt 上看得到
+    PowerOff,
+}
+
+impl SystemEvent {
+    /// 简短的说明，页面上一行要放得下时间，最长 13 个字符
+    pub fn write_label<W: Write>(&self, out: &mut W) -> fmt::Result {
+        match self {
+            SystemEvent::Boot { count, cause } => write!(out, "boot#{} {}", count, cause.name()),
+            SystemEvent::DisplayOffline => out.write_str("oled offline"),
+            SystemEvent::DisplayOnline => out.write_str("oled online"),
+            SystemEvent::AlarmFired(rule) => write!(out, "alarm {}", rule),
+            SystemEvent::SettingsSaved => out.write_str("cfg saved"),
+            SystemEvent::UsbConnected => out.write_str("usb up"),
+            SystemEvent::UsbDisconnected => out.write_str("usb down"),
+            SystemEvent::PowerOff => out.write_str("power off"),
+        }
+    }
+}
+
+/// 日志里的一条
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Entry {
+    /// 开机以来的毫秒数
+    pub at_ms: u64,
+    pub event: SystemEvent,
+}
+
+impl Entry {
+    /// 串口上的一行，比如 `12.345 oled offline (2m ago)`
+    pub fn write_line<W: Write>(&self, now_ms: u64, out: &mut W) -> fmt::Result {
+        write!(out, "{}.{:03} ", self.at_ms / 1000, self.at_ms % 1000)?;
+        self.event.write_label(out)?;
+        out.write_str(" (")?;
+        write_age(now_ms.saturating_sub(self.at_ms), out)?;
+        out.write_str(")\r\n")
+    }
+}
+
+/// 多久以前，取最大的一个单位：`now`、`42s ago`、`2m ago`、`3h ago`、`5d ago`
+pub fn write_age<W: Write>(age_ms: u64, out: &mut W) -> fmt::Result {
+    let seconds = age_ms / 1000;
+    if seconds == 0 {
+        return out.write_str("now");
+    }
+    let (value, unit) = match seconds {
+        0..=59 => (seconds, "s"),
+        60..=3599 => (seconds / 60, "m"),
+        3600..=86_399 => (seconds / 3600, "h"),
+        _ => (seconds / 86_400, "d"),
+    };
+    write!(out, "{}{} ago", value, unit)
+}
+
+struct Ring {
+    entries: Deque<Entry, CAPACITY>,
+    /// 一共记过几条，页面拿它判断有没有新的
+    recorded: u32,
+}
+
+static LOG: Shared<Ring> = Shared::new(Ring {
+    entries: Deque::new(),
+    recorded: 0,
+});
+
+/// 记一条，满了挤掉最老的。中断里也能调。时间从 `shared::now_ms` 读，装上定时器之前记的都是 0
+pub fn record(event: SystemEvent) {
+    let entry = Entry {
+        at_ms: shared::now_ms(),
+        event,
+    };
+    defmt::info!("event: {}", event);
+    LOG.lock(|ring| {
+        if ring.entries.is_full() {
+            ring.entries.pop_front();
+        }
+        let _ = ring.entries.push_back(entry);
+        ring.recorded = ring.recorded.wrapping_add(1);
+    });
+}
+
+/// 现在存着的，新的在前
+pub fn recent() -> Vec<Entry, CAPACITY> {
+    LOG.lock(|ring| ring.entries.iter().rev().copied().collect())
+}
+
+/// 一共记过几条，有新的就会变
+pub fn recorded() -> u32 {
+    LOG.lock(|ring| ring.recorded)
+}
@@ -0,0 +1,104 @@
+//! 事件日志页面：最近的系统事件，新的在上面(见 `event_log`)
+//!
+//! 从设置菜单进来。每行是 "事件 多久以前"，比如 `oled offline 2m ago`。
+//! Up/Down 滚动，Back 回去。来了新事件列表自动回到最上面。
+
+use core::convert::Infallible;
+use core::fmt::Write;
+
+use embedded_graphics::geometry::Point;
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::text::Text;
+use embedded_graphics::Drawable;
+use heapless::String;
+
+use crate::app::{Canvas, Event, Page, Transition};
+use crate::event_log::{self, write_age};
+use crate::input::{Button, ButtonEvent};
+use crate::widgets::{draw_menu_row, MENU_ROW_HEIGHT};
+
+/// 标题行基线
+const TITLE_Y: i32 = 8;
+
+/// 列表从哪一行开始
+const LIST_TOP: i32 = 12;
+
+/// 一屏放几行
+const VISIBLE_ROWS: usize = 4;
+
+/// 事件日志页面
+#[derive(Debug, Default)]
+pub struct EventLogPage {
+    /// 最上面显示的是第几条(0 是最新的)
+    first: usize,
+    /// 上次看到的 `event_log::recorded`
+    seen: u32,
+    /// 上次画的时候是开机第几秒，年龄按秒变
+    shown_s: u64,
+}
+
+impl EventLogPage {
+    pub const fn new() -> Self {
+        Self {
+            first: 0,
+            seen: 0,
+            shown_s: 0,
+        }
+    }
+}
+
+impl Page for EventLogPage {
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        match event {
+            Event::Button(ButtonEvent::Pressed(Button::Back)) => return Transition::Pop,
+            Event::Button(ButtonEvent::Pressed(Button::Up)) => {
+                self.first = self.first.saturating_sub(1);
+            }
+            Event::Button(ButtonEvent::Pressed(Button::Down)) => {
+                let last_first = event_log::recent().len().saturating_sub(VISIBLE_ROWS);
+                self.first = (self.first + 1).min(last_first);
+            }
+            _ => {}
+        }
+        Transition::None
+    }
+
+    fn desired_fps(&self) -> u16 {
+        1
+    }
+
+    fn tick(&mut self, now_ms: u64) -> bool {
+        let recorded = event_log::recorded();
+        if recorded != self.seen {
+            self.seen = recorded;
+            self.first = 0;
+            return true;
+        }
+        now_ms / 1000 != self.shown_s
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, now_ms: u64) -> Result<(), Infallible> {
+        self.shown_s = now_ms / 1000;
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        let entries = event_log::recent();
+        let mut title: String<24> = String::new();
+        let _ = write!(title, "events {}", entries.len());
+        Text::new(&title, Point::new(0, TITLE_Y), style).draw(canvas)?;
+        for (line, entry) in entries
+            .iter()
+            .skip(self.first)
+            .take(VISIBLE_ROWS)
+            .enumerate()
+        {
+            let mut label: String<24> = String::new();
+            let _ = entry.event.write_label(&mut label);
+            let mut age: String<12> = String::new();
+            let _ = write_age(now_ms.saturating_sub(entry.at_ms), &mut age);
+            let y = LIST_TOP + line as i32 * MENU_ROW_HEIGHT;
+            draw_menu_row(canvas, y, &label, &age, false)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,229 @@
+//! 定点 FFT：128 点、基 2、Q15，给话筒频谱页面用
+//!
+//! 输入是 `FFT_LEN` 个去掉直流的 ADC 样本(12 位，±2048)，先左移 4 位放大到 Q15 的满量程，乘 Hann 窗，
+//! 再做原地的按时间抽取 FFT。每一级蝶形都右移一位防止溢出，七级下来结果是 DFT 除以 `FFT_LEN`，
+//! 所以任何输入都不会溢出，代价是很小的信号低几位被舍掉了(频谱底噪大约在满量程以下 70dB，显示只到 48dB)。
+//!
+//! 旋转因子和 Hann 窗都是编译期从 `trig` 的正弦表算出来的，运行时只有整数乘法。
+//! 满量程的正弦(幅度 2048)落在一个频点上，幅度是 2048 * 16 / 2(正负频率各一半) / 2(Hann 窗的增益) = 8192，
+//! `magnitudes` 再除以 4，正好和 `vu_meter::dbfs10` 的满量程对上。
+//!
+//! 8kHz 采样率下一个频点是 62.5Hz，一共 64 个频点到 4kHz。在 125MHz 的 M0+ 上一次不到 0.2ms。
+
+use crate::trig::{quarter_sine_table, TABLE_STEPS};
+
+/// 多少点
+pub const FFT_LEN: usize = 128;
+
+/// 有用的频点(正频率那一半)
+pub const BINS: usize = FFT_LEN / 2;
+
+const LOG2_LEN: u32 = FFT_LEN.trailing_zeros();
+
+/// 正弦表四分之一周期有 `TABLE_STEPS` 段，半圈是它的两倍
+const HALF_TURN: usize = TABLE_STEPS * 2;
+
+/// `index` 个表格单位的正弦(一圈是 4 * TABLE_STEPS)，只用到 0..半圈
+const fn sine(table: &[i16; TABLE_STEPS + 1], index: usize) -> i16 {
+    if index <= TABLE_STEPS {
+        table[index]
+    } else {
+        table[HALF_TURN - index]
+    }
+}
+
+/// 旋转因子 W^k = cos(2πk/N) - j·sin(2πk/N)，k = 0..N/2，存 (cos, sin)，Q15
+static TWIDDLES: [(i16, i16); FFT_LEN / 2] = twiddles();
+
+const fn twiddles() -> [(i16, i16); FFT_LEN / 2] {
+    let table = quarter_sine_table();
+    let mut out = [(0i16, 0i16); FFT_LEN / 2];
+    let mut k = 0;
+    while k < FFT_LEN / 2 {
+        // 2πk/N 换成表格单位，0..半圈
+        let index = k * 4 * TABLE_STEPS / FFT_LEN;
+        let cos = if index <= TABLE_STEPS {
+            table[TABLE_STEPS - index]
+        } else {
+            -table[index - TABLE_STEPS]
+        };
+        out[k] = (cos, sine(&table, index));
+        k += 1;
+    }
+    out
+}
+
+/// Hann 窗 w[n] = sin²(πn/N)，Q15
+static HANN: [i16; FFT_LEN] = hann();
+
+const fn hann() -> [i16; FFT_LEN] {
+    let table = quarter_sine_table();
+    let mut out = [0i16; FFT_LEN];
+    let mut n = 0;
+    while n < FFT_LEN {
+        let s = sine(&table, n * HALF_TURN / FFT_LEN) as i32;
+        out[n] = ((s * s) >> 15) as i16;
+        n += 1;
+    }
+    out
+}
+
+/// 两个 Q15 相乘，四舍五入
+#[inline]
+fn mul(a: i16, b: i16) -> i32 {
+    (a as i32 * b as i32 + (1 << 14)) >> 15
+}
+
+/// 原地 FFT，`re`/`im` 进去是时域，出来是频域，都除过 `FFT_LEN`
+pub fn fft_q15(re: &mut [i16; FFT_LEN], im: &mut [i16; FFT_LEN]) {
+    // 位反转重排
+    for i in 0..FFT_LEN {
+        let j = i.reverse_bits() >> (usize::BITS - LOG2_LEN);
+        if j > i {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    let mut half = 1;
+    while half < FFT_LEN {
+        // 这一级每组 2 * half 个，旋转因子隔 step 取一个
+        let step = FFT_LEN / (half * 2);
+        for start in (0..FFT_LEN).step_by(half * 2) {
+            for k in 0..half {
+                let (cos, sin) = TWIDDLES[k * step];
+                let (a, b) = (start + k, start + k + half);
+                // b * W，W = cos - j·sin
+                let tr = mul(re[b], cos) + mul(im[b], sin);
+                let ti = mul(im[b], cos) - mul(re[b], sin);
+                let (ar, ai) = (re[a] as i32, im[a] as i32);
+                re[a] = ((ar + tr) >> 1) as i16;
+                im[a] = ((ai + ti) >> 1) as i16;
+                re[b] = ((ar - tr) >> 1) as i16;
+                im[b] = ((ai - ti) >> 1) as i16;
+            }
+        }
+        half *= 2;
+    }
+}
+
+/// 一段去掉直流的样本(±2048)的幅度谱，`out[k]` 是第 k 个频点，满量程的正弦是 2048。第 0 个是直流
+pub fn magnitudes(samples: &[i16; FFT_LEN], out: &mut [u16; BINS]) {
+    let mut re = [0i16; FFT_LEN];
+    let mut im = [0i16; FFT_LEN];
+    for (i, (&sample, &window)) in samples.iter().zip(HANN.iter()).enumerate() {
+        let scaled = (sample as i32 * 16).clamp(-(i16::MAX as i32), i16::MAX as i32) as i16;
+        re[i] = mul(scaled, window) as i16;
+    }
+    fft_q15(&mut re, &mut im);
+    for (k, magnitude) in out.iter_mut().enumerate() {
+        let (r, i) = (re[k].unsigned_abs() as u32, im[k].unsigned_abs() as u32);
+        *magnitude = ((r * r + i * i).isqrt() / 4) as u16;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    fn tone(bin: usize, amplitude: f64) -> [i16; FFT_LEN] {
+        let mut samples = [0i16; FFT_LEN];
+        for (n, sample) in samples.iter_mut().enumerate() {
+            let phase = 2.0 * PI * (bin * n) as f64 / FFT_LEN as f64;
+            *sample = (amplitude * phase.sin()).round() as i16;
+        }
+        samples
+    }
+
+    fn spectrum(samples: &[i16; FFT_LEN]) -> [u16; BINS] {
+        let mut out = [0u16; BINS];
+        magnitudes(samples, &mut out);
+        out
+    }
+
+    fn assert_near(actual: u16, expected: f64, tolerance: f64, what: &str) {
+        assert!(
+            (actual as f64 - expected).abs() <= tolerance,
+            "{what}: {actual}, expected {expected}"
+        );
+    }
+
+    #[test]
+    fn pure_tone_lands_in_its_bin() {
+        for bin in [3, 8, 20, 41, 60] {
+            let out = spectrum(&tone(bin, 2047.0));
+            // 满量程落在一个频点上是 2048，Hann 窗把一半漏到左右两个频点上
+            assert_near(out[bin], 2047.0, 20.0, "peak");
+            assert_near(out[bin - 1], 1023.5, 20.0, "left");
+            assert_near(out[bin + 1], 1023.5, 20.0, "right");
+            for (k, &magnitude) in out.iter().enumerate() {
+                if k.abs_diff(bin) > 1 {
+                    assert!(magnitude <= 4, "bin {bin}: leak {magnitude} at {k}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn magnitude_scales_with_amplitude() {
+        for amplitude in [256.0, 512.0, 1024.0] {
+            let out = spectrum(&tone(16, amplitude));
+            assert_near(out[16], amplitude, 4.0, "peak");
+        }
+    }
+
+    #[test]
+    fn dc_goes_to_bin_zero() {
+        // 直流乘 Hann 窗平均是一半，加上正负频率不分开，是同样幅度正弦的两倍，也往第 1 个频点漏一半
+        let out = spectrum(&[100; FFT_LEN]);
+        assert_near(out[0], 200.0, 2.0, "dc");
+        assert_near(out[1], 100.0, 2.0, "bin 1");
+        assert!(out[2..].iter().all(|&magnitude| magnitude <= 1));
+        assert_eq!(spectrum(&[0; FFT_LEN]), [0; BINS]);
+    }
+
+    #[test]
+    fn hann_window_is_symmetric_and_peaks_in_the_middle() {
+        assert_eq!(HANN[0], 0);
+        assert!(HANN[FFT_LEN / 2] >= 32760);
+        for n in 1..FFT_LEN {
+            assert_eq!(HANN[n], HANN[FFT_LEN - n], "n {n}");
+        }
+        let sum: i32 = HANN.iter().map(|&w| w as i32).sum();
+        // 平均是满量程的一半
+        assert!((sum / FFT_LEN as i32 - 16384).abs() < 32);
+    }
+
+    #[test]
+    fn fft_matches_a_float_dft_divided_by_len() {
+        let mut seed = 12345u32;
+        let mut re = [0i16; FFT_LEN];
+        let mut im = [0i16; FFT_LEN];
+        for sample in re.iter_mut() {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            *sample = ((seed >> 16) as i16) >> 2;
+        }
+        let input = re;
+        fft_q15(&mut re, &mut im);
+        for k in 0..FFT_LEN {
+            let (mut sum_re, mut sum_im) = (0.0, 0.0);
+            for (n, &x) in input.iter().enumerate() {
+                let angle = -2.0 * PI * (k * n) as f64 / FFT_LEN as f64;
+                sum_re += x as f64 * angle.cos();
+                sum_im += x as f64 * angle.sin();
+            }
+            let (expected_re, expected_im) = (sum_re / FFT_LEN as f64, sum_im / FFT_LEN as f64);
+            // 每级右移一位的舍入误差加起来几个 LSB
+            assert!(
+                (re[k] as f64 - expected_re).abs() <= 4.0,
+                "re[{k}] {} vs {expected_re}",
+                re[k]
+            );
+            assert!(
+                (im[k] as f64 - expected_im).abs() <= 4.0,
+                "im[{k}] {} vs {expected_im}",
+                im[k]
+            );
+        }
+    }
+}
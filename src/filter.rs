@@ -0,0 +1,101 @@
+//! 滑动平均：最近 N 个读数的平均，读数抖的时候用它压一压
+//!
+//! 定长的环形缓冲区，总和跟着进出的读数加减，每次 `push` 不用重新加一遍。
+//! 还没攒满 N 个的时候按已有的个数平均，刚开机第一个读数就有结果。
+
+/// 最近 `N` 个读数的滑动平均
+#[derive(Debug, Clone)]
+pub struct MovingAverage<const N: usize> {
+    samples: [i32; N],
+    /// 下一个读数写在哪
+    next: usize,
+    /// 攒了几个，最多 N
+    len: usize,
+    sum: i64,
+}
+
+impl<const N: usize> Default for MovingAverage<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> MovingAverage<N> {
+    pub const fn new() -> Self {
+        Self {
+            samples: [0; N],
+            next: 0,
+            len: 0,
+            sum: 0,
+        }
+    }
+
+    /// 加一个读数，挤掉最老的那个，返回新的平均
+    pub fn push(&mut self, value: i32) -> i32 {
+        if N == 0 {
+            return value;
+        }
+        if self.len == N {
+            self.sum -= self.samples[self.next] as i64;
+        } else {
+            self.len += 1;
+        }
+        self.samples[self.next] = value;
+        self.sum += value as i64;
+        self.next = (self.next + 1) % N;
+        (self.sum / self.len as i64) as i32
+    }
+
+    /// 现在的平均，一个读数都没有是 None
+    pub fn average(&self) -> Option<i32> {
+        (self.len > 0).then(|| (self.sum / self.len as i64) as i32)
+    }
+
+    /// 全部扔掉，从头攒
+    pub fn clear(&mut self) {
+        self.next = 0;
+        self.len = 0;
+        self.sum = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_push_is_its_own_average() {
+        let mut filter = MovingAverage::<4>::new();
+        assert_eq!(filter.average(), None);
+        assert_eq!(filter.push(1000), 1000);
+        assert_eq!(filter.average(), Some(1000));
+    }
+
+    #[test]
+    fn averages_what_it_has_until_the_window_fills() {
+        let mut filter = MovingAverage::<4>::new();
+        assert_eq!(filter.push(10), 10);
+        assert_eq!(filter.push(20), 15);
+        assert_eq!(filter.push(30), 20);
+        assert_eq!(filter.push(40), 25);
+        // 满了以后挤掉最老的
+        assert_eq!(filter.push(50), 35);
+        assert_eq!(filter.push(-100), 5);
+    }
+
+    #[test]
+    fn clear_starts_over() {
+        let mut filter = MovingAverage::<3>::new();
+        filter.push(7);
+        filter.push(9);
+        filter.clear();
+        assert_eq!(filter.average(), None);
+        assert_eq!(filter.push(100), 100);
+    }
+
+    #[test]
+    fn zero_window_passes_readings_through() {
+        let mut filter = MovingAverage::<0>::new();
+        assert_eq!(filter.push(42), 42);
+    }
+}
@@ -0,0 +1,259 @@
+//! 产线测试模式：一次烧一堆板子的时候，插到测试架上自动跑一遍自检，测试架直接读引脚就知道过没过
+//!
+//! 上电的时候测试跳线接地就进这个模式(引脚见 board.rs 的 `boot_strap_pins!`)，不进主循环、不用按键，
+//! 每一项检查都有超时，接少了一个传感器也不会卡住，整个流程在 `BUDGET_MS` 以内跑完。跑完以后：
+//!
+//! - 结果引脚(`fixture_result_pin!`)：全过一直是高电平，有一项没过就按 `FAIL_PULSE_MS` 高低交替
+//! - 串口：电脑一枚举完就打一块，每项一行 `TEST <名字> PASS|FAIL <说明>`，最后一行
+//!   `FIXTURE PASS|FAIL <过了几项>/<一共几项> <用时>ms`，之后串口每收到一行再打一遍(`Report::write_line`)
+//! - 屏幕：一个大勾或者一个大叉，下面一行小结(`render_verdict`)
+//!
+//! 这里只管记结果、算引脚电平、画结果，具体查什么在 main.rs 的 `fixture_mode` 里。
+
+use core::fmt::{self, Write};
+use core::ops::RangeInclusive;
+
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::primitives::{Line, Primitive, PrimitiveStyle};
+use embedded_graphics::text::{Alignment, Text};
+use embedded_graphics::Drawable;
+use heapless::{String, Vec};
+
+use crate::display::BufferedDisplay;
+
+/// 整个流程最多跑多久(毫秒)，每一项的超时都不会超过剩下的时间
+pub const BUDGET_MS: u64 = 10_000;
+
+/// 等电脑枚举 USB 最多等多久(毫秒)
+pub const USB_TIMEOUT_MS: u64 = 4_000;
+
+/// 等一个 ADC 读数最多等多久(毫秒)，打开 mic-vu 的时候第一批样本要等一会儿
+pub const READ_TIMEOUT_MS: u64 = 500;
+
+/// 片内温度在这个范围里才算正常(0.01°C)：产线上是室温，偏得太远就是 ADC 有问题
+pub const TEMP_RANGE_CENTI: RangeInclusive<i32> = 0..=6000;
+
+/// VSYS 在这个范围里才算正常(mV)：USB 供电是 5V 减一个二极管，接锂电池是 3.x V
+pub const VSYS_RANGE_MV: RangeInclusive<i32> = 3000..=5500;
+
+/// 最多记多少项
+pub const MAX_CHECKS: usize = 12;
+
+/// 串口一行最长多少字节(带 `\r\n`)
+pub const LINE_MAX: usize = 64;
+
+/// 一项的说明最长多少字节，多的截掉
+const DETAIL_LEN: usize = 40;
+
+/// 没过的时候结果引脚多久翻一次(毫秒)
+const FAIL_PULSE_MS: u64 = 250;
+
+/// 屏幕最下面小结那一行占多高
+const SUMMARY_HEIGHT: u32 = 11;
+
+/// 一项检查的结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub name: &'static str,
+    pub passed: bool,
+    pub detail: String<DETAIL_LEN>,
+}
+
+/// 一次测试的所有结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    checks: Vec<Check, MAX_CHECKS>,
+    started_ms: u64,
+    finished_ms: Option<u64>,
+}
+
+/// 往说明里写，写满了就不写了，不算错
+struct Truncate<'a>(&'a mut String<DETAIL_LEN>);
+
+impl Write for Truncate<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            if self.0.push(c).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Report {
+    pub fn new(now_ms: u64) -> Self {
+        Self {
+            checks: Vec::new(),
+            started_ms: now_ms,
+            finished_ms: None,
+        }
+    }
+
+    /// 记一项。记满了(`MAX_CHECKS`)就扔掉，已经结束了也不再记
+    pub fn record(&mut self, name: &'static str, passed: bool, detail: fmt::Arguments) {
+        if self.finished_ms.is_some() {
+            return;
+        }
+        let mut text = String::new();
+        let _ = Truncate(&mut text).write_fmt(detail);
+        let _ = self.checks.push(Check {
+            name,
+            passed,
+            detail: text,
+        });
+    }
+
+    /// 所有项都记完了
+    pub fn finish(&mut self, now_ms: u64) {
+        self.finished_ms.get_or_insert(now_ms);
+    }
+
+    /// 想等 `wanted_ms` 的话实际能等多久：不超过总时间里剩下的
+    pub fn timeout_ms(&self, wanted_ms: u64, now_ms: u64) -> u64 {
+        let used = now_ms.saturating_sub(self.started_ms);
+        wanted_ms.min(BUDGET_MS.saturating_sub(used))
+    }
+
+    pub fn checks(&self) -> &[Check] {
+        &self.checks
+    }
+
+    pub fn passed_count(&self) -> usize {
+        self.checks.iter().filter(|check| check.passed).count()
+    }
+
+    /// 一项都没记的话不算过
+    pub fn passed(&self) -> bool {
+        !self.checks.is_empty() && self.passed_count() == self.checks.len()
+    }
+
+    /// 从开始到结束用了多久(毫秒)，还没结束是 0
+    pub fn elapsed_ms(&self) -> u64 {
+        self.finished_ms
+            .map_or(0, |at| at.saturating_sub(self.started_ms))
+    }
+
+    /// 串口一共几行：每项一行，加最后的小结
+    pub fn lines(&self) -> usize {
+        self.checks.len() + 1
+    }
+
+    /// 串口的第 `index` 行(带 `\r\n`)
+    pub fn write_line<W: fmt::Write>(&self, index: usize, out: &mut W) -> fmt::Result {
+        match self.checks.get(index) {
+            Some(check) => write!(
+                out,
+                "TEST {} {} {}\r\n",
+                check.name,
+                verdict(check.passed),
+                check.detail
+            ),
+            None if index == self.checks.len() => write!(
+                out,
+                "FIXTURE {} {}/{} {}ms\r\n",
+                verdict(self.passed()),
+                self.passed_count(),
+                self.checks.len(),
+                self.elapsed_ms()
+            ),
+            None => Ok(()),
+        }
+    }
+}
+
+fn verdict(passed: bool) -> &'static str {
+    if passed {
+        "PASS"
+    } else {
+        "FAIL"
+    }
+}
+
+/// 一直调 `poll` 直到拿到结果，最多等 `timeout_ms`。至少调一次
+pub fn wait_for<T>(
+    timeout_ms: u64,
+    mut now_ms: impl FnMut() -> u64,
+    mut poll: impl FnMut() -> Option<T>,
+) -> Option<T> {
+    let started = now_ms();
+    loop {
+        if let Some(value) = poll() {
+            return Some(value);
+        }
+        if now_ms().saturating_sub(started) >= timeout_ms {
+            return None;
+        }
+    }
+}
+
+/// `now_ms` 这个时刻结果引脚该是什么电平
+pub fn result_level(passed: bool, now_ms: u64) -> bool {
+    passed || (now_ms / FAIL_PULSE_MS).is_multiple_of(2)
+}
+
+/// 画一个大勾或者大叉，下面一行小结，不清屏、不 flush
+pub fn draw_verdict<D>(display: &mut D, report: &Report) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let area = display.bounding_box();
+    let Size { width, height } = area.size;
+    let side = width.min(height.saturating_sub(SUMMARY_HEIGHT)) as i32;
+    let stroke = PrimitiveStyle::with_stroke(BinaryColor::On, (side as u32 / 6).max(3));
+    let left = area.top_left.x + (width as i32 - side) / 2;
+    let top = area.top_left.y;
+    let inset = side / 8;
+    let (near, far) = (inset, side - 1 - inset);
+    if report.passed() {
+        let corner = Point::new(left + side * 2 / 5, top + far);
+        Line::new(Point::new(left + near, top + side / 2), corner)
+            .into_styled(stroke)
+            .draw(display)?;
+        Line::new(corner, Point::new(left + far, top + near))
+            .into_styled(stroke)
+            .draw(display)?;
+    } else {
+        Line::new(
+            Point::new(left + near, top + near),
+            Point::new(left + far, top + far),
+        )
+        .into_styled(stroke)
+        .draw(display)?;
+        Line::new(
+            Point::new(left + near, top + far),
+            Point::new(left + far, top + near),
+        )
+        .into_styled(stroke)
+        .draw(display)?;
+    }
+    let mut summary: String<24> = String::new();
+    let _ = write!(
+        summary,
+        "{} {}/{}",
+        verdict(report.passed()),
+        report.passed_count(),
+        report.checks.len()
+    );
+    if let Some(failed) = report.checks.iter().find(|check| !check.passed) {
+        let _ = write!(summary, " {}", failed.name);
+    }
+    let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+    let baseline = Point::new(area.top_left.x + width as i32 / 2, top + height as i32 - 2);
+    Text::with_alignment(&summary, baseline, style, Alignment::Center).draw(display)?;
+    Ok(())
+}
+
+/// 整屏画结果，画完立刻 flush
+pub fn render_verdict<D: BufferedDisplay>(
+    display: &mut D,
+    report: &Report,
+) -> Result<(), D::Error> {
+    display.clear_buffer();
+    draw_verdict(display, report)?;
+    display.flush()
+}
@@ -0,0 +1,106 @@
+//! flash 分区和底层读写
+//!
+//! 2MB flash 的末尾划出来存数据，memory.x 里已经把这些从程序区扣掉了：
+//!
+//! | 位置 | 大小 | 用途 |
+//! |------|------|------|
+//! | `COUNTER_OFFSET` | 4K(1 个扇区) | 脉冲计数的总数(见 `pulse_counter`) |
+//! | `IMAGE_OFFSET` | 16K(4 个扇区) | 图片槽，一个槽一个扇区(见 `image_slots`) |
+//! | `LOG_OFFSET` | 64K(16 个扇区) | 数据记录(见 `datalog`) |
+//! | `SETTINGS_OFFSET` | 4K(最后一个扇区) | 第 0 页用户设置(见 `settings`)和 Wi-Fi 账号(见 `wifi_config`)，后面是屏幕累计记录(见 `panel_care`) |
+//!
+//! 擦写期间 XIP 不能用，CPU 只能跑 RAM 里的代码，所以全程关中断(rp2040-flash 会处理 RAM 里那段代码)。
+//! 擦一个扇区大概 50ms，写一页不到 1ms。
+//!
+//! core1 平时不跑，反应游戏(见 `reaction`)一局的时候才从 flash 里跑代码，这时候 `core1_busy` 是 true，
+//! 要写 flash 的先查 `low_voltage::flash_writes_allowed`(里面也看这个)等它停下来。
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// flash 擦除的最小单位
+pub const SECTOR_LEN: usize = 4096;
+
+/// flash 编程的最小单位
+pub const PAGE_LEN: usize = 256;
+
+/// flash 总大小
+const FLASH_LEN: u32 = 2048 * 1024;
+
+/// 用户设置：最后一个扇区
+pub const SETTINGS_OFFSET: u32 = FLASH_LEN - SECTOR_LEN as u32;
+
+/// 数据记录占几个扇区
+pub const LOG_SECTORS: usize = 16;
+
+/// 数据记录：设置前面的 16 个扇区
+pub const LOG_OFFSET: u32 = SETTINGS_OFFSET - (LOG_SECTORS * SECTOR_LEN) as u32;
+
+/// 图片槽有几个，每个一个扇区
+pub const IMAGE_SLOTS: usize = 4;
+
+/// 图片槽：数据记录前面的 4 个扇区
+pub const IMAGE_OFFSET: u32 = LOG_OFFSET - (IMAGE_SLOTS * SECTOR_LEN) as u32;
+
+/// 脉冲计数：图片槽前面的 1 个扇区，数据区从这里开始
+pub const COUNTER_OFFSET: u32 = IMAGE_OFFSET - SECTOR_LEN as u32;
+
+/// XIP 映射的起始地址，读 flash 直接读这个地址就行
+const XIP_BASE: u32 = 0x1000_0000;
+
+/// core1 是不是在 flash 里跑代码，是的话不能擦写
+static CORE1_BUSY: AtomicBool = AtomicBool::new(false);
+
+/// core0 让 core1 干活之前设 true；core1 停回 RAM 里的时候自己清掉(见 `reaction`)
+pub fn set_core1_busy(busy: bool) {
+    CORE1_BUSY.store(busy, Ordering::Relaxed);
+}
+
+/// core1 是不是在 flash 里跑代码
+pub fn core1_busy() -> bool {
+    CORE1_BUSY.load(Ordering::Relaxed)
+}
+
+/// `CORE1_BUSY` 的地址，给 core1 在 RAM 里的代码直接写(那里不能调 flash 里的函数)
+pub(crate) fn core1_busy_flag() -> *mut bool {
+    CORE1_BUSY.as_ptr()
+}
+
+/// 读 flash(通过 XIP 映射)
+pub fn read(offset: u32, len: usize) -> &'static [u8] {
+    // 安全性：只会读 memory.x 里划出程序区的那部分，这些地址一直有效，写的时候中断是关的
+    unsafe { core::slice::from_raw_parts((XIP_BASE + offset) as *const u8, len) }
+}
+
+/// 擦除一个扇区(擦完是全 0xFF)。`offset` 要按扇区对齐
+pub fn erase_sector(offset: u32) {
+    debug_assert!(offset.is_multiple_of(SECTOR_LEN as u32) && offset >= COUNTER_OFFSET);
+    // 安全性：地址对齐并且在数据区里；中断关掉了，core1 要么没起、要么停在 RAM 里(调用方查过 `core1_busy`)；DMA 只读 RAM 里的暂存区
+    #[cfg(target_os = "none")]
+    cortex_m::interrupt::free(|_| unsafe {
+        rp2040_flash::flash::flash_range_erase(offset, SECTOR_LEN as u32, true);
+    });
+    // 主机上跑单元测试的时候没有 flash，什么也不干
+    #[cfg(not(target_os = "none"))]
+    let _ = offset;
+}
+
+/// 写一页。flash 只能把 1 写成 0，所以 0xFF 的字节等于"不动"，可以用来只写一页里的一小段
+pub fn program_page(offset: u32, page: &[u8; PAGE_LEN]) {
+    debug_assert!(offset.is_multiple_of(PAGE_LEN as u32) && offset >= COUNTER_OFFSET);
+    // 安全性：同上
+    #[cfg(target_os = "none")]
+    cortex_m::interrupt::free(|_| unsafe {
+        rp2040_flash::flash::flash_range_program(offset, page, true);
+    });
+    #[cfg(not(target_os = "none"))]
+    let _ = (offset, page);
+}
+
+/// 在 `offset` 处写几个字节，所在页的其他字节不变。不能跨页
+pub fn program_bytes(offset: u32, bytes: &[u8]) {
+    let page_start = offset - offset % PAGE_LEN as u32;
+    let at = (offset - page_start) as usize;
+    let mut page = [0xFFu8; PAGE_LEN];
+    page[at..at + bytes.len()].copy_from_slice(bytes);
+    program_page(page_start, &page);
+}
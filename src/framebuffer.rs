@@ -0,0 +1,878 @@
+//! 自己管理的显存(framebuffer)
+//!
+//! ssd1306 crate 的 BufferedGraphicsMode 把缓冲区藏起来了，DMA 发送、校验、截图这些功能都要直接摸到
+//! 缓冲区，所以这里自己实现一份，布局和 SSD1306 的 GDDRAM 完全一样：
+//!
+//! - 128 列 x 8 页，每页 8 行像素，一共 1024 字节(128x64 的屏；尺寸跟着 `panel::PANEL` 走，128x32 的屏是 4 页 512 字节)
+//! - 第 `page` 页第 `col` 列对应 `buf[page * WIDTH + col]`
+//! - 字节里的 bit0 是这一页最上面那一行，bit7 是最下面那一行
+//!
+//! 这样 flush 的时候不用做任何转换，整块内存原样发给屏幕就行。
+//!
+//! 防烧屏平移的时候(见 `burn_in`)，通过 `DrawTarget` 画的东西都会挪 `set_shift` 设的偏移，
+//! `set_pixel`/`pixel` 直接用的坐标不受影响。
+//!
+//! `scroll` 把已经画好的内容按像素整体挪一下(竖着挪会跨页，一列拼成一个数移位)，走马灯、曲线图往左推这种
+//! 只有一边进新内容的可以挪完只画新的那一条，不用整屏重画。
+//!
+//! `xor_rect`/`xor_hline`/`xor_vline` 直接按字节异或显存，给光标、选中框这种要"橡皮筋"挪动的东西用：
+//! 在老位置再异或一次就擦掉了，不用知道下面原来是什么，也不用重画页面。它们跟 `DrawTarget` 一样受平移影响。
+//!
+//! 打开 `overlay-layer` feature 以后这里还带一层叠加层(见 `overlay_layer`)，`region_bytes` 出来的是两层合成以后的字节，
+//! 脏区域也把叠加层的改动算进去；`as_bytes`、`checksum`、`pixel` 只管页面这一层。
+
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::{Dimensions, OriginDimensions, Point, Size};
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::Pixel;
+use ssd1306::prelude::DisplayRotation;
+
+use crate::crc::{crc32, Crc32};
+#[cfg(feature = "overlay-layer")]
+use crate::overlay_layer::OverlayLayer;
+use crate::panel::PANEL;
+use crate::theme::{Fill, PatternTarget};
+
+/// 屏幕物理宽度(列数)
+pub const WIDTH: usize = PANEL.width;
+
+/// 屏幕物理高度(行数)
+pub const HEIGHT: usize = PANEL.height;
+
+/// 页数，每页 8 行
+pub const PAGES: usize = HEIGHT / 8;
+
+/// 缓冲区字节数
+pub const BUFFER_LEN: usize = WIDTH * PAGES;
+
+// `scroll` 把一列拼成一个 u64
+const _: () = assert!(PAGES <= 8);
+
+/// 一块需要刷新的区域，坐标都是物理坐标(和旋转无关)，范围是闭区间
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct DirtyRegion {
+    pub first_col: u8,
+    pub last_col: u8,
+    pub first_page: u8,
+    pub last_page: u8,
+}
+
+impl DirtyRegion {
+    /// 整个屏幕
+    pub const FULL: DirtyRegion = DirtyRegion {
+        first_col: 0,
+        last_col: WIDTH as u8 - 1,
+        first_page: 0,
+        last_page: PAGES as u8 - 1,
+    };
+
+    /// 区域里一共多少字节
+    pub fn byte_count(&self) -> usize {
+        self.columns() * self.pages()
+    }
+
+    /// 区域宽度(列数)
+    pub fn columns(&self) -> usize {
+        (self.last_col - self.first_col) as usize + 1
+    }
+
+    /// 区域高度(页数)
+    pub fn pages(&self) -> usize {
+        (self.last_page - self.first_page) as usize + 1
+    }
+
+    /// 只有 (col, page) 这一个字节
+    pub const fn byte(col: u8, page: u8) -> Self {
+        Self {
+            first_col: col,
+            last_col: col,
+            first_page: page,
+            last_page: page,
+        }
+    }
+
+    /// 同时盖住两块区域的最小区域
+    pub fn union(self, other: DirtyRegion) -> Self {
+        Self {
+            first_col: self.first_col.min(other.first_col),
+            last_col: self.last_col.max(other.last_col),
+            first_page: self.first_page.min(other.first_page),
+            last_page: self.last_page.max(other.last_page),
+        }
+    }
+}
+
+/// 两个可能为空的脏区域并起来
+pub(crate) fn merge_dirty(a: Option<DirtyRegion>, b: Option<DirtyRegion>) -> Option<DirtyRegion> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.union(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// 逻辑坐标(考虑旋转后的)换算成物理坐标。180 度是屏幕自己转的，坐标不用变
+pub(crate) fn to_physical(rotation: DisplayRotation, x: u32, y: u32) -> (usize, usize) {
+    match rotation {
+        DisplayRotation::Rotate0 | DisplayRotation::Rotate180 => (x as usize, y as usize),
+        DisplayRotation::Rotate90 | DisplayRotation::Rotate270 => (y as usize, x as usize),
+    }
+}
+
+/// 某个方向下的逻辑尺寸
+pub(crate) fn logical_size(rotation: DisplayRotation) -> Size {
+    match rotation {
+        DisplayRotation::Rotate0 | DisplayRotation::Rotate180 => {
+            Size::new(WIDTH as u32, HEIGHT as u32)
+        }
+        DisplayRotation::Rotate90 | DisplayRotation::Rotate270 => {
+            Size::new(HEIGHT as u32, WIDTH as u32)
+        }
+    }
+}
+
+/// 1bpp 显存，带脏区域记录
+pub struct FrameBuffer {
+    buf: [u8; BUFFER_LEN],
+    rotation: DisplayRotation,
+    dirty: Option<DirtyRegion>,
+    /// 绘制原点的偏移(逻辑坐标，右、下)
+    shift: (i32, i32),
+    #[cfg(feature = "overlay-layer")]
+    overlay: OverlayLayer,
+}
+
+impl Default for FrameBuffer {
+    fn default() -> Self {
+        Self::new(DisplayRotation::Rotate0)
+    }
+}
+
+impl FrameBuffer {
+    /// 新建一块全黑的显存。刚上电的屏幕内容是随机的，所以一开始整屏都算脏
+    pub const fn new(rotation: DisplayRotation) -> Self {
+        Self {
+            buf: [0; BUFFER_LEN],
+            rotation,
+            dirty: Some(DirtyRegion::FULL),
+            shift: (0, 0),
+            #[cfg(feature = "overlay-layer")]
+            overlay: OverlayLayer::new(rotation),
+        }
+    }
+
+    /// 原始缓冲区，布局见模块文档
+    pub fn as_bytes(&self) -> &[u8; BUFFER_LEN] {
+        &self.buf
+    }
+
+    /// 合成以后的整屏字节(有叠加层的话盖上叠加层)，就是 flush 出去的样子
+    pub fn composited(&self) -> [u8; BUFFER_LEN] {
+        let mut bytes = [0; BUFFER_LEN];
+        for (dst, byte) in bytes.iter_mut().zip(self.region_bytes(DirtyRegion::FULL)) {
+            *dst = byte;
+        }
+        bytes
+    }
+
+    /// 整块换成 `bytes`(布局见模块文档)，变了的字节算脏。不经过旋转和平移
+    pub fn load_bytes(&mut self, bytes: &[u8; BUFFER_LEN]) {
+        for (i, &byte) in bytes.iter().enumerate() {
+            if self.buf[i] != byte {
+                self.buf[i] = byte;
+                self.touch((i % WIDTH) as u8, (i / WIDTH) as u8);
+            }
+        }
+    }
+
+    pub fn rotation(&self) -> DisplayRotation {
+        self.rotation
+    }
+
+    /// 换方向。90/270 度的时候逻辑坐标和物理坐标是转置的，所以整屏都要重画重发。叠加层会清空
+    pub fn set_rotation(&mut self, rotation: DisplayRotation) {
+        self.rotation = rotation;
+        #[cfg(feature = "overlay-layer")]
+        self.overlay.set_rotation(rotation);
+        self.mark_all_dirty();
+    }
+
+    /// 叠加层，画在这里的东西 flush 的时候盖在页面上面
+    #[cfg(feature = "overlay-layer")]
+    pub fn overlay(&self) -> &OverlayLayer {
+        &self.overlay
+    }
+
+    #[cfg(feature = "overlay-layer")]
+    pub fn overlay_mut(&mut self) -> &mut OverlayLayer {
+        &mut self.overlay
+    }
+
+    pub fn shift(&self) -> (i32, i32) {
+        self.shift
+    }
+
+    /// 挪绘制原点，已经画好的内容不动，调用方要整屏重画
+    pub fn set_shift(&mut self, shift: (i32, i32)) {
+        self.shift = shift;
+    }
+
+    /// 清空(全黑)。只有原来亮着的字节才算脏，所以"清屏再重画同样的内容"不会变成整屏刷新。叠加层不动
+    pub fn clear(&mut self) {
+        self.fill_screen(false);
+    }
+
+    /// 整屏点亮(`on`)或者熄灭，不管平移。一页一页整块写，每页只把变了的那一段并进脏区域一次
+    pub fn fill_screen(&mut self, on: bool) {
+        let row = [if on { 0xFF } else { 0 }; WIDTH];
+        for page in 0..PAGES {
+            self.replace_page(page, &row);
+        }
+    }
+
+    /// 第 `page` 页整行换成 `row`，变了的那一段(第一个到最后一个不一样的字节)并进脏区域
+    fn replace_page(&mut self, page: usize, row: &[u8; WIDTH]) {
+        let dst = &mut self.buf[page * WIDTH..(page + 1) * WIDTH];
+        let differs = |(old, new): (&u8, &u8)| old != new;
+        let Some(first) = dst.iter().zip(row).position(differs) else {
+            return;
+        };
+        let last = dst.iter().zip(row).rposition(differs).unwrap_or(first);
+        dst[first..=last].copy_from_slice(&row[first..=last]);
+        self.dirty = merge_dirty(
+            self.dirty,
+            Some(DirtyRegion {
+                first_col: first as u8,
+                last_col: last as u8,
+                first_page: page as u8,
+                last_page: page as u8,
+            }),
+        );
+    }
+
+    /// 把整屏标记为需要刷新，比如重新 init 屏幕之后
+    pub fn mark_all_dirty(&mut self) {
+        self.dirty = Some(DirtyRegion::FULL);
+    }
+
+    /// 当前的脏区域(包括叠加层的改动)，没有改动的话是 None
+    pub fn dirty(&self) -> Option<DirtyRegion> {
+        #[cfg(feature = "overlay-layer")]
+        return merge_dirty(self.dirty, self.overlay.dirty());
+        #[cfg(not(feature = "overlay-layer"))]
+        self.dirty
+    }
+
+    /// 取走脏区域(同时清掉记录)，flush 的时候用
+    pub fn take_dirty(&mut self) -> Option<DirtyRegion> {
+        #[cfg(feature = "overlay-layer")]
+        return merge_dirty(self.dirty.take(), self.overlay.take_dirty());
+        #[cfg(not(feature = "overlay-layer"))]
+        self.dirty.take()
+    }
+
+    /// 按 flush 的顺序(一页一页，每页从左到右)迭代某块区域的字节，正好是水平寻址模式下屏幕要的数据流
+    ///
+    /// 有叠加层的话出来的是合成以后的字节，也就是屏幕上要显示的样子
+    pub fn region_bytes(&self, region: DirtyRegion) -> impl Iterator<Item = u8> + '_ {
+        let first_col = region.first_col as usize;
+        let cols = first_col..=region.last_col as usize;
+        (region.first_page as usize..=region.last_page as usize).flat_map(move |page| {
+            self.buf[page * WIDTH..][cols.clone()]
+                .iter()
+                .enumerate()
+                .map(move |(i, &byte)| self.composite(page, first_col + i, byte))
+        })
+    }
+
+    #[cfg(feature = "overlay-layer")]
+    #[inline]
+    fn composite(&self, page: usize, col: usize, byte: u8) -> u8 {
+        self.overlay.composite(page, col, byte)
+    }
+
+    #[cfg(not(feature = "overlay-layer"))]
+    #[inline]
+    fn composite(&self, _page: usize, _col: usize, byte: u8) -> u8 {
+        byte
+    }
+
+    /// 整块显存的 CRC-32，用来判断显存本身有没有被写坏
+    pub fn checksum(&self) -> u32 {
+        crc32(&self.buf)
+    }
+
+    /// 某块区域按 flush 顺序的 CRC-32，就是这次要发给屏幕的那些字节
+    pub fn region_checksum(&self, region: DirtyRegion) -> u32 {
+        let mut crc = Crc32::new();
+        self.region_bytes(region).for_each(|byte| crc.update(byte));
+        crc.finish()
+    }
+
+    /// 逻辑坐标下的一块矩形换算成要 flush 的物理区域，上下扩到整页。完全在屏幕外的返回 None
+    pub fn region_for(&self, area: Rectangle) -> Option<DirtyRegion> {
+        let area = area.intersection(&self.bounding_box());
+        let bottom_right = area.bottom_right()?;
+        let (x0, y0) = self.to_physical(area.top_left.x as u32, area.top_left.y as u32);
+        let (x1, y1) = self.to_physical(bottom_right.x as u32, bottom_right.y as u32);
+        Some(DirtyRegion {
+            first_col: x0.min(x1) as u8,
+            last_col: x0.max(x1) as u8,
+            first_page: (y0.min(y1) / 8) as u8,
+            last_page: (y0.max(y1) / 8) as u8,
+        })
+    }
+
+    fn to_physical(&self, x: u32, y: u32) -> (usize, usize) {
+        to_physical(self.rotation, x, y)
+    }
+
+    /// 把逻辑坐标下的一块矩形里的像素全部反过来，超出屏幕的部分忽略。再调一次同样的就恢复原样
+    pub fn xor_rect(&mut self, area: Rectangle) {
+        self.modify_rect(area, |_, bytes, mask| {
+            bytes.iter_mut().for_each(|byte| *byte ^= mask)
+        });
+    }
+
+    /// 把逻辑坐标下的一块矩形全部点亮(`on`)或者熄灭，超出屏幕的部分忽略。见 `fill_rect_fast`
+    pub fn fill_rect(&mut self, area: Rectangle, on: bool) {
+        self.fill_rect_fast(area, on);
+    }
+
+    /// 按页整字节填：整页都在矩形里的直接整段写(编译出来是 memset，按字写)，只占一部分的上下两头按掩码改。
+    /// 比通过 `DrawTarget` 一个像素一个像素画快得多，频谱柱这种每帧几十根的、换页动画和游戏的清屏都走这里。
+    /// 通过 `DrawTarget` 画的实心矩形(`fill_solid`)和 `clear` 也走这里
+    pub fn fill_rect_fast(&mut self, area: Rectangle, on: bool) {
+        let value = if on { 0xFF } else { 0 };
+        self.modify_rect(area, |_, bytes, mask| {
+            if mask == 0xFF {
+                bytes.fill(value);
+            } else {
+                bytes
+                    .iter_mut()
+                    .for_each(|byte| *byte = *byte & !mask | value & mask);
+            }
+        });
+    }
+
+    /// 按 `fill` 的花纹填一块(见 `theme`)：花纹亮的点是 `on`，不亮的点反过来。
+    /// 花纹是按物理的列定的，每一列是同一个字节(棋盘格单双列交替)，和 `fill_rect_fast` 一样按页整字节写
+    pub fn fill_pattern_fast(&mut self, area: Rectangle, fill: Fill, on: bool) {
+        let sideways = matches!(
+            self.rotation,
+            DisplayRotation::Rotate90 | DisplayRotation::Rotate270
+        );
+        // 物理的第 col 列(点亮的时候)是哪个字节
+        let column = move |col: usize| match fill {
+            Fill::None => None,
+            Fill::Solid => Some(0xFF),
+            Fill::Checker => Some(if col.is_multiple_of(2) { 0x55 } else { 0xAA }),
+            // 竖着放的时候逻辑上的行是物理上的列
+            Fill::HLines if sideways => Some(if col.is_multiple_of(2) { 0xFF } else { 0 }),
+            Fill::HLines => Some(0x55),
+        };
+        let invert = if on { 0 } else { 0xFF };
+        self.modify_rect(area, |first_col, bytes, mask| {
+            for (offset, byte) in bytes.iter_mut().enumerate() {
+                if let Some(value) = column(first_col + offset) {
+                    *byte = *byte & !mask | (value ^ invert) & mask;
+                }
+            }
+        });
+    }
+
+    /// 矩形落在每一页里的那一段字节(从第几列开始)，连同这一页里落在矩形里的那几位(掩码)交给 `op` 改，
+    /// 改过的页并进脏区域
+    fn modify_rect(&mut self, area: Rectangle, op: impl Fn(usize, &mut [u8], u8)) {
+        let area = Rectangle::new(area.top_left + Point::from(self.shift), area.size)
+            .intersection(&self.bounding_box());
+        let Some(bottom_right) = area.bottom_right() else {
+            return;
+        };
+        let (x0, y0) = self.to_physical(area.top_left.x as u32, area.top_left.y as u32);
+        let (x1, y1) = self.to_physical(bottom_right.x as u32, bottom_right.y as u32);
+        let (cols, rows) = (x0.min(x1)..=x0.max(x1), y0.min(y1)..=y0.max(y1));
+        for page in rows.start() / 8..=rows.end() / 8 {
+            // 这一页里落在矩形里的那几行，上下两头的页只占一部分
+            let top = rows.start().saturating_sub(page * 8).min(7);
+            let bottom = (rows.end() - page * 8).min(7);
+            let mask = (0xFFu8 >> (7 - bottom)) & (0xFFu8 << top);
+            op(
+                *cols.start(),
+                &mut self.buf[page * WIDTH..][cols.clone()],
+                mask,
+            );
+            self.dirty = merge_dirty(
+                self.dirty,
+                Some(DirtyRegion {
+                    first_col: *cols.start() as u8,
+                    last_col: *cols.end() as u8,
+                    first_page: page as u8,
+                    last_page: page as u8,
+                }),
+            );
+        }
+    }
+
+    /// 整块显存的内容往右挪 `dx`、往下挪 `dy` 个像素(逻辑坐标，负数是往左、往上)，空出来的行列是黑的
+    ///
+    /// 纯软件挪，任意像素都行，和屏幕自己的硬件滚动(只能按页、只能循环滚)没关系。挪出屏幕的内容就丢了。
+    /// 动的是已经画好的内容，`set_shift` 的偏移、叠加层都不管；变了的字节算脏
+    pub fn scroll(&mut self, dx: i32, dy: i32) {
+        let (dx, dy) = match self.rotation {
+            DisplayRotation::Rotate0 | DisplayRotation::Rotate180 => (dx, dy),
+            DisplayRotation::Rotate90 | DisplayRotation::Rotate270 => (dy, dx),
+        };
+        if dx.unsigned_abs() as usize >= WIDTH || dy.unsigned_abs() as usize >= HEIGHT {
+            self.clear();
+            return;
+        }
+        if dx == 0 && dy % 8 == 0 {
+            self.vscroll_pages(dy / 8);
+            return;
+        }
+        let old = self.buf;
+        for col in 0..WIDTH {
+            // 挪之后这一列的内容来自原来的哪一列，来自屏幕外面的就是空的
+            let source = col as i32 - dx;
+            let column = if (0..WIDTH as i32).contains(&source) {
+                // 一整列拼成一个数，第 n 位就是第 n 行，这样跨页的竖直移动就是一次移位
+                (0..PAGES).fold(0u64, |column, page| {
+                    column | (old[page * WIDTH + source as usize] as u64) << (page * 8)
+                })
+            } else {
+                0
+            };
+            let column = if dy >= 0 { column << dy } else { column >> -dy };
+            for page in 0..PAGES {
+                let byte = (column >> (page * 8)) as u8;
+                if self.buf[page * WIDTH + col] != byte {
+                    self.buf[page * WIDTH + col] = byte;
+                    self.touch(col as u8, page as u8);
+                }
+            }
+        }
+    }
+
+    /// 整块显存按页往下挪 `pages` 页(物理方向，负数是往上)，空出来的页是黑的，挪出去的就丢了
+    ///
+    /// 不用拼列、移位，整页整段搬(`copy_within`，按字搬)，一页 8 行的文字往上滚一行这种用。
+    /// 和 `scroll` 一样不管平移和叠加层，变了的字节算脏。`scroll` 竖着挪正好整页的时候也走这里
+    pub fn vscroll_pages(&mut self, pages: i32) {
+        if pages.unsigned_abs() as usize >= PAGES {
+            self.clear();
+            return;
+        }
+        let old = self.buf;
+        let blank = [0; WIDTH];
+        for page in 0..PAGES {
+            let source = page as i32 - pages;
+            let row = if (0..PAGES as i32).contains(&source) {
+                let start = source as usize * WIDTH;
+                old[start..start + WIDTH].try_into().unwrap_or(&blank)
+            } else {
+                &blank
+            };
+            self.replace_page(page, row);
+        }
+    }
+
+    /// 从 (x, y) 往右 `len` 个像素反色
+    pub fn xor_hline(&mut self, x: i32, y: i32, len: u32) {
+        self.xor_rect(Rectangle::new(Point::new(x, y), Size::new(len, 1)));
+    }
+
+    /// 从 (x, y) 往下 `len` 个像素反色
+    pub fn xor_vline(&mut self, x: i32, y: i32, len: u32) {
+        self.xor_rect(Rectangle::new(Point::new(x, y), Size::new(1, len)));
+    }
+
+    /// 设置一个像素，越界的直接忽略
+    pub fn set_pixel(&mut self, x: u32, y: u32, on: bool) {
+        let (col, row) = self.to_physical(x, y);
+        if col >= WIDTH || row >= HEIGHT {
+            return;
+        }
+        let page = row / 8;
+        let bit = 1 << (row % 8);
+        let byte = &mut self.buf[page * WIDTH + col];
+        let old = *byte;
+        if on {
+            *byte |= bit;
+        } else {
+            *byte &= !bit;
+        }
+        if *byte != old {
+            self.touch(col as u8, page as u8);
+        }
+    }
+
+    /// 读一个像素，越界的返回 false
+    pub fn pixel(&self, x: u32, y: u32) -> bool {
+        let (col, row) = self.to_physical(x, y);
+        if col >= WIDTH || row >= HEIGHT {
+            return false;
+        }
+        self.buf[row / 8 * WIDTH + col] & (1 << (row % 8)) != 0
+    }
+
+    /// 把 (col, page) 这个字节并进脏区域
+    fn touch(&mut self, col: u8, page: u8) {
+        self.dirty = merge_dirty(self.dirty, Some(DirtyRegion::byte(col, page)));
+    }
+}
+
+impl OriginDimensions for FrameBuffer {
+    fn size(&self) -> Size {
+        logical_size(self.rotation)
+    }
+}
+
+impl DrawTarget for FrameBuffer {
+    type Color = BinaryColor;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let (dx, dy) = self.shift;
+        for Pixel(point, color) in pixels {
+            let (x, y) = (point.x + dx, point.y + dy);
+            if x >= 0 && y >= 0 {
+                self.set_pixel(x as u32, y as u32, color.is_on());
+            }
+        }
+        Ok(())
+    }
+
+    /// 实心矩形(比如 `Rectangle` 用实心样式画)整字节填，不一个像素一个像素画。和 `draw_iter` 一样受平移影响
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        self.fill_rect_fast(*area, color.is_on());
+        Ok(())
+    }
+
+    /// 整屏填满，不管平移
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.fill_screen(color.is_on());
+        Ok(())
+    }
+}
+
+/// 花纹按字节填，见 `fill_pattern_fast`
+impl PatternTarget for FrameBuffer {
+    fn fill_pattern(
+        &mut self,
+        area: &Rectangle,
+        fill: Fill,
+        color: BinaryColor,
+    ) -> Result<(), Self::Error> {
+        self.fill_pattern_fast(*area, fill, color.is_on());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROTATIONS: [DisplayRotation; 4] = [
+        DisplayRotation::Rotate0,
+        DisplayRotation::Rotate90,
+        DisplayRotation::Rotate180,
+        DisplayRotation::Rotate270,
+    ];
+
+    /// 每个字节都不一样的底图，改错了位一眼能看出来
+    fn patterned(rotation: DisplayRotation) -> FrameBuffer {
+        let mut fb = FrameBuffer::new(rotation);
+        let mut bytes = [0u8; BUFFER_LEN];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = (i as u8).wrapping_mul(37).wrapping_add(11);
+        }
+        fb.load_bytes(&bytes);
+        fb.take_dirty();
+        fb
+    }
+
+    /// 逐个像素改的参考实现，超出屏幕的部分忽略
+    fn per_pixel(fb: &mut FrameBuffer, area: Rectangle, op: impl Fn(bool) -> bool) {
+        let size = fb.size();
+        for y in area.top_left.y..area.top_left.y + area.size.height as i32 {
+            for x in area.top_left.x..area.top_left.x + area.size.width as i32 {
+                if x < 0 || y < 0 || x >= size.width as i32 || y >= size.height as i32 {
+                    continue;
+                }
+                let (x, y) = (x as u32, y as u32);
+                let on = op(fb.pixel(x, y));
+                fb.set_pixel(x, y, on);
+            }
+        }
+    }
+
+    /// 每个页内偏移 0..7 开始，单行、到页尾、整页、跨页的高度
+    fn areas() -> impl Iterator<Item = Rectangle> {
+        (0..8).flat_map(|offset| {
+            [1, 2, 8 - offset, 8, 9, 17].into_iter().map(move |height| {
+                Rectangle::new(Point::new(3, offset as i32), Size::new(21, height))
+            })
+        })
+    }
+
+    #[test]
+    fn xor_rect_matches_per_pixel_reference() {
+        for rotation in ROTATIONS {
+            for area in areas().chain([Rectangle::new(Point::new(0, 8), Size::new(128, 8))]) {
+                let mut fast = patterned(rotation);
+                let mut reference = patterned(rotation);
+                fast.xor_rect(area);
+                per_pixel(&mut reference, area, |on| !on);
+                assert_eq!(
+                    fast.as_bytes(),
+                    reference.as_bytes(),
+                    "{rotation:?} {area:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn xor_rect_twice_restores_the_buffer() {
+        let mut fb = patterned(DisplayRotation::Rotate0);
+        let area = Rectangle::new(Point::new(5, 3), Size::new(40, 22));
+        fb.xor_rect(area);
+        fb.xor_rect(area);
+        assert_eq!(
+            fb.as_bytes(),
+            patterned(DisplayRotation::Rotate0).as_bytes()
+        );
+    }
+
+    #[test]
+    fn fill_rect_matches_per_pixel_reference() {
+        for rotation in ROTATIONS {
+            for area in areas() {
+                for on in [false, true] {
+                    let mut fast = patterned(rotation);
+                    let mut reference = patterned(rotation);
+                    fast.fill_rect(area, on);
+                    per_pixel(&mut reference, area, |_| on);
+                    assert_eq!(
+                        fast.as_bytes(),
+                        reference.as_bytes(),
+                        "{rotation:?} {area:?} {on}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn single_row_touches_only_its_bit() {
+        for offset in 0..8 {
+            let mut fb = FrameBuffer::new(DisplayRotation::Rotate0);
+            fb.xor_hline(0, 8 + offset, WIDTH as u32);
+            let page = &fb.as_bytes()[WIDTH..2 * WIDTH];
+            assert!(page.iter().all(|&byte| byte == 1 << offset));
+            assert_eq!(
+                fb.as_bytes().iter().filter(|&&byte| byte != 0).count(),
+                WIDTH
+            );
+        }
+    }
+
+    #[test]
+    fn modify_rect_clips_to_the_screen_and_marks_dirty_pages() {
+        let mut fb = patterned(DisplayRotation::Rotate0);
+        let mut reference = patterned(DisplayRotation::Rotate0);
+        let area = Rectangle::new(Point::new(-4, 13), Size::new(10, 6));
+        fb.xor_rect(area);
+        per_pixel(&mut reference, area, |on| !on);
+        assert_eq!(fb.as_bytes(), reference.as_bytes());
+        assert_eq!(
+            fb.take_dirty(),
+            Some(DirtyRegion {
+                first_col: 0,
+                last_col: 5,
+                first_page: 1,
+                last_page: 2,
+            })
+        );
+
+        fb.xor_rect(Rectangle::new(Point::new(200, 0), Size::new(10, 10)));
+        assert_eq!(fb.take_dirty(), None);
+    }
+
+    /// 逐个像素挪的参考实现：挪之后 (x, y) 是原来 (x - dx, y - dy) 的像素，来自屏幕外面的是黑的
+    fn scrolled_reference(fb: &FrameBuffer, dx: i32, dy: i32) -> FrameBuffer {
+        let mut reference = FrameBuffer::new(fb.rotation());
+        let size = fb.size();
+        for y in 0..size.height as i32 {
+            for x in 0..size.width as i32 {
+                let (from_x, from_y) = (x - dx, y - dy);
+                let inside = (0..size.width as i32).contains(&from_x)
+                    && (0..size.height as i32).contains(&from_y);
+                let on = inside && fb.pixel(from_x as u32, from_y as u32);
+                reference.set_pixel(x as u32, y as u32, on);
+            }
+        }
+        reference
+    }
+
+    /// 变了的字节都要在脏区域里
+    fn assert_dirty_covers(before: &FrameBuffer, after: &mut FrameBuffer) {
+        let dirty = after.take_dirty();
+        for (i, (old, new)) in before.as_bytes().iter().zip(after.as_bytes()).enumerate() {
+            if old == new {
+                continue;
+            }
+            let (col, page) = (i % WIDTH, i / WIDTH);
+            let covered = dirty.is_some_and(|region| {
+                (region.first_col as usize..=region.last_col as usize).contains(&col)
+                    && (region.first_page as usize..=region.last_page as usize).contains(&page)
+            });
+            assert!(covered, "col {col} page {page} 变了但不在 {dirty:?} 里");
+        }
+    }
+
+    #[test]
+    fn scroll_matches_per_pixel_reference() {
+        for rotation in ROTATIONS {
+            let base = patterned(rotation);
+            let size = base.size();
+            let (w, h) = (size.width as i32, size.height as i32);
+            // 每个横向偏移配几个竖向偏移，反过来也一样，出了屏幕的也算
+            let moves = (-w - 1..=w + 1)
+                .flat_map(|dx| [0, 1, -3, 8].map(|dy| (dx, dy)))
+                .chain((-h - 1..=h + 1).flat_map(|dy| [0, 2, -5].map(|dx| (dx, dy))));
+            for (dx, dy) in moves {
+                let mut fb = patterned(rotation);
+                fb.scroll(dx, dy);
+                let reference = scrolled_reference(&base, dx, dy);
+                assert_eq!(
+                    fb.as_bytes(),
+                    reference.as_bytes(),
+                    "{rotation:?} ({dx}, {dy})"
+                );
+                assert_dirty_covers(&base, &mut fb);
+            }
+        }
+    }
+
+    #[test]
+    fn scroll_ignores_shift() {
+        // 挪的是画好的内容，和 `set_shift` 的偏移没关系
+        let mut fb = patterned(DisplayRotation::Rotate0);
+        fb.set_shift((2, 1));
+        let mut plain = patterned(DisplayRotation::Rotate0);
+        fb.scroll(5, -3);
+        plain.scroll(5, -3);
+        assert_eq!(fb.as_bytes(), plain.as_bytes());
+    }
+
+    #[test]
+    fn scroll_by_nothing_changes_nothing() {
+        let mut fb = patterned(DisplayRotation::Rotate0);
+        fb.scroll(0, 0);
+        assert_eq!(
+            fb.as_bytes(),
+            patterned(DisplayRotation::Rotate0).as_bytes()
+        );
+        assert_eq!(fb.take_dirty(), None);
+    }
+
+    #[test]
+    fn vscroll_pages_matches_scroll() {
+        for pages in -(PAGES as i32) - 1..=PAGES as i32 + 1 {
+            let before = patterned(DisplayRotation::Rotate0);
+            let mut fb = patterned(DisplayRotation::Rotate0);
+            fb.vscroll_pages(pages);
+            let reference = scrolled_reference(&before, 0, pages * 8);
+            assert_eq!(fb.as_bytes(), reference.as_bytes(), "{pages}");
+            assert_dirty_covers(&before, &mut fb);
+        }
+    }
+
+    #[test]
+    fn fill_screen_and_clear() {
+        for on in [true, false] {
+            let before = patterned(DisplayRotation::Rotate90);
+            let mut fb = patterned(DisplayRotation::Rotate90);
+            fb.set_shift((3, 3));
+            fb.fill_screen(on);
+            let value = if on { 0xFF } else { 0 };
+            assert!(fb.as_bytes().iter().all(|&byte| byte == value));
+            assert_dirty_covers(&before, &mut fb);
+        }
+        // 清屏只有亮着的地方算脏，已经是黑的再清一遍什么都不算
+        let mut fb = FrameBuffer::new(DisplayRotation::Rotate0);
+        fb.set_pixel(10, 20, true);
+        fb.set_pixel(40, 21, true);
+        fb.take_dirty();
+        fb.clear();
+        assert_eq!(
+            fb.take_dirty(),
+            Some(DirtyRegion {
+                first_col: 10,
+                last_col: 40,
+                first_page: 2,
+                last_page: 2,
+            })
+        );
+        fb.clear();
+        assert_eq!(fb.take_dirty(), None);
+    }
+
+    #[test]
+    fn draw_target_fills_match_per_pixel_reference() {
+        use embedded_graphics::prelude::Primitive;
+        use embedded_graphics::primitives::PrimitiveStyle;
+        use embedded_graphics::Drawable;
+
+        for rotation in ROTATIONS {
+            for area in areas().chain([Rectangle::new(Point::new(-3, -2), Size::new(9, 11))]) {
+                for color in [BinaryColor::On, BinaryColor::Off] {
+                    let mut fast = patterned(rotation);
+                    let mut styled = patterned(rotation);
+                    let mut reference = patterned(rotation);
+                    fast.fill_solid(&area, color).unwrap();
+                    area.into_styled(PrimitiveStyle::with_fill(color))
+                        .draw(&mut styled)
+                        .unwrap();
+                    per_pixel(&mut reference, area, |_| color.is_on());
+                    assert_eq!(
+                        fast.as_bytes(),
+                        reference.as_bytes(),
+                        "{rotation:?} {area:?}"
+                    );
+                    assert_eq!(
+                        styled.as_bytes(),
+                        reference.as_bytes(),
+                        "{rotation:?} {area:?}"
+                    );
+                    let before = patterned(rotation);
+                    assert_dirty_covers(&before, &mut fast);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn fill_solid_follows_shift() {
+        use embedded_graphics::primitives::PointsIter;
+
+        // 和 `draw_iter` 一样受平移影响，`clear` 不管平移
+        let area = Rectangle::new(Point::new(4, 4), Size::new(6, 6));
+        let mut fast = FrameBuffer::new(DisplayRotation::Rotate0);
+        let mut slow = FrameBuffer::new(DisplayRotation::Rotate0);
+        for fb in [&mut fast, &mut slow] {
+            fb.set_shift((2, -1));
+        }
+        fast.fill_solid(&area, BinaryColor::On).unwrap();
+        slow.draw_iter(area.points().map(|point| Pixel(point, BinaryColor::On)))
+            .unwrap();
+        assert_eq!(fast.as_bytes(), slow.as_bytes());
+        DrawTarget::clear(&mut fast, BinaryColor::On).unwrap();
+        assert!(fast.as_bytes().iter().all(|&byte| byte == 0xFF));
+    }
+}
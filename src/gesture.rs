@@ -0,0 +1,328 @@
+//! 按键手势：一个键上分出短按、长按、双击
+//!
+//! 在 `ButtonPad` 消抖后的按下/松开事件上面再做一层，每个键一个小状态机：
+//!
+//! - 按下以后按住超过 `LONG_MS`：长按，松开的时候什么都不发
+//! - 按下又松开，`DOUBLE_TAP_MS` 之内又按下去：双击，第二次按下的时候就发
+//! - 按下又松开，`DOUBLE_TAP_MS` 之内没再按：短按。所以短按要等双击窗口过了才报，比 `Pressed` 晚一点
+//!
+//! 主循环把按键事件喂给 `feed`，每一圈调一次 `poll`(长按和短按是到时间才报的)，得到的手势作为
+//! `Event::Gesture` 发给当前页面。原来的 `Event::Button` 照发，页面挑一种用就行，两种都处理的话一次按键会被当成两次。
+//!
+//! 这里的长按和 `input::LONG_PRESS_MS`(进床头钟模式那个)是两回事，这个短一些，按着一下就算。
+//!
+//! 还有一种按满(`Gesture::Hold`)：一个键按住 `HOLD_MS` 不放，只给关机键用，由 `HoldGate` 报，不是 `GestureDetector`。
+//! 按住三秒的过程中不能让这个键先把页面切走了，可按下这个事件一般是马上就用的(Select 打开菜单项这种)，
+//! 所以这个键的 `ButtonEvent` 先压着不发：没按满就松开了，松开的时候把按下、长按、松开一起补发给页面；
+//! 按满了就报 `Hold`，这次按键的其他事件都丢掉。代价是这个键在页面上是松开的时候才有反应。
+//! `HoldGate` 只压给页面的按键事件，手势还是按真实的按下、松开算，短按本来就是松开以后才报的，
+//! 按满之前已经过了长按的时间，也不会再报短按。
+
+use crate::input::{Button, ButtonEvent};
+
+/// 按住多久算长按(毫秒)
+pub const LONG_MS: u64 = 500;
+
+/// 松开以后多久之内再按下算双击(毫秒)
+pub const DOUBLE_TAP_MS: u64 = 250;
+
+/// 按住多久算按满(毫秒)，见 `HoldGate`
+pub const HOLD_MS: u64 = 3000;
+
+/// 手势
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Gesture {
+    Short(Button),
+    Long(Button),
+    Double(Button),
+    /// 按住 `HOLD_MS`，只有 `HoldGate` 管的那个键有
+    Hold(Button),
+}
+
+/// 一个键的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Idle,
+    /// 第一次按下，什么时候按的
+    Down(u64),
+    /// 松开了，等等看会不会再按。什么时候松开的
+    Released(u64),
+    /// 已经报过长按或者双击了，等松开
+    Done,
+}
+
+/// 四个键的手势识别
+#[derive(Debug)]
+pub struct GestureDetector {
+    states: [State; 4],
+}
+
+impl Default for GestureDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GestureDetector {
+    pub const fn new() -> Self {
+        Self {
+            states: [State::Idle; 4],
+        }
+    }
+
+    /// 喂一个消抖后的按键事件，双击在这里报(还有 `poll` 没赶上报的短按)
+    pub fn feed(&mut self, event: ButtonEvent, now_ms: u64) -> Option<Gesture> {
+        match event {
+            ButtonEvent::Pressed(button) => {
+                let state = &mut self.states[index(button)];
+                match *state {
+                    State::Released(at) if now_ms.saturating_sub(at) < DOUBLE_TAP_MS => {
+                        *state = State::Done;
+                        Some(Gesture::Double(button))
+                    }
+                    // 双击窗口刚好过了、`poll` 还没来得及报上一次短按，这里补报
+                    State::Released(_) => {
+                        *state = State::Down(now_ms);
+                        Some(Gesture::Short(button))
+                    }
+                    _ => {
+                        *state = State::Down(now_ms);
+                        None
+                    }
+                }
+            }
+            ButtonEvent::Released(button) => {
+                let state = &mut self.states[index(button)];
+                *state = match *state {
+                    State::Down(_) => State::Released(now_ms),
+                    _ => State::Idle,
+                };
+                None
+            }
+            ButtonEvent::LongPress(_) => None,
+        }
+    }
+
+    /// 到时间的长按和短按，每个手势调一次 `on_gesture`。主循环里每一圈都调
+    pub fn poll(&mut self, now_ms: u64, mut on_gesture: impl FnMut(Gesture)) {
+        for (i, state) in self.states.iter_mut().enumerate() {
+            let button = BUTTONS[i];
+            match *state {
+                State::Down(at) if now_ms.saturating_sub(at) >= LONG_MS => {
+                    *state = State::Done;
+                    on_gesture(Gesture::Long(button));
+                }
+                State::Released(at) if now_ms.saturating_sub(at) >= DOUBLE_TAP_MS => {
+                    *state = State::Idle;
+                    on_gesture(Gesture::Short(button));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// 没有按着的键，也没有在等双击的键
+    pub fn is_idle(&self) -> bool {
+        self.states.iter().all(|state| *state == State::Idle)
+    }
+
+    /// 全部忘掉，比如从床头钟模式回来
+    pub fn reset(&mut self) {
+        self.states = [State::Idle; 4];
+    }
+}
+
+const BUTTONS: [Button; 4] = [Button::Up, Button::Down, Button::Select, Button::Back];
+
+fn index(button: Button) -> usize {
+    match button {
+        Button::Up => 0,
+        Button::Down => 1,
+        Button::Select => 2,
+        Button::Back => 3,
+    }
+}
+
+/// `HoldGate` 管的那个键现在怎么样了
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HoldState {
+    Idle,
+    /// 按下了，什么时候按的，报没报过长按(压着没发)
+    Down {
+        at: u64,
+        long: bool,
+    },
+    /// 按满了，等松开，中间的事件都丢掉
+    Held,
+}
+
+/// 一个键的按满检测，压着它的按键事件等结果，见模块文档
+#[derive(Debug)]
+pub struct HoldGate {
+    button: Button,
+    state: HoldState,
+}
+
+impl HoldGate {
+    pub const fn new(button: Button) -> Self {
+        Self {
+            button,
+            state: HoldState::Idle,
+        }
+    }
+
+    /// 喂一个消抖后的按键事件，该交给页面的交给 `forward`(别的键原样交)
+    pub fn feed(&mut self, event: ButtonEvent, now_ms: u64, mut forward: impl FnMut(ButtonEvent)) {
+        let button = self.button;
+        match (event, self.state) {
+            (ButtonEvent::Pressed(which), _) if which == button => {
+                self.state = HoldState::Down {
+                    at: now_ms,
+                    long: false,
+                };
+            }
+            (ButtonEvent::LongPress(which), HoldState::Down { at, .. }) if which == button => {
+                self.state = HoldState::Down { at, long: true };
+            }
+            (ButtonEvent::Released(which), HoldState::Down { long, .. }) if which == button => {
+                self.state = HoldState::Idle;
+                forward(ButtonEvent::Pressed(button));
+                if long {
+                    forward(ButtonEvent::LongPress(button));
+                }
+                forward(event);
+            }
+            (ButtonEvent::Released(which), HoldState::Held) if which == button => {
+                self.state = HoldState::Idle;
+            }
+            (_, HoldState::Held) if event_button(event) == button => {}
+            _ => forward(event),
+        }
+    }
+
+    /// 按满了报一次 `Gesture::Hold`，主循环里每一圈都调
+    pub fn poll(&mut self, now_ms: u64) -> Option<Gesture> {
+        match self.state {
+            HoldState::Down { at, .. } if now_ms.saturating_sub(at) >= HOLD_MS => {
+                self.state = HoldState::Held;
+                Some(Gesture::Hold(self.button))
+            }
+            _ => None,
+        }
+    }
+
+    /// 全部忘掉，比如从床头钟模式回来
+    pub fn reset(&mut self) {
+        self.state = HoldState::Idle;
+    }
+}
+
+fn event_button(event: ButtonEvent) -> Button {
+    match event {
+        ButtonEvent::Pressed(button)
+        | ButtonEvent::Released(button)
+        | ButtonEvent::LongPress(button) => button,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::vec::Vec;
+
+    const KEY: Button = Button::Select;
+
+    /// 和主循环一样每毫秒一圈：先喂这一毫秒的按键事件，再 `poll`。返回报出来的手势和时间
+    fn run(events: &[(u64, ButtonEvent)], end_ms: u64) -> Vec<(u64, Gesture)> {
+        let mut detector = GestureDetector::new();
+        let mut out = Vec::new();
+        for now in events.first().map_or(0, |&(at, _)| at)..=end_ms {
+            for &(_, event) in events.iter().filter(|&&(at, _)| at == now) {
+                if let Some(gesture) = detector.feed(event, now) {
+                    out.push((now, gesture));
+                }
+            }
+            detector.poll(now, |gesture| out.push((now, gesture)));
+        }
+        assert!(detector.is_idle());
+        out
+    }
+
+    fn tap(at: u64, hold: u64) -> [(u64, ButtonEvent); 2] {
+        [
+            (at, ButtonEvent::Pressed(KEY)),
+            (at + hold, ButtonEvent::Released(KEY)),
+        ]
+    }
+
+    #[test]
+    fn short_tap_is_reported_when_the_double_tap_window_closes() {
+        assert_eq!(
+            run(&tap(1000, 80), 2000),
+            [(1080 + DOUBLE_TAP_MS, Gesture::Short(KEY))]
+        );
+    }
+
+    #[test]
+    fn long_press_fires_at_exactly_long_ms_and_nothing_on_release() {
+        assert_eq!(
+            run(&tap(1000, 900), 3000),
+            [(1000 + LONG_MS, Gesture::Long(KEY))]
+        );
+        // 差 1 毫秒不算长按
+        assert_eq!(
+            run(&tap(1000, LONG_MS - 1), 3000),
+            [(1000 + LONG_MS - 1 + DOUBLE_TAP_MS, Gesture::Short(KEY))]
+        );
+    }
+
+    #[test]
+    fn double_tap_fires_on_the_second_press() {
+        let mut events = Vec::from(tap(1000, 60));
+        events.extend(tap(1060 + DOUBLE_TAP_MS - 1, 60));
+        let second = 1060 + DOUBLE_TAP_MS - 1;
+        assert_eq!(run(&events, 3000), [(second, Gesture::Double(KEY))]);
+    }
+
+    #[test]
+    fn second_tap_at_or_after_the_window_is_two_short_taps() {
+        for gap in [DOUBLE_TAP_MS, DOUBLE_TAP_MS + 1] {
+            let mut events = Vec::from(tap(1000, 60));
+            let second = 1060 + gap;
+            events.extend(tap(second, 60));
+            assert_eq!(
+                run(&events, 3000),
+                [
+                    (1060 + DOUBLE_TAP_MS, Gesture::Short(KEY)),
+                    (second + 60 + DOUBLE_TAP_MS, Gesture::Short(KEY)),
+                ],
+                "gap {gap}"
+            );
+        }
+
+        // 主循环卡住了，`poll` 没赶上：按下的时候补报上一次短按
+        let mut detector = GestureDetector::new();
+        detector.feed(ButtonEvent::Pressed(KEY), 1000);
+        detector.feed(ButtonEvent::Released(KEY), 1060);
+        assert_eq!(
+            detector.feed(ButtonEvent::Pressed(KEY), 1060 + DOUBLE_TAP_MS),
+            Some(Gesture::Short(KEY))
+        );
+        assert!(!detector.is_idle());
+    }
+
+    #[test]
+    fn keys_are_independent() {
+        let mut events = Vec::from(tap(1000, 60));
+        events.push((1100, ButtonEvent::Pressed(Button::Up)));
+        events.push((1150, ButtonEvent::Released(Button::Up)));
+        assert_eq!(
+            run(&events, 3000),
+            [
+                (1060 + DOUBLE_TAP_MS, Gesture::Short(KEY)),
+                (1150 + DOUBLE_TAP_MS, Gesture::Short(Button::Up)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,300 @@
+//! 画面回归测试：照着脚本画几屏，和仓库里存着的标准答案(golden 文件)逐像素比
+//!
+//! 只在电脑上跑，要加 feature：`cargo test-host --features golden`。脚本在下面的 `mod tests` 里(菜单、曲线图、
+//! 自动换行)，每个脚本一个文件 `golden/<宽>x<高>/<名字>.txt`。一帧是 `## <第几帧> <说明>` 开头、一行像素一行字的
+//! 字符画，`#` 亮 `.` 灭，直接打开就能看，改了什么在 git diff 里也看得出来。
+//!
+//! 对不上的时候测试失败，打出不一样的那几行：`#`/`.` 是一样的，`+` 是多亮了的点，`-` 是该亮没亮的点。
+//! 是故意改的(换了排版、字体)就加 `GOLDEN_UPDATE=1` 再跑一遍重新生成，和代码一起提交。
+//! 屏幕尺寸不一样(`panel-128x32`)的 golden 文件分开放；还没生成过也算失败，提示怎么生成。
+
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::{env, fs};
+
+use crate::framebuffer::{FrameBuffer, HEIGHT, WIDTH};
+
+/// 设了这个环境变量就重新生成 golden 文件，不比
+pub const UPDATE_ENV: &str = "GOLDEN_UPDATE";
+
+/// 差异前后多打几行没变的，看得出是哪一块
+const CONTEXT_ROWS: usize = 2;
+
+/// 一帧画成字符画
+pub fn to_ascii(canvas: &FrameBuffer) -> String {
+    let mut out = String::with_capacity((WIDTH + 1) * HEIGHT);
+    for y in 0..HEIGHT as u32 {
+        for x in 0..WIDTH as u32 {
+            out.push(if canvas.pixel(x, y) { '#' } else { '.' });
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// 两帧字符画不一样的话，返回标好了 `+`/`-` 的那几行(带行号)
+pub fn diff(expected: &str, actual: &str) -> Option<String> {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+    let (mut extra, mut missing) = (0, 0);
+    let mut changed = Vec::new();
+    let rows: Vec<String> = (0..expected.len().max(actual.len()))
+        .map(|row| {
+            let want = expected.get(row).map_or(&[][..], |line| line.as_bytes());
+            let got = actual.get(row).map_or(&[][..], |line| line.as_bytes());
+            let mut line = String::new();
+            for col in 0..want.len().max(got.len()) {
+                let lit = |pixels: &[u8]| pixels.get(col) == Some(&b'#');
+                line.push(match (lit(want), lit(got)) {
+                    (true, true) => '#',
+                    (false, false) => '.',
+                    (false, true) => {
+                        extra += 1;
+                        '+'
+                    }
+                    (true, false) => {
+                        missing += 1;
+                        '-'
+                    }
+                });
+            }
+            // 行数、宽度对不上(golden 文件手改坏了)也算变了
+            if line.contains(['+', '-']) || want.len() != got.len() {
+                changed.push(row);
+            }
+            line
+        })
+        .collect();
+    let (&first, &last) = (changed.first()?, changed.last()?);
+    let mut out = format!("{extra} extra (+), {missing} missing (-)\n");
+    let end = (last + CONTEXT_ROWS).min(rows.len() - 1);
+    for (row, line) in rows.iter().enumerate().take(end + 1).skip(first.saturating_sub(CONTEXT_ROWS)) {
+        let _ = writeln!(out, "{row:3} {line}");
+    }
+    Some(out)
+}
+
+/// 一个脚本画出来的一串帧
+#[derive(Debug)]
+pub struct Golden {
+    name: &'static str,
+    /// (说明, 字符画)
+    frames: Vec<(String, String)>,
+}
+
+impl Golden {
+    /// `name` 是 golden 文件的名字
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            frames: Vec::new(),
+        }
+    }
+
+    /// 记下画布上现在这一帧
+    pub fn capture(&mut self, label: &str, canvas: &FrameBuffer) {
+        let label = format!("{} {}", self.frames.len(), label);
+        self.frames.push((label, to_ascii(canvas)));
+    }
+
+    fn path(&self) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("golden")
+            .join(format!("{WIDTH}x{HEIGHT}"))
+            .join(format!("{}.txt", self.name))
+    }
+
+    fn to_file(&self) -> String {
+        let mut out = format!("# {} {WIDTH}x{HEIGHT}, {} frames\n", self.name, self.frames.len());
+        for (label, pixels) in &self.frames {
+            let _ = write!(out, "## {label}\n{pixels}");
+        }
+        out
+    }
+
+    /// 和 golden 文件比，哪一帧对不上都 panic；设了 `GOLDEN_UPDATE` 就把这次画的写进去
+    pub fn check(self) {
+        let path = self.path();
+        if env::var_os(UPDATE_ENV).is_some() {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir).expect("create golden dir");
+            }
+            fs::write(&path, self.to_file()).expect("write golden file");
+            return;
+        }
+        let Ok(text) = fs::read_to_string(&path) else {
+            panic!(
+                "{}: no golden file, generate it with {UPDATE_ENV}=1 cargo test-host --features golden",
+                path.display()
+            );
+        };
+        let expected = parse(&text);
+        let mut report = String::new();
+        if expected.len() != self.frames.len() {
+            let _ = writeln!(report, "expected {} frames, drew {}", expected.len(), self.frames.len());
+        }
+        for ((want_label, want), (label, got)) in expected.iter().zip(&self.frames) {
+            if want_label != label {
+                let _ = writeln!(report, "frame {label:?} is {want_label:?} in the golden file");
+            }
+            if let Some(diff) = diff(want, got) {
+                let _ = write!(report, "frame {label:?}: {diff}");
+            }
+        }
+        if !report.is_empty() {
+            panic!(
+                "{} does not match (rerun with {UPDATE_ENV}=1 if the change is intended):\n{report}",
+                path.display()
+            );
+        }
+    }
+}
+
+/// 读 golden 文件：`## ` 开头的是一帧的说明，后面到下一个 `## ` 是这一帧
+fn parse(text: &str) -> Vec<(String, String)> {
+    let mut frames: Vec<(String, String)> = Vec::new();
+    for line in text.lines() {
+        if let Some(label) = line.strip_prefix("## ") {
+            frames.push((label.to_string(), String::new()));
+        } else if let Some((_, pixels)) = frames.last_mut() {
+            pixels.push_str(line);
+            pixels.push('\n');
+        }
+    }
+    frames
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use embedded_graphics::geometry::{Point, Size};
+    use embedded_graphics::mono_font::ascii::FONT_6X10;
+    use embedded_graphics::mono_font::MonoTextStyle;
+    use embedded_graphics::pixelcolor::BinaryColor;
+    use embedded_graphics::primitives::Rectangle;
+    use ssd1306::prelude::DisplayRotation;
+
+    use crate::app::{Event, Page, PageId};
+    use crate::input::{Button, ButtonEvent};
+    use crate::settings_menu::SettingsMenu;
+    use crate::text::draw_wrapped;
+    use crate::widgets::{draw_bar_chart, draw_line_graph};
+
+    fn canvas() -> FrameBuffer {
+        FrameBuffer::new(DisplayRotation::Rotate0)
+    }
+
+    fn press(page: &mut impl Page, button: Button) {
+        page.on_event(&Event::Button(ButtonEvent::Pressed(button)), 0);
+    }
+
+    #[test]
+    fn diff_marks_changed_pixels() {
+        let expected = "....\n.##.\n....\n";
+        assert_eq!(diff(expected, expected), None);
+        let report = diff(expected, "....\n.#.#\n....\n").unwrap();
+        assert!(report.starts_with("1 extra (+), 1 missing (-)\n"), "{report}");
+        assert!(report.contains("  1 .#-+\n"), "{report}");
+        // 少了一行也算不一样
+        assert!(diff(expected, "....\n.##.\n").is_some());
+    }
+
+    #[test]
+    fn file_round_trips() {
+        let mut golden = Golden::new("round-trip");
+        let mut fb = canvas();
+        golden.capture("blank", &fb);
+        fb.set_pixel(3, 4, true);
+        golden.capture("one pixel", &fb);
+        let frames = parse(&golden.to_file());
+        assert_eq!(frames, golden.frames);
+        assert_eq!(frames[1].0, "1 one pixel");
+    }
+
+    #[test]
+    fn menu() {
+        let mut golden = Golden::new("menu");
+        let mut menu = SettingsMenu::new(
+            "settings",
+            [
+                ("display", PageId(1)),
+                ("sound", PageId(2)),
+                ("clock", PageId(3)),
+                ("sensors", PageId(4)),
+                ("alarms", PageId(5)),
+                ("about", PageId(6)),
+            ],
+        );
+        let mut fb = canvas();
+        let mut frame = |menu: &mut SettingsMenu<6>, label: &str| {
+            fb.clear();
+            menu.render(&mut fb, 0).unwrap();
+            golden.capture(label, &fb);
+        };
+        frame(&mut menu, "first item");
+        press(&mut menu, Button::Down);
+        frame(&mut menu, "down");
+        for _ in 0..4 {
+            press(&mut menu, Button::Down);
+        }
+        frame(&mut menu, "last item, scrolled");
+        press(&mut menu, Button::Down);
+        frame(&mut menu, "down wraps to the top");
+        press(&mut menu, Button::Up);
+        frame(&mut menu, "up wraps to the bottom");
+        golden.check();
+    }
+
+    #[test]
+    fn graph() {
+        let mut golden = Golden::new("graph");
+        let mut fb = canvas();
+        let area = Rectangle::new(Point::new(4, 4), Size::new(120, 40));
+
+        // 从最小值走到最大值的一条斜线，右边没数据的地方不画
+        draw_line_graph(&mut fb, area, (0..100).map(|i| Some(i * 10)), 0, 1000).unwrap();
+        golden.capture("ramp", &fb);
+
+        // 三角波，中间缺一段数据折线断开，两头超出范围的贴边
+        fb.clear();
+        let triangle = (0..160).map(|i: i32| match i {
+            40..=49 => None,
+            _ => Some((i % 40 - 20).abs() * 8 - 40),
+        });
+        draw_line_graph(&mut fb, area, triangle, 0, 100).unwrap();
+        golden.capture("gap and clamped", &fb);
+
+        fb.clear();
+        let bars = [0u8, 10, 25, 50, 75, 100, 120, 60];
+        let bar_area = Rectangle::new(Point::new(0, 16), Size::new(128, 48));
+        draw_bar_chart(&mut fb, bar_area, bars.into_iter(), 100).unwrap();
+        golden.capture("bar chart", &fb);
+        golden.check();
+    }
+
+    #[test]
+    fn text_wrapping() {
+        let mut golden = Golden::new("text-wrap");
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        let mut fb = canvas();
+        // 左边一列 60 像素正好 10 个字，右边一列窄到放不下一个字
+        let cases = [
+            ("exact fit", "0123456789 next line"),
+            ("long word", "supercalifragilistic ok"),
+            ("newlines and spaces", "a   b\n\nc  \n  d"),
+            ("non-ascii", "temp 25\u{00b0}C \u{6e29}\u{5ea6}"),
+            (
+                "runs off the bottom",
+                "one two three four five six seven eight nine ten eleven twelve thirteen",
+            ),
+        ];
+        for (label, text) in cases {
+            fb.clear();
+            draw_wrapped(&mut fb, text, Point::new(0, 0), 60, style).unwrap();
+            draw_wrapped(&mut fb, text, Point::new(96, 0), 4, style).unwrap();
+            golden.capture(label, &fb);
+        }
+        golden.check();
+    }
+}
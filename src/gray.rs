@@ -0,0 +1,135 @@
+//! 假灰度：SSD1306 每个像素只有亮和灭，一个像素在连着几帧里亮一部分帧，看起来就是暗一点的灰
+//!
+//! `GrayTarget` 是一块 2 位灰度(`Gray2`，0 黑到 3 白)的画布，存成两个位平面，布局和 `FrameBuffer` 一样。
+//! 一个周期 `PHASES` 帧，灰度 n 的像素在前 n 帧里亮：1 亮 1/3 的时间，2 亮 2/3，3 一直亮。
+//! 每一帧调一次 `write_to` 把这一帧该亮的像素整块写进显存，再 `advance` 到下一帧。
+//!
+//! 这招全靠刷得快，代价是闪：
+//!
+//! - 一个周期 3 帧，60 帧每秒的话灰度 1、2 是 20Hz 在闪，眼睛看得出来是在"抖"，拍照、录像更明显；
+//!   30 帧以下基本就是亮灭交替，不像灰了。所以至少要 50~60 帧每秒，而且每一帧都要真的发到屏幕上，
+//!   跳过一帧那一相的占空比就不对了
+//! - 400kHz 的 I2C 发一整屏要二十多毫秒，最多四十几帧，不够。只能让会变的区域小一点(演示页面只有中间 5 页，
+//!   十几毫秒)，而且要走 DMA flush，CPU 不用陪着等
+//! - 屏幕自己按一百多赫兹扫描，和我们换帧的节奏不同步，偶尔能看到横着的亮纹
+//!
+//! 所以适合演示、偶尔画一小块渐变，不适合整屏一直用。写进显存的时候不管防烧屏的平移。
+
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::{OriginDimensions, Size};
+use embedded_graphics::pixelcolor::{Gray2, GrayColor};
+use embedded_graphics::Pixel;
+use ssd1306::prelude::DisplayRotation;
+
+use crate::framebuffer::{logical_size, to_physical, FrameBuffer, BUFFER_LEN, WIDTH};
+
+/// 一个周期几帧，灰度 0..=3 正好要 3 帧
+pub const PHASES: u8 = 3;
+
+/// 2 位灰度画布
+pub struct GrayTarget {
+    /// 灰度的低位、高位，每个像素一位
+    low: [u8; BUFFER_LEN],
+    high: [u8; BUFFER_LEN],
+    rotation: DisplayRotation,
+    /// 现在是周期里的第几帧
+    phase: u8,
+}
+
+impl GrayTarget {
+    pub const fn new(rotation: DisplayRotation) -> Self {
+        Self {
+            low: [0; BUFFER_LEN],
+            high: [0; BUFFER_LEN],
+            rotation,
+            phase: 0,
+        }
+    }
+
+    pub fn rotation(&self) -> DisplayRotation {
+        self.rotation
+    }
+
+    /// 换方向，画好的东西清掉，调用方要重画
+    pub fn set_rotation(&mut self, rotation: DisplayRotation) {
+        self.rotation = rotation;
+        self.clear_all();
+    }
+
+    /// 全黑
+    pub fn clear_all(&mut self) {
+        self.low = [0; BUFFER_LEN];
+        self.high = [0; BUFFER_LEN];
+    }
+
+    pub fn phase(&self) -> u8 {
+        self.phase
+    }
+
+    /// 到周期里的下一帧
+    pub fn advance(&mut self) {
+        self.phase = (self.phase + 1) % PHASES;
+    }
+
+    /// 第 `phase` 帧的 1bpp 字节：灰度比 `phase` 大的像素亮
+    pub fn frame(&self, phase: u8) -> [u8; BUFFER_LEN] {
+        let mut bytes = [0; BUFFER_LEN];
+        for ((dst, &low), &high) in bytes.iter_mut().zip(&self.low).zip(&self.high) {
+            *dst = match phase {
+                0 => high | low,
+                1 => high,
+                _ => high & low,
+            };
+        }
+        bytes
+    }
+
+    /// 这一帧整块写进显存，变了的字节算脏
+    pub fn write_to(&self, fb: &mut FrameBuffer) {
+        fb.load_bytes(&self.frame(self.phase));
+    }
+
+    fn set(&mut self, x: u32, y: u32, level: u8) {
+        let (x, y) = to_physical(self.rotation, x, y);
+        let index = (y / 8) * WIDTH + x;
+        let bit = 1u8 << (y % 8);
+        for (plane, on) in [
+            (&mut self.low, level & 1 != 0),
+            (&mut self.high, level & 2 != 0),
+        ] {
+            if on {
+                plane[index] |= bit;
+            } else {
+                plane[index] &= !bit;
+            }
+        }
+    }
+}
+
+impl OriginDimensions for GrayTarget {
+    fn size(&self) -> Size {
+        logical_size(self.rotation)
+    }
+}
+
+impl DrawTarget for GrayTarget {
+    type Color = Gray2;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let size = self.size();
+        for Pixel(point, color) in pixels {
+            if point.x >= 0
+                && point.y >= 0
+                && (point.x as u32) < size.width
+                && (point.y as u32) < size.height
+            {
+                self.set(point.x as u32, point.y as u32, color.luma());
+            }
+        }
+        Ok(())
+    }
+}
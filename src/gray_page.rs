@@ -0,0 +1,98 @@
+//! 假灰度演示：四条竖条，从左到右灰度 0 到 3(见 `gray`)
+//!
+//! 从演示菜单的 "gray" 进来，Back 回去。灰度是靠连着几帧开开关关凑出来的，所以这一页要 60 帧每秒，
+//! 每一帧都是周期里的下一相。竖条只占屏幕中间的几页，上下不画任何东西：调度器每帧先清屏，
+//! 亮着的字节都会算脏，标题这种不变的字也会跟着重发，flush 就跟不上 60 帧了。
+//! 128x64 的屏是中间 5 页，DMA 发一帧十几毫秒，正好跟得上。看着闪是正常的，闪多厉害见 `gray` 的模块文档。
+
+use core::convert::Infallible;
+
+use embedded_graphics::geometry::{OriginDimensions, Point, Size};
+use embedded_graphics::pixelcolor::{Gray2, GrayColor};
+use embedded_graphics::primitives::{Primitive, PrimitiveStyle, Rectangle};
+use embedded_graphics::Drawable;
+use ssd1306::prelude::DisplayRotation;
+
+use crate::app::{Canvas, Event, Page, Transition};
+use crate::gray::GrayTarget;
+use crate::input::{Button, ButtonEvent};
+
+/// 灰度换相要每一帧都发出去，见模块文档
+const FPS: u16 = 60;
+
+/// 假灰度演示页面
+pub struct GrayPage {
+    gray: GrayTarget,
+    /// 竖条是按多大的逻辑尺寸画的(横屏、竖屏不一样)，None 是还没画
+    drawn: Option<Size>,
+}
+
+impl GrayPage {
+    pub const fn new() -> Self {
+        Self {
+            gray: GrayTarget::new(DisplayRotation::Rotate0),
+            drawn: None,
+        }
+    }
+
+    /// 按现在的方向画四条竖条，每条一个灰度
+    fn draw_bars(&mut self, rotation: DisplayRotation) {
+        self.gray.set_rotation(rotation);
+        // 上面空四分之一，竖条占八分之五(128x64 的屏是第 16 到 55 行，正好 5 页)
+        let size = self.gray.size();
+        let (width, top, height) = (size.width / 4, size.height / 4, size.height * 5 / 8);
+        for level in 0..4u8 {
+            let area = Rectangle::new(
+                Point::new((level as u32 * width) as i32, top as i32),
+                Size::new(width, height),
+            );
+            let Ok(()) = area
+                .into_styled(PrimitiveStyle::with_fill(Gray2::new(level)))
+                .draw(&mut self.gray);
+        }
+        // 灰度 0 的那条是黑的，描个边看得出来在哪
+        let first = Rectangle::new(Point::new(0, top as i32), Size::new(width, height));
+        let Ok(()) = first
+            .into_styled(PrimitiveStyle::with_stroke(Gray2::WHITE, 1))
+            .draw(&mut self.gray);
+        self.drawn = Some(size);
+    }
+}
+
+impl Default for GrayPage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Page for GrayPage {
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        match event {
+            Event::Button(ButtonEvent::Pressed(Button::Back)) => Transition::Pop,
+            _ => Transition::None,
+        }
+    }
+
+    /// 每一帧换一相
+    fn tick(&mut self, _now_ms: u64) -> bool {
+        self.gray.advance();
+        true
+    }
+
+    fn desired_fps(&self) -> u16 {
+        FPS
+    }
+
+    /// 90 度和 270 度尺寸一样、方向相反，光看尺寸看不出来，直接重画竖条
+    fn on_rotation_changed(&mut self, _size: Size) {
+        self.drawn = None;
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<(), Infallible> {
+        if self.drawn != Some(canvas.size()) {
+            self.draw_bars(canvas.rotation());
+        }
+        self.gray.write_to(canvas);
+        Ok(())
+    }
+}
@@ -0,0 +1,54 @@
+//! 硬件自检：确认屏幕真的接在总线上，以及扫描总线上有哪些设备(串口命令 `SCAN`)
+//!
+//! 往 SSD1306 写数据是"只管发不管收"的，屏幕没接、接错地址，`init`/`flush` 照样可能一路成功
+//! (比如总线上别的设备碰巧应答了，或者错误被忽略了)，所以需要一个单独的检测。
+//!
+//! ## SSD1306 的读取限制
+//!
+//! SSD1306 在 I2C 模式下基本没有可读的东西：
+//!
+//! - 手册里 I2C 接口只定义了写操作，显存(GDDRAM)读不回来，命令寄存器也读不回来
+//! - 部分屏幕在读方向上会应答地址并返回一个状态字节(bit6 是显示开关状态)，但不是所有模块/兼容芯片都支持，
+//!   有的直接返回 0xFF，所以状态字节的内容不可信
+//! - RP2040 的 I2C 硬件发不了"只有地址、没有数据"的零长度写
+//!
+//! 所以这里能做的只有：发一个不会改变屏幕状态的字节(空的命令流控制字节 0x00)，看地址有没有被应答。
+//! 应答了只能说明"这个地址上有个 I2C 设备"，不能证明它就是一块正常工作的屏幕，但对"有没有接屏幕"
+//! 这种判断已经足够了，而且可以在主循环里反复调用来检测热插拔。
+
+use embedded_hal::i2c::I2c;
+use heapless::Vec;
+
+use crate::watchdog_feed::Checkpoint;
+
+/// SSD1306 的命令流控制字节。后面不跟任何命令，屏幕收到之后什么都不会做
+const EMPTY_COMMAND: u8 = 0x00;
+
+/// 尽力检测 `address` 上有没有屏幕：应答了返回 true
+///
+/// 只能说明地址被应答了，详细的限制见模块文档
+pub fn display_present<I: I2c>(i2c: &mut I, address: u8) -> bool {
+    i2c.write(address, &[EMPTY_COMMAND]).is_ok()
+}
+
+/// 总线扫描最多记几个地址，一条总线上一般也就三四个设备
+pub const SCAN_MAX: usize = 16;
+
+/// 扫一遍 7 位地址里能用的 0x08..=0x77(两头是保留地址)，返回应答了的地址，超过 `SCAN_MAX` 个的不记
+///
+/// 和 `display_present` 不同，这里每个地址读 1 个字节而不是写：不知道对面是什么设备，写一个字节可能会改掉它的寄存器指针，
+/// 读一般不会改变设备状态(SSD1306 会返回一个状态字节)
+///
+/// 一百多个地址挨个问，每问完一个报一次检查点(见 `watchdog_feed`)；某个地址卡住了就到不了下一个检查点
+pub fn scan<I: I2c>(i2c: &mut I, checkpoint: &mut Checkpoint<'_>) -> Vec<u8, SCAN_MAX> {
+    let mut found = Vec::new();
+    for address in 0x08..=0x77 {
+        let mut byte = [0u8];
+        let acked = i2c.read(address, &mut byte).is_ok();
+        checkpoint.reached();
+        if acked && found.push(address).is_err() {
+            break;
+        }
+    }
+    found
+}
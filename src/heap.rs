@@ -0,0 +1,67 @@
+//! 可选的堆内存(`alloc` feature)
+//!
+//! 默认编译不带堆，所有字符串都是 heapless 的定长缓冲区，内存用量编译期就定死了。
+//! 有的页面确实需要长度不定的字符串(比如电脑发来的歌名)，这时可以打开 `alloc` feature：
+//! 会有一块 `HEAP_SIZE` 大小的静态内存作为全局分配器，`alloc::string::String` 这些就都能用了。
+//!
+//! 堆用完了(OOM)会 panic，死机画面上显示 "OOM"，见 `panic_screen`。
+//! 诊断页面会显示堆用了多少，方便调 `HEAP_SIZE`。
+
+/// 堆的使用情况
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct HeapUsage {
+    /// 已经分配出去的字节数
+    pub used: usize,
+    /// 堆总大小
+    pub size: usize,
+}
+
+#[cfg(feature = "alloc")]
+mod imp {
+    use core::mem::MaybeUninit;
+
+    use embedded_alloc::LlffHeap as Heap;
+
+    use super::HeapUsage;
+
+    /// 堆大小(字节)。RP2040 有 264K 内存，8K 对界面上的字符串绰绰有余
+    pub const HEAP_SIZE: usize = 8 * 1024;
+
+    // 电脑上跑单元测试的时候用 std 自己的分配器
+    #[cfg_attr(not(test), global_allocator)]
+    static HEAP: Heap = Heap::empty();
+
+    /// 初始化堆，要在第一次分配之前调用，重复调用没有效果
+    pub fn init() {
+        // singleton! 只有第一次给得出来，之后这块内存归分配器独占
+        let Some(memory) = cortex_m::singleton!(: [MaybeUninit<u8>; HEAP_SIZE] = [MaybeUninit::uninit(); HEAP_SIZE])
+        else {
+            return;
+        };
+        // 安全性：`memory` 是 'static 的独占引用，交出去以后没有别人再碰
+        unsafe { HEAP.init(memory.as_mut_ptr() as usize, HEAP_SIZE) }
+    }
+
+    pub fn usage() -> Option<HeapUsage> {
+        Some(HeapUsage {
+            used: HEAP.used(),
+            size: HEAP_SIZE,
+        })
+    }
+}
+
+#[cfg(not(feature = "alloc"))]
+mod imp {
+    use super::HeapUsage;
+
+    /// 没有堆，什么都不用做
+    pub fn init() {}
+
+    pub fn usage() -> Option<HeapUsage> {
+        None
+    }
+}
+
+#[cfg(feature = "alloc")]
+pub use imp::HEAP_SIZE;
+pub use imp::{init, usage};
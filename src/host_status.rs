@@ -0,0 +1,358 @@
+//! 电脑状态小屏：电脑上的小工具通过 USB HID 的 output report 把状态发过来，这里负责解析和显示
+//!
+//! 为什么用 HID 而不是 CDC 串口？HID 不需要装驱动、不占用串口号，电脑端脚本用 hidapi
+//! 直接按 VID/PID 打开就能写，串口则留给后面的命令控制台用。
+//!
+//! 一个报文固定 `REPORT_LEN` 字节，布局(版本 1)：
+//!
+//! | 偏移 | 含义 |
+//! |------|------|
+//! | 0    | 报文版本，目前只认 `PACKET_VERSION` |
+//! | 1    | 序号，设备回 ack 的时候原样带回去 |
+//! | 2    | CPU 占用百分比 0..=100 |
+//! | 3    | 音量百分比 0..=100 |
+//! | 4    | 标志位，见 `HostFlags` |
+//! | 5    | 标题长度 |
+//! | 6..  | 正在播放的标题(ASCII，非 ASCII 字符显示成 `?`) |
+//!
+//! 设备每收到一个报文就回一个 input report：`[版本, 序号, 结果码, 0...]`，结果码见 `AckCode`。
+//! 以后要加字段就升版本号，旧固件收到新版本会回 `AckCode::UnsupportedVersion`，电脑端可以据此降级。
+
+use core::convert::Infallible;
+
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::text::Text;
+use embedded_graphics::Drawable;
+use heapless::{Deque, String};
+
+use crate::app::{Canvas, Event, Page, Transition};
+use crate::sprite::{SpriteAnimation, SIGNAL};
+use crate::text::draw_centered;
+use crate::theme::{self, PatternTarget};
+use crate::widgets::{draw_bar_chart_styled, draw_level_gauge_styled, Marquee};
+
+/// 一个 HID 报文的长度(不含 report id，本设备不用 report id)
+pub const REPORT_LEN: usize = 32;
+
+/// 当前支持的报文版本
+pub const PACKET_VERSION: u8 = 1;
+
+/// 报文头长度，后面全是标题
+const HEADER_LEN: usize = 6;
+
+/// 标题最长多少字节
+pub const TITLE_CAPACITY: usize = REPORT_LEN - HEADER_LEN;
+
+/// 超过这么久没收到电脑的报文，就认为电脑端脚本没在跑，显示 "waiting for host"
+pub const HOST_TIMEOUT_MS: u64 = 3000;
+
+/// 标题几个逻辑 tick 滚一下(一下 2 像素)，50Hz 下一秒滚 20 像素
+const MARQUEE_TICKS: u32 = 5;
+
+/// CPU 占用历史保留多少个点(柱状图的柱子数)
+pub const LOAD_HISTORY_LEN: usize = 24;
+
+/// HID 报文描述符：厂商自定义 usage page(0xFF00)，32 字节 input + 32 字节 output
+///
+/// 系统看到厂商自定义的 usage 不会把它当键盘鼠标，只有我们自己的脚本会去读写
+#[rustfmt::skip]
+pub const REPORT_DESCRIPTOR: &[u8] = &[
+    0x06, 0x00, 0xFF, // Usage Page (Vendor Defined 0xFF00)
+    0x09, 0x01, // Usage (0x01)
+    0xA1, 0x01, // Collection (Application)
+    0x15, 0x00, //   Logical Minimum (0)
+    0x26, 0xFF, 0x00, //   Logical Maximum (255)
+    0x75, 0x08, //   Report Size (8)
+    0x95, REPORT_LEN as u8, //   Report Count (32)
+    0x09, 0x02, //   Usage (0x02)
+    0x81, 0x02, //   Input (Data, Var, Abs)
+    0x95, REPORT_LEN as u8, //   Report Count (32)
+    0x09, 0x03, //   Usage (0x03)
+    0x91, 0x02, //   Output (Data, Var, Abs)
+    0xC0, // End Collection
+];
+
+/// 报文第 4 字节的标志位
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct HostFlags(pub u8);
+
+impl HostFlags {
+    pub const NUM_LOCK: u8 = 1 << 0;
+    pub const CAPS_LOCK: u8 = 1 << 1;
+    pub const SCROLL_LOCK: u8 = 1 << 2;
+    pub const MUTED: u8 = 1 << 3;
+
+    pub fn contains(self, bit: u8) -> bool {
+        self.0 & bit != 0
+    }
+}
+
+/// 报文解析失败的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum PacketError {
+    /// 长度不够一个报文头
+    TooShort,
+    /// 版本号不认识(一般是电脑端脚本比固件新)
+    UnsupportedVersion(u8),
+    /// 标题长度字段超出了报文
+    BadTitleLength,
+    /// 百分比超过 100
+    OutOfRange,
+}
+
+/// 回给电脑的结果码
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum AckCode {
+    Ok = 0,
+    UnsupportedVersion = 1,
+    Malformed = 2,
+}
+
+impl From<PacketError> for AckCode {
+    fn from(err: PacketError) -> Self {
+        match err {
+            PacketError::UnsupportedVersion(_) => AckCode::UnsupportedVersion,
+            _ => AckCode::Malformed,
+        }
+    }
+}
+
+/// 解析好的一帧电脑状态
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusPacket {
+    pub seq: u8,
+    pub cpu_load: u8,
+    pub volume: u8,
+    pub flags: HostFlags,
+    pub title: String<TITLE_CAPACITY>,
+}
+
+impl StatusPacket {
+    /// 按报文版本解析，版本不对直接拒绝而不是瞎猜字段
+    pub fn parse(report: &[u8]) -> Result<Self, PacketError> {
+        if report.len() < HEADER_LEN {
+            return Err(PacketError::TooShort);
+        }
+        match report[0] {
+            1 => Self::parse_v1(report),
+            other => Err(PacketError::UnsupportedVersion(other)),
+        }
+    }
+
+    fn parse_v1(report: &[u8]) -> Result<Self, PacketError> {
+        let cpu_load = report[2];
+        let volume = report[3];
+        if cpu_load > 100 || volume > 100 {
+            return Err(PacketError::OutOfRange);
+        }
+
+        let title_len = report[5] as usize;
+        let title_bytes = report
+            .get(HEADER_LEN..HEADER_LEN + title_len)
+            .filter(|_| title_len <= TITLE_CAPACITY)
+            .ok_or(PacketError::BadTitleLength)?;
+
+        // 字体只有 ASCII，其它字符换成 ?，容量是按字节算的所以一定放得下
+        let mut title = String::new();
+        for &b in title_bytes {
+            let c = if b.is_ascii_graphic() || b == b' ' { b as char } else { '?' };
+            let _ = title.push(c);
+        }
+
+        Ok(Self {
+            seq: report[1],
+            cpu_load,
+            volume,
+            flags: HostFlags(report[4]),
+            title,
+        })
+    }
+}
+
+/// 生成回给电脑的 ack 报文
+pub fn encode_ack(seq: u8, code: AckCode) -> [u8; REPORT_LEN] {
+    let mut ack = [0u8; REPORT_LEN];
+    ack[0] = PACKET_VERSION;
+    ack[1] = seq;
+    ack[2] = code as u8;
+    ack
+}
+
+/// 电脑状态页面的全部状态
+pub struct HostStatus {
+    latest: Option<StatusPacket>,
+    last_seen_ms: Option<u64>,
+    load_history: Deque<u8, LOAD_HISTORY_LEN>,
+    marquee: Marquee,
+    /// 上一帧是不是连着的，用来发现连上/断开的那一刻
+    was_connected: bool,
+    /// 等电脑的时候转的信号格
+    waiting: SpriteAnimation,
+}
+
+impl Default for HostStatus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HostStatus {
+    pub const fn new() -> Self {
+        Self {
+            latest: None,
+            last_seen_ms: None,
+            load_history: Deque::new(),
+            marquee: Marquee::new(),
+            was_connected: false,
+            waiting: SpriteAnimation::new(&SIGNAL),
+        }
+    }
+
+    /// 收到一帧新状态
+    pub fn apply(&mut self, packet: StatusPacket, now_ms: u64) {
+        if self.load_history.is_full() {
+            self.load_history.pop_front();
+        }
+        let _ = self.load_history.push_back(packet.cpu_load);
+
+        let title_changed = self.latest.as_ref().map(|p| &p.title) != Some(&packet.title);
+        if title_changed {
+            self.marquee.reset();
+        }
+
+        self.latest = Some(packet);
+        self.last_seen_ms = Some(now_ms);
+    }
+
+    /// 电脑端最近 `HOST_TIMEOUT_MS` 内有没有发过报文
+    pub fn is_connected(&self, now_ms: u64) -> bool {
+        self.last_seen_ms
+            .is_some_and(|seen| now_ms.saturating_sub(seen) < HOST_TIMEOUT_MS)
+    }
+
+    /// 每一帧调用一次，返回是否需要重画：连上、断开的那一刻画一次
+    pub fn tick(&mut self, now_ms: u64) -> bool {
+        let connected = self.is_connected(now_ms);
+        let changed = connected != self.was_connected;
+        self.was_connected = connected;
+        changed
+    }
+
+    /// 每个逻辑 tick 调用一次(见 `timestep`)，返回是否需要重画：
+    /// 连着的时候标题每 `MARQUEE_TICKS` 个 tick 滚一下，断开以后信号格换帧的时候画一次
+    pub fn step(&mut self, tick: u32) -> bool {
+        if !self.was_connected {
+            return self.waiting.step();
+        }
+        if !tick.is_multiple_of(MARQUEE_TICKS) {
+            return false;
+        }
+        self.marquee.advance(2);
+        true
+    }
+
+    /// 画整个页面：上面是滚动标题，中间左边 CPU 柱状图、右边音量条，最下面一行是锁定键状态
+    pub fn render<D>(&self, display: &mut D, now_ms: u64) -> Result<(), D::Error>
+    where
+        D: PatternTarget,
+    {
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+
+        let packet = match (&self.latest, self.is_connected(now_ms)) {
+            (Some(packet), true) => packet,
+            _ => {
+                let width = display.bounding_box().size.width as i32;
+                let icon = SIGNAL.size().width as i32;
+                self.waiting
+                    .draw(display, Point::new((width - icon) / 2, 18))?;
+                return draw_centered(display, "waiting for host", 40, style);
+            }
+        };
+
+        let width = display.bounding_box().size.width;
+        let title = if packet.title.is_empty() { "-" } else { packet.title.as_str() };
+        self.marquee.draw(
+            display,
+            title,
+            Rectangle::new(Point::zero(), Size::new(width, 10)),
+            8,
+            style,
+        )?;
+
+        // CPU 占用：标签 + 历史柱状图
+        let mut label: String<12> = String::new();
+        let _ = core::fmt::write(&mut label, format_args!("CPU {}%", packet.cpu_load));
+        Text::new(&label, Point::new(0, 21), style).draw(display)?;
+        let theme = theme::active();
+        draw_bar_chart_styled(
+            display,
+            Rectangle::new(Point::new(0, 24), Size::new(width.saturating_sub(28), 28)),
+            self.load_history.iter().copied(),
+            100,
+            &theme.bar_chart,
+        )?;
+
+        // 音量：右边一根竖条，静音的时候显示 M
+        let gauge_x = width as i32 - 12;
+        Text::new("V", Point::new(gauge_x + 2, 21), style).draw(display)?;
+        let volume = if packet.flags.contains(HostFlags::MUTED) { 0 } else { packet.volume };
+        draw_level_gauge_styled(
+            display,
+            Rectangle::new(Point::new(gauge_x, 24), Size::new(10, 28)),
+            volume,
+            &theme.gauge,
+        )?;
+        if packet.flags.contains(HostFlags::MUTED) {
+            Text::new("M", Point::new(gauge_x + 2, 62), style).draw(display)?;
+        }
+
+        // 最下面：键盘锁定状态
+        let locks = [
+            (HostFlags::NUM_LOCK, "NUM", 0),
+            (HostFlags::CAPS_LOCK, "CAPS", 24),
+            (HostFlags::SCROLL_LOCK, "SCRL", 54),
+        ];
+        for (bit, name, x) in locks {
+            if packet.flags.contains(bit) {
+                Text::new(name, Point::new(x, 62), style).draw(display)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Page for HostStatus {
+    fn on_event(&mut self, event: &Event, now_ms: u64) -> Transition {
+        if let Event::Host(packet) = event {
+            self.apply(packet.clone(), now_ms);
+        }
+        Transition::None
+    }
+
+    fn tick(&mut self, now_ms: u64) -> bool {
+        HostStatus::tick(self, now_ms)
+    }
+
+    fn step(&mut self, tick: u32) -> bool {
+        HostStatus::step(self, tick)
+    }
+
+    /// 标题栏宽度变了，字幕从头滚
+    fn on_rotation_changed(&mut self, _size: Size) {
+        self.marquee.reset();
+    }
+
+    /// 电脑一次都没发过状态的话轮播跳过这一页
+    fn has_data(&self) -> bool {
+        self.latest.is_some()
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, now_ms: u64) -> Result<(), Infallible> {
+        HostStatus::render(self, canvas, now_ms)
+    }
+}
@@ -0,0 +1,223 @@
+//! 温湿度传感器：SHT31、AHT20 两种芯片，驱动都实现 `HumiditySensor`，上面的代码不用管接的是哪种
+//!
+//! 开机的时候 `detect` 按 SHT31(0x44/0x45)、AHT20(0x38) 的顺序探测，用找到的第一个。
+//! 两种芯片都要"发命令 -> 等十几到几十毫秒 -> 读结果"，主循环不能干等，所以测量分成 `start` 和 `read` 两步，
+//! 中间隔多久由 `measurement_ms` 说，`HumidityMonitor` 负责这个节奏，总线空闲了才动(总线和屏幕共用)。
+//!
+//! 数据的 CRC-8 对不上就当这次没读到，马上重测一次；重测还不对才把读数作废。
+//! 每次 CRC 出错都会计数，诊断页面上能看到，线太长、上拉太弱的时候这个数会一直涨。
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+
+use crate::aht20::Aht20;
+use crate::sht31::Sht31;
+
+/// 隔多久测一次(毫秒)，温湿度变得慢，测太勤芯片自己发热还会让温度偏高
+pub const POLL_MS: u32 = 2000;
+
+/// 忙位还置着的时候再等多久
+const BUSY_RETRY_MS: u64 = 10;
+
+/// CRC 校验失败的次数，诊断页面要看，所以放在静态区
+static CRC_ERRORS: AtomicU32 = AtomicU32::new(0);
+
+/// 一共有几次 CRC 校验失败
+pub fn crc_errors() -> u32 {
+    CRC_ERRORS.load(Ordering::Relaxed)
+}
+
+/// 记一次 CRC 失败。只在主循环里调用，读和写之间不会被打断(M0+ 没有原子加)
+fn record_crc_error() {
+    CRC_ERRORS.store(crc_errors().wrapping_add(1), Ordering::Relaxed);
+}
+
+/// 一次测量的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct HumidityReading {
+    /// 温度，单位 0.01°C
+    pub temp_centi: i32,
+    /// 相对湿度，单位 0.01%
+    pub humidity_centi: i32,
+}
+
+/// 测量失败的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum HumidityError {
+    /// 芯片没应答
+    Bus,
+    /// 数据的 CRC-8 对不上
+    Crc,
+    /// 还没测完
+    Busy,
+}
+
+/// 温湿度传感器的驱动
+pub trait HumiditySensor {
+    /// 芯片型号，页面标题上显示
+    fn name(&self) -> &'static str;
+
+    /// `start` 之后至少等多久才能 `read`
+    fn measurement_ms(&self) -> u32;
+
+    /// 开始一次测量，马上返回
+    fn start<I: I2c>(&mut self, i2c: &mut I) -> Result<(), HumidityError>;
+
+    /// 读测量结果
+    fn read<I: I2c>(&mut self, i2c: &mut I) -> Result<HumidityReading, HumidityError>;
+}
+
+/// 探测到的传感器
+#[derive(Debug, Clone, Copy)]
+pub enum AnyHumiditySensor {
+    Sht31(Sht31),
+    Aht20(Aht20),
+}
+
+impl HumiditySensor for AnyHumiditySensor {
+    fn name(&self) -> &'static str {
+        match self {
+            AnyHumiditySensor::Sht31(sensor) => sensor.name(),
+            AnyHumiditySensor::Aht20(sensor) => sensor.name(),
+        }
+    }
+
+    fn measurement_ms(&self) -> u32 {
+        match self {
+            AnyHumiditySensor::Sht31(sensor) => sensor.measurement_ms(),
+            AnyHumiditySensor::Aht20(sensor) => sensor.measurement_ms(),
+        }
+    }
+
+    fn start<I: I2c>(&mut self, i2c: &mut I) -> Result<(), HumidityError> {
+        match self {
+            AnyHumiditySensor::Sht31(sensor) => sensor.start(i2c),
+            AnyHumiditySensor::Aht20(sensor) => sensor.start(i2c),
+        }
+    }
+
+    fn read<I: I2c>(&mut self, i2c: &mut I) -> Result<HumidityReading, HumidityError> {
+        match self {
+            AnyHumiditySensor::Sht31(sensor) => sensor.read(i2c),
+            AnyHumiditySensor::Aht20(sensor) => sensor.read(i2c),
+        }
+    }
+}
+
+/// 按 SHT31、AHT20 的顺序探测，哪个都没有返回 None。AHT20 要初始化，会阻塞几十毫秒
+pub fn detect<I: I2c, T: DelayNs>(i2c: &mut I, timer: &mut T) -> Option<AnyHumiditySensor> {
+    let sensor = Sht31::probe(i2c)
+        .map(AnyHumiditySensor::Sht31)
+        .or_else(|| Aht20::probe(i2c, timer).map(AnyHumiditySensor::Aht20))?;
+    defmt::info!("humidity sensor: {=str}", sensor.name());
+    Some(sensor)
+}
+
+/// 露点，单位 0.01°C。用的是简化公式 `Td = T - (100 - RH) / 5`，全是整数，
+/// 相对湿度 50% 以上误差在 1°C 左右，再干就偏低，不过那时候也不用担心结露了
+pub fn dew_point_centi(temp_centi: i32, humidity_centi: i32) -> i32 {
+    temp_centi - (10_000 - humidity_centi.clamp(0, 10_000)) / 5
+}
+
+/// 按节奏测量，记住最近一次的读数
+#[derive(Debug, Clone, Copy)]
+pub struct HumidityMonitor<H> {
+    sensor: H,
+    latest: Option<HumidityReading>,
+    /// 已经 `start` 了，在等结果
+    measuring: bool,
+    /// 这次测量是不是 CRC 出错之后的重测
+    retried: bool,
+    /// 下次什么时候动(开始测量或者读结果)
+    next_ms: u64,
+}
+
+impl<H: HumiditySensor> HumidityMonitor<H> {
+    pub const fn new(sensor: H) -> Self {
+        Self {
+            sensor,
+            latest: None,
+            measuring: false,
+            retried: false,
+            next_ms: 0,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.sensor.name()
+    }
+
+    /// 到时间该动总线了
+    pub fn is_due(&self, now_ms: u64) -> bool {
+        now_ms >= self.next_ms
+    }
+
+    /// 到时间了就开始测量或者读结果，调用方保证总线这会儿是空的
+    pub fn service<I: I2c>(&mut self, i2c: &mut I, now_ms: u64) {
+        if !self.is_due(now_ms) {
+            return;
+        }
+        if !self.measuring {
+            self.start(i2c, now_ms);
+            return;
+        }
+        self.measuring = false;
+        match self.sensor.read(i2c) {
+            Ok(reading) => {
+                self.latest = Some(reading);
+                self.retried = false;
+                self.next_ms = now_ms + POLL_MS as u64;
+            }
+            Err(HumidityError::Busy) => {
+                self.measuring = true;
+                self.next_ms = now_ms + BUSY_RETRY_MS;
+            }
+            Err(HumidityError::Crc) if !self.retried => {
+                record_crc_error();
+                defmt::debug!("{=str} CRC mismatch, measuring again", self.name());
+                self.retried = true;
+                self.start(i2c, now_ms);
+            }
+            Err(err) => {
+                if err == HumidityError::Crc {
+                    record_crc_error();
+                }
+                defmt::warn!("{=str} read failed: {}", self.name(), err);
+                self.latest = None;
+                self.retried = false;
+                self.next_ms = now_ms + POLL_MS as u64;
+            }
+        }
+    }
+
+    fn start<I: I2c>(&mut self, i2c: &mut I, now_ms: u64) {
+        match self.sensor.start(i2c) {
+            Ok(()) => {
+                self.measuring = true;
+                self.next_ms = now_ms + self.sensor.measurement_ms() as u64;
+            }
+            Err(err) => {
+                defmt::warn!("{=str} start failed: {}", self.name(), err);
+                self.latest = None;
+                self.retried = false;
+                self.next_ms = now_ms + POLL_MS as u64;
+            }
+        }
+    }
+
+    pub fn latest(&self) -> Option<HumidityReading> {
+        self.latest
+    }
+
+    /// 温度，单位 0.01°C
+    pub fn temp_centi(&self) -> Option<i32> {
+        self.latest.map(|r| r.temp_centi)
+    }
+
+    /// 相对湿度，单位 0.01%
+    pub fn humidity_centi(&self) -> Option<i32> {
+        self.latest.map(|r| r.humidity_centi)
+    }
+}
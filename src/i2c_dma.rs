@@ -0,0 +1,394 @@
+//! 用 DMA 把显存发到 I2C 上，CPU 不用陪着等
+//!
+//! 400kHz 的 I2C 发一整屏 1024 字节要二十多毫秒，阻塞式 flush 期间 CPU 什么都干不了(USB 也没法 poll)。
+//! rp2040-hal 的 I2C 没有 DMA 支持，所以这里直接操作 I2C 寄存器：
+//!
+//! 1. 写好目标地址(IC_TAR)，打开 I2C 的 TX DMA 请求(IC_DMA_CR.TDMAE)
+//! 2. DMA 通道按 I2C0_TX/I2C1_TX 的 DREQ 节奏，把 16 位的"数据+控制位"一个个写进 IC_DATA_CMD
+//! 3. 最后一个字节带 STOP 位，硬件发完会自己产生停止条件
+//!
+//! IC_DATA_CMD 是 32 位寄存器，DMA 用 16 位宽写。RP2040 的 APB 总线会把窄写复制到 32 位的高低两半，
+//! 高 16 位在这个寄存器里都是保留位，所以没有副作用，换来的是暂存区只要 2 字节/像素字节。
+//!
+//! ## 双缓冲要求
+//!
+//! DMA 传输期间，被读取的内存绝对不能改，否则屏幕上会出现一半新一半旧的画面。这里的做法是：
+//! `start_flush` 先把脏区域从 `FrameBuffer` 拷到单独的暂存区(顺便编码成 IC_DATA_CMD 的格式)，
+//! DMA 只读暂存区。所以 `start_flush` 一返回，显存就可以继续画下一帧了；
+//! 暂存区在传输期间被移交给了 DMA 的 `Transfer` 对象，代码层面根本拿不到，也就不可能改到它。
+//!
+//! 同一时间只能有一个传输：上一帧没发完时再调 `start_flush` 会返回 `FlushError::Busy`，
+//! 主循环的用法是每一圈先 `poll_flush`，空闲了再画下一帧、再 `start_flush`。
+//! 普通命令(调亮度之类)走阻塞写，发之前会先等 DMA 发完，所以不会和 DMA 的数据搅在一起。
+//!
+//! 总线上的其他设备(比如 `bmp280`)也是一样：`Display::shared_bus` 只在 DMA 空闲的时候把 HAL 的 I2C 借出去，
+//! 正在发显存就返回 None，调用方下一圈再来。HAL 每次传输都会重新设目标地址，所以借出去用完不用恢复什么。
+
+use core::iter::once;
+use core::marker::PhantomData;
+use core::ops::Deref;
+
+use display_interface::{DataFormat, DisplayError, WriteOnlyDataCommand};
+use rp2040_hal::dma::single_buffer::{Config, Transfer};
+use rp2040_hal::dma::{ReadTarget, SingleChannel, WriteTarget};
+use rp2040_hal::i2c::I2C;
+use rp2040_hal::pac;
+
+use crate::display::Display;
+use crate::framebuffer::BUFFER_LEN;
+use crate::health::display_present;
+
+/// I2C 寄存器块
+type Block = pac::i2c0::RegisterBlock;
+
+/// SSD1306 的 I2C 控制字节：0x00 表示后面是命令，0x40 表示后面是显存数据
+const CONTROL_COMMAND: u8 = 0x00;
+const CONTROL_DATA: u8 = 0x40;
+
+/// IC_DATA_CMD 的 STOP 位
+const DATA_CMD_STOP: u16 = 1 << 9;
+
+/// 暂存区长度：一整屏数据加一个控制字节
+pub const STAGING_LEN: usize = BUFFER_LEN + 1;
+
+/// DMA 暂存区，要放在静态区(`cortex_m::singleton!`)，因为 DMA 要求 `'static`
+pub type StagingBuffer = [u16; STAGING_LEN];
+
+/// 能走 DMA 的 I2C 外设，区别只在 DREQ 编号
+pub trait DmaI2cBlock: Deref<Target = Block> {
+    /// TX 方向的 DREQ 编号(手册 DMA 章节的 DREQ 表)
+    const TX_DREQ: u8;
+
+    /// 寄存器块。HAL 的 I2C 对象拿着外设的所有权，这里只在 DMA 相关的寄存器上做它不会碰的操作
+    fn regs() -> &'static Block;
+}
+
+impl DmaI2cBlock for pac::I2C0 {
+    const TX_DREQ: u8 = 32;
+
+    fn regs() -> &'static Block {
+        unsafe { &*pac::I2C0::ptr() }
+    }
+}
+
+impl DmaI2cBlock for pac::I2C1 {
+    const TX_DREQ: u8 = 34;
+
+    fn regs() -> &'static Block {
+        unsafe { &*pac::I2C1::ptr() }
+    }
+}
+
+/// 暂存区 + 本次有效长度
+pub struct Staging {
+    words: &'static mut StagingBuffer,
+    len: usize,
+}
+
+// 安全性：传输期间 Staging 被 Transfer 持有，没人能拿到 &mut，地址和长度都不会变
+unsafe impl ReadTarget for Staging {
+    type ReceivedWord = u16;
+
+    fn rx_treq() -> Option<u8> {
+        None
+    }
+
+    fn rx_address_count(&self) -> (u32, u32) {
+        (self.words.as_ptr() as u32, self.len as u32)
+    }
+
+    fn rx_increment(&self) -> bool {
+        true
+    }
+}
+
+/// I2C 的发送 FIFO，作为 DMA 的写入目标
+pub struct TxFifo<B>(PhantomData<B>);
+
+// 安全性：IC_DATA_CMD 是固定地址的外设寄存器，地址永远有效
+unsafe impl<B: DmaI2cBlock> WriteTarget for TxFifo<B> {
+    type TransmittedWord = u16;
+
+    fn tx_treq() -> Option<u8> {
+        Some(B::TX_DREQ)
+    }
+
+    fn tx_address_count(&mut self) -> (u32, u32) {
+        (B::regs().ic_data_cmd().as_ptr() as u32, u32::MAX)
+    }
+
+    fn tx_increment(&self) -> bool {
+        false
+    }
+}
+
+/// DMA flush 的错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum FlushError {
+    /// 上一次传输还没结束
+    Busy,
+    /// 从机没有应答或者总线仲裁失败，值是 IC_TX_ABRT_SOURCE
+    Abort(u32),
+    /// 发送窗口命令的时候出错
+    Bus,
+}
+
+/// `poll_flush` 的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum FlushPoll {
+    /// 没有正在进行的传输
+    Idle,
+    /// 还在发
+    InProgress,
+    /// 刚刚发完(只会报告一次，之后变成 Idle)
+    Complete,
+}
+
+enum State<CH: SingleChannel, B: DmaI2cBlock> {
+    Idle(CH, Staging),
+    /// DMA 还在往 FIFO 里搬数据
+    Sending(Transfer<CH, Staging, TxFifo<B>>),
+    /// DMA 搬完了，但 FIFO 里的最后几个字节还在往总线上发
+    Draining(CH, Staging),
+}
+
+/// 支持 DMA flush 的 I2C 传输接口，阻塞命令走 HAL，显存数据走 DMA
+pub struct DmaI2c<B: DmaI2cBlock, P, CH: SingleChannel> {
+    i2c: I2C<B, P>,
+    address: u8,
+    // 只有在状态切换的一瞬间是 None
+    state: Option<State<CH, B>>,
+}
+
+impl<B, P, CH> DmaI2c<B, P, CH>
+where
+    B: DmaI2cBlock,
+    CH: SingleChannel,
+{
+    /// `address` 一般是 0x3C(有的模块是 0x3D)
+    pub fn new(i2c: I2C<B, P>, address: u8, channel: CH, staging: &'static mut StagingBuffer) -> Self {
+        Self {
+            i2c,
+            address,
+            state: Some(State::Idle(channel, Staging { words: staging, len: 0 })),
+        }
+    }
+
+    /// DMA 是否空闲
+    pub fn is_idle(&self) -> bool {
+        matches!(self.state, Some(State::Idle(..)))
+    }
+
+    /// 开始一次 DMA 传输，`bytes` 是要发的显存数据(不含控制字节)
+    fn start_transfer(&mut self, bytes: impl Iterator<Item = u8>) -> Result<(), FlushError> {
+        let (channel, mut staging) = match self.state.take() {
+            Some(State::Idle(channel, staging)) => (channel, staging),
+            other => {
+                self.state = other;
+                return Err(FlushError::Busy);
+            }
+        };
+
+        // 编码：控制字节 + 数据，最后一个字带 STOP
+        let mut len = 0;
+        for (slot, byte) in staging.words.iter_mut().zip(once(CONTROL_DATA).chain(bytes)) {
+            *slot = byte as u16;
+            len += 1;
+        }
+        staging.words[len - 1] |= DATA_CMD_STOP;
+        staging.len = len;
+        #[cfg(feature = "trace_i2c")]
+        {
+            use crate::i2c_trace::{log_write, PREVIEW_LEN};
+            let preview: [u8; PREVIEW_LEN] =
+                core::array::from_fn(|index| staging.words[1 + index] as u8);
+            let shown = PREVIEW_LEN.min(len - 1);
+            log_write(self.address, CONTROL_DATA, Some(len - 1), &preview[..shown]);
+        }
+
+        // 和 HAL 的 setup 一样：改目标地址要先关掉 I2C
+        let regs = B::regs();
+        regs.ic_enable().write(|w| w.enable().disabled());
+        regs.ic_tar().write(|w| unsafe { w.ic_tar().bits(self.address as u16) });
+        regs.ic_enable().write(|w| w.enable().enabled());
+        // FIFO 深度 16，低于 8 个就请求 DMA 继续填
+        regs.ic_dma_tdlr().write(|w| unsafe { w.dmatdl().bits(8) });
+        regs.ic_dma_cr().write(|w| w.tdmae().enabled());
+
+        let transfer = Config::new(channel, staging, TxFifo(PhantomData)).start();
+        self.state = Some(State::Sending(transfer));
+        Ok(())
+    }
+
+    /// 查询当前传输的进度，不会阻塞
+    pub fn poll(&mut self) -> Result<FlushPoll, FlushError> {
+        let (channel, staging) = match self.state.take() {
+            Some(State::Sending(transfer)) => {
+                if !transfer.is_done() {
+                    self.state = Some(State::Sending(transfer));
+                    return Ok(FlushPoll::InProgress);
+                }
+                let (channel, staging, _fifo) = transfer.wait();
+                (channel, staging)
+            }
+            Some(State::Draining(channel, staging)) => (channel, staging),
+            other => {
+                self.state = other;
+                return Ok(FlushPoll::Idle);
+            }
+        };
+
+        let regs = B::regs();
+        let raw = regs.ic_raw_intr_stat().read();
+        let result = if raw.tx_abrt().bit_is_set() {
+            let reason = regs.ic_tx_abrt_source().read().bits();
+            // 读 IC_CLR_TX_ABRT 清掉 abort 标志，FIFO 才会恢复工作
+            regs.ic_clr_tx_abrt().read();
+            Err(FlushError::Abort(reason))
+        } else if raw.stop_det().bit_is_set() {
+            Ok(FlushPoll::Complete)
+        } else {
+            self.state = Some(State::Draining(channel, staging));
+            return Ok(FlushPoll::InProgress);
+        };
+
+        regs.ic_clr_stop_det().read();
+        regs.ic_dma_cr().write(|w| w.tdmae().disabled());
+        self.state = Some(State::Idle(channel, staging));
+        result
+    }
+
+    /// 阻塞等到当前传输结束
+    pub fn wait_idle(&mut self) -> Result<(), FlushError> {
+        loop {
+            match self.poll()? {
+                FlushPoll::InProgress => cortex_m::asm::nop(),
+                FlushPoll::Idle | FlushPoll::Complete => return Ok(()),
+            }
+        }
+    }
+
+    /// 上一次 DMA 发出去的显存字节(不含控制字节)，从暂存区解码回来。正在发的时候是空的
+    #[cfg(feature = "panic-restore")]
+    pub(crate) fn sent_bytes(&self) -> impl Iterator<Item = u8> + '_ {
+        let staging = match &self.state {
+            Some(State::Idle(_, staging)) => staging.words.get(1..staging.len),
+            _ => None,
+        };
+        // 低 8 位是数据，最后一个字的 STOP 位在第 9 位
+        staging.into_iter().flatten().map(|&word| word as u8)
+    }
+
+    /// DMA 空闲的时候借出总线给别的 I2C 设备，正在发显存返回 None(不等)
+    pub fn bus(&mut self) -> Option<&mut I2C<B, P>> {
+        self.is_idle().then_some(&mut self.i2c)
+    }
+
+    /// 检测屏幕是否应答(见 `health::display_present`)，会先等 DMA 发完
+    pub fn probe(&mut self) -> bool {
+        if let Err(err) = self.wait_idle() {
+            defmt::warn!("dma flush failed before probe: {}", err);
+        }
+        display_present(&mut self.i2c, self.address)
+    }
+
+    /// 阻塞写：控制字节 + 内容
+    fn write_blocking(&mut self, control: u8, buf: DataFormat<'_>) -> Result<(), DisplayError> {
+        // 上一次 DMA 的结果在 poll_flush 里已经报告过了，这里只要保证总线空下来
+        if let Err(err) = self.wait_idle() {
+            defmt::warn!("dma flush failed before command: {}", err);
+        }
+        let address = self.address;
+        #[cfg(feature = "trace_i2c")]
+        crate::i2c_trace::log_format(address, control, &buf);
+        let result = match buf {
+            DataFormat::U8(bytes) => self
+                .i2c
+                .write_iter(address, once(control).chain(bytes.iter().copied())),
+            DataFormat::U8Iter(iter) => self.i2c.write_iter(address, once(control).chain(iter)),
+            _ => return Err(DisplayError::DataFormatNotImplemented),
+        };
+        result.map_err(|_| DisplayError::BusWriteError)
+    }
+}
+
+impl<B, P, CH> WriteOnlyDataCommand for DmaI2c<B, P, CH>
+where
+    B: DmaI2cBlock,
+    CH: SingleChannel,
+{
+    fn send_commands(&mut self, cmd: DataFormat<'_>) -> Result<(), DisplayError> {
+        self.write_blocking(CONTROL_COMMAND, cmd)
+    }
+
+    fn send_data(&mut self, buf: DataFormat<'_>) -> Result<(), DisplayError> {
+        self.write_blocking(CONTROL_DATA, buf)
+    }
+}
+
+impl<B, P, CH> Display<DmaI2c<B, P, CH>>
+where
+    B: DmaI2cBlock,
+    CH: SingleChannel,
+{
+    /// 是否有 DMA flush 正在进行
+    pub fn is_flushing(&self) -> bool {
+        !self.interface().is_idle()
+    }
+
+    /// 开始一次非阻塞 flush：设置窗口(阻塞，只有几个字节)，然后把脏区域交给 DMA，立刻返回
+    ///
+    /// 返回 `Ok(false)` 表示没有需要刷新的内容。返回之后显存就可以继续画了，见模块文档
+    pub fn start_flush(&mut self) -> Result<bool, FlushError> {
+        if self.is_flushing() {
+            return Err(FlushError::Busy);
+        }
+        let Some(region) = self.framebuffer_mut().take_dirty() else {
+            return Ok(false);
+        };
+        self.count_flush(region);
+
+        let result = self
+            .set_window(region)
+            .map_err(|_| FlushError::Bus)
+            .and_then(|()| {
+                let (interface, fb) = self.split_mut();
+                interface.start_transfer(fb.region_bytes(region))
+            });
+        if result.is_err() {
+            self.framebuffer_mut().mark_all_dirty();
+        }
+        #[cfg(feature = "panic-restore")]
+        self.stage_shadow(result.is_ok().then_some(region));
+        result.map(|()| true)
+    }
+
+    /// 借出总线给别的 I2C 设备(见模块文档)，正在 flush 返回 None
+    ///
+    /// 发完了但还没 `poll_flush` 过也算忙，所以要在 `poll_flush` 之后调
+    pub fn shared_bus(&mut self) -> Option<&mut I2C<B, P>> {
+        self.interface_mut().bus()
+    }
+
+    /// 查询 flush 进度。发送失败的话整屏标记为脏，下一次 `start_flush` 会重发
+    pub fn poll_flush(&mut self) -> Result<FlushPoll, FlushError> {
+        let result = self.interface_mut().poll();
+        if result.is_err() {
+            self.framebuffer_mut().mark_all_dirty();
+        }
+        // 发完了才算屏幕上有了这一帧，发失败的就扔掉
+        #[cfg(feature = "panic-restore")]
+        match result {
+            Ok(FlushPoll::Complete) => {
+                if let Some(region) = self.stage_shadow(None) {
+                    self.commit_shadow(region, self.interface().sent_bytes());
+                }
+            }
+            Err(_) => {
+                self.stage_shadow(None);
+            }
+            Ok(_) => {}
+        }
+        result
+    }
+}
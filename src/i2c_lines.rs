@@ -0,0 +1,208 @@
+//! I2C 线诊断：用一路 ADC 看 SCL 上的电平，估计上拉够不够
+//!
+//! 读数不稳、换了长线就扫不到设备，十有八九是上拉太弱(只靠片内上拉、上拉电阻太大、总线电容太大)：
+//! 上升沿拖得很慢，高电平的时候线还没爬到逻辑高，或者一大半时间卡在中间电平。这里在总线空闲和发数据的时候
+//! 各采一批 SCL 的电压，看三件事：
+//!
+//! - 空闲的时候有多高：不到 VDD 的 30% 说是 "stuck low"(没有上拉、有设备把线拉住了，或者分压点根本没接)，
+//!   不到 70%(I2C 的逻辑高门限)说是 "weak pull-up"
+//! - 发数据的时候最高到多少：到不了 70% 也是 "weak pull-up"
+//! - 发数据的时候有多少样本落在 30%~70% 之间：超过 `MID_WEAK_PERCENT` 说明边沿慢得厉害，也算 "weak pull-up"
+//!
+//! 接线：SCL 经过两个 100kΩ 分压接到 GP27(ADC1)，中点接 ADC，下面那个电阻接地，引脚见 board.rs 的 `scl_tap_adc_pin!`。
+//! 分压是为了上拉接到 5V 的屏幕模块也不会把 ADC 引脚烧了，读数乘 `TAP_DIVIDER` 就是线上的电压。
+//! 分压点不要加滤波电容。两个电阻对地一共 200kΩ，和外接的 2.2k~4.7kΩ 上拉比起来可以不管；
+//! 只靠片内上拉(50~80kΩ)的话高电平会被分掉两三成，会报 "weak pull-up"，本来也确实是弱的。
+//!
+//! 这只是个大概的指示，不能代替示波器：ADC 一次转换要 2µs，400kHz 的 SCL 一个周期才 2.5µs，
+//! 采到的是一堆随机时刻的电平，分压电阻和引脚电容还会再把边沿拖慢一点，所以中间电平的比例只当参考，门限放得比较宽。
+//! 看上升时间到底是多少、有没有振铃，还是要上示波器。
+//!
+//! 打开 `mic-vu` 的时候 ADC 一直在给话筒做连续转换(见 mic.rs)，腾不出来，诊断不可用。
+
+use core::fmt::{self, Write};
+
+use heapless::Vec;
+
+use crate::health::SCAN_MAX;
+use crate::sampler::{Sampler, SamplerPin};
+use crate::watchdog_feed::Checkpoint;
+
+/// 分压比：线上的电压是 ADC 读数的几倍
+pub const TAP_DIVIDER: u32 = 2;
+
+/// 逻辑电平按 3.3V 算(mV)
+const VDD_MV: u32 = 3300;
+
+/// 低于这个算低电平，I2C 的 VIL 是 0.3 VDD
+const LOW_MV: u32 = VDD_MV * 3 / 10;
+
+/// 高于这个算高电平，I2C 的 VIH 是 0.7 VDD
+const HIGH_MV: u32 = VDD_MV * 7 / 10;
+
+/// 空闲的时候采几个，发数据的时候采几个
+const IDLE_SAMPLES: usize = 32;
+const TRAFFIC_SAMPLES: usize = 256;
+
+/// 发数据的时候中间电平的样本超过百分之几算上拉太弱
+///
+/// 上拉正常的话上升沿只占周期的一小部分，随机采到中间电平的一般不到 15%
+pub const MID_WEAK_PERCENT: u8 = 40;
+
+/// 能读 SCL 分压点的 ADC，返回分压点上的电压(mV)，转换失败返回 None
+pub trait LineAdc {
+    fn tap_mv(&mut self) -> Option<u16>;
+}
+
+/// 借采样器的 ADC 读分压点引脚
+pub struct SclTap<'a, P, V, T> {
+    pub sampler: &'a mut Sampler<P, V>,
+    pub pin: &'a mut T,
+}
+
+impl<P, V, T> LineAdc for SclTap<'_, P, V, T>
+where
+    P: SamplerPin,
+    V: SamplerPin,
+    T: SamplerPin,
+{
+    fn tap_mv(&mut self) -> Option<u16> {
+        self.sampler.read_mv(self.pin)
+    }
+}
+
+/// 结论
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum LineVerdict {
+    Healthy,
+    /// 高电平不够高，或者边沿太慢
+    WeakPullUp,
+    /// 空闲的时候也是低电平
+    StuckLow,
+    /// ADC 一直转换失败
+    NoReading,
+}
+
+impl LineVerdict {
+    pub fn name(self) -> &'static str {
+        match self {
+            LineVerdict::Healthy => "ok",
+            LineVerdict::WeakPullUp => "weak pull-up",
+            LineVerdict::StuckLow => "stuck low",
+            LineVerdict::NoReading => "no reading",
+        }
+    }
+}
+
+/// 诊断结果，电压都是换算到线上的(mV)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct LineReport {
+    pub verdict: LineVerdict,
+    /// 空闲的时候的平均电压
+    pub idle_mv: u16,
+    /// 发数据的时候采到的最高电压，没发出去的话是 None
+    pub high_mv: Option<u16>,
+    /// 发数据的时候中间电平的样本占百分之几。没发出去或者一个低电平都没采到(看不出边沿)是 None
+    pub mid_percent: Option<u8>,
+}
+
+impl LineReport {
+    /// 一行的摘要，比如 `ok idle=3280mV high=3260mV mid=12%`，没测到的项不写
+    pub fn write_summary<W: Write>(&self, out: &mut W) -> fmt::Result {
+        out.write_str(self.verdict.name())?;
+        if self.verdict == LineVerdict::NoReading {
+            return Ok(());
+        }
+        write!(out, " idle={}mV", self.idle_mv)?;
+        if let Some(high) = self.high_mv {
+            write!(out, " high={}mV", high)?;
+        }
+        if let Some(mid) = self.mid_percent {
+            write!(out, " mid={}%", mid)?;
+        }
+        Ok(())
+    }
+}
+
+/// 一批样本的统计
+#[derive(Debug, Clone, Copy, Default)]
+struct Samples {
+    count: u32,
+    sum: u32,
+    max: u32,
+    low: u32,
+    mid: u32,
+}
+
+impl Samples {
+    fn collect<A: LineAdc>(adc: &mut A, n: usize) -> Self {
+        let mut samples = Self::default();
+        for _ in 0..n {
+            let Some(tap) = adc.tap_mv() else {
+                continue;
+            };
+            let mv = tap as u32 * TAP_DIVIDER;
+            samples.count += 1;
+            samples.sum += mv;
+            samples.max = samples.max.max(mv);
+            if mv < LOW_MV {
+                samples.low += 1;
+            } else if mv < HIGH_MV {
+                samples.mid += 1;
+            }
+        }
+        samples
+    }
+
+    fn mean(&self) -> u32 {
+        self.sum / self.count.max(1)
+    }
+}
+
+/// 诊断 SCL：先在空闲的时候采一批，再调 `start_traffic` 让总线上跑起数据(一般是让屏幕整屏发一帧)，
+/// 边发边采一批。`start_traffic` 返回 false 表示没发出去(比如屏幕掉线了)，那就只看空闲电平。
+///
+/// 调之前总线要是空闲的，`start_traffic` 发起的传输要在后台跑(DMA)，不然采的时候已经发完了。
+/// 空闲、发送两批采完各报一次检查点(见 `watchdog_feed`)。
+pub fn diagnose_i2c_lines<A: LineAdc>(
+    adc: &mut A,
+    checkpoint: &mut Checkpoint<'_>,
+    start_traffic: impl FnOnce() -> bool,
+) -> LineReport {
+    let idle = Samples::collect(adc, IDLE_SAMPLES);
+    checkpoint.reached();
+    let traffic = start_traffic().then(|| Samples::collect(adc, TRAFFIC_SAMPLES));
+    checkpoint.reached();
+    let traffic = traffic.filter(|samples| samples.count > 0);
+    let high_mv = traffic.map(|samples| samples.max.min(u16::MAX as u32) as u16);
+    let mid_percent = traffic
+        .filter(|samples| samples.low > 0)
+        .map(|samples| (samples.mid * 100 / samples.count) as u8);
+    let idle_mv = idle.mean();
+
+    let verdict = if idle.count == 0 {
+        LineVerdict::NoReading
+    } else if idle_mv < LOW_MV {
+        LineVerdict::StuckLow
+    } else if idle_mv < HIGH_MV
+        || high_mv.is_some_and(|high| (high as u32) < HIGH_MV)
+        || mid_percent.is_some_and(|mid| mid > MID_WEAK_PERCENT)
+    {
+        LineVerdict::WeakPullUp
+    } else {
+        LineVerdict::Healthy
+    };
+    LineReport {
+        verdict,
+        idle_mv: idle_mv.min(u16::MAX as u32) as u16,
+        high_mv,
+        mid_percent,
+    }
+}
+
+/// 一次总线检查的结果：扫到的地址和 SCL 诊断(诊断不可用是 None)，总线页面和串口 `SCAN` 用
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BusReport {
+    pub addresses: Vec<u8, SCAN_MAX>,
+    pub lines: Option<LineReport>,
+}
@@ -0,0 +1,76 @@
+//! I2C 发送日志：每次往屏幕写东西之前用 defmt 打一行，查屏幕不正常的时候看驱动到底发了什么
+//!
+//! 打开 `trace_i2c` feature 才编译进来，不开的话调用的地方也都包在 cfg 里，一点开销都没有。
+//! 一行是地址、命令还是数据(SSD1306 的控制字节 0x00/0x40)、长度、前 `PREVIEW_LEN` 个字节，比如
+//! `i2c 0x3c cmd len=3 [21, 00, 7f]`。初始化序列、设窗口、调亮度都是命令，显存是数据。
+//!
+//! 主循环用的 `DmaI2c` 在里面自己打(阻塞写和 DMA 开始之前各打一次)；
+//! panic 画面用的是 ssd1306 自带的 `I2CDisplayInterface`，在外面套一层 `TraceI2c`。
+//! 整屏 flush 一帧就是一行，但是 60fps 的时候 defmt 也够忙的，看命令的时候最好让画面停下来。
+
+use display_interface::{DataFormat, DisplayError, WriteOnlyDataCommand};
+
+/// 每次写打出来前几个字节
+pub const PREVIEW_LEN: usize = 8;
+
+/// SSD1306 的控制字节：0x00 后面是命令，0x40 后面是显存数据
+const CONTROL_COMMAND: u8 = 0x00;
+const CONTROL_DATA: u8 = 0x40;
+
+/// 打一行：`len` 是控制字节后面的长度，迭代器给的数据事先不知道多长，是 None
+pub fn log_write(address: u8, control: u8, len: Option<usize>, preview: &[u8]) {
+    let kind = if control == CONTROL_DATA {
+        "data"
+    } else {
+        "cmd"
+    };
+    let preview = &preview[..preview.len().min(PREVIEW_LEN)];
+    match len {
+        Some(len) => defmt::debug!(
+            "i2c {=u8:#04x} {=str} len={=usize} {=[u8]:02x}",
+            address,
+            kind,
+            len,
+            preview
+        ),
+        None => defmt::debug!("i2c {=u8:#04x} {=str} len=? (iter)", address, kind),
+    }
+}
+
+/// 一次写换成日志：切片直接看，迭代器不拆开(拆了就发不出去了)，只记一笔
+pub fn log_format(address: u8, control: u8, buf: &DataFormat<'_>) {
+    match buf {
+        DataFormat::U8(bytes) => log_write(address, control, Some(bytes.len()), bytes),
+        _ => log_write(address, control, None, &[]),
+    }
+}
+
+/// 在别的传输接口外面套一层，发之前先打日志，其他原样转交
+#[derive(Debug)]
+pub struct TraceI2c<DI> {
+    inner: DI,
+    /// 只是打日志用的，真正的地址在 `inner` 里
+    address: u8,
+}
+
+impl<DI> TraceI2c<DI> {
+    pub fn new(inner: DI, address: u8) -> Self {
+        Self { inner, address }
+    }
+
+    pub fn into_inner(self) -> DI {
+        self.inner
+    }
+}
+
+impl<DI: WriteOnlyDataCommand> WriteOnlyDataCommand for TraceI2c<DI> {
+    fn send_commands(&mut self, cmd: DataFormat<'_>) -> Result<(), DisplayError> {
+        log_format(self.address, CONTROL_COMMAND, &cmd);
+        self.inner.send_commands(cmd)
+    }
+
+    fn send_data(&mut self, buf: DataFormat<'_>) -> Result<(), DisplayError> {
+        log_format(self.address, CONTROL_DATA, &buf);
+        self.inner.send_data(buf)
+    }
+}
@@ -0,0 +1,317 @@
+//! 图片槽：串口分块传上来的 1bpp 图片存进 flash，重启以后还在，可以遥控显示，也可以当开机画面
+//!
+//! 串口上这样传(见 `console`)：
+//!
+//! ```text
+//! IMG BEGIN 1 64 32     # 往 1 号槽传一张 64x32 的图，太大的这一步就拒绝
+//! IMG DATA <base64>     # 图片数据分几行发，每行的 base64 长度要是 4 的倍数(最后一行可以带 =)
+//! IMG END 1a2b3c4d      # 整张图数据的 CRC-32(十六进制，和 zlib 的 crc32 一样)，对上了才写 flash
+//! IMG SHOW 1 32 16      # 在遥控显示页面的 (32,16) 画出 1 号槽的图
+//! ```
+//!
+//! 数据格式和 embedded-graphics 的 `ImageRaw<BinaryColor>` 一样：一行一行从上往下，每行从左往右，
+//! 一个字节 8 个像素、高位在左，1 是亮；每行凑整到字节，所以一张 w x h 的图是 `ceil(w/8) * h` 字节。
+//! 最大就是整屏(128x64 的屏是 1024 字节)。
+//!
+//! 传的过程中数据先攒在内存里，`END` 校验通过才擦写 flash，所以传到一半断了不会动到槽里原来的图。
+//! 这些情况整次上传作废，要从 `BEGIN` 重新来：数据比说好的多、base64 不对、CRC 或者长度对不上、
+//! 10 秒没收到下一行(`expire`)、中途又来了一个 `BEGIN`。
+//!
+//! flash 里每个槽占一个扇区(见 `flash`)：第 0 页是槽头(魔数、宽高、数据的 CRC、槽头自己的 CRC)，数据从第 1 页开始。
+//! 写的时候先擦扇区、写数据、最后写槽头，写到一半断电的话槽头是空的，这个槽就当成空的。
+//! 读的时候槽头和数据的 CRC 都要对上，不然当成坏的(`SlotState::Corrupt`)，不会画出一堆乱点。
+
+use core::fmt;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::Point;
+use embedded_graphics::image::{Image, ImageRaw};
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::Drawable;
+use heapless::Vec;
+
+use crate::crc::crc32;
+use crate::flash;
+use crate::framebuffer::{BUFFER_LEN, HEIGHT, WIDTH};
+
+/// 一共几个槽
+pub const SLOTS: usize = flash::IMAGE_SLOTS;
+
+/// 图片最宽、最高多少(整屏)
+pub const MAX_WIDTH: u16 = WIDTH as u16;
+pub const MAX_HEIGHT: u16 = HEIGHT as u16;
+
+/// 图片数据最多多少字节
+pub const MAX_IMAGE_BYTES: usize = BUFFER_LEN;
+
+/// 多久没收到下一块就作废(毫秒)
+pub const UPLOAD_TIMEOUT_MS: u64 = 10_000;
+
+/// 槽头开头的魔数 "IMG1"
+const MAGIC: [u8; 4] = *b"IMG1";
+
+/// 槽头：魔数 4 + 宽 2 + 高 2 + 数据 CRC 4 + 槽头 CRC 4，都是小端
+const HEADER_LEN: usize = 16;
+
+/// 数据在槽里从哪里开始(第 1 页)
+const DATA_OFFSET: u32 = flash::PAGE_LEN as u32;
+
+const _: () = assert!(flash::PAGE_LEN + MAX_IMAGE_BYTES <= flash::SECTOR_LEN);
+
+/// 一张 w x h 的图要多少字节
+pub const fn image_len(width: u16, height: u16) -> usize {
+    (width as usize).div_ceil(8) * height as usize
+}
+
+/// 上传出了什么问题
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum ImageError {
+    /// 没有在传(没 `BEGIN`，或者已经作废了)
+    NotUploading,
+    /// 宽或者高是 0
+    Empty,
+    /// 比整屏还大
+    TooLarge,
+    /// base64 解不开
+    Encoding,
+    /// 数据比说好的多
+    Overflow,
+    /// `END` 的时候数据还不够
+    Short { received: u16, expected: u16 },
+    /// CRC 对不上
+    BadCrc,
+}
+
+impl ImageError {
+    /// 给串口用的错误说明
+    pub fn message(self) -> &'static str {
+        match self {
+            ImageError::NotUploading => "no upload in progress, send IMG BEGIN first",
+            ImageError::Empty => "width and height must be at least 1",
+            ImageError::TooLarge => "image larger than the screen",
+            ImageError::Encoding => "invalid base64 (chunk length must be a multiple of 4)",
+            ImageError::Overflow => "more data than w x h, upload aborted",
+            ImageError::Short { .. } => "image data incomplete, upload aborted",
+            ImageError::BadCrc => "CRC mismatch, upload aborted",
+        }
+    }
+}
+
+/// 一个槽现在是什么状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum SlotState {
+    Empty,
+    /// 有东西但是校验不过(写到一半断电之类的)
+    Corrupt,
+    Image {
+        width: u16,
+        height: u16,
+    },
+}
+
+impl fmt::Display for SlotState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SlotState::Empty => f.write_str("empty"),
+            SlotState::Corrupt => f.write_str("corrupt"),
+            SlotState::Image { width, height } => write!(f, "{}x{}", width, height),
+        }
+    }
+}
+
+/// 存在 flash 里的一张图，数据直接从 XIP 读
+#[derive(Debug, Clone, Copy)]
+pub struct StoredImage {
+    pub width: u16,
+    pub height: u16,
+    data: &'static [u8],
+}
+
+impl StoredImage {
+    /// 左上角放在 `top_left` 画出来，画出屏幕的部分不管
+    pub fn draw<D>(&self, display: &mut D, top_left: Point) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        let raw = ImageRaw::<BinaryColor>::new(self.data, self.width as u32);
+        Image::new(&raw, top_left).draw(display)
+    }
+}
+
+fn slot_offset(slot: usize) -> u32 {
+    flash::IMAGE_OFFSET + (slot * flash::SECTOR_LEN) as u32
+}
+
+fn header_crc(header: &[u8]) -> u32 {
+    crc32(&header[..HEADER_LEN - 4])
+}
+
+/// 读第 `slot` 个槽(从 0 数)，空的、坏的、编号不对都返回 None
+pub fn load(slot: usize) -> Option<StoredImage> {
+    if slot >= SLOTS {
+        return None;
+    }
+    let header = flash::read(slot_offset(slot), HEADER_LEN);
+    let word = |at: usize| {
+        u32::from_le_bytes([header[at], header[at + 1], header[at + 2], header[at + 3]])
+    };
+    if header[..4] != MAGIC || word(12) != header_crc(header) {
+        return None;
+    }
+    let width = u16::from_le_bytes([header[4], header[5]]);
+    let height = u16::from_le_bytes([header[6], header[7]]);
+    if width == 0 || height == 0 || width > MAX_WIDTH || height > MAX_HEIGHT {
+        return None;
+    }
+    let data = flash::read(slot_offset(slot) + DATA_OFFSET, image_len(width, height));
+    (crc32(data) == word(8)).then_some(StoredImage {
+        width,
+        height,
+        data,
+    })
+}
+
+/// 第 `slot` 个槽(从 0 数)的状态
+pub fn state(slot: usize) -> SlotState {
+    if let Some(image) = load(slot) {
+        return SlotState::Image {
+            width: image.width,
+            height: image.height,
+        };
+    }
+    if slot < SLOTS
+        && flash::read(slot_offset(slot), HEADER_LEN)
+            .iter()
+            .all(|&byte| byte == 0xFF)
+    {
+        SlotState::Empty
+    } else {
+        SlotState::Corrupt
+    }
+}
+
+/// 擦掉整个槽，写数据，最后写槽头。擦写期间关中断，大概 50ms
+fn store(slot: usize, width: u16, height: u16, data: &[u8]) {
+    let base = slot_offset(slot);
+    flash::erase_sector(base);
+    for (i, chunk) in data.chunks(flash::PAGE_LEN).enumerate() {
+        let mut page = [0xFFu8; flash::PAGE_LEN];
+        page[..chunk.len()].copy_from_slice(chunk);
+        flash::program_page(base + DATA_OFFSET + (i * flash::PAGE_LEN) as u32, &page);
+    }
+    let mut header = [0u8; HEADER_LEN];
+    header[..4].copy_from_slice(&MAGIC);
+    header[4..6].copy_from_slice(&width.to_le_bytes());
+    header[6..8].copy_from_slice(&height.to_le_bytes());
+    header[8..12].copy_from_slice(&crc32(data).to_le_bytes());
+    let crc = header_crc(&header);
+    header[12..].copy_from_slice(&crc.to_le_bytes());
+    flash::program_bytes(base, &header);
+}
+
+/// 正在传的一张图
+struct Upload {
+    slot: usize,
+    width: u16,
+    height: u16,
+    data: Vec<u8, MAX_IMAGE_BYTES>,
+    last_ms: u64,
+}
+
+impl Upload {
+    fn expected(&self) -> usize {
+        image_len(self.width, self.height)
+    }
+}
+
+/// 串口上传的状态机，同一时间只传一张
+#[derive(Default)]
+pub struct ImageUpload {
+    current: Option<Upload>,
+}
+
+impl ImageUpload {
+    pub const fn new() -> Self {
+        Self { current: None }
+    }
+
+    /// 开始往第 `slot` 个槽(从 0 数)传一张 w x h 的图，正在传的那张作废
+    pub fn begin(
+        &mut self,
+        slot: usize,
+        width: u16,
+        height: u16,
+        now_ms: u64,
+    ) -> Result<(), ImageError> {
+        self.current = None;
+        if width == 0 || height == 0 {
+            return Err(ImageError::Empty);
+        }
+        if width > MAX_WIDTH || height > MAX_HEIGHT {
+            return Err(ImageError::TooLarge);
+        }
+        self.current = Some(Upload {
+            slot,
+            width,
+            height,
+            data: Vec::new(),
+            last_ms: now_ms,
+        });
+        Ok(())
+    }
+
+    /// 收一块 base64 数据，返回一共收了多少字节。出错整次作废
+    pub fn data(&mut self, chunk: &str, now_ms: u64) -> Result<usize, ImageError> {
+        let result = self.append(chunk, now_ms);
+        if result.is_err() {
+            self.current = None;
+        }
+        result
+    }
+
+    fn append(&mut self, chunk: &str, now_ms: u64) -> Result<usize, ImageError> {
+        let upload = self.current.as_mut().ok_or(ImageError::NotUploading)?;
+        // 一行命令最多一百多字节 base64，解出来不到 120 字节
+        let mut decoded = [0u8; 120];
+        let len = STANDARD
+            .decode_slice(chunk, &mut decoded)
+            .map_err(|_| ImageError::Encoding)?;
+        if upload.data.len() + len > upload.expected() {
+            return Err(ImageError::Overflow);
+        }
+        // 上面比过了，expected 不会超过容量
+        let _ = upload.data.extend_from_slice(&decoded[..len]);
+        upload.last_ms = now_ms;
+        Ok(upload.data.len())
+    }
+
+    /// 收完了：长度和 CRC 都对就写进 flash，返回写到了哪个槽。不管成不成，这次上传都结束了
+    pub fn end(&mut self, crc: u32) -> Result<usize, ImageError> {
+        let upload = self.current.take().ok_or(ImageError::NotUploading)?;
+        if upload.data.len() != upload.expected() {
+            return Err(ImageError::Short {
+                received: upload.data.len() as u16,
+                expected: upload.expected() as u16,
+            });
+        }
+        if crc32(&upload.data) != crc {
+            return Err(ImageError::BadCrc);
+        }
+        store(upload.slot, upload.width, upload.height, &upload.data);
+        Ok(upload.slot)
+    }
+
+    /// 太久没收到数据就作废，作废了返回 true(只返回一次)
+    pub fn expire(&mut self, now_ms: u64) -> bool {
+        let stale = self
+            .current
+            .as_ref()
+            .is_some_and(|upload| now_ms.saturating_sub(upload.last_ms) >= UPLOAD_TIMEOUT_MS);
+        if stale {
+            self.current = None;
+        }
+        stale
+    }
+}
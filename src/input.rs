@@ -0,0 +1,169 @@
+//! 按键输入：消抖，然后变成按下/松开事件
+//!
+//! 按键都是一端接引脚、一端接地，引脚配成上拉输入，所以按下是低电平。
+//! 哪个按键接哪个引脚在 board.rs 里配。
+
+use embedded_hal::digital::InputPin;
+use rp2040_hal::gpio::{DynPinId, FunctionSioInput, Interrupt, Pin, PullUp};
+
+/// 按键引脚，统一成动态引脚类型，这样几个按键可以放进一个数组
+pub type ButtonPin = Pin<DynPinId, FunctionSioInput, PullUp>;
+
+/// 电平保持多久不变才算数(毫秒)，普通轻触开关 20ms 足够了
+pub const DEBOUNCE_MS: u64 = 20;
+
+/// 按住多久算长按(毫秒)
+pub const LONG_PRESS_MS: u64 = 1000;
+
+/// 按键
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Button {
+    Up,
+    Down,
+    Select,
+    Back,
+}
+
+/// 消抖之后的按键事件
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum ButtonEvent {
+    Pressed(Button),
+    Released(Button),
+    /// 按住超过 `LONG_PRESS_MS`，一次按下只发一次。在这之前已经发过 `Pressed` 了
+    LongPress(Button),
+}
+
+/// 单个按键的消抖器：原始电平变化之后要稳定 `DEBOUNCE_MS` 才会改变输出
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Debouncer {
+    stable: bool,
+    raw: bool,
+    raw_since_ms: u64,
+    /// 这次按下已经报过长按了
+    long_reported: bool,
+}
+
+impl Debouncer {
+    pub const fn new() -> Self {
+        Self {
+            stable: false,
+            raw: false,
+            raw_since_ms: 0,
+            long_reported: false,
+        }
+    }
+
+    /// 喂一次原始读数，稳定状态变了就返回新的状态
+    pub fn update(&mut self, pressed: bool, now_ms: u64) -> Option<bool> {
+        if pressed != self.raw {
+            self.raw = pressed;
+            self.raw_since_ms = now_ms;
+            return None;
+        }
+        if self.raw != self.stable && now_ms.saturating_sub(self.raw_since_ms) >= DEBOUNCE_MS {
+            self.stable = self.raw;
+            return Some(self.stable);
+        }
+        None
+    }
+
+    /// 当前(消抖后)是否按着
+    pub fn is_pressed(&self) -> bool {
+        self.stable
+    }
+
+    /// 按住的时间刚超过 `LONG_PRESS_MS` 的时候返回一次 true，松开之后重新计
+    pub fn long_press(&mut self, now_ms: u64) -> bool {
+        if !self.stable {
+            self.long_reported = false;
+            return false;
+        }
+        // 按下的时刻就是原始电平变化的时刻，之后一直没变过
+        let held_ms = now_ms.saturating_sub(self.raw_since_ms);
+        if self.long_reported || held_ms < LONG_PRESS_MS {
+            return false;
+        }
+        self.long_reported = true;
+        true
+    }
+
+    /// 松开而且电平稳定，不需要再轮询了
+    pub fn is_settled(&self) -> bool {
+        !self.stable && !self.raw
+    }
+}
+
+/// 一组按键
+pub struct ButtonPad<const N: usize> {
+    buttons: [(Button, ButtonPin, Debouncer); N],
+}
+
+impl<const N: usize> ButtonPad<N> {
+    /// 引脚用 `button_pins!` 取
+    pub fn new(pins: [(Button, ButtonPin); N]) -> Self {
+        Self {
+            buttons: pins.map(|(button, pin)| (button, pin, Debouncer::new())),
+        }
+    }
+
+    /// 读一遍所有按键，每个状态变化调用一次 `on_event`。主循环里每一圈都调
+    pub fn poll(&mut self, now_ms: u64, mut on_event: impl FnMut(ButtonEvent)) {
+        for (button, pin, debouncer) in self.buttons.iter_mut() {
+            let pressed = pin.is_low().unwrap_or(false);
+            match debouncer.update(pressed, now_ms) {
+                Some(true) => on_event(ButtonEvent::Pressed(*button)),
+                Some(false) => on_event(ButtonEvent::Released(*button)),
+                None => {}
+            }
+            if debouncer.long_press(now_ms) {
+                on_event(ButtonEvent::LongPress(*button));
+            }
+        }
+    }
+
+    /// 所有按键都松开而且消抖完了。没松开的时候要一直轮询，不然消抖和长按都没法算
+    pub fn is_idle(&self) -> bool {
+        self.buttons
+            .iter()
+            .all(|(_, _, debouncer)| debouncer.is_settled())
+    }
+
+    /// 直接读引脚，`button` 现在是不是按着(不消抖)。没有这个键返回 false
+    pub fn is_down_raw(&mut self, button: Button) -> bool {
+        self.buttons
+            .iter_mut()
+            .find(|(which, _, _)| *which == button)
+            .is_some_and(|(_, pin, _)| pin.is_low().unwrap_or(false))
+    }
+
+    /// `button` 接在哪个 GPIO，没有这个键返回 None
+    pub fn gpio(&self, button: Button) -> Option<u8> {
+        self.buttons
+            .iter()
+            .find(|(which, _, _)| *which == button)
+            .map(|(_, pin, _)| pin.id().num)
+    }
+
+    /// 按下按键(下降沿)的时候触发 IO_IRQ_BANK0，睡眠的时候用来唤醒 CPU
+    pub fn set_wake_on_press(&mut self, enabled: bool) {
+        for (_, pin, _) in self.buttons.iter_mut() {
+            pin.clear_interrupt(Interrupt::EdgeLow);
+            pin.set_interrupt_enabled(Interrupt::EdgeLow, enabled);
+        }
+    }
+
+    /// 只让 `button` 按下(下降沿)把芯片从 DORMANT 叫醒，别的按键都不行。见 `power_off`
+    pub fn set_dormant_wake(&mut self, button: Button) {
+        for (which, pin, _) in self.buttons.iter_mut() {
+            pin.clear_interrupt(Interrupt::EdgeLow);
+            pin.set_dormant_wake_enabled(Interrupt::EdgeLow, *which == button);
+        }
+    }
+
+    /// 清掉按键的唤醒中断标志
+    pub fn clear_wake(&mut self) {
+        for (_, pin, _) in self.buttons.iter_mut() {
+            pin.clear_interrupt(Interrupt::EdgeLow);
+        }
+    }
+}
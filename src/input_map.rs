@@ -0,0 +1,76 @@
+//! 按键映射表：哪个按键、哪个手势直接触发一个全局动作，不交给页面
+//!
+//! 以前长按 Back 进床头钟、双击 Back 回演示菜单是写死在主循环的按键处理里的，
+//! 现在都登记在一张表里(main.rs 的 `INPUT_MAP`)，一条是"输入 -> `Action`"。主循环每收到一个按键事件、
+//! 每识别出一个手势先查表：查到了就把动作排进队列，这次输入不再交给页面；查不到照旧发给当前页面。
+//! 排队的动作和页面交出来的动作(`Scheduler::take_action`)在同一个地方处理，所以页面也能直接交出这些动作，
+//! 比如菜单里放一项 "sleep" 返回 `Action::EnterSleepClock`。
+//!
+//! 改按键的话只改表：换成别的键、别的手势，或者加一条。某一页自己要用这个输入的(比如闹钟响的时候长按 Back 是关闹钟)，
+//! 在那一条的 `unless` 里写上那一页，它在最上面的时候这条不生效。
+//!
+//! 加一个新动作：
+//!
+//! 1. `app::Action` 加一个变体
+//! 2. main.rs 处理动作的 `match` 里加一个分支(编译器会提示)
+//! 3. `INPUT_MAP` 里加一条绑到某个输入上(`InputMap` 的长度跟着改)
+//!
+//! 只查表不改表，表是编译的时候定的，不存 flash。
+
+use crate::app::{Action, PageId};
+use crate::gesture::Gesture;
+use crate::input::ButtonEvent;
+
+/// 一次输入：消抖后的按键事件，或者识别出来的手势，都是重映射以后的(见 `remap`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum InputEvent {
+    Button(ButtonEvent),
+    Gesture(Gesture),
+    /// 关机键按满了，哪个键是关机键看按键重映射
+    PowerHold,
+}
+
+/// 表里的一条
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Binding {
+    pub input: InputEvent,
+    pub action: Action,
+    /// 这一页在最上面的时候这条不生效，输入照旧交给页面
+    pub unless: Option<PageId>,
+}
+
+impl Binding {
+    pub const fn new(input: InputEvent, action: Action) -> Self {
+        Self {
+            input,
+            action,
+            unless: None,
+        }
+    }
+
+    /// `page` 在最上面的时候不生效
+    pub const fn unless(mut self, page: PageId) -> Self {
+        self.unless = Some(page);
+        self
+    }
+}
+
+/// 按键映射表，同一个输入有好几条的话用前面那条
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputMap<const N: usize> {
+    bindings: [Binding; N],
+}
+
+impl<const N: usize> InputMap<N> {
+    pub const fn new(bindings: [Binding; N]) -> Self {
+        Self { bindings }
+    }
+
+    /// 查表：`current` 是现在最上面的页面
+    pub fn lookup(&self, input: InputEvent, current: PageId) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|binding| binding.input == input && binding.unless != Some(current))
+            .map(|binding| binding.action)
+    }
+}
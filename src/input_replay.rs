@@ -0,0 +1,176 @@
+//! 录一段输入、原样放出来：无人值守的演示，或者复现偶尔才出现的界面问题
+//!
+//! 录的是重映射和手势识别之后、交给按键映射表和页面之前的 `InputEvent`(见 main.rs 的 `route_inputs`)，
+//! 每个带着从开始录算起的毫秒数。放的时候按主循环的定时器对时间，到点的塞回同一个入口，
+//! 按键映射、页面看到的和真按的时候一样。串口命令是 `REC START`/`REC STOP`/`REC PLAY [LOOP]`。
+//!
+//! - 最多录 `CAPACITY` 个(按一下键是按下、松开、短按手势三个，大概 40 下)，满了后面的不录，`overflowed` 返回 true
+//! - 录的东西只在内存里，重启就没了
+//! - 放的时候真按键照样有用，两边的输入交错着进同一个入口，谁也不让谁：放到一半按一下，
+//!   页面可能就不在录的时候那一页了，后面放出来的键按在别的页面上。要原样复现就别碰
+//! - 放之前不会自己回到录的时候那一页，先自己切过去，或者录的时候从一个固定的地方(比如先双击 Back 进演示菜单)开始
+
+use heapless::Vec;
+
+use crate::input_map::InputEvent;
+
+/// 最多录多少个输入
+pub const CAPACITY: usize = 128;
+
+/// 录下来的一个输入
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Recorded {
+    /// 从开始录过了多少毫秒
+    pub at_ms: u32,
+    pub input: InputEvent,
+}
+
+/// 录输入
+#[derive(Debug, Clone, Default)]
+pub struct Recorder {
+    events: Vec<Recorded, CAPACITY>,
+    /// 在录的话是什么时候开始的
+    started_ms: Option<u64>,
+    /// 停下来的时候一共录了多久，循环放的时候最后一个输入之后还要等这么久
+    duration_ms: u32,
+    overflowed: bool,
+}
+
+impl Recorder {
+    pub const fn new() -> Self {
+        Self {
+            events: Vec::new(),
+            started_ms: None,
+            duration_ms: 0,
+            overflowed: false,
+        }
+    }
+
+    /// 清掉以前录的，从现在开始录
+    pub fn start(&mut self, now_ms: u64) {
+        self.events.clear();
+        self.started_ms = Some(now_ms);
+        self.duration_ms = 0;
+        self.overflowed = false;
+    }
+
+    /// 停下来，返回刚才在不在录
+    pub fn stop(&mut self, now_ms: u64) -> bool {
+        let Some(started) = self.started_ms.take() else {
+            return false;
+        };
+        self.duration_ms = elapsed(started, now_ms);
+        true
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.started_ms.is_some()
+    }
+
+    /// 在录的话记一个，满了就扔掉
+    pub fn record(&mut self, input: InputEvent, now_ms: u64) {
+        let Some(started) = self.started_ms else {
+            return;
+        };
+        let entry = Recorded {
+            at_ms: elapsed(started, now_ms),
+            input,
+        };
+        if self.events.push(entry).is_err() {
+            self.overflowed = true;
+        }
+    }
+
+    pub fn events(&self) -> &[Recorded] {
+        &self.events
+    }
+
+    /// 一共录了多久(毫秒)，还在录的话是 0
+    pub fn duration_ms(&self) -> u32 {
+        self.duration_ms
+    }
+
+    /// 有没有因为满了扔掉过
+    pub fn overflowed(&self) -> bool {
+        self.overflowed
+    }
+}
+
+/// 按录的时候的间隔把输入放出来
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Player {
+    /// 在放的话是什么时候开始这一遍的
+    started_ms: Option<u64>,
+    /// 下一个该放第几个
+    next: usize,
+    looping: bool,
+}
+
+impl Player {
+    pub const fn new() -> Self {
+        Self {
+            started_ms: None,
+            next: 0,
+            looping: false,
+        }
+    }
+
+    /// 从头开始放，`looping` 是放完了接着从头放
+    pub fn start(&mut self, now_ms: u64, looping: bool) {
+        self.started_ms = Some(now_ms);
+        self.next = 0;
+        self.looping = looping;
+    }
+
+    pub fn stop(&mut self) {
+        self.started_ms = None;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.started_ms.is_some()
+    }
+
+    /// 把到点了的输入一个一个交给 `emit`。放完了(不循环的话)自己停下，返回 true
+    pub fn poll(
+        &mut self,
+        recording: &Recorder,
+        now_ms: u64,
+        mut emit: impl FnMut(InputEvent),
+    ) -> bool {
+        let Some(started) = self.started_ms else {
+            return false;
+        };
+        let events = recording.events();
+        let elapsed = elapsed(started, now_ms);
+        while let Some(entry) = events.get(self.next).filter(|entry| entry.at_ms <= elapsed) {
+            emit(entry.input);
+            self.next += 1;
+        }
+        if self.next < events.len() {
+            return false;
+        }
+        if !self.looping || events.is_empty() {
+            self.started_ms = None;
+            return true;
+        }
+        // 最后一个放完了，还要等到录的时候按停的那一刻才算一遍，下一遍从下一次调用开始放
+        let length = recording
+            .duration_ms()
+            .max(events.last().map_or(0, |entry| entry.at_ms)) as u64;
+        if (elapsed as u64) >= length {
+            // 主循环卡了好几遍那么久的话不补，从现在重新开始
+            let next = started + length.max(1);
+            self.started_ms = Some(if now_ms.saturating_sub(next) >= length {
+                now_ms
+            } else {
+                next
+            });
+            self.next = 0;
+        }
+        false
+    }
+}
+
+fn elapsed(started_ms: u64, now_ms: u64) -> u32 {
+    now_ms.saturating_sub(started_ms).min(u32::MAX as u64) as u32
+}
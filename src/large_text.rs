@@ -0,0 +1,196 @@
+//! 大字模式：离远了也看得清，`SETTINGS LARGE ON` 或者设置菜单里的 "large text" 打开，存在设置里，马上生效
+//!
+//! 打开以后调度器不调页面的 `render`，改调 `Page::render_large`：页面只画一两个最要紧的读数，
+//! 用 `LARGE_FONT`(9x18 粗体)或者七段大数字，`draw_large_readings` 是现成的两行排版。
+//! 没写大字版的页面(`render_large` 返回 false)照常画在一块临时的显存上，再放大两倍贴出来(`Zoom2x`)，
+//! 一屏只放得下四分之一，长按 Up/Down 换到上一块/下一块(左上、右上、左下、右下)，换页以后回到左上。
+//!
+//! 小窗口(弹窗)没有大字版的话不放大，照原样盖在上面，放大了就只剩一角了。
+//! 页面上面盖的东西只剩告警图标和提示条，低电量图标、轮播进度条都不画。
+
+use core::convert::Infallible;
+
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::{OriginDimensions, Point, Size};
+use embedded_graphics::mono_font::ascii::{FONT_6X10, FONT_9X18_BOLD};
+use embedded_graphics::mono_font::{MonoFont, MonoTextStyle};
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::text::Text;
+use embedded_graphics::{Drawable, Pixel};
+
+use crate::app::{Action, Canvas, Event, Page, Transition};
+use crate::framebuffer::FrameBuffer;
+use crate::input::{Button, ButtonEvent};
+use crate::text::{draw_centered, draw_right_aligned, text_pixel_width};
+
+/// 大字版用的字体
+pub const LARGE_FONT: MonoFont<'static> = FONT_9X18_BOLD;
+
+/// 放大了以后一屏分成几块
+pub const QUADRANTS: u8 = 4;
+
+/// 两行读数的基线
+const READING_Y: [i32; 2] = [26, 56];
+
+/// 只有一行读数的时候名字和读数的基线
+const SINGLE_LABEL_Y: i32 = 24;
+const SINGLE_VALUE_Y: i32 = 50;
+
+/// 把画上来的东西放大两倍再画到 `target` 上，`origin` 这一点对到 `target` 的左上角
+///
+/// 尺寸和 `target` 一样，页面照常排版，只有 `origin` 开始的那四分之一能看到，其他的画出去了直接丢掉
+pub struct Zoom2x<'a, D> {
+    target: &'a mut D,
+    origin: Point,
+}
+
+impl<'a, D> Zoom2x<'a, D>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    pub fn new(target: &'a mut D, origin: Point) -> Self {
+        Self { target, origin }
+    }
+}
+
+impl<D> OriginDimensions for Zoom2x<'_, D>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    fn size(&self) -> Size {
+        self.target.bounding_box().size
+    }
+}
+
+impl<D> DrawTarget for Zoom2x<'_, D>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    type Color = BinaryColor;
+    type Error = D::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let area = self.target.bounding_box();
+        for Pixel(point, color) in pixels {
+            let scaled = (point - self.origin) * 2;
+            if area.contains(scaled) {
+                self.target
+                    .fill_solid(&Rectangle::new(scaled, Size::new(2, 2)), color)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 第 `quadrant` 块(0 是左上，按行往后数)的左上角，`size` 是整屏的大小
+pub fn quadrant_origin(quadrant: u8, size: Size) -> Point {
+    let quadrant = (quadrant % QUADRANTS) as u32;
+    Point::new(
+        (quadrant % 2 * size.width / 2) as i32,
+        (quadrant / 2 * size.height / 2) as i32,
+    )
+}
+
+/// 把 `source` 的第 `quadrant` 块放大两倍画到 `canvas` 上，只画亮的点(画布是清过的)
+pub fn draw_zoomed(source: &FrameBuffer, quadrant: u8, canvas: &mut Canvas) {
+    let size = source.size();
+    let origin = quadrant_origin(quadrant, size);
+    let (width, height) = (size.width / 2, size.height / 2);
+    let lit = (0..height).flat_map(|dy| {
+        (0..width).filter_map(move |dx| {
+            let (x, y) = (origin.x as u32 + dx, origin.y as u32 + dy);
+            source
+                .pixel(x, y)
+                .then_some(Pixel(Point::new(x as i32, y as i32), BinaryColor::On))
+        })
+    });
+    let Ok(()) = Zoom2x::new(canvas, origin).draw_iter(lit);
+}
+
+/// 大字版的读数：一行一个 "名字 ... 读数"，最多两行，多的不画
+///
+/// 名字放不下的时候只画读数(右对齐)，所以名字尽量短。只有一行的时候名字、读数各占一行，居中
+pub fn draw_large_readings<D>(display: &mut D, readings: &[(&str, &str)]) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let style = MonoTextStyle::new(&LARGE_FONT, BinaryColor::On);
+    if let [(label, value)] = readings {
+        draw_centered(display, label, SINGLE_LABEL_Y, style)?;
+        return draw_centered(display, value, SINGLE_VALUE_Y, style);
+    }
+    let width = display.bounding_box().size.width;
+    let gap = LARGE_FONT.character_size.width;
+    for (&(label, value), y) in readings.iter().zip(READING_Y) {
+        let used =
+            text_pixel_width(label, &LARGE_FONT) + gap + text_pixel_width(value, &LARGE_FONT);
+        if used <= width {
+            Text::new(label, Point::new(0, y), style).draw(display)?;
+        }
+        draw_right_aligned(display, value, width as i32 - 1, y, style)?;
+    }
+    Ok(())
+}
+
+/// 设置菜单里的开关页面：显示现在开着还是关着，Select 切换并保存(`Action::SaveLargeText`)，Back 回去
+#[derive(Debug)]
+pub struct LargeTextPage {
+    enabled: bool,
+    pending: Option<Action>,
+}
+
+impl LargeTextPage {
+    /// `enabled` 是设置里存的
+    pub const fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            pending: None,
+        }
+    }
+
+    fn draw<D>(&self, display: &mut D, font: &MonoFont<'_>) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        let style = MonoTextStyle::new(font, BinaryColor::On);
+        let state = if self.enabled { "ON" } else { "OFF" };
+        draw_centered(display, "large text", SINGLE_LABEL_Y, style)?;
+        draw_centered(display, state, SINGLE_VALUE_Y, style)
+    }
+}
+
+impl Page for LargeTextPage {
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        match event {
+            Event::Button(ButtonEvent::Pressed(Button::Back)) => return Transition::Pop,
+            Event::Button(ButtonEvent::Pressed(Button::Select)) => {
+                self.enabled = !self.enabled;
+                self.pending = Some(Action::SaveLargeText(self.enabled));
+            }
+            Event::LargeText(enabled) => self.enabled = *enabled,
+            _ => {}
+        }
+        Transition::None
+    }
+
+    fn desired_fps(&self) -> u16 {
+        1
+    }
+
+    fn take_action(&mut self) -> Option<Action> {
+        self.pending.take()
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<(), Infallible> {
+        self.draw(canvas, &FONT_6X10)
+    }
+
+    fn render_large(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<bool, Infallible> {
+        self.draw(canvas, &LARGE_FONT)?;
+        Ok(true)
+    }
+}
@@ -0,0 +1,170 @@
+//! rp2040 + ssd1306 OLED 固件的公共模块
+//!
+//! main.rs 只负责外设初始化和把各个功能串起来，真正的显示、文字排版等逻辑都放在这里，
+//! 这样功能多起来以后 main.rs 也不会变成一坨。
+// 单元测试在主机上跑(`cargo test --target host-tuple`，见 README)，要用 std
+#![cfg_attr(not(test), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+pub mod adc_scan;
+pub mod aht20;
+pub mod alarm_page;
+pub mod alarms;
+pub mod alert;
+pub mod analog_clock;
+pub mod app;
+pub mod auto_brightness;
+pub mod banner;
+pub mod baro_page;
+pub mod battery;
+pub mod blink_code;
+pub mod bmp280;
+pub mod board;
+pub mod boards;
+pub mod boot_loop;
+pub mod boot_mode;
+pub mod boot_progress;
+pub mod bouncing_ball;
+pub mod burn_in;
+pub mod bus_page;
+pub mod buzzer;
+pub mod calibration;
+pub mod calibration_page;
+pub mod capabilities;
+pub mod carousel;
+pub mod carousel_page;
+pub mod clip;
+pub mod comfort_page;
+pub mod command;
+pub mod compositor;
+pub mod confirm;
+pub mod console;
+pub mod cpu_load;
+pub mod crc;
+pub mod dashboard;
+pub mod datalog;
+pub mod demo_auto;
+#[cfg(feature = "devtools")]
+pub mod devtools;
+pub mod diagnostics;
+pub mod dim_page;
+pub mod dimming;
+pub mod display;
+pub mod display_link;
+pub mod display_profile;
+pub mod distance_page;
+pub mod ds3231;
+pub mod event_log;
+pub mod event_page;
+pub mod fft;
+pub mod fixture;
+pub mod filter;
+pub mod flash;
+pub mod framebuffer;
+pub mod gesture;
+#[cfg(all(test, feature = "golden"))]
+pub mod golden;
+pub mod gray;
+pub mod gray_page;
+pub mod health;
+pub mod heap;
+pub mod host_status;
+pub mod humidity;
+pub mod i2c_dma;
+pub mod i2c_lines;
+#[cfg(feature = "trace_i2c")]
+pub mod i2c_trace;
+pub mod image_slots;
+pub mod input;
+pub mod input_map;
+pub mod input_replay;
+pub mod large_text;
+pub mod life;
+pub mod log_page;
+pub mod logo;
+pub mod low_voltage;
+pub mod maze;
+#[cfg(feature = "mic-vu")]
+pub mod mic;
+pub mod mode;
+pub mod net_console;
+pub mod outputs;
+#[cfg(feature = "overlay-layer")]
+pub mod overlay_layer;
+pub mod panel;
+pub mod panel_care;
+pub mod panic_screen;
+pub mod perf;
+pub mod perf_page;
+pub mod periodic;
+pub mod popup;
+pub mod power_off;
+pub mod preflight;
+#[cfg(feature = "devtools")]
+pub mod preview_page;
+pub mod profile_page;
+pub mod pulse_counter;
+pub mod pulse_page;
+pub mod reaction;
+pub mod reaction_page;
+pub mod reader_page;
+#[cfg(test)]
+pub mod recording;
+pub mod remap;
+pub mod remap_page;
+pub mod remote_page;
+pub mod retry;
+pub mod rng;
+#[cfg(feature = "no-xosc")]
+pub mod rosc_clock;
+pub mod sampler;
+pub mod screen;
+pub mod screen_timeout;
+pub mod sensors;
+pub mod servo;
+pub mod servo_page;
+pub mod settings;
+pub mod settings_menu;
+#[cfg(feature = "panic-restore")]
+pub mod shadow_frame;
+pub mod shared;
+pub mod sht31;
+pub mod sleep_clock;
+pub mod snapshot;
+pub mod spectrum_page;
+pub mod splash_page;
+pub mod sprite;
+pub mod stack;
+pub mod starfield;
+pub mod status;
+pub mod status_led;
+pub mod system_info;
+pub mod telemetry;
+pub mod text;
+pub mod text_field;
+pub mod theme;
+pub mod theme_page;
+pub mod timestep;
+pub mod timezone;
+pub mod timezone_page;
+pub mod tone;
+pub mod transition;
+pub mod trig;
+pub mod typewriter;
+pub mod usb;
+pub mod vl53l0x;
+pub mod vu_meter;
+pub mod vu_page;
+pub mod wake_alarm;
+pub mod wake_alarm_page;
+pub mod watch_face;
+pub mod watch_face_page;
+pub mod watchdog_feed;
+pub mod widgets;
+pub mod wifi_config;
+#[cfg(feature = "pico-w")]
+pub mod wireless;
+#[cfg(feature = "ws2812")]
+pub mod ws2812;
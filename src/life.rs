@@ -0,0 +1,360 @@
+//! 生命游戏：康威的细胞自动机，一个像素一个细胞
+//!
+//! 棋盘和屏幕一样大(`WIDTH` x `HEIGHT`)，一行存在一个 `u128` 里，第 x 位是第 x 列。
+//! 算下一代的时候整行一起算：把上、中、下三行各往左右挪一位，得到每个细胞的 8 个邻居，
+//! 再用按位的加法器数出邻居个数，一代只要六十多次整行运算，不用一个一个细胞数。
+//!
+//! 规则是标准的 B3/S23：死细胞正好 3 个邻居就活，活细胞 2 个或 3 个邻居就接着活，其他都死。
+//! 边上怎么算可以选(`Edges`)：卷起来(左边接右边、上边接下边)，或者边外面全是死细胞。
+//!
+//! `LifeBoard::step` 只动棋盘自己，不碰屏幕和外设，同样的棋盘总是得到同样的下一代。
+//! 演示页面(`LifePage`)每 `TICKS_PER_GENERATION` 个逻辑 tick 走一代(见 `timestep`)，变成不动的或者两代一循环的就重新随机撒一遍：
+//! Select 马上重新撒，Up/Down 切换边的算法(也会重新撒)，Back 回去。
+
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::Point;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::Pixel;
+
+use crate::app::Event;
+use crate::framebuffer::{HEIGHT, WIDTH};
+use crate::input::{Button, ButtonEvent};
+use crate::rng::Rng;
+use crate::screen::back_pops;
+
+/// 随机撒的时候多少比例是活的(百分比)
+const SEED_DENSITY_PERCENT: u32 = 30;
+
+/// 几个逻辑 tick 走一代，50Hz 下一秒八代多
+const TICKS_PER_GENERATION: u32 = 6;
+
+/// 最多走多少代就重新撒，有的图案会一直慢慢变，看久了也腻
+const MAX_GENERATIONS: u32 = 1000;
+
+/// 一行里用到的位
+const ROW_MASK: u128 = u128::MAX >> (128 - WIDTH);
+
+/// 边上的细胞怎么找邻居
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Edges {
+    /// 卷起来：最左边一列的左邻居是最右边一列，上下也一样
+    Wrap,
+    /// 边外面都是死细胞
+    Dead,
+}
+
+impl Edges {
+    pub fn name(self) -> &'static str {
+        match self {
+            Edges::Wrap => "wrap",
+            Edges::Dead => "dead",
+        }
+    }
+}
+
+/// 棋盘
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LifeBoard {
+    rows: [u128; HEIGHT],
+    edges: Edges,
+}
+
+impl LifeBoard {
+    /// 全是死细胞的棋盘
+    pub const fn new(edges: Edges) -> Self {
+        Self {
+            rows: [0; HEIGHT],
+            edges,
+        }
+    }
+
+    pub fn edges(&self) -> Edges {
+        self.edges
+    }
+
+    pub fn set_edges(&mut self, edges: Edges) {
+        self.edges = edges;
+    }
+
+    /// 第 x 列、第 y 行活着没有，棋盘外面的算死的
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        x < WIDTH && y < HEIGHT && self.rows[y] >> x & 1 != 0
+    }
+
+    /// 设置一个细胞，棋盘外面的不管
+    pub fn set(&mut self, x: usize, y: usize, alive: bool) {
+        if x >= WIDTH || y >= HEIGHT {
+            return;
+        }
+        if alive {
+            self.rows[y] |= 1 << x;
+        } else {
+            self.rows[y] &= !(1 << x);
+        }
+    }
+
+    /// 全部杀掉
+    pub fn clear(&mut self) {
+        self.rows = [0; HEIGHT];
+    }
+
+    /// 活着的细胞有几个
+    pub fn population(&self) -> u32 {
+        self.rows.iter().map(|row| row.count_ones()).sum()
+    }
+
+    /// 重新随机撒一遍，每个细胞有 `density_percent`% 的机会是活的
+    pub fn randomize(&mut self, rng: &mut Rng, density_percent: u32) {
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                self.set(x, y, rng.next_range(100) < density_percent);
+            }
+        }
+    }
+
+    /// 第 y 行上面、下面那一行，边外面的是空行
+    fn neighbour_rows(&self, y: usize) -> (u128, u128) {
+        match self.edges {
+            Edges::Wrap => (
+                self.rows[(y + HEIGHT - 1) % HEIGHT],
+                self.rows[(y + 1) % HEIGHT],
+            ),
+            Edges::Dead => (
+                y.checked_sub(1).map_or(0, |above| self.rows[above]),
+                self.rows.get(y + 1).copied().unwrap_or(0),
+            ),
+        }
+    }
+
+    /// 一行里每个细胞的左邻居、右邻居各自挪到这个细胞的位置上
+    fn sideways(&self, row: u128) -> (u128, u128) {
+        let from_left = row << 1;
+        let from_right = row >> 1;
+        match self.edges {
+            Edges::Wrap => (
+                (from_left | row >> (WIDTH - 1)) & ROW_MASK,
+                from_right | (row & 1) << (WIDTH - 1),
+            ),
+            Edges::Dead => (from_left & ROW_MASK, from_right),
+        }
+    }
+
+    /// 走一代，返回有没有细胞变了(没变就是停住了)
+    pub fn step(&mut self) -> bool {
+        let mut next = [0u128; HEIGHT];
+        for (y, out) in next.iter_mut().enumerate() {
+            let row = self.rows[y];
+            let (above, below) = self.neighbour_rows(y);
+            let (above_left, above_right) = self.sideways(above);
+            let (left, right) = self.sideways(row);
+            let (below_left, below_right) = self.sideways(below);
+            // 按位数邻居：ones、twos 是个数的低两位，fours 是"至少 4 个"
+            let (mut ones, mut twos, mut fours) = (0u128, 0u128, 0u128);
+            for neighbours in [
+                above_left,
+                above,
+                above_right,
+                left,
+                right,
+                below_left,
+                below,
+                below_right,
+            ] {
+                let carry = ones & neighbours;
+                ones ^= neighbours;
+                fours |= twos & carry;
+                twos ^= carry;
+            }
+            // 2 个或 3 个邻居：twos 是 1、fours 是 0；正好 3 个的 ones 也是 1
+            *out = twos & !fours & (ones | row);
+        }
+        let changed = next != self.rows;
+        self.rows = next;
+        changed
+    }
+
+    /// 画活着的细胞，死的不画(画之前要先清屏)
+    pub fn render<D>(&self, display: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        let cells = self.rows.iter().enumerate().flat_map(|(y, &row)| {
+            (0..WIDTH)
+                .filter(move |&x| row >> x & 1 != 0)
+                .map(move |x| Pixel(Point::new(x as i32, y as i32), BinaryColor::On))
+        });
+        display.draw_iter(cells)
+    }
+}
+
+/// 演示页面的状态：棋盘、两代以前的棋盘(看是不是在来回闪)和随机数
+pub struct LifeDemo {
+    pub board: LifeBoard,
+    previous: LifeBoard,
+    rng: Rng,
+    generation: u32,
+}
+
+impl LifeDemo {
+    pub fn new(rng: Rng, edges: Edges) -> Self {
+        let mut demo = Self {
+            board: LifeBoard::new(edges),
+            previous: LifeBoard::new(edges),
+            rng,
+            generation: 0,
+        };
+        demo.reseed();
+        demo
+    }
+
+    /// 重新随机撒一遍
+    pub fn reseed(&mut self) {
+        self.board.randomize(&mut self.rng, SEED_DENSITY_PERCENT);
+        self.previous.clear();
+        self.generation = 0;
+    }
+
+    /// 走一代。停住了、两代一循环或者走得太久了就重新撒
+    pub fn advance(&mut self) {
+        let before = self.board.clone();
+        let changed = self.board.step();
+        let blinking = self.board == self.previous;
+        self.previous = before;
+        self.generation += 1;
+        if !changed || blinking || self.generation >= MAX_GENERATIONS {
+            self.reseed();
+        }
+    }
+}
+
+crate::screen! {
+    /// 生命游戏演示
+    pub screen LifePage: LifeDemo {
+        name: "life",
+        fps: 10,
+        event(demo, event, _now_ms) {
+            match event {
+                Event::Button(ButtonEvent::Pressed(Button::Select)) => demo.reseed(),
+                Event::Button(ButtonEvent::Pressed(Button::Up | Button::Down)) => {
+                    let edges = match demo.board.edges() {
+                        Edges::Wrap => Edges::Dead,
+                        Edges::Dead => Edges::Wrap,
+                    };
+                    demo.board.set_edges(edges);
+                    demo.previous.set_edges(edges);
+                    defmt::info!("life edges: {=str}", edges.name());
+                    demo.reseed();
+                }
+                _ => {}
+            }
+            back_pops(event)
+        }
+        step(demo, tick) {
+            if !tick.is_multiple_of(TICKS_PER_GENERATION) {
+                return false;
+            }
+            demo.advance();
+            true
+        }
+        render(demo, canvas, _now_ms) {
+            demo.board.render(canvas)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 往右下走的滑翔机，4 代走一格
+    const GLIDER: [(usize, usize); 5] = [(1, 0), (2, 1), (0, 2), (1, 2), (2, 2)];
+
+    /// 横着的一条三格(闪光灯)
+    const BLINKER: [(usize, usize); 3] = [(0, 1), (1, 1), (2, 1)];
+
+    /// 把图案放到 (x, y)，超出右边、下边的卷回来
+    fn board(edges: Edges, pattern: &[(usize, usize)], x: usize, y: usize) -> LifeBoard {
+        let mut board = LifeBoard::new(edges);
+        for &(dx, dy) in pattern {
+            board.set((x + dx) % WIDTH, (y + dy) % HEIGHT, true);
+        }
+        board
+    }
+
+    #[test]
+    fn blinker_has_period_two() {
+        for edges in [Edges::Wrap, Edges::Dead] {
+            let start = board(edges, &BLINKER, 40, 20);
+            let mut life = start.clone();
+            assert!(life.step());
+            // 竖过来了
+            assert_eq!(
+                life,
+                board(edges, &[(1, 0), (1, 1), (1, 2)], 40, 20),
+                "{}",
+                edges.name()
+            );
+            assert!(life.step());
+            assert_eq!(life, start, "{}", edges.name());
+        }
+    }
+
+    #[test]
+    fn blinker_across_the_edge() {
+        // 躺在最上面一行：卷起来的话竖过来上面那一格在最下面一行
+        let top_row = [(0, 0), (1, 0), (2, 0)];
+        let start = board(Edges::Wrap, &top_row, 10, 0);
+        let mut life = start.clone();
+        life.step();
+        assert_eq!(
+            life,
+            board(Edges::Wrap, &[(1, 0), (1, 1), (1, 2)], 10, HEIGHT - 1)
+        );
+        life.step();
+        assert_eq!(life, start);
+
+        // 边外面是死的：竖不起来，变成两格以后就死光了
+        let mut life = board(Edges::Dead, &top_row, 10, 0);
+        life.step();
+        assert_eq!(life, board(Edges::Dead, &[(1, 0), (1, 1)], 10, 0));
+        life.step();
+        assert_eq!(life.population(), 0);
+        assert!(!life.step());
+    }
+
+    #[test]
+    fn glider_moves_one_cell_diagonally_every_four_generations() {
+        for edges in [Edges::Wrap, Edges::Dead] {
+            let mut life = board(edges, &GLIDER, 30, 10);
+            for generation in 1..=8 {
+                life.step();
+                assert_eq!(life.population(), 5, "{} gen {generation}", edges.name());
+                if generation % 4 == 0 {
+                    let moved = generation / 4;
+                    let expected = board(edges, &GLIDER, 30 + moved, 10 + moved);
+                    assert_eq!(life, expected, "{} gen {generation}", edges.name());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn glider_wraps_around_the_corner() {
+        let mut life = board(Edges::Wrap, &GLIDER, WIDTH - 2, HEIGHT - 2);
+        for _ in 0..4 {
+            life.step();
+        }
+        assert_eq!(life, board(Edges::Wrap, &GLIDER, WIDTH - 1, HEIGHT - 1));
+        for _ in 0..4 {
+            life.step();
+        }
+        assert_eq!(life, board(Edges::Wrap, &GLIDER, 0, 0));
+
+        // 撞到死的边上就不是滑翔机了
+        let mut life = board(Edges::Dead, &GLIDER, WIDTH - 3, HEIGHT - 3);
+        for _ in 0..4 {
+            life.step();
+        }
+        assert_ne!(life, board(Edges::Dead, &GLIDER, WIDTH - 2, HEIGHT - 2));
+    }
+}
@@ -0,0 +1,183 @@
+//! 数据记录浏览页面：用折线图画 flash 里记下来的某一个通道，顶上显示用了多少容量
+//!
+//! 从仪表盘按 Down 进来。Up 往旧的方向翻，Down 往新的方向翻，Select 换下一个通道，Back 回去。
+//! 板子上没有旋钮，翻页就用这两个键代替。
+
+use core::convert::Infallible;
+use core::fmt::Write;
+
+use embedded_graphics::geometry::Dimensions;
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::primitives::{Primitive, PrimitiveStyle, Rectangle};
+use embedded_graphics::text::Text;
+use embedded_graphics::Drawable;
+use heapless::{String, Vec};
+
+use crate::app::{Canvas, Event, Page, Transition};
+use crate::datalog::{LogIndex, CAPACITY, LOG_CHANNELS};
+use crate::input::{Button, ButtonEvent};
+use crate::sensors::ChannelInfo;
+use crate::widgets::draw_line_graph;
+
+/// 每按一次翻多少个点
+const PAN_STEP: usize = 32;
+
+/// 标题行基线
+const TITLE_Y: i32 = 8;
+
+/// 容量条：右上角的小框
+const GAUGE_WIDTH: u32 = 30;
+const GAUGE_HEIGHT: u32 = 7;
+
+/// 曲线区域的上边和下面留给文字的高度
+const GRAPH_TOP: i32 = 12;
+const FOOTER_HEIGHT: i32 = 12;
+
+/// 数据记录浏览页面
+#[derive(Debug)]
+pub struct LogPage {
+    /// 记录里的通道(注册表里的前几个)
+    channels: Vec<ChannelInfo, LOG_CHANNELS>,
+    /// 画的是第几个通道
+    channel: usize,
+    index: Option<LogIndex>,
+    /// 从最新的点往回翻了多少个点
+    offset: usize,
+    shown_second: Option<u64>,
+}
+
+impl LogPage {
+    /// `channels` 是传感器注册表里的通道，只用得到前 `LOG_CHANNELS` 个
+    pub fn new(channels: &[ChannelInfo]) -> Self {
+        Self {
+            channels: channels.iter().take(LOG_CHANNELS).copied().collect(),
+            channel: 0,
+            index: None,
+            offset: 0,
+            shown_second: None,
+        }
+    }
+
+    fn slots(&self) -> usize {
+        self.index.as_ref().map_or(0, LogIndex::slots)
+    }
+}
+
+impl Page for LogPage {
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        match event {
+            Event::Button(ButtonEvent::Pressed(Button::Back)) => return Transition::Pop,
+            Event::Button(ButtonEvent::Pressed(Button::Up)) => {
+                self.offset = (self.offset + PAN_STEP).min(self.slots().saturating_sub(1));
+            }
+            Event::Button(ButtonEvent::Pressed(Button::Down)) => {
+                self.offset = self.offset.saturating_sub(PAN_STEP);
+            }
+            Event::Button(ButtonEvent::Pressed(Button::Select)) => {
+                self.channel = (self.channel + 1) % self.channels.len().max(1);
+            }
+            _ => {}
+        }
+        Transition::None
+    }
+
+    /// 内容每秒才变一次，2 帧每秒保证秒数变了最多晚半秒显示
+    fn desired_fps(&self) -> u16 {
+        2
+    }
+
+    /// 每秒重新扫一次目录，有新记录了才重画
+    fn tick(&mut self, now_ms: u64) -> bool {
+        let second = now_ms / 1000;
+        if self.shown_second == Some(second) {
+            return false;
+        }
+        self.shown_second = Some(second);
+        let index = LogIndex::scan();
+        let changed = self.index.as_ref() != Some(&index);
+        self.index = Some(index);
+        changed
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<(), Infallible> {
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        let size = canvas.bounding_box().size;
+        let slots = self.slots();
+        let mut line: String<24> = String::new();
+
+        let Some(channel) = self.channels.get(self.channel).copied() else {
+            Text::new("no channels", Point::new(0, TITLE_Y), style).draw(canvas)?;
+            return Ok(());
+        };
+        let _ = write!(line, "{} {}/{}", channel.name, slots, CAPACITY);
+        Text::new(&line, Point::new(0, TITLE_Y), style).draw(canvas)?;
+        let gauge = Rectangle::new(
+            Point::new(size.width as i32 - GAUGE_WIDTH as i32, 1),
+            Size::new(GAUGE_WIDTH, GAUGE_HEIGHT),
+        );
+        gauge
+            .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+            .draw(canvas)?;
+        let filled = (GAUGE_WIDTH - 2) * slots as u32 / CAPACITY as u32;
+        if filled > 0 {
+            Rectangle::new(
+                gauge.top_left + Point::new(1, 1),
+                Size::new(filled, GAUGE_HEIGHT - 2),
+            )
+            .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+            .draw(canvas)?;
+        }
+
+        let footer_y = size.height as i32 - 2;
+        let Some(index) = self.index.as_ref().filter(|_| slots > 0) else {
+            Text::new("no data", Point::new(0, footer_y), style).draw(canvas)?;
+            return Ok(());
+        };
+
+        // 屏幕宽多少像素就画多少个点，最右边是最新的(减去翻页的偏移)
+        let end = slots - self.offset.min(slots - 1);
+        let start = end.saturating_sub(size.width as usize);
+        let column = self.channel;
+        let value = |i: usize| {
+            index
+                .get(i)
+                .and_then(|r| r.values[column])
+                .map(|v| v as i32)
+        };
+        let (min, max) = (start..end)
+            .filter_map(value)
+            .fold((i32::MAX, i32::MIN), |(lo, hi), v| (lo.min(v), hi.max(v)));
+        if min > max {
+            Text::new("no valid samples", Point::new(0, footer_y), style).draw(canvas)?;
+            return Ok(());
+        }
+        // 曲线太平的话上下各留半个单位(温度就是 0.5 度)，不然一点噪声就满屏跳
+        let margin = channel.scale as i32 / 2;
+        let (lo, hi) = if max - min < 2 * margin {
+            (min - margin, max + margin)
+        } else {
+            (min, max)
+        };
+        let graph = Rectangle::new(
+            Point::new(0, GRAPH_TOP),
+            Size::new(
+                size.width,
+                (size.height as i32 - GRAPH_TOP - FOOTER_HEIGHT).max(1) as u32,
+            ),
+        );
+        draw_line_graph(canvas, graph, (start..end).map(value), lo, hi)?;
+
+        line.clear();
+        let _ = channel.write_number(min, &mut line);
+        let _ = line.push_str("..");
+        let _ = channel.write_value(Some(max), &mut line);
+        if self.offset > 0 {
+            let _ = write!(line, " -{}", self.offset);
+        }
+        Text::new(&line, Point::new(0, footer_y), style).draw(canvas)?;
+        Ok(())
+    }
+}
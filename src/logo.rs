@@ -0,0 +1,46 @@
+//! 编译进固件的开机 logo：build.rs 在编译的时候把 assets/logo.png(或者 logo.pbm)转成显存的按页格式放进来
+//!
+//! 怎么准备图、最大多大见 build/logo.rs 和 README 的"开机 logo"。没放图的话 `LOGO` 是 None，开机还是显示版本号。
+//! 设置里选了图片槽当开机画面(见 `splash_page`)的话用槽里的图，这里的 logo 只在没选的时候用。
+
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::Point;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::Pixel;
+
+/// 按页打包的单色图：一页 8 行，一页里每一列一个字节，最低位在最上面，和屏幕显存一样
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Logo {
+    pub width: u32,
+    pub height: u32,
+    /// 一共 `height` 补齐到 8 的倍数再除以 8 页，每页 `width` 个字节
+    pub pages: &'static [u8],
+}
+
+include!(concat!(env!("OUT_DIR"), "/logo.rs"));
+
+impl Logo {
+    /// (x, y) 亮不亮，超出图的算暗
+    pub fn is_lit(&self, x: u32, y: u32) -> bool {
+        if x >= self.width || y >= self.height {
+            return false;
+        }
+        let index = ((y / 8) * self.width + x) as usize;
+        self.pages
+            .get(index)
+            .is_some_and(|byte| byte & (1 << (y % 8)) != 0)
+    }
+
+    /// 左上角放在 `top_left` 画出来，只画亮的点(调用方先清屏)
+    pub fn draw<D>(&self, display: &mut D, top_left: Point) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        let pixels = (0..self.height).flat_map(move |y| {
+            (0..self.width)
+                .filter(move |&x| self.is_lit(x, y))
+                .map(move |x| Pixel(top_left + Point::new(x as i32, y as i32), BinaryColor::On))
+        });
+        display.draw_iter(pixels)
+    }
+}
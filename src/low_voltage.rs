@@ -0,0 +1,240 @@
+//! 低电压保护：用电池供电的时候 VSYS 掉下去了，先停写 flash，再低就关屏等电压回来，而不是写 flash 写到一半掉电
+//!
+//! 看的是传感器通道 "vsys"(校准过的，见 `calibration`)，分两档，阈值存在设置里(`SETTINGS BATTLOW`)：
+//!
+//! - 低于 `warn_mv`(`PowerLevel::Low`)：右上角画一个电池图标，不再写 flash。保存设置只记一个"有设置等着存"
+//!   (电池图标旁边多一个小方块)，电压回到 `warn_mv` 以上(带回差)再存，存的是那时候最新的设置；屏幕累计、数据记录这些
+//!   定时写的也先不写，图片上传的 `IMG END` 回复错误
+//! - 低于 `critical_mv`(`PowerLevel::Critical`)：主循环画一屏 "battery low"，关屏、关灯，停下所有别的事，
+//!   每秒醒一次看电压，回到 `critical_mv` 以上(带回差)才接着跑(一般是回到 `Low` 档，flash 照样不写)。
+//!   USB 这时候也不 poll 了，电脑上会看到串口没反应；不想等的话重新上电
+//!
+//! 电压要连续 `SETTLE_READINGS` 次(VSYS 通道 100ms 读一次，也就是一秒；关屏等着的时候是十秒)都过了线才换档，负载突然变大拉低一下不算；
+//! 往回走的时候要高出阈值 `HYSTERESIS_MV`，不会在阈值附近来回跳。
+//!
+//! 插着 USB 的时候 VSYS 差不多 4.7V 以上，永远到不了阈值。默认的阈值是按一节锂电池定的(见 `battery`)，
+//! 别的电池要改阈值，`SETTINGS BATTLOW OFF` 整个关掉。
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+
+use crate::flash;
+use crate::text::draw_centered;
+
+/// 编码之后多少字节：警告阈值 2 字节 + 严重阈值 2 字节(mV)
+pub const ENCODED_LEN: usize = 4;
+
+/// 默认的警告阈值(mV)，一节锂电池剩 5% 左右
+pub const DEFAULT_WARN_MV: u16 = 3500;
+
+/// 默认的严重阈值(mV)，再往下稳压芯片快撑不住了
+pub const DEFAULT_CRITICAL_MV: u16 = 3350;
+
+/// 阈值最高能设多少(mV)，再高插着 USB 也会触发
+pub const MAX_THRESHOLD_MV: u16 = 4500;
+
+/// 往回走的时候要高出阈值多少(mV)
+pub const HYSTERESIS_MV: u16 = 100;
+
+/// 连续几次读数都过了线才换档
+pub const SETTLE_READINGS: u8 = 10;
+
+/// "battery low" 那一屏关屏之前停多久(毫秒)，让人看得到
+pub const NOTICE_MS: u32 = 3000;
+
+/// 低电压保护的设置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct LowVoltageConfig {
+    /// 低于这个停写 flash(mV)，0 是整个关掉
+    pub warn_mv: u16,
+    /// 低于这个关屏等电压回来(mV)，比 `warn_mv` 低
+    pub critical_mv: u16,
+}
+
+impl Default for LowVoltageConfig {
+    fn default() -> Self {
+        Self {
+            warn_mv: DEFAULT_WARN_MV,
+            critical_mv: DEFAULT_CRITICAL_MV,
+        }
+    }
+}
+
+impl LowVoltageConfig {
+    /// 关掉
+    pub const OFF: Self = Self {
+        warn_mv: 0,
+        critical_mv: 0,
+    };
+
+    pub fn is_enabled(&self) -> bool {
+        self.warn_mv > 0
+    }
+
+    /// 两个阈值合不合理：关掉的是两个都是 0，开着的要 0 < 严重 < 警告 <= `MAX_THRESHOLD_MV`
+    pub fn is_valid(&self) -> bool {
+        *self == Self::OFF
+            || (0 < self.critical_mv
+                && self.critical_mv < self.warn_mv
+                && self.warn_mv <= MAX_THRESHOLD_MV)
+    }
+
+    pub fn encode(&self) -> [u8; ENCODED_LEN] {
+        let mut out = [0u8; ENCODED_LEN];
+        out[0..2].copy_from_slice(&self.warn_mv.to_le_bytes());
+        out[2..4].copy_from_slice(&self.critical_mv.to_le_bytes());
+        out
+    }
+
+    /// 解码，阈值不合理返回 None
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let &[warn_lo, warn_hi, critical_lo, critical_hi] = bytes else {
+            return None;
+        };
+        let config = Self {
+            warn_mv: u16::from_le_bytes([warn_lo, warn_hi]),
+            critical_mv: u16::from_le_bytes([critical_lo, critical_hi]),
+        };
+        config.is_valid().then_some(config)
+    }
+}
+
+/// 电压在哪一档
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum PowerLevel {
+    Normal,
+    /// 低于警告阈值：不写 flash
+    Low,
+    /// 低于严重阈值：关屏等电压回来
+    Critical,
+}
+
+/// 现在能不能写 flash
+static FLASH_WRITES: AtomicBool = AtomicBool::new(true);
+
+/// 有设置等着电压回来再存
+static PENDING_SAVE: AtomicBool = AtomicBool::new(false);
+
+/// 现在能不能写 flash(电压低的时候、core1 在 flash 里跑代码的时候不能)
+pub fn flash_writes_allowed() -> bool {
+    FLASH_WRITES.load(Ordering::Relaxed) && !flash::core1_busy()
+}
+
+/// 记下有设置没存成，`Settings::store` 在不能写 flash 的时候调
+pub fn defer_save() {
+    PENDING_SAVE.store(true, Ordering::Relaxed);
+}
+
+/// 有没有设置等着存
+pub fn save_pending() -> bool {
+    PENDING_SAVE.load(Ordering::Relaxed)
+}
+
+/// 能写 flash 了而且有设置等着存的话返回 true(只返回一次)，调用方接着存最新的设置
+pub fn take_pending_save() -> bool {
+    // thumbv6m 没有原子交换，只有主循环在用，先读后写就行
+    if !flash_writes_allowed() || !save_pending() {
+        return false;
+    }
+    PENDING_SAVE.store(false, Ordering::Relaxed);
+    true
+}
+
+/// 按 VSYS 读数换档，主循环里每读到一个新数调一次 `update`
+#[derive(Debug, Clone, Copy)]
+pub struct LowVoltageMonitor {
+    config: LowVoltageConfig,
+    level: PowerLevel,
+    /// 连续几次读数想换到 `candidate` 这一档
+    candidate: PowerLevel,
+    streak: u8,
+}
+
+impl LowVoltageMonitor {
+    pub const fn new(config: LowVoltageConfig) -> Self {
+        Self {
+            config,
+            level: PowerLevel::Normal,
+            candidate: PowerLevel::Normal,
+            streak: 0,
+        }
+    }
+
+    pub fn level(&self) -> PowerLevel {
+        self.level
+    }
+
+    /// 换阈值。关掉的话马上回到 `Normal`
+    pub fn set_config(&mut self, config: LowVoltageConfig) {
+        self.config = config;
+        if !config.is_enabled() {
+            self.apply(PowerLevel::Normal);
+        }
+    }
+
+    /// 这个读数该在哪一档：往下按阈值，往上要多出回差
+    fn target(&self, mv: i32) -> PowerLevel {
+        let LowVoltageConfig {
+            warn_mv,
+            critical_mv,
+        } = self.config;
+        if !self.config.is_enabled() {
+            return PowerLevel::Normal;
+        }
+        let above = |threshold: u16, current_below: bool| {
+            let margin = if current_below { HYSTERESIS_MV } else { 0 };
+            mv >= (threshold + margin) as i32
+        };
+        let below_warn = self.level != PowerLevel::Normal;
+        let below_critical = self.level == PowerLevel::Critical;
+        if !above(critical_mv, below_critical) {
+            PowerLevel::Critical
+        } else if !above(warn_mv, below_warn) {
+            PowerLevel::Low
+        } else {
+            PowerLevel::Normal
+        }
+    }
+
+    /// 新的 VSYS 读数(mV)。换了档返回新的那一档
+    pub fn update(&mut self, mv: i32) -> Option<PowerLevel> {
+        let target = self.target(mv);
+        if target == self.level {
+            self.streak = 0;
+            return None;
+        }
+        if target != self.candidate {
+            self.candidate = target;
+            self.streak = 0;
+        }
+        self.streak += 1;
+        if self.streak < SETTLE_READINGS {
+            return None;
+        }
+        self.apply(target);
+        Some(target)
+    }
+
+    fn apply(&mut self, level: PowerLevel) {
+        self.level = level;
+        self.candidate = level;
+        self.streak = 0;
+        FLASH_WRITES.store(level == PowerLevel::Normal, Ordering::Relaxed);
+    }
+}
+
+/// 关屏之前的那一屏：整屏清掉，中间两行字
+pub fn draw_battery_low<D>(display: &mut D) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+    let middle = display.bounding_box().center().y;
+    display.clear(BinaryColor::Off)?;
+    draw_centered(display, "battery low", middle - 2, style)?;
+    draw_centered(display, "please charge", middle + 10, style)
+}
@@ -1,30 +1,340 @@
 //! Blinks the LED on a Pico board
 //!
 //! This will blink an LED attached to GP25, which is the pin the Pico uses for the on-board LED.
+// 固件只在板子上跑。在电脑上(跑单元测试、clippy)这个 crate 是空的，只检查 lib 里的模块
+#![cfg_attr(all(not(target_os = "none"), not(test)), no_main)]
+#![cfg(target_os = "none")]
 #![no_std]
 #![no_main]
 
 use bsp::entry;
 use defmt_rtt as _;
-use panic_probe as _;
-use defmt::info;
+use core::cell::RefCell;
+use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::fmt::{self, Write};
+use defmt::{error, info, warn};
+use embedded_graphics::geometry::{OriginDimensions, Point};
 
 // Provide an alias for our BSP so we can switch targets quickly.
 // Uncomment the BSP you included in Cargo.toml, the rest of the code does not need to change.
 use rp_pico as bsp;
-use embedded_graphics::Drawable;
-use embedded_graphics::geometry::Point;
-use embedded_graphics::mono_font::ascii::FONT_6X10;
-use embedded_graphics::mono_font::MonoTextStyle;
-use embedded_graphics::pixelcolor::BinaryColor;
-use embedded_graphics::text::Text;
 use rp2040_hal::Clock;
+#[cfg(not(feature = "no-xosc"))]
 use rp2040_hal::clocks::init_clocks_and_plls;
-use rp2040_hal::fugit::RateExtU32;
-use rp2040_hal::gpio::{FunctionI2C,  Pins, PullUp};
+#[cfg(feature = "no-xosc")]
+use rp2040_i2c_oled_rust::rosc_clock;
+use rp2040_hal::fugit::{HertzU32, RateExtU32};
+use rp2040_hal::gpio::{FunctionI2C,  Pin, Pins};
+use rp2040_hal::pac;
 use rp2040_hal::i2c::I2C;
-use ssd1306::{I2CDisplayInterface, Ssd1306};
-use ssd1306::prelude::{DisplayConfig, DisplayRotation, DisplaySize128x64};
+#[cfg(not(feature = "no-xosc"))]
+use rp2040_hal::usb::UsbBus;
+use rp2040_hal::Timer;
+use rp2040_hal::watchdog::Watchdog;
+#[cfg(not(feature = "no-xosc"))]
+use usb_device::bus::UsbBusAllocator;
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::OutputPin as _;
+use rp2040_hal::dma::{Channel, DMAExt, CH0};
+use ssd1306::I2CDisplayInterface;
+#[cfg(feature = "trace_i2c")]
+use rp2040_i2c_oled_rust::i2c_trace::TraceI2c;
+use ssd1306::prelude::DisplayRotation;
+use rp2040_i2c_oled_rust::{boot_strap_pins, button_pins, fixture_result_pin, oled_i2c};
+use rp2040_i2c_oled_rust::board::{configure_i2c_pad, OledI2cBlock, OledI2cPull, OledScl, OledSda, INTERNAL_PULLUP_MAX_HZ, I2C_INTERNAL_PULLUPS};
+#[cfg(not(feature = "mic-vu"))]
+use rp2040_i2c_oled_rust::board::SclTapPin;
+use rp2040_i2c_oled_rust::boards::BOARD;
+use rp2040_i2c_oled_rust::alarm_page::AlarmPage;
+use rp2040_i2c_oled_rust::alarms::{Actions, AlarmEngine, AlarmRules};
+use rp2040_i2c_oled_rust::app::{Action, Confirm, Event, PageId, Scheduler, Transition};
+use rp2040_i2c_oled_rust::auto_brightness::{self, AutoBrightness};
+use rp2040_i2c_oled_rust::dim_page::DimPage;
+use rp2040_i2c_oled_rust::dimming::Dimmer;
+use rp2040_i2c_oled_rust::input::ButtonPad;
+use rp2040_i2c_oled_rust::boot_loop::{self, draw_recovery_screen};
+use rp2040_i2c_oled_rust::boot_mode::{BootMode, BootStrap};
+use rp2040_i2c_oled_rust::boot_progress::BootProgress;
+use rp2040_i2c_oled_rust::burn_in::AntiBurnIn;
+use rp2040_i2c_oled_rust::carousel::Carousel;
+use rp2040_i2c_oled_rust::carousel_page::CarouselPage;
+use rp2040_i2c_oled_rust::dashboard::DashboardPage;
+use rp2040_i2c_oled_rust::event_log::{self, ResetCause, SystemEvent};
+use rp2040_i2c_oled_rust::event_page::EventLogPage;
+use rp2040_i2c_oled_rust::bus_page::BusPage;
+use rp2040_i2c_oled_rust::calibration::Calibration;
+use rp2040_i2c_oled_rust::calibration_page::{CalibrationPage, CalibrationResetPage};
+use rp2040_i2c_oled_rust::capabilities::{self, CapabilitiesPage};
+use rp2040_i2c_oled_rust::demo_auto::DemoAuto;
+use rp2040_i2c_oled_rust::diagnostics::DiagnosticsPage;
+use rp2040_i2c_oled_rust::distance_page::DistancePage;
+use rp2040_i2c_oled_rust::bouncing_ball::BouncingBallPage;
+use rp2040_i2c_oled_rust::command;
+use rp2040_i2c_oled_rust::heap;
+use rp2040_i2c_oled_rust::panel_care::{PanelCare, PanelWear};
+use rp2040_i2c_oled_rust::panic_screen::draw_panic_screen;
+#[cfg(feature = "panic-restore")]
+use rp2040_i2c_oled_rust::shadow_frame;
+use rp2040_i2c_oled_rust::preflight::preflight;
+use rp2040_i2c_oled_rust::remap::{self, InputBindings, RemapEvent, Remapper, Role};
+use rp2040_i2c_oled_rust::remap_page::RemapPage;
+use rp2040_i2c_oled_rust::remote_page::{RemoteText, RemoteTextPage};
+use rp2040_i2c_oled_rust::retry::with_retry;
+use rp2040_i2c_oled_rust::baro_page::{BaroChannels, BaroPage};
+use rp2040_i2c_oled_rust::battery::estimate_battery_percent;
+use rp2040_i2c_oled_rust::bmp280::{Bmp280, TREND_WINDOW_MS};
+use rp2040_i2c_oled_rust::comfort_page::{ComfortChannels, ComfortPage};
+use rp2040_i2c_oled_rust::humidity::{self, AnyHumiditySensor, HumidityMonitor};
+use rp2040_i2c_oled_rust::screen_timeout::ScreenTimeout;
+use rp2040_i2c_oled_rust::vl53l0x::{self, DistanceMonitor, Vl53l0x};
+use rp2040_i2c_oled_rust::cpu_load::{self, CpuMeter};
+use rp2040_i2c_oled_rust::banner::{hold_banner, show_logo, show_splash, show_version_banner, type_subtitle, DEFAULT_BANNER_DURATION_MS};
+use rp2040_i2c_oled_rust::logo::LOGO;
+use rp2040_i2c_oled_rust::console::{Console, ConsoleCommand, Help, HELP_LINE_MAX};
+use rp2040_i2c_oled_rust::display::{fade_in, Display, LazyDisplay};
+use rp2040_i2c_oled_rust::i2c_dma::{DmaI2c, StagingBuffer, STAGING_LEN};
+use rp2040_i2c_oled_rust::host_status::{encode_ack, AckCode, HostStatus, StatusPacket, REPORT_LEN};
+use rp2040_i2c_oled_rust::settings::{Settings, BASE64_CAPACITY, BLOB_CAPACITY};
+use rp2040_i2c_oled_rust::shared;
+use rp2040_i2c_oled_rust::reader_page::ReaderPage;
+use rp2040_i2c_oled_rust::settings_menu::SettingsMenu;
+use rp2040_i2c_oled_rust::transition::transition;
+use rp2040_i2c_oled_rust::perf::{self, PerfTable};
+use rp2040_i2c_oled_rust::perf_page::PerfPage;
+use rp2040_i2c_oled_rust::periodic::{Periodic, Task};
+use rp2040_i2c_oled_rust::snapshot::{self, dump_framebuffer, Snapshot};
+use rp2040_i2c_oled_rust::power_off::{self, PowerOffPage};
+use rp2040_i2c_oled_rust::image_slots::{self, ImageError, ImageUpload, SlotState};
+use rp2040_i2c_oled_rust::splash_page::SplashPage;
+use rp2040_i2c_oled_rust::profile_page::ProfilePage;
+use rp2040_i2c_oled_rust::pulse_counter::{self, CounterStore, PulseConfig};
+use rp2040_i2c_oled_rust::pulse_page::PulsePage;
+use rp2040_i2c_oled_rust::gray_page::GrayPage;
+use rp2040_i2c_oled_rust::theme;
+use rp2040_i2c_oled_rust::theme_page::ThemePage;
+use rp2040_i2c_oled_rust::timezone::TimeZone;
+use rp2040_i2c_oled_rust::timezone_page::TimeZonePage;
+#[cfg(feature = "devtools")]
+use rp2040_i2c_oled_rust::devtools::{Preview, Progress};
+#[cfg(feature = "devtools")]
+use rp2040_i2c_oled_rust::preview_page::PreviewPage;
+use rp2040_i2c_oled_rust::display_profile::{self, apply_profile};
+use rp2040_i2c_oled_rust::low_voltage::{self, LowVoltageMonitor, PowerLevel};
+use rp2040_i2c_oled_rust::popup::{PopupPage, PopupText};
+use rp2040_i2c_oled_rust::display_link::{self, DisplayLink};
+use rp2040_i2c_oled_rust::rng::Rng;
+use rp2040_i2c_oled_rust::starfield::StarfieldPage;
+use rp2040_i2c_oled_rust::usb::UsbLink;
+use rp2040_i2c_oled_rust::net_console::{NetLink, NetState};
+use rp2040_i2c_oled_rust::wifi_config;
+use rp2040_i2c_oled_rust::alert::{AlertLevel, Alerts};
+use rp2040_i2c_oled_rust::buzzer::PwmBuzzer;
+use rp2040_i2c_oled_rust::buzzer_pwm;
+use rp2040_i2c_oled_rust::servo_pwm;
+use rp2040_i2c_oled_rust::servo::ServoOutput;
+use rp2040_i2c_oled_rust::servo_page::ServoPage;
+use rp2040_i2c_oled_rust::input::{Button, ButtonEvent};
+use rp2040_i2c_oled_rust::gesture::{Gesture, GestureDetector, HoldGate};
+use rp2040_i2c_oled_rust::input_map::{Binding, InputEvent, InputMap};
+use rp2040_i2c_oled_rust::input_replay::{Player, Recorder};
+use rp2040_i2c_oled_rust::sleep_clock::{ClockFace, WallClock};
+use rp2040_hal::timer::{Alarm, Alarm0};
+use rp2040_hal::fugit::MicrosDurationU32;
+use rp2040_i2c_oled_rust::tone::{Sound, ToneEngine, ToneOutput, NAV_BEEP};
+use rp2040_hal::pwm::Slices;
+use rp2040_hal::adc::Adc;
+use rp2040_i2c_oled_rust::pulse_input_pin;
+use rp2040_i2c_oled_rust::sampler_adc_pin;
+#[cfg(not(feature = "mic-vu"))]
+use rp2040_i2c_oled_rust::scl_tap_adc_pin;
+use rp2040_i2c_oled_rust::datalog::{write_csv_header, DataLog, LogIndex, CSV_LINE_MAX};
+use rp2040_i2c_oled_rust::life::{Edges, LifeDemo, LifePage};
+use rp2040_i2c_oled_rust::maze::{MazeDemo, MazePage};
+use rp2040_i2c_oled_rust::mode::{self, Mode, ModeEvent, Observed};
+use rp2040_i2c_oled_rust::reaction;
+use rp2040_i2c_oled_rust::reaction_page::ReactionPage;
+use rp2040_i2c_oled_rust::large_text::LargeTextPage;
+use rp2040_hal::multicore::{Multicore, Stack};
+use rp2040_hal::pac::interrupt;
+use rp2040_hal::sio::SioFifo;
+use rp2040_i2c_oled_rust::analog_clock::{AnalogClock, AnalogClockPage};
+use rp2040_i2c_oled_rust::log_page::LogPage;
+use rp2040_i2c_oled_rust::output_pins;
+use rp2040_i2c_oled_rust::outputs::OutputControl;
+use rp2040_i2c_oled_rust::status_led_pin;
+use rp2040_i2c_oled_rust::status_led::{BoardLed, StatusLed};
+use rp2040_i2c_oled_rust::system_info::{self, SystemInfo, UsbState};
+use rp2040_i2c_oled_rust::stack;
+use rp2040_i2c_oled_rust::panel::PANEL;
+use rp2040_i2c_oled_rust::blink_code;
+use rp2040_i2c_oled_rust::ds3231::{Ds3231, Time};
+use rp2040_i2c_oled_rust::flash;
+use rp2040_i2c_oled_rust::health;
+use rp2040_i2c_oled_rust::board::FIXTURE_SENSORS;
+use rp2040_i2c_oled_rust::fixture::{self, render_verdict, Report};
+use rp2040_i2c_oled_rust::outputs::OutputPin;
+use rp2040_i2c_oled_rust::i2c_lines::LineVerdict;
+use rp2040_i2c_oled_rust::i2c_lines::BusReport;
+#[cfg(not(feature = "mic-vu"))]
+use rp2040_i2c_oled_rust::i2c_lines::{diagnose_i2c_lines, SclTap};
+use rp2040_i2c_oled_rust::watchdog_feed::feed_during;
+#[cfg(not(feature = "mic-vu"))]
+use rp2040_i2c_oled_rust::sampler::Sampler;
+#[cfg(feature = "mic-vu")]
+use rp2040_i2c_oled_rust::mic::{MicBuffer, MicSampler as Sampler, BUFFER_LEN as MIC_BUFFER_LEN};
+use rp2040_i2c_oled_rust::sampler::SamplerPin;
+use rp2040_i2c_oled_rust::vu_meter::VuMeter;
+use rp2040_i2c_oled_rust::vu_page::VuPage;
+use rp2040_i2c_oled_rust::wake_alarm::{AlarmClock, AlarmEvent, Ringing, WakeAlarmConfig};
+use rp2040_i2c_oled_rust::wake_alarm_page::{RingingPage, WakeAlarmPage};
+use rp2040_i2c_oled_rust::watch_face::Widget;
+use rp2040_i2c_oled_rust::watch_face_page::WatchFacePage;
+use rp2040_i2c_oled_rust::spectrum_page::SpectrumPage;
+use rp2040_i2c_oled_rust::sensors::{ChannelId, ChannelInfo, Correction, SensorRegistry, HISTORY_LEN, UNKNOWN_CHANNEL};
+use rp2040_i2c_oled_rust::telemetry::{self, Fields, Telemetry};
+use rp2040_i2c_oled_rust::status::{self, serialize_status, State, StatusReporter};
+use rp2040_i2c_oled_rust::vsys_adc_pin;
+use heapless::{Deque, String, Vec};
+use rp2040_i2c_oled_rust::i2c_dma::{FlushError, FlushPoll};
+#[cfg(feature = "ws2812")]
+use rp2040_hal::dma::CH1;
+#[cfg(feature = "ws2812")]
+use rp2040_hal::pio::{PIOExt, SM0};
+#[cfg(feature = "ws2812")]
+use rp2040_i2c_oled_rust::board::LED_STRIP_LEN;
+#[cfg(feature = "ws2812")]
+use rp2040_i2c_oled_rust::led_strip_pin;
+#[cfg(feature = "ws2812")]
+use rp2040_i2c_oled_rust::ws2812::{AlertStrip, StripBuffer, Ws2812};
+
+/// OLED 的 I2C 地址，大部分 128x64 的模块都是 0x3C
+const OLED_I2C_ADDRESS: u8 = 0x3C;
+
+/// 开机淡入的时长(毫秒)，算在横幅停留时间里面
+const BOOT_FADE_MS: u32 = 600;
+
+/// 横幅之后还有几步初始化(传感器、页面、数据记录、USB)，开机进度条按这个分格
+const BOOT_STAGES: u8 = 4;
+
+/// 开机时屏幕初始化、第一次 flush 最多试几次，第一次重试前等多久(毫秒)
+const I2C_ATTEMPTS: u8 = 4;
+const I2C_RETRY_DELAY_MS: u32 = 10;
+
+/// 看门狗超时(毫秒)，RP2040 最长 8.3 秒。开机最长的一步是横幅和硬件小结(各停 2、3 秒)，前后都喂；
+/// 主循环每一圈喂一次。panic 以后没人喂了，到时间整片复位，反复重启才数得出来(见 boot_loop.rs)
+const WATCHDOG_TIMEOUT_MS: u32 = 8_000;
+
+/// 页面编号，和下面传给 `Scheduler::new` 的数组顺序一致
+const DASHBOARD_PAGE: PageId = PageId(0);
+const HOST_PAGE: PageId = PageId(1);
+const DIAGNOSTICS_PAGE: PageId = PageId(2);
+const BALL_PAGE: PageId = PageId(3);
+const LOG_PAGE: PageId = PageId(4);
+const OUTPUTS_PAGE: PageId = PageId(5);
+const DIM_PAGE: PageId = PageId(6);
+const ALARM_PAGE: PageId = PageId(7);
+const BARO_PAGE: PageId = PageId(8);
+const COMFORT_PAGE: PageId = PageId(9);
+const DISTANCE_PAGE: PageId = PageId(10);
+const REMOTE_PAGE: PageId = PageId(11);
+const SETTINGS_MENU_PAGE: PageId = PageId(12);
+const CAROUSEL_PAGE: PageId = PageId(13);
+const PERF_PAGE: PageId = PageId(14);
+const POPUP_PAGE: PageId = PageId(15);
+const STARFIELD_PAGE: PageId = PageId(16);
+const DEMO_MENU_PAGE: PageId = PageId(17);
+const READER_PAGE: PageId = PageId(18);
+const VU_PAGE: PageId = PageId(19);
+const SPECTRUM_PAGE: PageId = PageId(20);
+const SERVO_PAGE: PageId = PageId(21);
+const EVENTS_PAGE: PageId = PageId(22);
+const BUS_PAGE: PageId = PageId(23);
+const CALIBRATION_PAGE: PageId = PageId(24);
+const CALIBRATION_RESET_PAGE: PageId = PageId(25);
+const WATCH_FACE_PAGE: PageId = PageId(26);
+const LIFE_PAGE: PageId = PageId(27);
+const ANALOG_CLOCK_PAGE: PageId = PageId(28);
+const POWER_OFF_PAGE: PageId = PageId(29);
+const SPLASH_PAGE: PageId = PageId(30);
+const MAZE_PAGE: PageId = PageId(31);
+const REACTION_PAGE: PageId = PageId(32);
+const LARGE_TEXT_PAGE: PageId = PageId(33);
+const WAKE_ALARM_PAGE: PageId = PageId(34);
+const RINGING_PAGE: PageId = PageId(35);
+const PROFILE_PAGE: PageId = PageId(36);
+const PULSE_PAGE: PageId = PageId(37);
+const GRAY_PAGE: PageId = PageId(38);
+const THEME_PAGE: PageId = PageId(39);
+const FEATURES_PAGE: PageId = PageId(40);
+const REMAP_PAGE: PageId = PageId(41);
+const TIME_ZONE_PAGE: PageId = PageId(42);
+#[cfg(feature = "devtools")]
+const PREVIEW_PAGE: PageId = PageId(43);
+
+/// 演示自动轮换放哪几个演示，按演示菜单的顺序。舵机是测硬件用的，不参加
+const DEMO_SCENES: [PageId; 11] = [COMFORT_PAGE, LOG_PAGE, REMOTE_PAGE, BALL_PAGE, STARFIELD_PAGE, DIAGNOSTICS_PAGE, READER_PAGE, VU_PAGE, SPECTRUM_PAGE, LIFE_PAGE, MAZE_PAGE];
+
+/// 全局按键：查到的输入不交给页面，换成动作交给主循环(见 input_map.rs)。改键、加快捷键改这里
+const INPUT_MAP: InputMap<3> = InputMap::new([
+    // 闹钟响着的时候长按 Back 是关闹钟，交给闹钟页面
+    Binding::new(InputEvent::Button(ButtonEvent::LongPress(Button::Back)), Action::EnterSleepClock).unless(RINGING_PAGE),
+    Binding::new(InputEvent::Gesture(Gesture::Double(Button::Back)), Action::OpenDemoMenu),
+    // 按满 3 秒只有 `HoldGate` 管的关机键有，哪个键是关机键看按键重映射(见 gesture.rs、remap.rs)
+    Binding::new(InputEvent::PowerHold, Action::AskPowerOff),
+]);
+
+/// 告警规则多久检查一次(毫秒)
+const ALARM_CHECK_MS: u64 = 1000;
+
+/// 闹钟多久看一次时间(毫秒)，分钟变了才真的比较
+const WAKE_ALARM_CHECK_MS: u64 = 1000;
+
+/// 各个传感器通道多久读一次(毫秒)：温度变得慢，外部 ADC 要给遥测画曲线用，读得最勤
+const TEMP_POLL_MS: u32 = 1000;
+const ADC0_POLL_MS: u32 = 10;
+const VSYS_POLL_MS: u32 = 100;
+const PULSE_POLL_MS: u32 = 1000;
+
+/// 多久从 DS3231 对一次时(毫秒)，定时器一分钟差不了多少
+const RTC_SYNC_MS: u64 = 60_000;
+
+/// 多久查一次栈用到多深了(毫秒)，查一次要把没用过的内存读一遍，别太勤
+const STACK_CHECK_MS: u64 = 10_000;
+
+/// 主循环里的定时任务(见 `periodic`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Job {
+    VsysCheck,
+    Log,
+    AlarmCheck,
+    WakeAlarm,
+    RtcSync,
+    StackCheck,
+    AutoBrightness,
+}
+
+/// BMP280 每 0.5 秒出一个结果，读得再勤也没用
+const BARO_POLL_MS: u32 = 1000;
+
+/// 主循环一圈最多睡到多长(微秒)：一圈的活干完了就睡到这一毫秒结束，USB 一毫秒一帧，poll 得过来
+const IDLE_SLICE_US: u64 = 1000;
+
+/// 屏幕的具体类型：board.rs 里配的 I2C 块和引脚(Pico 上是 I2C0，GP4/GP5) + DMA 0 号通道
+type OledDisplay = Display<
+    DmaI2c<
+        OledI2cBlock,
+        (
+            Pin<OledSda, FunctionI2C, OledI2cPull>,
+            Pin<OledScl, FunctionI2C, OledI2cPull>,
+        ),
+        Channel<CH0>,
+    >,
+>;
+
+/// 告警灯带
+#[cfg(feature = "ws2812")]
+type LedStrip = AlertStrip<pac::PIO0, SM0, Channel<CH1>, LED_STRIP_LEN>;
 
 
 // #[defmt::panic_handler]
@@ -33,9 +343,88 @@ use ssd1306::prelude::{DisplayConfig, DisplayRotation, DisplaySize128x64};
 //     loop {}
 // }
 
+/// GPIO 中断：core1 上是反应游戏的两个按键，core0 上是脉冲计数(按键唤醒的标志也在那里清掉)
+#[interrupt]
+fn IO_IRQ_BANK0() {
+    match rp2040_hal::sio::Sio::core() {
+        // core0 的处理函数耗时算忙，不算空闲(见 cpu_load.rs)
+        rp2040_hal::sio::CoreId::Core0 => cpu_load::isr(pulse_counter::on_gpio_interrupt),
+        rp2040_hal::sio::CoreId::Core1 => reaction::on_gpio_interrupt(),
+    }
+}
+
+/// 是否已经在 panic 里了。画死机画面的时候再 panic 就不画了，免得无限递归
+static PANICKING: AtomicBool = AtomicBool::new(false);
+
+/// panic 的时候除了通过 defmt 打印，还把原因画到屏幕上(堆用完了显示 OOM)。
+/// 打开 `panic-restore` 的话改成把最后一帧完整的画面发回去，右上角标 FAULT
+///
+/// 这时候原来的外设对象都拿不到了，只能 steal 出来重新初始化 I2C，用最简单的阻塞接口画一屏
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    cortex_m::interrupt::disable();
+    defmt::error!("{}", defmt::Display2Format(info));
+
+    if !PANICKING.load(Ordering::Relaxed) {
+        PANICKING.store(true, Ordering::Relaxed);
+        show_panic_screen(info);
+    }
+
+    // 画完再喂一次狗，死机画面至少停一个看门狗超时。计数器每微秒减 2(RP2040-E1)，和 `Watchdog::start` 算的一样
+    // 安全性：只写 LOAD，看门狗没启动的话没有影响
+    unsafe { (*pac::WATCHDOG::ptr()).load().write(|w| w.bits(WATCHDOG_TIMEOUT_MS * 1000 * 2)) };
+    // 和 panic-probe 一样用 udf 触发 HardFault，probe-rs 看到就会退出。调试器停住 CPU 的时候看门狗也跟着停
+    // (CTRL 的 PAUSE_DBG 复位以后就是开着的)，所以接着调试器不会复位；没接的话停在 HardFault 里等看门狗复位，
+    // 启动计数加一(见 boot_loop.rs)
+    cortex_m::asm::udf()
+}
+
+fn show_panic_screen(info: &PanicInfo) {
+    // 安全性：已经 panic 了，原来的外设对象不会再被使用
+    let mut pac = unsafe { pac::Peripherals::steal() };
+    // DMA 可能正在往 I2C 里搬数据，先全部停掉
+    pac.DMA.chan_abort().write(|w| unsafe { w.bits(0xFFF) });
+
+    let sio = rp2040_hal::sio::Sio::new(pac.SIO);
+    let pins = Pins::new(pac.IO_BANK0, pac.PADS_BANK0, sio.gpio_bank0, &mut pac.RESETS);
+    let (block, mut sda, mut scl) = oled_i2c!(pac, pins);
+    configure_i2c_pad(&mut sda);
+    configure_i2c_pad(&mut scl);
+    // init_clocks_and_plls 默认把系统时钟配到 125MHz(16MHz 晶振是 124MHz，差一点不影响)
+    let (baud, system_freq) = i2c_timing(125.MHz());
+    let i2c = I2C::new_controller(block, sda, scl, baud, &mut pac.RESETS, system_freq);
+    let interface = I2CDisplayInterface::new_custom_address(i2c, OLED_I2C_ADDRESS);
+    #[cfg(feature = "trace_i2c")]
+    let interface = TraceI2c::new(interface, OLED_I2C_ADDRESS);
+    // 有完整的最后一帧就发回去标上 FAULT，原因只在 defmt 日志里(见 shadow_frame.rs)
+    #[cfg(feature = "panic-restore")]
+    if let Some(frame) = shadow_frame::last_good() {
+        let _ = frame.restore(interface);
+        return;
+    }
+    let rotation = Settings::load().unwrap_or_default().rotation();
+    // 先画好再初始化：flush 的时候才发初始化序列，屏幕亮起来就是完整的一屏
+    let mut display = LazyDisplay::new(Display::new(interface, rotation));
+    let _ = draw_panic_screen(&mut display, info);
+}
+
+/// 屏幕 I2C 的波特率，和算分频用的系统时钟频率
+#[cfg(not(feature = "no-xosc"))]
+fn i2c_timing(system_freq: HertzU32) -> (HertzU32, HertzU32) {
+    (400.kHz(), system_freq)
+}
+
+/// 没晶振的时候按 ROSC 最快的频率算分频，总线只会比 100kHz 慢，不会快
+#[cfg(feature = "no-xosc")]
+fn i2c_timing(_system_freq: HertzU32) -> (HertzU32, HertzU32) {
+    (rosc_clock::I2C_BAUD, rosc_clock::RANGE.max_freq())
+}
+
 #[entry]
 fn main() -> ! {
     info!("Program start");
+    // 打开了 alloc feature 才有堆，没打开的话这行什么都不做
+    heap::init();
     // 'pac'是一个模块,通常由外设访问层(Peripheral Access Crate)生成工具自动生成.
     // pac 模块包含了对微控制器所有外设的定义和访问接口。所以我们后续要使用I2C连接OLED显示屏的时候就是一种外设控制，所以要使用pac模块
     // 至于Peripherals 是一个代表微控制器外设的结构体，在该代码中则代表rp2040的外设。take方法则是一种实例化方式。
@@ -49,7 +438,12 @@ fn main() -> ! {
     // 具体在硬件层面我们看两个步骤
     // 1. pac.WATCHDOG 代表寄存器块，该块包含了控制看门狗定时器的各种寄存器
     // 2. Watchdog::new() 是一个初始化过程，主要包括设置初始的计数值,配置看门狗的超时行为等
+    // 上一次为什么重启要趁看门狗还没交出去先读，开机那条事件里要用
+    let reset_cause = ResetCause::read(&pac.WATCHDOG, &pac.VREG_AND_CHIP_RESET);
     let mut watchdog = rp2040_hal::watchdog::Watchdog::new(pac.WATCHDOG);
+    // 连着重启太多次就进恢复模式，不读设置，见 boot_loop.rs
+    let boots = boot_loop::record_boot(&mut watchdog);
+    let recovery = boot_loop::is_boot_loop(boots);
     // 那么问题来了？ watchdog 是怎么解决系统死锁和系统恢复的呢？
     // 实际上我们有一个“喂狗”的概念在里面，可以初步理解为通过一个赋值的动作代表喂狗，如果某个超时时间内没有触发喂狗操作，则认为死锁。于是触发复位。
     // 所以我们系统运行的过程中要喂狗----喂狗----喂狗
@@ -58,7 +452,7 @@ fn main() -> ! {
     // sio提供了一些常用的功能模块，比如GPIO控制、内部计时器等。这些模块通常可以更快速的访问硬件资源
     // 最简单的理解就是如果在RP2040中想操作GPIO引脚，使用SIO寄存器就对了
     // SIO 可以做很多事比如1. GPIO控制 2. 内部计时器 3. 互斥和锁
-    let sio = rp2040_hal::sio::Sio::new(pac.SIO);
+    let mut sio = rp2040_hal::sio::Sio::new(pac.SIO);
 
     // 我们后续的操作要利用到GPIO引脚，而pin则代表了RP2040上面的GPIO引脚的配置，而下面的参数我将一一介绍
     // 1. IO_BANK0 是控制虽有GPIO引脚的输入输出功能、中断配置等
@@ -68,17 +462,27 @@ fn main() -> ! {
     let pins = Pins::new(pac.IO_BANK0,pac.PADS_BANK0,sio.gpio_bank0,& mut pac.RESETS);
     // 综上所属Pins::new 的初始化是整合了针对GPIO的通用操作的权限，保证后续针对GPIO的操作都可以使用pins去控制
 
+    // 启动模式跳线，接在哪个引脚见 board.rs。先配成上拉输入，等定时器起来了再消抖读取
+    let mut strap = BootStrap::new(boot_strap_pins!(pins));
+    // 按键也一样，接线见 board.rs
+    let mut buttons = ButtonPad::new(button_pins!(pins));
+
     // scl 和sda 是I2C协议中的两根线(剩下两根是VCC和GND)
     // scl 代表串行时钟线，由主设备生成的时钟信号，用于同步数据传输
     // sda 代表串行数据线，用于传输数据，主设备和从设备共用这条线
     // 流程是主设备通过SCL生成时钟信号，通过SDA发送或者接受数据。多种设备共享这两条线的时候通过设备地址进行区分
 
-    let scl_pin  = pins.gpio5.reconfigure::<FunctionI2C,PullUp>();
-    let sda_pin = pins.gpio4.reconfigure::<FunctionI2C,PullUp>();
+    // 接在哪个 I2C 块、哪两个引脚由板子型号决定，见 board.rs 和 boards.rs
+    let (i2c_block, mut sda_pin, mut scl_pin) = oled_i2c!(pac, pins);
+    configure_i2c_pad(&mut sda_pin);
+    configure_i2c_pad(&mut scl_pin);
+    debug_assert_eq!((sda_pin.id().num, scl_pin.id().num), (BOARD.i2c_sda, BOARD.i2c_scl));
+    info!("board: {}", BOARD.name);
 
-    let external_xtal_freq_hz = 12_000_000u32;
+    // 晶振频率也跟着板子走，Pico 是 12MHz
+    #[cfg(not(feature = "no-xosc"))]
     let clocks = init_clocks_and_plls(
-        external_xtal_freq_hz,
+        BOARD.xosc_hz,
         pac.XOSC,
         pac.CLOCKS,
         pac.PLL_SYS,
@@ -88,43 +492,2477 @@ fn main() -> ! {
     )
         .ok()
         .unwrap();
+    // 没焊晶振的板子：全部跑在 ROSC 上，频率不准，见 rosc_clock.rs
+    #[cfg(feature = "no-xosc")]
+    let clocks = rosc_clock::init_clocks(pac.ROSC, pac.CLOCKS, &mut watchdog).unwrap();
+    // 看门狗的 tick 要等参考时钟起来，所以时钟配好了才启动。从这里开始哪一步卡住超过超时都会复位
+    watchdog.start(MicrosDurationU32::millis(WATCHDOG_TIMEOUT_MS));
+
+    // 硬件定时器，用来做毫秒级的延时(比如开机横幅停留两秒)
+    let mut timer = Timer::new(pac.TIMER, &mut pac.RESETS, &clocks);
+    // 时钟已经跑快了，刷得快一点；这时候栈还浅，见 stack.rs
+    stack::paint();
+    // 装一份给中断和各个模块读时间用(事件日志之类)，见 shared.rs
+    shared::install_timer(timer);
+    // 碰屏幕之前先确认时钟、内存这些都正常，有问题就死机，死机画面上会显示是哪一项没过
+    if let Err(err) = preflight(&pac.RESETS, &clocks) {
+        error!("preflight failed: {}", err);
+        panic!("preflight: {}", err);
+    }
+    let boot_mode = strap.detect(&mut timer);
+    info!("boot mode: {}", boot_mode);
     // 实际上开始初始化I2C外设
-    let i2c = I2C::i2c0(
-        pac.I2C0,
+    let (i2c_baud, i2c_system_freq) = i2c_timing(clocks.system_clock.freq());
+    if I2C_INTERNAL_PULLUPS && i2c_baud.to_Hz() > INTERNAL_PULLUP_MAX_HZ {
+        warn!(
+            "I2C at {} kHz relies on the weak internal pull-ups; fit 2.2k-4.7k external pull-ups and build with external-i2c-pullups",
+            i2c_baud.to_kHz()
+        );
+    }
+    let i2c = I2C::new_controller(
+        i2c_block,
         sda_pin,
         scl_pin,
-        400.kHz(), // 指定时钟频率为400.kHZ
+        i2c_baud, // 有晶振的时候是 400kHZ
         &mut pac.RESETS,
-        clocks.system_clock.freq(),
+        i2c_system_freq,
     );
 
     // 这行就是用上面的i2c去初始化我们显示屏显示的interface
-    // I2cDisplayInterface 是一个抽象的I2C显示的接口
-    // 有许多显示的协议支持这个接口，比如下面要用的ssd1306
-    let interface = I2CDisplayInterface::new(i2c);
+    // 以前用的是 ssd1306 自带的 I2CDisplayInterface，每次 flush 都要 CPU 一个字节一个字节地塞进 I2C，
+    // 整屏要二十多毫秒，这期间什么都干不了。现在换成自己的 DmaI2c：命令还是阻塞发送，显存数据交给 DMA 搬运
+    // DMA 有 12 个通道，这里拿 0 号通道专门给屏幕用；暂存区要 'static，所以也放到静态区
+    let dma = pac.DMA.split(&mut pac.RESETS);
+    let staging = cortex_m::singleton!(: StagingBuffer = [0; STAGING_LEN]).unwrap();
+    let mut interface = DmaI2c::new(i2c, OLED_I2C_ADDRESS, dma.ch0, staging);
+    // 告警灯带：PIO0 的 0 号状态机 + DMA 1 号通道
+    #[cfg(feature = "ws2812")]
+    let strip = {
+        let (mut pio, sm0, _, _, _) = pac.PIO0.split(&mut pac.RESETS);
+        let buffer = cortex_m::singleton!(: StripBuffer<LED_STRIP_LEN> = [0; LED_STRIP_LEN]).unwrap();
+        AlertStrip::new(Ws2812::new(
+            led_strip_pin!(pins),
+            &mut pio,
+            sm0,
+            dma.ch1,
+            buffer,
+            clocks.system_clock.freq().to_Hz(),
+        ))
+    };
+    // 先确认屏幕在总线上应答了，没应答后面的 init 多半也是白发
+    if !interface.probe() {
+        warn!("no display ACK at address {=u8:#x}", OLED_I2C_ADDRESS);
+    }
 
-    // 利用ssd1306包操作OLED，初始化出display对象
-    // rotate0 代表初始化旋转式0度
+    // 初始化出display对象
+    // rotate0 代表初始化旋转式0度，实际的方向下面按设置再改
     // size 是128 * 64 的像素
-    let mut display = Ssd1306::new(interface,DisplaySize128x64,DisplayRotation::Rotate0)
-        .into_buffered_graphics_mode();
+    let mut display = Display::new(interface, DisplayRotation::Rotate0);
+    // 连着重启太多次就直接进恢复模式，屏幕初始化、读设置都不做了(恢复模式自己初始化一次屏幕)，
+    // 万一就是哪个设置、或者屏幕初始化本身把程序带崩的
+    if recovery {
+        warn!("{} boots without settling, recovery mode: stored settings ignored", boots);
+        #[cfg(not(feature = "no-xosc"))]
+        let usb = Some(usb_link(pac.USBCTRL_REGS, pac.USBCTRL_DPRAM, clocks.usb_clock, &mut pac.RESETS));
+        #[cfg(feature = "no-xosc")]
+        let usb = None;
+        change_mode(&mut Mode::Boot, ModeEvent::BootLoop);
+        recovery_mode(display, timer, watchdog, usb, status_led_pin!(pins), reset_cause, boots);
+    }
+    // 读出上次保存的设置(旋转、亮度)，没保存过就用默认值
+    let settings = Settings::load().unwrap_or_else(|err| {
+        info!("no stored settings ({}), using defaults", err);
+        Settings::default()
+    });
     // 初始化显示屏操作
-    display.init().unwrap();
+    // 线长、干扰大的时候 I2C 偶尔会失败一次，重试几次。实在不行就不要屏幕接着启动，主循环里会定时再试(见 display_link.rs)
+    let display_online = with_retry(I2C_ATTEMPTS, I2C_RETRY_DELAY_MS, &mut timer, || display.init())
+        .and_then(|()| settings.apply(&mut display))
+        .is_ok();
+    watchdog.feed();
+    // 产线测试模式不要开机横幅，省下来的时间给检查用
+    if display_online && boot_mode != BootMode::Fixture {
+        // 开机先展示固件名和版本号，确认烧进去的是哪一版，最下面一行是这次的启动模式
+        // 先把对比度压到 0 再画横幅，然后慢慢亮起来，而不是"啪"的一下全亮
+        // 设置里选了开机画面、槽里的图也读得出来才用，不然还是版本号
+        // 没选的话用编译进去的 logo(见 logo.rs)，也没有才是版本号
+        let splash = settings.splash_slot.and_then(|slot| image_slots::load(slot as usize));
+        let logo = if splash.is_none() { LOGO } else { None };
+        let shown = display.send_commands(&command::contrast(0)).and_then(|()| {
+            with_retry(I2C_ATTEMPTS, I2C_RETRY_DELAY_MS, &mut timer, || match (&splash, &logo) {
+                (Some(image), _) => show_splash(&mut display, image, Some(boot_mode.name())),
+                (None, Some(logo)) => show_logo(&mut display, logo, Some(boot_mode.name())),
+                // 版本号画面的启动模式等亮起来以后再一个字一个字打出来
+                (None, None) => show_version_banner(&mut display, None),
+            })
+        });
+        if shown.and_then(|()| fade_in(&mut display, BOOT_FADE_MS, &mut timer)).is_err() {
+            warn!("boot banner failed");
+        }
+        // 横幅停留期间(包括打字的时候)按任意键直接跳过，烧一次等一次太烦了
+        let clock = timer;
+        let now_ms = || clock.get_counter().ticks() / 1000;
+        let mut skip = || {
+            let mut pressed = false;
+            buttons.poll(now_ms(), |event| pressed |= matches!(event, ButtonEvent::Pressed(_)));
+            pressed
+        };
+        let typed = if splash.is_some() || logo.is_some() {
+            Ok(false)
+        } else {
+            type_subtitle(&mut display, boot_mode.name(), &mut timer, &mut skip)
+        };
+        let skipped = typed.and_then(|skipped| {
+            if skipped {
+                return Ok(true);
+            }
+            hold_banner(&mut display, &mut timer, DEFAULT_BANNER_DURATION_MS - BOOT_FADE_MS, &mut skip)
+        });
+        match skipped {
+            Ok(true) => {
+                info!("boot banner skipped");
+                wait_buttons_idle(&mut buttons, &mut timer, &watchdog);
+            }
+            Ok(false) => {}
+            Err(_) => warn!("boot banner failed"),
+        }
+    } else if !display_online {
+        // 没有屏幕也接着跑：传感器、记录、告警照常，USB 串口强制打开(可以用 SCAN 查接线)，板载 LED 闪错误码
+        error!("display init failed, running headless (LED blinks {} times)", blink_code::NO_DISPLAY.pulses());
+    }
 
-    let text_style = MonoTextStyle::new(&FONT_6X10,BinaryColor::On);
+    watchdog.feed();
 
-    // 设置display的图画功能，分别是文案是hello,world。位置是 (0,10)，文字的样式是6 * 10的大小、白色展示
-    Text::new("hello,world",Point::new(0,20),text_style)
-        .draw(&mut display).unwrap();
+    // 后面几步每做完一步推一格进度条。进度条只是给人看的，画不出来也接着启动
+    let mut progress = BootProgress::new(BOOT_STAGES);
 
-    // flush生效显示屏内容显示
-    display.flush().unwrap();
+    // 采样(片内温度 + 一路 ADC)，引脚见 board.rs；BMP280、温湿度、测距传感器和屏幕共用 I2C 总线，没接就算了
+    let adc = Adc::new(pac.ADC, &mut pac.RESETS);
+    // 话筒电平表的数据，打开 mic-vu 的时候采样器往里喂，电平表页面来读
+    let vu_meter: &'static RefCell<VuMeter> = cortex_m::singleton!(: RefCell<VuMeter> = RefCell::new(VuMeter::new())).unwrap();
+    #[cfg(not(feature = "mic-vu"))]
+    let sampler = Sampler::new(adc, sampler_adc_pin!(pins), vsys_adc_pin!(pins));
+    // 打开 mic-vu 的话 ADC 一直连续转换，温度和 VSYS 也从话筒的样本流里取(见 mic.rs)
+    #[cfg(feature = "mic-vu")]
+    let sampler = {
+        let adc = cortex_m::singleton!(: Adc = adc).unwrap();
+        let buffers = cortex_m::singleton!(: [MicBuffer; 2] = [[0; MIC_BUFFER_LEN]; 2]).unwrap();
+        Sampler::new(adc, sampler_adc_pin!(pins), vsys_adc_pin!(pins), dma.ch2, buffers, clocks.adc_clock.freq().to_Hz(), vu_meter)
+    };
+    let baro = display
+        .shared_bus()
+        .and_then(|bus| Bmp280::probe(bus, settings.sea_level_pa));
+    if baro.is_none() {
+        info!("no BMP280, barometer disabled");
+    }
+    let humidity = display
+        .shared_bus()
+        .and_then(|bus| humidity::detect(bus, &mut timer))
+        .map(HumidityMonitor::new);
+    if humidity.is_none() {
+        info!("no SHT31/AHT20, humidity disabled");
+    }
+    let distance = display
+        .shared_bus()
+        .and_then(|bus| Vl53l0x::probe(bus, &mut timer))
+        .map(DistanceMonitor::new);
+    if distance.is_none() {
+        info!("no VL53L0X, distance disabled");
+    }
+    let mut rtc = display.shared_bus().and_then(Ds3231::probe);
+    match (rtc.as_mut(), display.shared_bus()) {
+        (Some(rtc), Some(bus)) => match (rtc.read_time(bus), rtc.read_temperature(bus)) {
+            (Some(time), temp) => info!("DS3231 time {}, temperature {} (0.01 C)", time, temp),
+            (None, _) => warn!("DS3231 lost power, time not set"),
+        },
+        _ => info!("no DS3231, clock runs on the timer"),
+    }
+    // I2C 线诊断的 SCL 分压点，接法见 i2c_lines.rs。打开 mic-vu 的时候 ADC 腾不出来，不用
+    #[cfg(not(feature = "mic-vu"))]
+    let mut hub = SensorHub { sampler, baro, humidity, distance, rtc, vsys_correction: Correction::IDENTITY, scl_tap: scl_tap_adc_pin!(pins) };
+    #[cfg(feature = "mic-vu")]
+    let mut hub = SensorHub { sampler, baro, humidity, distance, rtc, vsys_correction: Correction::IDENTITY };
+
+    // 产线测试模式从这里分出去，不进主循环(见 fixture.rs)
+    if boot_mode == BootMode::Fixture {
+        #[cfg(not(feature = "no-xosc"))]
+        let usb = Some(usb_link(pac.USBCTRL_REGS, pac.USBCTRL_DPRAM, clocks.usb_clock, &mut pac.RESETS));
+        #[cfg(feature = "no-xosc")]
+        let usb = None;
+        fixture_mode(display, timer, watchdog, hub, buttons, usb, fixture_result_pin!(pins));
+    }
+
+    // 脉冲计数(流量计、雨量计)，接线见 board.rs，总数从 flash 里读出来接着数(见 pulse_counter.rs)
+    let pulse_saved = pulse_counter::load_total();
+    pulse_counter::start(pulse_input_pin!(pins).id().num, settings.pulse, pulse_saved);
 
-    // 这行就需要好好理解了
-    // 下面需要做的事是让微控制器进入低功耗状态，直到发生中断为止。具体来说它使用了ARM-Cortex-M 内核的wfi(Wait-for-Interrupt)指令
-    // 节能啊
+    // 传感器通道，编号就是注册的顺序，告警规则按编号存，所以新传感器只能往后加
+    let mut registry = SensorRegistry::new();
+    let temp_channel = registry.register(ChannelInfo::new("temp", "C", 100), TEMP_POLL_MS, |hub: &mut SensorHub<_, _>| hub.sampler.temp_centi()).unwrap();
+    let adc0_channel = registry.register(ChannelInfo::new("adc0", "V", 1000), ADC0_POLL_MS, |hub: &mut SensorHub<_, _>| hub.sampler.adc0_mv()).unwrap();
+    registry.register(ChannelInfo::new("vsys", "V", 1000), VSYS_POLL_MS, |hub: &mut SensorHub<_, _>| hub.sampler.vsys_mv());
+    // BMP280 的读数是主循环读好放在 hub 里的，这里只是拿出来
+    let baro_channels = hub.baro.is_some().then(|| {
+        let pressure = registry.register(ChannelInfo::new("baro", "hPa", 100), BARO_POLL_MS, |hub: &mut SensorHub<_, _>| hub.baro.as_ref()?.pressure_pa());
+        let temperature = registry.register(ChannelInfo::new("btemp", "C", 100), BARO_POLL_MS, |hub: &mut SensorHub<_, _>| hub.baro.as_ref()?.temp_centi());
+        registry.register(ChannelInfo::new("alt", "m", 10), BARO_POLL_MS, |hub: &mut SensorHub<_, _>| hub.baro.as_ref()?.altitude_dm());
+        // 32 个历史值正好盖住 3 小时，气压趋势就从这里算
+        let pressure = pressure.unwrap();
+        registry.set_history_interval(pressure, TREND_WINDOW_MS / (HISTORY_LEN as u32 - 1));
+        BaroChannels { pressure, temperature: temperature.unwrap() }
+    });
+    let comfort_channels = hub.humidity.as_ref().map(|monitor| {
+        let humidity = registry.register(ChannelInfo::new("hum", "%", 100), humidity::POLL_MS, |hub: &mut SensorHub<_, _>| hub.humidity.as_ref()?.humidity_centi());
+        let temperature = registry.register(ChannelInfo::new("htemp", "C", 100), humidity::POLL_MS, |hub: &mut SensorHub<_, _>| hub.humidity.as_ref()?.temp_centi());
+        ComfortChannels { name: monitor.name(), temperature: temperature.unwrap(), humidity: humidity.unwrap() }
+    });
+    let distance_channel = hub.distance.is_some().then(|| {
+        registry.register(ChannelInfo::new("dist", "mm", 1), vl53l0x::AWAKE_POLL_MS, |hub: &mut SensorHub<_, _>| hub.distance.as_ref()?.distance_mm().map(i32::from)).unwrap()
+    });
+    // 电池电量是从 VSYS 估出来的，只对直接接一节锂电池的接法有意义(见 battery.rs)
+    let battery_channel = registry.register(ChannelInfo::new("batt", "%", 1), VSYS_POLL_MS, |hub: &mut SensorHub<_, _>| hub.sampler.vsys_mv().map(|mv| estimate_battery_percent(hub.vsys_correction.apply(mv).clamp(0, u16::MAX as i32) as u16) as i32));
+    // 空闲百分比是主循环自己每秒算好的(见 cpu_load.rs)，这里只是拿出来
+    registry.register(ChannelInfo::new("idle", "%", 1), cpu_load::WINDOW_US / 1000, |_: &mut SensorHub<_, _>| cpu_load::idle_percent().map(i32::from));
+    // 脉冲计数的总量和每小时速率是中断里数好的，单位跟着设置走
+    let pulse_channels = (
+        registry.register(ChannelInfo::new("pulse", settings.pulse.unit_name(), 1000), PULSE_POLL_MS, |_: &mut SensorHub<_, _>| Some(pulse_counter::amount_milli())).unwrap(),
+        registry.register(ChannelInfo::new("rate", settings.pulse.rate_unit(), 1000), PULSE_POLL_MS, |_: &mut SensorHub<_, _>| pulse_counter::rate_per_hour_milli()).unwrap(),
+    );
+    let channels = registry.infos();
+    apply_calibration(&mut registry, &mut hub, &settings.calibration);
+    let sensors = RefCell::new(registry);
+    let _ = progress.advance("sensors", &mut display);
+    // 串口 PREVIEW 收的图，只在内存里(见 devtools.rs)。两张图 2K 多，不放在栈上
+    #[cfg(feature = "devtools")]
+    let preview: &'static RefCell<Preview> = cortex_m::singleton!(: RefCell<Preview> = RefCell::new(Preview::new())).unwrap();
+
+    // 每个页面画一次要多久，名字和下面的页面顺序一致
+    let perf = RefCell::new(PerfTable::new(["dashboard", "host", "diagnostics", "ball", "log", "outputs", "dimming", "alarms", "baro", "comfort", "distance", "remote", "settings", "carousel", "perf", "popup", "stars", "demos", "reader", "vu", "fft", "servo", "events", "bus", "calibrate", "reset cal", "face", LifePage::NAME, AnalogClockPage::NAME, "power off", "splash", MazePage::NAME, "reaction", "large text", "wake alarms", "ringing", "profile", "counter", "gray", "theme", CapabilitiesPage::NAME, "inputs", "time zone", #[cfg(feature = "devtools")] "preview"]));
+
+    // 所有页面都放在这里，由调度器统一管理；启动模式决定最底下是哪一页
+    let mut dashboard = DashboardPage::new(DIAGNOSTICS_PAGE, LOG_PAGE, OUTPUTS_PAGE, BARO_PAGE, &sensors, battery_channel);
+    let mut host = HostStatus::new();
+    let mut diagnostics = DiagnosticsPage::new(&sensors, [temp_channel, adc0_channel], BALL_PAGE, SETTINGS_MENU_PAGE, ALARM_PAGE);
+    let mut ball = BouncingBallPage::new(STARFIELD_PAGE);
+    let mut log_page = LogPage::new(&channels);
+    // 输出控制菜单，引脚见 board.rs
+    let mut outputs = OutputControl::new(output_pins!(pins));
+    let mut dim_page = DimPage::new(settings.dim_schedule);
+    let mut alarm_page = AlarmPage::new(settings.alarm_rules, channels.clone());
+    // 气压计、温湿度、距离三个页面之间用 Up/Down 转圈切
+    let mut baro_page = BaroPage::new(&sensors, baro_channels, DISTANCE_PAGE, COMFORT_PAGE, settings.sea_level_pa);
+    let mut comfort_page = ComfortPage::new(&sensors, comfort_channels, BARO_PAGE, DISTANCE_PAGE);
+    let mut distance_page = DistancePage::new(&sensors, distance_channel, COMFORT_PAGE, BARO_PAGE);
+    let mut remote_page = RemoteTextPage::new();
+    let mut settings_menu = SettingsMenu::new("settings", [("dimming", DIM_PAGE), ("alarms", ALARM_PAGE), ("carousel", CAROUSEL_PAGE), ("perf", PERF_PAGE), ("events", EVENTS_PAGE), ("bus", BUS_PAGE), ("calibrate", CALIBRATION_PAGE), ("reset cal", CALIBRATION_RESET_PAGE), ("face", WATCH_FACE_PAGE), ("splash", SPLASH_PAGE), ("large text", LARGE_TEXT_PAGE), ("wake alarms", WAKE_ALARM_PAGE), ("time zone", TIME_ZONE_PAGE), ("profile", PROFILE_PAGE), ("theme", THEME_PAGE), ("inputs", REMAP_PAGE), ("features", FEATURES_PAGE), ("power off", POWER_OFF_PAGE)])
+        .with_actions([("reset counter", Confirm { prompt: "reset counter?", action: Action::ResetPulseCount })]);
+    // 能参加自动轮播的页面；没接的传感器页面和没连电脑的副屏页面轮播的时候会自己跳过
+    let mut carousel_page = CarouselPage::new(settings.carousel, [("dashboard", DASHBOARD_PAGE), ("host", HOST_PAGE), ("diagnostics", DIAGNOSTICS_PAGE), ("log", LOG_PAGE), ("baro", BARO_PAGE), ("comfort", COMFORT_PAGE), ("distance", DISTANCE_PAGE), ("stars", STARFIELD_PAGE), ("face", WATCH_FACE_PAGE)]);
+    let mut perf_page = PerfPage::new(&perf);
+    let mut popup = PopupPage::new();
+    // 随机数的种子取定时器的低位，每次开机走到这里的时间都不太一样
+    let mut starfield = StarfieldPage::new(Rng::new(timer.get_counter().ticks() as u32), BALL_PAGE);
+    // 开机先进演示菜单，Back 回仪表盘；在哪都可以双击 Back 回到这里
+    let mut demo_menu = SettingsMenu::new("demos", [("temp", COMFORT_PAGE), ("graph", LOG_PAGE), ("text", REMOTE_PAGE), ("ball", BALL_PAGE), ("stars", STARFIELD_PAGE), ("self-test", DIAGNOSTICS_PAGE), ("notes", READER_PAGE), ("mic", VU_PAGE), ("fft", SPECTRUM_PAGE), ("servo", SERVO_PAGE), (LifePage::NAME, LIFE_PAGE), (MazePage::NAME, MAZE_PAGE), ("reaction", REACTION_PAGE), (AnalogClockPage::NAME, ANALOG_CLOCK_PAGE), ("counter", PULSE_PAGE), ("gray", GRAY_PAGE)]);
+    let mut reader = ReaderPage::new(include_str!("release_notes.txt"));
+    let mut vu_page = VuPage::new(vu_meter);
+    let mut spectrum_page = SpectrumPage::new(vu_meter);
+    // 舵机和蜂鸣器都用 PWM，接线见 board.rs
+    let pwm_slices = Slices::new(pac.PWM, &mut pac.RESETS);
+    let mut event_page = EventLogPage::new();
+    let mut bus_page = BusPage::new();
+    let mut calibration_page = CalibrationPage::new(&sensors, temp_channel, adc0_channel);
+    let mut calibration_reset_page = CalibrationResetPage::new();
+    let mut watch_face_page = WatchFacePage::new(&sensors, battery_channel, settings.watch_face);
+    let mut life_page = LifePage::new(LifeDemo::new(Rng::new(timer.get_counter().ticks() as u32), Edges::Wrap));
+    let mut maze_page = MazePage::new(MazeDemo::new(Rng::new(timer.get_counter().ticks() as u32 ^ 0x4d41_5a45)));
+    let mut reaction_page = ReactionPage::new();
+    let mut large_text_page = LargeTextPage::new(settings.large_text);
+    let mut wake_alarm_page = WakeAlarmPage::new(settings.wake_alarms);
+    let mut ringing_page = RingingPage::new();
+    let mut analog_clock_page = AnalogClockPage::new(AnalogClock::new(WallClock::new()));
+    let mut power_off_page = PowerOffPage::new();
+    let mut splash_page = SplashPage::new(settings.splash_slot);
+    let mut profile_page = ProfilePage::new(settings.display_profile);
+    let mut pulse_page = PulsePage::new();
+    let mut gray_page = GrayPage::new();
+    let mut theme_page = ThemePage::new();
+    let mut features_page = CapabilitiesPage::new(());
+    let mut remap_page = RemapPage::new(settings.input_bindings);
+    let mut time_zone_page = TimeZonePage::new(settings.timezone);
+    #[cfg(feature = "devtools")]
+    let mut preview_page = PreviewPage::new(preview);
+    let mut servo_page = ServoPage::new(ServoOutput::new(servo_pwm!(pwm_slices, pins), clocks.system_clock.freq().to_Hz()));
+    let root = match boot_mode {
+        BootMode::Dashboard => DASHBOARD_PAGE,
+        BootMode::SmartDisplay => HOST_PAGE,
+        // 产线测试模式上面就分出去了，走不到这里
+        BootMode::Fixture => DASHBOARD_PAGE,
+    };
+    let mut scheduler = Scheduler::new(
+        [&mut dashboard, &mut host, &mut diagnostics, &mut ball, &mut log_page, &mut outputs, &mut dim_page, &mut alarm_page, &mut baro_page, &mut comfort_page, &mut distance_page, &mut remote_page, &mut settings_menu, &mut carousel_page, &mut perf_page, &mut popup, &mut starfield, &mut demo_menu, &mut reader, &mut vu_page, &mut spectrum_page, &mut servo_page, &mut event_page, &mut bus_page, &mut calibration_page, &mut calibration_reset_page, &mut watch_face_page, &mut life_page, &mut analog_clock_page, &mut power_off_page, &mut splash_page, &mut maze_page, &mut reaction_page, &mut large_text_page, &mut wake_alarm_page, &mut ringing_page, &mut profile_page, &mut pulse_page, &mut gray_page, &mut theme_page, &mut features_page, &mut remap_page, &mut time_zone_page, #[cfg(feature = "devtools")] &mut preview_page],
+        root,
+    );
+    if boot_mode == BootMode::Dashboard {
+        scheduler.apply(Transition::Push(DEMO_MENU_PAGE));
+    }
+    scheduler.set_perf(&perf);
+    scheduler.set_large_text(settings.large_text);
+    scheduler.set_theme(settings.theme);
+    scheduler.set_frame_pacing(settings.frame_pacing.then_some(command::REFRESH_PERIOD_US));
+    let _ = progress.advance("pages", &mut display);
+
+    // 蜂鸣器，接线见 board.rs
+    let buzzer = PwmBuzzer::new(buzzer_pwm!(pwm_slices, pins), clocks.system_clock.freq().to_Hz());
+
+    // 数据记录，flash 分区见 flash.rs
+    let datalog = DataLog::open();
+    info!("data log: {} slots, boot #{}", datalog.index().slots(), datalog.boot());
+    event_log::record(SystemEvent::Boot { count: datalog.boot(), cause: reset_cause });
+    let _ = progress.advance("data log", &mut display);
+
+    // 反应游戏的计时放在 core1 上(见 reaction.rs)，起来以后停在 RAM 里等命令，不耽误写 flash
+    let core1_stack = cortex_m::singleton!(: Stack<{ reaction::CORE1_STACK_WORDS }> = Stack::new()).unwrap();
+    let players = [buttons.gpio(Button::Up).unwrap(), buttons.gpio(Button::Down).unwrap()];
+    rp2040_i2c_oled_rust::flash::set_core1_busy(true);
+    let mut multicore = Multicore::new(&mut pac.PSM, &mut pac.PPB, &mut sio.fifo);
+    if multicore.cores()[1].spawn(&mut core1_stack.mem, move || reaction::timing_core(players, timer)).is_err() {
+        rp2040_i2c_oled_rust::flash::set_core1_busy(false);
+        warn!("core1 failed to start, reaction game has no timer");
+    }
+
+    // 只有电脑副屏模式才需要 USB；没有屏幕的时候也打开，不然就没法知道板子在干什么了
+    #[cfg(not(feature = "no-xosc"))]
+    let usb = match boot_mode {
+        BootMode::Dashboard if display_online => None,
+        _ => Some(usb_link(pac.USBCTRL_REGS, pac.USBCTRL_DPRAM, clocks.usb_clock, &mut pac.RESETS)),
+    };
+    // 没有晶振就没有准的 48MHz，USB 用不了
+    #[cfg(feature = "no-xosc")]
+    let usb: Option<UsbLink<'static>> = None;
+    let _ = progress.advance("usb", &mut display);
+
+    // 开机硬件小结：defmt 打一块，屏幕上停几秒(按任意键跳过)，串口 SYSINFO 也打这个，见 system_info.rs
+    let system_info = SystemInfo {
+        board: BOARD.name,
+        #[cfg(not(feature = "no-xosc"))]
+        xosc_hz: Some(BOARD.xosc_hz),
+        #[cfg(feature = "no-xosc")]
+        xosc_hz: None,
+        sys_hz: clocks.system_clock.freq().to_Hz(),
+        peri_hz: clocks.peripheral_clock.freq().to_Hz(),
+        i2c_block: BOARD.i2c_block,
+        i2c_sda: BOARD.i2c_sda,
+        i2c_scl: BOARD.i2c_scl,
+        i2c_hz: i2c_baud.to_Hz(),
+        display_address: OLED_I2C_ADDRESS,
+        controller: "SSD1306",
+        panel: PANEL.name,
+        display_ok: display_online,
+        baro: hub.baro.is_some().then_some("BMP280"),
+        humidity: hub.humidity.as_ref().map(|monitor| monitor.name()),
+        distance: hub.distance.is_some().then_some("VL53L0X"),
+        usb: if usb.is_some() { UsbState::Waiting } else { UsbState::Off },
+        free_ram: system_info::free_ram_bytes(),
+    };
+    system_info.log();
+    capabilities::log();
+    if display_online {
+        watchdog.feed();
+        let clock = timer;
+        let now_ms = || clock.get_counter().ticks() / 1000;
+        let shown = system_info.draw(&mut display).and_then(|()| {
+            hold_banner(&mut display, &mut timer, system_info::SCREEN_MS, || {
+                let mut pressed = false;
+                buttons.poll(now_ms(), |event| pressed |= matches!(event, ButtonEvent::Pressed(_)));
+                pressed
+            })
+        });
+        match shown {
+            Ok(true) => wait_buttons_idle(&mut buttons, &mut timer, &watchdog),
+            Ok(false) => {}
+            Err(_) => warn!("system info screen failed"),
+        }
+    }
+
+    // Pico W 上取状态灯要起无线芯片的驱动，最多几秒(见 wireless.rs)
+    watchdog.feed();
+    #[allow(unused_mut)]
+    let mut status_led = status_led_pin!(pins);
+    // 无线芯片起来了就顺便开网络，连哪个 Wi-Fi 见 wifi_config.rs
+    #[cfg(all(feature = "pico-w", not(feature = "board-custom")))]
+    let mut network = status_led.as_mut().and_then(|led| led.start_network());
+    #[cfg(not(all(feature = "pico-w", not(feature = "board-custom"))))]
+    let mut network: Option<NetLink> = None;
+    if let Some(net) = network.as_mut() {
+        net.join(wifi_config::load());
+    }
+    let devices = Devices {
+        buttons,
+        status_led,
+        usb,
+        network,
+        buzzer,
+        hub,
+        datalog,
+        fifo: sio.fifo,
+        watchdog,
+        system_info,
+        pulse_saved,
+        pulse_channels,
+        #[cfg(feature = "devtools")]
+        preview,
+        #[cfg(feature = "ws2812")]
+        strip,
+    };
+    run(display, timer, scheduler, devices, &sensors, &perf, settings)
+}
+
+/// 等按键都松开再往下走，不然跳过开机画面的那一下会被主循环当成长按或者松开事件交给页面。
+/// 一直按着不放也等，边等边喂狗
+fn wait_buttons_idle<const B: usize>(buttons: &mut ButtonPad<B>, timer: &mut Timer, watchdog: &Watchdog) {
+    while !buttons.is_idle() {
+        watchdog.feed();
+        buttons.poll(timer.get_counter().ticks() / 1000, |_| {});
+        timer.delay_ms(1);
+    }
+}
+
+/// USB 设备(CDC 串口 + HID)。UsbBusAllocator 要一直活着(设备和各个接口都借用它)，所以用 singleton! 放到静态区
+#[cfg(not(feature = "no-xosc"))]
+fn usb_link(regs: pac::USBCTRL_REGS, dpram: pac::USBCTRL_DPRAM, clock: rp2040_hal::clocks::UsbClock, resets: &mut pac::RESETS) -> UsbLink<'static> {
+    let usb_bus = cortex_m::singleton!(: UsbBusAllocator<UsbBus> = UsbBusAllocator::new(UsbBus::new(regs, dpram, clock, true, resets))).unwrap();
+    UsbLink::new(usb_bus)
+}
+
+/// 恢复模式重启之前等多久(毫秒)，让 OK 先发出去
+const RECOVERY_REBOOT_DELAY_MS: u64 = 200;
+
+/// 恢复模式(见 boot_loop.rs)：只显示恢复画面、闪 LED、等串口的 `SETTINGS RESET`，
+/// 收到就写默认设置、启动计数清零、重启。别的外设都不碰；屏幕只按默认设置初始化一次，不重试
+fn recovery_mode(mut display: OledDisplay, timer: Timer, mut watchdog: Watchdog, mut usb: Option<UsbLink<'static>>, mut status_led: Option<BoardLed>, reset_cause: ResetCause, boots: u16) -> ! {
+    if display.init().and_then(|()| draw_recovery_screen(&mut display, reset_cause.name(), boots)).is_err() {
+        warn!("recovery screen failed");
+    }
+    let mut console = Console::new();
+    let mut serial_rx = [0u8; 64];
+    let mut reboot_at_ms = None;
+    loop {
+        watchdog.feed();
+        let now_ms = timer.get_counter().ticks() / 1000;
+        if let Some(led) = status_led.as_mut() {
+            led.set_on(blink_code::RECOVERY.level(now_ms));
+        }
+        if reboot_at_ms.is_some_and(|at| now_ms >= at) {
+            boot_loop::mark_healthy(&mut watchdog);
+            power_off::reboot();
+        }
+        let Some(usb) = usb.as_mut() else {
+            continue;
+        };
+        usb.poll();
+        let received = usb.read_serial(&mut serial_rx);
+        for &byte in &serial_rx[..received] {
+            match console.feed(byte) {
+                None => {}
+                Some(ConsoleCommand::SettingsReset) => {
+                    Settings::default().store();
+                    let _ = write!(usb, "OK rebooting\r\n");
+                    reboot_at_ms = Some(now_ms + RECOVERY_REBOOT_DELAY_MS);
+                }
+                Some(_) => {
+                    let _ = write!(usb, "ERR recovery mode, only SETTINGS RESET\r\n");
+                }
+            }
+        }
+    }
+}
+
+/// 产线测试模式(见 fixture.rs)：每一项带着超时查一遍，结果从引脚、串口、屏幕报出来，然后一直停在这里。
+/// USB 放在最后查，一边等枚举一边轮询。结果要一直停着给产线看，所以先把看门狗关掉
+fn fixture_mode<const B: usize, P, V>(mut display: OledDisplay, timer: Timer, watchdog: Watchdog, mut hub: SensorHub<P, V>, mut buttons: ButtonPad<B>, mut usb: Option<UsbLink<'static>>, mut result_pin: OutputPin) -> !
+where
+    P: SamplerPin,
+    V: SamplerPin,
+{
+    watchdog.disable();
+    let now_ms = || timer.get_counter().ticks() / 1000;
+    let mut report = Report::new(now_ms());
+    let online = display.shared_bus().is_some_and(|bus| health::display_present(bus, OLED_I2C_ADDRESS));
+    report.record("display", online, format_args!("addr={:#04x}", OLED_I2C_ADDRESS));
+    match check_bus(&mut display, &mut hub, online, &watchdog) {
+        Some(bus) => {
+            report.record("bus", !bus.addresses.is_empty(), format_args!("{} devices", bus.addresses.len()));
+            if let Some(lines) = bus.lines {
+                let mut summary: String<{ fixture::LINE_MAX }> = String::new();
+                let _ = lines.write_summary(&mut summary);
+                report.record("scl", lines.verdict == LineVerdict::Healthy, format_args!("{}", summary));
+            }
+        }
+        None => report.record("bus", false, format_args!("bus busy")),
+    }
+    for &name in FIXTURE_SENSORS {
+        let found = match name {
+            "baro" => hub.baro.is_some(),
+            "humidity" => hub.humidity.is_some(),
+            "distance" => hub.distance.is_some(),
+            "rtc" => hub.rtc.is_some(),
+            _ => false,
+        };
+        report.record(name, found, format_args!("{}", if found { "found" } else { "missing" }));
+    }
+    match fixture::wait_for(report.timeout_ms(fixture::READ_TIMEOUT_MS, now_ms()), now_ms, || hub.sampler.temp_centi()) {
+        Some(centi) => report.record("temp", fixture::TEMP_RANGE_CENTI.contains(&centi), format_args!("{}C", centi / 100)),
+        None => report.record("temp", false, format_args!("no reading")),
+    }
+    match fixture::wait_for(report.timeout_ms(fixture::READ_TIMEOUT_MS, now_ms()), now_ms, || hub.sampler.vsys_mv()) {
+        Some(mv) => report.record("vsys", fixture::VSYS_RANGE_MV.contains(&mv), format_args!("{}mV", mv)),
+        None => report.record("vsys", false, format_args!("no reading")),
+    }
+    // 测试架上没人按键，按下去的就是短路了或者焊错了
+    let mut stuck: String<{ fixture::LINE_MAX }> = String::new();
+    for button in [Button::Up, Button::Down, Button::Select, Button::Back] {
+        if buttons.is_down_raw(button) {
+            let _ = write!(stuck, " {:?}", button);
+        }
+    }
+    report.record("buttons", stuck.is_empty(), format_args!("{}", if stuck.is_empty() { "released" } else { stuck.trim_start() }));
+    // 新烧的板子设置区还是空的，空的也算过
+    let flash_ok = match Settings::load() {
+        Ok(_) => Ok("settings ok"),
+        Err(_) if flash::read(flash::SETTINGS_OFFSET, BLOB_CAPACITY).iter().all(|&byte| byte == 0xFF) => Ok("blank"),
+        Err(err) => Err(err.message()),
+    };
+    match flash_ok {
+        Ok(detail) => report.record("flash", true, format_args!("{}", detail)),
+        Err(detail) => report.record("flash", false, format_args!("{}", detail)),
+    }
+    if let Some(usb) = usb.as_mut() {
+        let configured = fixture::wait_for(report.timeout_ms(fixture::USB_TIMEOUT_MS, now_ms()), now_ms, || {
+            usb.poll();
+            usb.is_configured().then_some(())
+        });
+        report.record("usb", configured.is_some(), format_args!("{}", if configured.is_some() { "configured" } else { "not enumerated" }));
+    }
+    report.finish(now_ms());
+    let passed = report.passed();
+    info!("fixture: {} {}/{} in {} ms", if passed { "PASS" } else { "FAIL" }, report.passed_count(), report.checks().len(), report.elapsed_ms());
+    for check in report.checks() {
+        info!("  {=str} {=bool} {=str}", check.name, check.passed, check.detail.as_str());
+    }
+    if render_verdict(&mut display, &report).is_err() {
+        warn!("fixture verdict screen failed");
+    }
+    let mut serial_rx = [0u8; 64];
+    // 串口接下来打第几行。电脑那边可能枚举完了才打开串口，所以每收到一行就从头再打一遍
+    let mut next_line = 0;
     loop {
-        cortex_m::asm::wfi();
+        let _ = result_pin.set_state(fixture::result_level(passed, now_ms()).into());
+        let Some(usb) = usb.as_mut() else {
+            continue;
+        };
+        usb.poll();
+        let received = usb.read_serial(&mut serial_rx);
+        if serial_rx[..received].iter().any(|&byte| byte == b'\r' || byte == b'\n') {
+            next_line = 0;
+        }
+        while usb.is_configured() && next_line < report.lines() && usb.serial_tx_free() >= fixture::LINE_MAX {
+            let _ = report.write_line(next_line, usb);
+            next_line += 1;
+        }
+    }
+}
+
+/// 串口命令是从哪边来的
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Source {
+    Usb,
+    Net,
+}
+
+/// 命令的回复往哪里写：USB 串口，或者网络上连着的人
+enum Reply<'r> {
+    Usb(&'r mut UsbLink<'static>),
+    Net(&'r mut NetLink),
+}
+
+impl Reply<'_> {
+    /// 发送缓冲区还能放多少字节，分批导出的时候看它
+    fn tx_free(&self) -> usize {
+        match self {
+            Reply::Usb(usb) => usb.serial_tx_free(),
+            Reply::Net(net) => net.tx_free(),
+        }
+    }
+}
+
+impl fmt::Write for Reply<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        match self {
+            Reply::Usb(usb) => usb.write_str(s),
+            Reply::Net(net) => net.write_str(s),
+        }
+    }
+}
+
+/// `source` 那一边在的话拿它的回复通道
+fn reply_to<'r>(source: Source, usb: &'r mut Option<UsbLink<'static>>, network: &'r mut Option<NetLink>) -> Option<Reply<'r>> {
+    match source {
+        Source::Usb => usb.as_mut().map(Reply::Usb),
+        Source::Net => network.as_mut().map(Reply::Net),
+    }
+}
+
+/// 状态行(带 `\r\n`)：温度和电量取注册表里的最新读数，页面名字和 PERF 里的一样
+fn status_line<S, const N: usize>(sensors: &SensorRegistry<S>, perf: &PerfTable<N>, screen: PageId, now_ms: u64) -> String<{ status::LINE_MAX }> {
+    let value = |name| sensors.find(name).and_then(|id| sensors.value(id));
+    let state = State {
+        uptime_s: now_ms / 1000,
+        temp_centi: value("temp"),
+        battery_percent: value("batt"),
+        screen: perf.name(screen.0 as usize).unwrap_or("?"),
+    };
+    let mut line = String::new();
+    let _ = serialize_status(&mut line, &state);
+    let _ = line.push_str("\r\n");
+    line
+}
+
+/// 传感器注册表的读数函数拿到的东西：ADC 采样器、BMP280、温湿度和测距传感器、DS3231(没接就是 None)
+struct SensorHub<P, V> {
+    sampler: Sampler<P, V>,
+    baro: Option<Bmp280>,
+    humidity: Option<HumidityMonitor<AnyHumiditySensor>>,
+    distance: Option<DistanceMonitor>,
+    /// 接了 DS3231 的话时间从它那里来，不是传感器通道
+    rtc: Option<Ds3231>,
+    /// 电池电量按修正过的 VSYS 估，注册表的修正管不到那个通道(见 `apply_calibration`)
+    vsys_correction: Correction,
+    #[cfg(not(feature = "mic-vu"))]
+    scl_tap: SclTapPin,
+}
+
+/// 从 DS3231 对一次时：里面存的是 UTC，按 `zone` 换成当地时间给墙上时间，广播 `Event::ClockSet` 和 `Event::RtcTime`。
+/// 对上了返回 true。没接、总线正忙、芯片停过电都不动
+fn sync_wall_clock<P, V, const N: usize>(display: &mut OledDisplay, hub: &mut SensorHub<P, V>, clock: &mut WallClock, zone: &TimeZone, scheduler: &mut Scheduler<'_, N>, now_ms: u64) -> bool {
+    let Some(utc) = hub.rtc.as_mut().zip(display.shared_bus()).and_then(|(rtc, bus)| rtc.read_time(bus)) else {
+        return false;
+    };
+    let local = zone.to_local(&utc);
+    clock.set_time(local.hour, local.minute, local.second, now_ms);
+    scheduler.broadcast(Event::ClockSet(*clock), now_ms);
+    scheduler.broadcast(Event::RtcTime(utc), now_ms);
+    true
+}
+
+/// 把校准值挂到温度、ADC0、VSYS 通道上(见 calibration.rs)，电池电量是从 VSYS 换算的，也跟着修正
+fn apply_calibration<P, V>(registry: &mut SensorRegistry<SensorHub<P, V>>, hub: &mut SensorHub<P, V>, calibration: &Calibration) {
+    for (name, correction) in [("temp", calibration.temperature()), ("adc0", calibration.voltage()), ("vsys", calibration.voltage())] {
+        if let Some(id) = registry.find(name) {
+            registry.set_correction(id, correction);
+        }
+    }
+    hub.vsys_correction = calibration.voltage();
+}
+
+/// 主循环里除了屏幕以外要用到的外设
+struct Devices<const B: usize, T, P, V> {
+    buttons: ButtonPad<B>,
+    /// 板载 LED，屏幕不在的时候闪错误码。Pico W 上没有(见 status_led.rs)
+    status_led: Option<BoardLed>,
+    usb: Option<UsbLink<'static>>,
+    /// Pico W 上的网络显示(见 net_console.rs)，别的板子上是 None
+    network: Option<NetLink>,
+    buzzer: T,
+    hub: SensorHub<P, V>,
+    datalog: DataLog,
+    /// 和 core1(反应游戏计时)说话的 FIFO
+    fifo: SioFifo,
+    /// 开机就启动了(`WATCHDOG_TIMEOUT_MS`)，主循环一圈喂一次；scratch 寄存器记启动次数(见 boot_loop.rs)
+    watchdog: Watchdog,
+    /// 开机硬件小结，串口 SYSINFO 打的就是它
+    system_info: SystemInfo,
+    /// 开机时 flash 里存着的脉冲总数，脉冲计数的两个传感器通道("pulse"、"rate")
+    pulse_saved: u32,
+    pulse_channels: (ChannelId, ChannelId),
+    /// 串口 PREVIEW 收的图，预览页面也看着它
+    #[cfg(feature = "devtools")]
+    preview: &'static RefCell<Preview>,
+    #[cfg(feature = "ws2812")]
+    strip: LedStrip,
+}
+
+/// 主循环：处理 USB 和按键 -> 交给调度器 -> 调度器觉得要重画就画一帧 -> DMA 发出去
+fn run<const N: usize, const B: usize, T, P, V>(
+    mut display: OledDisplay,
+    mut timer: Timer,
+    mut scheduler: Scheduler<'_, N>,
+    devices: Devices<B, T, P, V>,
+    sensors: &RefCell<SensorRegistry<SensorHub<P, V>>>,
+    perf: &RefCell<PerfTable<N>>,
+    mut settings: Settings,
+) -> !
+where
+    T: ToneOutput,
+    P: SamplerPin,
+    V: SamplerPin,
+{
+    let Devices {
+        mut buttons,
+        mut status_led,
+        mut usb,
+        mut network,
+        mut buzzer,
+        mut hub,
+        mut datalog,
+        mut fifo,
+        mut watchdog,
+        system_info,
+        pulse_saved,
+        pulse_channels,
+        #[cfg(feature = "devtools")]
+        preview,
+        #[cfg(feature = "ws2812")]
+        mut strip,
+    } = devices;
+    // 开机以后稳定跑够 30 秒就把连续启动计数清掉
+    let mut settled = false;
+    // 短按、长按、双击
+    let mut gestures = GestureDetector::new();
+    // 物理按键换成页面认的按键(见 remap.rs)
+    let mut remap = Remapper::new(settings.input_bindings);
+    // 关机键按满 3 秒是关机，它的按键事件要等松开了才交给页面
+    let mut power_hold = HoldGate::new(remap.bindings().key(Role::PowerHold));
+    // 串口 REC 命令录的输入和放的进度(见 input_replay.rs)
+    let mut recorder = Recorder::new();
+    let mut player = Player::new();
+    // 串口命令行，网络上连着的人另有一个，两边打到一半的行不会拼在一起
+    let mut console = Console::new();
+    let mut net_console = Console::new();
+    let mut serial_rx = [0u8; 64];
+    let mut net_rx = [0u8; 64];
+    // 分批导出(LOG DUMP、HELP 这些)往哪边接着打，遥测和 STATUS ON 各自记着是哪边开的
+    let mut stream_to = Source::Usb;
+    let mut telemetry_to = Source::Usb;
+    let mut status_to = Source::Usb;
+    // 上一圈网络是什么状态，变了才更新状态栏的图标
+    let mut net_state = NetState::Off;
+    let mut report = [0u8; REPORT_LEN];
+    // 告警状态，屏幕现在是不是反色的
+    let mut alerts = Alerts::new();
+    let mut inverted = false;
+    // 屏幕在不在，拔掉了就定时探测。开机初始化失败但是有应答的话先当它在，flush 连着失败再按掉线处理
+    let mut link = DisplayLink::new(display.interface_mut().probe(), timer.get_counter().ticks() / 1000);
+    // 串口命令 INVERT 设的反色
+    let mut remote_inverted = false;
+    // 串口 IMG 命令正在传的图
+    let mut uploads = ImageUpload::new();
+    let mut alert_level = AlertLevel::None;
+    // 声音不阻塞，放在主循环里推进，开机音也是在这里才开始放
+    let mut tones = ToneEngine::new();
+    tones.set_muted(settings.muted);
+    tones.play(Sound::BootChime);
+    // LOG DUMP 导出到第几条了(一次只写串口缓冲区放得下的几行)
+    let mut dump: Option<(LogIndex, usize)> = None;
+    // PERF 命令输出到第几行了，和 LOG DUMP 一样发送缓冲区有空了再接着写
+    let mut perf_dump: Option<usize> = None;
+    // EVENTS 命令拷下来的事件和发到第几条了
+    let mut events_dump: Option<(Vec<event_log::Entry, { event_log::CAPACITY }>, usize)> = None;
+    // HELP 要列的命令和打到第几条了
+    let mut help_dump: Option<(Help, usize)> = None;
+    // SYSINFO 的内容和打到第几行了
+    let mut sysinfo_dump: Option<(SystemInfo, usize)> = None;
+    // FEATURES 打到第几行了
+    let mut features_dump: Option<usize> = None;
+    // FB 命令拷下来的显存和发到第几行了
+    let mut frame_dump: Option<(Snapshot, usize)> = None;
+    // 正在发的这一帧是什么时候开始发的
+    let mut flush_started: Option<u32> = None;
+    // 串口遥测，和主循环最长一圈用了多久(两行遥测之间)
+    let mut telemetry = Telemetry::new();
+    // STATUS ON 定时打的状态行
+    let mut status_reporter = StatusReporter::new();
+    let mut last_loop_us = timer.get_counter().ticks();
+    // 每秒算一次空闲百分比，诊断页面和 "idle" 通道看
+    let mut cpu = CpuMeter::new(perf::ticks());
+    let mut frame_max_us = 0u32;
+    // 床头钟模式：墙上时间和叫醒 CPU 用的闹钟
+    let mut wall_clock = WallClock::new();
+    // 接了 DS3231 就从它那里拿时间，之后每分钟再对一次(见 ds3231.rs)，不用每次开机 CLOCK SET
+    sync_wall_clock(&mut display, &mut hub, &mut wall_clock, &settings.timezone, &mut scheduler, timer.get_counter().ticks() / 1000);
+    let mut alarm = timer.alarm_0().unwrap();
+    // 按时间表自动调亮度
+    let mut dimmer = Dimmer::new();
+    // 屏幕一共亮了多久，热的时候调暗；亮度都从这里发出去
+    let mut panel = PanelCare::new(PanelWear::load(), settings.contrast, timer.get_counter().ticks() / 1000);
+    // 脉冲计数的总数隔一阵存一次 flash
+    let mut pulse_store = CounterStore::new(pulse_saved, timer.get_counter().ticks() / 1000);
+    // 阈值告警
+    let mut alarm_engine = AlarmEngine::new();
+    // 防烧屏，默认关
+    let mut burn_in = AntiBurnIn::new(settings.burn_in, timer.get_counter().ticks() / 1000);
+    // 没人操作自动关屏，默认关
+    let mut screen = ScreenTimeout::new(settings.screen_off_min, timer.get_counter().ticks() / 1000);
+    // 自动轮播，默认关
+    let mut carousel = Carousel::new();
+    // 演示自动轮换，默认关
+    let mut demo_auto = DemoAuto::new();
+    // 下次什么时候读 BMP280
+    let mut next_baro_ms = 0u64;
+    // 数据记录、LOG DUMP 的表头要用通道的名字和单位
+    let channels = sensors.borrow().infos();
+    let temp_channel = sensors.borrow().find("temp");
+    // 电池供电的时候电压低了停写 flash，再低关屏等着
+    let vsys_channel = sensors.borrow().find("vsys");
+    let mut power = LowVoltageMonitor::new(settings.low_voltage);
+    // 光敏电阻接在 ADC0 上的话跟着环境光调亮度，开着的时候按时间调亮度不管用
+    let adc0_channel = sensors.borrow().find("adc0");
+    let mut auto_light = AutoBrightness::new();
+    // 闹钟
+    let mut alarm_clock = AlarmClock::new();
+    // 查电压、记录数据、查告警、看闹钟
+    let mut jobs = Periodic::new([
+        Task::every(Job::VsysCheck, VSYS_POLL_MS as u64),
+        Task::every(Job::Log, settings.log_interval_s as u64 * 1000),
+        Task::every(Job::AlarmCheck, ALARM_CHECK_MS),
+        Task::every(Job::WakeAlarm, WAKE_ALARM_CHECK_MS),
+        Task::every(Job::RtcSync, RTC_SYNC_MS),
+        Task::every(Job::StackCheck, STACK_CHECK_MS),
+        Task::every(Job::AutoBrightness, auto_brightness::UPDATE_MS),
+    ]);
+
+    // 以前这里是 loop { wfi }，画完一屏就睡觉了。
+    // 现在有了 USB，协议栈要靠主循环不停地 poll，所以改成轮询：每一圈干完活睡到这一毫秒结束，
+    // 中间按下按键也会马上醒过来处理、重画，不用等这一毫秒睡完(见 idle_until_input)
+    // 按键唤醒开着没有，反应游戏那一页要关掉，见下面睡觉的地方
+    let mut input_wake = false;
+    // 设备现在在干什么(见 mode.rs)，下面画不画、放不放铃声都看它
+    let mut device_mode = Mode::Boot;
+    // 上一次打日志的时候栈最深用了多少
+    let mut stack_peak = 0;
+    change_mode(&mut device_mode, ModeEvent::BootDone);
+    'main: loop {
+        watchdog.feed();
+        let now_us = timer.get_counter().ticks();
+        let now_ms = now_us / 1000;
+        frame_max_us = frame_max_us.max((now_us - last_loop_us) as u32);
+        if !settled && now_ms >= boot_loop::HEALTHY_AFTER_MS {
+            boot_loop::mark_healthy(&mut watchdog);
+            settled = true;
+        }
+
+        // 按键映射表里有的输入(长按 Back 进床头钟这些)换成动作排队，不交给页面；关着屏的时候按下去只是亮屏，也不交给页面
+        let mut input_actions: Deque<Action, 4> = Deque::new();
+        let mut woke = false;
+        buttons.poll(now_ms, |event| {
+            // 按住 10 秒恢复默认映射，关着屏的时候也算
+            remap.note(event, now_ms);
+            if let ButtonEvent::Pressed(_) = event {
+                carousel.hold(&settings.carousel, now_ms);
+                demo_auto.input(now_ms);
+                if screen.activity(now_ms) {
+                    woke = true;
+                    return;
+                }
+                tones.beep(NAV_BEEP.freq_hz, NAV_BEEP.duration_ms);
+            }
+            let mut logical = Deque::new();
+            if let Some(gesture) = gestures.feed(event, now_ms) {
+                remap.gesture(gesture, &mut logical);
+            }
+            // 双击这种在第二次按下的时候报，映射掉了(查到了按键映射表，或者直接绑成了一个按键)的话这一下也不再交给页面
+            let swallow = logical.iter().any(|&input| matches!(input, InputEvent::Button(_)) || INPUT_MAP.lookup(input, scheduler.current()).is_some());
+            if !swallow {
+                power_hold.feed(event, now_ms, |event| remap.feed(event, &mut logical));
+            }
+            route_inputs(&mut logical, &mut scheduler, &mut input_actions, &mut recorder, now_ms);
+        });
+        let mut logical = Deque::new();
+        gestures.poll(now_ms, |gesture| remap.gesture(gesture, &mut logical));
+        if let Some(gesture) = power_hold.poll(now_ms) {
+            remap.gesture(gesture, &mut logical);
+        }
+        route_inputs(&mut logical, &mut scheduler, &mut input_actions, &mut recorder, now_ms);
+        // 录下来的输入到点了从同一个入口放进去，和真按的一样算有人在操作(亮屏这一下不吞掉)
+        let mut replayed = Deque::new();
+        if player.poll(&recorder, now_ms, |input| {
+            let _ = replayed.push_back(input);
+        }) {
+            info!("input replay finished");
+        }
+        if !replayed.is_empty() {
+            carousel.hold(&settings.carousel, now_ms);
+            demo_auto.input(now_ms);
+            woke |= screen.activity(now_ms);
+            route_inputs(&mut replayed, &mut scheduler, &mut input_actions, &mut recorder, now_ms);
+        }
+        // 等到了用户按的输入、等超时了、有键按满了 10 秒
+        match remap.poll(now_ms) {
+            Some(RemapEvent::Captured(role, input)) => {
+                let mut bindings = *remap.bindings();
+                match bindings.set(role, input) {
+                    Ok(()) => {
+                        settings.input_bindings = bindings;
+                        settings.store();
+                        apply_input_bindings(&mut remap, &mut power_hold, bindings);
+                        scheduler.show_toast("input saved", now_ms);
+                    }
+                    Err(other) => {
+                        let mut message: String<24> = String::new();
+                        let _ = write!(message, "used by {}", other.name());
+                        scheduler.show_toast(&message, now_ms);
+                    }
+                }
+                scheduler.broadcast(Event::InputBindings(bindings), now_ms);
+            }
+            Some(RemapEvent::TimedOut(_)) => {
+                scheduler.show_toast("no input", now_ms);
+                scheduler.broadcast(Event::InputBindings(*remap.bindings()), now_ms);
+            }
+            Some(RemapEvent::Reset) => {
+                let _ = input_actions.push_back(Action::ResetInputBindings);
+            }
+            None => {}
+        }
+        while let Some(message) = reaction::poll_message(&mut fifo) {
+            scheduler.broadcast(Event::Reaction(message), now_ms);
+        }
+        // 按键映射出来的动作先做，然后是页面交出来的
+        while let Some(action) = input_actions.pop_front().or_else(|| scheduler.take_action()) {
+            match action {
+                Action::EnterSleepClock => {
+                    if screen.activity(now_ms) {
+                        let _ = display.send_commands(&command::display_on(true));
+                    }
+                    tones.stop(&mut buzzer);
+                    inverted = false;
+                    run_sleep_clock(&mut display, (&timer, &watchdog), &mut alarm, (&mut buttons, remap.bindings().key(Role::Back)), &wall_clock, &mut alarm_clock, &settings.wake_alarms);
+                    // 床头钟出来的时候把按键唤醒关了，下一圈重新打开
+                    input_wake = false;
+                    // 是闹钟响了才出来的
+                    if let Some(ring) = alarm_clock.ringing() {
+                        show_wake_alarm(&mut scheduler, ring, now_ms);
+                    }
+                    // 回来之后整屏按正常的页面重画，睡眠的时间不算进帧时间
+                    gestures.reset();
+                    power_hold.reset();
+                    scheduler.invalidate();
+                    dimmer.invalidate();
+                    auto_light.invalidate();
+                    last_loop_us = timer.get_counter().ticks();
+                    continue 'main;
+                }
+                Action::OpenDemoMenu => {
+                    scheduler.pop_to_root();
+                    scheduler.apply(Transition::Push(DEMO_MENU_PAGE));
+                }
+                Action::CaptureInput(role) => remap.start_capture(role, now_ms),
+                Action::ResetInputBindings => {
+                    settings.input_bindings = InputBindings::DEFAULT;
+                    settings.store();
+                    apply_input_bindings(&mut remap, &mut power_hold, InputBindings::DEFAULT);
+                    scheduler.broadcast(Event::InputBindings(InputBindings::DEFAULT), now_ms);
+                    scheduler.show_toast("inputs reset", now_ms);
+                }
+                Action::SaveTimeZone(zone) => {
+                    settings.timezone = zone;
+                    settings.store();
+                    // 不用等下一分钟，表盘和按时间调亮度马上换到新的当地时间
+                    if sync_wall_clock(&mut display, &mut hub, &mut wall_clock, &zone, &mut scheduler, now_ms) {
+                        dimmer.invalidate();
+                    }
+                    scheduler.show_toast("time zone saved", now_ms);
+                }
+                Action::SaveDimSchedule(schedule) => {
+                    settings.dim_schedule = schedule;
+                    settings.store();
+                    dimmer.invalidate();
+                    auto_light.invalidate();
+                    scheduler.show_toast("schedule saved", now_ms);
+                }
+                Action::SaveAlarmRules(rules) => {
+                    settings.alarm_rules = rules;
+                    settings.store();
+                    // 规则的编号可能变了，全部重新评估
+                    alarm_engine.reset();
+                    show_alarms(&mut scheduler, &mut alerts, &settings.alarm_rules, 0, now_ms);
+                    jobs.run_now(Job::AlarmCheck);
+                    scheduler.show_toast("alarms saved", now_ms);
+                }
+                Action::SaveCarousel(config) => {
+                    settings.carousel = config;
+                    settings.store();
+                    scheduler.show_toast("carousel saved", now_ms);
+                }
+                Action::ScanBus => {
+                    // 等上一帧发完再借总线
+                    while display.is_flushing() {
+                        let _ = display.poll_flush();
+                    }
+                    if let Some(report) = check_bus(&mut display, &mut hub, link.is_online(), &watchdog) {
+                        scheduler.broadcast(Event::BusReport(report), now_ms);
+                    }
+                }
+                Action::Calibrate(input) => match input.apply(settings.calibration) {
+                    Some(calibration) => {
+                        settings.calibration = calibration;
+                        settings.store();
+                        apply_calibration(&mut sensors.borrow_mut(), &mut hub, &calibration);
+                        info!("calibration: {}", calibration);
+                        scheduler.show_toast("calibration saved", now_ms);
+                    }
+                    None => scheduler.show_toast("out of range", now_ms),
+                },
+                Action::ResetCalibration => {
+                    settings.calibration = Calibration::default();
+                    settings.store();
+                    apply_calibration(&mut sensors.borrow_mut(), &mut hub, &settings.calibration);
+                    scheduler.show_toast("calibration reset", now_ms);
+                }
+                Action::SaveWatchFace(config) => {
+                    settings.watch_face = config;
+                    settings.store();
+                    scheduler.show_toast("face saved", now_ms);
+                }
+                Action::SaveSeaLevel(pa) => {
+                    settings.sea_level_pa = pa;
+                    settings.store();
+                    if let Some(baro) = hub.baro.as_mut() {
+                        baro.set_sea_level(pa);
+                    }
+                    scheduler.show_toast("sea level saved", now_ms);
+                }
+                Action::AskPowerOff => scheduler.ask(Confirm { prompt: "power off?", action: Action::PowerOff }),
+                Action::PowerOff => {
+                    // 没到时间存的计数关机之前存掉，开机接着算
+                    if low_voltage::flash_writes_allowed() {
+                        let pulses = pulse_counter::total();
+                        if pulse_store.has_unsaved(pulses) {
+                            pulse_store.save(pulses, now_ms);
+                        }
+                        if panel.has_unsaved() {
+                            panel.save(now_ms);
+                        }
+                    }
+                    event_log::record(SystemEvent::PowerOff);
+                    power_off(&mut display, (&timer, &watchdog), &mut buttons, remap.bindings().key(Role::PowerHold), &mut status_led)
+                }
+                Action::SaveSplash(slot) => {
+                    settings.splash_slot = slot;
+                    settings.store();
+                    scheduler.show_toast("splash saved", now_ms);
+                }
+                Action::SaveDisplayProfile(index) => {
+                    if let Some(profile) = display_profile::get(index) {
+                        settings.display_profile = Some(index);
+                        settings.contrast = profile.contrast;
+                        settings.burn_in = profile.burn_in;
+                        settings.store();
+                        if apply_profile(&mut display, &mut burn_in, profile, now_ms).is_ok() {
+                            inverted = profile.inverted;
+                        }
+                        panel.set_level(profile.contrast);
+                        dimmer.invalidate();
+                        auto_light.invalidate();
+                        scheduler.invalidate();
+                        scheduler.show_toast(profile.name, now_ms);
+                    }
+                }
+                Action::SaveTheme(index) => {
+                    settings.theme = index;
+                    settings.store();
+                    scheduler.set_theme(index);
+                    scheduler.show_toast(theme::active().name, now_ms);
+                }
+                Action::SaveLargeText(on) => {
+                    settings.large_text = on;
+                    settings.store();
+                    scheduler.set_large_text(on);
+                    scheduler.show_toast(if on { "large text on" } else { "large text off" }, now_ms);
+                }
+                Action::SaveWakeAlarms(config) => {
+                    settings.wake_alarms = config;
+                    settings.store();
+                    scheduler.show_toast("wake alarms saved", now_ms);
+                }
+                Action::SnoozeWakeAlarm => {
+                    alarm_clock.snooze(&settings.wake_alarms);
+                    tones.stop(&mut buzzer);
+                    let mut message: String<20> = String::new();
+                    let _ = write!(message, "snooze {} min", settings.wake_alarms.snooze_min);
+                    scheduler.broadcast(Event::WakeAlarm(None), now_ms);
+                    scheduler.show_toast(&message, now_ms);
+                }
+                Action::DismissWakeAlarm => {
+                    alarm_clock.dismiss();
+                    tones.stop(&mut buzzer);
+                    scheduler.broadcast(Event::WakeAlarm(None), now_ms);
+                    scheduler.show_toast("alarm off", now_ms);
+                }
+                Action::ResetPulseCount => {
+                    reset_pulse_count(&mut pulse_store, now_ms);
+                    scheduler.show_toast("counter reset", now_ms);
+                }
+                Action::StartReaction { countdown_ms } => {
+                    if !reaction::start_round(&mut fifo, countdown_ms) {
+                        warn!("reaction: core1 not parked");
+                        scheduler.show_toast("core1 busy", now_ms);
+                    }
+                }
+            }
+        }
+
+        if let Some(usb) = usb.as_mut() {
+            usb.poll();
+
+            while let Some(len) = usb.read_host_report(&mut report) {
+                let (seq, code) = match StatusPacket::parse(&report[..len]) {
+                    Ok(packet) => {
+                        let seq = packet.seq;
+                        scheduler.broadcast(Event::Host(packet), now_ms);
+                        (seq, AckCode::Ok)
+                    }
+                    Err(err) => {
+                        warn!("bad host packet: {}", err);
+                        (report[1], AckCode::from(err))
+                    }
+                };
+                usb.send_host_report(&encode_ack(seq, code));
+            }
+
+        }
+
+        // Pico W 上的网络：各任务走一步，状态栏的图标跟着变(见 net_console.rs)
+        if let Some(net) = network.as_mut() {
+            net.poll();
+            let state = net.state();
+            if state != net_state {
+                if let (NetState::Online(ip), NetState::Joining) = (state, net_state) {
+                    let mut text: String<16> = String::new();
+                    let _ = write!(text, "{}.{}.{}.{}", ip[0], ip[1], ip[2], ip[3]);
+                    scheduler.show_toast(&text, now_ms);
+                }
+                net_state = state;
+                scheduler.set_network_indicator(state);
+            }
+        }
+
+        // USB 串口和网络上收到的命令走同一套处理，回复发回命令是从哪边来的那边
+        let usb_state = match usb.as_ref() {
+            Some(usb) if usb.is_configured() => UsbState::Configured,
+            Some(_) => UsbState::Waiting,
+            None => UsbState::Off,
+        };
+        let received = usb.as_mut().map_or(0, |usb| usb.read_serial(&mut serial_rx));
+        let net_received = network.as_mut().map_or(0, |net| net.read(&mut net_rx));
+        let incoming = serial_rx[..received].iter().map(|&byte| (Source::Usb, byte));
+        let incoming = incoming.chain(net_rx[..net_received].iter().map(|&byte| (Source::Net, byte)));
+        // SETTINGS WIFI 要换的网络，回复还借着 network，处理完这一批再交给它
+        #[cfg(feature = "pico-w")]
+        let mut pending_join = None;
+        for (source, byte) in incoming {
+            let line = match source {
+                Source::Usb => &mut console,
+                Source::Net => &mut net_console,
+            };
+            let Some(command) = line.feed(byte) else {
+                continue;
+            };
+            let Some(mut reply) = reply_to(source, &mut usb, &mut network) else {
+                continue;
+            };
+            let out = &mut reply;
+            if command.streams() {
+                stream_to = source;
+            }
+            match command {
+                ConsoleCommand::SettingsDump => {
+                    let mut text = [0u8; BASE64_CAPACITY];
+                    let _ = write!(out, "SETTINGS {}\r\n", settings.to_base64(&mut text));
+                }
+                ConsoleCommand::SettingsLoad(_) | ConsoleCommand::SettingsReset => {
+                    // 恢复默认和导入走同一条路，只是新设置不是从 base64 来的
+                    let reset = command == ConsoleCommand::SettingsReset;
+                    let incoming = match command {
+                        ConsoleCommand::SettingsLoad(blob) => Settings::from_base64(blob),
+                        _ => Ok(Settings::default()),
+                    };
+                    match incoming {
+                        Ok(imported) => {
+                            let rotated = imported.quarter_turns != settings.quarter_turns;
+                            // 校准是这块板子自己的，不跟着别的板子导出来的设置走
+                            settings = Settings { calibration: settings.calibration, ..imported };
+                            settings.store();
+                            if let Err(err) = settings.apply(&mut display) {
+                                warn!("apply settings failed: {}", defmt::Debug2Format(&err));
+                            }
+                            // 旋转了的话页面按新方向重算布局，整屏按新方向重画
+                            if rotated {
+                                scheduler.rotation_changed(display.size());
+                            } else {
+                                scheduler.invalidate();
+                            }
+                            dimmer.invalidate();
+                            auto_light.invalidate();
+                            alarm_engine.reset();
+                            show_alarms(&mut scheduler, &mut alerts, &settings.alarm_rules, 0, now_ms);
+                            jobs.run_now(Job::AlarmCheck);
+                            jobs.set_interval(Job::Log, settings.log_interval_s as u64 * 1000);
+                            if let Some(baro) = hub.baro.as_mut() {
+                                baro.set_sea_level(settings.sea_level_pa);
+                            }
+                            burn_in.set_config(settings.burn_in, now_ms);
+                            screen.set_minutes(settings.screen_off_min, now_ms);
+                            display.framebuffer_mut().set_shift(burn_in.offset());
+                            scheduler.broadcast(Event::WatchFace(settings.watch_face), now_ms);
+                            scheduler.set_large_text(settings.large_text);
+                            scheduler.set_theme(settings.theme);
+                            scheduler.set_frame_pacing(settings.frame_pacing.then_some(command::REFRESH_PERIOD_US));
+                            scheduler.broadcast(Event::LargeText(settings.large_text), now_ms);
+                            scheduler.broadcast(Event::DisplayProfile(settings.display_profile), now_ms);
+                            apply_pulse_config(&mut sensors.borrow_mut(), pulse_channels, settings.pulse);
+                            apply_input_bindings(&mut remap, &mut power_hold, settings.input_bindings);
+                            scheduler.broadcast(Event::InputBindings(settings.input_bindings), now_ms);
+                            scheduler.broadcast(Event::TimeZone(settings.timezone), now_ms);
+                            sync_wall_clock(&mut display, &mut hub, &mut wall_clock, &settings.timezone, &mut scheduler, now_ms);
+                            scheduler.show_toast(if reset { "settings reset" } else { "settings imported" }, now_ms);
+                            let _ = write!(out, "OK\r\n");
+                        }
+                        Err(err) => {
+                            warn!("settings import rejected: {}", err);
+                            let _ = write!(out, "ERR {}\r\n", err.message());
+                        }
+                    }
+                }
+                ConsoleCommand::SettingsMute(muted) => {
+                    settings.muted = muted;
+                    settings.store();
+                    tones.set_muted(muted);
+                    let _ = write!(out, "OK\r\n");
+                }
+                ConsoleCommand::SettingsLog(interval) => {
+                    match interval {
+                        Some(secs) => {
+                            settings.logging = true;
+                            settings.log_interval_s = secs;
+                        }
+                        None => settings.logging = false,
+                    }
+                    settings.store();
+                    jobs.set_interval(Job::Log, settings.log_interval_s as u64 * 1000);
+                    jobs.run_now(Job::Log);
+                    let _ = write!(out, "OK\r\n");
+                }
+                ConsoleCommand::SettingsScreenOff(minutes) => {
+                    settings.screen_off_min = minutes.unwrap_or(0);
+                    settings.store();
+                    screen.set_minutes(settings.screen_off_min, now_ms);
+                    let _ = write!(out, "OK\r\n");
+                }
+                ConsoleCommand::SettingsWake(mm) => {
+                    settings.wake_mm = mm.unwrap_or(0);
+                    settings.store();
+                    let _ = write!(out, "OK\r\n");
+                }
+                ConsoleCommand::SettingsDemo(config) => {
+                    settings.demo_auto = config;
+                    settings.store();
+                    // 从现在开始算闲了多久，不然设完马上就开始换
+                    demo_auto.input(now_ms);
+                    let _ = write!(out, "OK\r\n");
+                }
+                ConsoleCommand::SettingsLarge(on) => {
+                    settings.large_text = on;
+                    settings.store();
+                    scheduler.set_large_text(on);
+                    scheduler.broadcast(Event::LargeText(on), now_ms);
+                    let _ = write!(out, "OK\r\n");
+                }
+                ConsoleCommand::SettingsProfile(index) => {
+                    // 编号是 find 出来的，一定在表里
+                    if let Some(profile) = display_profile::get(index) {
+                        settings.display_profile = Some(index);
+                        settings.contrast = profile.contrast;
+                        settings.burn_in = profile.burn_in;
+                        settings.store();
+                        if apply_profile(&mut display, &mut burn_in, profile, now_ms).is_ok() {
+                            inverted = profile.inverted;
+                        }
+                        panel.set_level(profile.contrast);
+                        dimmer.invalidate();
+                        auto_light.invalidate();
+                        scheduler.invalidate();
+                        scheduler.broadcast(Event::DisplayProfile(Some(index)), now_ms);
+                    }
+                    let _ = write!(out, "OK\r\n");
+                }
+                ConsoleCommand::SettingsTheme(index) => {
+                    settings.theme = index;
+                    settings.store();
+                    scheduler.set_theme(index);
+                    let _ = write!(out, "OK\r\n");
+                }
+                ConsoleCommand::SettingsPulse(config) => {
+                    settings.pulse = config;
+                    settings.store();
+                    apply_pulse_config(&mut sensors.borrow_mut(), pulse_channels, config);
+                    let _ = write!(out, "OK\r\n");
+                }
+                ConsoleCommand::Pulse => {
+                    let _ = write!(out, "PULSE ");
+                    let _ = pulse_counter::write_summary(out);
+                    let _ = write!(out, "\r\nOK\r\n");
+                }
+                ConsoleCommand::PulseReset => {
+                    reset_pulse_count(&mut pulse_store, now_ms);
+                    let _ = write!(out, "OK\r\n");
+                }
+                // 录和放不同时进行：放出来的输入也走 route_inputs，边放边录会把自己录进去
+                ConsoleCommand::RecordStart => {
+                    player.stop();
+                    recorder.start(now_ms);
+                    info!("recording input");
+                    let _ = write!(out, "OK\r\n");
+                }
+                ConsoleCommand::RecordStop => {
+                    if recorder.stop(now_ms) {
+                        let full = if recorder.overflowed() { " full" } else { "" };
+                        let _ = write!(out, "REC {} events {}ms{}\r\n", recorder.events().len(), recorder.duration_ms(), full);
+                    }
+                    player.stop();
+                    let _ = write!(out, "OK\r\n");
+                }
+                ConsoleCommand::RecordPlay(looping) => {
+                    recorder.stop(now_ms);
+                    if recorder.events().is_empty() {
+                        let _ = write!(out, "ERR nothing recorded\r\n");
+                    } else {
+                        info!("replaying {} inputs", recorder.events().len());
+                        player.start(now_ms, looping);
+                        let _ = write!(out, "OK\r\n");
+                    }
+                }
+                ConsoleCommand::SettingsDerate(on) => {
+                    settings.thermal_derate = on;
+                    settings.store();
+                    let _ = write!(out, "OK\r\n");
+                }
+                ConsoleCommand::SettingsPacing(on) => {
+                    settings.frame_pacing = on;
+                    settings.store();
+                    scheduler.set_frame_pacing(on.then_some(command::REFRESH_PERIOD_US));
+                    let _ = write!(out, "OK\r\n");
+                }
+                ConsoleCommand::SettingsTimeZone(zone) => {
+                    settings.timezone = zone;
+                    settings.store();
+                    scheduler.broadcast(Event::TimeZone(zone), now_ms);
+                    if sync_wall_clock(&mut display, &mut hub, &mut wall_clock, &zone, &mut scheduler, now_ms) {
+                        dimmer.invalidate();
+                    }
+                    let _ = write!(out, "OK\r\n");
+                }
+                ConsoleCommand::SettingsAutoBright(config) => {
+                    settings.auto_brightness = config;
+                    settings.store();
+                    // 关掉的话按时间表(或者设置里的对比度)重新来
+                    auto_light.invalidate();
+                    dimmer.invalidate();
+                    let _ = write!(out, "OK\r\n");
+                }
+                ConsoleCommand::SettingsBattLow(config) => {
+                    settings.low_voltage = config;
+                    power.set_config(config);
+                    settings.store();
+                    let _ = write!(out, "OK\r\n");
+                }
+                ConsoleCommand::SettingsBurnIn(config) => {
+                    settings.burn_in = config;
+                    settings.store();
+                    burn_in.set_config(config, now_ms);
+                    // 关掉的时候画面要回到原位
+                    display.framebuffer_mut().set_shift(burn_in.offset());
+                    scheduler.invalidate();
+                    let _ = write!(out, "OK\r\n");
+                }
+                ConsoleCommand::Face => {
+                    let registry = sensors.borrow();
+                    let _ = write!(out, "FACE ");
+                    let _ = settings.watch_face.write_summary(out, |id| registry.get(id).map_or("?", |channel| channel.info().name));
+                    let _ = write!(out, "\r\nOK\r\n");
+                }
+                ConsoleCommand::FaceLayout(layout) => {
+                    settings.watch_face.layout = layout;
+                    settings.store();
+                    scheduler.broadcast(Event::WatchFace(settings.watch_face), now_ms);
+                    let _ = write!(out, "OK\r\n");
+                }
+                ConsoleCommand::FaceSlot { slot, kind, channel } => {
+                    let found = channel.map(|name| sensors.borrow().find(name));
+                    if let Some(None) = found {
+                        let _ = write!(out, "ERR unknown channel\r\n");
+                    } else {
+                        settings.watch_face.slots[slot] = Widget::new(kind, found.flatten());
+                        settings.store();
+                        scheduler.broadcast(Event::WatchFace(settings.watch_face), now_ms);
+                        let _ = write!(out, "OK\r\n");
+                    }
+                }
+                ConsoleCommand::Perf => perf_dump = Some(0),
+                ConsoleCommand::Events => events_dump = Some((event_log::recent(), 0)),
+                ConsoleCommand::PerfReset => {
+                    perf.borrow_mut().reset();
+                    let _ = write!(out, "OK\r\n");
+                }
+                ConsoleCommand::PerfBudget(ms) => {
+                    perf.borrow_mut().set_budget_us(ms as u32 * 1000);
+                    let _ = write!(out, "OK\r\n");
+                }
+                ConsoleCommand::FrameDump => {
+                    let snapshot = Snapshot::capture(display.framebuffer());
+                    let _ = snapshot.write_header(out);
+                    frame_dump = Some((snapshot, 0));
+                }
+                ConsoleCommand::SysInfo => {
+                    sysinfo_dump = Some((SystemInfo { usb: usb_state, ..system_info }, 0));
+                }
+                ConsoleCommand::Features => features_dump = Some(0),
+                ConsoleCommand::FrameDumpDefmt => {
+                    dump_framebuffer(&display);
+                    let _ = write!(out, "OK\r\n");
+                }
+                ConsoleCommand::Scan => match check_bus(&mut display, &mut hub, link.is_online(), &watchdog) {
+                    Some(report) => {
+                        let _ = write!(out, "SCAN");
+                        for address in &report.addresses {
+                            let _ = write!(out, " {:#04x}", address);
+                        }
+                        let _ = write!(out, "\r\nLINES ");
+                        match report.lines {
+                            Some(lines) => {
+                                let _ = lines.write_summary(out);
+                            }
+                            None => {
+                                let _ = write!(out, "n/a");
+                            }
+                        }
+                        let _ = write!(out, "\r\nOK\r\n");
+                    }
+                    None => {
+                        let _ = write!(out, "ERR bus busy\r\n");
+                    }
+                },
+                ConsoleCommand::LogDump => {
+                    let _ = write_csv_header(&channels, out);
+                    dump = Some((datalog.index().clone(), 0));
+                }
+                ConsoleCommand::Telemetry(Some(rate)) => {
+                    telemetry.start(rate, now_ms);
+                    telemetry_to = source;
+                    let _ = telemetry::write_header(telemetry.fields(), &sensors.borrow(), out);
+                }
+                ConsoleCommand::Telemetry(None) => {
+                    telemetry.stop();
+                    let _ = write!(out, "OK\r\n");
+                }
+                ConsoleCommand::Status => {
+                    let line = status_line(&sensors.borrow(), &perf.borrow(), scheduler.current(), now_ms);
+                    let _ = out.write_str(&line);
+                }
+                ConsoleCommand::StatusEvery(Some(seconds)) => {
+                    status_reporter.start(seconds, now_ms);
+                    status_to = source;
+                    let _ = write!(out, "OK\r\n");
+                }
+                ConsoleCommand::StatusEvery(None) => {
+                    status_reporter.stop();
+                    let _ = write!(out, "OK\r\n");
+                }
+                ConsoleCommand::TelemetryFields(list) => {
+                    let registry = sensors.borrow();
+                    match Fields::parse(list, |name| registry.find(name)) {
+                        Some(fields) => {
+                            telemetry.set_fields(fields);
+                            let _ = telemetry::write_header(fields, &registry, out);
+                        }
+                        None => {
+                            let _ = write!(out, "ERR unknown field\r\n");
+                        }
+                    }
+                }
+                ConsoleCommand::ClockSet { hour, minute } => {
+                    wall_clock.set(hour, minute, now_ms);
+                    // 设的是当地时间，当地的日期不动；DS3231 里存 UTC，换算的时候日期可能跟着变。
+                    // 停过电的话日期也不可信，从 2000-01-01 算
+                    if let (Some(rtc), Some(bus)) = (hub.rtc.as_mut(), display.shared_bus()) {
+                        let date = settings.timezone.to_local(&rtc.read_time(bus).unwrap_or(Time::EPOCH));
+                        let utc = settings.timezone.to_utc(&date.with_time_of_day(hour, minute, 0));
+                        if rtc.set_time(bus, &utc) {
+                            scheduler.broadcast(Event::RtcTime(utc), now_ms);
+                        } else {
+                            warn!("DS3231 write failed");
+                        }
+                    }
+                    scheduler.broadcast(Event::ClockSet(wall_clock), now_ms);
+                    let _ = write!(out, "OK\r\n");
+                }
+                ConsoleCommand::Text(text) => {
+                    let mut message = RemoteText::new();
+                    // 一行命令本身就不会比这个长
+                    let _ = message.push_str(text);
+                    scheduler.broadcast(Event::RemoteText(message), now_ms);
+                    show_remote_page(&mut scheduler);
+                    woke |= screen.activity(now_ms);
+                    let _ = write!(out, "OK\r\n");
+                }
+                ConsoleCommand::Clear => {
+                    scheduler.broadcast(Event::RemoteClear, now_ms);
+                    show_remote_page(&mut scheduler);
+                    woke |= screen.activity(now_ms);
+                    let _ = write!(out, "OK\r\n");
+                }
+                ConsoleCommand::Bright(level) => match display.set_contrast(level) {
+                    Ok(()) => {
+                        // 热的时候还是要打折，下一圈按调暗以后的重发
+                        panel.set_level(level);
+                        woke |= screen.activity(now_ms);
+                        let _ = write!(out, "OK\r\n");
+                    }
+                    Err(_) => {
+                        let _ = write!(out, "ERR display not responding\r\n");
+                    }
+                },
+                ConsoleCommand::Invert(on) => {
+                    remote_inverted = on;
+                    woke |= screen.activity(now_ms);
+                    let _ = write!(out, "OK\r\n");
+                }
+                ConsoleCommand::ImageBegin { slot, width, height } => match uploads.begin(slot, width, height, now_ms) {
+                    Ok(()) => {
+                        let _ = write!(out, "OK {} bytes\r\n", image_slots::image_len(width, height));
+                    }
+                    Err(ImageError::TooLarge) => {
+                        let _ = write!(out, "ERR {} (max {}x{})\r\n", ImageError::TooLarge.message(), image_slots::MAX_WIDTH, image_slots::MAX_HEIGHT);
+                    }
+                    Err(err) => {
+                        let _ = write!(out, "ERR {}\r\n", err.message());
+                    }
+                },
+                ConsoleCommand::ImageData(chunk) => match uploads.data(chunk, now_ms) {
+                    Ok(received) => {
+                        let _ = write!(out, "OK {}\r\n", received);
+                    }
+                    Err(err) => {
+                        let _ = write!(out, "ERR {}\r\n", err.message());
+                    }
+                },
+                // 擦写一个扇区期间关中断，USB 少 poll 几十毫秒，主机那边会重试
+                // 电压低的时候先不收尾，传好的数据还留着，电压回来了(10 秒内)再发一次 END
+                ConsoleCommand::ImageEnd(_) if !low_voltage::flash_writes_allowed() => {
+                    let _ = write!(out, "ERR flash writes paused\r\n");
+                }
+                ConsoleCommand::ImageEnd(crc) => match uploads.end(crc) {
+                    Ok(slot) => {
+                        info!("image stored in slot {}", slot + 1);
+                        let _ = write!(out, "OK slot {} {}\r\n", slot + 1, image_slots::state(slot));
+                    }
+                    Err(ImageError::Short { received, expected }) => {
+                        let _ = write!(out, "ERR {} ({}/{} bytes)\r\n", ImageError::Short { received, expected }.message(), received, expected);
+                    }
+                    Err(err) => {
+                        let _ = write!(out, "ERR {}\r\n", err.message());
+                    }
+                },
+                ConsoleCommand::ImageShow { slot, top_left: (x, y) } => match image_slots::state(slot) {
+                    SlotState::Image { .. } => {
+                        scheduler.broadcast(Event::RemoteImage { slot, top_left: Point::new(x, y) }, now_ms);
+                        show_remote_page(&mut scheduler);
+                        woke |= screen.activity(now_ms);
+                        let _ = write!(out, "OK\r\n");
+                    }
+                    state => {
+                        let _ = write!(out, "ERR slot {} is {}\r\n", slot + 1, state);
+                    }
+                },
+                ConsoleCommand::ImageList => {
+                    for slot in 0..image_slots::SLOTS {
+                        let _ = write!(out, "slot {} {}\r\n", slot + 1, image_slots::state(slot));
+                    }
+                    let _ = write!(out, "OK\r\n");
+                }
+                #[cfg(feature = "devtools")]
+                ConsoleCommand::Preview { width, height, data } => match preview.borrow_mut().chunk(width, height, data, now_ms) {
+                    Ok(Progress::Partial { received, expected }) => {
+                        let _ = write!(out, "OK {}/{}\r\n", received, expected);
+                    }
+                    Ok(Progress::Complete) => {
+                        if scheduler.current() != PREVIEW_PAGE {
+                            scheduler.apply(Transition::Push(PREVIEW_PAGE));
+                        }
+                        woke |= screen.activity(now_ms);
+                        let _ = write!(out, "OK shown\r\n");
+                    }
+                    Err(ImageError::TooLarge) => {
+                        let _ = write!(out, "ERR {} (max {}x{})\r\n", ImageError::TooLarge.message(), image_slots::MAX_WIDTH, image_slots::MAX_HEIGHT);
+                    }
+                    Err(err) => {
+                        let _ = write!(out, "ERR {}\r\n", err.message());
+                    }
+                },
+                #[cfg(feature = "devtools")]
+                ConsoleCommand::PreviewAt(x, y) => {
+                    preview.borrow_mut().set_origin(Point::new(x, y));
+                    let _ = write!(out, "OK\r\n");
+                }
+                // 预览下面的页面整页重画，屏幕上不留预览的东西
+                #[cfg(feature = "devtools")]
+                ConsoleCommand::PreviewOff => {
+                    if scheduler.current() == PREVIEW_PAGE {
+                        scheduler.apply(Transition::Pop);
+                    }
+                    let _ = write!(out, "OK\r\n");
+                }
+                #[cfg(feature = "devtools")]
+                ConsoleCommand::Grid(on) => {
+                    preview.borrow_mut().set_grid(on);
+                    let _ = write!(out, "OK\r\n");
+                }
+                ConsoleCommand::Usage(usage) => {
+                    let _ = write!(out, "ERR usage: {}\r\n", usage);
+                }
+                ConsoleCommand::TooLong => {
+                    let _ = write!(out, "ERR line too long\r\n");
+                }
+                ConsoleCommand::Unknown(Some(closest)) => {
+                    let _ = write!(out, "ERR unknown command, did you mean {}?\r\n", closest);
+                }
+                ConsoleCommand::Unknown(None) => {
+                    let _ = write!(out, "ERR unknown command, try HELP\r\n");
+                }
+                ConsoleCommand::Help(topic) => help_dump = Some((Help::new(topic), 0)),
+                #[cfg(feature = "pico-w")]
+                ConsoleCommand::SettingsWifi(account) => {
+                    let credentials = match account.map(|(ssid, password)| wifi_config::WifiCredentials::new(ssid, password)) {
+                        Some(None) => {
+                            let _ = write!(out, "ERR usage: SETTINGS WIFI <ssid> [password]|OFF\r\n");
+                            continue;
+                        }
+                        credentials => credentials.flatten(),
+                    };
+                    if wifi_config::store(credentials.as_ref()) {
+                        pending_join = Some(credentials);
+                        let _ = write!(out, "OK\r\n");
+                    } else {
+                        let _ = write!(out, "ERR flash writes paused\r\n");
+                    }
+                }
+            }
+        }
+        #[cfg(feature = "pico-w")]
+        if let (Some(net), Some(credentials)) = (network.as_mut(), pending_join.take()) {
+            net.join(credentials);
+        }
+
+        // 上传到一半断了的话过一会儿作废，内存里攒的不留着
+        if uploads.expire(now_ms) {
+            info!("image upload timed out");
+        }
+        #[cfg(feature = "devtools")]
+        if preview.borrow_mut().expire(now_ms) {
+            info!("preview upload timed out");
+        }
+
+        // 话筒采满一个窗口就换缓冲区，算好的电平喂给电平表
+        #[cfg(feature = "mic-vu")]
+        hub.sampler.service(now_ms);
+
+        // 到时间的传感器通道读一次，下面的遥测、记录、告警和仪表盘都只看注册表里的读数
+        sensors.borrow_mut().poll(&mut hub, now_ms);
+
+        if let (Some(mut out), Some((index, next))) = (reply_to(stream_to, &mut usb, &mut network), dump.as_mut()) {
+            while *next < index.slots() && out.tx_free() >= CSV_LINE_MAX {
+                if let Some(record) = index.get(*next) {
+                    let _ = record.write_csv(&channels, &mut out);
+                }
+                *next += 1;
+            }
+            if *next >= index.slots() && out.tx_free() >= CSV_LINE_MAX {
+                let _ = write!(out, "OK\r\n");
+                dump = None;
+            }
+        }
+
+        if let (Some(mut out), Some(row)) = (reply_to(stream_to, &mut usb, &mut network), perf_dump.as_mut()) {
+            let table = perf.borrow();
+            while *row < table.lines() && out.tx_free() >= perf::LINE_MAX {
+                let _ = table.write_line(*row, &mut out);
+                *row += 1;
+            }
+            if *row >= table.lines() && out.tx_free() >= perf::LINE_MAX {
+                let _ = write!(out, "OK\r\n");
+                perf_dump = None;
+            }
+        }
+
+        if let (Some(mut out), Some((entries, index))) = (reply_to(stream_to, &mut usb, &mut network), events_dump.as_mut()) {
+            while *index < entries.len() && out.tx_free() >= event_log::LINE_MAX {
+                let _ = entries[*index].write_line(now_ms, &mut out);
+                *index += 1;
+            }
+            if *index >= entries.len() && out.tx_free() >= event_log::LINE_MAX {
+                let _ = write!(out, "OK\r\n");
+                events_dump = None;
+            }
+        }
+
+        if let (Some(mut out), Some((help, index))) = (reply_to(stream_to, &mut usb, &mut network), help_dump.as_mut()) {
+            while *index < Help::LINES && out.tx_free() >= HELP_LINE_MAX {
+                let _ = help.write_line(*index, &mut out);
+                *index += 1;
+            }
+            if *index >= Help::LINES && out.tx_free() >= HELP_LINE_MAX {
+                let _ = write!(out, "OK\r\n");
+                help_dump = None;
+            }
+        }
+
+        if let (Some(mut out), Some((info, index))) = (reply_to(stream_to, &mut usb, &mut network), sysinfo_dump.as_mut()) {
+            while *index < system_info::LINES && out.tx_free() >= system_info::LINE_MAX {
+                let _ = info.write_line(*index, &mut out);
+                *index += 1;
+            }
+            if *index >= system_info::LINES && out.tx_free() >= system_info::LINE_MAX {
+                let _ = write!(out, "OK\r\n");
+                sysinfo_dump = None;
+            }
+        }
+
+        if let (Some(mut out), Some(index)) = (reply_to(stream_to, &mut usb, &mut network), features_dump.as_mut()) {
+            while *index < capabilities::LINES && out.tx_free() >= capabilities::LINE_MAX {
+                let _ = capabilities::write_line(*index, &mut out);
+                *index += 1;
+            }
+            if *index >= capabilities::LINES && out.tx_free() >= capabilities::LINE_MAX {
+                let _ = write!(out, "OK\r\n");
+                features_dump = None;
+            }
+        }
+
+        if let (Some(mut out), Some((snapshot, line))) = (reply_to(stream_to, &mut usb, &mut network), frame_dump.as_mut()) {
+            while *line < snapshot::LINES && out.tx_free() >= snapshot::LINE_MAX {
+                let _ = snapshot.write_line(*line, &mut out);
+                *line += 1;
+            }
+            if *line >= snapshot::LINES && out.tx_free() >= snapshot::LINE_MAX {
+                let _ = write!(out, "OK\r\n");
+                frame_dump = None;
+            }
+        }
+
+        if let Some(mut out) = reply_to(telemetry_to, &mut usb, &mut network) {
+            if telemetry.is_due(now_ms) {
+                // 先拼好一整行，发送缓冲区放不下就整行扔掉，不能等
+                let mut line: String<{ telemetry::LINE_MAX }> = String::new();
+                let _ = telemetry::write_line(
+                    telemetry.fields(),
+                    &sensors.borrow(),
+                    now_ms,
+                    frame_max_us,
+                    &mut line,
+                );
+                frame_max_us = 0;
+                if out.tx_free() >= line.len() {
+                    let _ = out.write_str(&line);
+                } else {
+                    telemetry::record_drop();
+                }
+            }
+        }
+        if let Some(mut out) = reply_to(status_to, &mut usb, &mut network) {
+            if status_reporter.is_due(now_ms) {
+                // 和遥测一样，放不下就等下一次
+                let line = status_line(&sensors.borrow(), &perf.borrow(), scheduler.current(), now_ms);
+                if out.tx_free() >= line.len() {
+                    let _ = out.write_str(&line);
+                }
+            }
+        }
+
+        // 到点的定时任务，按 jobs 里的顺序一个一个跑
+        while let Some(job) = jobs.next_due(now_ms) {
+            match job {
+                // VSYS 通道每读到一个新数看一次电压
+                Job::VsysCheck => {
+                    let change = vsys_channel.and_then(|id| sensors.borrow().value(id)).and_then(|mv| power.update(mv));
+                    match change {
+                        Some(PowerLevel::Critical) => {
+                            tones.stop(&mut buzzer);
+                            inverted = false;
+                            change_mode(&mut device_mode, ModeEvent::BatteryCritical);
+                            park_low_battery(&mut display, (&timer, &watchdog), &mut alarm, sensors, &mut hub, &mut power, &mut status_led);
+                            change_mode(&mut device_mode, ModeEvent::Woke);
+                            scheduler.invalidate();
+                            dimmer.invalidate();
+                            auto_light.invalidate();
+                            last_loop_us = timer.get_counter().ticks();
+                            continue 'main;
+                        }
+                        Some(PowerLevel::Low) => {
+                            warn!("battery low, flash writes paused");
+                            scheduler.show_toast("battery low", now_ms);
+                        }
+                        Some(PowerLevel::Normal) => info!("battery voltage recovered"),
+                        None => {}
+                    }
+                }
+                // 电压低的时候不记(写 flash)，这段时间的记录就没有了
+                Job::Log => {
+                    if settings.logging && low_voltage::flash_writes_allowed() {
+                        let registry = sensors.borrow();
+                        let values = core::array::from_fn(|i| registry.value(ChannelId(i as u8)));
+                        datalog.append(now_ms / 1000, values);
+                    }
+                }
+                Job::WakeAlarm => match alarm_clock.update(&settings.wake_alarms, wall_clock.minute_of_day(now_ms), now_ms) {
+                    Some(AlarmEvent::Ring(ring)) => {
+                        info!("wake alarm {} ringing", ring.index + 1);
+                        carousel.hold(&settings.carousel, now_ms);
+                        demo_auto.input(now_ms);
+                        woke |= screen.activity(now_ms);
+                        show_wake_alarm(&mut scheduler, ring, now_ms);
+                    }
+                    Some(AlarmEvent::TimedOut) => {
+                        info!("wake alarm timed out");
+                        tones.stop(&mut buzzer);
+                        scheduler.broadcast(Event::WakeAlarm(None), now_ms);
+                    }
+                    None => {}
+                },
+                // 总线正忙就等下一分钟
+                Job::RtcSync => {
+                    sync_wall_clock(&mut display, &mut hub, &mut wall_clock, &settings.timezone, &mut scheduler, now_ms);
+                }
+                Job::AutoBrightness => {
+                    let mv = adc0_channel.and_then(|id| sensors.borrow().value(id));
+                    if let Some(level) = auto_light.update(&settings.auto_brightness, mv) {
+                        panel.set_level(level);
+                    }
+                }
+                // 栈又深了就打一条，不深不打
+                Job::StackCheck => {
+                    let peak = stack::stack_watermark();
+                    if peak > stack_peak {
+                        stack_peak = peak;
+                        if let Some(usage) = stack::usage() {
+                            info!("stack peak {} B, {} B free", usage.peak, usage.free());
+                        }
+                    }
+                }
+                Job::AlarmCheck => {
+                    let registry = sensors.borrow();
+                    let changes = alarm_engine.evaluate(&settings.alarm_rules, |id| registry.value(id));
+                    if !changes.is_empty() {
+                        for (i, rule) in settings.alarm_rules.rules().iter().enumerate() {
+                            let raised = changes.raised & (1 << i) != 0;
+                            let cleared = changes.cleared & (1 << i) != 0;
+                            if raised {
+                                event_log::record(SystemEvent::AlarmFired(i as u8 + 1));
+                            }
+                            if raised && rule.actions.contains(Actions::BEEP) {
+                                tones.play(Sound::Alert);
+                            }
+                            // 要闪屏的规则顺便弹个框说清楚是哪一条，比如 "alarm 1: temp>50.00C"
+                            if raised && rule.actions.contains(Actions::FLASH) {
+                                let mut message = PopupText::new();
+                                let info = channels.get(rule.source.0 as usize).copied().unwrap_or(UNKNOWN_CHANNEL);
+                                let _ = write!(message, "alarm {}: ", i + 1);
+                                let _ = rule.write_condition(&info, &mut message);
+                                show_popup(&mut scheduler, message, now_ms);
+                            }
+                            if !(raised || cleared) || !rule.actions.contains(Actions::SERIAL) {
+                                continue;
+                            }
+                            // 串口上打一行，比如 "ALARM 1 ON temp>50.00C now 51.20C"
+                            if let Some(usb) = usb.as_mut() {
+                                let _ = write!(usb, "ALARM {} {} ", i + 1, if raised { "ON" } else { "OFF" });
+                                let info = channels.get(rule.source.0 as usize).copied().unwrap_or(UNKNOWN_CHANNEL);
+                                let _ = rule.write_condition(&info, usb);
+                                let _ = write!(usb, " now ");
+                                let _ = info.write_value(registry.value(rule.source), usb);
+                                let _ = write!(usb, "\r\n");
+                            }
+                        }
+                        show_alarms(&mut scheduler, &mut alerts, &settings.alarm_rules, alarm_engine.firing(), now_ms);
+                    }
+                }
+            }
+        }
+        // 电压回来了，电压低的时候没存成的设置现在存(存的是最新的)
+        if low_voltage::take_pending_save() {
+            settings.store();
+            scheduler.show_toast("settings saved", now_ms);
+        }
+        scheduler.set_battery_indicator(power.level() != PowerLevel::Normal, low_voltage::save_pending());
+
+        // 开着自动亮度的时候亮度归它管(上面的 Job::AutoBrightness)
+        if !settings.auto_brightness.is_enabled() {
+            if let Some(level) = dimmer.update(
+                &settings.dim_schedule,
+                wall_clock.minute_of_day(now_ms),
+                settings.contrast,
+                now_ms,
+            ) {
+                panel.set_level(level);
+            }
+        }
+        // 闹钟在响的时候不因为没人操作关屏，当成一直有人在操作，响完了从那时候开始计时。
+        // 模式要在下面用到它的地方(屏幕保养、画图)之前切好，不然用的是上一圈的
+        if device_mode == Mode::Alarm {
+            screen.activity(now_ms);
+        } else if screen.update(now_ms) {
+            info!("no input for a while, screen off");
+            let _ = display.send_commands(&command::display_on(false));
+        }
+        let observed = Observed {
+            ringing: alarm_clock.ringing().is_some(),
+            screen_off: screen.is_asleep(),
+            in_menu: scheduler.current() == SETTINGS_MENU_PAGE,
+        };
+        while let Some(event) = observed.event(device_mode) {
+            if !change_mode(&mut device_mode, event) {
+                break;
+            }
+        }
+        let temp = temp_channel.and_then(|id| sensors.borrow().value(id));
+        if let Some(level) = panel.update(now_ms, device_mode.renders() && link.is_online(), temp, settings.thermal_derate) {
+            let _ = display.send_commands(&command::contrast(level));
+        }
+        if panel.save_due(now_ms) && low_voltage::flash_writes_allowed() {
+            panel.save(now_ms);
+        }
+        pulse_counter::update(now_ms);
+        let pulses = pulse_counter::total();
+        if pulse_store.save_due(pulses, now_ms) && low_voltage::flash_writes_allowed() {
+            pulse_store.save(pulses, now_ms);
+        }
+
+        // 防烧屏平移：换个原点整屏重画
+        if burn_in.update(now_ms) {
+            display.framebuffer_mut().set_shift(burn_in.offset());
+            scheduler.invalidate();
+        }
+
+        // 关着屏就不画了；显存和 DMA 用的暂存区是分开的，所以就算上一帧还在发也可以先画
+        if device_mode.renders() && link.is_online() {
+            if device_mode.autoplays() && !demo_auto.update(&settings.demo_auto, &mut scheduler, DEMO_MENU_PAGE, &DEMO_SCENES, now_ms) {
+                carousel.update(&settings.carousel, &mut scheduler, now_ms);
+            }
+            // 换页了就先存一份屏幕上现在的样子，新页面画好以后从这一份动画过去。上一帧还在发的话不播
+            let effect = scheduler.take_transition().filter(|_| !display.is_flushing());
+            let from = effect.map(|_| display.framebuffer().composited());
+            if scheduler.frame(display.framebuffer_mut(), now_ms) {
+                if let (Some(effect), Some(from)) = (effect, from) {
+                    let to = display.framebuffer().composited();
+                    if let Err(err) = transition(&mut display, &from, &to, effect, &mut timer) {
+                        warn!("page transition failed: {}", defmt::Debug2Format(&err));
+                    }
+                }
+            }
+        }
+        service_display(&mut display, &mut link, &mut alerts, perf, &mut flush_started, now_ms);
+        // 屏幕掉线了就定时探测，接上了按现在的亮度、旋转、镜像重新初始化，反色和关屏状态下面补发
+        if link.probe_due(now_ms) {
+            if display.interface_mut().probe() && display.init().is_ok() {
+                link.restored();
+                info!("display back online (restored {} times)", link.restore_count());
+                inverted = false;
+                if screen.is_asleep() {
+                    let _ = display.send_commands(&command::display_on(false));
+                }
+                scheduler.invalidate();
+            } else {
+                link.probe_failed(now_ms);
+            }
+        }
+        if let Some(led) = status_led.as_mut() {
+            let on = if !link.is_online() {
+                blink_code::NO_DISPLAY.level(now_ms)
+            } else if device_mode == Mode::Alarm {
+                blink_code::WAKE_ALARM.level(now_ms)
+            } else {
+                settings.alarm_rules.active_actions(alarm_engine.firing()).contains(Actions::LED)
+                    && blink_code::ALARM.level(now_ms)
+            };
+            led.set_on(on);
+        }
+
+        // BMP280 要等屏幕的 DMA 空下来才能用总线，这一圈正好在发显存就下一圈再读
+        if let Some(baro) = hub.baro.as_mut() {
+            if now_ms >= next_baro_ms {
+                if let Some(bus) = display.shared_bus() {
+                    baro.update(bus);
+                    next_baro_ms = now_ms + BARO_POLL_MS as u64;
+                }
+            }
+        }
+        // 温湿度传感器也一样，测量节奏它自己管
+        if let Some(monitor) = hub.humidity.as_mut() {
+            if monitor.is_due(now_ms) {
+                if let Some(bus) = display.shared_bus() {
+                    monitor.service(bus, now_ms);
+                }
+            }
+        }
+        // 测距也是；关着屏的时候只用来等手靠近，测得慢一点省电。有东西在亮屏距离以内就当有人在操作
+        if let Some(ranger) = hub.distance.as_mut() {
+            ranger.set_poll_ms(if device_mode == Mode::LowPower { vl53l0x::ASLEEP_POLL_MS } else { vl53l0x::AWAKE_POLL_MS });
+            if ranger.is_due(now_ms) {
+                if let Some(bus) = display.shared_bus() {
+                    let measured = ranger.service(bus, now_ms);
+                    let near = ranger.distance_mm().is_some_and(|mm| mm <= settings.wake_mm);
+                    if measured && settings.wake_mm > 0 && near && screen.activity(now_ms) {
+                        info!("proximity wake");
+                        woke = true;
+                    }
+                }
+            }
+        }
+
+        // 有告警的时候屏幕反色闪烁，灯带也跟着亮；防烧屏和 INVERT 命令的反色叠在上面
+        // 显示方案的反色垫在最底下
+        let profile_inverted = settings.display_profile.and_then(display_profile::get).is_some_and(|profile| profile.inverted);
+        let flash = alerts.display_inverted(now_ms) ^ burn_in.inverted(now_ms) ^ remote_inverted ^ profile_inverted;
+        if flash != inverted && display.set_inverted(flash).is_ok() {
+            inverted = flash;
+        }
+        #[cfg(feature = "ws2812")]
+        strip.update(&mut alerts, now_ms);
+
+        // 告警升级的时候响一下，同一个告警不会一直响
+        let level = alerts.level(now_ms);
+        if level > alert_level {
+            woke |= screen.activity(now_ms);
+            match level {
+                AlertLevel::Notice => tones.play(Sound::CountdownDone),
+                AlertLevel::Warning | AlertLevel::Critical => tones.play(Sound::Alert),
+                AlertLevel::None => {}
+            }
+        }
+        alert_level = level;
+        // 闹钟响着就一遍一遍地放
+        if device_mode == Mode::Alarm && tones.is_idle() {
+            tones.play(Sound::WakeAlarm);
+        }
+        tones.tick(now_ms, &mut buzzer);
+
+        if woke {
+            let _ = display.send_commands(&command::display_on(true));
+            scheduler.invalidate();
+        }
+
+        // 反应游戏的两个键由 core1 的中断记时间，core0 醒来清按键的中断标志会跟它抢，所以这一页只靠定时器醒
+        let want_input_wake = scheduler.current() != REACTION_PAGE;
+        if want_input_wake != input_wake {
+            buttons.set_wake_on_press(want_input_wake);
+            input_wake = want_input_wake;
+        }
+        let worked_us = timer.get_counter().ticks() - now_us;
+        if worked_us < IDLE_SLICE_US {
+            let us = (IDLE_SLICE_US - worked_us) as u32;
+            cpu.idle(|| {
+                if input_wake {
+                    idle_until_input(&mut alarm, &mut buttons, us)
+                } else {
+                    idle_for(&mut alarm, us)
+                }
+            });
+        }
+        cpu.update(perf::ticks());
+        // 睡着的时间不算进帧时间
+        last_loop_us = timer.get_counter().ticks();
+    }
+}
+
+/// 切换设备模式(见 mode.rs)，切了就打一条日志。不允许的切换返回 false，模式不变
+fn change_mode(mode: &mut Mode, event: ModeEvent) -> bool {
+    let next = mode::transition(*mode, event);
+    if next == *mode {
+        return false;
+    }
+    info!("mode {} -> {} ({})", mode.name(), next.name(), event);
+    *mode = next;
+    true
+}
+
+/// 软关机：清屏、关显示和电荷泵、等按键都松开，然后进 DORMANT，按住关机键(默认 Select)从头开机(见 power_off.rs)
+///
+/// 看门狗先关掉：关着机没人喂，醒来数按住多久的时候参考时钟还在走，看门狗也跟着数，碰几下就复位开机了
+fn power_off<const B: usize>(display: &mut OledDisplay, (timer, watchdog): (&Timer, &Watchdog), buttons: &mut ButtonPad<B>, wake: Button, status_led: &mut Option<BoardLed>) -> ! {
+    info!("powering off, hold Select for 1s to start again");
+    watchdog.disable();
+    while display.is_flushing() {
+        let _ = display.poll_flush();
+    }
+    display.framebuffer_mut().clear();
+    let _ = display.flush();
+    let _ = display.send_commands(&command::display_on(false));
+    let _ = display.send_commands(&command::charge_pump(false));
+    if let Some(led) = status_led.as_mut() {
+        led.set_on(false);
+    }
+    // 确认的那一下 Select 可能还按着，松开了才能等下一次按下
+    while !buttons.is_idle() {
+        buttons.poll(timer.get_counter().ticks() / 1000, |_| {});
+    }
+    power_off::shut_down(buttons, wake)
+}
+
+/// 电压太低(见 low_voltage.rs)：画一屏 "battery low"，关屏、关电荷泵、关灯，然后每秒醒一次读 VSYS，
+/// 电压回到严重阈值以上(带回差)才重新开屏返回。这期间按键、USB、告警都不管，只是每次醒来喂狗
+fn park_low_battery<P: SamplerPin, V: SamplerPin>(
+    display: &mut OledDisplay,
+    (timer, watchdog): (&Timer, &Watchdog),
+    alarm: &mut Alarm0,
+    sensors: &RefCell<SensorRegistry<SensorHub<P, V>>>,
+    hub: &mut SensorHub<P, V>,
+    power: &mut LowVoltageMonitor,
+    status_led: &mut Option<BoardLed>,
+) {
+    warn!("battery critical, display off until the voltage recovers");
+    while display.is_flushing() {
+        let _ = display.poll_flush();
+    }
+    let _ = display.set_inverted(false);
+    let Ok(()) = low_voltage::draw_battery_low(display.framebuffer_mut());
+    let _ = display.flush();
+    idle_for(alarm, low_voltage::NOTICE_MS * 1000);
+    watchdog.feed();
+    let _ = display.send_commands(&command::display_on(false));
+    let _ = display.send_commands(&command::charge_pump(false));
+    if let Some(led) = status_led.as_mut() {
+        led.set_on(false);
+    }
+
+    let vsys_channel = sensors.borrow().find("vsys");
+    while power.level() == PowerLevel::Critical {
+        idle_for(alarm, 1_000_000);
+        watchdog.feed();
+        let mut registry = sensors.borrow_mut();
+        registry.poll(hub, timer.get_counter().ticks() / 1000);
+        if let Some(mv) = vsys_channel.and_then(|id| registry.value(id)) {
+            power.update(mv);
+        }
+    }
+
+    let _ = display.send_commands(&command::charge_pump(true));
+    let _ = display.send_commands(&command::display_on(true));
+    display.framebuffer_mut().mark_all_dirty();
+    info!("battery voltage back above critical");
+}
+
+/// 关着中断睡 `us` 微秒，由定时器闹钟叫醒。和床头钟一样，TIMER_IRQ_0 只用来叫醒 WFI，不会真的进处理函数：
+/// 闹钟在关中断的时候才打开，醒来以后取消、清掉挂起位、重新屏蔽了才开中断
+fn idle_for(alarm: &mut Alarm0, us: u32) {
+    cortex_m::interrupt::free(|_| {
+        alarm.enable_interrupt();
+        // 安全性：在 interrupt::free 里，处理函数进不去，出去之前又屏蔽掉了
+        unsafe { pac::NVIC::unmask(pac::Interrupt::TIMER_IRQ_0) };
+        if alarm.schedule(MicrosDurationU32::micros(us)).is_ok() {
+            cortex_m::asm::wfi();
+        }
+        let _ = alarm.cancel();
+        alarm.clear_interrupt();
+        alarm.disable_interrupt();
+        pac::NVIC::mask(pac::Interrupt::TIMER_IRQ_0);
+        pac::NVIC::unpend(pac::Interrupt::TIMER_IRQ_0);
+    });
+}
+
+/// 主循环每一圈剩下的时间：和 `idle_for` 一样睡 `us` 微秒，中间有按键按下也会醒
+///
+/// 要叫醒 WFI 得打开两个中断：TIMER_IRQ_0(闹钟 0 到点)和 IO_IRQ_BANK0(按键的下降沿，`set_wake_on_press`
+/// 打开的是 PROC0_INTE 里的位)，NVIC 里只在关着中断的这一小段解除屏蔽，所以处理函数都不会真的进去。
+/// 开着脉冲计数的时候 IO_IRQ_BANK0 一直是开着的(见 pulse_counter.rs)，临界区外面按下的键标志被处理函数清掉了，
+/// 这一下睡满这一毫秒才醒，下一圈轮询照样读得到。
+/// 按键的中断标志醒来以后才清：上一圈轮询完按键以后才按下的，标志一直挂着，WFI 马上返回，不会睡过这一下；
+/// 清掉以后才按下的，下一圈轮询的时候读引脚电平照样读得到。按键事件都是轮询出来的(消抖、长按)，中断只负责早点醒
+fn idle_until_input<const B: usize>(alarm: &mut Alarm0, buttons: &mut ButtonPad<B>, us: u32) {
+    cortex_m::interrupt::free(|_| {
+        alarm.enable_interrupt();
+        // 安全性：在 interrupt::free 里，处理函数进不去，出去之前又屏蔽掉了
+        unsafe {
+            pac::NVIC::unmask(pac::Interrupt::TIMER_IRQ_0);
+            pac::NVIC::unmask(pac::Interrupt::IO_IRQ_BANK0);
+        }
+        if alarm.schedule(MicrosDurationU32::micros(us)).is_ok() {
+            cortex_m::asm::wfi();
+        }
+        let _ = alarm.cancel();
+        alarm.clear_interrupt();
+        alarm.disable_interrupt();
+        buttons.clear_wake();
+        pac::NVIC::mask(pac::Interrupt::TIMER_IRQ_0);
+        pulse_counter::mask_gpio_irq();
+        pac::NVIC::unpend(pac::Interrupt::TIMER_IRQ_0);
+        pac::NVIC::unpend(pac::Interrupt::IO_IRQ_BANK0);
+    });
+}
+
+/// 重映射出来的逻辑输入：按键映射表里有的换成动作排队，别的交给当前页面
+fn route_inputs<const N: usize>(inputs: &mut Deque<InputEvent, { remap::QUEUE_LEN }>, scheduler: &mut Scheduler<'_, N>, actions: &mut Deque<Action, 4>, recorder: &mut Recorder, now_ms: u64) {
+    while let Some(input) = inputs.pop_front() {
+        recorder.record(input, now_ms);
+        match (INPUT_MAP.lookup(input, scheduler.current()), input) {
+            (Some(action), _) => {
+                let _ = actions.push_back(action);
+            }
+            (None, InputEvent::Button(event)) => scheduler.dispatch(Event::Button(event), now_ms),
+            (None, InputEvent::Gesture(gesture)) => scheduler.dispatch(Event::Gesture(gesture), now_ms),
+            (None, InputEvent::PowerHold) => {}
+        }
+    }
+}
+
+/// 换了按键重映射：关机键换了的话按满检测跟着换
+fn apply_input_bindings(remap: &mut Remapper, power_hold: &mut HoldGate, bindings: InputBindings) {
+    if bindings.key(Role::PowerHold) != remap.bindings().key(Role::PowerHold) {
+        *power_hold = HoldGate::new(bindings.key(Role::PowerHold));
+    }
+    remap.set_bindings(bindings);
+}
+
+/// 脉冲计数换了设置：中断里的换算和最短脉宽，两个通道的单位
+fn apply_pulse_config<S>(registry: &mut SensorRegistry<S>, (pulse, rate): (ChannelId, ChannelId), config: PulseConfig) {
+    pulse_counter::set_config(config);
+    registry.set_unit(pulse, config.unit_name());
+    registry.set_unit(rate, config.rate_unit());
+}
+
+/// 脉冲计数清零，能写 flash 的话马上存，不然重启又回来了(不能写的话等下一次定时存)
+fn reset_pulse_count(store: &mut CounterStore, now_ms: u64) {
+    pulse_counter::reset();
+    if low_voltage::flash_writes_allowed() {
+        store.save(0, now_ms);
+    }
+}
+
+/// 弹一个告警框，已经弹着的话换成新的消息
+fn show_popup<const N: usize>(scheduler: &mut Scheduler<'_, N>, message: PopupText, now_ms: u64) {
+    scheduler.broadcast(Event::Popup(message), now_ms);
+    if scheduler.current() != POPUP_PAGE {
+        scheduler.apply(Transition::Push(POPUP_PAGE));
+    }
+}
+
+/// 闹钟响了：告诉响的页面是哪个闹钟，把它放到最上面
+fn show_wake_alarm<const N: usize>(scheduler: &mut Scheduler<'_, N>, ring: Ringing, now_ms: u64) {
+    scheduler.broadcast(Event::WakeAlarm(Some(ring)), now_ms);
+    if scheduler.current() != RINGING_PAGE {
+        scheduler.apply(Transition::Push(RINGING_PAGE));
+    }
+}
+
+/// 把遥控页面放到最上面，已经在最上面就不动
+fn show_remote_page<const N: usize>(scheduler: &mut Scheduler<'_, N>) {
+    if scheduler.current() != REMOTE_PAGE {
+        scheduler.apply(Transition::Push(REMOTE_PAGE));
+    }
+}
+
+/// 床头钟模式：接管整个主循环，直到再长按一次 Back(`back`，按键重映射以后当 Back 用的那个物理键)，或者闹钟响了
+///
+/// 进来的时候等上一帧 DMA 发完，对比度压到最低；之后每秒醒一次更新钟面(只 flush 变了的字节)，
+/// 其余时间在 WFI 里睡觉。按键按下会通过 GPIO 中断把 CPU 叫醒，按着的时候每 10ms 轮询一次做消抖和长按。
+/// 中断只用来唤醒，不进中断处理函数：WFI 在关中断的临界区里执行，醒来以后在临界区里就把中断标志清掉。
+/// 脉冲计数的边沿例外，出了临界区照样进处理函数去数(见 pulse_counter.rs)。最多睡一秒，每次醒来喂狗
+fn run_sleep_clock<const B: usize>(
+    display: &mut OledDisplay,
+    (timer, watchdog): (&Timer, &Watchdog),
+    alarm: &mut Alarm0,
+    (buttons, back): (&mut ButtonPad<B>, Button),
+    clock: &WallClock,
+    wake: &mut AlarmClock,
+    wake_alarms: &WakeAlarmConfig,
+) {
+    info!("entering sleep clock");
+    while display.is_flushing() {
+        let _ = display.poll_flush();
+    }
+    let _ = display.set_inverted(false);
+    let _ = display.send_commands(&command::contrast(0));
+
+    let mut face = ClockFace::new();
+    let mut shown_second = None;
+    let mut minute_start_bytes = display.flushed_bytes();
+    buttons.set_wake_on_press(true);
+    alarm.enable_interrupt();
+    // 安全性：两个中断都不会真的进处理函数，见上面的说明
+    unsafe {
+        pac::NVIC::unmask(pac::Interrupt::TIMER_IRQ_0);
+        pac::NVIC::unmask(pac::Interrupt::IO_IRQ_BANK0);
+    }
+
+    loop {
+        watchdog.feed();
+        let now_us = timer.get_counter().ticks();
+        let now_ms = now_us / 1000;
+
+        let mut leave = false;
+        buttons.poll(now_ms, |event| {
+            if event == ButtonEvent::LongPress(back) {
+                leave = true;
+            }
+        });
+        if leave {
+            break;
+        }
+
+        let (hour, minute, second) = clock.time(now_ms);
+        if shown_second != Some(second) {
+            shown_second = Some(second);
+            // 闹钟在这里也要每秒看一次，响了就回主循环去响
+            if let Some(AlarmEvent::Ring(ring)) = wake.update(wake_alarms, clock.minute_of_day(now_ms), now_ms) {
+                info!("wake alarm {} ringing, leaving sleep clock", ring.index + 1);
+                break;
+            }
+            if let Err(err) = face.update(display, hour, minute, second) {
+                warn!("sleep clock flush failed: {}", defmt::Debug2Format(&err));
+                face.reset();
+            }
+            if second == 0 {
+                let bytes = display.flushed_bytes().wrapping_sub(minute_start_bytes);
+                info!("sleep clock: {} bytes flushed in the last minute", bytes);
+                minute_start_bytes = display.flushed_bytes();
+            }
+        }
+
+        // 没按键的时候睡到下一秒，按着的时候要轮询消抖
+        let sleep_us = if buttons.is_idle() {
+            1_000_000 - now_us % 1_000_000
+        } else {
+            10_000
+        };
+        let _ = alarm.schedule(MicrosDurationU32::micros(sleep_us as u32));
+        cortex_m::interrupt::free(|_| {
+            // 睡觉之前按键刚好按下的话，中断已经挂起了，WFI 会马上返回
+            cortex_m::asm::wfi();
+            alarm.clear_interrupt();
+            buttons.clear_wake();
+            pac::NVIC::unpend(pac::Interrupt::TIMER_IRQ_0);
+            pac::NVIC::unpend(pac::Interrupt::IO_IRQ_BANK0);
+        });
+    }
+
+    pac::NVIC::mask(pac::Interrupt::TIMER_IRQ_0);
+    pulse_counter::mask_gpio_irq();
+    alarm.disable_interrupt();
+    buttons.set_wake_on_press(false);
+    let _ = display.send_commands(&command::contrast(display.contrast()));
+    display.framebuffer_mut().mark_all_dirty();
+    info!("leaving sleep clock");
+}
+
+/// 正在触发的告警规则变了：更新右上角的图标和告警页面，有规则要闪屏的话报 `Warning`
+fn show_alarms<const N: usize>(
+    scheduler: &mut Scheduler<'_, N>,
+    alerts: &mut Alerts,
+    rules: &AlarmRules,
+    firing: u8,
+    now_ms: u64,
+) {
+    scheduler.set_alarm_indicator(firing != 0);
+    scheduler.broadcast(Event::Alarms(firing), now_ms);
+    if rules.active_actions(firing).contains(Actions::FLASH) {
+        alerts.raise(AlertLevel::Warning, now_ms);
+    } else {
+        alerts.clear(AlertLevel::Warning);
+    }
+}
+
+/// 推进屏幕的 DMA 刷新：先看看上一帧有没有发完，DMA 空闲了就把新的改动交给它，立刻返回
+///
+/// 发送失败算严重告警，下一帧发成功了就解除；连着失败几次当屏幕掉线，之后就不发了。
+/// 每一帧从开始发到发完的时间记进 `perf`
+fn service_display<const N: usize>(
+    display: &mut OledDisplay,
+    link: &mut DisplayLink,
+    alerts: &mut Alerts,
+    perf: &RefCell<PerfTable<N>>,
+    flush_started: &mut Option<u32>,
+    now_ms: u64,
+) {
+    if !link.is_online() {
+        return;
+    }
+    let polled = display.poll_flush();
+    let finished = perf::ticks();
+    match polled {
+        Ok(FlushPoll::Complete) => {
+            alerts.clear(AlertLevel::Critical);
+            link.flush_succeeded();
+            if let Some(started) = flush_started.take() {
+                let elapsed = finished.wrapping_sub(started);
+                let mut perf = perf.borrow_mut();
+                if perf.record_flush(elapsed) {
+                    warn!("flush took {} us (budget {} us)", elapsed, perf.budget_us());
+                }
+            }
+        }
+        Ok(_) => {}
+        Err(err) => {
+            *flush_started = None;
+            flush_failed(link, alerts, err, now_ms);
+        }
+    }
+    // flush生效显示屏内容显示
+    if link.is_online() && !display.is_flushing() {
+        let started = perf::ticks();
+        match display.start_flush() {
+            Ok(true) => *flush_started = Some(started),
+            Ok(false) => {}
+            Err(err) => flush_failed(link, alerts, err, now_ms),
+        }
+    }
+}
+
+/// 扫一遍总线，再诊断 SCL 线：空闲采一批，让屏幕整屏发一帧(DMA 在后台发)边发边采一批，见 i2c_lines.rs。
+/// 屏幕掉线了就只看空闲电平。总线正忙返回 None
+fn check_bus<P, V>(display: &mut OledDisplay, hub: &mut SensorHub<P, V>, online: bool, watchdog: &Watchdog) -> Option<BusReport>
+where
+    P: SamplerPin,
+    V: SamplerPin,
+{
+    // 扫总线、诊断 SCL 线要跑好一会儿，一边跑一边喂狗(见 watchdog_feed.rs)
+    let addresses = feed_during(watchdog, |checkpoint| Some(health::scan(display.shared_bus()?, checkpoint)))?;
+    #[cfg(not(feature = "mic-vu"))]
+    let lines = {
+        let mut tap = SclTap { sampler: &mut hub.sampler, pin: &mut hub.scl_tap };
+        Some(feed_during(watchdog, |checkpoint| {
+            diagnose_i2c_lines(&mut tap, checkpoint, || {
+                if !online {
+                    return false;
+                }
+                display.framebuffer_mut().mark_all_dirty();
+                display.start_flush().unwrap_or(false)
+            })
+        }))
+    };
+    #[cfg(feature = "mic-vu")]
+    let lines = {
+        let _ = (hub, online);
+        None
+    };
+    if let Some(lines) = lines {
+        info!("i2c lines: {}", lines);
+    }
+    Some(BusReport { addresses, lines })
+}
+
+fn flush_failed(link: &mut DisplayLink, alerts: &mut Alerts, err: FlushError, now_ms: u64) {
+    warn!("display flush failed: {}", err);
+    alerts.raise(AlertLevel::Critical, now_ms);
+    if link.flush_failed(now_ms) {
+        warn!("display offline (#{}), retrying every {} ms", link.offline_count(), display_link::REPROBE_INTERVAL_MS);
     }
 }
 
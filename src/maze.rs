@@ -0,0 +1,614 @@
+//! 迷宫：随机挖一个迷宫，再用广度优先搜索找从左上角到右下角的路，两步都一点一点画出来
+//!
+//! 格子是 `COLS` x `ROWS`(63x31)，每个格子占 2x2 像素：左上那个像素是墙角，右上是和右边格子之间的墙，
+//! 左下是和下面格子之间的墙，右下是格子自己。再加上最右边一列、最下边一行墙，一共 127x63 像素，
+//! 正好放进 128x64 的屏。屏幕竖着转(90/270 度)的时候放不下，画出屏幕的部分直接丢掉。
+//!
+//! 挖迷宫用的是回溯法(recursive backtracker)：从左上角出发，随机挑一个没去过的邻居打通墙走过去，
+//! 四周都去过了就退回上一格，退回起点就挖完了。不用递归，走过的路记在一个 heapless 的栈里，
+//! 每个格子只进栈一次，栈的长度是格子数就不会满。这样挖出来的迷宫所有格子都连通，而且没有环，
+//! 任意两格之间只有一条路。
+//!
+//! 找路是广度优先(BFS)：每个格子记下是从哪个方向来的，走到终点以后顺着往回找就是那条路。
+//! 队列用一个定长的 `Vec` 加一个读指针，每个格子也只进一次队列。
+//!
+//! `Carver`、`Solver` 一步只走一格，不碰屏幕，同一个种子总是挖出同一个迷宫。
+//! 演示页面(`MazePage`)每个逻辑 tick 走几步(见 `timestep`)：挖的时候没挖到的地方是实心的，找的时候搜过的格子填满，
+//! 找到了以后只留下那条路，一闪一闪停几秒，再换一个种子重新挖。Select 马上换一个，Back 回去。
+
+use heapless::Vec;
+
+use crate::app::{Canvas, Event};
+use crate::input::{Button, ButtonEvent};
+use crate::rng::Rng;
+use crate::screen::back_pops;
+
+/// 一行几个格子
+pub const COLS: usize = 63;
+
+/// 一共几行格子
+pub const ROWS: usize = 31;
+
+/// 格子总数
+pub const CELLS: usize = COLS * ROWS;
+
+/// 每个逻辑 tick 挖几步(打通一格或者退回一格都算一步)
+const CARVE_STEPS_PER_TICK: usize = 10;
+
+/// 每个逻辑 tick 搜几个格子
+const SOLVE_STEPS_PER_TICK: usize = 7;
+
+/// 找到以后停多少个逻辑 tick 再重新挖(3 秒)
+const SHOW_TICKS: u32 = 150;
+
+/// 路径每隔几个逻辑 tick 亮灭一次
+const BLINK_TICKS: u32 = 13;
+
+// 一行格子的墙存在一个 u64 里
+const _: () = assert!(COLS <= 64);
+
+/// 格子的编号：`y * COLS + x`
+pub type Cell = u16;
+
+const fn cell(x: usize, y: usize) -> Cell {
+    (y * COLS + x) as Cell
+}
+
+const fn cell_xy(cell: Cell) -> (usize, usize) {
+    (cell as usize % COLS, cell as usize / COLS)
+}
+
+/// 起点：左上角
+pub const START: Cell = cell(0, 0);
+
+/// 终点：右下角
+pub const GOAL: Cell = cell(COLS - 1, ROWS - 1);
+
+/// 往哪边走
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Dir {
+    North,
+    South,
+    East,
+    West,
+}
+
+impl Dir {
+    pub const ALL: [Dir; 4] = [Dir::North, Dir::South, Dir::East, Dir::West];
+
+    pub fn reverse(self) -> Dir {
+        match self {
+            Dir::North => Dir::South,
+            Dir::South => Dir::North,
+            Dir::East => Dir::West,
+            Dir::West => Dir::East,
+        }
+    }
+}
+
+/// 往 `dir` 走一格到哪个格子，出了边返回 None
+pub fn neighbour(from: Cell, dir: Dir) -> Option<Cell> {
+    let (x, y) = cell_xy(from);
+    match dir {
+        Dir::North => y.checked_sub(1).map(|y| cell(x, y)),
+        Dir::South => (y + 1 < ROWS).then(|| cell(x, y + 1)),
+        Dir::East => (x + 1 < COLS).then(|| cell(x + 1, y)),
+        Dir::West => x.checked_sub(1).map(|x| cell(x, y)),
+    }
+}
+
+/// 每个格子一位
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CellSet([u64; ROWS]);
+
+impl CellSet {
+    const fn new() -> Self {
+        Self([0; ROWS])
+    }
+
+    fn contains(&self, cell: Cell) -> bool {
+        let (x, y) = cell_xy(cell);
+        self.0[y] >> x & 1 != 0
+    }
+
+    fn insert(&mut self, cell: Cell) {
+        let (x, y) = cell_xy(cell);
+        self.0[y] |= 1 << x;
+    }
+}
+
+/// 迷宫：哪些墙打通了。每个格子只记右边和下边的墙，左边、上边的是邻居的右边、下边
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Maze {
+    east: CellSet,
+    south: CellSet,
+}
+
+impl Default for Maze {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Maze {
+    /// 所有墙都在
+    pub const fn new() -> Self {
+        Self {
+            east: CellSet::new(),
+            south: CellSet::new(),
+        }
+    }
+
+    /// 从 `from` 往 `dir` 走得过去吗(出了边的算墙)
+    pub fn is_open(&self, from: Cell, dir: Dir) -> bool {
+        match (dir, neighbour(from, dir)) {
+            (_, None) => false,
+            (Dir::East, _) => self.east.contains(from),
+            (Dir::South, _) => self.south.contains(from),
+            (Dir::West, Some(to)) => self.east.contains(to),
+            (Dir::North, Some(to)) => self.south.contains(to),
+        }
+    }
+
+    /// 打通 `from` 往 `dir` 那面墙，出了边的不管
+    pub fn open(&mut self, from: Cell, dir: Dir) {
+        match (dir, neighbour(from, dir)) {
+            (_, None) => {}
+            (Dir::East, _) => self.east.insert(from),
+            (Dir::South, _) => self.south.insert(from),
+            (Dir::West, Some(to)) => self.east.insert(to),
+            (Dir::North, Some(to)) => self.south.insert(to),
+        }
+    }
+}
+
+/// 回溯法挖迷宫，`step` 一次走一步
+pub struct Carver {
+    visited: CellSet,
+    stack: Vec<Cell, CELLS>,
+    rng: Rng,
+}
+
+impl Carver {
+    /// 从起点开始挖，配一个墙全在的 `Maze` 用
+    pub fn new(seed: u32) -> Self {
+        let mut carver = Self {
+            visited: CellSet::new(),
+            stack: Vec::new(),
+            rng: Rng::new(seed),
+        };
+        carver.visited.insert(START);
+        // 空栈，放得下
+        let _ = carver.stack.push(START);
+        carver
+    }
+
+    /// 这一格挖到过没有
+    pub fn is_carved(&self, cell: Cell) -> bool {
+        self.visited.contains(cell)
+    }
+
+    /// 挖完了(退回了起点)
+    pub fn is_done(&self) -> bool {
+        self.stack.is_empty()
+    }
+
+    /// 走一步：打通一格或者退回一格。已经挖完了返回 false
+    pub fn step(&mut self, maze: &mut Maze) -> bool {
+        let Some(&current) = self.stack.last() else {
+            return false;
+        };
+        let mut options: Vec<(Dir, Cell), 4> = Vec::new();
+        for dir in Dir::ALL {
+            if let Some(next) = neighbour(current, dir).filter(|&next| !self.is_carved(next)) {
+                let _ = options.push((dir, next));
+            }
+        }
+        if options.is_empty() {
+            self.stack.pop();
+            return true;
+        }
+        let (dir, next) = options[self.rng.next_range(options.len() as u32) as usize];
+        maze.open(current, dir);
+        self.visited.insert(next);
+        // 每个格子只进栈一次，栈和格子一样多，放得下
+        let _ = self.stack.push(next);
+        true
+    }
+
+    /// 一口气挖完
+    pub fn finish(&mut self, maze: &mut Maze) {
+        while self.step(maze) {}
+    }
+}
+
+/// 广度优先找路，`step` 一次搜一格
+pub struct Solver {
+    /// 每个格子是从哪边来的，没搜到的是 None
+    came_from: [Option<Dir>; CELLS],
+    queue: Vec<Cell, CELLS>,
+    /// 队列里下一个要搜的
+    head: usize,
+    found: bool,
+}
+
+impl Default for Solver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Solver {
+    /// 从起点开始找
+    pub fn new() -> Self {
+        let mut solver = Self {
+            came_from: [None; CELLS],
+            queue: Vec::new(),
+            head: 0,
+            found: false,
+        };
+        // 起点自己没有"从哪来"，随便记一个方向当成搜到过，找路的时候走到起点就停
+        solver.came_from[START as usize] = Some(Dir::North);
+        let _ = solver.queue.push(START);
+        solver
+    }
+
+    /// 这一格搜到过没有
+    pub fn is_seen(&self, cell: Cell) -> bool {
+        self.came_from[cell as usize].is_some()
+    }
+
+    /// 找到终点了
+    pub fn is_found(&self) -> bool {
+        self.found
+    }
+
+    /// 搜一格。找到终点或者搜完了(走不到终点)返回 false
+    pub fn step(&mut self, maze: &Maze) -> bool {
+        if self.found {
+            return false;
+        }
+        let Some(&current) = self.queue.get(self.head) else {
+            return false;
+        };
+        self.head += 1;
+        if current == GOAL {
+            self.found = true;
+            return false;
+        }
+        for dir in Dir::ALL {
+            if !maze.is_open(current, dir) {
+                continue;
+            }
+            let Some(next) = neighbour(current, dir) else {
+                continue;
+            };
+            if !self.is_seen(next) {
+                self.came_from[next as usize] = Some(dir.reverse());
+                // 每个格子只进队一次，放得下
+                let _ = self.queue.push(next);
+            }
+        }
+        true
+    }
+
+    /// 一口气搜完
+    pub fn finish(&mut self, maze: &Maze) {
+        while self.step(maze) {}
+    }
+
+    /// 从终点往回到起点的每一格(包括两头)，还没找到的话是空的
+    pub fn path(&self) -> impl Iterator<Item = Cell> + '_ {
+        let mut next = self.found.then_some(GOAL);
+        core::iter::from_fn(move || {
+            let current = next?;
+            next = if current == START {
+                None
+            } else {
+                self.came_from[current as usize].and_then(|dir| neighbour(current, dir))
+            };
+            Some(current)
+        })
+    }
+}
+
+/// 每个格子右下那个像素
+fn cell_pixel(cell: Cell) -> (u32, u32) {
+    let (x, y) = cell_xy(cell);
+    (2 * x as u32 + 1, 2 * y as u32 + 1)
+}
+
+/// 画的时候哪些格子算"挖开了"
+fn draw_maze(canvas: &mut Canvas, maze: &Maze, carved: impl Fn(Cell) -> bool) {
+    // 先全涂满墙，再把挖开的格子和打通的墙抠掉
+    for y in 0..2 * ROWS as u32 + 1 {
+        for x in 0..2 * COLS as u32 + 1 {
+            canvas.set_pixel(x, y, true);
+        }
+    }
+    for cell in 0..CELLS as Cell {
+        if !carved(cell) {
+            continue;
+        }
+        let (px, py) = cell_pixel(cell);
+        canvas.set_pixel(px, py, false);
+        if maze.is_open(cell, Dir::East) {
+            canvas.set_pixel(px + 1, py, false);
+        }
+        if maze.is_open(cell, Dir::South) {
+            canvas.set_pixel(px, py + 1, false);
+        }
+    }
+}
+
+/// 把 `filled` 的格子和它们之间打通的墙涂亮
+fn fill_cells(canvas: &mut Canvas, maze: &Maze, filled: impl Fn(Cell) -> bool) {
+    for cell in 0..CELLS as Cell {
+        if !filled(cell) {
+            continue;
+        }
+        let (px, py) = cell_pixel(cell);
+        canvas.set_pixel(px, py, true);
+        for (dir, dx, dy) in [(Dir::East, 1, 0), (Dir::South, 0, 1)] {
+            if maze.is_open(cell, dir) && neighbour(cell, dir).is_some_and(&filled) {
+                canvas.set_pixel(px + dx, py + dy, true);
+            }
+        }
+    }
+}
+
+/// 演示走到哪一步了
+enum Phase {
+    Carving(Carver),
+    Solving(Solver),
+    Showing { solver: Solver, ticks: u32 },
+}
+
+/// 演示页面的状态：迷宫、现在在干什么，和给每一轮挑种子的随机数
+pub struct MazeDemo {
+    maze: Maze,
+    phase: Phase,
+    seeds: Rng,
+}
+
+impl MazeDemo {
+    pub fn new(seeds: Rng) -> Self {
+        let mut demo = Self {
+            maze: Maze::new(),
+            phase: Phase::Solving(Solver::new()),
+            seeds,
+        };
+        demo.restart();
+        demo
+    }
+
+    /// 换一个种子重新挖
+    pub fn restart(&mut self) {
+        let seed = self.seeds.next_u32();
+        defmt::info!("maze seed {=u32:#x}", seed);
+        self.maze = Maze::new();
+        self.phase = Phase::Carving(Carver::new(seed));
+    }
+
+    /// 走一个逻辑 tick
+    pub fn advance(&mut self) {
+        match &mut self.phase {
+            Phase::Carving(carver) => {
+                for _ in 0..CARVE_STEPS_PER_TICK {
+                    carver.step(&mut self.maze);
+                }
+                if carver.is_done() {
+                    self.phase = Phase::Solving(Solver::new());
+                }
+            }
+            Phase::Solving(solver) => {
+                for _ in 0..SOLVE_STEPS_PER_TICK {
+                    if !solver.step(&self.maze) {
+                        let solver = core::mem::take(solver);
+                        self.phase = Phase::Showing { solver, ticks: 0 };
+                        return;
+                    }
+                }
+            }
+            Phase::Showing { ticks, .. } => {
+                *ticks += 1;
+                if *ticks >= SHOW_TICKS {
+                    self.restart();
+                }
+            }
+        }
+    }
+
+    pub fn render(&self, canvas: &mut Canvas) {
+        match &self.phase {
+            Phase::Carving(carver) => draw_maze(canvas, &self.maze, |cell| carver.is_carved(cell)),
+            Phase::Solving(solver) => {
+                draw_maze(canvas, &self.maze, |_| true);
+                fill_cells(canvas, &self.maze, |cell| solver.is_seen(cell));
+            }
+            Phase::Showing { solver, ticks } => {
+                draw_maze(canvas, &self.maze, |_| true);
+                if (ticks / BLINK_TICKS).is_multiple_of(2) {
+                    let mut on_path = CellSet::new();
+                    for cell in solver.path() {
+                        on_path.insert(cell);
+                    }
+                    fill_cells(canvas, &self.maze, |cell| on_path.contains(cell));
+                }
+            }
+        }
+    }
+}
+
+crate::screen! {
+    /// 迷宫演示
+    pub screen MazePage: MazeDemo {
+        name: "maze",
+        fps: 30,
+        event(demo, event, _now_ms) {
+            if let Event::Button(ButtonEvent::Pressed(Button::Select)) = event {
+                demo.restart();
+            }
+            back_pops(event)
+        }
+        step(demo, _tick) {
+            demo.advance();
+            true
+        }
+        render(demo, canvas, _now_ms) {
+            demo.render(canvas);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn carved(seed: u32) -> Maze {
+        let mut maze = Maze::new();
+        let mut carver = Carver::new(seed);
+        carver.finish(&mut maze);
+        assert!(carver.is_done());
+        maze
+    }
+
+    /// 打通了几面墙
+    fn open_walls(maze: &Maze) -> usize {
+        (0..CELLS as Cell)
+            .map(|cell| {
+                maze.is_open(cell, Dir::East) as usize + maze.is_open(cell, Dir::South) as usize
+            })
+            .sum()
+    }
+
+    /// 从起点顺着打通的墙能走到几个格子
+    fn reachable(maze: &Maze) -> usize {
+        let mut seen = CellSet::new();
+        let mut stack = std::vec![START];
+        seen.insert(START);
+        let mut count = 0;
+        while let Some(cell) = stack.pop() {
+            count += 1;
+            for dir in Dir::ALL {
+                if let Some(next) = neighbour(cell, dir).filter(|_| maze.is_open(cell, dir)) {
+                    if !seen.contains(next) {
+                        seen.insert(next);
+                        stack.push(next);
+                    }
+                }
+            }
+        }
+        count
+    }
+
+    #[test]
+    fn neighbours_at_edges() {
+        assert_eq!(neighbour(START, Dir::North), None);
+        assert_eq!(neighbour(START, Dir::West), None);
+        assert_eq!(neighbour(START, Dir::East), Some(cell(1, 0)));
+        assert_eq!(neighbour(START, Dir::South), Some(cell(0, 1)));
+        assert_eq!(neighbour(GOAL, Dir::East), None);
+        assert_eq!(neighbour(GOAL, Dir::South), None);
+        // 一行的最右边不会绕到下一行的最左边
+        assert_eq!(neighbour(cell(COLS - 1, 3), Dir::East), None);
+        assert_eq!(neighbour(cell(0, 3), Dir::West), None);
+        for cell in 0..CELLS as Cell {
+            for dir in Dir::ALL {
+                if let Some(next) = neighbour(cell, dir) {
+                    assert_eq!(neighbour(next, dir.reverse()), Some(cell));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn walls_are_shared() {
+        let mut maze = Maze::new();
+        let a = cell(5, 5);
+        maze.open(a, Dir::West);
+        maze.open(a, Dir::North);
+        assert!(maze.is_open(cell(4, 5), Dir::East));
+        assert!(maze.is_open(cell(5, 4), Dir::South));
+        assert!(!maze.is_open(a, Dir::East));
+        assert!(!maze.is_open(a, Dir::South));
+        // 出了边的一直是墙
+        maze.open(START, Dir::North);
+        maze.open(GOAL, Dir::East);
+        assert_eq!(maze, {
+            let mut expected = Maze::new();
+            expected.open(a, Dir::West);
+            expected.open(a, Dir::North);
+            expected
+        });
+    }
+
+    #[test]
+    fn carved_maze_is_spanning_tree() {
+        for seed in 0..300 {
+            let maze = carved(seed);
+            // 连通而且边数比格子少一，就是一棵树：没有环，任意两格只有一条路
+            assert_eq!(reachable(&maze), CELLS, "seed {}", seed);
+            assert_eq!(open_walls(&maze), CELLS - 1, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn same_seed_same_maze() {
+        assert_eq!(carved(0x1234_5678), carved(0x1234_5678));
+        assert_ne!(carved(1), carved(2));
+    }
+
+    #[test]
+    fn carver_steps_are_bounded() {
+        // 起点一开始就在栈里，别的格子进栈一次，所有格子出栈一次
+        let mut maze = Maze::new();
+        let mut carver = Carver::new(7);
+        let mut steps = 0;
+        while carver.step(&mut maze) {
+            steps += 1;
+        }
+        assert_eq!(steps, 2 * CELLS - 1);
+        assert!(!carver.step(&mut maze));
+    }
+
+    #[test]
+    fn solver_finds_connected_path() {
+        for seed in 0..300 {
+            let maze = carved(seed);
+            let mut solver = Solver::new();
+            assert_eq!(solver.path().count(), 0);
+            solver.finish(&maze);
+            assert!(solver.is_found(), "seed {}", seed);
+            let path: std::vec::Vec<Cell> = solver.path().collect();
+            assert_eq!(path.first(), Some(&GOAL));
+            assert_eq!(path.last(), Some(&START));
+            for pair in path.windows(2) {
+                let dir = Dir::ALL
+                    .into_iter()
+                    .find(|&dir| neighbour(pair[0], dir) == Some(pair[1]))
+                    .expect("相邻两格要挨着");
+                assert!(maze.is_open(pair[0], dir), "seed {} 穿墙了", seed);
+            }
+            // 不走回头路
+            let mut seen = CellSet::new();
+            for &cell in &path {
+                assert!(!seen.contains(cell));
+                seen.insert(cell);
+            }
+        }
+    }
+
+    #[test]
+    fn solver_gives_up_when_walled_in() {
+        let mut maze = Maze::new();
+        maze.open(START, Dir::East);
+        let mut solver = Solver::new();
+        solver.finish(&maze);
+        assert!(!solver.is_found());
+        assert!(solver.is_seen(cell(1, 0)));
+        assert!(!solver.is_seen(cell(2, 0)));
+        assert_eq!(solver.path().count(), 0);
+    }
+}
@@ -0,0 +1,176 @@
+//! 话筒采样：MAX9814 驻极体话筒模块接 ADC0，ADC 自己连续转换、DMA 搬走，CPU 只管算(feature `mic-vu`)
+//!
+//! 平时 ADC 是 `sampler::Sampler` 一次读一个数，打开 `mic-vu` 以后 main.rs 换成这里的 `MicSampler`，ADC 一直在转：
+//! round-robin 轮流转换温度(ADC4)、话筒(ADC0)、VSYS(ADC3)，每路 `SAMPLE_RATE_HZ`，
+//! 结果进 FIFO，DMA 通道 2 按 ADC 的 DREQ 搬进一块静态缓冲区。一块缓冲区正好是一个窗口(`WINDOW_MS`)的三路交错样本。
+//!
+//! 主循环每一圈调 `service`：缓冲区满了就换另一块接着采，满的这块里话筒那一路算成 `MicWindow` 喂给 `VuMeter`，
+//! 最后 `FFT_LEN` 个样本也存进去给频谱页面，另外两路求平均。温度、VSYS、ADC0 三个传感器通道读的就是
+//! 最近一个窗口的平均值，和原来一样注册在传感器注册表里(ADC0 读出来是话筒输出的直流偏置，大约 1.25V)。
+//!
+//! 每个窗口都重新开一次 FIFO(停下、清空、从温度那一路开始)，这样三路的顺序永远对得上。代价是主循环取走之前的
+//! 那一小段没采，对电平表没有影响；主循环卡住的时候(比如换页动画)电平表就停在那一刻。
+//!
+//! 接线：模块 OUT 接 GP26(ADC0)，VDD 接 3V3，GND 接 AGND。增益用模块上的 GAIN 脚选，削顶(CLIP 常亮)就调小。
+
+use core::cell::RefCell;
+
+use rp2040_hal::adc::{Adc, AdcFifo, DmaReadTarget, TempSense};
+use rp2040_hal::dma::single_buffer::{Config, Transfer};
+use rp2040_hal::dma::{Channel, Pace, CH2};
+
+use crate::fft::FFT_LEN;
+use crate::sampler::{millivolts_from_raw, temp_centi_from_raw, vsys_mv_from_raw, SamplerPin};
+use crate::vu_meter::{MicWindow, VuMeter};
+
+/// 每一路的采样率
+pub const SAMPLE_RATE_HZ: u32 = 8_000;
+
+/// 一个窗口多长(毫秒)，电平表按窗口算
+pub const WINDOW_MS: u32 = 20;
+
+/// 一个窗口每一路几个样本
+pub const WINDOW_SAMPLES: usize = (SAMPLE_RATE_HZ * WINDOW_MS / 1000) as usize;
+
+/// 轮流转换几路
+const CHANNELS: usize = 3;
+
+/// 每一组三个样本里各是哪一路：从温度开始，然后按通道号往上转
+const TEMP_SLOT: usize = 0;
+const MIC_SLOT: usize = 1;
+const VSYS_SLOT: usize = 2;
+
+/// 一块缓冲区，一个窗口
+pub const BUFFER_LEN: usize = WINDOW_SAMPLES * CHANNELS;
+
+// 频谱要的样本一个窗口里就得凑够
+const _: () = assert!(WINDOW_SAMPLES >= FFT_LEN);
+
+pub type MicBuffer = [u16; BUFFER_LEN];
+
+type Capture = Transfer<Channel<CH2>, DmaReadTarget<u16>, &'static mut MicBuffer>;
+
+/// 话筒、温度、VSYS 一起连续采样
+pub struct MicSampler<P, V> {
+    /// 正在采的 FIFO 和 DMA，`service` 换缓冲区的那一下是 None
+    running: Option<(AdcFifo<'static, u16>, Capture)>,
+    /// 另一块缓冲区，等着下一个窗口
+    spare: Option<&'static mut MicBuffer>,
+    temp: TempSense,
+    pin: P,
+    vsys: V,
+    divider: u16,
+    meter: &'static RefCell<VuMeter>,
+    /// 最近一个窗口各路的平均(原始读数)，还没采满一个窗口是 None
+    temp_raw: Option<u16>,
+    mic_raw: Option<u16>,
+    vsys_raw: Option<u16>,
+}
+
+impl<P, V> MicSampler<P, V>
+where
+    P: SamplerPin,
+    V: SamplerPin,
+{
+    /// `adc_clock_hz` 是 clk_adc 的频率，按它算分频。温度传感器在这里打开，ADC 马上开始转
+    pub fn new(
+        adc: &'static mut Adc,
+        pin: P,
+        vsys: V,
+        dma: Channel<CH2>,
+        buffers: &'static mut [MicBuffer; 2],
+        adc_clock_hz: u32,
+        meter: &'static RefCell<VuMeter>,
+    ) -> Self {
+        // 温度传感器只能打开一次，这里是唯一打开它的地方
+        let temp = adc.take_temp_sensor().unwrap();
+        // 三路加起来的转换速率是 clk_adc / (1 + 分频)，一次转换至少要 96 个周期，分频再小也没用
+        let divider = (adc_clock_hz / (SAMPLE_RATE_HZ * CHANNELS as u32))
+            .saturating_sub(1)
+            .clamp(96, u16::MAX as u32) as u16;
+        let [first, second] = buffers;
+        let mut sampler = Self {
+            running: None,
+            spare: Some(second),
+            temp,
+            pin,
+            vsys,
+            divider,
+            meter,
+            temp_raw: None,
+            mic_raw: None,
+            vsys_raw: None,
+        };
+        sampler.start(adc, dma, first);
+        sampler
+    }
+
+    /// 从温度那一路开始采一个窗口
+    fn start(&mut self, adc: &'static mut Adc, dma: Channel<CH2>, buffer: &'static mut MicBuffer) {
+        let mut fifo = adc
+            .build_fifo()
+            .clock_divider(self.divider, 0)
+            .set_channel(&mut self.temp)
+            .round_robin((&self.pin, &self.vsys, &self.temp))
+            .enable_dma()
+            .start_paused();
+        let mut config = Config::new(dma, fifo.dma_read_target(), buffer);
+        config.pace(Pace::PreferSource);
+        let transfer = config.start();
+        fifo.resume();
+        self.running = Some((fifo, transfer));
+    }
+
+    /// 主循环每一圈调：一个窗口采满了就换缓冲区接着采，算好的窗口喂给电平表
+    pub fn service(&mut self, now_ms: u64) {
+        match &self.running {
+            Some((_, transfer)) if transfer.is_done() => {}
+            _ => return,
+        }
+        let Some((fifo, transfer)) = self.running.take() else {
+            return;
+        };
+        let (dma, _, full) = transfer.wait();
+        let adc = fifo.stop();
+        let next = self.spare.take().unwrap();
+        self.start(adc, dma, next);
+
+        let window = MicWindow::analyze(slot(full, MIC_SLOT));
+        self.mic_raw = Some(window.dc);
+        self.temp_raw = Some(average(full, TEMP_SLOT));
+        self.vsys_raw = Some(average(full, VSYS_SLOT));
+        let dc = window.dc as i16;
+        let samples = slot(full, MIC_SLOT).skip(WINDOW_SAMPLES - FFT_LEN);
+        let mut meter = self.meter.borrow_mut();
+        meter.set_samples(samples.map(|raw| raw as i16 - dc));
+        meter.push(&window, now_ms);
+        self.spare = Some(full);
+    }
+}
+
+impl<P, V> MicSampler<P, V> {
+    /// 片内温度，单位 0.01°C
+    pub fn temp_centi(&mut self) -> Option<i32> {
+        Some(temp_centi_from_raw(self.temp_raw?) as i32)
+    }
+
+    /// 话筒输出的直流偏置(mV)
+    pub fn adc0_mv(&mut self) -> Option<i32> {
+        Some(millivolts_from_raw(self.mic_raw?) as i32)
+    }
+
+    /// VSYS 电压(mV)
+    pub fn vsys_mv(&mut self) -> Option<i32> {
+        Some(vsys_mv_from_raw(self.vsys_raw?) as i32)
+    }
+}
+
+/// 交错样本里的某一路
+fn slot(buffer: &MicBuffer, index: usize) -> impl Iterator<Item = u16> + Clone + '_ {
+    buffer.iter().skip(index).step_by(CHANNELS).copied()
+}
+
+fn average(buffer: &MicBuffer, index: usize) -> u16 {
+    let sum: u32 = slot(buffer, index).map(u32::from).sum();
+    (sum / WINDOW_SAMPLES as u32) as u16
+}
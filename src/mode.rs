@@ -0,0 +1,351 @@
+//! 设备现在处在哪种模式，以及模式之间怎么切换
+//!
+//! 和 boot_mode.rs 不是一回事：那边是上电时跳线选的"跑哪套界面"，一次开机只读一次；
+//! 这里是运行中设备在干什么，主循环按它决定这一圈要不要画、要不要自动轮播、要不要放闹钟：
+//!
+//! | 模式 | 说明 | 主循环干什么 |
+//! |------|------|------|
+//! | `Boot` | 开机横幅、探测外设，还没进主循环 | - |
+//! | `Running` | 正常显示某个页面 | 画页面，跑自动轮播和演示轮换 |
+//! | `Menu` | 在设置菜单里 | 画页面，不自动换页 |
+//! | `Alarm` | 闹钟在响 | 画页面，一遍一遍地放铃声，状态灯闪闹钟的码 |
+//! | `LowPower` | 没人操作关了屏，或者电压太低停下来等 | 不画，测距测得慢一点 |
+//! | `SafeMode` | 反复重启，进了恢复模式(见 boot_loop.rs) | 只认 `SETTINGS RESET`，出不去 |
+//!
+//! 允许的切换(`transition`)，表里没有的事件原地不动：
+//!
+//! | 现在 | 事件 | 之后 |
+//! |------|------|------|
+//! | `Boot` | `BootDone` | `Running` |
+//! | `Boot` | `BootLoop` | `SafeMode` |
+//! | `Running` | `MenuOpened` | `Menu` |
+//! | `Menu` | `MenuClosed` | `Running` |
+//! | `Running`/`Menu`/`LowPower` | `AlarmStarted` | `Alarm` |
+//! | `Alarm` | `AlarmStopped` | `Running` |
+//! | `Running`/`Menu` | `ScreenOff` | `LowPower` |
+//! | `Running`/`Menu`/`Alarm` | `BatteryCritical` | `LowPower` |
+//! | `LowPower` | `Woke` | `Running` |
+//!
+//! 闹钟在响的时候不会因为没人操作关屏(响铃把屏幕叫醒，响着的时候主循环一直当有人在操作)，但电压太低的话还是先保电池。
+//! 事件不用调用方自己判断什么时候发：主循环每一圈把看到的状态填进 `Observed`，`Observed::event`
+//! 挑出和现在的模式对不上的那一个。从 `LowPower`/`Alarm` 出来一律先回 `Running`，还在菜单里的话下一个事件再进 `Menu`。
+//! 产线测试模式(`BootMode::Fixture`)不进主循环，不在这里面。
+
+/// 设备的模式，见模块文档
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Mode {
+    Boot,
+    Running,
+    Menu,
+    Alarm,
+    LowPower,
+    SafeMode,
+}
+
+/// 让模式切换的事件
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum ModeEvent {
+    /// 开机流程走完，要进主循环了
+    BootDone,
+    /// 连续启动次数超了
+    BootLoop,
+    /// 进了设置菜单
+    MenuOpened,
+    /// 从设置菜单出来了
+    MenuClosed,
+    /// 闹钟响了
+    AlarmStarted,
+    /// 闹钟按掉了或者响够时间了
+    AlarmStopped,
+    /// 没人操作，关屏了
+    ScreenOff,
+    /// 电压降到严重档
+    BatteryCritical,
+    /// 亮屏了，或者电压回来了
+    Woke,
+}
+
+impl Mode {
+    /// 日志里用的名字
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Boot => "boot",
+            Mode::Running => "running",
+            Mode::Menu => "menu",
+            Mode::Alarm => "alarm",
+            Mode::LowPower => "low power",
+            Mode::SafeMode => "safe mode",
+        }
+    }
+
+    /// 这个模式下屏幕是亮的，主循环要画
+    pub fn renders(self) -> bool {
+        matches!(self, Mode::Running | Mode::Menu | Mode::Alarm)
+    }
+
+    /// 这个模式下自动轮播和演示轮换可以换页。菜单里和响铃的时候都不换
+    pub fn autoplays(self) -> bool {
+        self == Mode::Running
+    }
+}
+
+/// 从 `current` 收到 `event` 以后是什么模式，不允许的切换原地不动
+pub fn transition(current: Mode, event: ModeEvent) -> Mode {
+    use Mode::*;
+    use ModeEvent::*;
+    match (current, event) {
+        (Boot, BootDone) => Running,
+        (Boot, BootLoop) => SafeMode,
+        (Running, MenuOpened) => Menu,
+        (Menu, MenuClosed) => Running,
+        (Running | Menu | LowPower, AlarmStarted) => Alarm,
+        (Alarm, AlarmStopped) => Running,
+        (Running | Menu, ScreenOff) => LowPower,
+        (Running | Menu | Alarm, BatteryCritical) => LowPower,
+        (LowPower, Woke) => Running,
+        (mode, _) => mode,
+    }
+}
+
+/// 主循环一圈里看到的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Observed {
+    /// 闹钟在响
+    pub ringing: bool,
+    /// 屏幕因为没人操作关了
+    pub screen_off: bool,
+    /// 当前页面是设置菜单
+    pub in_menu: bool,
+}
+
+impl Observed {
+    /// 现在的模式和看到的状态对不上的话该发哪个事件，对得上是 None。
+    /// 同时有好几个的时候按闹钟、关屏、菜单的顺序只挑一个，调用方一直调到 None 为止
+    pub fn event(&self, current: Mode) -> Option<ModeEvent> {
+        match current {
+            Mode::Boot | Mode::SafeMode => None,
+            Mode::Alarm => (!self.ringing).then_some(ModeEvent::AlarmStopped),
+            _ if self.ringing => Some(ModeEvent::AlarmStarted),
+            Mode::LowPower => (!self.screen_off).then_some(ModeEvent::Woke),
+            _ if self.screen_off => Some(ModeEvent::ScreenOff),
+            Mode::Running => self.in_menu.then_some(ModeEvent::MenuOpened),
+            Mode::Menu => (!self.in_menu).then_some(ModeEvent::MenuClosed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODES: [Mode; 6] = [
+        Mode::Boot,
+        Mode::Running,
+        Mode::Menu,
+        Mode::Alarm,
+        Mode::LowPower,
+        Mode::SafeMode,
+    ];
+
+    const EVENTS: [ModeEvent; 9] = [
+        ModeEvent::BootDone,
+        ModeEvent::BootLoop,
+        ModeEvent::MenuOpened,
+        ModeEvent::MenuClosed,
+        ModeEvent::AlarmStarted,
+        ModeEvent::AlarmStopped,
+        ModeEvent::ScreenOff,
+        ModeEvent::BatteryCritical,
+        ModeEvent::Woke,
+    ];
+
+    /// 模块文档里的切换表
+    const ALLOWED: [(Mode, ModeEvent, Mode); 14] = [
+        (Mode::Boot, ModeEvent::BootDone, Mode::Running),
+        (Mode::Boot, ModeEvent::BootLoop, Mode::SafeMode),
+        (Mode::Running, ModeEvent::MenuOpened, Mode::Menu),
+        (Mode::Menu, ModeEvent::MenuClosed, Mode::Running),
+        (Mode::Running, ModeEvent::AlarmStarted, Mode::Alarm),
+        (Mode::Menu, ModeEvent::AlarmStarted, Mode::Alarm),
+        (Mode::LowPower, ModeEvent::AlarmStarted, Mode::Alarm),
+        (Mode::Alarm, ModeEvent::AlarmStopped, Mode::Running),
+        (Mode::Running, ModeEvent::ScreenOff, Mode::LowPower),
+        (Mode::Menu, ModeEvent::ScreenOff, Mode::LowPower),
+        (Mode::Running, ModeEvent::BatteryCritical, Mode::LowPower),
+        (Mode::Menu, ModeEvent::BatteryCritical, Mode::LowPower),
+        (Mode::Alarm, ModeEvent::BatteryCritical, Mode::LowPower),
+        (Mode::LowPower, ModeEvent::Woke, Mode::Running),
+    ];
+
+    #[test]
+    fn transition_matches_table() {
+        for mode in MODES {
+            for event in EVENTS {
+                let expected = ALLOWED
+                    .iter()
+                    .find(|(from, on, _)| *from == mode && *on == event)
+                    .map_or(mode, |(_, _, to)| *to);
+                assert_eq!(
+                    transition(mode, event),
+                    expected,
+                    "{:?} + {:?}",
+                    mode,
+                    event
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn safe_mode_never_leaves() {
+        for event in EVENTS {
+            assert_eq!(transition(Mode::SafeMode, event), Mode::SafeMode);
+        }
+    }
+
+    #[test]
+    fn alarm_ignores_screen_off() {
+        let observed = Observed {
+            ringing: true,
+            screen_off: true,
+            in_menu: true,
+        };
+        assert_eq!(observed.event(Mode::Alarm), None);
+        assert_eq!(transition(Mode::Alarm, ModeEvent::ScreenOff), Mode::Alarm);
+    }
+
+    #[test]
+    fn event_when_consistent_is_none() {
+        let cases = [
+            (Mode::Running, Observed::default()),
+            (
+                Mode::Menu,
+                Observed {
+                    in_menu: true,
+                    ..Observed::default()
+                },
+            ),
+            (
+                Mode::Alarm,
+                Observed {
+                    ringing: true,
+                    ..Observed::default()
+                },
+            ),
+            (
+                Mode::LowPower,
+                Observed {
+                    screen_off: true,
+                    ..Observed::default()
+                },
+            ),
+            (
+                Mode::LowPower,
+                Observed {
+                    screen_off: true,
+                    in_menu: true,
+                    ringing: false,
+                },
+            ),
+        ];
+        for (mode, observed) in cases {
+            assert_eq!(observed.event(mode), None, "{:?} {:?}", mode, observed);
+        }
+    }
+
+    #[test]
+    fn boot_and_safe_mode_ignore_observed() {
+        let observed = Observed {
+            ringing: true,
+            screen_off: true,
+            in_menu: true,
+        };
+        assert_eq!(observed.event(Mode::Boot), None);
+        assert_eq!(observed.event(Mode::SafeMode), None);
+    }
+
+    #[test]
+    fn event_priority() {
+        let all = Observed {
+            ringing: true,
+            screen_off: true,
+            in_menu: true,
+        };
+        // 闹钟最先
+        assert_eq!(all.event(Mode::Running), Some(ModeEvent::AlarmStarted));
+        assert_eq!(all.event(Mode::Menu), Some(ModeEvent::AlarmStarted));
+        assert_eq!(all.event(Mode::LowPower), Some(ModeEvent::AlarmStarted));
+        // 然后是关屏，菜单最后
+        let off_in_menu = Observed {
+            screen_off: true,
+            in_menu: true,
+            ..Observed::default()
+        };
+        assert_eq!(off_in_menu.event(Mode::Running), Some(ModeEvent::ScreenOff));
+        let menu = Observed {
+            in_menu: true,
+            ..Observed::default()
+        };
+        assert_eq!(menu.event(Mode::Running), Some(ModeEvent::MenuOpened));
+        assert_eq!(
+            Observed::default().event(Mode::Menu),
+            Some(ModeEvent::MenuClosed)
+        );
+        assert_eq!(
+            Observed::default().event(Mode::Alarm),
+            Some(ModeEvent::AlarmStopped)
+        );
+        assert_eq!(
+            Observed::default().event(Mode::LowPower),
+            Some(ModeEvent::Woke)
+        );
+    }
+
+    /// 和主循环一样一直调到 None，看最后停在哪
+    fn settle(mut mode: Mode, observed: Observed) -> Mode {
+        for _ in 0..8 {
+            let Some(event) = observed.event(mode) else {
+                return mode;
+            };
+            let next = transition(mode, event);
+            if next == mode {
+                return mode;
+            }
+            mode = next;
+        }
+        panic!("{:?} 没有停下来", observed);
+    }
+
+    #[test]
+    fn settles_in_few_steps() {
+        let menu = Observed {
+            in_menu: true,
+            ..Observed::default()
+        };
+        // 从关屏或者响铃回来还在菜单里：先回 Running 再进 Menu
+        assert_eq!(settle(Mode::LowPower, menu), Mode::Menu);
+        assert_eq!(settle(Mode::Alarm, menu), Mode::Menu);
+        let off = Observed {
+            screen_off: true,
+            ..Observed::default()
+        };
+        assert_eq!(settle(Mode::Alarm, off), Mode::LowPower);
+        assert_eq!(settle(Mode::Menu, off), Mode::LowPower);
+        let ringing = Observed {
+            ringing: true,
+            screen_off: true,
+            ..Observed::default()
+        };
+        assert_eq!(settle(Mode::LowPower, ringing), Mode::Alarm);
+        for mode in MODES {
+            for bits in 0..8u8 {
+                let observed = Observed {
+                    ringing: bits & 1 != 0,
+                    screen_off: bits & 2 != 0,
+                    in_menu: bits & 4 != 0,
+                };
+                settle(mode, observed);
+            }
+        }
+    }
+}
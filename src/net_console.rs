@@ -0,0 +1,273 @@
+//! 网络显示：Pico W 连上 Wi-Fi 以后在 TCP 端口 `PORT` 上收一行一行的命令，和 USB 串口走同一个命令行
+//!
+//! 局域网里的电脑 `nc <板子的 IP> 2323` 连上来，打的东西和串口上一样：`TEXT`、`CLEAR`、`BRIGHT`、`IMG` 这些
+//! 画屏幕的命令原样能用，`SETTINGS ...` 也能用，回复发回这个连接(见 main.rs)。同一时间只接一个连接，
+//! 后来的要等前一个断开。
+//!
+//! 这里只管网络那边和主循环之间的两条字节队列和连接状态，都在 `static PIPE` 里：
+//!
+//! - 收：网络任务把收到的字节塞进 `rx`，主循环每一圈 `NetLink::read` 取出来喂给自己的 `Console`
+//! - 发：主循环 `write!` 进 `tx`，网络任务有空的时候发出去。满了后面的丢掉，大段输出按 `tx_free` 分批写，和 USB 一样
+//! - 状态：没配 Wi-Fi / 在连 / 拿到 IP / 有人连着，主循环按它画状态栏上的图标(见 `widgets::draw_network_indicator`)
+//!
+//! 连 Wi-Fi、跑协议栈的是 wireless.rs 里的几个任务，主循环每一圈 `NetLink::poll` 一次，每个任务走到下一次等待就返回，
+//! 不会因为 Wi-Fi 断了卡住界面。连着的人断开以后两条队列都清空，上一个人没收完的回复不会发给下一个。
+
+use core::fmt;
+
+use heapless::Deque;
+
+use crate::shared::Shared;
+use crate::wifi_config::WifiCredentials;
+
+/// 听哪个 TCP 端口
+pub const PORT: u16 = 2323;
+
+/// 收的队列多长，主循环一圈就取空了
+pub const RX_CAPACITY: usize = 256;
+
+/// 发的队列多长，要比最长的一行输出(`telemetry::LINE_MAX`)长
+pub const TX_CAPACITY: usize = 1024;
+
+/// 网络这边现在怎么样
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, defmt::Format)]
+pub enum NetState {
+    /// 没配 Wi-Fi(或者 `SETTINGS WIFI OFF`)
+    #[default]
+    Off,
+    /// 在连 Wi-Fi、等 DHCP，断了以后重连也是这个
+    Joining,
+    /// 拿到 IP 了，等人连上来
+    Online([u8; 4]),
+    /// 有人连着
+    Client([u8; 4]),
+}
+
+impl NetState {
+    /// 拿到的 IP，没拿到是 None
+    pub fn ip(&self) -> Option<[u8; 4]> {
+        match *self {
+            NetState::Online(ip) | NetState::Client(ip) => Some(ip),
+            NetState::Off | NetState::Joining => None,
+        }
+    }
+}
+
+/// 网络任务和主循环之间的东西
+#[derive(Debug)]
+pub struct Pipe {
+    rx: Deque<u8, RX_CAPACITY>,
+    tx: Deque<u8, TX_CAPACITY>,
+    state: NetState,
+    /// `SETTINGS WIFI` 要换的网络还没交给网络任务，里面的 None 是断开
+    join: Option<Option<WifiCredentials>>,
+}
+
+impl Pipe {
+    pub const fn new() -> Self {
+        Self {
+            rx: Deque::new(),
+            tx: Deque::new(),
+            state: NetState::Off,
+            join: None,
+        }
+    }
+
+    pub fn state(&self) -> NetState {
+        self.state
+    }
+
+    /// 网络任务更新状态。连着的人断开了两条队列都清空
+    pub fn set_state(&mut self, state: NetState) {
+        if matches!(self.state, NetState::Client(_)) && !matches!(state, NetState::Client(_)) {
+            self.rx.clear();
+            self.tx.clear();
+        }
+        self.state = state;
+    }
+
+    /// 收的队列还能放多少，网络任务最多从连接里读这么多
+    pub fn rx_free(&self) -> usize {
+        self.rx.capacity() - self.rx.len()
+    }
+
+    /// 网络任务把收到的塞进来，放不下的丢掉
+    pub fn push_received(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            if self.rx.push_back(byte).is_err() {
+                break;
+            }
+        }
+    }
+
+    /// 主循环取收到的，返回取了几个
+    pub fn read(&mut self, buf: &mut [u8]) -> usize {
+        let mut count = 0;
+        while let Some(slot) = buf.get_mut(count) {
+            let Some(byte) = self.rx.pop_front() else {
+                break;
+            };
+            *slot = byte;
+            count += 1;
+        }
+        count
+    }
+
+    /// 主循环要发的，放不下的丢掉。没人连着的时候直接丢
+    pub fn write(&mut self, bytes: &[u8]) {
+        if !matches!(self.state, NetState::Client(_)) {
+            return;
+        }
+        for &byte in bytes {
+            if self.tx.push_back(byte).is_err() {
+                break;
+            }
+        }
+    }
+
+    /// 发的队列还能放多少。没人连着的时候写什么都丢，当成全空，分批导出的一下子就"发"完了
+    pub fn tx_free(&self) -> usize {
+        if matches!(self.state, NetState::Client(_)) {
+            self.tx.capacity() - self.tx.len()
+        } else {
+            TX_CAPACITY
+        }
+    }
+
+    /// 网络任务取要发的，最多 `buf.len()` 个，返回取了几个
+    pub fn take_tx(&mut self, buf: &mut [u8]) -> usize {
+        let mut count = 0;
+        while let Some(slot) = buf.get_mut(count) {
+            let Some(byte) = self.tx.pop_front() else {
+                break;
+            };
+            *slot = byte;
+            count += 1;
+        }
+        count
+    }
+
+    /// 换网络，None 是断开不连。网络任务还没取走的话只留最新的
+    pub fn request_join(&mut self, credentials: Option<WifiCredentials>) {
+        self.join = Some(credentials);
+    }
+
+    /// 网络任务取走要换的网络
+    pub fn take_join(&mut self) -> Option<Option<WifiCredentials>> {
+        self.join.take()
+    }
+}
+
+impl Default for Pipe {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 网络任务和主循环共用的那一份
+pub(crate) static PIPE: Shared<Pipe> = Shared::new(Pipe::new());
+
+/// 主循环手里的网络，只有 Pico W 上无线芯片起来了才有(见 `wireless::Cyw43Led::start_network`)
+pub struct NetLink {
+    /// 驱动一下网络任务
+    poll: fn(),
+}
+
+impl NetLink {
+    #[cfg(feature = "pico-w")]
+    pub(crate) const fn new(poll: fn()) -> Self {
+        Self { poll }
+    }
+
+    /// 让网络任务各走一步，主循环每一圈调一次
+    pub fn poll(&mut self) {
+        (self.poll)();
+    }
+
+    pub fn state(&self) -> NetState {
+        PIPE.lock(|pipe| pipe.state())
+    }
+
+    /// 读收到的数据，返回读到的字节数
+    pub fn read(&mut self, buf: &mut [u8]) -> usize {
+        PIPE.lock(|pipe| pipe.read(buf))
+    }
+
+    /// 发送队列还能放多少字节，和 `UsbLink::serial_tx_free` 一样用
+    pub fn tx_free(&self) -> usize {
+        PIPE.lock(|pipe| pipe.tx_free())
+    }
+
+    /// 换一个 Wi-Fi 连，None 是断开。不等连上就返回，状态看 `state`
+    pub fn join(&mut self, credentials: Option<WifiCredentials>) {
+        PIPE.lock(|pipe| pipe.request_join(credentials));
+    }
+}
+
+/// 方便用 `write!` 往连着的人那里打
+impl fmt::Write for NetLink {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        PIPE.lock(|pipe| pipe.write(s.as_bytes()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IP: [u8; 4] = [192, 168, 1, 20];
+
+    #[test]
+    fn queues_only_while_a_client_is_connected() {
+        let mut pipe = Pipe::new();
+        pipe.set_state(NetState::Online(IP));
+        pipe.write(b"lost");
+        assert_eq!(pipe.tx_free(), TX_CAPACITY);
+        let mut out = [0u8; 16];
+        assert_eq!(pipe.take_tx(&mut out), 0);
+
+        pipe.set_state(NetState::Client(IP));
+        pipe.write(b"OK\r\n");
+        assert_eq!(pipe.tx_free(), TX_CAPACITY - 4);
+        assert_eq!(pipe.take_tx(&mut out[..2]), 2);
+        assert_eq!(&out[..2], b"OK");
+        assert_eq!(pipe.take_tx(&mut out), 2);
+        assert_eq!(&out[..2], b"\r\n");
+    }
+
+    #[test]
+    fn disconnect_drops_both_queues() {
+        let mut pipe = Pipe::new();
+        pipe.set_state(NetState::Client(IP));
+        pipe.push_received(b"TEXT hi");
+        pipe.write(b"half a reply");
+        pipe.set_state(NetState::Online(IP));
+        let mut out = [0u8; 16];
+        assert_eq!(pipe.read(&mut out), 0);
+        pipe.set_state(NetState::Client(IP));
+        assert_eq!(pipe.take_tx(&mut out), 0);
+    }
+
+    #[test]
+    fn received_bytes_are_bounded() {
+        let mut pipe = Pipe::new();
+        pipe.push_received(&[b'x'; RX_CAPACITY + 10]);
+        assert_eq!(pipe.rx_free(), 0);
+        let mut out = [0u8; RX_CAPACITY + 10];
+        assert_eq!(pipe.read(&mut out), RX_CAPACITY);
+        assert_eq!(pipe.rx_free(), RX_CAPACITY);
+    }
+
+    #[test]
+    fn keeps_the_latest_join_request() {
+        let mut pipe = Pipe::new();
+        assert_eq!(pipe.take_join(), None);
+        pipe.request_join(WifiCredentials::new("first", ""));
+        pipe.request_join(None);
+        assert_eq!(pipe.take_join(), Some(None));
+        assert_eq!(pipe.take_join(), None);
+        assert_eq!(NetState::Client(IP).ip(), Some(IP));
+        assert_eq!(NetState::Joining.ip(), None);
+    }
+}
@@ -0,0 +1,102 @@
+//! 输出控制页面：把板子当成一个小控制面板，用按键开关继电器、LED 这些外接设备
+//!
+//! 哪些引脚、叫什么名字在 board.rs 的 `output_pins!` 里配。菜单用的是 `widgets::draw_menu`，
+//! 按键还是 `input` 里消抖过的那四个：Up/Down 选，Select 开关，Back 回去。
+//! 从仪表盘按 Up 进来。
+//!
+//! "开"就是输出高电平。很多继电器模块是低电平触发的，接这种模块的时候屏幕上的 on/off 是反的。
+
+use core::convert::Infallible;
+
+use embedded_graphics::geometry::Point;
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::text::Text;
+use embedded_graphics::Drawable;
+use embedded_hal::digital::OutputPin as _;
+use rp2040_hal::gpio::{DynPinId, FunctionSioOutput, Pin, PullDown};
+
+use crate::app::{Canvas, Event, Page, Transition};
+use crate::input::{Button, ButtonEvent};
+use crate::widgets::draw_menu;
+
+/// 输出引脚，统一成动态引脚类型，这样几个引脚可以放进一个数组
+pub type OutputPin = Pin<DynPinId, FunctionSioOutput, PullDown>;
+
+/// 标题行基线
+const TITLE_Y: i32 = 8;
+
+/// 菜单从哪一行开始
+const MENU_TOP: i32 = 12;
+
+/// 一组可以开关的输出引脚，同时也是控制它们的菜单页面
+pub struct OutputControl<const N: usize> {
+    outputs: [(&'static str, OutputPin, bool); N],
+    selected: usize,
+}
+
+impl<const N: usize> OutputControl<N> {
+    /// 引脚用 `output_pins!` 取，一开始全部是关的
+    pub fn new(pins: [(&'static str, OutputPin); N]) -> Self {
+        let mut control = Self {
+            outputs: pins.map(|(name, pin)| (name, pin, false)),
+            selected: 0,
+        };
+        for index in 0..N {
+            control.set(index, false);
+        }
+        control
+    }
+
+    pub fn is_on(&self, index: usize) -> bool {
+        self.outputs.get(index).is_some_and(|&(_, _, on)| on)
+    }
+
+    /// 设置第 `index` 个输出，超出范围的忽略
+    pub fn set(&mut self, index: usize, on: bool) {
+        let Some((_, pin, state)) = self.outputs.get_mut(index) else {
+            return;
+        };
+        // rp2040 的 GPIO 写电平不会失败
+        let _ = pin.set_state(on.into());
+        *state = on;
+    }
+
+    pub fn toggle(&mut self, index: usize) {
+        self.set(index, !self.is_on(index));
+    }
+}
+
+impl<const N: usize> Page for OutputControl<N> {
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        if N == 0 {
+            return match event {
+                Event::Button(ButtonEvent::Pressed(Button::Back)) => Transition::Pop,
+                _ => Transition::None,
+            };
+        }
+        match event {
+            Event::Button(ButtonEvent::Pressed(Button::Back)) => return Transition::Pop,
+            Event::Button(ButtonEvent::Pressed(Button::Up)) => {
+                self.selected = (self.selected + N - 1) % N;
+            }
+            Event::Button(ButtonEvent::Pressed(Button::Down)) => {
+                self.selected = (self.selected + 1) % N;
+            }
+            Event::Button(ButtonEvent::Pressed(Button::Select)) => self.toggle(self.selected),
+            _ => {}
+        }
+        Transition::None
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<(), Infallible> {
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        Text::new("outputs", Point::new(0, TITLE_Y), style).draw(canvas)?;
+        let items = self
+            .outputs
+            .iter()
+            .map(|&(name, _, on)| (name, if on { "on" } else { "off" }));
+        draw_menu(canvas, MENU_TOP, items, self.selected)
+    }
+}
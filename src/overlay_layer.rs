@@ -0,0 +1,193 @@
+//! 叠加层：提示条、告警图标、轮播进度这些盖在页面上面的东西单独画一层(feature `overlay-layer`)
+//!
+//! 只有一块显存的时候，这些东西和页面内容画在一起，提示条消失就得让整个页面重画一遍才能把下面露出来。
+//! 打开 `overlay-layer` 以后 `FrameBuffer` 里多一层，flush 的时候逐字节合成，页面那一层不用动：
+//!
+//! - 每个像素两位：`ink`(亮不亮)和 `mask`(盖不盖住页面)。合成是 `(页面 & !mask) | ink`
+//! - mask 是 0：透明，显示页面
+//! - mask 是 1：叠加层说了算，ink 是 1 显示亮，ink 是 0 显示黑(把页面抠掉)。ink 是 1 的像素 mask 一定是 1
+//!
+//! 通过 `DrawTarget` 画 `On` 的像素两位都置 1；画 `Off` 的像素看 `BlendMode`：
+//! `Masked`(默认)是不透明的黑，提示条那种黑底白字就是这样盖住页面的；`Or` 是透明，只有亮的像素叠上去。
+//! `clear` 把整层变回透明，改了哪些字节就只把哪些字节并进脏区域，页面不用重画，flush 的时候只重发那一块。
+//!
+//! 坐标和页面一样是逻辑坐标，跟着旋转走，但是不跟防烧屏平移走。
+//! 为了省内存可以只覆盖几页(`OVERLAY_FIRST_PAGE`、`OVERLAY_PAGES`，物理页)，
+//! 比如底下 2 页就是 128x16，范围外画的像素直接丢掉。默认覆盖整屏，两位加起来多占 2K 内存。
+
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::{OriginDimensions, Size};
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::Pixel;
+use ssd1306::prelude::DisplayRotation;
+
+use crate::framebuffer::{
+    logical_size, merge_dirty, to_physical, DirtyRegion, HEIGHT, PAGES, WIDTH,
+};
+use crate::theme::PatternTarget;
+
+/// 叠加层从第几页开始(物理页)
+pub const OVERLAY_FIRST_PAGE: usize = 0;
+
+/// 叠加层一共几页
+pub const OVERLAY_PAGES: usize = PAGES;
+
+const LEN: usize = WIDTH * OVERLAY_PAGES;
+
+const _: () = assert!(OVERLAY_PAGES > 0 && OVERLAY_FIRST_PAGE + OVERLAY_PAGES <= PAGES);
+
+/// 画 `Off` 的像素算什么
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, defmt::Format)]
+pub enum BlendMode {
+    /// 不透明的黑，把页面抠掉
+    #[default]
+    Masked,
+    /// 透明，只把亮的像素叠(OR)到页面上
+    Or,
+}
+
+/// 叠加层，布局和 `FrameBuffer` 一样按页存
+pub struct OverlayLayer {
+    ink: [u8; LEN],
+    mask: [u8; LEN],
+    rotation: DisplayRotation,
+    mode: BlendMode,
+    dirty: Option<DirtyRegion>,
+}
+
+impl OverlayLayer {
+    /// 全透明的一层
+    pub const fn new(rotation: DisplayRotation) -> Self {
+        Self {
+            ink: [0; LEN],
+            mask: [0; LEN],
+            rotation,
+            mode: BlendMode::Masked,
+            dirty: None,
+        }
+    }
+
+    pub fn mode(&self) -> BlendMode {
+        self.mode
+    }
+
+    /// 之后画的 `Off` 像素怎么处理，已经画好的不变
+    pub fn set_mode(&mut self, mode: BlendMode) {
+        self.mode = mode;
+    }
+
+    /// 换方向，原来画的坐标都不对了，直接清空
+    pub(crate) fn set_rotation(&mut self, rotation: DisplayRotation) {
+        self.rotation = rotation;
+        self.clear();
+    }
+
+    /// 整层变回透明
+    pub fn clear(&mut self) {
+        for i in 0..LEN {
+            if self.mask[i] != 0 {
+                self.ink[i] = 0;
+                self.mask[i] = 0;
+                self.touch(i);
+            }
+        }
+    }
+
+    /// 整层都是透明的
+    pub fn is_empty(&self) -> bool {
+        self.mask.iter().all(|&byte| byte == 0)
+    }
+
+    /// 叠加层改过的地方，没有是 None
+    pub fn dirty(&self) -> Option<DirtyRegion> {
+        self.dirty
+    }
+
+    pub(crate) fn take_dirty(&mut self) -> Option<DirtyRegion> {
+        self.dirty.take()
+    }
+
+    /// 第 `page` 页第 `col` 列的页面字节盖上叠加层以后是什么
+    #[inline]
+    pub fn composite(&self, page: usize, col: usize, byte: u8) -> u8 {
+        match page.checked_sub(OVERLAY_FIRST_PAGE) {
+            Some(page) if page < OVERLAY_PAGES => {
+                let i = page * WIDTH + col;
+                (byte & !self.mask[i]) | self.ink[i]
+            }
+            _ => byte,
+        }
+    }
+
+    /// 设一个像素(逻辑坐标)，`None` 是变回透明。范围外的忽略
+    pub fn set_pixel(&mut self, x: u32, y: u32, value: Option<bool>) {
+        let (col, row) = to_physical(self.rotation, x, y);
+        if col >= WIDTH || row >= HEIGHT {
+            return;
+        }
+        let Some(page) = (row / 8).checked_sub(OVERLAY_FIRST_PAGE) else {
+            return;
+        };
+        if page >= OVERLAY_PAGES {
+            return;
+        }
+        let i = page * WIDTH + col;
+        let bit = 1 << (row % 8);
+        let old = (self.ink[i], self.mask[i]);
+        match value {
+            Some(true) => {
+                self.ink[i] |= bit;
+                self.mask[i] |= bit;
+            }
+            Some(false) => {
+                self.ink[i] &= !bit;
+                self.mask[i] |= bit;
+            }
+            None => {
+                self.ink[i] &= !bit;
+                self.mask[i] &= !bit;
+            }
+        }
+        if (self.ink[i], self.mask[i]) != old {
+            self.touch(i);
+        }
+    }
+
+    fn touch(&mut self, i: usize) {
+        let col = (i % WIDTH) as u8;
+        let page = (i / WIDTH + OVERLAY_FIRST_PAGE) as u8;
+        self.dirty = merge_dirty(self.dirty, Some(DirtyRegion::byte(col, page)));
+    }
+}
+
+impl OriginDimensions for OverlayLayer {
+    fn size(&self) -> Size {
+        logical_size(self.rotation)
+    }
+}
+
+/// 叠加层有画和透明两种状态，花纹一个点一个点画
+impl PatternTarget for OverlayLayer {}
+
+impl DrawTarget for OverlayLayer {
+    type Color = BinaryColor;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            if point.x < 0 || point.y < 0 {
+                continue;
+            }
+            let value = match (color, self.mode) {
+                (BinaryColor::On, _) => Some(true),
+                (BinaryColor::Off, BlendMode::Masked) => Some(false),
+                (BinaryColor::Off, BlendMode::Or) => None,
+            };
+            self.set_pixel(point.x as u32, point.y as u32, value);
+        }
+        Ok(())
+    }
+}
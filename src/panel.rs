@@ -0,0 +1,72 @@
+//! 屏幕型号：分辨率和跟分辨率有关的初始化参数
+//!
+//! 和板子型号(boards.rs)一样用 Cargo feature 选，不用改源码：
+//!
+//! - 不开 feature：128x64 的 SSD1306，最常见的 0.96 寸模块
+//! - `panel-128x32`：128x32 的 SSD1306(0.91 寸长条模块)
+//!
+//! 显存大小、脏区域范围、初始化序列里的复用率和 COM 引脚配置都从这里选出来的 `PANEL` 算，
+//! 换屏幕只换 feature。以前显存写死 128x64，接 128x32 的屏幕的时候下面四页照样往屏幕上发，
+//! 屏幕把多出来的数据绕回到上面几页，画面就乱了。
+//!
+//! 整屏字节在内存里同时会有好几份(显存、叠加层、换页动画的临时拷贝)，编译期检查加起来不超过 `RAM_BUDGET`，
+//! 以后加更大的屏幕的时候超了直接编译失败，而不是运行时栈溢出。
+//!
+//! 页面的布局大多是按 64 行设计的，128x32 的屏幕上画到下半截的东西会被裁掉，不会写到显存外面去。
+
+/// 一种屏幕的配置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PanelVariant {
+    pub name: &'static str,
+    pub width: usize,
+    pub height: usize,
+    /// 初始化序列里 0xDA(COM 引脚配置)的参数：64 行的屏是交替模式，32 行的是顺序模式
+    pub com_pins: u8,
+}
+
+impl PanelVariant {
+    /// 整屏多少字节，每字节竖着 8 个像素
+    pub const fn buffer_len(&self) -> usize {
+        self.width * self.height / 8
+    }
+}
+
+pub const SSD1306_128X64: PanelVariant = PanelVariant {
+    name: "128x64",
+    width: 128,
+    height: 64,
+    com_pins: 0x12,
+};
+
+pub const SSD1306_128X32: PanelVariant = PanelVariant {
+    name: "128x32",
+    width: 128,
+    height: 32,
+    com_pins: 0x02,
+};
+
+/// 编译的时候选的屏幕
+#[cfg(feature = "panel-128x32")]
+pub const PANEL: PanelVariant = SSD1306_128X32;
+#[cfg(not(feature = "panel-128x32"))]
+pub const PANEL: PanelVariant = SSD1306_128X64;
+
+/// 所有整屏字节的拷贝加起来最多能占多少 RAM(RP2040 一共 264K，主循环的栈和别的静态变量要留够)
+pub const RAM_BUDGET: usize = 8 * 1024;
+
+/// 叠加层占几份整屏字节(`ink` 和 `mask`)，没开 `overlay-layer` 是 0
+const OVERLAY_COPIES: usize = if cfg!(feature = "overlay-layer") {
+    2
+} else {
+    0
+};
+
+/// 整屏字节同时有几份：显存一份，叠加层，换页动画在栈上临时拷的四份(起点、终点、显存原样、中间帧)
+pub const SCREEN_COPIES: usize = 1 + OVERLAY_COPIES + 4;
+
+// SSD1306 最多 128 列 64 行，行数按页(8 行)算
+const _: () = assert!(PANEL.width <= 128 && PANEL.height <= 64 && PANEL.height.is_multiple_of(8));
+const _: () = assert!(
+    PANEL.buffer_len() * SCREEN_COPIES <= RAM_BUDGET,
+    "framebuffer copies for this panel exceed RAM_BUDGET"
+);
@@ -0,0 +1,279 @@
+//! 屏幕保养：记 OLED 一共亮了多久、平均多亮，片内温度一直偏高的时候自动调暗一点
+//!
+//! OLED 用久了会变暗，越热、越亮老得越快。这里每秒记一次：屏幕亮着就把亮着的秒数加一，
+//! 再把这一秒实际用的对比度加进累计值，两个一除就是平均亮度。诊断页面上显示一共亮了多少小时。
+//!
+//! 累计值要跨重启，存在设置那个扇区里(见 `flash`)：第 0 页是设置，后面 15 页每 16 字节一条记录，
+//! 每次保存都往后写一条新的(flash 没擦过的地方可以直接写)，读的时候取最后一条 CRC 对得上的。
+//! 240 条写满了才擦一次扇区，擦完把设置和最新一条写回去；反过来保存设置擦扇区的时候也会把最新一条带过去。
+//! 最多一小时存一次，也就是十天才擦一次扇区。断电会丢掉最后不到一小时的累计，这个精度够了。
+//!
+//! 调暗(`SETTINGS DERATE ON`，默认关)：片内温度先做一个几分钟的平滑，只看持续的高温，
+//! 然后查 `DERATE_CURVE` 这张表，按两点之间线性插值得到一个百分比，乘到时间表/设置给的亮度上。
+//! 表是按温度从低到高排的 (温度°C, 百分比)，低于第一个点用第一个点的百分比，高于最后一个点用最后一个点的，
+//! 要改曲线直接改这张表。只用整数运算。
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::crc::crc32;
+use crate::flash;
+
+/// 最多多久存一次 flash(毫秒)
+pub const SAVE_INTERVAL_MS: u64 = 60 * 60 * 1000;
+
+/// 多久记一次(毫秒)
+const TICK_MS: u64 = 1000;
+
+/// 一条记录：亮着的秒数 4 字节 + 对比度乘秒数的累计 8 字节 + CRC-32 4 字节，都是小端
+const RECORD_LEN: usize = 16;
+
+/// 第一条记录的位置：设置扇区的第 1 页
+const RECORDS_OFFSET: u32 = flash::SETTINGS_OFFSET + flash::PAGE_LEN as u32;
+
+/// 扇区里一共放得下几条
+const RECORD_SLOTS: usize = (flash::SECTOR_LEN - flash::PAGE_LEN) / RECORD_LEN;
+
+/// 温度平滑：每秒往新读数挪 1/N，时间常数大约 N 秒
+const TEMP_SMOOTHING: i32 = 180;
+
+/// 调暗曲线的一个点
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct DeratePoint {
+    pub temp_c: i16,
+    pub percent: u8,
+}
+
+impl DeratePoint {
+    pub const fn new(temp_c: i16, percent: u8) -> Self {
+        Self { temp_c, percent }
+    }
+}
+
+/// 默认的调暗曲线：45°C 以下不动，到 65°C 降到一半
+pub const DERATE_CURVE: [DeratePoint; 3] = [
+    DeratePoint::new(45, 100),
+    DeratePoint::new(55, 75),
+    DeratePoint::new(65, 50),
+];
+
+/// 按曲线查 `temp_centi`(0.01°C)对应的百分比，空表返回 100
+pub fn derate_percent(curve: &[DeratePoint], temp_centi: i32) -> u8 {
+    let (Some(first), Some(last)) = (curve.first(), curve.last()) else {
+        return 100;
+    };
+    if temp_centi <= first.temp_c as i32 * 100 {
+        return first.percent;
+    }
+    for pair in curve.windows(2) {
+        let (low, high) = (pair[0], pair[1]);
+        let (t0, t1) = (low.temp_c as i32 * 100, high.temp_c as i32 * 100);
+        if temp_centi <= t1 && t1 > t0 {
+            let (p0, p1) = (low.percent as i32, high.percent as i32);
+            return (p0 + (p1 - p0) * (temp_centi - t0) / (t1 - t0)) as u8;
+        }
+    }
+    last.percent
+}
+
+/// 屏幕一共亮了多久
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, defmt::Format)]
+pub struct PanelWear {
+    /// 亮着的秒数
+    pub lit_s: u32,
+    /// 每一秒的对比度加起来
+    pub brightness_s: u64,
+}
+
+impl PanelWear {
+    /// 一共亮了几个小时
+    pub fn lit_hours(&self) -> u32 {
+        self.lit_s / 3600
+    }
+
+    /// 亮着的时候平均对比度，还没亮过返回 None
+    pub fn average_contrast(&self) -> Option<u8> {
+        (self.lit_s > 0).then(|| (self.brightness_s / self.lit_s as u64).min(255) as u8)
+    }
+
+    fn encode(&self) -> [u8; RECORD_LEN] {
+        let mut out = [0u8; RECORD_LEN];
+        out[..4].copy_from_slice(&self.lit_s.to_le_bytes());
+        out[4..12].copy_from_slice(&self.brightness_s.to_le_bytes());
+        let crc = crc32(&out[..12]);
+        out[12..].copy_from_slice(&crc.to_le_bytes());
+        out
+    }
+
+    fn decode(record: &[u8]) -> Option<Self> {
+        let crc = u32::from_le_bytes(record[12..16].try_into().ok()?);
+        if crc != crc32(&record[..12]) {
+            return None;
+        }
+        Some(Self {
+            lit_s: u32::from_le_bytes(record[..4].try_into().ok()?),
+            brightness_s: u64::from_le_bytes(record[4..12].try_into().ok()?),
+        })
+    }
+
+    /// 从 flash 读最新的一条，一条都没有(新板子、数据坏了)返回全 0
+    pub fn load() -> Self {
+        latest().map_or_else(Self::default, |(_, wear)| wear)
+    }
+
+    /// 往后写一条新记录，写满了先擦扇区(设置会写回去)。擦写期间关中断
+    pub fn store(&self) {
+        let next = latest().map_or(0, |(slot, _)| slot + 1);
+        let slot = match (next..RECORD_SLOTS).find(|&slot| is_empty(slot)) {
+            Some(slot) => slot,
+            None => {
+                let mut settings = [0u8; flash::PAGE_LEN];
+                settings.copy_from_slice(flash::read(flash::SETTINGS_OFFSET, flash::PAGE_LEN));
+                flash::erase_sector(flash::SETTINGS_OFFSET);
+                flash::program_page(flash::SETTINGS_OFFSET, &settings);
+                0
+            }
+        };
+        flash::program_bytes(slot_offset(slot), &self.encode());
+    }
+}
+
+fn slot_offset(slot: usize) -> u32 {
+    RECORDS_OFFSET + (slot * RECORD_LEN) as u32
+}
+
+fn is_empty(slot: usize) -> bool {
+    flash::read(slot_offset(slot), RECORD_LEN)
+        .iter()
+        .all(|&byte| byte == 0xFF)
+}
+
+/// 最后一条对得上的记录和它在第几格。写坏了的(写到一半断电)跳过去
+fn latest() -> Option<(usize, PanelWear)> {
+    (0..RECORD_SLOTS)
+        .take_while(|&slot| !is_empty(slot))
+        .filter_map(|slot| {
+            PanelWear::decode(flash::read(slot_offset(slot), RECORD_LEN)).map(|wear| (slot, wear))
+        })
+        .last()
+}
+
+/// 要擦设置扇区的时候用：先记下最新一条，`erase_and_write` 擦完写完以后写回第 0 格
+pub(crate) fn carry_over(erase_and_write: impl FnOnce()) {
+    let record = latest().map(|(slot, _)| {
+        let mut record = [0u8; RECORD_LEN];
+        record.copy_from_slice(flash::read(slot_offset(slot), RECORD_LEN));
+        record
+    });
+    erase_and_write();
+    if let Some(record) = record {
+        flash::program_bytes(slot_offset(0), &record);
+    }
+}
+
+/// 最新的亮屏秒数，给诊断页面看
+static LIT_SECONDS: AtomicU32 = AtomicU32::new(0);
+
+/// 屏幕一共亮了多少秒(包括还没存进 flash 的)
+pub fn lit_seconds() -> u32 {
+    LIT_SECONDS.load(Ordering::Relaxed)
+}
+
+/// 每秒记账、按温度调暗，主循环里用
+pub struct PanelCare {
+    wear: PanelWear,
+    /// flash 里存的是哪一份
+    saved: PanelWear,
+    last_tick_ms: u64,
+    last_save_ms: u64,
+    /// 时间表或者设置要的亮度
+    level: u8,
+    /// 屏幕上现在的亮度，None 表示要重新发
+    applied: Option<u8>,
+    /// 平滑过的温度(0.01°C)乘 `TEMP_SMOOTHING`，多留几位，不然差得少的时候一直除成 0 跟不上
+    temp_sum: Option<i32>,
+}
+
+impl PanelCare {
+    /// `wear` 是开机时从 flash 读出来的，`level` 是开机的亮度
+    pub fn new(wear: PanelWear, level: u8, now_ms: u64) -> Self {
+        LIT_SECONDS.store(wear.lit_s, Ordering::Relaxed);
+        Self {
+            wear,
+            saved: wear,
+            last_tick_ms: now_ms,
+            last_save_ms: now_ms,
+            level,
+            applied: None,
+            temp_sum: None,
+        }
+    }
+
+    /// 累计到现在的
+    pub fn wear(&self) -> PanelWear {
+        self.wear
+    }
+
+    /// 亮度要改成 `level`(调暗之前的)。屏幕亮度可能被别的地方动过，下一次 `update` 一定重发
+    pub fn set_level(&mut self, level: u8) {
+        self.level = level;
+        self.applied = None;
+    }
+
+    /// 现在按温度要打几折(百分比)，还没读到温度返回 100
+    pub fn derate_percent(&self) -> u8 {
+        self.temp_sum.map_or(100, |sum| {
+            derate_percent(&DERATE_CURVE, sum / TEMP_SMOOTHING)
+        })
+    }
+
+    /// `lit` 是屏幕现在亮不亮，`temp_centi` 是片内温度。返回要发给屏幕的亮度，不用改返回 None
+    pub fn update(
+        &mut self,
+        now_ms: u64,
+        lit: bool,
+        temp_centi: Option<i32>,
+        derate: bool,
+    ) -> Option<u8> {
+        let seconds = now_ms.saturating_sub(self.last_tick_ms) / TICK_MS;
+        if seconds > 0 {
+            self.last_tick_ms += seconds * TICK_MS;
+            if let Some(temp) = temp_centi {
+                self.temp_sum = Some(match self.temp_sum {
+                    Some(sum) => sum + temp - sum / TEMP_SMOOTHING,
+                    None => temp * TEMP_SMOOTHING,
+                });
+            }
+            if lit {
+                let contrast = self.applied.unwrap_or(self.level) as u64;
+                self.wear.lit_s = self.wear.lit_s.saturating_add(seconds as u32);
+                self.wear.brightness_s = self.wear.brightness_s.saturating_add(contrast * seconds);
+                LIT_SECONDS.store(self.wear.lit_s, Ordering::Relaxed);
+            }
+        }
+
+        let percent = if derate { self.derate_percent() } else { 100 };
+        let level = (self.level as u32 * percent as u32 / 100) as u8;
+        if self.applied == Some(level) {
+            return None;
+        }
+        self.applied = Some(level);
+        Some(level)
+    }
+
+    /// 到了该存 flash 的时候(离上一次存满一小时，而且有新的累计)
+    pub fn save_due(&self, now_ms: u64) -> bool {
+        self.wear != self.saved && now_ms - self.last_save_ms >= SAVE_INTERVAL_MS
+    }
+
+    /// 有没存的累计(不管离上次存多久)，关机之前看这个
+    pub fn has_unsaved(&self) -> bool {
+        self.wear != self.saved
+    }
+
+    /// 存进 flash
+    pub fn save(&mut self, now_ms: u64) {
+        self.wear.store();
+        self.saved = self.wear;
+        self.last_save_ms = now_ms;
+    }
+}
@@ -0,0 +1,74 @@
+//! 死机画面：panic 的时候把原因显示在屏幕上，不接调试器也能知道发生了什么
+//!
+//! 真正的 `#[panic_handler]` 在 main.rs 里，因为要重新拿外设、重新初始化 I2C；
+//! 这里只管"画什么"。
+
+use core::fmt::Write;
+use core::panic::PanicInfo;
+
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::primitives::{Primitive, PrimitiveStyle, Rectangle};
+use embedded_graphics::Drawable;
+use heapless::String;
+
+use crate::display::BufferedDisplay;
+use crate::text::{draw_centered, draw_wrapped};
+
+/// 普通 panic 的标题
+pub const PANIC_TITLE: &str = "PANIC";
+
+/// 堆内存用完的标题
+pub const OOM_TITLE: &str = "OOM";
+
+/// 标题栏高度
+const TITLE_HEIGHT: u32 = 12;
+
+/// 标准库的默认 OOM 处理会 panic，消息是 "memory allocation of N bytes failed"
+const OOM_MESSAGE_PREFIX: &str = "memory allocation of";
+
+/// 根据 panic 消息选标题：堆分配失败显示 OOM，其它的显示 PANIC
+pub fn panic_title(message: &str) -> &'static str {
+    if message.starts_with(OOM_MESSAGE_PREFIX) {
+        OOM_TITLE
+    } else {
+        PANIC_TITLE
+    }
+}
+
+/// 画死机画面并立刻 flush：最上面反色的标题栏，下面是 panic 消息和出错位置(自动换行)
+///
+/// 消息太长的部分直接丢掉，死机的时候没必要讲究
+pub fn draw_panic_screen<D: BufferedDisplay>(
+    display: &mut D,
+    info: &PanicInfo,
+) -> Result<(), D::Error> {
+    let mut text: String<160> = String::new();
+    let _ = write!(text, "{}", info.message());
+    let title = panic_title(&text);
+    if let Some(location) = info.location() {
+        let _ = write!(text, "\n{}:{}", location.file(), location.line());
+    }
+
+    let width = display.bounding_box().size.width;
+    display.clear_buffer();
+    Rectangle::new(Point::zero(), Size::new(width, TITLE_HEIGHT))
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(display)?;
+    draw_centered(
+        display,
+        title,
+        9,
+        MonoTextStyle::new(&FONT_6X10, BinaryColor::Off),
+    )?;
+    draw_wrapped(
+        display,
+        &text,
+        Point::new(0, TITLE_HEIGHT as i32 + 2),
+        width,
+        MonoTextStyle::new(&FONT_6X10, BinaryColor::On),
+    )?;
+    display.flush()
+}
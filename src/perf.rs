@@ -0,0 +1,230 @@
+//! 渲染耗时统计：每个页面的 `render`、每一帧、每次 flush 花了多久
+//!
+//! 页面越写越复杂，哪个慢了要能一眼看出来。调度器每画一个页面记一次，主循环每发完一帧记一次 flush，
+//! 按来源分别记最小/平均/最大(微秒)。看的地方有两个：设置菜单里的 "perf" 页面，串口命令 `PERF`。
+//! 一帧画的时间或者一次 flush 超过预算(默认 50ms，`PERF BUDGET <ms>` 改)会打一条 defmt 警告。
+//!
+//! 计时直接读定时器的低 32 位(`ticks`)，一次寄存器读，不关中断、不拼 64 位；
+//! 被测的代码前后各读一次，减法和记账都在第二次读之后做，不算进耗时。32 位微秒 71 分钟绕一圈，
+//! 用 `wrapping_sub` 算差值，只要单次不超过 71 分钟就没问题。
+//!
+//! flush 是 DMA 在后台发的，记的是从开始发到主循环发现发完的时间，比真正的传输时间多一点点。
+//!
+//! 另外数着逻辑 tick 走了几步、丢了几步，画了几帧、因为落后跳过了几帧(`Cadence`，见 `timestep`)。
+
+use core::fmt::Write;
+
+use rp2040_hal::pac;
+
+/// 默认的一帧预算(微秒)
+pub const DEFAULT_BUDGET_US: u32 = 50_000;
+
+/// 串口一行最长多少字节
+pub const LINE_MAX: usize = 80;
+
+/// 定时器现在的读数(微秒，低 32 位)
+pub fn ticks() -> u32 {
+    // TIMERAWL 是只读的原始计数，读它没有副作用，不影响 hal 的 Timer
+    unsafe { (*pac::TIMER::ptr()).timerawl().read().bits() }
+}
+
+/// 一种东西的耗时统计
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timing {
+    count: u32,
+    min_us: u32,
+    max_us: u32,
+    total_us: u64,
+}
+
+impl Timing {
+    pub const fn new() -> Self {
+        Self {
+            count: 0,
+            min_us: 0,
+            max_us: 0,
+            total_us: 0,
+        }
+    }
+
+    pub fn record(&mut self, us: u32) {
+        if self.count == 0 || us < self.min_us {
+            self.min_us = us;
+        }
+        self.max_us = self.max_us.max(us);
+        self.total_us += us as u64;
+        self.count = self.count.saturating_add(1);
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    pub fn min_us(&self) -> u32 {
+        self.min_us
+    }
+
+    pub fn max_us(&self) -> u32 {
+        self.max_us
+    }
+
+    pub fn avg_us(&self) -> u32 {
+        if self.count == 0 {
+            0
+        } else {
+            (self.total_us / self.count as u64) as u32
+        }
+    }
+}
+
+/// 逻辑 tick 和画图各走了多少次，见 `timestep`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Cadence {
+    /// 走了几个 tick
+    pub ticks: u32,
+    /// 补不上丢掉的 tick
+    pub dropped: u32,
+    /// 画了几帧
+    pub frames: u32,
+    /// 落后了跳过没画的帧
+    pub skipped: u32,
+}
+
+impl Cadence {
+    pub const fn new() -> Self {
+        Self {
+            ticks: 0,
+            dropped: 0,
+            frames: 0,
+            skipped: 0,
+        }
+    }
+}
+
+/// 所有页面加上整帧、flush 的统计，`N` 是页面个数。调度器和 perf 页面通过 `RefCell` 共用一份
+#[derive(Debug)]
+pub struct PerfTable<const N: usize> {
+    /// 页面的名字，显示和串口输出用
+    names: [&'static str; N],
+    pages: [Timing; N],
+    frame: Timing,
+    flush: Timing,
+    cadence: Cadence,
+    budget_us: u32,
+}
+
+impl<const N: usize> PerfTable<N> {
+    /// `names` 和传给 `Scheduler::new` 的页面一一对应
+    pub const fn new(names: [&'static str; N]) -> Self {
+        Self {
+            names,
+            pages: [Timing::new(); N],
+            frame: Timing::new(),
+            flush: Timing::new(),
+            cadence: Cadence::new(),
+            budget_us: DEFAULT_BUDGET_US,
+        }
+    }
+
+    pub fn budget_us(&self) -> u32 {
+        self.budget_us
+    }
+
+    pub fn set_budget_us(&mut self, budget_us: u32) {
+        self.budget_us = budget_us;
+    }
+
+    /// 全部清零，预算不变
+    pub fn reset(&mut self) {
+        self.pages = [Timing::new(); N];
+        self.frame = Timing::new();
+        self.flush = Timing::new();
+        self.cadence = Cadence::new();
+    }
+
+    pub fn record_render(&mut self, page: usize, us: u32) {
+        if let Some(timing) = self.pages.get_mut(page) {
+            timing.record(us);
+        }
+    }
+
+    /// 返回 true 表示超预算了
+    pub fn record_frame(&mut self, us: u32) -> bool {
+        self.frame.record(us);
+        self.cadence.frames = self.cadence.frames.wrapping_add(1);
+        us > self.budget_us
+    }
+
+    /// 调度器这一圈走了 `ticks` 步、丢了 `dropped` 步，`skipped` 是这一帧因为落后没画
+    pub fn record_steps(&mut self, ticks: u32, dropped: u32, skipped: bool) {
+        let cadence = &mut self.cadence;
+        cadence.ticks = cadence.ticks.wrapping_add(ticks);
+        cadence.dropped = cadence.dropped.wrapping_add(dropped);
+        cadence.skipped = cadence.skipped.wrapping_add(skipped as u32);
+    }
+
+    pub fn cadence(&self) -> Cadence {
+        self.cadence
+    }
+
+    /// 返回 true 表示超预算了
+    pub fn record_flush(&mut self, us: u32) -> bool {
+        self.flush.record(us);
+        us > self.budget_us
+    }
+
+    /// 一共几行：整帧、flush，然后每个页面一行
+    pub fn rows(&self) -> usize {
+        2 + N
+    }
+
+    /// 第 `page` 个页面的名字
+    pub fn name(&self, page: usize) -> Option<&'static str> {
+        self.names.get(page).copied()
+    }
+
+    /// 第 `row` 行的名字和统计
+    pub fn row(&self, row: usize) -> Option<(&'static str, Timing)> {
+        match row {
+            0 => Some(("frame", self.frame)),
+            1 => Some(("flush", self.flush)),
+            _ => self
+                .names
+                .get(row - 2)
+                .map(|&name| (name, self.pages[row - 2])),
+        }
+    }
+
+    /// 串口输出一共几行：每个 `row` 一行，最后一行是 tick 和帧数
+    pub fn lines(&self) -> usize {
+        self.rows() + 1
+    }
+
+    /// 串口输出的一行：`PERF <名字> n=<次数> min=<us> avg=<us> max=<us>`，还没画过的页面不输出。
+    /// 最后一行是 `PERF ticks n=<tick 数> drop=<丢掉的> frames=<帧数> skip=<跳过的>`
+    pub fn write_line<W: Write>(&self, row: usize, out: &mut W) -> core::fmt::Result {
+        if row == self.rows() {
+            let cadence = self.cadence;
+            return write!(
+                out,
+                "PERF ticks n={} drop={} frames={} skip={}\r\n",
+                cadence.ticks, cadence.dropped, cadence.frames, cadence.skipped
+            );
+        }
+        let Some((name, timing)) = self.row(row) else {
+            return Ok(());
+        };
+        if timing.count() == 0 && row >= 2 {
+            return Ok(());
+        }
+        write!(
+            out,
+            "PERF {} n={} min={} avg={} max={}\r\n",
+            name,
+            timing.count(),
+            timing.min_us(),
+            timing.avg_us(),
+            timing.max_us()
+        )
+    }
+}
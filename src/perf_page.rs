@@ -0,0 +1,182 @@
+//! 耗时统计页面：每个页面画一次要多久，还有整帧和 flush(见 `perf`)
+//!
+//! 从设置菜单进来。每行是 "名字 平均/最大"，单位毫秒；还没画过的页面显示 `-`。
+//! 最上面两行是每秒走了几个逻辑 tick、画了几帧(`tick/frame`)，一共丢了几个 tick、跳过了几帧(`drop/skip`)，
+//! 画得慢的时候帧数掉下去，tick 还是 50(见 `timestep`)。
+//! Up/Down 滚动，Select 清零重新统计，Back 回去。
+
+use core::cell::RefCell;
+use core::convert::Infallible;
+use core::fmt::Write;
+
+use embedded_graphics::geometry::Point;
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::text::Text;
+use embedded_graphics::Drawable;
+use heapless::String;
+
+use crate::app::{Canvas, Event, Page, Transition};
+use crate::input::{Button, ButtonEvent};
+use crate::perf::{Cadence, PerfTable, Timing};
+use crate::widgets::{draw_menu_row, MENU_ROW_HEIGHT};
+
+/// 标题行基线
+const TITLE_Y: i32 = 8;
+
+/// 列表从哪一行开始
+const LIST_TOP: i32 = 12;
+
+/// 一屏放几行
+const VISIBLE_ROWS: usize = 4;
+
+/// 耗时统计前面的两行：每秒的 tick/帧数、丢掉的 tick/跳过的帧
+const CADENCE_ROWS: usize = 2;
+
+/// 每秒的 tick、帧数隔多久算一次
+const RATE_WINDOW_MS: u64 = 1000;
+
+/// 算每秒多少用的：上次算的时候的时间和计数
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    at_ms: u64,
+    cadence: Cadence,
+}
+
+/// 耗时统计页面
+pub struct PerfPage<'a, const N: usize> {
+    perf: &'a RefCell<PerfTable<N>>,
+    /// 最上面显示的是第几行
+    first: usize,
+    sample: Option<Sample>,
+    /// 上一秒的 tick 数和帧数
+    rates: Option<(u32, u32)>,
+}
+
+impl<'a, const N: usize> PerfPage<'a, N> {
+    pub const fn new(perf: &'a RefCell<PerfTable<N>>) -> Self {
+        Self {
+            perf,
+            first: 0,
+            sample: None,
+            rates: None,
+        }
+    }
+
+    /// 隔一秒拿计数的差算一次每秒多少
+    fn update_rates(&mut self, now_ms: u64) {
+        let cadence = self.perf.borrow().cadence();
+        let sample = Sample {
+            at_ms: now_ms,
+            cadence,
+        };
+        let Some(last) = self.sample else {
+            self.sample = Some(sample);
+            return;
+        };
+        let elapsed = now_ms.saturating_sub(last.at_ms);
+        if elapsed < RATE_WINDOW_MS {
+            return;
+        }
+        let per_second =
+            |now: u32, then: u32| (now.wrapping_sub(then) as u64 * 1000 / elapsed) as u32;
+        self.rates = Some((
+            per_second(cadence.ticks, last.cadence.ticks),
+            per_second(cadence.frames, last.cadence.frames),
+        ));
+        self.sample = Some(sample);
+    }
+
+    /// 第 `row` 行的名字和值，前两行是 tick 和帧数
+    fn row(&self, perf: &PerfTable<N>, row: usize) -> Option<(&'static str, String<16>)> {
+        let mut value = String::new();
+        match row {
+            0 => {
+                match self.rates {
+                    Some((ticks, frames)) => {
+                        let _ = write!(value, "{}/{}", ticks, frames);
+                    }
+                    None => {
+                        let _ = value.push('-');
+                    }
+                }
+                Some(("tick/frame", value))
+            }
+            1 => {
+                let cadence = perf.cadence();
+                let _ = write!(value, "{}/{}", cadence.dropped, cadence.skipped);
+                Some(("drop/skip", value))
+            }
+            _ => perf
+                .row(row - CADENCE_ROWS)
+                .map(|(name, timing)| (name, format_timing(&timing))),
+        }
+    }
+}
+
+/// 微秒写成一位小数的毫秒
+fn write_ms(out: &mut String<16>, us: u32) {
+    let tenths = (us + 50) / 100;
+    let _ = write!(out, "{}.{}", tenths / 10, tenths % 10);
+}
+
+fn format_timing(timing: &Timing) -> String<16> {
+    let mut value = String::new();
+    if timing.count() == 0 {
+        let _ = value.push('-');
+        return value;
+    }
+    write_ms(&mut value, timing.avg_us());
+    let _ = value.push('/');
+    write_ms(&mut value, timing.max_us());
+    value
+}
+
+impl<const N: usize> Page for PerfPage<'_, N> {
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        let rows = self.perf.borrow().rows() + CADENCE_ROWS;
+        let last_first = rows.saturating_sub(VISIBLE_ROWS);
+        match event {
+            Event::Button(ButtonEvent::Pressed(Button::Back)) => return Transition::Pop,
+            Event::Button(ButtonEvent::Pressed(Button::Up)) => {
+                self.first = self.first.saturating_sub(1);
+            }
+            Event::Button(ButtonEvent::Pressed(Button::Down)) => {
+                self.first = (self.first + 1).min(last_first);
+            }
+            Event::Button(ButtonEvent::Pressed(Button::Select)) => {
+                self.perf.borrow_mut().reset();
+                self.sample = None;
+                self.rates = None;
+            }
+            _ => {}
+        }
+        Transition::None
+    }
+
+    /// 数字一直在变，但是看清楚要时间，2 帧每秒就够了
+    fn desired_fps(&self) -> u16 {
+        2
+    }
+
+    fn tick(&mut self, now_ms: u64) -> bool {
+        self.update_rates(now_ms);
+        true
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<(), Infallible> {
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        Text::new("perf ms avg/max", Point::new(0, TITLE_Y), style).draw(canvas)?;
+        let perf = self.perf.borrow();
+        let rows = perf.rows() + CADENCE_ROWS;
+        for (line, row) in (self.first..rows).take(VISIBLE_ROWS).enumerate() {
+            let Some((name, value)) = self.row(&perf, row) else {
+                break;
+            };
+            let y = LIST_TOP + line as i32 * MENU_ROW_HEIGHT;
+            draw_menu_row(canvas, y, name, &value, false)?;
+        }
+        Ok(())
+    }
+}
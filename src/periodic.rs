@@ -0,0 +1,67 @@
+//! 定时任务：一组按固定间隔跑的活(查电压、记录数据、查告警……)，主循环每一圈问一次谁到点了
+//!
+//! 任务用调用方自己的枚举标识，`Periodic` 只管时间：每个任务一个间隔和下次什么时候跑，放在定长数组里，不分配内存。
+//! 到点的任务由主循环自己执行(一般是 `while let Some(task) = tasks.next_due(now_ms) { match task { .. } }`)，
+//! 这样任务直接用主循环里的局部变量，不用把状态塞进闭包。
+//!
+//! 加一个任务：枚举里加一项，`Periodic::new` 的数组里加一个 `Task::every`，`match` 里加一个分支。
+//!
+//! 不抢占：任务一个接一个在主循环里跑，跑完一个才轮到下一个，慢的任务会把后面的(包括 USB 和画图)一起拖慢，
+//! 所以每个任务都要很快返回，长的活自己分几圈做。下次运行时间从这次实际跑的时候算起，晚了不补，
+//! 同一个任务一圈最多跑一次，主循环卡了一会儿以后不会连着跑好几次。
+
+/// 一个定时任务
+#[derive(Debug, Clone, Copy)]
+pub struct Task<T> {
+    id: T,
+    interval_ms: u64,
+    next_ms: u64,
+}
+
+impl<T> Task<T> {
+    /// 每隔 `interval_ms` 毫秒跑一次，开机以后第一次主循环就跑
+    pub const fn every(id: T, interval_ms: u64) -> Self {
+        Self {
+            id,
+            interval_ms,
+            next_ms: 0,
+        }
+    }
+}
+
+/// 一组定时任务，按数组里的顺序检查，同一圈里前面的先跑
+#[derive(Debug)]
+pub struct Periodic<T, const N: usize> {
+    tasks: [Task<T>; N],
+}
+
+impl<T: Copy + PartialEq, const N: usize> Periodic<T, N> {
+    pub const fn new(tasks: [Task<T>; N]) -> Self {
+        Self { tasks }
+    }
+
+    fn find(&mut self, id: T) -> Option<&mut Task<T>> {
+        self.tasks.iter_mut().find(|task| task.id == id)
+    }
+
+    /// 下一个到点的任务，顺便把它的下次运行时间往后排一个间隔；都没到点返回 None
+    pub fn next_due(&mut self, now_ms: u64) -> Option<T> {
+        let task = self.tasks.iter_mut().find(|task| now_ms >= task.next_ms)?;
+        task.next_ms = now_ms + task.interval_ms;
+        Some(task.id)
+    }
+
+    /// 改间隔，从下次运行以后生效；想马上按新间隔来就再调 `run_now`
+    pub fn set_interval(&mut self, id: T, interval_ms: u64) {
+        if let Some(task) = self.find(id) {
+            task.interval_ms = interval_ms;
+        }
+    }
+
+    /// 下一圈就跑，不等间隔到(比如规则改了要马上重新评估)
+    pub fn run_now(&mut self, id: T) {
+        if let Some(task) = self.find(id) {
+            task.next_ms = 0;
+        }
+    }
+}
@@ -0,0 +1,58 @@
+//! 告警弹窗页面：盖在当前页面上面的一个框(`widgets::draw_alert`)，按任意键关掉
+//!
+//! 主循环广播 `Event::Popup`，这一页收下消息，不在最上面的话再把它 push 上去(见 main.rs)。
+//! 这是一个小窗口页面(`is_overlay`)，调度器会先画下面的页面再画它；关掉以后整屏按下面的页面重画，
+//! 显存里被框盖住的内容就回来了，不用另外保存。弹着的时候又来一条，新的顶掉旧的。
+
+use core::convert::Infallible;
+
+use heapless::String;
+
+use crate::app::{Canvas, Event, Page, Transition};
+use crate::input::ButtonEvent;
+use crate::widgets::draw_alert;
+
+/// 弹窗消息最长多少字节，框里最多也就放四行
+pub const POPUP_CAPACITY: usize = 64;
+
+/// 弹窗消息
+pub type PopupText = String<POPUP_CAPACITY>;
+
+/// 告警弹窗页面
+#[derive(Debug, Default)]
+pub struct PopupPage {
+    message: PopupText,
+}
+
+impl PopupPage {
+    pub const fn new() -> Self {
+        Self {
+            message: String::new(),
+        }
+    }
+}
+
+impl Page for PopupPage {
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        match event {
+            Event::Popup(message) => self.message = message.clone(),
+            // 关掉弹窗的这一下不交给下面的页面
+            Event::Button(ButtonEvent::Pressed(_)) => return Transition::Pop,
+            _ => {}
+        }
+        Transition::None
+    }
+
+    /// 弹出来就不变了，帧率跟着下面的页面走
+    fn desired_fps(&self) -> u16 {
+        1
+    }
+
+    fn is_overlay(&self) -> bool {
+        true
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<(), Infallible> {
+        draw_alert(canvas, &self.message)
+    }
+}
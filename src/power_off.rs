@@ -0,0 +1,225 @@
+//! 软关机：设置菜单里的 "power off"，或者在哪一页都行、按住 Select 三秒，确认以后关屏、让 RP2040 进最省电的 DORMANT 模式，
+//! 再按住 Select 一秒开机。关机键可以在按键重映射里换成别的键(见 `remap`)，开机也跟着用那个键
+//!
+//! 确认页面默认选着 "no"，Up/Down 换，Select 确定；选 "no" 或者按 Back 都回到设置菜单。
+//! 按住三秒(`gesture::HOLD_MS`，见 `gesture::HoldGate`)弹的是调度器的确认框 "power off?"(见 `confirm`)，
+//! 只有一个键的外壳就靠这个关机：这个键按满三秒的那一下不会被页面当成一次普通按键。
+//! 选了 "yes" 交给主循环(`Action::PowerOff`)：把还没存的脉冲计数、屏幕累计(见 `panel_care`)存进 flash(电压太低不让写的话就算了)，
+//! 记一条关机事件，清屏、关显示和电荷泵，等所有按键松开，然后调 `shut_down`。
+//!
+//! DORMANT 是 RP2040 最深的睡眠：晶振(没晶振的板子是 ROSC)停掉，所有时钟都停，只有 GPIO 的唤醒逻辑还在，
+//! 电流降到一毫安以下(不算屏幕和稳压芯片)。进去之前关掉所有中断、把 USB、I2C、DMA、PIO、PWM、ADC 这些外设都复位掉，
+//! 只打开**一个**唤醒源：Select 键(默认 GP14，见 `button_pins`)的下降沿。别的按键、USB、定时器都叫不醒它；
+//! 插拔 USB 线或者按 RUN 键复位也能开机。按一下醒过来以后在参考时钟上数 `WAKE_HOLD_MS`，中间松开了就接着睡，
+//! 口袋里碰一下不会开机。
+//!
+//! 按满了不去恢复 PLL 和外设，直接用看门狗把整片复位，和重新上电一样从头开机(初始化序列全部重来)。
+//! 所以开机的时候事件日志里记的复位原因是 `forced`(见 `event_log`)。
+//! USB 在关机那一刻就断了，电脑上会看到串口消失。
+
+use core::convert::Infallible;
+
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use rp2040_hal::pac;
+
+use crate::app::{Action, Canvas, Event, Page, Transition};
+use crate::input::{Button, ButtonEvent, ButtonPad};
+use crate::text::draw_centered;
+use crate::widgets::draw_menu;
+
+/// 默认的唤醒键，按键重映射里换了关机键的话跟着换(见 `remap`)
+pub const WAKE_BUTTON: Button = Button::Select;
+
+/// 醒过来以后唤醒键要按住多久才开机(毫秒)
+pub const WAKE_HOLD_MS: u32 = 1000;
+
+/// 数按住多久的时候多久看一次引脚(毫秒)
+const WAKE_POLL_MS: u32 = 10;
+
+/// DORMANT 醒来以后 CPU 跑在参考时钟上，也就是刚起振的晶振(没晶振的板子是 ROSC，大概 6MHz，不准)
+#[cfg(not(feature = "no-xosc"))]
+const REF_HZ: u32 = crate::boards::BOARD.xosc_hz;
+#[cfg(feature = "no-xosc")]
+const REF_HZ: u32 = 6_000_000;
+
+/// 问题那一行的基线
+const QUESTION_Y: i32 = 14;
+
+/// 两个选项从哪一行开始
+const CHOICES_TOP: i32 = 24;
+
+/// 两个选项，下标 0 是默认的
+const CHOICES: [&str; 2] = ["no", "yes"];
+
+/// 写进 XOSC/ROSC 的 DORMANT 寄存器就停振("coma")
+const DORMANT_VALUE: u32 = 0x636f_6d61;
+
+/// 确认页面
+#[derive(Debug, Default)]
+pub struct PowerOffPage {
+    selected: usize,
+    pending: Option<Action>,
+    /// 已经确认了，等主循环关机
+    confirmed: bool,
+}
+
+impl PowerOffPage {
+    pub const fn new() -> Self {
+        Self {
+            selected: 0,
+            pending: None,
+            confirmed: false,
+        }
+    }
+}
+
+impl Page for PowerOffPage {
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        let Event::Button(ButtonEvent::Pressed(button)) = event else {
+            return Transition::None;
+        };
+        if self.confirmed {
+            return Transition::None;
+        }
+        match button {
+            Button::Up | Button::Down => {
+                self.selected = (self.selected + 1) % CHOICES.len();
+                Transition::None
+            }
+            Button::Select if CHOICES[self.selected] == "yes" => {
+                self.pending = Some(Action::PowerOff);
+                self.confirmed = true;
+                Transition::None
+            }
+            // "no" 和 Back 一样：回到菜单，下次进来还是选着 "no"
+            Button::Select | Button::Back => {
+                self.selected = 0;
+                Transition::Pop
+            }
+        }
+    }
+
+    fn desired_fps(&self) -> u16 {
+        1
+    }
+
+    fn take_action(&mut self) -> Option<Action> {
+        self.pending.take()
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<(), Infallible> {
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        if self.confirmed {
+            return draw_centered(canvas, "shutting down", 32, style);
+        }
+        draw_centered(canvas, "shut down?", QUESTION_Y, style)?;
+        draw_menu(
+            canvas,
+            CHOICES_TOP,
+            CHOICES.iter().map(|&choice| (choice, "")),
+            self.selected,
+        )
+    }
+}
+
+/// 进 DORMANT，按住 `wake`(关机键，默认 `WAKE_BUTTON`)满 `WAKE_HOLD_MS` 以后整片复位，不会返回
+///
+/// 调之前屏幕要已经关好，按键要都松开了(不然 Select 还按着，松开再按才醒)。
+pub fn shut_down<const N: usize>(buttons: &mut ButtonPad<N>, wake: Button) -> ! {
+    buttons.set_wake_on_press(false);
+    cortex_m::interrupt::free(|_| {
+        // 安全性：关着中断，马上就要停振了，没有别人会再碰时钟和外设；醒来以后直接复位
+        unsafe {
+            // 醒来以后也不进中断，NVIC 里全部关掉，挂着的也清掉
+            let nvic = &*pac::NVIC::PTR;
+            for (icer, icpr) in nvic.icer.iter().zip(nvic.icpr.iter()) {
+                icer.write(u32::MAX);
+                icpr.write(u32::MAX);
+            }
+            // clk_sys 先切到 clk_ref(无毛刺切换)，clk_ref 本来就接着要停的那个振荡器，PLL 跟着停了也不影响 CPU
+            let clocks = &*pac::CLOCKS::ptr();
+            clocks.clk_sys_ctrl().modify(|_, w| w.src().clk_ref());
+            while clocks.clk_sys_selected().read().bits() != 1 {}
+            reset_peripherals();
+        }
+        loop {
+            // 顺便清掉上一次唤醒的边沿
+            buttons.set_dormant_wake(wake);
+            // 安全性：同上
+            unsafe {
+                #[cfg(not(feature = "no-xosc"))]
+                (*pac::XOSC::ptr())
+                    .dormant()
+                    .write(|w| w.bits(DORMANT_VALUE));
+                #[cfg(feature = "no-xosc")]
+                (*pac::ROSC::ptr())
+                    .dormant()
+                    .write(|w| w.bits(DORMANT_VALUE));
+            }
+            // 停在上面那一句，按键以后振荡器重新起振，接着往下跑
+            if held_for_wake(buttons, wake) {
+                reboot()
+            }
+        }
+    })
+}
+
+/// 醒来以后唤醒键是不是一直按着，按满 `WAKE_HOLD_MS`。定时器已经复位了，按参考时钟空转计时
+fn held_for_wake<const N: usize>(buttons: &mut ButtonPad<N>, wake: Button) -> bool {
+    for _ in 0..WAKE_HOLD_MS / WAKE_POLL_MS {
+        if !buttons.is_down_raw(wake) {
+            return false;
+        }
+        cortex_m::asm::delay(REF_HZ / 1000 * WAKE_POLL_MS);
+    }
+    true
+}
+
+/// 睡着的时候用不到的外设全部按住复位。GPIO(唤醒要用)和 QSPI(代码在 flash 里跑)不动，
+/// PLL 已经没人用了也一起复位；醒来以后看门狗整片复位，这些都会重新初始化
+///
+/// # Safety
+///
+/// clk_sys 要已经切到 clk_ref，而且关着中断
+unsafe fn reset_peripherals() {
+    (*pac::RESETS::ptr()).reset().modify(|_, w| {
+        w.adc().set_bit();
+        w.dma().set_bit();
+        w.i2c0().set_bit();
+        w.i2c1().set_bit();
+        w.pio0().set_bit();
+        w.pio1().set_bit();
+        w.pwm().set_bit();
+        w.rtc().set_bit();
+        w.spi0().set_bit();
+        w.spi1().set_bit();
+        w.timer().set_bit();
+        w.uart0().set_bit();
+        w.uart1().set_bit();
+        w.usbctrl().set_bit();
+        w.pll_usb().set_bit();
+        w.pll_sys().set_bit();
+        w
+    });
+}
+
+/// 看门狗立刻复位整片(振荡器除外)，和 pico-sdk 的 `watchdog_reboot` 一样
+pub fn reboot() -> ! {
+    // 安全性：马上就复位了，PSM 和看门狗别处没有在用
+    unsafe {
+        (*pac::PSM::ptr()).wdsel().write_with_zero(|w| {
+            w.bits(0x0001_ffff);
+            w.xosc().clear_bit();
+            w.rosc().clear_bit();
+            w
+        });
+        (*pac::WATCHDOG::ptr())
+            .ctrl()
+            .modify(|_, w| w.trigger().set_bit());
+    }
+    loop {
+        cortex_m::asm::nop();
+    }
+}
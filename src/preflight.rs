@@ -0,0 +1,181 @@
+//! 开机自检：在碰屏幕之前确认关键的外设都正常起来了
+//!
+//! 板子供电不稳、晶振虚焊这类问题，往往不会让程序直接跑不起来，而是表现成各种奇怪的小毛病
+//! (I2C 时快时慢、USB 枚举失败)，很难查。所以开机的时候先检查一遍，有问题就直接报出来，
+//! 而不是带着问题继续跑：
+//!
+//! 1. 已经用到的外设都退出了复位(RESETS.RESET_DONE)
+//! 2. 晶振稳定、两个 PLL 都锁定了，用片上的频率计实测系统时钟，和配置的频率对得上
+//! 3. SRAM4/SRAM5 两块小内存(memory.x 里没有用到)读写一致
+//!
+//! 除了频率计，只读寄存器，不改外设的状态；内存检查只写没人用的那两块。
+//!
+//! 打开 `no-xosc` 的时候没有晶振也没有 PLL，第 2 步整个跳过：频率计要拿晶振当基准，没有晶振量了也白量(见 rosc_clock.rs)。
+
+use core::fmt;
+use core::ptr::{read_volatile, write_volatile};
+
+use rp2040_hal::clocks::ClocksManager;
+use rp2040_hal::pac;
+#[cfg(not(feature = "no-xosc"))]
+use rp2040_hal::Clock;
+
+/// SRAM4 和 SRAM5 的起始地址，各 4K。memory.x 里的 RAM 只到 0x2004_0000，所以这两块没人用
+const SCRATCH_BANKS: [u32; 2] = [0x2004_0000, 0x2004_1000];
+
+/// 每块内存检查多少个字，不用全测，能发现"这块内存根本不工作"就够了
+const SCRATCH_WORDS: usize = 64;
+
+/// 实测的系统时钟和配置值最多差多少(千分之几)，频率计本身的误差远小于这个
+#[cfg(not(feature = "no-xosc"))]
+const CLOCK_TOLERANCE_PERMILLE: u32 = 10;
+
+/// 测试用的数据：交替的 01/10 能发现相邻位短路，地址本身当数据能发现地址线的问题
+const PATTERNS: [u32; 2] = [0x5555_5555, 0xAAAA_AAAA];
+
+/// 自检失败的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum BoardError {
+    /// 这个外设一直没退出复位
+    ResetStuck(&'static str),
+    /// 晶振没有稳定
+    XoscUnstable,
+    /// 这个 PLL 没有锁定
+    PllUnlocked(&'static str),
+    /// 实测的系统时钟(kHz)和配置的对不上
+    ClockMismatch {
+        expected_khz: u32,
+        measured_khz: u32,
+    },
+    /// 这个地址写进去读出来不一样
+    RamMismatch(u32),
+}
+
+impl fmt::Display for BoardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BoardError::ResetStuck(name) => write!(f, "{} stuck in reset", name),
+            BoardError::XoscUnstable => write!(f, "crystal oscillator unstable"),
+            BoardError::PllUnlocked(name) => write!(f, "{} not locked", name),
+            BoardError::ClockMismatch {
+                expected_khz,
+                measured_khz,
+            } => write!(
+                f,
+                "clk_sys is {} kHz, expected {} kHz",
+                measured_khz, expected_khz
+            ),
+            BoardError::RamMismatch(address) => write!(f, "RAM test failed at {:#010x}", address),
+        }
+    }
+}
+
+/// 跑一遍自检，第一个失败的检查会被返回。要在时钟和定时器初始化之后、屏幕初始化之前调用
+pub fn preflight(resets: &pac::RESETS, clocks: &ClocksManager) -> Result<(), BoardError> {
+    check_resets(resets)?;
+    #[cfg(not(feature = "no-xosc"))]
+    check_clocks(clocks)?;
+    #[cfg(feature = "no-xosc")]
+    let _ = clocks;
+    for bank in SCRATCH_BANKS {
+        check_ram(bank)?;
+    }
+    Ok(())
+}
+
+/// 到这一步为止 HAL 已经把这些外设从复位里放出来了
+fn check_resets(resets: &pac::RESETS) -> Result<(), BoardError> {
+    let done = resets.reset_done().read();
+    let checks = [
+        ("IO_BANK0", done.io_bank0().bit_is_set()),
+        ("PADS_BANK0", done.pads_bank0().bit_is_set()),
+        ("TIMER", done.timer().bit_is_set()),
+        #[cfg(not(feature = "no-xosc"))]
+        ("PLL_SYS", done.pll_sys().bit_is_set()),
+        #[cfg(not(feature = "no-xosc"))]
+        ("PLL_USB", done.pll_usb().bit_is_set()),
+    ];
+    match checks.iter().find(|(_, ok)| !ok) {
+        Some(&(name, _)) => Err(BoardError::ResetStuck(name)),
+        None => Ok(()),
+    }
+}
+
+/// PLL 和晶振的所有权已经交给了 `ClocksManager`，这里直接读状态寄存器
+#[cfg(not(feature = "no-xosc"))]
+fn check_clocks(clocks: &ClocksManager) -> Result<(), BoardError> {
+    // 安全性：只读状态寄存器，不会影响 HAL 对这些外设的配置
+    let (xosc, pll_sys, pll_usb) = unsafe {
+        (
+            &*pac::XOSC::ptr(),
+            &*pac::PLL_SYS::ptr(),
+            &*pac::PLL_USB::ptr(),
+        )
+    };
+    if xosc.status().read().stable().bit_is_clear() {
+        return Err(BoardError::XoscUnstable);
+    }
+    if pll_sys.cs().read().lock().bit_is_clear() {
+        return Err(BoardError::PllUnlocked("PLL_SYS"));
+    }
+    if pll_usb.cs().read().lock().bit_is_clear() {
+        return Err(BoardError::PllUnlocked("PLL_USB"));
+    }
+
+    let expected_khz = clocks.system_clock.freq().to_kHz();
+    let measured_khz = measure_sys_khz(clocks.reference_clock.freq().to_kHz());
+    if expected_khz.abs_diff(measured_khz) > expected_khz * CLOCK_TOLERANCE_PERMILLE / 1000 {
+        return Err(BoardError::ClockMismatch {
+            expected_khz,
+            measured_khz,
+        });
+    }
+    Ok(())
+}
+
+/// 用 CLOCKS 里的频率计(FC0)以参考时钟为基准测 clk_sys，步骤和 pico-sdk 的 `frequency_count_khz` 一样
+#[cfg(not(feature = "no-xosc"))]
+fn measure_sys_khz(ref_khz: u32) -> u32 {
+    // 安全性：频率计只是个测量电路，和时钟的配置互不影响，HAL 也不会用它
+    let regs = unsafe { &*pac::CLOCKS::ptr() };
+    while regs.fc0_status().read().running().bit_is_set() {}
+    regs.fc0_ref_khz()
+        .write(|w| unsafe { w.fc0_ref_khz().bits(ref_khz) });
+    regs.fc0_interval()
+        .write(|w| unsafe { w.fc0_interval().bits(10) });
+    regs.fc0_min_khz()
+        .write(|w| unsafe { w.fc0_min_khz().bits(0) });
+    regs.fc0_max_khz()
+        .write(|w| unsafe { w.fc0_max_khz().bits(0x1FF_FFFF) });
+    // 写测量源就开始测了
+    regs.fc0_src().write(|w| w.fc0_src().clk_sys());
+    while regs.fc0_status().read().done().bit_is_clear() {}
+    regs.fc0_result().read().khz().bits()
+}
+
+/// 写一遍读一遍，每种数据都来一次
+fn check_ram(base: u32) -> Result<(), BoardError> {
+    let words = base as *mut u32;
+    for pattern in PATTERNS {
+        for i in 0..SCRATCH_WORDS {
+            // 安全性：SRAM4/5 不在链接脚本的 RAM 里，栈和静态变量都不会放在这
+            unsafe { write_volatile(words.add(i), pattern) };
+        }
+        for i in 0..SCRATCH_WORDS {
+            if unsafe { read_volatile(words.add(i)) } != pattern {
+                return Err(BoardError::RamMismatch(base + (i * 4) as u32));
+            }
+        }
+    }
+    // 地址当数据：如果两个地址其实是同一块内存，后写的会把先写的盖掉
+    for i in 0..SCRATCH_WORDS {
+        unsafe { write_volatile(words.add(i), base + (i * 4) as u32) };
+    }
+    for i in 0..SCRATCH_WORDS {
+        let address = base + (i * 4) as u32;
+        if unsafe { read_volatile(words.add(i)) } != address {
+            return Err(BoardError::RamMismatch(address));
+        }
+    }
+    Ok(())
+}
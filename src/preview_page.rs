@@ -0,0 +1,64 @@
+//! 精灵图预览页面(feature `devtools`，见 `devtools`)
+//!
+//! 串口收齐第一张 `PREVIEW` 的时候主循环把这一页推上来，之后再收到的图直接换上去。
+//! 还没有图的时候(比如从别处进来)中间写一行提示。`PREVIEW OFF` 或者按 Back 关掉，下面的页面整页重画。
+
+use core::cell::RefCell;
+use core::convert::Infallible;
+
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+
+use crate::app::{Canvas, Event, Page, Transition};
+use crate::devtools::Preview;
+use crate::input::{Button, ButtonEvent};
+use crate::text::draw_centered;
+
+/// 精灵图预览页面
+pub struct PreviewPage<'a> {
+    preview: &'a RefCell<Preview>,
+    /// 上次画的时候 `Preview::generation` 是多少
+    drawn: Option<u32>,
+}
+
+impl<'a> PreviewPage<'a> {
+    pub const fn new(preview: &'a RefCell<Preview>) -> Self {
+        Self {
+            preview,
+            drawn: None,
+        }
+    }
+}
+
+impl Page for PreviewPage<'_> {
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        match event {
+            Event::Button(ButtonEvent::Pressed(Button::Back)) => Transition::Pop,
+            _ => Transition::None,
+        }
+    }
+
+    /// 换了图、挪了位置、开关了网格就重画
+    fn tick(&mut self, _now_ms: u64) -> bool {
+        let generation = self.preview.borrow().generation();
+        let changed = self.drawn != Some(generation);
+        self.drawn = Some(generation);
+        changed
+    }
+
+    fn desired_fps(&self) -> u16 {
+        10
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<(), Infallible> {
+        let preview = self.preview.borrow();
+        self.drawn = Some(preview.generation());
+        if !preview.has_picture() {
+            let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+            draw_centered(canvas, "waiting for PREVIEW", 30, style)?;
+        }
+        preview.draw(canvas);
+        Ok(())
+    }
+}
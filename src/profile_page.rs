@@ -0,0 +1,112 @@
+//! 显示方案的设置页面：室内、太阳底下、夜里选一个(见 `display_profile`)
+//!
+//! 从设置菜单进来。每行一个方案，值是方案的亮度，现在用着的那一行前面有个 `*`。
+//! Up/Down 选行，Select 选定并保存(`Action::SaveDisplayProfile`)，Back 回去。
+//! 串口换了方案、导入了设置会广播 `Event::DisplayProfile`，`*` 跟着挪。
+
+use core::convert::Infallible;
+use core::fmt::Write;
+
+use embedded_graphics::geometry::Point;
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::text::Text;
+use embedded_graphics::Drawable;
+use heapless::String;
+
+use crate::app::{Action, Canvas, Event, Page, Transition};
+use crate::display_profile::PROFILES;
+use crate::input::{Button, ButtonEvent};
+use crate::widgets::draw_menu;
+
+/// 标题行基线
+const TITLE_Y: i32 = 8;
+
+/// 菜单从哪一行开始
+const MENU_TOP: i32 = 12;
+
+const ROWS: usize = PROFILES.len();
+
+/// 显示方案设置页面
+#[derive(Debug)]
+pub struct ProfilePage {
+    /// 现在用着的方案
+    current: Option<u8>,
+    selected: usize,
+    pending: Option<Action>,
+}
+
+impl ProfilePage {
+    /// `current` 是设置里存的方案
+    pub const fn new(current: Option<u8>) -> Self {
+        Self {
+            current,
+            selected: 0,
+            pending: None,
+        }
+    }
+}
+
+impl Page for ProfilePage {
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        let button = match *event {
+            Event::DisplayProfile(current) => {
+                self.current = current;
+                return Transition::None;
+            }
+            Event::Button(ButtonEvent::Pressed(button)) => button,
+            _ => return Transition::None,
+        };
+        match button {
+            Button::Back => return Transition::Pop,
+            Button::Up => self.selected = (self.selected + ROWS - 1) % ROWS,
+            Button::Down => self.selected = (self.selected + 1) % ROWS,
+            Button::Select => {
+                self.current = Some(self.selected as u8);
+                self.pending = Some(Action::SaveDisplayProfile(self.selected as u8));
+            }
+        }
+        Transition::None
+    }
+
+    fn desired_fps(&self) -> u16 {
+        1
+    }
+
+    fn take_action(&mut self) -> Option<Action> {
+        self.pending.take()
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<(), Infallible> {
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        Text::new("display profile", Point::new(0, TITLE_Y), style).draw(canvas)?;
+
+        // 先把每一行拼好，菜单只借用字符串
+        let labels: [String<12>; ROWS] = core::array::from_fn(|row| {
+            let mut label = String::new();
+            let mark = if self.current == Some(row as u8) {
+                '*'
+            } else {
+                ' '
+            };
+            let _ = label.push(mark);
+            let _ = label.push_str(PROFILES[row].name);
+            label
+        });
+        let values: [String<8>; ROWS] = core::array::from_fn(|row| {
+            let mut value = String::new();
+            let _ = write!(value, "{}", PROFILES[row].contrast);
+            value
+        });
+        draw_menu(
+            canvas,
+            MENU_TOP,
+            labels
+                .iter()
+                .zip(values.iter())
+                .map(|(label, value)| (label.as_str(), value.as_str())),
+            self.selected,
+        )
+    }
+}
@@ -0,0 +1,507 @@
+//! 外部脉冲计数：流量计、翻斗雨量计这种来一个脉冲算一份量的传感器(见 `pulse_page`)
+//!
+//! 接法：传感器的输出接 `pulse_input_pin!`(默认 GP19)和 GND，引脚开上拉，传感器把引脚拉低一下就是一个脉冲
+//! (干簧管、霍尔开关、开漏输出都是这样)。
+//!
+//! 计数在 core0 的 GPIO 中断里做(main.rs 的 `IO_IRQ_BANK0` 调 `on_gpio_interrupt`)：下降沿记下时间，
+//! 上升沿看低了多久，不到 `min_width_ms` 的算抖动，不数(干簧管一合一开要抖几毫秒)。
+//! 写 flash 的时候中断关着，这期间来的边沿只留下一个标志，两个边沿都攒着的话宽度量不出来，按一个脉冲算。
+//! 打开计数以后 core0 的 IO_IRQ_BANK0 在 NVIC 里一直开着，别的地方(床头钟、主循环睡觉)拿它叫醒 WFI 以后
+//! 要用 `mask_gpio_irq` 代替直接屏蔽。按键唤醒的标志中断里顺手清掉，按键本来就是轮询的，不影响。
+//!
+//! 每个脉冲算多少、单位是什么存在设置里(`PulseConfig`，`SETTINGS PULSE 2.5 mL`)，总数乘上它就是工程单位的量。
+//! 速率是滑动窗口算的(`RateWindow`)：每秒记一次总数，每分钟是最近 60 秒的差，每小时是最近 60 分钟的差，
+//! 刚开机窗口还没满的时候按已经有的那一段折算。
+//!
+//! 总数跨重启：flash 里单独一个扇区(见 `flash`)，8 字节一条记录，每次往后写一条，读的时候取最后一条对得上的。
+//! 写满 512 条才擦一次扇区；最多 `SAVE_INTERVAL_MS` 存一次，而且总数变了才存，差不多三天半才擦一次。
+//! 断电会丢掉最后不到十分钟的脉冲。
+
+use heapless::HistoryBuffer;
+use rp2040_hal::pac;
+
+use crate::crc::crc32;
+use crate::flash;
+use crate::shared::{self, Shared};
+
+/// 编码之后多少字节：每个脉冲多少(千分之一单位) 4 字节 + 单位 1 字节 + 最短脉宽(毫秒) 2 字节
+pub const ENCODED_LEN: usize = 7;
+
+/// 能选的单位，设置里存的是编号，所以只能往后加
+pub const UNITS: [&str; 5] = ["pulse", "mL", "L", "mm", "gal"];
+
+/// 每小时速率通道的单位，和 `UNITS` 一一对应
+pub const RATE_UNITS: [&str; 5] = ["pulse/h", "mL/h", "L/h", "mm/h", "gal/h"];
+
+/// 每个脉冲最多算多少(千分之一单位)
+pub const MAX_MILLI_PER_PULSE: u32 = 1_000_000;
+
+/// 最短脉宽最长能设多少(毫秒)
+pub const MAX_MIN_WIDTH_MS: u16 = 1000;
+
+/// 默认的最短脉宽(毫秒)。霍尔流量计流量大的时候脉冲只有两三毫秒，干簧管抖得厉害的可以调到 10 以上
+pub const DEFAULT_MIN_WIDTH_MS: u16 = 2;
+
+/// 最多多久存一次 flash(毫秒)
+pub const SAVE_INTERVAL_MS: u64 = 10 * 60 * 1000;
+
+/// 一条记录：总数 4 字节 + CRC-32 4 字节，都是小端
+const RECORD_LEN: usize = 8;
+
+/// 扇区里一共放得下几条
+const RECORD_SLOTS: usize = flash::SECTOR_LEN / RECORD_LEN;
+
+/// IO_BANK0 中断寄存器里一个 GPIO 的四位：低电平、高电平、下降沿、上升沿
+const EDGE_LOW: u32 = 1 << 2;
+const EDGE_HIGH: u32 = 1 << 3;
+
+/// 脉冲计数的设置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct PulseConfig {
+    /// 每个脉冲算多少，千分之一单位(2.5 mL 存 2500)
+    pub milli_per_pulse: u32,
+    /// 单位，`UNITS` 里的编号
+    pub unit: u8,
+    /// 比这个短的脉冲不数(毫秒)，0 是都数
+    pub min_width_ms: u16,
+}
+
+impl Default for PulseConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl PulseConfig {
+    /// 一个脉冲算 1 个 "pulse"
+    pub const DEFAULT: Self = Self {
+        milli_per_pulse: 1000,
+        unit: 0,
+        min_width_ms: DEFAULT_MIN_WIDTH_MS,
+    };
+
+    pub fn is_valid(&self) -> bool {
+        (1..=MAX_MILLI_PER_PULSE).contains(&self.milli_per_pulse)
+            && (self.unit as usize) < UNITS.len()
+            && self.min_width_ms <= MAX_MIN_WIDTH_MS
+    }
+
+    pub fn unit_name(&self) -> &'static str {
+        UNITS.get(self.unit as usize).copied().unwrap_or("?")
+    }
+
+    pub fn rate_unit(&self) -> &'static str {
+        RATE_UNITS.get(self.unit as usize).copied().unwrap_or("?")
+    }
+
+    /// `pulses` 个脉冲一共多少(千分之一单位)，太大了停在 i32 的最大值
+    pub fn amount_milli(&self, pulses: u32) -> i32 {
+        (pulses as u64 * self.milli_per_pulse as u64).min(i32::MAX as u64) as i32
+    }
+
+    pub fn encode(&self) -> [u8; ENCODED_LEN] {
+        let mut out = [0u8; ENCODED_LEN];
+        out[0..4].copy_from_slice(&self.milli_per_pulse.to_le_bytes());
+        out[4] = self.unit;
+        out[5..7].copy_from_slice(&self.min_width_ms.to_le_bytes());
+        out
+    }
+
+    /// 解码，值不合理返回 None
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let &[m0, m1, m2, m3, unit, w0, w1] = bytes else {
+            return None;
+        };
+        let config = Self {
+            milli_per_pulse: u32::from_le_bytes([m0, m1, m2, m3]),
+            unit,
+            min_width_ms: u16::from_le_bytes([w0, w1]),
+        };
+        config.is_valid().then_some(config)
+    }
+}
+
+/// 按名字找单位(不区分大小写)，返回编号
+pub fn find_unit(name: &str) -> Option<u8> {
+    UNITS
+        .iter()
+        .position(|unit| unit.eq_ignore_ascii_case(name))
+        .map(|index| index as u8)
+}
+
+/// 解析 "2.5" 这样的小数，小数点后最多 3 位，返回千分之一单位
+pub fn parse_milli(text: &str) -> Option<u32> {
+    let (whole, fraction) = text.split_once('.').unwrap_or((text, ""));
+    if whole.is_empty() && fraction.is_empty() || fraction.len() > 3 {
+        return None;
+    }
+    let digits = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+    if !digits(whole) || !digits(fraction) {
+        return None;
+    }
+    let whole: u32 = if whole.is_empty() {
+        0
+    } else {
+        whole.parse().ok()?
+    };
+    let mut milli: u32 = if fraction.is_empty() {
+        0
+    } else {
+        fraction.parse().ok()?
+    };
+    for _ in fraction.len()..3 {
+        milli *= 10;
+    }
+    whole.checked_mul(1000)?.checked_add(milli)
+}
+
+/// 写一个千分之一单位的数，小数末尾的 0 不写，比如 2500 -> "2.5"、3000 -> "3"
+pub fn write_milli<W: core::fmt::Write>(milli: u64, out: &mut W) -> core::fmt::Result {
+    let (whole, mut fraction) = (milli / 1000, milli % 1000);
+    if fraction == 0 {
+        return write!(out, "{}", whole);
+    }
+    let mut width = 3;
+    while fraction % 10 == 0 {
+        fraction /= 10;
+        width -= 1;
+    }
+    write!(out, "{}.{:0width$}", whole, fraction)
+}
+
+/// 中断和主循环共用的计数状态
+struct Counter {
+    /// 接在哪个 GPIO，还没 `start` 是 None
+    gpio: Option<u8>,
+    config: PulseConfig,
+    /// 上一个下降沿的时间(微秒)，现在是高电平是 None
+    fall_us: Option<u64>,
+    total: u32,
+    /// 太短被当成抖动丢掉的
+    rejected: u32,
+}
+
+impl Counter {
+    /// `fell`/`rose` 是这次攒下的两个边沿标志，`high` 是现在的电平
+    fn on_edges(&mut self, fell: bool, rose: bool, high: bool, now_us: u64) {
+        match (fell, rose) {
+            (true, false) => self.fall_us = Some(now_us),
+            (false, true) => {
+                // 开机的时候就是低电平的，第一个上升沿前面没有下降沿，不算
+                if let Some(fall) = self.fall_us.take() {
+                    if now_us - fall >= self.config.min_width_ms as u64 * 1000 {
+                        self.total = self.total.wrapping_add(1);
+                    } else {
+                        self.rejected = self.rejected.wrapping_add(1);
+                    }
+                }
+            }
+            (true, true) => {
+                self.total = self.total.wrapping_add(1);
+                self.fall_us = (!high).then_some(now_us);
+            }
+            (false, false) => {}
+        }
+    }
+}
+
+static COUNTER: Shared<Counter> = Shared::new(Counter {
+    gpio: None,
+    config: PulseConfig::DEFAULT,
+    fall_us: None,
+    total: 0,
+    rejected: 0,
+});
+
+static WINDOW: Shared<RateWindow> = Shared::new(RateWindow::new());
+
+/// GPIO `gpio` 在 IO_BANK0 中断寄存器里是第几个寄存器、从第几位开始
+fn irq_position(gpio: u8) -> (usize, u32) {
+    (gpio as usize / 8, 4 * (gpio as u32 % 8))
+}
+
+/// 开始数 `gpio` 上的脉冲，`total` 是从 flash 读出来的总数。引脚要先配成上拉输入
+pub fn start(gpio: u8, config: PulseConfig, total: u32) {
+    COUNTER.lock(|counter| {
+        counter.gpio = Some(gpio);
+        counter.config = config;
+        counter.total = total;
+        counter.fall_us = None;
+    });
+    let (reg, shift) = irq_position(gpio);
+    let edges = (EDGE_LOW | EDGE_HIGH) << shift;
+    // 安全性：INTR 写 1 清零、PROC0_INTE 只加这个脚的两位，别的脚不动
+    let io = unsafe { &*pac::IO_BANK0::ptr() };
+    io.intr(reg).write(|w| unsafe { w.bits(edges) });
+    io.proc0_inte(reg)
+        .modify(|r, w| unsafe { w.bits(r.bits() | edges) });
+    // 安全性：处理函数只动 `COUNTER` 和中断标志
+    unsafe { pac::NVIC::unmask(pac::Interrupt::IO_IRQ_BANK0) };
+}
+
+/// 开始计数了没有
+pub fn is_running() -> bool {
+    COUNTER.lock(|counter| counter.gpio.is_some())
+}
+
+/// 拿 IO_IRQ_BANK0 叫醒 WFI 的地方用完了调这个代替 `NVIC::mask`：开着计数的话不能屏蔽
+pub fn mask_gpio_irq() {
+    if !is_running() {
+        pac::NVIC::mask(pac::Interrupt::IO_IRQ_BANK0);
+    }
+}
+
+/// main.rs 里 `IO_IRQ_BANK0` 中断在 core0 上调这个
+///
+/// 计数引脚的两个边沿交给 `Counter`，别的标志(按键唤醒的下降沿)清掉就行，不然标志不清会一直进
+pub fn on_gpio_interrupt() {
+    let now = shared::now_us();
+    // 安全性：读 PROC0_INTS 和 SIO 的输入电平，INTR 写 1 清零只清这次看到的位
+    let io = unsafe { &*pac::IO_BANK0::ptr() };
+    let sio = unsafe { &*pac::SIO::ptr() };
+    let gpio = COUNTER.lock(|counter| counter.gpio);
+    for reg in 0..4 {
+        let pending = io.proc0_ints(reg).read().bits();
+        if pending == 0 {
+            continue;
+        }
+        io.intr(reg).write(|w| unsafe { w.bits(pending) });
+        let Some(gpio) = gpio.filter(|&gpio| irq_position(gpio).0 == reg) else {
+            continue;
+        };
+        let bits = pending >> irq_position(gpio).1;
+        let high = sio.gpio_in().read().bits() & (1 << gpio) != 0;
+        COUNTER.lock(|counter| {
+            counter.on_edges(bits & EDGE_LOW != 0, bits & EDGE_HIGH != 0, high, now)
+        });
+    }
+}
+
+/// 现在的设置，改了设置以后调
+pub fn set_config(config: PulseConfig) {
+    COUNTER.lock(|counter| counter.config = config);
+}
+
+pub fn config() -> PulseConfig {
+    COUNTER.lock(|counter| counter.config)
+}
+
+/// 一共数了多少个脉冲
+pub fn total() -> u32 {
+    COUNTER.lock(|counter| counter.total)
+}
+
+/// 一共丢掉了多少个太短的
+pub fn rejected() -> u32 {
+    COUNTER.lock(|counter| counter.rejected)
+}
+
+/// 总数乘上每个脉冲的量(千分之一单位)，传感器通道 "pulse" 用
+pub fn amount_milli() -> i32 {
+    COUNTER.lock(|counter| counter.config.amount_milli(counter.total))
+}
+
+/// 最近一小时的速率(千分之一单位每小时)，还算不出来返回 None，传感器通道 "rate" 用
+pub fn rate_per_hour_milli() -> Option<i32> {
+    let config = config();
+    let milli_pulses = WINDOW.lock(|window| window.per_hour())?;
+    Some((milli_pulses * config.milli_per_pulse as u64 / 1000).min(i32::MAX as u64) as i32)
+}
+
+/// 最近一分钟、一小时的速率(千分之一个脉冲)
+pub fn rates() -> (Option<u64>, Option<u64>) {
+    WINDOW.lock(|window| (window.per_minute(), window.per_hour()))
+}
+
+/// 串口 `PULSE` 打的一行(不带 `\r\n`)：原始数、总量、两个速率(工程单位)、丢掉的抖动数
+pub fn write_summary<W: core::fmt::Write>(out: &mut W) -> core::fmt::Result {
+    let config = config();
+    let (per_minute, per_hour) = rates();
+    write!(out, "count={} total=", total())?;
+    write_milli(config.amount_milli(total()) as u64, out)?;
+    write!(out, "{}", config.unit_name())?;
+    for (name, rate) in [("per_min", per_minute), ("per_hour", per_hour)] {
+        write!(out, " {}=", name)?;
+        match rate {
+            Some(milli_pulses) => {
+                write_milli(milli_pulses * config.milli_per_pulse as u64 / 1000, out)?
+            }
+            None => out.write_str("--")?,
+        }
+    }
+    write!(out, " bounce={}", rejected())
+}
+
+/// 清零，速率窗口也从头开始
+pub fn reset() {
+    COUNTER.lock(|counter| {
+        counter.total = 0;
+        counter.rejected = 0;
+    });
+    WINDOW.lock(RateWindow::clear);
+}
+
+/// 主循环每一圈调，过了一秒就往速率窗口里记一次总数，记了返回 true
+pub fn update(now_ms: u64) -> bool {
+    let total = total();
+    WINDOW.lock(|window| window.update(total, now_ms))
+}
+
+/// 每秒一个总数要留几个(多一个才有 60 秒的差)
+const WINDOW_LEN: usize = 61;
+
+/// 滑动窗口：每秒记一次总数，每 60 秒再记一个到分钟的那一格里
+pub struct RateWindow {
+    seconds: HistoryBuffer<u32, WINDOW_LEN>,
+    minutes: HistoryBuffer<u32, WINDOW_LEN>,
+    /// 下一次该记的时间，还没记过是 None
+    next_ms: Option<u64>,
+    /// 一共记了多少秒
+    ticks: u32,
+}
+
+impl Default for RateWindow {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RateWindow {
+    pub const fn new() -> Self {
+        Self {
+            seconds: HistoryBuffer::new(),
+            minutes: HistoryBuffer::new(),
+            next_ms: None,
+            ticks: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// 到时间了就记一次 `total`，主循环卡了好几秒的话补上(最多补满一圈)。记了返回 true
+    pub fn update(&mut self, total: u32, now_ms: u64) -> bool {
+        let next = *self.next_ms.get_or_insert(now_ms);
+        if now_ms < next {
+            return false;
+        }
+        let seconds = (now_ms - next) / 1000 + 1;
+        for _ in 0..seconds.min(WINDOW_LEN as u64) {
+            if self.ticks.is_multiple_of(60) {
+                self.minutes.write(total);
+            }
+            self.seconds.write(total);
+            self.ticks = self.ticks.wrapping_add(1);
+        }
+        self.next_ms = Some(next + seconds * 1000);
+        true
+    }
+
+    /// 最近一分钟多少个脉冲(千分之一个)，不到两个快照返回 None
+    pub fn per_minute(&self) -> Option<u64> {
+        span(&self.seconds, 1).map(|(delta, seconds)| delta * 60_000 / seconds)
+    }
+
+    /// 最近一小时多少个脉冲(千分之一个)，分钟的快照还不够就按秒的那一段折算
+    pub fn per_hour(&self) -> Option<u64> {
+        span(&self.minutes, 60)
+            .or_else(|| span(&self.seconds, 1))
+            .map(|(delta, seconds)| delta * 3_600_000 / seconds)
+    }
+}
+
+/// 最新和最老的快照差多少个脉冲、隔了多少秒
+fn span(snapshots: &HistoryBuffer<u32, WINDOW_LEN>, step_s: u64) -> Option<(u64, u64)> {
+    if snapshots.len() < 2 {
+        return None;
+    }
+    let newest = *snapshots.recent()?;
+    let oldest = *snapshots.oldest_ordered().next()?;
+    Some((
+        newest.wrapping_sub(oldest) as u64,
+        (snapshots.len() as u64 - 1) * step_s,
+    ))
+}
+
+fn slot_offset(slot: usize) -> u32 {
+    flash::COUNTER_OFFSET + (slot * RECORD_LEN) as u32
+}
+
+fn is_empty(slot: usize) -> bool {
+    flash::read(slot_offset(slot), RECORD_LEN)
+        .iter()
+        .all(|&byte| byte == 0xFF)
+}
+
+fn decode(record: &[u8]) -> Option<u32> {
+    let crc = u32::from_le_bytes(record[4..8].try_into().ok()?);
+    (crc == crc32(&record[..4]))
+        .then(|| u32::from_le_bytes([record[0], record[1], record[2], record[3]]))
+}
+
+/// 最后一条对得上的记录和它在第几格。写坏了的(写到一半断电)跳过去
+fn latest() -> Option<(usize, u32)> {
+    (0..RECORD_SLOTS)
+        .take_while(|&slot| !is_empty(slot))
+        .filter_map(|slot| {
+            decode(flash::read(slot_offset(slot), RECORD_LEN)).map(|total| (slot, total))
+        })
+        .last()
+}
+
+/// 从 flash 读最新的总数，一条都没有是 0
+pub fn load_total() -> u32 {
+    latest().map_or(0, |(_, total)| total)
+}
+
+/// 往后写一条新记录，写满了先擦扇区。擦写期间关中断
+fn store_total(total: u32) {
+    let next = latest().map_or(0, |(slot, _)| slot + 1);
+    let slot = match (next..RECORD_SLOTS).find(|&slot| is_empty(slot)) {
+        Some(slot) => slot,
+        None => {
+            flash::erase_sector(flash::COUNTER_OFFSET);
+            0
+        }
+    };
+    let mut record = [0u8; RECORD_LEN];
+    record[..4].copy_from_slice(&total.to_le_bytes());
+    let crc = crc32(&record[..4]);
+    record[4..].copy_from_slice(&crc.to_le_bytes());
+    flash::program_bytes(slot_offset(slot), &record);
+}
+
+/// 定时把总数存进 flash，主循环里用
+pub struct CounterStore {
+    /// flash 里存的是多少
+    saved: u32,
+    last_save_ms: u64,
+}
+
+impl CounterStore {
+    /// `saved` 是开机时从 flash 读出来的
+    pub const fn new(saved: u32, now_ms: u64) -> Self {
+        Self {
+            saved,
+            last_save_ms: now_ms,
+        }
+    }
+
+    /// 到了该存 flash 的时候(离上一次存满 `SAVE_INTERVAL_MS`，而且总数变了)
+    pub fn save_due(&self, total: u32, now_ms: u64) -> bool {
+        total != self.saved && now_ms - self.last_save_ms >= SAVE_INTERVAL_MS
+    }
+
+    /// 有没存的计数(不管离上次存多久)，关机之前看这个
+    pub fn has_unsaved(&self, total: u32) -> bool {
+        total != self.saved
+    }
+
+    /// 存进 flash，调用方先查 `low_voltage::flash_writes_allowed`
+    pub fn save(&mut self, total: u32, now_ms: u64) {
+        store_total(total);
+        self.saved = total;
+        self.last_save_ms = now_ms;
+    }
+}
@@ -0,0 +1,144 @@
+//! 脉冲计数页面：总量、每分钟、每小时(见 `pulse_counter`)
+//!
+//! 从演示菜单的 "counter" 进来。第一行是总量(按设置换算成工程单位)，下面两行是最近一分钟、一小时的速率，
+//! 最后一行是原始脉冲数和丢掉的抖动数。长按 Select 问一下要不要清零(见 `confirm`)，
+//! 选 yes 交给主循环(`Action::ResetPulseCount`，主循环顺手存一次 flash)，Back 回去。
+
+use core::convert::Infallible;
+use core::fmt::Write;
+
+use embedded_graphics::geometry::Point;
+use embedded_graphics::mono_font::ascii::{FONT_10X20, FONT_6X10};
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::text::Text;
+use embedded_graphics::Drawable;
+use heapless::String;
+
+use crate::app::{Action, Canvas, Confirm, Event, Page, Transition};
+use crate::input::{Button, ButtonEvent};
+use crate::large_text::draw_large_readings;
+use crate::pulse_counter::{self, write_milli, PulseConfig};
+
+/// 标题行基线
+const TITLE_Y: i32 = 8;
+
+/// 页面上显示的东西，变了才重画
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Shown {
+    total: u32,
+    rejected: u32,
+    rates: (Option<u64>, Option<u64>),
+    config: PulseConfig,
+}
+
+impl Shown {
+    fn now() -> Self {
+        Self {
+            total: pulse_counter::total(),
+            rejected: pulse_counter::rejected(),
+            rates: pulse_counter::rates(),
+            config: pulse_counter::config(),
+        }
+    }
+
+    /// 总量，比如 "12.5mL"
+    fn amount_text(&self) -> String<20> {
+        let mut text = String::new();
+        let milli = self.config.amount_milli(self.total) as u64;
+        let _ = write_milli(milli, &mut text);
+        let _ = text.push_str(self.config.unit_name());
+        text
+    }
+
+    /// 一个速率(千分之一个脉冲)换算成工程单位，算不出来写 "--"
+    fn rate_text(&self, milli_pulses: Option<u64>, per: &str) -> String<24> {
+        let mut text = String::new();
+        let _ = write!(text, "{:<5}", per);
+        match milli_pulses {
+            Some(milli_pulses) => {
+                let milli = milli_pulses * self.config.milli_per_pulse as u64 / 1000;
+                let _ = write_milli(milli, &mut text);
+                let _ = text.push_str(self.config.unit_name());
+            }
+            None => {
+                let _ = text.push_str("--");
+            }
+        }
+        text
+    }
+}
+
+/// 脉冲计数页面
+#[derive(Debug, Default)]
+pub struct PulsePage {
+    shown: Option<Shown>,
+    pending: Option<Action>,
+}
+
+impl PulsePage {
+    pub const fn new() -> Self {
+        Self {
+            shown: None,
+            pending: None,
+        }
+    }
+}
+
+impl Page for PulsePage {
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        match event {
+            Event::Button(ButtonEvent::Pressed(Button::Back)) => Transition::Pop,
+            Event::Button(ButtonEvent::LongPress(Button::Select)) => Transition::Confirm(Confirm {
+                prompt: "reset counter?",
+                action: Action::ResetPulseCount,
+            }),
+            Event::Confirmed { request, yes: true } => {
+                self.pending = Some(request.action);
+                Transition::None
+            }
+            _ => Transition::None,
+        }
+    }
+
+    fn tick(&mut self, _now_ms: u64) -> bool {
+        let now = Shown::now();
+        let changed = self.shown != Some(now);
+        self.shown = Some(now);
+        changed
+    }
+
+    fn desired_fps(&self) -> u16 {
+        4
+    }
+
+    fn take_action(&mut self) -> Option<Action> {
+        self.pending.take()
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<(), Infallible> {
+        let shown = *self.shown.get_or_insert_with(Shown::now);
+        let small = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        Text::new("counter", Point::new(0, TITLE_Y), small).draw(canvas)?;
+        let big = MonoTextStyle::new(&FONT_10X20, BinaryColor::On);
+        Text::new(&shown.amount_text(), Point::new(0, 27), big).draw(canvas)?;
+        let (per_minute, per_hour) = shown.rates;
+        Text::new(
+            &shown.rate_text(per_minute, "/min"),
+            Point::new(0, 40),
+            small,
+        )
+        .draw(canvas)?;
+        Text::new(&shown.rate_text(per_hour, "/h"), Point::new(0, 51), small).draw(canvas)?;
+        let mut raw: String<32> = String::new();
+        let _ = write!(raw, "n {} bounce {}", shown.total, shown.rejected);
+        Text::new(&raw, Point::new(0, 62), small).draw(canvas)?;
+        Ok(())
+    }
+
+    fn render_large(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<bool, Infallible> {
+        let shown = *self.shown.get_or_insert_with(Shown::now);
+        draw_large_readings(canvas, &[("TOTAL", &shown.amount_text())])?;
+        Ok(true)
+    }
+}
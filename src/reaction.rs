@@ -0,0 +1,432 @@
+//! 双人反应游戏的计时核：core1 只管计时，core0 照常跑界面(见 `reaction_page`)
+//!
+//! 一局是这样的：core0 发 `TimerCommand::Start`，core1 先随机等一段时间(倒计时 `countdown_ms` 加上
+//! `MIN_WAIT_MS`..`MAX_WAIT_MS`，随机数取 ROSC 的 RANDOMBIT，是真的物理噪声而不是 `rng` 那种伪随机)，
+//! 到点发 `Go`，然后等两个玩家按键，各自发一条 `Reaction`(从 GO 到按下的微秒数)，
+//! `REACTION_TIMEOUT_MS` 内没按的发 `Missed`。`Go` 之前谁按了就是抢跑，发 `FalseStart` 这一局马上结束。
+//!
+//! 两个玩家的按键是 Up(玩家 1)和 Down(玩家 2)，GPIO 下降沿中断只在 core1 上打开(IO_BANK0 的 PROC1_INTE)，
+//! 中断处理函数(`on_gpio_interrupt`，main.rs 里的 `IO_IRQ_BANK0`)读计数器记下时间，所以时间精确到微秒，
+//! 不受 core0 画屏、flush、USB 的影响。记的是按下的第一个下降沿，后面的抖动不管。
+//! 注意 "GO" 是 core0 收到 `Go` 以后才画出来的，屏幕上看到的比计时起点晚一帧左右(20ms 上下)，
+//! 两个人晚得一样多，比输赢不受影响。
+//!
+//! 两个核之间只走 SIO 的 FIFO，一条消息一个 u32(`encode`/`decode`)。
+//!
+//! flash 擦写的时候 XIP 不能用，另一个核也不能在 flash 里跑代码。所以 core1 不比赛的时候停在 `park` 里：
+//! 这个函数放在 RAM 里(`.data.ram_func`)，关着中断，只读 SIO 的寄存器等下一条命令。
+//! core0 等 core1 停回 `park` 以后才发下一条 `Start`，发之前先 `flash::set_core1_busy(true)`，core1 回到 `park` 的时候自己清掉，
+//! 中间这几秒要写 flash 的(设置、数据记录、屏幕累计、图片)都和电压低的时候一样先等着(见 `low_voltage`)。
+
+use embedded_hal::delay::DelayNs;
+use rp2040_hal::pac;
+use rp2040_hal::sio::{Sio, SioFifo};
+use rp2040_hal::Timer;
+
+use crate::flash;
+use crate::shared::{self, Shared};
+
+/// 随机等待最短多久(毫秒，倒计时之后)
+pub const MIN_WAIT_MS: u32 = 1000;
+
+/// 随机等待最长多久(毫秒，倒计时之后)
+pub const MAX_WAIT_MS: u32 = 4000;
+
+/// GO 以后多久没按算没按(毫秒)
+pub const REACTION_TIMEOUT_MS: u32 = 2000;
+
+/// core1 的栈多大(字)
+pub const CORE1_STACK_WORDS: usize = 1024;
+
+/// 哪个玩家
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Player {
+    One,
+    Two,
+}
+
+impl Player {
+    pub const ALL: [Player; 2] = [Player::One, Player::Two];
+
+    pub fn index(self) -> usize {
+        match self {
+            Player::One => 0,
+            Player::Two => 1,
+        }
+    }
+
+    fn from_bit(bit: u32) -> Self {
+        if bit & 1 == 0 {
+            Player::One
+        } else {
+            Player::Two
+        }
+    }
+
+    pub fn other(self) -> Self {
+        match self {
+            Player::One => Player::Two,
+            Player::Two => Player::One,
+        }
+    }
+}
+
+/// core0 发给 core1 的
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum TimerCommand {
+    /// 开一局：先倒计时 `countdown_ms`，再随机等一会儿发 GO。倒计时期间按键也算抢跑
+    Start { countdown_ms: u16 },
+}
+
+/// core1 发给 core0 的
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum TimerMessage {
+    /// 到点了，该显示 GO
+    Go,
+    /// 有人抢跑，这一局结束
+    FalseStart(Player),
+    /// 从 GO 到按下多少微秒
+    Reaction { player: Player, micros: u32 },
+    /// GO 以后 `REACTION_TIMEOUT_MS` 都没按
+    Missed(Player),
+}
+
+/// 消息种类放在最高 4 位
+const TAG_SHIFT: u32 = 28;
+
+/// `Reaction` 的玩家放在第 24 位，下面 24 位是微秒(最多 16 秒，比超时长得多)
+const PLAYER_SHIFT: u32 = 24;
+const MICROS_MASK: u32 = (1 << PLAYER_SHIFT) - 1;
+
+impl TimerCommand {
+    pub fn encode(self) -> u32 {
+        match self {
+            TimerCommand::Start { countdown_ms } => 1 << TAG_SHIFT | countdown_ms as u32,
+        }
+    }
+
+    pub fn decode(word: u32) -> Option<Self> {
+        match word >> TAG_SHIFT {
+            1 => Some(TimerCommand::Start {
+                countdown_ms: word as u16,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl TimerMessage {
+    pub fn encode(self) -> u32 {
+        match self {
+            TimerMessage::Go => 1 << TAG_SHIFT,
+            TimerMessage::FalseStart(player) => 2 << TAG_SHIFT | player.index() as u32,
+            TimerMessage::Reaction { player, micros } => {
+                3 << TAG_SHIFT | (player.index() as u32) << PLAYER_SHIFT | micros.min(MICROS_MASK)
+            }
+            TimerMessage::Missed(player) => 4 << TAG_SHIFT | player.index() as u32,
+        }
+    }
+
+    pub fn decode(word: u32) -> Option<Self> {
+        match word >> TAG_SHIFT {
+            1 => Some(TimerMessage::Go),
+            2 => Some(TimerMessage::FalseStart(Player::from_bit(word))),
+            3 => Some(TimerMessage::Reaction {
+                player: Player::from_bit(word >> PLAYER_SHIFT),
+                micros: word & MICROS_MASK,
+            }),
+            4 => Some(TimerMessage::Missed(Player::from_bit(word))),
+            _ => None,
+        }
+    }
+}
+
+/// 两个玩家的按键接在哪个 GPIO，core1 开始之前填好
+static PLAYER_GPIOS: Shared<[u8; 2]> = Shared::new([0; 2]);
+
+/// 中断里记下的按下时间(微秒)，每个玩家只记第一次
+static PRESSES: Shared<[Option<u64>; 2]> = Shared::new([None; 2]);
+
+/// IO_BANK0 的中断寄存器里，GPIO `gpio` 的下降沿是哪个寄存器的哪一位
+fn edge_low_bit(gpio: u8) -> (usize, u32) {
+    (gpio as usize / 8, 1 << (4 * (gpio as u32 % 8) + 2))
+}
+
+/// 在 core1 上打开/关掉两个玩家按键的下降沿中断，打开之前清掉以前攒的
+fn set_player_interrupts(gpios: [u8; 2], enabled: bool) {
+    // 安全性：只改这两个脚在 PROC1_INTE 里的位(core0 的按键唤醒用的是 PROC0_INTE)，INTR 写 1 清零只动这两位
+    let io = unsafe { &*pac::IO_BANK0::ptr() };
+    for gpio in gpios {
+        let (reg, bit) = edge_low_bit(gpio);
+        io.intr(reg).write(|w| unsafe { w.bits(bit) });
+        io.proc1_inte(reg).modify(|r, w| unsafe {
+            w.bits(if enabled {
+                r.bits() | bit
+            } else {
+                r.bits() & !bit
+            })
+        });
+    }
+}
+
+/// main.rs 里 `IO_IRQ_BANK0` 中断在 core1 上调这个(core0 上是脉冲计数，见 `pulse_counter`)
+///
+/// 两个玩家的脚哪个来了下降沿就记下时间、清掉标志
+pub fn on_gpio_interrupt() {
+    let now = shared::now_us();
+    let gpios = PLAYER_GPIOS.lock(|gpios| *gpios);
+    // 安全性：读 PROC1_INTS、写 INTR 清零，都只动这两个脚的位
+    let io = unsafe { &*pac::IO_BANK0::ptr() };
+    for (player, gpio) in gpios.into_iter().enumerate() {
+        let (reg, bit) = edge_low_bit(gpio);
+        if io.proc1_ints(reg).read().bits() & bit == 0 {
+            continue;
+        }
+        io.intr(reg).write(|w| unsafe { w.bits(bit) });
+        PRESSES.lock(|presses| {
+            presses[player].get_or_insert(now);
+        });
+    }
+}
+
+/// ROSC 的随机位攒一个 `0..max` 的数
+fn random_below(max: u32) -> u32 {
+    // 安全性：只读 RANDOMBIT，不改振荡器
+    let rosc = unsafe { &*pac::ROSC::ptr() };
+    let mut word = 0u32;
+    for _ in 0..32 {
+        word = word << 1 | rosc.randombit().read().randombit().bit() as u32;
+        // 连着读几次之间振荡器要跑几圈，不然相邻的位差不多
+        cortex_m::asm::delay(64);
+    }
+    ((word as u64 * max as u64) >> 32) as u32
+}
+
+/// SIO 的地址
+const SIO_BASE: usize = 0xd000_0000;
+
+/// 不比赛的时候 core1 停在这里，等 core0 发下一条命令回来
+///
+/// 放在 RAM 里，关着中断，只碰 SIO 的寄存器和 `busy`(`flash::set_core1_busy` 那个标志)，
+/// core0 这时候擦写 flash 也不会碰到 XIP
+#[inline(never)]
+#[link_section = ".data.ram_func"]
+fn park(busy: *mut bool) -> u32 {
+    // 直接用地址，不走 pac 的访问函数，免得没内联、跳回 flash 里
+    const FIFO_ST: *const u32 = (SIO_BASE + 0x50) as *const u32;
+    const FIFO_RD: *const u32 = (SIO_BASE + 0x58) as *const u32;
+    const FIFO_ST_VLD: u32 = 1;
+    // 安全性：`busy` 指向一个静态的 AtomicBool，这里只写不读；SIO 的 FIFO 寄存器是每个核自己的
+    unsafe {
+        core::ptr::write_volatile(busy, false);
+        loop {
+            if core::ptr::read_volatile(FIFO_ST) & FIFO_ST_VLD != 0 {
+                return core::ptr::read_volatile(FIFO_RD);
+            }
+            core::arch::asm!("wfe");
+        }
+    }
+}
+
+/// 一局：倒计时加随机等待，GO，等两个人按。发出去的消息都走 `fifo`
+fn play_round(fifo: &mut SioFifo, timer: &mut Timer, gpios: [u8; 2], countdown_ms: u16) {
+    let send = |fifo: &mut SioFifo, message: TimerMessage| {
+        if fifo.is_write_ready() {
+            fifo.write(message.encode());
+        }
+    };
+    PRESSES.lock(|presses| *presses = [None; 2]);
+    set_player_interrupts(gpios, true);
+    // 安全性：这个核上只有这一个中断，处理函数只记时间
+    unsafe { pac::NVIC::unmask(pac::Interrupt::IO_IRQ_BANK0) };
+
+    let wait_ms = countdown_ms as u32 + MIN_WAIT_MS + random_below(MAX_WAIT_MS - MIN_WAIT_MS);
+    let go_us = shared::now_us() + wait_ms as u64 * 1000;
+    let mut false_start = None;
+    while shared::now_us() < go_us {
+        let presses = PRESSES.lock(|presses| *presses);
+        false_start = Player::ALL
+            .into_iter()
+            .find(|p| presses[p.index()].is_some());
+        if false_start.is_some() {
+            break;
+        }
+        timer.delay_us(100);
+    }
+
+    match false_start {
+        Some(player) => send(fifo, TimerMessage::FalseStart(player)),
+        None => {
+            send(fifo, TimerMessage::Go);
+            let deadline = go_us + REACTION_TIMEOUT_MS as u64 * 1000;
+            let mut reported = [false; 2];
+            while reported != [true; 2] && shared::now_us() < deadline {
+                let presses = PRESSES.lock(|presses| *presses);
+                for player in Player::ALL {
+                    if let (Some(at), false) = (presses[player.index()], reported[player.index()]) {
+                        let micros = at.saturating_sub(go_us) as u32;
+                        send(fifo, TimerMessage::Reaction { player, micros });
+                        reported[player.index()] = true;
+                    }
+                }
+                timer.delay_us(100);
+            }
+            for player in Player::ALL.into_iter().filter(|p| !reported[p.index()]) {
+                send(fifo, TimerMessage::Missed(player));
+            }
+        }
+    }
+
+    pac::NVIC::mask(pac::Interrupt::IO_IRQ_BANK0);
+    set_player_interrupts(gpios, false);
+}
+
+/// core1 的入口，`gpios` 是玩家 1、玩家 2 按键的 GPIO 号。不会返回
+///
+/// 用 `rp2040_hal::multicore` 起在 core1 上；起之前 core0 要 `flash::set_core1_busy(true)`，
+/// 这里一进 `park` 就清掉
+pub fn timing_core(gpios: [u8; 2], mut timer: Timer) -> ! {
+    PLAYER_GPIOS.lock(|slot| *slot = gpios);
+    // 安全性：core1 自己那一头的 FIFO，别处不会在 core1 上再用
+    let pac = unsafe { pac::Peripherals::steal() };
+    let mut fifo = Sio::new(pac.SIO).fifo;
+    loop {
+        cortex_m::interrupt::disable();
+        let word = park(flash::core1_busy_flag());
+        // 安全性：回到 flash 里的代码了，这个核上的中断只有上面那一个
+        unsafe { cortex_m::interrupt::enable() };
+        match TimerCommand::decode(word) {
+            Some(TimerCommand::Start { countdown_ms }) => {
+                play_round(&mut fifo, &mut timer, gpios, countdown_ms)
+            }
+            None => defmt::warn!("reaction: unknown command {=u32:#x}", word),
+        }
+    }
+}
+
+/// core0 这边等 core1 回到 `park` 最多等多久(微秒)。上一局的最后一条消息发出来以后它马上就回去了
+const PARK_WAIT_US: u64 = 10_000;
+
+/// core0 这边：让 core1 开一局，先标记 core1 要从 flash 里跑代码了
+///
+/// 要等 core1 已经停在 `park` 里(`core1_busy` 是 false)才发，不然它回 `park` 的时候会把标记清掉，
+/// 接着拿到这条命令又跑回 flash 里。等了 `PARK_WAIT_US` 还没停下来(或者 core1 根本没起来)返回 false
+pub fn start_round(fifo: &mut SioFifo, countdown_ms: u16) -> bool {
+    let deadline = shared::now_us() + PARK_WAIT_US;
+    while flash::core1_busy() {
+        if shared::now_us() >= deadline {
+            return false;
+        }
+    }
+    flash::set_core1_busy(true);
+    fifo.write_blocking(TimerCommand::Start { countdown_ms }.encode());
+    true
+}
+
+/// core0 这边：取一条 core1 发来的消息
+pub fn poll_message(fifo: &mut SioFifo) -> Option<TimerMessage> {
+    fifo.read().and_then(TimerMessage::decode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_round_trips() {
+        for countdown_ms in [0, 1, 3000, u16::MAX] {
+            let command = TimerCommand::Start { countdown_ms };
+            assert_eq!(TimerCommand::decode(command.encode()), Some(command));
+        }
+    }
+
+    #[test]
+    fn message_round_trips() {
+        let mut messages = std::vec![TimerMessage::Go];
+        for player in Player::ALL {
+            messages.push(TimerMessage::FalseStart(player));
+            messages.push(TimerMessage::Missed(player));
+            for micros in [0, 1, 123_456, REACTION_TIMEOUT_MS * 1000, MICROS_MASK] {
+                messages.push(TimerMessage::Reaction { player, micros });
+            }
+        }
+        for message in messages {
+            assert_eq!(
+                TimerMessage::decode(message.encode()),
+                Some(message),
+                "{:?}",
+                message
+            );
+        }
+    }
+
+    #[test]
+    fn reaction_time_saturates() {
+        // 超过 24 位的截到最大，不会把玩家位冲掉
+        let word = TimerMessage::Reaction {
+            player: Player::One,
+            micros: u32::MAX,
+        }
+        .encode();
+        assert_eq!(
+            TimerMessage::decode(word),
+            Some(TimerMessage::Reaction {
+                player: Player::One,
+                micros: MICROS_MASK,
+            })
+        );
+    }
+
+    #[test]
+    fn messages_are_distinct() {
+        let words = [
+            TimerMessage::Go.encode(),
+            TimerMessage::FalseStart(Player::One).encode(),
+            TimerMessage::FalseStart(Player::Two).encode(),
+            TimerMessage::Missed(Player::One).encode(),
+            TimerMessage::Missed(Player::Two).encode(),
+            TimerMessage::Reaction {
+                player: Player::One,
+                micros: 0,
+            }
+            .encode(),
+            TimerMessage::Reaction {
+                player: Player::Two,
+                micros: 0,
+            }
+            .encode(),
+        ];
+        for (i, a) in words.iter().enumerate() {
+            for b in &words[i + 1..] {
+                assert_ne!(a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_tags_decode_to_none() {
+        for tag in [0u32, 5, 15] {
+            assert_eq!(TimerMessage::decode(tag << TAG_SHIFT), None);
+        }
+        for tag in [0u32, 2, 15] {
+            assert_eq!(TimerCommand::decode(tag << TAG_SHIFT | 3000), None);
+        }
+    }
+
+    #[test]
+    fn edge_low_bits() {
+        assert_eq!(edge_low_bit(0), (0, 1 << 2));
+        assert_eq!(edge_low_bit(7), (0, 1 << 30));
+        assert_eq!(edge_low_bit(8), (1, 1 << 2));
+        assert_eq!(edge_low_bit(21), (2, 1 << 22));
+    }
+
+    #[test]
+    fn players() {
+        assert_eq!(Player::One.other(), Player::Two);
+        assert_eq!(Player::Two.other(), Player::One);
+        for player in Player::ALL {
+            assert_eq!(Player::from_bit(player.index() as u32), player);
+        }
+    }
+}
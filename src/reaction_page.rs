@@ -0,0 +1,413 @@
+//! 双人反应游戏的界面(core0)，计时在 core1 上(见 `reaction`)
+//!
+//! 玩家 1 按 Up，玩家 2 按 Down。Select 开一局：屏幕上 3、2、1 倒计时，然后 "wait"，
+//! 随机一会儿以后整屏变亮出 "GO!"，谁先按谁赢这一局。结果页显示两个人各用了多少毫秒和比分，
+//! 先赢 `WIN_SCORE` 局的赢(五局三胜)，这时候再按 Select 比分清零从头来。
+//!
+//! 一局怎么算：
+//!
+//! - 倒计时、wait 的时候按了是抢跑(`false`)，马上结束，这一局算另一个人赢
+//! - 两个人都按了，快的赢；一样快(微秒都一样，基本不会)谁也不得分
+//! - 只有一个人按了，他赢；都没按(`miss`)谁也不得分
+//!
+//! 一局进行中(倒计时到出结果)Back 不管用，core1 这时候在等按键，要等它把这一局算完。
+
+use core::convert::Infallible;
+use core::fmt::Write;
+
+use embedded_graphics::geometry::{Dimensions, Point, Size};
+use embedded_graphics::mono_font::ascii::{FONT_10X20, FONT_6X10};
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::text::Text;
+use embedded_graphics::Drawable;
+use heapless::String;
+
+use crate::app::{Action, Canvas, Event, Page, Transition};
+use crate::input::{Button, ButtonEvent};
+use crate::reaction::{Player, TimerMessage, MAX_WAIT_MS, REACTION_TIMEOUT_MS};
+use crate::text::draw_centered;
+use crate::widgets::draw_big_digit;
+
+/// 倒计时多久(毫秒)，3、2、1 各一秒
+const COUNTDOWN_MS: u16 = 3000;
+
+/// 先赢几局算赢
+pub const WIN_SCORE: u8 = 3;
+
+/// core1 一局最长多久(毫秒)，过了还没收到结果就当没按，免得消息丢了卡在这里
+const ROUND_LIMIT_MS: u64 = (COUNTDOWN_MS as u32 + MAX_WAIT_MS + REACTION_TIMEOUT_MS + 1000) as u64;
+
+/// 倒计时数字的格子
+const DIGIT_CELL: Rectangle = Rectangle::new(Point::new(52, 14), Size::new(24, 40));
+
+/// 标题行基线
+const TITLE_Y: i32 = 8;
+
+/// 一个玩家这一局怎么样了
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Outcome {
+    /// 还没结果
+    Pending,
+    /// 从 GO 到按下多少微秒
+    Time(u32),
+    /// 抢跑
+    FalseStart,
+    /// 没按
+    Missed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    /// 等 Select 开第一局
+    Ready,
+    /// 倒计时和随机等待，`started_ms` 是发 `StartReaction` 的时候
+    Counting { started_ms: u64 },
+    /// GO 了，等两个人的结果
+    Go { started_ms: u64 },
+    /// 这一局的结果，`winner` 是这一局谁得分
+    Result { winner: Option<Player> },
+}
+
+/// 反应游戏页面
+#[derive(Debug)]
+pub struct ReactionPage {
+    stage: Stage,
+    outcomes: [Outcome; 2],
+    score: [u8; 2],
+    pending: Option<Action>,
+}
+
+impl Default for ReactionPage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 这一局谁得分
+pub fn round_winner(outcomes: [Outcome; 2]) -> Option<Player> {
+    match outcomes {
+        [Outcome::FalseStart, _] => Some(Player::Two),
+        [_, Outcome::FalseStart] => Some(Player::One),
+        [Outcome::Time(one), Outcome::Time(two)] if one < two => Some(Player::One),
+        [Outcome::Time(one), Outcome::Time(two)] if two < one => Some(Player::Two),
+        [Outcome::Time(_), Outcome::Missed] => Some(Player::One),
+        [Outcome::Missed, Outcome::Time(_)] => Some(Player::Two),
+        _ => None,
+    }
+}
+
+impl ReactionPage {
+    pub const fn new() -> Self {
+        Self {
+            stage: Stage::Ready,
+            outcomes: [Outcome::Pending; 2],
+            score: [0; 2],
+            pending: None,
+        }
+    }
+
+    fn in_round(&self) -> bool {
+        matches!(self.stage, Stage::Counting { .. } | Stage::Go { .. })
+    }
+
+    /// 有人赢够了的话是谁
+    fn champion(&self) -> Option<Player> {
+        Player::ALL
+            .into_iter()
+            .find(|player| self.score[player.index()] >= WIN_SCORE)
+    }
+
+    fn start_round(&mut self, now_ms: u64) {
+        if self.champion().is_some() {
+            self.score = [0; 2];
+        }
+        self.outcomes = [Outcome::Pending; 2];
+        self.stage = Stage::Counting { started_ms: now_ms };
+        self.pending = Some(Action::StartReaction {
+            countdown_ms: COUNTDOWN_MS,
+        });
+    }
+
+    /// 一局算完了，记分
+    fn finish_round(&mut self) {
+        let winner = round_winner(self.outcomes);
+        if let Some(player) = winner {
+            self.score[player.index()] += 1;
+        }
+        self.stage = Stage::Result { winner };
+    }
+
+    fn on_message(&mut self, message: TimerMessage, now_ms: u64) {
+        if !self.in_round() {
+            return;
+        }
+        match message {
+            TimerMessage::Go => self.stage = Stage::Go { started_ms: now_ms },
+            TimerMessage::FalseStart(player) => {
+                self.outcomes[player.index()] = Outcome::FalseStart;
+                self.finish_round();
+            }
+            TimerMessage::Reaction { player, micros } => {
+                self.outcomes[player.index()] = Outcome::Time(micros)
+            }
+            TimerMessage::Missed(player) => self.outcomes[player.index()] = Outcome::Missed,
+        }
+        if matches!(self.stage, Stage::Go { .. }) && !self.outcomes.contains(&Outcome::Pending) {
+            self.finish_round();
+        }
+    }
+
+    fn draw_score(&self, canvas: &mut Canvas) -> Result<(), Infallible> {
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        let mut title: String<24> = String::new();
+        let _ = write!(title, "P1  {} : {}  P2", self.score[0], self.score[1]);
+        draw_centered(canvas, &title, TITLE_Y, style)
+    }
+
+    fn draw_result(&self, canvas: &mut Canvas, winner: Option<Player>) -> Result<(), Infallible> {
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        for player in Player::ALL {
+            let mut line: String<20> = String::new();
+            let _ = write!(line, "P{} ", player.index() + 1);
+            let _ = match self.outcomes[player.index()] {
+                Outcome::Time(micros) => {
+                    write!(line, "{:>4}.{} ms", micros / 1000, micros % 1000 / 100)
+                }
+                Outcome::FalseStart => write!(line, "   false"),
+                Outcome::Missed => write!(line, "    miss"),
+                Outcome::Pending => write!(line, "       -"),
+            };
+            if winner == Some(player) {
+                let _ = line.push_str(" <");
+            }
+            let y = 26 + player.index() as i32 * 12;
+            Text::new(&line, Point::new(16, y), style).draw(canvas)?;
+        }
+        match self.champion() {
+            Some(champion) => {
+                let mut line: String<24> = String::new();
+                let _ = write!(line, "P{} wins! (Select)", champion.index() + 1);
+                draw_centered(canvas, &line, 60, style)
+            }
+            None => draw_centered(canvas, "Select: next round", 60, style),
+        }
+    }
+}
+
+impl Page for ReactionPage {
+    fn on_event(&mut self, event: &Event, now_ms: u64) -> Transition {
+        match event {
+            Event::Reaction(message) => self.on_message(*message, now_ms),
+            Event::Button(ButtonEvent::Pressed(Button::Back)) if !self.in_round() => {
+                return Transition::Pop
+            }
+            Event::Button(ButtonEvent::Pressed(Button::Select)) if !self.in_round() => {
+                self.start_round(now_ms)
+            }
+            _ => {}
+        }
+        Transition::None
+    }
+
+    fn tick(&mut self, now_ms: u64) -> bool {
+        match self.stage {
+            Stage::Counting { started_ms } | Stage::Go { started_ms }
+                if now_ms >= started_ms + ROUND_LIMIT_MS =>
+            {
+                for outcome in self.outcomes.iter_mut() {
+                    if *outcome == Outcome::Pending {
+                        *outcome = Outcome::Missed;
+                    }
+                }
+                self.finish_round();
+                true
+            }
+            // 倒计时的数字要跟着走
+            Stage::Counting { .. } => true,
+            _ => false,
+        }
+    }
+
+    fn take_action(&mut self) -> Option<Action> {
+        self.pending.take()
+    }
+
+    /// GO 出来得越快越好，这一页帧率高一点
+    fn desired_fps(&self) -> u16 {
+        if self.in_round() {
+            60
+        } else {
+            10
+        }
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, now_ms: u64) -> Result<(), Infallible> {
+        let small = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        match self.stage {
+            Stage::Ready => {
+                self.draw_score(canvas)?;
+                draw_centered(canvas, "P1: Up  P2: Down", 30, small)?;
+                draw_centered(canvas, "Select: start", 46, small)?;
+            }
+            Stage::Counting { started_ms } => {
+                self.draw_score(canvas)?;
+                let elapsed = now_ms.saturating_sub(started_ms);
+                if elapsed < COUNTDOWN_MS as u64 {
+                    let digit = 3 - (elapsed / 1000) as u8;
+                    draw_big_digit(canvas, DIGIT_CELL, 4, digit)?;
+                } else {
+                    draw_centered(
+                        canvas,
+                        "wait",
+                        40,
+                        MonoTextStyle::new(&FONT_10X20, BinaryColor::On),
+                    )?;
+                }
+            }
+            Stage::Go { .. } => {
+                let area = canvas.bounding_box();
+                canvas.fill_rect(area, true);
+                let style = MonoTextStyle::new(&FONT_10X20, BinaryColor::Off);
+                draw_centered(canvas, "GO!", 40, style)?;
+            }
+            Stage::Result { winner } => {
+                self.draw_score(canvas)?;
+                self.draw_result(canvas, winner)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ONE: Player = Player::One;
+    const TWO: Player = Player::Two;
+
+    #[test]
+    fn winner_table() {
+        use Outcome::*;
+        let cases = [
+            ([Time(150_000), Time(180_000)], Some(ONE)),
+            ([Time(180_000), Time(150_000)], Some(TWO)),
+            ([Time(150_000), Time(150_000)], None),
+            ([FalseStart, Pending], Some(TWO)),
+            ([Pending, FalseStart], Some(ONE)),
+            ([FalseStart, Time(100)], Some(TWO)),
+            ([Time(100), FalseStart], Some(ONE)),
+            // 两个人同时抢跑的话 core1 只报先发现的那个，这里都算玩家 1 抢跑
+            ([FalseStart, FalseStart], Some(TWO)),
+            ([Time(1_999_000), Missed], Some(ONE)),
+            ([Missed, Time(1_999_000)], Some(TWO)),
+            ([Missed, Missed], None),
+            ([Pending, Pending], None),
+        ];
+        for (outcomes, winner) in cases {
+            assert_eq!(round_winner(outcomes), winner, "{:?}", outcomes);
+        }
+    }
+
+    fn press(page: &mut ReactionPage, button: Button, now_ms: u64) -> Transition {
+        page.on_event(&Event::Button(ButtonEvent::Pressed(button)), now_ms)
+    }
+
+    fn message(page: &mut ReactionPage, message: TimerMessage, now_ms: u64) {
+        page.on_event(&Event::Reaction(message), now_ms);
+    }
+
+    /// 开一局并走到 GO
+    fn start(page: &mut ReactionPage, now_ms: u64) {
+        press(page, Button::Select, now_ms);
+        assert_eq!(
+            page.take_action(),
+            Some(Action::StartReaction {
+                countdown_ms: COUNTDOWN_MS
+            })
+        );
+        message(page, TimerMessage::Go, now_ms + 5000);
+    }
+
+    fn react(page: &mut ReactionPage, player: Player, micros: u32) {
+        message(page, TimerMessage::Reaction { player, micros }, 0);
+    }
+
+    #[test]
+    fn scores_a_round_once_both_report() {
+        let mut page = ReactionPage::new();
+        start(&mut page, 0);
+        react(&mut page, TWO, 200_000);
+        assert!(page.in_round());
+        react(&mut page, ONE, 250_000);
+        assert_eq!(page.stage, Stage::Result { winner: Some(TWO) });
+        assert_eq!(page.score, [0, 1]);
+    }
+
+    #[test]
+    fn false_start_ends_round() {
+        let mut page = ReactionPage::new();
+        press(&mut page, Button::Select, 0);
+        message(&mut page, TimerMessage::FalseStart(ONE), 1000);
+        assert_eq!(page.stage, Stage::Result { winner: Some(TWO) });
+        assert_eq!(page.score, [0, 1]);
+        // 这一局已经结束，后面迟到的消息不算
+        message(&mut page, TimerMessage::Go, 2000);
+        react(&mut page, ONE, 100);
+        assert_eq!(page.score, [0, 1]);
+    }
+
+    #[test]
+    fn best_of_five() {
+        let mut page = ReactionPage::new();
+        for round in 0..WIN_SCORE {
+            assert_eq!(page.champion(), None);
+            start(&mut page, round as u64 * 10_000);
+            react(&mut page, ONE, 150_000);
+            message(&mut page, TimerMessage::Missed(TWO), 0);
+        }
+        assert_eq!(page.champion(), Some(ONE));
+        assert_eq!(page.score, [WIN_SCORE, 0]);
+        // 分出胜负以后再开一局比分清零
+        press(&mut page, Button::Select, 100_000);
+        assert_eq!(page.score, [0, 0]);
+        assert_eq!(page.champion(), None);
+    }
+
+    #[test]
+    fn back_only_between_rounds() {
+        let mut page = ReactionPage::new();
+        press(&mut page, Button::Select, 0);
+        assert_eq!(press(&mut page, Button::Back, 10), Transition::None);
+        // 一局进行中 Select 也不会重新开
+        page.take_action();
+        press(&mut page, Button::Select, 20);
+        assert_eq!(page.take_action(), None);
+        message(&mut page, TimerMessage::FalseStart(TWO), 30);
+        assert_eq!(press(&mut page, Button::Back, 40), Transition::Pop);
+    }
+
+    #[test]
+    fn lost_messages_time_out() {
+        let mut page = ReactionPage::new();
+        start(&mut page, 0);
+        react(&mut page, ONE, 300_000);
+        // Go 是 5000 收到的，从那时候再算 ROUND_LIMIT_MS
+        assert!(!page.tick(5000 + ROUND_LIMIT_MS - 1));
+        assert!(page.in_round());
+        assert!(page.tick(5000 + ROUND_LIMIT_MS));
+        assert_eq!(page.outcomes, [Outcome::Time(300_000), Outcome::Missed]);
+        assert_eq!(page.stage, Stage::Result { winner: Some(ONE) });
+    }
+
+    #[test]
+    fn messages_outside_a_round_are_ignored() {
+        let mut page = ReactionPage::new();
+        message(&mut page, TimerMessage::Go, 0);
+        react(&mut page, ONE, 100);
+        assert_eq!(page.stage, Stage::Ready);
+        assert_eq!(page.score, [0, 0]);
+    }
+}
@@ -0,0 +1,199 @@
+//! 长文阅读页面：一篇固件里带的文字(比如更新说明)，上下按像素平滑滚动
+//!
+//! 演示菜单里进来。Up/Down 每按一次滚 `STEP_PX` 像素，Select 换自动滚动的速度(停、慢、中、快)，Back 回去。
+//! 离开再进来还停在原来的位置，滚动状态都存在页面自己身上。
+//!
+//! 换行只做一次：第一次画的时候按屏幕宽度用 `wrap_lines` 切好，每一行存成原文里的一段 `&str`，不拷贝。
+//! 屏幕方向变了(宽度变了)才重新切。画的时候只画落在窗口里的那几行：
+//! 第一行是 `scroll / 行高`，往上挪 `scroll % 行高` 像素，上下露出一半的行交给裁剪去切，整篇文章不会每帧都排一遍。
+//! 右边一条细滚动条表示看到哪了。
+
+use core::convert::Infallible;
+
+use embedded_graphics::geometry::{OriginDimensions, Point, Size};
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::primitives::{Primitive, PrimitiveStyle, Rectangle};
+use embedded_graphics::text::{Baseline, Text};
+use embedded_graphics::Drawable;
+use heapless::Vec;
+
+use crate::app::{Canvas, Event, Page, Transition};
+use crate::clip::with_clip;
+use crate::input::{Button, ButtonEvent};
+use crate::text::wrap_lines;
+
+/// 最多几行，多出来的不显示
+pub const MAX_LINES: usize = 160;
+
+/// 按一次滚多少像素
+const STEP_PX: u32 = 4;
+
+/// 自动滚动的几档速度(像素/秒)，0 是停
+const AUTO_SPEEDS: [u32; 4] = [0, 8, 16, 32];
+
+/// 两次 tick 之间最多算多久(毫秒)
+const MAX_TICK_MS: u64 = 200;
+
+/// 滚动条宽度，和文字之间再空 1 像素
+const SCROLLBAR_WIDTH: u32 = 2;
+
+/// 行高就是字高
+const LINE_HEIGHT: u32 = 10;
+
+/// 长文阅读页面
+#[derive(Debug)]
+pub struct ReaderPage {
+    text: &'static str,
+    lines: Vec<&'static str, MAX_LINES>,
+    /// 按多宽切的行，0 是还没切
+    wrapped_width: u32,
+    /// 窗口顶边在文章里的位置(像素)
+    scroll: u32,
+    /// 窗口有多高，`on_event` 里算滚动上限要用，画过一次才知道
+    view_height: u32,
+    speed: usize,
+    /// 自动滚动攒下来不够一像素的部分(像素 * 1000)
+    carry: u32,
+    last_tick_ms: u64,
+}
+
+impl ReaderPage {
+    pub const fn new(text: &'static str) -> Self {
+        Self {
+            text,
+            lines: Vec::new(),
+            wrapped_width: 0,
+            scroll: 0,
+            view_height: 0,
+            speed: 0,
+            carry: 0,
+            last_tick_ms: 0,
+        }
+    }
+
+    /// 按 `width` 重新切行
+    fn wrap(&mut self, width: u32) {
+        self.lines.clear();
+        for line in wrap_lines(self.text, &FONT_6X10, width) {
+            if self.lines.push(line).is_err() {
+                defmt::warn!("reader text longer than {} lines, truncated", MAX_LINES);
+                break;
+            }
+        }
+        self.wrapped_width = width;
+        self.scroll = self.scroll.min(self.max_scroll());
+    }
+
+    /// 屏幕 `size` 大的时候按多宽切行，右边留滚动条
+    fn text_width(size: Size) -> u32 {
+        size.width - SCROLLBAR_WIDTH - 1
+    }
+
+    /// 整篇多高(像素)
+    fn content_height(&self) -> u32 {
+        self.lines.len() as u32 * LINE_HEIGHT
+    }
+
+    /// 最多能往下滚多少：最后一行贴着窗口底边
+    fn max_scroll(&self) -> u32 {
+        self.content_height().saturating_sub(self.view_height)
+    }
+
+    /// 往下(正)或者往上(负)滚，顶到头就停
+    fn scroll_by(&mut self, delta: i32) {
+        self.scroll = self
+            .scroll
+            .saturating_add_signed(delta)
+            .min(self.max_scroll());
+    }
+
+    fn draw_scrollbar(&self, canvas: &mut Canvas, size: Size) -> Result<(), Infallible> {
+        let content = self.content_height();
+        if content <= size.height {
+            return Ok(());
+        }
+        let x = (size.width - SCROLLBAR_WIDTH) as i32;
+        let thumb = (size.height * size.height / content).max(3);
+        let top = (size.height - thumb) * self.scroll / self.max_scroll().max(1);
+        Rectangle::new(Point::new(x, top as i32), Size::new(SCROLLBAR_WIDTH, thumb))
+            .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+            .draw(canvas)
+    }
+}
+
+impl Page for ReaderPage {
+    fn on_event(&mut self, event: &Event, now_ms: u64) -> Transition {
+        match event {
+            Event::Button(ButtonEvent::Pressed(Button::Back)) => return Transition::Pop,
+            Event::Button(ButtonEvent::Pressed(Button::Up)) => self.scroll_by(-(STEP_PX as i32)),
+            Event::Button(ButtonEvent::Pressed(Button::Down)) => self.scroll_by(STEP_PX as i32),
+            Event::Button(ButtonEvent::Pressed(Button::Select)) => {
+                self.speed = (self.speed + 1) % AUTO_SPEEDS.len();
+                self.carry = 0;
+                self.last_tick_ms = now_ms;
+                // 滚到底了再打开自动滚动，从头开始
+                if AUTO_SPEEDS[self.speed] > 0 && self.scroll >= self.max_scroll() {
+                    self.scroll = 0;
+                }
+            }
+            _ => {}
+        }
+        Transition::None
+    }
+
+    fn tick(&mut self, now_ms: u64) -> bool {
+        // 不在最上面的时候不会 tick，回来的时候不要一下跳过去一大截
+        let elapsed = now_ms.saturating_sub(self.last_tick_ms).min(MAX_TICK_MS) as u32;
+        self.last_tick_ms = now_ms;
+        let speed = AUTO_SPEEDS[self.speed];
+        if speed == 0 || self.scroll >= self.max_scroll() {
+            return false;
+        }
+        self.carry += speed * elapsed;
+        let step = self.carry / 1000;
+        self.carry %= 1000;
+        let before = self.scroll;
+        self.scroll_by(step as i32);
+        self.scroll != before
+    }
+
+    /// 按新的宽度重新切行，滚动位置收回新的上限里，不等下一次画
+    fn on_rotation_changed(&mut self, size: Size) {
+        self.view_height = size.height;
+        self.wrap(Self::text_width(size));
+    }
+
+    /// 自动滚动的时候一帧大约走一像素，停着只有按键才变
+    fn desired_fps(&self) -> u16 {
+        match AUTO_SPEEDS[self.speed] {
+            0 => 1,
+            speed => speed as u16,
+        }
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<(), Infallible> {
+        let size = canvas.size();
+        let text_width = Self::text_width(size);
+        self.view_height = size.height;
+        if self.wrapped_width != text_width {
+            self.wrap(text_width);
+        }
+
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        let first = (self.scroll / LINE_HEIGHT) as usize;
+        let offset = (self.scroll % LINE_HEIGHT) as i32;
+        // 窗口里能露出来的行数，上下各露一半的时候比整行数多一行
+        let visible = (size.height / LINE_HEIGHT + 2) as usize;
+        let area = Rectangle::new(Point::zero(), Size::new(text_width, size.height));
+        with_clip(canvas, area, |canvas| -> Result<(), Infallible> {
+            for (row, line) in self.lines.iter().skip(first).take(visible).enumerate() {
+                let y = row as i32 * LINE_HEIGHT as i32 - offset;
+                Text::with_baseline(line, Point::new(0, y), style, Baseline::Top).draw(canvas)?;
+            }
+            Ok(())
+        })?;
+        self.draw_scrollbar(canvas, size)
+    }
+}
@@ -0,0 +1,292 @@
+//! 记下画了什么，不画像素：一个只记操作的 `DrawTarget`，查排版的时候用
+//!
+//! 页面的 `render`、`widgets` 里的画图函数都是对任意 `DrawTarget` 泛型的，换成 `RecordingTarget` 画一遍，
+//! 就能拿到它们对屏幕做了哪些操作、按什么顺序：清屏、填矩形、整块填、一串零散的点。
+//! 比起对比整屏像素，这个能直接看出"进度条是从哪里画到哪里的""这一段文字有没有画出屏幕"。
+//!
+//! 记的是 `DrawTarget` 这一层看到的东西，不是 embedded-graphics 的图元：文字、线、圆到这里都已经拆成了点或者矩形，
+//! 所以只有 `Pixels` 的范围和点数，不知道原来是哪个字。
+//!
+//! 只在电脑上跑单元测试的时候编译(`cfg(test)`)，固件里没有。记录放在 `heapless::Vec` 里，
+//! 满了以后的操作不再记，`overflowed` 返回 true。
+
+use core::convert::Infallible;
+
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::{OriginDimensions, Point, Size};
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::Pixel;
+use heapless::Vec;
+
+use crate::theme::PatternTarget;
+
+/// 一次画图操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrawOp {
+    /// 整屏填成一个颜色
+    Clear(BinaryColor),
+    /// 一个矩形填成一个颜色
+    Fill { area: Rectangle, color: BinaryColor },
+    /// 一个矩形按顺序给了每个点的颜色，`lit` 是里面亮的点数
+    Contiguous { area: Rectangle, lit: u32 },
+    /// 一串零散的点：盖住所有点的矩形、一共几个点、亮的几个。没有点的不记
+    Pixels {
+        bounds: Rectangle,
+        count: u32,
+        lit: u32,
+    },
+}
+
+/// 只记操作的画布，最多记 `N` 条
+pub struct RecordingTarget<const N: usize> {
+    size: Size,
+    ops: Vec<DrawOp, N>,
+    overflowed: bool,
+}
+
+impl<const N: usize> RecordingTarget<N> {
+    /// `size` 是假装的屏幕大小，页面靠它决定怎么排版
+    pub const fn new(size: Size) -> Self {
+        Self {
+            size,
+            ops: Vec::new(),
+            overflowed: false,
+        }
+    }
+
+    /// 按顺序记下来的操作
+    pub fn ops(&self) -> &[DrawOp] {
+        &self.ops
+    }
+
+    /// 有没有操作因为记满了没记上
+    pub fn overflowed(&self) -> bool {
+        self.overflowed
+    }
+
+    /// 清空记录，接着记下一帧
+    pub fn reset(&mut self) {
+        self.ops.clear();
+        self.overflowed = false;
+    }
+
+    /// 所有操作里画亮过的地方合起来的范围，什么都没画亮返回 None
+    pub fn lit_bounds(&self) -> Option<Rectangle> {
+        let full = Rectangle::new(Point::zero(), self.size);
+        self.ops
+            .iter()
+            .filter_map(|op| match *op {
+                DrawOp::Clear(BinaryColor::On) => Some(full),
+                DrawOp::Fill {
+                    area,
+                    color: BinaryColor::On,
+                } => Some(area),
+                DrawOp::Contiguous { area, lit } if lit > 0 => Some(area),
+                DrawOp::Pixels { bounds, lit, .. } if lit > 0 => Some(bounds),
+                _ => None,
+            })
+            .reduce(bounding)
+    }
+
+    fn record(&mut self, op: DrawOp) {
+        if self.ops.push(op).is_err() {
+            self.overflowed = true;
+        }
+    }
+}
+
+impl<const N: usize> OriginDimensions for RecordingTarget<N> {
+    fn size(&self) -> Size {
+        self.size
+    }
+}
+
+impl<const N: usize> DrawTarget for RecordingTarget<N> {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        // 左上角和右下角(含)
+        let mut corners: Option<(Point, Point)> = None;
+        let (mut count, mut lit) = (0, 0);
+        for Pixel(point, color) in pixels {
+            count += 1;
+            lit += color.is_on() as u32;
+            corners = Some(match corners {
+                Some((min, max)) => (
+                    Point::new(min.x.min(point.x), min.y.min(point.y)),
+                    Point::new(max.x.max(point.x), max.y.max(point.y)),
+                ),
+                None => (point, point),
+            });
+        }
+        if let Some((min, max)) = corners {
+            let bounds = Rectangle::with_corners(min, max);
+            self.record(DrawOp::Pixels { bounds, count, lit });
+        }
+        Ok(())
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        let lit = colors
+            .into_iter()
+            .take(area.size.width as usize * area.size.height as usize)
+            .filter(|color| color.is_on())
+            .count() as u32;
+        self.record(DrawOp::Contiguous { area: *area, lit });
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        self.record(DrawOp::Fill { area: *area, color });
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.record(DrawOp::Clear(color));
+        Ok(())
+    }
+}
+
+/// 花纹填充走默认的 `fill_contiguous`，记成一条 `Contiguous`
+impl<const N: usize> PatternTarget for RecordingTarget<N> {}
+
+/// 同时盖住两个矩形的最小矩形
+fn bounding(a: Rectangle, b: Rectangle) -> Rectangle {
+    let end = |r: Rectangle| r.top_left + r.size;
+    let (a_end, b_end) = (end(a), end(b));
+    let top_left = Point::new(
+        a.top_left.x.min(b.top_left.x),
+        a.top_left.y.min(b.top_left.y),
+    );
+    let bottom_right = Point::new(a_end.x.max(b_end.x), a_end.y.max(b_end.y));
+    Rectangle::new(
+        top_left,
+        Size::new(
+            (bottom_right.x - top_left.x) as u32,
+            (bottom_right.y - top_left.y) as u32,
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_graphics::mono_font::ascii::FONT_6X10;
+    use embedded_graphics::mono_font::MonoTextStyle;
+    use embedded_graphics::prelude::Primitive;
+    use embedded_graphics::primitives::{Line, PrimitiveStyle};
+    use embedded_graphics::text::Text;
+    use embedded_graphics::Drawable;
+
+    const SIZE: Size = Size::new(128, 64);
+
+    fn rect(x: i32, y: i32, width: u32, height: u32) -> Rectangle {
+        Rectangle::new(Point::new(x, y), Size::new(width, height))
+    }
+
+    #[test]
+    fn records_each_kind_in_order() {
+        let mut target = RecordingTarget::<8>::new(SIZE);
+        target.clear(BinaryColor::Off).unwrap();
+        target
+            .fill_solid(&rect(1, 2, 3, 4), BinaryColor::On)
+            .unwrap();
+        target
+            .fill_contiguous(
+                &rect(0, 0, 2, 2),
+                [
+                    BinaryColor::On,
+                    BinaryColor::Off,
+                    BinaryColor::On,
+                    BinaryColor::Off,
+                ],
+            )
+            .unwrap();
+        Line::new(Point::new(5, 9), Point::new(2, 9))
+            .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+            .draw(&mut target)
+            .unwrap();
+        assert_eq!(
+            target.ops(),
+            [
+                DrawOp::Clear(BinaryColor::Off),
+                DrawOp::Fill {
+                    area: rect(1, 2, 3, 4),
+                    color: BinaryColor::On
+                },
+                DrawOp::Contiguous {
+                    area: rect(0, 0, 2, 2),
+                    lit: 2
+                },
+                DrawOp::Pixels {
+                    bounds: rect(2, 9, 4, 1),
+                    count: 4,
+                    lit: 4
+                },
+            ]
+        );
+        assert!(!target.overflowed());
+    }
+
+    #[test]
+    fn empty_pixel_runs_are_not_recorded() {
+        let mut target = RecordingTarget::<4>::new(SIZE);
+        target.draw_iter(core::iter::empty()).unwrap();
+        assert!(target.ops().is_empty());
+        assert_eq!(target.lit_bounds(), None);
+    }
+
+    #[test]
+    fn overflow_and_reset() {
+        let mut target = RecordingTarget::<2>::new(SIZE);
+        for _ in 0..3 {
+            target.clear(BinaryColor::Off).unwrap();
+        }
+        assert_eq!(target.ops().len(), 2);
+        assert!(target.overflowed());
+        target.reset();
+        assert!(target.ops().is_empty());
+        assert!(!target.overflowed());
+    }
+
+    #[test]
+    fn lit_bounds_skips_dark_ops() {
+        let mut target = RecordingTarget::<8>::new(SIZE);
+        target
+            .fill_solid(&rect(0, 0, 128, 64), BinaryColor::Off)
+            .unwrap();
+        target
+            .fill_solid(&rect(10, 10, 5, 5), BinaryColor::On)
+            .unwrap();
+        target
+            .fill_contiguous(&rect(40, 0, 2, 1), [BinaryColor::Off; 2])
+            .unwrap();
+        target
+            .draw_iter([Pixel(Point::new(30, 40), BinaryColor::On)])
+            .unwrap();
+        assert_eq!(target.lit_bounds(), Some(rect(10, 10, 21, 31)));
+        target.clear(BinaryColor::On).unwrap();
+        assert_eq!(target.lit_bounds(), Some(rect(0, 0, 128, 64)));
+    }
+
+    #[test]
+    fn text_is_recorded_as_pixels() {
+        let mut target = RecordingTarget::<4>::new(SIZE);
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        Text::new("Hi", Point::new(20, 30), style)
+            .draw(&mut target)
+            .unwrap();
+        // 字拆成了点，只知道范围：两个字 12 像素宽，基线往上 7 行
+        let bounds = target.lit_bounds().unwrap();
+        assert!(bounds.top_left.x >= 20 && bounds.top_left.x + bounds.size.width as i32 <= 32);
+        assert!(bounds.top_left.y >= 30 - 7 && bounds.top_left.y + bounds.size.height as i32 <= 31);
+    }
+}
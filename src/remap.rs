@@ -0,0 +1,420 @@
+//! 按键重映射：哪个物理按键、什么手势当 Up(上一个)、Down(下一个)、Select、Back、关机键用，存在设置里
+//!
+//! 不同的外壳按键接法不一样，哪个键当 Select 每个人想法也不一样。所以在按键驱动和页面中间加一层：
+//!
+//! - 物理输入(`PhysicalInput`)：一个键加上一种手势。键用它在 board.rs 里接线时的名字(`Button`)表示，
+//!   手势是单击、长按、双击(`Trigger`)，关机键固定是按满
+//! - 角色(`Role`)：页面认的逻辑按键，就是 `Button` 的四个加上关机(`PowerHold`)
+//! - 映射表(`InputBindings`)：每个角色绑一个物理输入
+//!
+//! 主循环把消抖后的物理按键事件和手势喂给 `Remapper`，出来的是逻辑按键事件和手势(`InputEvent`)，
+//! 再查全局按键表(见 `input_map`)、交给页面。所以页面里的 `Button::Select` 说的都是"当 Select 用的那个输入"。
+//!
+//! - 绑在单击上的角色：跟着那个键走，按下、松开、长按(`input::LONG_PRESS_MS`)原样发，
+//!   那个键上的手势也换成这个角色的手势(`Gesture::Short(Button::Select)` 这些)
+//! - 绑在长按、双击上的角色：那个手势出来的时候发一次按下、松开，不发手势。同一个键单击还可以绑别的角色，
+//!   这时候长按之前单击那个角色已经按下去了
+//! - 关机键：哪个键按满 3 秒关机(见 `gesture::HoldGate`)，关机以后也是按住这个键开机(见 `power_off`)
+//!
+//! 同一个物理输入不能绑两个角色，`InputBindings::set` 会拒绝，并说出被谁占了。
+//! 没绑到任何角色的键、手势直接丢掉。
+//!
+//! 在设置菜单的 "inputs" 里改(见 `remap_page`)：选一个角色，屏幕上说"现在按你想当 SELECT 的那个输入"，
+//! 按一下(单击、长按、双击都行)就记下来(`Remapper::start_capture`)，10 秒没按就算了。
+//! 改坏了没法操作的时候，任何一个键按住 10 秒不放恢复默认的映射(`RESET_HOLD_MS`)。
+//! 默认 Back 长按 1 秒是进床头钟，关机键按满 3 秒会先弹关机确认框，接着按到 10 秒就行。
+
+use heapless::Deque;
+
+use crate::gesture::Gesture;
+use crate::input::{Button, ButtonEvent};
+use crate::input_map::InputEvent;
+
+/// 任何一个键按住多久恢复默认映射(毫秒)
+pub const RESET_HOLD_MS: u64 = 10_000;
+
+/// 等用户按键等多久(毫秒)，没按就不改了
+pub const CAPTURE_TIMEOUT_MS: u64 = 10_000;
+
+/// 设置里占几个字节：每个角色一个
+pub const ENCODED_LEN: usize = ROLES.len();
+
+/// `Remapper` 一次最多攒多少个逻辑输入
+pub const QUEUE_LEN: usize = 16;
+
+/// 四个物理按键，编码用的顺序
+const KEYS: [Button; 4] = [Button::Up, Button::Down, Button::Select, Button::Back];
+
+/// 逻辑按键
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Role {
+    /// 上一个(`Button::Up`)
+    Up,
+    /// 下一个(`Button::Down`)
+    Down,
+    Select,
+    Back,
+    /// 按满关机
+    PowerHold,
+}
+
+/// 所有角色，设置里按这个顺序存
+pub const ROLES: [Role; 5] = [
+    Role::Up,
+    Role::Down,
+    Role::Select,
+    Role::Back,
+    Role::PowerHold,
+];
+
+impl Role {
+    pub fn name(self) -> &'static str {
+        match self {
+            Role::Up => "prev",
+            Role::Down => "next",
+            Role::Select => "select",
+            Role::Back => "back",
+            Role::PowerHold => "power",
+        }
+    }
+
+    /// 页面收到的是哪个按键，关机键不交给页面
+    pub fn button(self) -> Option<Button> {
+        match self {
+            Role::Up => Some(Button::Up),
+            Role::Down => Some(Button::Down),
+            Role::Select => Some(Button::Select),
+            Role::Back => Some(Button::Back),
+            Role::PowerHold => None,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// 物理按键上的手势
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Trigger {
+    Click,
+    Long,
+    Double,
+    /// 按满 `gesture::HOLD_MS`，只给关机键用
+    Hold,
+}
+
+impl Trigger {
+    pub fn name(self) -> &'static str {
+        match self {
+            Trigger::Click => "click",
+            Trigger::Long => "long",
+            Trigger::Double => "double",
+            Trigger::Hold => "hold",
+        }
+    }
+}
+
+/// 一个物理输入：哪个键、什么手势
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct PhysicalInput {
+    pub key: Button,
+    pub trigger: Trigger,
+}
+
+impl PhysicalInput {
+    pub const fn new(key: Button, trigger: Trigger) -> Self {
+        Self { key, trigger }
+    }
+
+    /// 键的短名字，菜单里用
+    pub fn key_name(self) -> &'static str {
+        match self.key {
+            Button::Up => "up",
+            Button::Down => "down",
+            Button::Select => "sel",
+            Button::Back => "back",
+        }
+    }
+}
+
+/// 每个角色绑的物理输入
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct InputBindings {
+    inputs: [PhysicalInput; 5],
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl InputBindings {
+    /// 按接线的名字：四个键单击各是各的，Select 按满关机
+    pub const DEFAULT: Self = Self {
+        inputs: [
+            PhysicalInput::new(Button::Up, Trigger::Click),
+            PhysicalInput::new(Button::Down, Trigger::Click),
+            PhysicalInput::new(Button::Select, Trigger::Click),
+            PhysicalInput::new(Button::Back, Trigger::Click),
+            PhysicalInput::new(Button::Select, Trigger::Hold),
+        ],
+    };
+
+    pub fn get(&self, role: Role) -> PhysicalInput {
+        self.inputs[role.index()]
+    }
+
+    /// 角色绑的是哪个物理键
+    pub fn key(&self, role: Role) -> Button {
+        self.get(role).key
+    }
+
+    /// 绑在这个物理输入上的角色
+    pub fn role_for(&self, input: PhysicalInput) -> Option<Role> {
+        ROLES.into_iter().find(|&role| self.get(role) == input)
+    }
+
+    /// 把 `role` 绑到 `input`。关机键只能是按满，别的不能是按满；
+    /// 这个输入已经被别的角色占了的话不改，返回那个角色
+    pub fn set(&mut self, role: Role, input: PhysicalInput) -> Result<(), Role> {
+        let input = match role {
+            Role::PowerHold => PhysicalInput::new(input.key, Trigger::Hold),
+            _ if input.trigger == Trigger::Hold => PhysicalInput::new(input.key, Trigger::Click),
+            _ => input,
+        };
+        match self.role_for(input) {
+            Some(other) if other != role => Err(other),
+            _ => {
+                self.inputs[role.index()] = input;
+                Ok(())
+            }
+        }
+    }
+
+    /// 一个角色一个字节：低 4 位是键，高 4 位是手势
+    pub fn encode(&self) -> [u8; ENCODED_LEN] {
+        self.inputs.map(|input| {
+            let key = KEYS.iter().position(|&key| key == input.key).unwrap_or(0) as u8;
+            let trigger = match input.trigger {
+                Trigger::Click => 0,
+                Trigger::Long => 1,
+                Trigger::Double => 2,
+                Trigger::Hold => 3,
+            };
+            key | trigger << 4
+        })
+    }
+
+    /// 解码，键或者手势不对、关机键不是按满、两个角色绑了同一个输入的都返回 None
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != ENCODED_LEN {
+            return None;
+        }
+        let mut bindings = Self::DEFAULT;
+        for (role, &byte) in ROLES.into_iter().zip(bytes) {
+            let key = *KEYS.get((byte & 0x0F) as usize)?;
+            let trigger = match byte >> 4 {
+                0 => Trigger::Click,
+                1 => Trigger::Long,
+                2 => Trigger::Double,
+                3 => Trigger::Hold,
+                _ => return None,
+            };
+            if (role == Role::PowerHold) != (trigger == Trigger::Hold) {
+                return None;
+            }
+            bindings.inputs[role.index()] = PhysicalInput::new(key, trigger);
+        }
+        let distinct = ROLES.into_iter().all(|role| {
+            ROLES
+                .into_iter()
+                .all(|other| other == role || bindings.get(other) != bindings.get(role))
+        });
+        distinct.then_some(bindings)
+    }
+}
+
+/// `Remapper::poll` 报的事
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum RemapEvent {
+    /// 等到了用户按的输入，还没检查有没有被占
+    Captured(Role, PhysicalInput),
+    /// 等了 `CAPTURE_TIMEOUT_MS` 没按
+    TimedOut(Role),
+    /// 有个键按住了 `RESET_HOLD_MS`，该恢复默认映射了
+    Reset,
+}
+
+/// 正在等用户按键
+#[derive(Debug, Clone, Copy)]
+struct Capture {
+    role: Role,
+    since_ms: u64,
+    /// 开始等以后才按下去的键，开始之前就按着的(菜单里按的那一下 Select)不算
+    armed: [bool; 4],
+}
+
+/// 物理输入翻译成逻辑输入，见模块文档
+#[derive(Debug)]
+pub struct Remapper {
+    bindings: InputBindings,
+    /// 每个键按下去的时候按单击绑的角色，松开的时候照这个发，中途改了映射也不会漏掉松开
+    held: [Option<Button>; 4],
+    /// 每个键什么时候按下去的，报过恢复默认的是 None
+    down_since: [Option<u64>; 4],
+    capture: Option<Capture>,
+    /// 等到的输入，下一次 `poll` 报
+    pending: Option<RemapEvent>,
+}
+
+impl Remapper {
+    pub const fn new(bindings: InputBindings) -> Self {
+        Self {
+            bindings,
+            held: [None; 4],
+            down_since: [None; 4],
+            capture: None,
+            pending: None,
+        }
+    }
+
+    pub fn bindings(&self) -> &InputBindings {
+        &self.bindings
+    }
+
+    /// 换映射表，按着的键松开的时候还是按原来的角色发
+    pub fn set_bindings(&mut self, bindings: InputBindings) {
+        self.bindings = bindings;
+    }
+
+    /// 开始等用户按一个输入给 `role`，等的时候所有按键都不交给页面
+    pub fn start_capture(&mut self, role: Role, now_ms: u64) {
+        self.capture = Some(Capture {
+            role,
+            since_ms: now_ms,
+            armed: [false; 4],
+        });
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        self.capture.is_some()
+    }
+
+    /// 每个物理按键事件先给这里：记着按了多久(恢复默认用)、等按键的时候哪些键是新按的
+    pub fn note(&mut self, event: ButtonEvent, now_ms: u64) {
+        match event {
+            ButtonEvent::Pressed(key) => {
+                self.down_since[key_index(key)] = Some(now_ms);
+                if let Some(capture) = self.capture.as_mut() {
+                    capture.armed[key_index(key)] = true;
+                }
+            }
+            ButtonEvent::Released(key) => self.down_since[key_index(key)] = None,
+            ButtonEvent::LongPress(_) => {}
+        }
+    }
+
+    /// 物理按键事件(已经过了 `HoldGate`)，翻译出来的逻辑事件放进 `out`
+    pub fn feed(&mut self, event: ButtonEvent, out: &mut Deque<InputEvent, QUEUE_LEN>) {
+        if self.capture.is_some() {
+            return;
+        }
+        let logical = match event {
+            ButtonEvent::Pressed(key) => {
+                let role = self.click_role(key);
+                self.held[key_index(key)] = role;
+                role.map(ButtonEvent::Pressed)
+            }
+            ButtonEvent::LongPress(key) => self.held[key_index(key)].map(ButtonEvent::LongPress),
+            ButtonEvent::Released(key) => {
+                self.held[key_index(key)].take().map(ButtonEvent::Released)
+            }
+        };
+        if let Some(logical) = logical {
+            let _ = out.push_back(InputEvent::Button(logical));
+        }
+    }
+
+    /// 物理按键上识别出来的手势(包括 `HoldGate` 报的按满)，翻译出来的放进 `out`。
+    /// 等按键的时候这就是用户按的那个输入，什么都不放
+    pub fn gesture(&mut self, gesture: Gesture, out: &mut Deque<InputEvent, QUEUE_LEN>) {
+        let (key, trigger) = match gesture {
+            Gesture::Short(key) => (key, Trigger::Click),
+            Gesture::Long(key) => (key, Trigger::Long),
+            Gesture::Double(key) => (key, Trigger::Double),
+            Gesture::Hold(key) => (key, Trigger::Hold),
+        };
+        if let Some(capture) = self.capture {
+            if capture.armed[key_index(key)] {
+                self.capture = None;
+                self.pending = Some(RemapEvent::Captured(
+                    capture.role,
+                    PhysicalInput::new(key, trigger),
+                ));
+            }
+            return;
+        }
+        let input = match self.bindings.role_for(PhysicalInput::new(key, trigger)) {
+            Some(Role::PowerHold) => Some(InputEvent::PowerHold),
+            Some(role) if trigger != Trigger::Click => {
+                // 直接绑在长按、双击上的角色当一次单击
+                if let Some(button) = role.button() {
+                    let _ = out.push_back(InputEvent::Button(ButtonEvent::Pressed(button)));
+                    let _ = out.push_back(InputEvent::Button(ButtonEvent::Released(button)));
+                }
+                None
+            }
+            _ => self.click_role(key).map(|button| {
+                InputEvent::Gesture(match trigger {
+                    Trigger::Click => Gesture::Short(button),
+                    Trigger::Long => Gesture::Long(button),
+                    Trigger::Double => Gesture::Double(button),
+                    Trigger::Hold => Gesture::Hold(button),
+                })
+            }),
+        };
+        if let Some(input) = input {
+            let _ = out.push_back(input);
+        }
+    }
+
+    /// 每一圈调一次：等到的输入、等超时了、有键按满了 `RESET_HOLD_MS`
+    pub fn poll(&mut self, now_ms: u64) -> Option<RemapEvent> {
+        if let Some(event) = self.pending.take() {
+            return Some(event);
+        }
+        for since in self.down_since.iter_mut() {
+            if since.is_some_and(|at| now_ms.saturating_sub(at) >= RESET_HOLD_MS) {
+                *since = None;
+                self.capture = None;
+                return Some(RemapEvent::Reset);
+            }
+        }
+        match self.capture {
+            Some(capture) if now_ms.saturating_sub(capture.since_ms) >= CAPTURE_TIMEOUT_MS => {
+                self.capture = None;
+                Some(RemapEvent::TimedOut(capture.role))
+            }
+            _ => None,
+        }
+    }
+
+    /// 这个键单击当哪个按键用
+    fn click_role(&self, key: Button) -> Option<Button> {
+        self.bindings
+            .role_for(PhysicalInput::new(key, Trigger::Click))
+            .and_then(Role::button)
+    }
+}
+
+/// 键在 `KEYS` 里的位置
+fn key_index(key: Button) -> usize {
+    match key {
+        Button::Up => 0,
+        Button::Down => 1,
+        Button::Select => 2,
+        Button::Back => 3,
+    }
+}
@@ -0,0 +1,131 @@
+//! 按键重映射的设置页面(见 `remap`)
+//!
+//! 从设置菜单的 "inputs" 进来。一行一个角色，右边是现在绑的物理输入(比如 `sel hold`)，最后一行 "reset" 恢复默认。
+//! Up/Down 选，Select 选中一个角色以后屏幕上说现在按哪个(`Action::CaptureInput`)，按了或者超时了主循环用
+//! `Event::InputBindings` 广播回来，这一页回到列表。"reset" 先弹确认框。Back 回去。
+
+use core::convert::Infallible;
+use core::fmt::Write;
+
+use embedded_graphics::geometry::Point;
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::text::Text;
+use embedded_graphics::Drawable;
+use heapless::String;
+
+use crate::app::{Action, Canvas, Confirm, Event, Page, Transition};
+use crate::input::{Button, ButtonEvent};
+use crate::remap::{InputBindings, Role, CAPTURE_TIMEOUT_MS, ROLES};
+use crate::widgets::draw_menu;
+
+/// 标题行基线
+const TITLE_Y: i32 = 8;
+
+/// 菜单从哪一行开始
+const MENU_TOP: i32 = 12;
+
+/// 角色后面还有一行 "reset"
+const ROWS: usize = ROLES.len() + 1;
+
+const RESET: Confirm = Confirm {
+    prompt: "reset inputs?",
+    action: Action::ResetInputBindings,
+};
+
+/// 按键重映射页面
+#[derive(Debug)]
+pub struct RemapPage {
+    bindings: InputBindings,
+    selected: usize,
+    /// 在等用户按键：给哪个角色、什么时候开始的
+    capturing: Option<(Role, u64)>,
+    pending: Option<Action>,
+}
+
+impl RemapPage {
+    pub const fn new(bindings: InputBindings) -> Self {
+        Self {
+            bindings,
+            selected: 0,
+            capturing: None,
+            pending: None,
+        }
+    }
+}
+
+impl Page for RemapPage {
+    fn on_event(&mut self, event: &Event, now_ms: u64) -> Transition {
+        if let Event::InputBindings(bindings) = event {
+            self.bindings = *bindings;
+            self.capturing = None;
+            return Transition::None;
+        }
+        // 等按键的时候主循环不把按键交给页面，这里收不到
+        match event {
+            Event::Button(ButtonEvent::Pressed(Button::Back)) => return Transition::Pop,
+            Event::Button(ButtonEvent::Pressed(Button::Up)) => {
+                self.selected = (self.selected + ROWS - 1) % ROWS;
+            }
+            Event::Button(ButtonEvent::Pressed(Button::Down)) => {
+                self.selected = (self.selected + 1) % ROWS;
+            }
+            Event::Button(ButtonEvent::Pressed(Button::Select)) => match ROLES.get(self.selected) {
+                Some(&role) => {
+                    self.capturing = Some((role, now_ms));
+                    self.pending = Some(Action::CaptureInput(role));
+                }
+                None => return Transition::Confirm(RESET),
+            },
+            Event::Confirmed { request, yes: true } => self.pending = Some(request.action),
+            _ => {}
+        }
+        Transition::None
+    }
+
+    fn take_action(&mut self) -> Option<Action> {
+        self.pending.take()
+    }
+
+    /// 等按键的时候每秒更新一次还剩几秒
+    fn desired_fps(&self) -> u16 {
+        1
+    }
+
+    fn tick(&mut self, _now_ms: u64) -> bool {
+        self.capturing.is_some()
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, now_ms: u64) -> Result<(), Infallible> {
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        if let Some((role, since_ms)) = self.capturing {
+            let left_s = CAPTURE_TIMEOUT_MS.saturating_sub(now_ms.saturating_sub(since_ms)) / 1000;
+            let mut name: String<12> = String::new();
+            for c in role.name().chars() {
+                let _ = name.push(c.to_ascii_uppercase());
+            }
+            let mut line: String<24> = String::new();
+            let _ = write!(line, "for {} now", name);
+            let mut timeout: String<24> = String::new();
+            let _ = write!(timeout, "{}s left", left_s);
+            Text::new("press the input", Point::new(0, TITLE_Y), style).draw(canvas)?;
+            Text::new(&line, Point::new(0, TITLE_Y + 12), style).draw(canvas)?;
+            Text::new(&timeout, Point::new(0, TITLE_Y + 24), style).draw(canvas)?;
+            return Ok(());
+        }
+        Text::new("inputs", Point::new(0, TITLE_Y), style).draw(canvas)?;
+        let values: [String<12>; ROLES.len()] = ROLES.map(|role| {
+            let input = self.bindings.get(role);
+            let mut value = String::new();
+            let _ = write!(value, "{} {}", input.key_name(), input.trigger.name());
+            value
+        });
+        let items = ROLES
+            .iter()
+            .zip(values.iter())
+            .map(|(role, value)| (role.name(), value.as_str()))
+            .chain([("reset", "")]);
+        draw_menu(canvas, MENU_TOP, items, self.selected)
+    }
+}
@@ -0,0 +1,73 @@
+//! 电脑遥控显示：串口命令 `TEXT ...` 发过来的文字、`IMG SHOW` 指定的图片槽显示在这一页
+//!
+//! `TEXT`、`IMG SHOW` 和 `CLEAR` 由 main.rs 广播给这一页(`Event::RemoteText`/`Event::RemoteImage`/`Event::RemoteClear`)，
+//! 不在最上面的话顺便把它打开。文字按屏幕宽度自动换行，放不下的部分不显示。
+//! 图片先画，文字叠在上面；新的 `IMG SHOW` 换掉原来的图，`CLEAR` 把两样都清掉。按 Back 关掉。
+
+use core::convert::Infallible;
+
+use embedded_graphics::geometry::{OriginDimensions, Point};
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use heapless::String;
+
+use crate::app::{Canvas, Event, Page, Transition};
+use crate::console::LINE_CAPACITY;
+use crate::image_slots;
+use crate::input::{Button, ButtonEvent};
+use crate::text::draw_wrapped;
+
+/// 遥控文字最长多少字节，一行命令放得下多少就是多少
+pub type RemoteText = String<LINE_CAPACITY>;
+
+/// 遥控显示页面
+#[derive(Debug, Default)]
+pub struct RemoteTextPage {
+    text: RemoteText,
+    /// 显示哪个图片槽、放在哪
+    image: Option<(usize, Point)>,
+}
+
+impl RemoteTextPage {
+    pub const fn new() -> Self {
+        Self {
+            text: String::new(),
+            image: None,
+        }
+    }
+}
+
+impl Page for RemoteTextPage {
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        match event {
+            Event::RemoteText(text) => self.text = text.clone(),
+            Event::RemoteImage { slot, top_left } => self.image = Some((*slot, *top_left)),
+            Event::RemoteClear => {
+                self.text.clear();
+                self.image = None;
+            }
+            Event::Button(ButtonEvent::Pressed(Button::Back)) => return Transition::Pop,
+            _ => {}
+        }
+        Transition::None
+    }
+
+    /// 内容只在收到命令的时候变，那时候会马上重画
+    fn desired_fps(&self) -> u16 {
+        1
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<(), Infallible> {
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        // 每帧从 flash 读，槽被重新传过的话画的就是新图
+        if let Some((slot, top_left)) = self.image {
+            if let Some(image) = image_slots::load(slot) {
+                image.draw(canvas, top_left)?;
+            }
+        }
+        let width = canvas.size().width;
+        draw_wrapped(canvas, &self.text, Point::zero(), width, style)?;
+        Ok(())
+    }
+}
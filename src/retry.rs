@@ -0,0 +1,39 @@
+//! 失败重试：线比较长、干扰比较大的时候，I2C 偶尔会丢一次 ACK，这种一下子的错误重试一次就好了，不值得死机
+//!
+//! `with_retry` 第一次直接执行，失败了才开始等：第一次等 `base_delay_ms`，之后每次翻倍，
+//! 最多等 `MAX_BACKOFF_MS`，所以就算次数给得多，总的等待时间也有上限。
+//! 全部失败就把最后一次的错误原样还给调用方，记日志、退回无屏模式这些由调用方决定。
+
+use embedded_hal::delay::DelayNs;
+
+/// 两次重试之间最多等多久(毫秒)
+pub const MAX_BACKOFF_MS: u32 = 200;
+
+/// 最多执行 `attempts` 次 `op`(0 也算 1 次)，成功就马上返回
+pub fn with_retry<T, E>(
+    attempts: u8,
+    base_delay_ms: u32,
+    timer: &mut impl DelayNs,
+    mut op: impl FnMut() -> Result<T, E>,
+) -> Result<T, E> {
+    let attempts = attempts.max(1);
+    let mut delay_ms = base_delay_ms.min(MAX_BACKOFF_MS);
+    let mut attempt = 1;
+    loop {
+        match op() {
+            Ok(value) => return Ok(value),
+            Err(err) if attempt >= attempts => return Err(err),
+            Err(_) => {
+                defmt::warn!(
+                    "attempt {}/{} failed, retrying in {} ms",
+                    attempt,
+                    attempts,
+                    delay_ms
+                );
+                timer.delay_ms(delay_ms);
+                delay_ms = delay_ms.saturating_mul(2).min(MAX_BACKOFF_MS);
+                attempt += 1;
+            }
+        }
+    }
+}
@@ -0,0 +1,97 @@
+//! 伪随机数：给星空、雪花点这种动画用
+//!
+//! xorshift32(Marsaglia 2003)：三次移位异或，周期 2^32-1，0 以外的每个数都会出现一次，
+//! 统计上对画动画来说足够均匀了。同一个种子总是得到同一串数，方便复现问题。
+//!
+//! **不是密码学安全的**：看到几个输出就能推出内部状态，不要用来生成密钥、令牌之类的东西。
+//!
+//! 种子由 main.rs 在开机的时候取定时器的低位。上电到这里的时间每次都差一点(晶振起振、I2C 重试、
+//! 传感器探测)，所以每次开机的动画不一样，但这也不是什么真随机。
+
+/// xorshift32 伪随机数发生器
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u32,
+}
+
+impl Rng {
+    /// 种子是 0 的话 xorshift 会一直输出 0，这种情况换成一个固定的非零数
+    pub const fn new(seed: u32) -> Self {
+        Self {
+            state: if seed == 0 { 0x9E37_79B9 } else { seed },
+        }
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        x
+    }
+
+    /// `0..max` 里的一个数，`max` 是 0 的话返回 0
+    ///
+    /// 用乘法取高 32 位而不是取模，偏差不超过 max/2^32
+    pub fn next_range(&mut self, max: u32) -> u32 {
+        ((self.next_u32() as u64 * max as u64) >> 32) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let mut rng = Rng::new(1);
+        // xorshift32 从 1 开始的前三个数
+        assert_eq!(
+            [rng.next_u32(), rng.next_u32(), rng.next_u32()],
+            [270_369, 67_634_689, 2_647_435_461]
+        );
+        let mut a = Rng::new(0xDEAD_BEEF);
+        let mut b = a.clone();
+        assert!((0..1000).all(|_| a.next_u32() == b.next_u32()));
+        assert_ne!(
+            Rng::new(0xDEAD_BEEF).next_u32(),
+            Rng::new(0xDEAD_BEF0).next_u32()
+        );
+    }
+
+    #[test]
+    fn zero_seed_is_replaced() {
+        let mut zero = Rng::new(0);
+        let mut fixed = Rng::new(0x9E37_79B9);
+        assert_eq!(zero.next_u32(), 0x510C_4619);
+        assert_eq!(fixed.next_u32(), 0x510C_4619);
+        // 不会卡在 0 上
+        assert!((0..1000).all(|_| zero.next_u32() != 0));
+    }
+
+    #[test]
+    fn next_range_stays_in_bounds() {
+        let mut rng = Rng::new(42);
+        for max in [1, 2, 3, 7, 100, 1000, u32::MAX] {
+            assert!((0..1000).all(|_| rng.next_range(max) < max), "max {max}");
+        }
+        assert!((0..100).all(|_| rng.next_range(0) == 0));
+        assert!((0..100).all(|_| rng.next_range(1) == 0));
+    }
+
+    /// 粗看一下均匀：分 10 个桶，每个桶都在期望值的 ±10% 以内
+    #[test]
+    fn next_range_is_roughly_uniform() {
+        const SAMPLES: u32 = 100_000;
+        let mut rng = Rng::new(0x1234_5678);
+        let mut buckets = [0u32; 10];
+        for _ in 0..SAMPLES {
+            buckets[rng.next_range(10) as usize] += 1;
+        }
+        let expected = SAMPLES / 10;
+        for (digit, &count) in buckets.iter().enumerate() {
+            assert!(count.abs_diff(expected) < expected / 10, "{digit}: {count}");
+        }
+    }
+}
@@ -0,0 +1,106 @@
+//! 没有晶振的板子：时钟全部跑在片内环形振荡器(ROSC)上(feature `no-xosc`)
+//!
+//! `init_clocks_and_plls` 要先等 12MHz 晶振起振，板子上没焊晶振就一直卡在那里。打开 `no-xosc` 以后 main.rs 改用
+//! 这里的 `init_clocks`：clk_ref、clk_sys、clk_peri、clk_adc 都直接接 ROSC，两个 PLL 不用(PLL 的参考只能是晶振)。
+//!
+//! ## 精度
+//!
+//! ROSC 的频率随芯片、电压、温度变，手册给的范围是默认档 1.8~12MHz，标称 6.5MHz，开机以后也会跟着温度慢慢漂。
+//! 片上的频率计(FC0)量不准它：FC0 是拿 clk_ref 当尺子量别的时钟，手册 2.15.6.2 要求 clk_ref 来自晶振或者
+//! 外部已知频率的时钟，没晶振的板子上 clk_ref 本身就是 ROSC，拿 ROSC 量 ROSC 永远得到设进去的那个数。所以：
+//!
+//! - I2C 分频按这一档**最快可能的频率**(`RoscRange::max_hz`)算，ROSC 实际慢多少总线就慢多少，
+//!   但是不会超过 `I2C_BAUD`，屏幕和传感器都能用。刷一屏要的时间比有晶振的时候长好几倍
+//! - 定时器的 1 微秒节拍是按标称频率分出来的，所以运行时间、床头钟、告警延时、记录间隔这些都会差百分之几到几十，
+//!   跟着温度还会变，不能当钟用
+//! - USB 要准确的 48MHz，ROSC 给不了，所以这个模式下 USB 不编译(没有串口命令行，也没有电脑副屏)
+//! - 蜂鸣器的音调、WS2812 的时序也跟着偏，灯带大概率不能用
+//!
+//! `RANGE` 可以把 ROSC 调快一档(少几级反相器)，CPU 快一点，精度不会变好。
+
+use rp2040_hal::clocks::{ClockError, ClocksManager};
+use rp2040_hal::fugit::{HertzU32, RateExtU32};
+use rp2040_hal::pac;
+use rp2040_hal::rosc::RingOscillator;
+use rp2040_hal::watchdog::Watchdog;
+use rp2040_hal::Clock;
+
+/// 没晶振的时候屏幕 I2C 最快多少，按最快的 ROSC 算分频，实际只会更慢
+pub const I2C_BAUD: HertzU32 = HertzU32::kHz(100);
+
+/// ROSC 的频率档，反相器级数越少越快
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum RoscRange {
+    /// 8 级，上电默认
+    Low,
+    /// 6 级
+    Medium,
+    /// 4 级
+    High,
+}
+
+/// 用哪一档。TOOHIGH(2 级)手册说不要用，这里没有
+pub const RANGE: RoscRange = RoscRange::Medium;
+
+impl RoscRange {
+    fn stages(self) -> u32 {
+        match self {
+            RoscRange::Low => 8,
+            RoscRange::Medium => 6,
+            RoscRange::High => 4,
+        }
+    }
+
+    /// 标称频率：默认档 6.5MHz，频率大致和级数成反比
+    pub fn nominal_hz(self) -> u32 {
+        6_500_000 * 8 / self.stages()
+    }
+
+    /// 最快可能多快：默认档手册上限 12MHz，同样按级数换算
+    pub fn max_hz(self) -> u32 {
+        12_000_000 * 8 / self.stages()
+    }
+
+    /// 算 I2C 分频的时候当作系统时钟的频率
+    pub fn max_freq(self) -> HertzU32 {
+        self.max_hz().Hz()
+    }
+}
+
+/// 把所有时钟切到 ROSC 上，调到 `RANGE` 这一档，定时器节拍按标称频率分
+pub fn init_clocks(
+    rosc: pac::ROSC,
+    clocks: pac::CLOCKS,
+    watchdog: &mut Watchdog,
+) -> Result<ClocksManager, ClockError> {
+    let rosc = RingOscillator::new(rosc).initialize_with_freq(RANGE.nominal_hz().Hz());
+    set_range(RANGE);
+
+    // 定时器的节拍从 clk_ref 分出来，每几个周期算一微秒
+    let ref_mhz = (RANGE.nominal_hz() + 500_000) / 1_000_000;
+    watchdog.enable_tick_generation(ref_mhz as u8);
+
+    let mut clocks = ClocksManager::new(clocks);
+    let freq = rosc.operating_frequency();
+    clocks.reference_clock.configure_clock(&rosc, freq)?;
+    clocks.system_clock.configure_clock(&rosc, freq)?;
+    clocks.adc_clock.configure_clock(&rosc, freq)?;
+    clocks
+        .peripheral_clock
+        .configure_clock(&clocks.system_clock, clocks.system_clock.freq())?;
+    Ok(clocks)
+}
+
+/// 一档一档往上调，手册建议不要一下跳好几档
+fn set_range(range: RoscRange) {
+    // 安全性：`RingOscillator` 只在 `initialize`/`disable` 的时候写 CTRL，之后不会再碰
+    let regs = unsafe { &*pac::ROSC::ptr() };
+    let steps = [RoscRange::Medium, RoscRange::High];
+    for step in steps.iter().filter(|step| step.stages() >= range.stages()) {
+        regs.ctrl().modify(|_, w| match step {
+            RoscRange::Low => w.freq_range().low(),
+            RoscRange::Medium => w.freq_range().medium(),
+            RoscRange::High => w.freq_range().high(),
+        });
+    }
+}
@@ -0,0 +1,93 @@
+//! 传感器采样：RP2040 片内温度 + 一路 ADC
+//!
+//! 片内温度传感器接在 ADC 的 4 号通道，手册给的换算公式是
+//! `T = 27 - (V - 0.706) / 0.001721`，这里全部用整数算，精度到 0.01°C(实际误差有好几度，看趋势够用)。
+//! 外部 ADC 引脚接在哪见 board.rs 的 `sampler_adc_pin!`，VSYS 见 `vsys_adc_pin!`，I2C 线诊断的分压点见 `scl_tap_adc_pin!`。
+//!
+//! 每个读数方法对应一个传感器通道，在 main.rs 里注册到 `sensors::SensorRegistry`，
+//! ADC 转换失败的时候返回 None，连续失败几次注册表会把这个通道标成不可用。
+
+use embedded_hal_0_2::adc::{Channel, OneShot};
+use rp2040_hal::adc::{Adc, TempSense};
+
+pub use rp2040_hal::adc::AdcPin;
+
+/// ADC 参考电压(mV)，Pico 上就是 3.3V
+const VREF_MV: i32 = 3300;
+
+/// ADC 是 12 位的
+const ADC_FULL_SCALE: i32 = 4096;
+
+/// Pico 上 VSYS 经过 3:1 分压才接到 ADC
+const VSYS_DIVIDER: u32 = 3;
+
+/// 温度传感器的原始读数换算成 0.01°C
+pub fn temp_centi_from_raw(raw: u16) -> i16 {
+    let microvolts = raw as i64 * VREF_MV as i64 * 1000 / ADC_FULL_SCALE as i64;
+    (2700 - (microvolts - 706_000) * 100 / 1721) as i16
+}
+
+/// ADC 原始读数换算成引脚上的电压(mV)
+pub fn millivolts_from_raw(raw: u16) -> u16 {
+    (raw as u32 * VREF_MV as u32 / ADC_FULL_SCALE as u32) as u16
+}
+
+/// VSYS 引脚的原始读数换算成 VSYS 电压(mV)
+pub fn vsys_mv_from_raw(raw: u16) -> u16 {
+    (millivolts_from_raw(raw) as u32 * VSYS_DIVIDER) as u16
+}
+
+/// 采样器能用的 ADC 引脚：一次读一个数(`OneShot`)和连续转换(见 mic.rs)都要用到
+pub trait SamplerPin: Channel<Adc, ID = u8> + rp2040_hal::adc::AdcChannel {}
+
+impl<T> SamplerPin for T where T: Channel<Adc, ID = u8> + rp2040_hal::adc::AdcChannel {}
+
+/// 采样器：拿着 ADC、温度传感器、外部引脚和 VSYS 引脚
+pub struct Sampler<P, V> {
+    adc: Adc,
+    temp: TempSense,
+    pin: P,
+    vsys: V,
+}
+
+impl<P, V> Sampler<P, V>
+where
+    P: SamplerPin,
+    V: SamplerPin,
+{
+    /// 温度传感器在这里打开，之后一直开着(只多几十微安)
+    pub fn new(mut adc: Adc, pin: P, vsys: V) -> Self {
+        // 温度传感器只能打开一次，这里是唯一打开它的地方
+        let temp = adc.take_temp_sensor().unwrap();
+        Self {
+            adc,
+            temp,
+            pin,
+            vsys,
+        }
+    }
+
+    /// 片内温度，单位 0.01°C
+    pub fn temp_centi(&mut self) -> Option<i32> {
+        let raw: u16 = self.adc.read(&mut self.temp).ok()?;
+        Some(temp_centi_from_raw(raw) as i32)
+    }
+
+    /// 外部 ADC 引脚电压(mV)
+    pub fn adc0_mv(&mut self) -> Option<i32> {
+        let raw: u16 = self.adc.read(&mut self.pin).ok()?;
+        Some(millivolts_from_raw(raw) as i32)
+    }
+
+    /// VSYS 电压(mV)，USB 供电的时候差不多是 5V 减去二极管压降
+    pub fn vsys_mv(&mut self) -> Option<i32> {
+        let raw: u16 = self.adc.read(&mut self.vsys).ok()?;
+        Some(vsys_mv_from_raw(raw) as i32)
+    }
+
+    /// 顺便读一个不归采样器管的 ADC 引脚(mV)，比如 I2C 线诊断的 SCL 分压点(见 i2c_lines.rs)
+    pub fn read_mv<T: SamplerPin>(&mut self, pin: &mut T) -> Option<u16> {
+        let raw: u16 = self.adc.read(pin).ok()?;
+        Some(millivolts_from_raw(raw))
+    }
+}
@@ -0,0 +1,153 @@
+//! `screen!`：少写点样板代码定义一个演示页面
+//!
+//! 演示页面大多长一个样：一个结构体装状态，`step` 里往前走一步，`render` 里画出来，Back 回去。
+//! 每加一个都要把 `Page` 的几个方法从头抄一遍，这个宏把这些生成出来，只写不一样的部分：
+//!
+//! ```ignore
+//! screen! {
+//!     /// 生命游戏
+//!     pub screen LifePage: LifeBoard {
+//!         name: "life",
+//!         fps: 8,
+//!         event(board, event, _now_ms) {
+//!             if let Event::Button(ButtonEvent::Pressed(Button::Select)) = event {
+//!                 board.randomize();
+//!             }
+//!             back_pops(event)
+//!         }
+//!         step(board, tick) {
+//!             // 每 6 个逻辑 tick(120ms)走一代
+//!             tick % 6 == 0 && board.step()
+//!         }
+//!         render(board, canvas, _now_ms) {
+//!             board.draw(canvas)
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! 生成的是一个结构体 `LifePage { pub state: LifeBoard }`、`LifePage::new(state)`、`LifePage::NAME`
+//! 和 `Page` 的实现。每一段的几个名字是那一段里能用的变量，由自己起(不用的前面加 `_`)：
+//! 状态(`&mut` 状态类型)、事件(`&Event`)、画布(`&mut Canvas`)、开机以来的毫秒数、逻辑 tick 的编号(`u32`)。
+//!
+//! - `name`：必须有。perf 表和演示菜单里用的名字，`NAME` 常量，main.rs 登记的时候写 `LifePage::NAME`
+//! - `fps`：可以不写，默认 `DEFAULT_FPS`
+//! - `event`：可以不写，默认 Back 回去(`back_pops`)，其他按键不管。要返回 `Transition`
+//! - `update`：可以不写，默认不重画。就是 `Page::tick`，每帧一次，返回要不要重画
+//! - `step`：可以不写。就是 `Page::step`，按固定的节奏走(见 `timestep`)，动画往前走放这里，返回要不要重画
+//! - `render`：必须有。画之前画布已经清空了，返回 `Result<(), Infallible>`
+//!
+//! 几段的顺序是固定的，就是上面的顺序。写错了会报一条写法说明，不会是一大片看不懂的宏展开错误。
+//! 只用到了 core 和本 crate 的东西，`no_std` 里能用。
+//!
+//! 页面编号还是 main.rs 里页面数组的下标，新页面要像别的页面一样加到数组和名字表的最后面(见 `app`)。
+//! 要用到 `has_data`、`take_action` 这些的复杂页面还是直接实现 `Page`。
+
+use crate::app::{Event, Transition};
+use crate::input::{Button, ButtonEvent};
+
+/// 按了 Back 就回去，其他事件不管。`screen!` 默认的事件处理，自己写 `event` 的时候最后也可以交给它
+pub fn back_pops(event: &Event) -> Transition {
+    match event {
+        Event::Button(ButtonEvent::Pressed(Button::Back)) => Transition::Pop,
+        _ => Transition::None,
+    }
+}
+
+/// 定义一个演示页面，写法见 screen.rs 的模块文档
+#[macro_export]
+macro_rules! screen {
+    (
+        $(#[$meta:meta])*
+        $vis:vis screen $name:ident : $state:ty {
+            name: $label:literal,
+            $(fps: $fps:expr,)?
+            $(event($event_state:ident, $event:ident, $event_now:ident) $event_body:block)?
+            $(update($update_state:ident, $update_now:ident) $update_body:block)?
+            $(step($step_state:ident, $step_tick:ident) $step_body:block)?
+            render($render_state:ident, $canvas:ident, $render_now:ident) $render_body:block
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            pub state: $state,
+        }
+
+        impl $name {
+            /// perf 表和演示菜单里的名字
+            pub const NAME: &'static str = $label;
+
+            pub const fn new(state: $state) -> Self {
+                Self { state }
+            }
+        }
+
+        impl $crate::app::Page for $name {
+            $crate::screen!(@event $($event_state, $event, $event_now, $event_body)?);
+
+            $(
+                fn tick(&mut self, now_ms: u64) -> bool {
+                    let $update_state = &mut self.state;
+                    let $update_now = now_ms;
+                    $update_body
+                }
+            )?
+
+            $(
+                fn step(&mut self, tick: u32) -> bool {
+                    let $step_state = &mut self.state;
+                    let $step_tick = tick;
+                    $step_body
+                }
+            )?
+
+            $(
+                fn desired_fps(&self) -> u16 {
+                    $fps
+                }
+            )?
+
+            fn render(
+                &mut self,
+                canvas: &mut $crate::app::Canvas,
+                now_ms: u64,
+            ) -> Result<(), core::convert::Infallible> {
+                let $render_state = &mut self.state;
+                let $canvas = canvas;
+                let $render_now = now_ms;
+                $render_body
+            }
+        }
+    };
+
+    (@event) => {
+        fn on_event(
+            &mut self,
+            event: &$crate::app::Event,
+            _now_ms: u64,
+        ) -> $crate::app::Transition {
+            $crate::screen::back_pops(event)
+        }
+    };
+
+    (@event $event_state:ident, $event:ident, $event_now:ident, $event_body:block) => {
+        fn on_event(
+            &mut self,
+            event: &$crate::app::Event,
+            now_ms: u64,
+        ) -> $crate::app::Transition {
+            let $event_state = &mut self.state;
+            let $event = event;
+            let $event_now = now_ms;
+            $event_body
+        }
+    };
+
+    ($($rest:tt)*) => {
+        core::compile_error!(
+            "screen! 的写法：screen! { pub screen 名字: 状态类型 { name: \"名字\", [fps: 帧率,] \
+             [event(状态, 事件, 毫秒) { .. }] [update(状态, 毫秒) { .. }] [step(状态, tick) { .. }] render(状态, 画布, 毫秒) { .. } } }，\
+             几段按这个顺序，方括号里的可以不写(见 screen.rs)"
+        );
+    };
+}
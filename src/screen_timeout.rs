@@ -0,0 +1,54 @@
+//! 没人操作一段时间自动关屏
+//!
+//! `SETTINGS SCREENOFF <分钟>` 打开，默认关着。关屏只是让面板不亮(SSD1306 的 display off)，
+//! 主循环、USB、传感器、告警都照常跑，只是不再画图。按任意键亮屏，这一下按键不交给页面；
+//! 告警升级也会亮屏。接了 VL53L0X 的话还可以手靠近亮屏(`SETTINGS WAKE <mm>`，见 main.rs)。
+
+/// 关屏计时
+#[derive(Debug, Clone, Copy)]
+pub struct ScreenTimeout {
+    /// 多久没操作关屏(毫秒)，None 是不关
+    timeout_ms: Option<u64>,
+    last_activity_ms: u64,
+    asleep: bool,
+}
+
+impl ScreenTimeout {
+    /// `minutes` 是 0 的话不关屏
+    pub fn new(minutes: u16, now_ms: u64) -> Self {
+        Self {
+            timeout_ms: (minutes > 0).then_some(minutes as u64 * 60_000),
+            last_activity_ms: now_ms,
+            asleep: false,
+        }
+    }
+
+    /// 换时间，从现在重新计时。正关着屏的话还是关着，等下一次操作
+    pub fn set_minutes(&mut self, minutes: u16, now_ms: u64) {
+        let asleep = self.asleep;
+        *self = Self::new(minutes, now_ms);
+        self.asleep = asleep;
+    }
+
+    pub fn is_asleep(&self) -> bool {
+        self.asleep
+    }
+
+    /// 有人操作了。返回 true 表示本来关着屏，调用方要把屏打开、整屏重画
+    pub fn activity(&mut self, now_ms: u64) -> bool {
+        self.last_activity_ms = now_ms;
+        core::mem::replace(&mut self.asleep, false)
+    }
+
+    /// 返回 true 表示刚到时间，调用方要关屏
+    pub fn update(&mut self, now_ms: u64) -> bool {
+        let Some(timeout_ms) = self.timeout_ms else {
+            return false;
+        };
+        if self.asleep || now_ms < self.last_activity_ms + timeout_ms {
+            return false;
+        }
+        self.asleep = true;
+        true
+    }
+}
@@ -0,0 +1,312 @@
+//! 传感器通道注册表：所有传感器在这里登记一次，仪表盘、遥测、数据记录、告警都遍历这张表，不再各自点名
+//!
+//! 一个通道有名字、单位、换算比例和一个读数函数。读数统一是整数，`scale` 说明多少算一个单位
+//! (比如温度读数是 0.01°C，就是单位 "C"、比例 100)，显示的时候按比例加小数点。
+//!
+//! - 主循环每一圈调 `SensorRegistry::poll`，每个通道按自己的间隔读，读到的值和最近 `HISTORY_LEN` 个历史值都存在表里。
+//!   历史默认每读一次记一个，变得慢的量(比如气压)可以用 `set_history_interval` 隔久一点才记，让历史盖住更长的时间
+//! - 读数函数返回 None 算一次失败，连续失败 `MAX_FAILURES` 次就标成不可用，屏幕上显示 "--"，
+//!   之后再读成功一次就恢复
+//! - 每个通道可以挂一个线性修正(`set_correction`，板子的校准见 calibration.rs)，`value`、历史拿到的都是修正过的，
+//!   `raw_value` 是修正之前的
+//! - 通道编号就是注册的顺序。告警规则、遥测的列都是按编号存的，所以新传感器只能往后加，不要插到中间
+//!
+//! 读数函数的参数 `S` 是拿着硬件的那个东西(比如 `Sampler`)，由主循环在 `poll` 的时候借给注册表。
+//! 加一个传感器只要在 main.rs 里多注册一次。
+
+use core::fmt::{self, Write};
+
+use heapless::{HistoryBuffer, Vec};
+
+/// 最多几个通道
+pub const MAX_CHANNELS: usize = 14;
+
+/// 每个通道存多少个历史读数
+pub const HISTORY_LEN: usize = 32;
+
+/// 连续失败几次算不可用
+pub const MAX_FAILURES: u8 = 3;
+
+/// 通道编号，就是注册的顺序
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct ChannelId(pub u8);
+
+/// 通道的描述
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelInfo {
+    /// 命令、表头、屏幕上用的名字，比如 "temp"
+    pub name: &'static str,
+    /// 单位，比如 "C"、"V"
+    pub unit: &'static str,
+    /// 读数里多少算一个单位，必须是 10 的幂(1、10、100、1000...)
+    pub scale: u16,
+}
+
+impl ChannelInfo {
+    pub const fn new(name: &'static str, unit: &'static str, scale: u16) -> Self {
+        Self { name, unit, scale }
+    }
+
+    /// 小数点后几位，就是 `scale` 后面有几个 0
+    fn decimals(&self) -> usize {
+        let mut scale = self.scale.max(1);
+        let mut decimals = 0;
+        while scale >= 10 {
+            scale /= 10;
+            decimals += 1;
+        }
+        decimals
+    }
+
+    /// 按比例写读数，不带单位，比如 2345 -> "23.45"
+    pub fn write_number<W: Write>(&self, value: i32, out: &mut W) -> fmt::Result {
+        let sign = if value < 0 { "-" } else { "" };
+        let abs = value.unsigned_abs();
+        let scale = self.scale.max(1) as u32;
+        match self.decimals() {
+            0 => write!(out, "{}{}", sign, abs),
+            width => write!(out, "{}{}.{:0width$}", sign, abs / scale, abs % scale),
+        }
+    }
+
+    /// 带单位写读数，读不到(None)写 "--"
+    pub fn write_value<W: Write>(&self, value: Option<i32>, out: &mut W) -> fmt::Result {
+        match value {
+            Some(value) => {
+                self.write_number(value, out)?;
+                out.write_str(self.unit)
+            }
+            None => out.write_str("--"),
+        }
+    }
+}
+
+/// 编号对不上任何注册过的通道(比如导入了别的板子的设置)的时候用这个描述
+pub const UNKNOWN_CHANNEL: ChannelInfo = ChannelInfo::new("?", "", 1);
+
+/// 修正比例的 1 倍
+pub const GAIN_ONE: i32 = 10_000;
+
+/// 读数的线性修正：`读数 * gain / GAIN_ONE + offset`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Correction {
+    pub gain: i32,
+    pub offset: i32,
+}
+
+impl Correction {
+    /// 不修正
+    pub const IDENTITY: Self = Self {
+        gain: GAIN_ONE,
+        offset: 0,
+    };
+
+    pub const fn offset(offset: i32) -> Self {
+        Self {
+            gain: GAIN_ONE,
+            offset,
+        }
+    }
+
+    pub const fn gain(gain: i32) -> Self {
+        Self { gain, offset: 0 }
+    }
+
+    /// 修正一个读数，乘的时候四舍五入
+    pub fn apply(&self, raw: i32) -> i32 {
+        let scaled = raw as i64 * self.gain as i64;
+        let half = (GAIN_ONE / 2) as i64 * scaled.signum();
+        ((scaled + half) / GAIN_ONE as i64 + self.offset as i64)
+            .clamp(i32::MIN as i64, i32::MAX as i64) as i32
+    }
+}
+
+/// 读数函数：读一次，失败返回 None
+pub type PollFn<S> = fn(&mut S) -> Option<i32>;
+
+/// 一个注册过的通道
+pub struct SensorChannel<S> {
+    info: ChannelInfo,
+    interval_ms: u32,
+    poll: PollFn<S>,
+    correction: Correction,
+    next_ms: u64,
+    /// 隔多久记一个历史值，0 表示每次读到都记
+    history_interval_ms: u32,
+    next_history_ms: u64,
+    /// 连续失败了几次
+    failures: u8,
+    latest: Option<i32>,
+    /// 修正之前的最新读数
+    latest_raw: Option<i32>,
+    history: HistoryBuffer<i32, HISTORY_LEN>,
+}
+
+impl<S> SensorChannel<S> {
+    pub fn info(&self) -> &ChannelInfo {
+        &self.info
+    }
+
+    /// 最近没有连续失败太多次
+    pub fn is_available(&self) -> bool {
+        self.failures < MAX_FAILURES
+    }
+
+    /// 最新读数，不可用或者还没读过返回 None
+    pub fn value(&self) -> Option<i32> {
+        self.latest.filter(|_| self.is_available())
+    }
+
+    /// 修正之前的最新读数，诊断页面对照用
+    pub fn raw_value(&self) -> Option<i32> {
+        self.latest_raw.filter(|_| self.is_available())
+    }
+
+    /// 历史读数，从旧到新(失败的那几次不算)
+    pub fn history(&self) -> impl Iterator<Item = i32> + '_ {
+        self.history.oldest_ordered().copied()
+    }
+
+    /// 到时间了就读一次，返回读数或者可用状态有没有变
+    fn poll(&mut self, sensors: &mut S, now_ms: u64) -> bool {
+        if now_ms < self.next_ms {
+            return false;
+        }
+        self.next_ms = now_ms + self.interval_ms as u64;
+        let before = self.value();
+        match (self.poll)(sensors) {
+            Some(raw) => {
+                let value = self.correction.apply(raw);
+                self.failures = 0;
+                self.latest_raw = Some(raw);
+                self.latest = Some(value);
+                if now_ms >= self.next_history_ms {
+                    self.next_history_ms = now_ms + self.history_interval_ms as u64;
+                    self.history.write(value);
+                }
+            }
+            None => {
+                if self.failures == MAX_FAILURES - 1 {
+                    defmt::warn!("sensor {=str} unavailable", self.info.name);
+                }
+                self.failures = self.failures.saturating_add(1);
+            }
+        }
+        self.value() != before
+    }
+}
+
+/// 通道注册表
+pub struct SensorRegistry<S> {
+    channels: Vec<SensorChannel<S>, MAX_CHANNELS>,
+    /// 每有一次读数变化加一，页面用它判断要不要重画
+    generation: u32,
+}
+
+impl<S> Default for SensorRegistry<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> SensorRegistry<S> {
+    pub const fn new() -> Self {
+        Self {
+            channels: Vec::new(),
+            generation: 0,
+        }
+    }
+
+    /// 注册一个通道，每 `interval_ms` 毫秒用 `poll` 读一次。满了返回 None
+    pub fn register(
+        &mut self,
+        info: ChannelInfo,
+        interval_ms: u32,
+        poll: PollFn<S>,
+    ) -> Option<ChannelId> {
+        let id = ChannelId(self.channels.len() as u8);
+        self.channels
+            .push(SensorChannel {
+                info,
+                interval_ms,
+                poll,
+                correction: Correction::IDENTITY,
+                next_ms: 0,
+                history_interval_ms: 0,
+                next_history_ms: 0,
+                failures: 0,
+                latest: None,
+                latest_raw: None,
+                history: HistoryBuffer::new(),
+            })
+            .ok()?;
+        Some(id)
+    }
+
+    /// 这个通道隔 `interval_ms` 毫秒才记一个历史值，最新读数还是照常更新
+    pub fn set_history_interval(&mut self, id: ChannelId, interval_ms: u32) {
+        if let Some(channel) = self.channels.get_mut(id.0 as usize) {
+            channel.history_interval_ms = interval_ms;
+        }
+    }
+
+    /// 这个通道之后的读数按 `correction` 修正。最新读数马上按新的修正重算，历史不动
+    pub fn set_correction(&mut self, id: ChannelId, correction: Correction) {
+        if let Some(channel) = self.channels.get_mut(id.0 as usize) {
+            channel.correction = correction;
+            channel.latest = channel.latest_raw.map(|raw| correction.apply(raw));
+            self.generation = self.generation.wrapping_add(1);
+        }
+    }
+
+    /// 换这个通道的单位(比如脉冲计数改了设置)。已经拷走描述的页面要重启才跟着换
+    pub fn set_unit(&mut self, id: ChannelId, unit: &'static str) {
+        if let Some(channel) = self.channels.get_mut(id.0 as usize) {
+            channel.info.unit = unit;
+            self.generation = self.generation.wrapping_add(1);
+        }
+    }
+
+    /// 读所有到时间的通道，返回有没有读数变了
+    pub fn poll(&mut self, sensors: &mut S, now_ms: u64) -> bool {
+        let mut changed = false;
+        for channel in &mut self.channels {
+            changed |= channel.poll(sensors, now_ms);
+        }
+        if changed {
+            self.generation = self.generation.wrapping_add(1);
+        }
+        changed
+    }
+
+    /// 读数变化的计数，和上次看到的不一样就说明有新读数了
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    pub fn channels(&self) -> &[SensorChannel<S>] {
+        &self.channels
+    }
+
+    pub fn get(&self, id: ChannelId) -> Option<&SensorChannel<S>> {
+        self.channels.get(id.0 as usize)
+    }
+
+    /// 某个通道的最新读数，没有这个通道或者不可用返回 None
+    pub fn value(&self, id: ChannelId) -> Option<i32> {
+        self.get(id).and_then(SensorChannel::value)
+    }
+
+    /// 按名字找通道，不区分大小写
+    pub fn find(&self, name: &str) -> Option<ChannelId> {
+        self.channels
+            .iter()
+            .position(|channel| channel.info.name.eq_ignore_ascii_case(name))
+            .map(|index| ChannelId(index as u8))
+    }
+
+    /// 所有通道的描述，给构造时就要知道有哪些通道的页面用
+    pub fn infos(&self) -> Vec<ChannelInfo, MAX_CHANNELS> {
+        self.channels.iter().map(|channel| channel.info).collect()
+    }
+}
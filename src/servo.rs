@@ -0,0 +1,151 @@
+//! 舵机输出：50Hz 的 PWM，脉宽 `MIN_PULSE_US`~`MAX_PULSE_US`
+//!
+//! 普通舵机每 20ms 要一个脉冲，脉宽 1000µs 到 2000µs 对应转到一头到另一头(一般是 0°~180°)，
+//! 没有脉冲舵机就不出力，可以用手掰动。
+//!
+//! 分频的目标是一个计数正好 1µs，这样 TOP 是 19999、比较值就是脉宽本身。125MHz、124MHz(16MHz 晶振)、
+//! 133MHz 这些整数 MHz 的系统时钟都能正好凑出来；凑不整的(比如没有晶振的时候跑在 ROSC 上)分频取最接近的，
+//! TOP 和比较值再按实际的计数频率四舍五入，所以不管系统时钟是多少，脉宽误差都不超过半个计数(一般不到 0.5µs)。
+//!
+//! 比较值是双缓冲的，计数器回绕的时候才生效，改脉宽不会出半截的脉冲。
+//! 舵机接在哪个引脚、用哪个 PWM slice 见 board.rs 的 `servo_pwm!`。
+
+use embedded_hal::pwm::SetDutyCycle;
+use rp2040_hal::pwm::{FreeRunning, Slice, SliceId, ValidSliceMode};
+
+/// 脉宽范围(µs)
+pub const MIN_PULSE_US: u16 = 1000;
+pub const MAX_PULSE_US: u16 = 2000;
+
+/// 中间位置
+pub const CENTER_PULSE_US: u16 = 1500;
+
+/// 一个周期多长(µs)，50Hz
+pub const PERIOD_US: u32 = 20_000;
+
+/// 分频寄存器是 8.4 定点数，最大 255 + 15/16
+const MAX_DIV_16THS: u64 = 255 * 16 + 15;
+
+/// 按系统时钟算出来的分频、TOP
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ServoTiming {
+    /// 系统时钟乘 16，分频以 1/16 为单位，一起算省得到处乘
+    clock_16ths: u64,
+    div_16ths: u64,
+    top: u16,
+}
+
+impl ServoTiming {
+    pub const fn new(system_clock_hz: u32) -> Self {
+        let clock_16ths = system_clock_hz as u64 * 16;
+        // 一个计数 1µs 要的分频，四舍五入到 1/16
+        let mut div_16ths = (clock_16ths + 500_000) / 1_000_000;
+        if div_16ths < 16 {
+            div_16ths = 16;
+        } else if div_16ths > MAX_DIV_16THS {
+            div_16ths = MAX_DIV_16THS;
+        }
+        let mut timing = Self {
+            clock_16ths,
+            div_16ths,
+            top: 0,
+        };
+        let period = timing.ticks(PERIOD_US);
+        timing.top = if period < 2 {
+            1
+        } else if period > 65536 {
+            u16::MAX
+        } else {
+            (period - 1) as u16
+        };
+        timing
+    }
+
+    /// `us` 微秒是多少个计数，四舍五入
+    const fn ticks(&self, us: u32) -> u64 {
+        let per_second = self.div_16ths * 1_000_000;
+        (self.clock_16ths * us as u64 + per_second / 2) / per_second
+    }
+
+    pub const fn div_int(&self) -> u8 {
+        (self.div_16ths >> 4) as u8
+    }
+
+    pub const fn div_frac(&self) -> u8 {
+        (self.div_16ths & 0xF) as u8
+    }
+
+    pub const fn top(&self) -> u16 {
+        self.top
+    }
+
+    /// 脉宽 `pulse_us` 对应的比较值
+    pub const fn compare(&self, pulse_us: u16) -> u16 {
+        let ticks = self.ticks(pulse_us as u32);
+        let limit = self.top as u64 + 1;
+        if ticks > limit {
+            limit as u16
+        } else {
+            ticks as u16
+        }
+    }
+}
+
+/// 一路舵机输出，用 slice 的 A 通道
+pub struct ServoOutput<S>
+where
+    S: SliceId,
+    FreeRunning: ValidSliceMode<S>,
+{
+    slice: Slice<S, FreeRunning>,
+    timing: ServoTiming,
+    pulse_us: Option<u16>,
+}
+
+impl<S> ServoOutput<S>
+where
+    S: SliceId,
+    FreeRunning: ValidSliceMode<S>,
+{
+    /// `system_clock_hz` 用来算分频，要传实际的系统时钟。一开始没有脉冲
+    pub fn new(mut slice: Slice<S, FreeRunning>, system_clock_hz: u32) -> Self {
+        let timing = ServoTiming::new(system_clock_hz);
+        slice.disable();
+        slice.set_div_int(timing.div_int());
+        slice.set_div_frac(timing.div_frac());
+        slice.set_top(timing.top());
+        slice.set_counter(0);
+        let _ = slice.channel_a.set_duty_cycle(0);
+        slice.enable();
+        Self {
+            slice,
+            timing,
+            pulse_us: None,
+        }
+    }
+
+    /// 设脉宽，超出范围的按两头算。None 是不出脉冲(输出一直是低电平)，下一个周期生效
+    pub fn set_pulse(&mut self, pulse_us: Option<u16>) {
+        let pulse_us = pulse_us.map(|us| us.clamp(MIN_PULSE_US, MAX_PULSE_US));
+        let compare = pulse_us.map_or(0, |us| self.timing.compare(us));
+        let _ = self.slice.channel_a.set_duty_cycle(compare);
+        self.pulse_us = pulse_us;
+    }
+
+    /// 现在的脉宽，没有脉冲是 None
+    pub fn pulse_us(&self) -> Option<u16> {
+        self.pulse_us
+    }
+}
+
+/// 脉宽对应的角度(度)，`MIN_PULSE_US` 是 0°，`MAX_PULSE_US` 是 180°
+pub const fn pulse_to_degrees(pulse_us: u16) -> u16 {
+    let pulse_us = if pulse_us < MIN_PULSE_US {
+        MIN_PULSE_US
+    } else if pulse_us > MAX_PULSE_US {
+        MAX_PULSE_US
+    } else {
+        pulse_us
+    };
+    ((pulse_us - MIN_PULSE_US) as u32 * 180 / (MAX_PULSE_US - MIN_PULSE_US) as u32) as u16
+}
@@ -0,0 +1,254 @@
+//! 舵机测试页面：调脉宽、显示角度、自动来回扫
+//!
+//! 板子上没有旋钮，Up/Down 当旋钮用，按一下脉宽加减一格：细调一格 `FINE_STEP_US`，粗调一格 `COARSE_STEP_US`。
+//!
+//! - Select 短按：粗调/细调切换
+//! - Select 双击：扫描模式开关，脉宽在两头之间来回走，每毫秒走 `SWEEP_US_PER_MS`，从一头到另一头一秒
+//! - Select 长按：急停，马上不出脉冲；停着的时候长按 Select 按现在的脉宽重新输出
+//! - Back：停掉输出回去
+//!
+//! 进来的时候是停着的，要长按一下 Select 舵机才会动，免得一进页面舵机就猛转一下。停着的时候也能先把脉宽调好。
+//! 双击 Back 直接回演示菜单的时候这一页收不到按键，舵机停在最后的位置，要停的话回来长按 Select。
+//!
+//! 屏幕上是脉宽(七段大数字，µs)、对应的角度、现在的状态和粗细，下面一根条是脉宽在 1000~2000µs 里的位置。
+
+use core::convert::Infallible;
+use core::fmt::Write as _;
+
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::text::Text;
+use embedded_graphics::Drawable;
+use heapless::String;
+use rp2040_hal::pwm::{FreeRunning, SliceId, ValidSliceMode};
+
+use crate::app::{Canvas, Event, Page, Transition};
+use crate::gesture::Gesture;
+use crate::input::{Button, ButtonEvent};
+use crate::servo::{pulse_to_degrees, ServoOutput, CENTER_PULSE_US, MAX_PULSE_US, MIN_PULSE_US};
+use crate::text::draw_centered;
+use crate::theme;
+use crate::widgets::{draw_big_digit, draw_progress_bar_styled};
+
+/// 细调、粗调一格多少 µs
+pub const FINE_STEP_US: u16 = 5;
+pub const COARSE_STEP_US: u16 = 50;
+
+/// 扫描的速度：每毫秒走多少 µs
+const SWEEP_US_PER_MS: u64 = 1;
+
+/// 两次 tick 隔太久(比如上面盖着弹窗)只按这么久算，不然一下跳到另一头
+const MAX_SWEEP_STEP_MS: u64 = 100;
+
+/// 扫描的时候每秒几帧
+const SWEEP_FPS: u16 = 20;
+
+/// 标题行基线
+const TITLE_Y: i32 = 8;
+
+/// 大数字：四位，每位 20x30，笔画 3 像素，和距离页面一样
+const DIGIT_TOP: i32 = 12;
+const DIGIT_WIDTH: u32 = 20;
+const DIGIT_HEIGHT: u32 = 30;
+const DIGIT_STROKE: i32 = 3;
+const DIGIT_PITCH: i32 = 24;
+const DIGITS_LEFT: i32 = 8;
+
+/// 单位的位置，跟在数字右下角
+const UNIT_X: i32 = DIGITS_LEFT + 4 * DIGIT_PITCH + 4;
+const UNIT_Y: i32 = DIGIT_TOP + DIGIT_HEIGHT as i32 - 1;
+
+/// 脉宽条
+const BAR: Rectangle = Rectangle::new(Point::new(8, 50), Size::new(112, 10));
+
+/// 输出在干什么
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// 没有脉冲
+    Stopped,
+    /// 停在设的脉宽上
+    Hold,
+    /// 来回扫，`rising` 是现在往哪边走
+    Sweep { rising: bool },
+}
+
+/// 舵机测试页面，舵机输出归它管
+pub struct ServoPage<S>
+where
+    S: SliceId,
+    FreeRunning: ValidSliceMode<S>,
+{
+    output: ServoOutput<S>,
+    pulse_us: u16,
+    coarse: bool,
+    mode: Mode,
+    /// 扫描的时候上次 tick 的时间
+    last_tick_ms: Option<u64>,
+}
+
+impl<S> ServoPage<S>
+where
+    S: SliceId,
+    FreeRunning: ValidSliceMode<S>,
+{
+    /// 从中间位置开始，没有输出
+    pub fn new(output: ServoOutput<S>) -> Self {
+        Self {
+            output,
+            pulse_us: CENTER_PULSE_US,
+            coarse: false,
+            mode: Mode::Stopped,
+            last_tick_ms: None,
+        }
+    }
+
+    fn set_mode(&mut self, mode: Mode) {
+        self.mode = mode;
+        self.last_tick_ms = None;
+        self.apply();
+    }
+
+    /// 把脉宽和模式写到输出上
+    fn apply(&mut self) {
+        let pulse = (self.mode != Mode::Stopped).then_some(self.pulse_us);
+        self.output.set_pulse(pulse);
+    }
+
+    /// 旋钮转一格，`up` 是往大了调。扫描的时候调一下就停在当前位置
+    fn step(&mut self, up: bool) {
+        let step = if self.coarse {
+            COARSE_STEP_US
+        } else {
+            FINE_STEP_US
+        };
+        self.pulse_us = if up {
+            self.pulse_us.saturating_add(step).min(MAX_PULSE_US)
+        } else {
+            self.pulse_us.saturating_sub(step).max(MIN_PULSE_US)
+        };
+        if matches!(self.mode, Mode::Sweep { .. }) {
+            self.mode = Mode::Hold;
+        }
+        self.apply();
+    }
+
+    /// 扫描走 `elapsed_ms`，到头了掉头
+    fn sweep(&mut self, rising: bool, elapsed_ms: u64) {
+        let distance =
+            (elapsed_ms * SWEEP_US_PER_MS).min((MAX_PULSE_US - MIN_PULSE_US) as u64) as u16;
+        let (pulse, rising) = if rising {
+            let room = MAX_PULSE_US - self.pulse_us;
+            if distance <= room {
+                (self.pulse_us + distance, true)
+            } else {
+                (MAX_PULSE_US - (distance - room), false)
+            }
+        } else {
+            let room = self.pulse_us - MIN_PULSE_US;
+            if distance <= room {
+                (self.pulse_us - distance, false)
+            } else {
+                (MIN_PULSE_US + (distance - room), true)
+            }
+        };
+        self.pulse_us = pulse;
+        self.mode = Mode::Sweep { rising };
+        self.apply();
+    }
+}
+
+impl<S> Page for ServoPage<S>
+where
+    S: SliceId,
+    FreeRunning: ValidSliceMode<S>,
+{
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        match event {
+            Event::Button(ButtonEvent::Pressed(Button::Back)) => {
+                self.set_mode(Mode::Stopped);
+                return Transition::Pop;
+            }
+            Event::Button(ButtonEvent::Pressed(Button::Up)) => self.step(true),
+            Event::Button(ButtonEvent::Pressed(Button::Down)) => self.step(false),
+            Event::Gesture(Gesture::Short(Button::Select)) => self.coarse = !self.coarse,
+            Event::Gesture(Gesture::Double(Button::Select)) => {
+                let mode = match self.mode {
+                    Mode::Sweep { .. } => Mode::Hold,
+                    _ => Mode::Sweep { rising: true },
+                };
+                self.set_mode(mode);
+            }
+            Event::Gesture(Gesture::Long(Button::Select)) => {
+                let mode = match self.mode {
+                    Mode::Stopped => Mode::Hold,
+                    _ => Mode::Stopped,
+                };
+                self.set_mode(mode);
+            }
+            _ => {}
+        }
+        Transition::None
+    }
+
+    fn tick(&mut self, now_ms: u64) -> bool {
+        let Mode::Sweep { rising } = self.mode else {
+            return false;
+        };
+        let elapsed = self
+            .last_tick_ms
+            .map_or(0, |last| now_ms.saturating_sub(last).min(MAX_SWEEP_STEP_MS));
+        self.last_tick_ms = Some(now_ms);
+        let before = self.pulse_us;
+        self.sweep(rising, elapsed);
+        self.pulse_us != before
+    }
+
+    fn desired_fps(&self) -> u16 {
+        match self.mode {
+            Mode::Sweep { .. } => SWEEP_FPS,
+            _ => 1,
+        }
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<(), Infallible> {
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        let state = match self.mode {
+            Mode::Stopped => "STOP",
+            Mode::Hold => "RUN",
+            Mode::Sweep { .. } => "SWEEP",
+        };
+        let step = if self.coarse { "coarse" } else { "fine" };
+        let mut title: String<24> = String::new();
+        let _ = write!(
+            title,
+            "{} {}deg {}",
+            state,
+            pulse_to_degrees(self.pulse_us),
+            step
+        );
+        draw_centered(canvas, &title, TITLE_Y, style)?;
+
+        let pulse = self.pulse_us;
+        let digits = [
+            pulse / 1000 % 10,
+            pulse / 100 % 10,
+            pulse / 10 % 10,
+            pulse % 10,
+        ];
+        for (i, &digit) in digits.iter().enumerate() {
+            let cell = Rectangle::new(
+                Point::new(DIGITS_LEFT + i as i32 * DIGIT_PITCH, DIGIT_TOP),
+                Size::new(DIGIT_WIDTH, DIGIT_HEIGHT),
+            );
+            draw_big_digit(canvas, cell, DIGIT_STROKE, digit as u8)?;
+        }
+        Text::new("us", Point::new(UNIT_X, UNIT_Y), style).draw(canvas)?;
+
+        let percent =
+            ((pulse - MIN_PULSE_US) as u32 * 100 / (MAX_PULSE_US - MIN_PULSE_US) as u32) as u8;
+        draw_progress_bar_styled(canvas, BAR, percent, &theme::active().progress_bar)
+    }
+}
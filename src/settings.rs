@@ -0,0 +1,983 @@
+//! 用户设置：保存在 flash 最后一个扇区，也可以通过串口导出/导入
+//!
+//! 设置在 flash 里和在串口上用的是同一种二进制格式(串口上再套一层 base64)：
+//!
+//! | 偏移 | 长度 | 内容 |
+//! |------|------|------|
+//! | 0 | 1 | 格式版本(`SETTINGS_VERSION`) |
+//! | 1 | 1 | 后面数据段的长度 N |
+//! | 2 | N | 数据段，每个版本的布局不同 |
+//! | 2+N | 4 | 前面所有字节的 CRC-32(小端) |
+//!
+//! 以后加字段就把版本号加一，在 `decode` 里给旧版本留一个分支，旧数据缺的字段用默认值补上，
+//! 这样老板子导出来的设置还能导进新固件。反过来，新固件的设置导进老固件会被拒绝，
+//! 因为老固件不知道多出来的字段是什么意思。
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use display_interface::{DisplayError, WriteOnlyDataCommand};
+use ssd1306::prelude::DisplayRotation;
+
+use crate::alarms::{self, AlarmRules};
+use crate::auto_brightness::{self, AutoBrightnessConfig};
+use crate::bmp280::{DEFAULT_SEA_LEVEL_PA, SEA_LEVEL_MAX_PA, SEA_LEVEL_MIN_PA};
+use crate::burn_in::{self, BurnInConfig};
+use crate::calibration::{self, Calibration};
+use crate::carousel::{self, CarouselConfig};
+use crate::crc::crc32;
+use crate::demo_auto::{self, DemoAutoConfig};
+use crate::dimming::{self, DimSchedule};
+use crate::display::{Display, DEFAULT_CONTRAST};
+use crate::display_profile;
+use crate::event_log::{self, SystemEvent};
+use crate::flash;
+use crate::image_slots;
+use crate::low_voltage::{self, LowVoltageConfig};
+use crate::panel_care;
+use crate::pulse_counter::{self, PulseConfig};
+use crate::remap::{self, InputBindings};
+use crate::theme;
+use crate::timezone::{self, TimeZone};
+use crate::vl53l0x::MAX_RANGE_MM;
+use crate::wake_alarm::{self, WakeAlarmConfig};
+use crate::watch_face::{self, WatchFaceConfig};
+
+/// 当前的设置格式版本
+pub const SETTINGS_VERSION: u8 = 24;
+
+/// 二进制格式最长多少字节(留了余量给以后的版本)
+pub const BLOB_CAPACITY: usize = 132;
+
+/// base64 之后最长多少字符
+pub const BASE64_CAPACITY: usize = BLOB_CAPACITY.div_ceil(3) * 4;
+
+/// 版本号 + 长度
+const HEADER_LEN: usize = 2;
+
+/// CRC-32 的长度
+const CRC_LEN: usize = 4;
+
+/// v1 数据段：旋转、对比度
+const V1_PAYLOAD_LEN: usize = 2;
+
+/// v2 数据段：v1 + 标志位
+const V2_PAYLOAD_LEN: usize = V1_PAYLOAD_LEN + 1;
+
+/// v2 标志位：静音
+const FLAG_MUTED: u8 = 1 << 0;
+
+/// v3 数据段：v2 + 记录间隔(秒，小端)
+const V3_PAYLOAD_LEN: usize = V2_PAYLOAD_LEN + 2;
+
+/// v3 标志位：打开数据记录
+const FLAG_LOGGING: u8 = 1 << 1;
+
+/// v4 数据段：v3 + 亮度时间表(见 `dimming`)
+const V4_PAYLOAD_LEN: usize = V3_PAYLOAD_LEN + dimming::ENCODED_LEN;
+
+/// v5 数据段：v4 + 告警规则表(见 `alarms`)
+const V5_PAYLOAD_LEN: usize = V4_PAYLOAD_LEN + alarms::ENCODED_LEN;
+
+/// v6 数据段：v5 + 海平面气压(Pa，小端)
+const V6_PAYLOAD_LEN: usize = V5_PAYLOAD_LEN + 4;
+
+/// v7 数据段：v6 + 防烧屏设置(见 `burn_in`)
+const V7_PAYLOAD_LEN: usize = V6_PAYLOAD_LEN + burn_in::ENCODED_LEN;
+
+/// v8 数据段：v7 + 自动关屏(分钟，小端) + 靠近亮屏的距离(mm，小端)
+const V8_PAYLOAD_LEN: usize = V7_PAYLOAD_LEN + 4;
+
+/// v9 数据段：v8 + 自动轮播设置(见 `carousel`)
+const V9_PAYLOAD_LEN: usize = V8_PAYLOAD_LEN + carousel::ENCODED_LEN;
+
+/// v10 数据段：v9 + 演示自动轮换设置(见 `demo_auto`)
+const V10_PAYLOAD_LEN: usize = V9_PAYLOAD_LEN + demo_auto::ENCODED_LEN;
+
+/// v11 数据段：v10 + 板子的校准值(见 `calibration`)
+const V11_PAYLOAD_LEN: usize = V10_PAYLOAD_LEN + calibration::ENCODED_LEN;
+
+/// v12 数据段：v11 + 表盘设置(见 `watch_face`)
+const V12_PAYLOAD_LEN: usize = V11_PAYLOAD_LEN + watch_face::ENCODED_LEN;
+
+/// v13 数据段：v12 + 高温调暗开关(见 `panel_care`)
+const V13_PAYLOAD_LEN: usize = V12_PAYLOAD_LEN + 1;
+
+/// v14 数据段：v13 + 开机画面用哪个图片槽(见 `image_slots`)，0xFF 是不用
+const V14_PAYLOAD_LEN: usize = V13_PAYLOAD_LEN + 1;
+
+/// 开机画面不用图片槽的时候存的值
+const NO_SPLASH: u8 = 0xFF;
+
+/// v15 数据段：v14 + 低电压保护的两个阈值(见 `low_voltage`)
+const V15_PAYLOAD_LEN: usize = V14_PAYLOAD_LEN + low_voltage::ENCODED_LEN;
+
+/// v16 数据段：v15 + 大字模式开关(见 `large_text`)
+const V16_PAYLOAD_LEN: usize = V15_PAYLOAD_LEN + 1;
+
+/// v17 数据段：v16 + 闹钟(见 `wake_alarm`)
+const V17_PAYLOAD_LEN: usize = V16_PAYLOAD_LEN + wake_alarm::ENCODED_LEN;
+
+/// v18 数据段：v17 + 显示方案(见 `display_profile`)，0xFF 是没选
+const V18_PAYLOAD_LEN: usize = V17_PAYLOAD_LEN + 1;
+
+/// 没选显示方案的时候存的值
+const NO_PROFILE: u8 = 0xFF;
+
+/// v19 数据段：v18 + 脉冲计数的换算和最短脉宽(见 `pulse_counter`)
+const V19_PAYLOAD_LEN: usize = V18_PAYLOAD_LEN + pulse_counter::ENCODED_LEN;
+
+/// v20 数据段：v19 + 主题(见 `theme`)
+const V20_PAYLOAD_LEN: usize = V19_PAYLOAD_LEN + 1;
+
+/// v21 数据段：v20 + 按键重映射(见 `remap`)
+const V21_PAYLOAD_LEN: usize = V20_PAYLOAD_LEN + remap::ENCODED_LEN;
+
+/// v22 数据段：v21 + 帧同步开关(见 `frame_pacing`)
+const V22_PAYLOAD_LEN: usize = V21_PAYLOAD_LEN + 1;
+
+/// v23 数据段：v22 + 自动亮度(见 `auto_brightness`)
+const V23_PAYLOAD_LEN: usize = V22_PAYLOAD_LEN + auto_brightness::ENCODED_LEN;
+
+/// v24 数据段：v23 + 时区和夏令时(见 `timezone`)
+const V24_PAYLOAD_LEN: usize = V23_PAYLOAD_LEN + timezone::ENCODED_LEN;
+
+const _: () = assert!(HEADER_LEN + V24_PAYLOAD_LEN + CRC_LEN <= BLOB_CAPACITY);
+
+/// 默认的记录间隔(秒)
+pub const DEFAULT_LOG_INTERVAL_S: u16 = 60;
+
+/// 设置解析失败的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum SettingsError {
+    /// base64 解码失败
+    Encoding,
+    /// 长度和头部里写的对不上
+    Malformed,
+    /// CRC 校验失败
+    BadCrc,
+    /// 比当前固件新的版本，不认识
+    NewerVersion(u8),
+    /// 字段的值不合法
+    OutOfRange,
+}
+
+impl SettingsError {
+    /// 给串口用的错误说明
+    pub fn message(self) -> &'static str {
+        match self {
+            SettingsError::Encoding => "invalid base64",
+            SettingsError::Malformed => "blob length mismatch",
+            SettingsError::BadCrc => "CRC mismatch, blob is corrupted",
+            SettingsError::NewerVersion(_) => "blob is from a newer firmware, upgrade first",
+            SettingsError::OutOfRange => "value out of range",
+        }
+    }
+}
+
+/// 用户设置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Settings {
+    /// 屏幕旋转，顺时针转了几个 90 度(0..=3)
+    pub quarter_turns: u8,
+    /// 屏幕对比度(亮度)，0..=255
+    pub contrast: u8,
+    /// 蜂鸣器静音
+    pub muted: bool,
+    /// 打开数据记录
+    pub logging: bool,
+    /// 记录间隔(秒)，至少 1 秒
+    pub log_interval_s: u16,
+    /// 按时间自动调亮度，空的就一直用 `contrast`
+    pub dim_schedule: DimSchedule,
+    /// 阈值告警规则
+    pub alarm_rules: AlarmRules,
+    /// 海平面气压(Pa)，BMP280 算海拔用
+    pub sea_level_pa: u32,
+    /// 防烧屏，默认关
+    pub burn_in: BurnInConfig,
+    /// 多久没操作自动关屏(分钟)，0 是不关
+    pub screen_off_min: u16,
+    /// 关屏的时候手靠近到多少 mm 以内亮屏(要接 VL53L0X)，0 是不用
+    pub wake_mm: u16,
+    /// 自动轮播，默认关
+    pub carousel: CarouselConfig,
+    /// 演示自动轮换，默认关
+    pub demo_auto: DemoAutoConfig,
+    /// 片内温度和 ADC 的校准，默认不修正
+    pub calibration: Calibration,
+    /// 表盘的布局和每个格子放什么
+    pub watch_face: WatchFaceConfig,
+    /// 片内温度一直偏高的时候自动调暗，默认关
+    pub thermal_derate: bool,
+    /// 开机画面用哪个图片槽(从 0 数)，None 是显示版本号
+    pub splash_slot: Option<u8>,
+    /// 低电压保护的阈值，默认按一节锂电池
+    pub low_voltage: LowVoltageConfig,
+    /// 大字模式，默认关
+    pub large_text: bool,
+    /// 闹钟，默认都关着
+    pub wake_alarms: WakeAlarmConfig,
+    /// 选的显示方案(`display_profile::PROFILES` 里的编号)，None 是没选(不反色)
+    pub display_profile: Option<u8>,
+    /// 脉冲计数每个脉冲算多少、什么单位，默认一个脉冲算 1 个 "pulse"
+    pub pulse: PulseConfig,
+    /// 主题(`theme::THEMES` 里的编号)，默认 0
+    pub theme: u8,
+    /// 哪个物理输入当哪个按键用，默认按接线的名字
+    pub input_bindings: InputBindings,
+    /// flush 对齐屏幕刷新周期，默认关
+    pub frame_pacing: bool,
+    /// 跟着光敏电阻自动调亮度，默认关
+    pub auto_brightness: AutoBrightnessConfig,
+    /// 时区和夏令时，DS3231 里存的是 UTC，默认就按 UTC 显示
+    pub timezone: TimeZone,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            quarter_turns: 0,
+            contrast: DEFAULT_CONTRAST,
+            muted: false,
+            logging: false,
+            log_interval_s: DEFAULT_LOG_INTERVAL_S,
+            dim_schedule: DimSchedule::new(),
+            alarm_rules: AlarmRules::new(),
+            sea_level_pa: DEFAULT_SEA_LEVEL_PA,
+            burn_in: BurnInConfig::default(),
+            screen_off_min: 0,
+            wake_mm: 0,
+            carousel: CarouselConfig::default(),
+            demo_auto: DemoAutoConfig::default(),
+            calibration: Calibration::default(),
+            watch_face: WatchFaceConfig::default(),
+            thermal_derate: false,
+            splash_slot: None,
+            low_voltage: LowVoltageConfig::default(),
+            large_text: false,
+            wake_alarms: WakeAlarmConfig::default(),
+            display_profile: None,
+            pulse: PulseConfig::DEFAULT,
+            theme: 0,
+            input_bindings: InputBindings::DEFAULT,
+            frame_pacing: false,
+            auto_brightness: AutoBrightnessConfig::OFF,
+            timezone: TimeZone::UTC,
+        }
+    }
+}
+
+impl Settings {
+    /// 屏幕旋转
+    pub fn rotation(&self) -> DisplayRotation {
+        match self.quarter_turns {
+            1 => DisplayRotation::Rotate90,
+            2 => DisplayRotation::Rotate180,
+            3 => DisplayRotation::Rotate270,
+            _ => DisplayRotation::Rotate0,
+        }
+    }
+
+    /// 编码成二进制格式，返回实际长度
+    pub fn encode(&self, out: &mut [u8; BLOB_CAPACITY]) -> usize {
+        out[0] = SETTINGS_VERSION;
+        out[1] = V24_PAYLOAD_LEN as u8;
+        out[2] = self.quarter_turns;
+        out[3] = self.contrast;
+        out[4] = (if self.muted { FLAG_MUTED } else { 0 })
+            | (if self.logging { FLAG_LOGGING } else { 0 });
+        out[5..7].copy_from_slice(&self.log_interval_s.to_le_bytes());
+        // 时间表本来就是整理过的，这里再整理一次，保证存下来的东西一定是有序的
+        let mut schedule = self.dim_schedule;
+        schedule.normalize();
+        out[7..7 + dimming::ENCODED_LEN].copy_from_slice(&schedule.encode());
+        let rules = HEADER_LEN + V4_PAYLOAD_LEN;
+        out[rules..rules + alarms::ENCODED_LEN].copy_from_slice(&self.alarm_rules.encode());
+        let sea_level = HEADER_LEN + V5_PAYLOAD_LEN;
+        out[sea_level..sea_level + 4].copy_from_slice(&self.sea_level_pa.to_le_bytes());
+        let burn_in = HEADER_LEN + V6_PAYLOAD_LEN;
+        out[burn_in..burn_in + burn_in::ENCODED_LEN].copy_from_slice(&self.burn_in.encode());
+        let screen = HEADER_LEN + V7_PAYLOAD_LEN;
+        out[screen..screen + 2].copy_from_slice(&self.screen_off_min.to_le_bytes());
+        out[screen + 2..screen + 4].copy_from_slice(&self.wake_mm.to_le_bytes());
+        let carousel = HEADER_LEN + V8_PAYLOAD_LEN;
+        out[carousel..carousel + carousel::ENCODED_LEN].copy_from_slice(&self.carousel.encode());
+        let demo = HEADER_LEN + V9_PAYLOAD_LEN;
+        out[demo..demo + demo_auto::ENCODED_LEN].copy_from_slice(&self.demo_auto.encode());
+        let cal = HEADER_LEN + V10_PAYLOAD_LEN;
+        out[cal..cal + calibration::ENCODED_LEN].copy_from_slice(&self.calibration.encode());
+        let face = HEADER_LEN + V11_PAYLOAD_LEN;
+        out[face..face + watch_face::ENCODED_LEN].copy_from_slice(&self.watch_face.encode());
+        out[HEADER_LEN + V12_PAYLOAD_LEN] = self.thermal_derate as u8;
+        out[HEADER_LEN + V13_PAYLOAD_LEN] = self.splash_slot.unwrap_or(NO_SPLASH);
+        let battery = HEADER_LEN + V14_PAYLOAD_LEN;
+        out[battery..battery + low_voltage::ENCODED_LEN]
+            .copy_from_slice(&self.low_voltage.encode());
+        out[HEADER_LEN + V15_PAYLOAD_LEN] = self.large_text as u8;
+        let wake = HEADER_LEN + V16_PAYLOAD_LEN;
+        out[wake..wake + wake_alarm::ENCODED_LEN].copy_from_slice(&self.wake_alarms.encode());
+        out[HEADER_LEN + V17_PAYLOAD_LEN] = self.display_profile.unwrap_or(NO_PROFILE);
+        let pulse = HEADER_LEN + V18_PAYLOAD_LEN;
+        out[pulse..pulse + pulse_counter::ENCODED_LEN].copy_from_slice(&self.pulse.encode());
+        out[HEADER_LEN + V19_PAYLOAD_LEN] = self.theme;
+        let inputs = HEADER_LEN + V20_PAYLOAD_LEN;
+        out[inputs..inputs + remap::ENCODED_LEN].copy_from_slice(&self.input_bindings.encode());
+        out[HEADER_LEN + V21_PAYLOAD_LEN] = self.frame_pacing as u8;
+        let auto = HEADER_LEN + V22_PAYLOAD_LEN;
+        out[auto..auto + auto_brightness::ENCODED_LEN]
+            .copy_from_slice(&self.auto_brightness.encode());
+        let zone = HEADER_LEN + V23_PAYLOAD_LEN;
+        out[zone..zone + timezone::ENCODED_LEN].copy_from_slice(&self.timezone.encode());
+        let body = HEADER_LEN + V24_PAYLOAD_LEN;
+        let crc = crc32(&out[..body]);
+        out[body..body + CRC_LEN].copy_from_slice(&crc.to_le_bytes());
+        body + CRC_LEN
+    }
+
+    /// 从二进制格式解析，多余的尾巴(比如 flash 里没擦掉的 0xFF)会被忽略
+    pub fn decode(blob: &[u8]) -> Result<Self, SettingsError> {
+        if blob.len() < HEADER_LEN {
+            return Err(SettingsError::Malformed);
+        }
+        let body = HEADER_LEN + blob[1] as usize;
+        if blob.len() < body + CRC_LEN {
+            return Err(SettingsError::Malformed);
+        }
+        let crc = u32::from_le_bytes([blob[body], blob[body + 1], blob[body + 2], blob[body + 3]]);
+        if crc != crc32(&blob[..body]) {
+            return Err(SettingsError::BadCrc);
+        }
+
+        let payload = &blob[HEADER_LEN..body];
+        match blob[0] {
+            1 => Self::decode_v1(payload),
+            2 => Self::decode_v2(payload),
+            3 => Self::decode_v3(payload),
+            4 => Self::decode_v4(payload),
+            5 => Self::decode_v5(payload),
+            6 => Self::decode_v6(payload),
+            7 => Self::decode_v7(payload),
+            8 => Self::decode_v8(payload),
+            9 => Self::decode_v9(payload),
+            10 => Self::decode_v10(payload),
+            11 => Self::decode_v11(payload),
+            12 => Self::decode_v12(payload),
+            13 => Self::decode_v13(payload),
+            14 => Self::decode_v14(payload),
+            15 => Self::decode_v15(payload),
+            16 => Self::decode_v16(payload),
+            17 => Self::decode_v17(payload),
+            18 => Self::decode_v18(payload),
+            19 => Self::decode_v19(payload),
+            20 => Self::decode_v20(payload),
+            21 => Self::decode_v21(payload),
+            22 => Self::decode_v22(payload),
+            23 => Self::decode_v23(payload),
+            24 => Self::decode_v24(payload),
+            version if version > SETTINGS_VERSION => Err(SettingsError::NewerVersion(version)),
+            _ => Err(SettingsError::Malformed),
+        }
+    }
+
+    fn decode_v1(payload: &[u8]) -> Result<Self, SettingsError> {
+        let &[quarter_turns, contrast] = payload else {
+            return Err(SettingsError::Malformed);
+        };
+        if quarter_turns > 3 {
+            return Err(SettingsError::OutOfRange);
+        }
+        Ok(Self {
+            quarter_turns,
+            contrast,
+            ..Self::default()
+        })
+    }
+
+    fn decode_v2(payload: &[u8]) -> Result<Self, SettingsError> {
+        let &[quarter_turns, contrast, flags] = payload else {
+            return Err(SettingsError::Malformed);
+        };
+        if flags & !FLAG_MUTED != 0 {
+            return Err(SettingsError::OutOfRange);
+        }
+        Ok(Self {
+            muted: flags & FLAG_MUTED != 0,
+            ..Self::decode_v1(&[quarter_turns, contrast])?
+        })
+    }
+
+    fn decode_v3(payload: &[u8]) -> Result<Self, SettingsError> {
+        let &[quarter_turns, contrast, flags, interval_lo, interval_hi] = payload else {
+            return Err(SettingsError::Malformed);
+        };
+        let log_interval_s = u16::from_le_bytes([interval_lo, interval_hi]);
+        if log_interval_s == 0 {
+            return Err(SettingsError::OutOfRange);
+        }
+        Ok(Self {
+            logging: flags & FLAG_LOGGING != 0,
+            log_interval_s,
+            ..Self::decode_v2(&[quarter_turns, contrast, flags & !FLAG_LOGGING])?
+        })
+    }
+
+    fn decode_v4(payload: &[u8]) -> Result<Self, SettingsError> {
+        if payload.len() != V4_PAYLOAD_LEN {
+            return Err(SettingsError::Malformed);
+        }
+        let (v3, schedule) = payload.split_at(V3_PAYLOAD_LEN);
+        Ok(Self {
+            dim_schedule: DimSchedule::decode(schedule).ok_or(SettingsError::OutOfRange)?,
+            ..Self::decode_v3(v3)?
+        })
+    }
+
+    fn decode_v5(payload: &[u8]) -> Result<Self, SettingsError> {
+        if payload.len() != V5_PAYLOAD_LEN {
+            return Err(SettingsError::Malformed);
+        }
+        let (v4, rules) = payload.split_at(V4_PAYLOAD_LEN);
+        Ok(Self {
+            alarm_rules: AlarmRules::decode(rules).ok_or(SettingsError::OutOfRange)?,
+            ..Self::decode_v4(v4)?
+        })
+    }
+
+    fn decode_v6(payload: &[u8]) -> Result<Self, SettingsError> {
+        if payload.len() != V6_PAYLOAD_LEN {
+            return Err(SettingsError::Malformed);
+        }
+        let (v5, sea_level) = payload.split_at(V5_PAYLOAD_LEN);
+        let sea_level_pa =
+            u32::from_le_bytes([sea_level[0], sea_level[1], sea_level[2], sea_level[3]]);
+        if !(SEA_LEVEL_MIN_PA..=SEA_LEVEL_MAX_PA).contains(&sea_level_pa) {
+            return Err(SettingsError::OutOfRange);
+        }
+        Ok(Self {
+            sea_level_pa,
+            ..Self::decode_v5(v5)?
+        })
+    }
+
+    fn decode_v7(payload: &[u8]) -> Result<Self, SettingsError> {
+        if payload.len() != V7_PAYLOAD_LEN {
+            return Err(SettingsError::Malformed);
+        }
+        let (v6, burn_in) = payload.split_at(V6_PAYLOAD_LEN);
+        Ok(Self {
+            burn_in: BurnInConfig::decode(burn_in).ok_or(SettingsError::OutOfRange)?,
+            ..Self::decode_v6(v6)?
+        })
+    }
+
+    fn decode_v8(payload: &[u8]) -> Result<Self, SettingsError> {
+        if payload.len() != V8_PAYLOAD_LEN {
+            return Err(SettingsError::Malformed);
+        }
+        let (v7, screen) = payload.split_at(V7_PAYLOAD_LEN);
+        let wake_mm = u16::from_le_bytes([screen[2], screen[3]]);
+        if wake_mm > MAX_RANGE_MM {
+            return Err(SettingsError::OutOfRange);
+        }
+        Ok(Self {
+            screen_off_min: u16::from_le_bytes([screen[0], screen[1]]),
+            wake_mm,
+            ..Self::decode_v7(v7)?
+        })
+    }
+
+    fn decode_v9(payload: &[u8]) -> Result<Self, SettingsError> {
+        if payload.len() != V9_PAYLOAD_LEN {
+            return Err(SettingsError::Malformed);
+        }
+        let (v8, carousel) = payload.split_at(V8_PAYLOAD_LEN);
+        Ok(Self {
+            carousel: CarouselConfig::decode(carousel).ok_or(SettingsError::OutOfRange)?,
+            ..Self::decode_v8(v8)?
+        })
+    }
+
+    fn decode_v10(payload: &[u8]) -> Result<Self, SettingsError> {
+        if payload.len() != V10_PAYLOAD_LEN {
+            return Err(SettingsError::Malformed);
+        }
+        let (v9, demo) = payload.split_at(V9_PAYLOAD_LEN);
+        Ok(Self {
+            demo_auto: DemoAutoConfig::decode(demo).ok_or(SettingsError::OutOfRange)?,
+            ..Self::decode_v9(v9)?
+        })
+    }
+
+    fn decode_v11(payload: &[u8]) -> Result<Self, SettingsError> {
+        if payload.len() != V11_PAYLOAD_LEN {
+            return Err(SettingsError::Malformed);
+        }
+        let (v10, cal) = payload.split_at(V10_PAYLOAD_LEN);
+        Ok(Self {
+            calibration: Calibration::decode(cal).ok_or(SettingsError::OutOfRange)?,
+            ..Self::decode_v10(v10)?
+        })
+    }
+
+    fn decode_v12(payload: &[u8]) -> Result<Self, SettingsError> {
+        if payload.len() != V12_PAYLOAD_LEN {
+            return Err(SettingsError::Malformed);
+        }
+        let (v11, face) = payload.split_at(V11_PAYLOAD_LEN);
+        Ok(Self {
+            watch_face: WatchFaceConfig::decode(face).ok_or(SettingsError::OutOfRange)?,
+            ..Self::decode_v11(v11)?
+        })
+    }
+
+    fn decode_v13(payload: &[u8]) -> Result<Self, SettingsError> {
+        if payload.len() != V13_PAYLOAD_LEN {
+            return Err(SettingsError::Malformed);
+        }
+        let (v12, &[derate]) = payload.split_at(V12_PAYLOAD_LEN) else {
+            return Err(SettingsError::Malformed);
+        };
+        if derate > 1 {
+            return Err(SettingsError::OutOfRange);
+        }
+        Ok(Self {
+            thermal_derate: derate == 1,
+            ..Self::decode_v12(v12)?
+        })
+    }
+
+    fn decode_v14(payload: &[u8]) -> Result<Self, SettingsError> {
+        if payload.len() != V14_PAYLOAD_LEN {
+            return Err(SettingsError::Malformed);
+        }
+        let (v13, &[splash]) = payload.split_at(V13_PAYLOAD_LEN) else {
+            return Err(SettingsError::Malformed);
+        };
+        let splash_slot = match splash {
+            NO_SPLASH => None,
+            slot if (slot as usize) < image_slots::SLOTS => Some(slot),
+            _ => return Err(SettingsError::OutOfRange),
+        };
+        Ok(Self {
+            splash_slot,
+            ..Self::decode_v13(v13)?
+        })
+    }
+
+    fn decode_v15(payload: &[u8]) -> Result<Self, SettingsError> {
+        if payload.len() != V15_PAYLOAD_LEN {
+            return Err(SettingsError::Malformed);
+        }
+        let (v14, thresholds) = payload.split_at(V14_PAYLOAD_LEN);
+        Ok(Self {
+            low_voltage: LowVoltageConfig::decode(thresholds).ok_or(SettingsError::OutOfRange)?,
+            ..Self::decode_v14(v14)?
+        })
+    }
+
+    fn decode_v16(payload: &[u8]) -> Result<Self, SettingsError> {
+        if payload.len() != V16_PAYLOAD_LEN {
+            return Err(SettingsError::Malformed);
+        }
+        let (v15, &[large]) = payload.split_at(V15_PAYLOAD_LEN) else {
+            return Err(SettingsError::Malformed);
+        };
+        if large > 1 {
+            return Err(SettingsError::OutOfRange);
+        }
+        Ok(Self {
+            large_text: large == 1,
+            ..Self::decode_v15(v15)?
+        })
+    }
+
+    fn decode_v17(payload: &[u8]) -> Result<Self, SettingsError> {
+        if payload.len() != V17_PAYLOAD_LEN {
+            return Err(SettingsError::Malformed);
+        }
+        let (v16, wake) = payload.split_at(V16_PAYLOAD_LEN);
+        Ok(Self {
+            wake_alarms: WakeAlarmConfig::decode(wake).ok_or(SettingsError::OutOfRange)?,
+            ..Self::decode_v16(v16)?
+        })
+    }
+
+    fn decode_v18(payload: &[u8]) -> Result<Self, SettingsError> {
+        if payload.len() != V18_PAYLOAD_LEN {
+            return Err(SettingsError::Malformed);
+        }
+        let (v17, &[profile]) = payload.split_at(V17_PAYLOAD_LEN) else {
+            return Err(SettingsError::Malformed);
+        };
+        let display_profile = match profile {
+            NO_PROFILE => None,
+            index if display_profile::get(index).is_some() => Some(index),
+            _ => return Err(SettingsError::OutOfRange),
+        };
+        Ok(Self {
+            display_profile,
+            ..Self::decode_v17(v17)?
+        })
+    }
+
+    fn decode_v19(payload: &[u8]) -> Result<Self, SettingsError> {
+        if payload.len() != V19_PAYLOAD_LEN {
+            return Err(SettingsError::Malformed);
+        }
+        let (v18, pulse) = payload.split_at(V18_PAYLOAD_LEN);
+        Ok(Self {
+            pulse: PulseConfig::decode(pulse).ok_or(SettingsError::OutOfRange)?,
+            ..Self::decode_v18(v18)?
+        })
+    }
+
+    fn decode_v20(payload: &[u8]) -> Result<Self, SettingsError> {
+        if payload.len() != V20_PAYLOAD_LEN {
+            return Err(SettingsError::Malformed);
+        }
+        let (v19, &[theme]) = payload.split_at(V19_PAYLOAD_LEN) else {
+            return Err(SettingsError::Malformed);
+        };
+        if theme::get(theme).is_none() {
+            return Err(SettingsError::OutOfRange);
+        }
+        Ok(Self {
+            theme,
+            ..Self::decode_v19(v19)?
+        })
+    }
+
+    fn decode_v21(payload: &[u8]) -> Result<Self, SettingsError> {
+        if payload.len() != V21_PAYLOAD_LEN {
+            return Err(SettingsError::Malformed);
+        }
+        let (v20, inputs) = payload.split_at(V20_PAYLOAD_LEN);
+        Ok(Self {
+            input_bindings: InputBindings::decode(inputs).ok_or(SettingsError::OutOfRange)?,
+            ..Self::decode_v20(v20)?
+        })
+    }
+
+    fn decode_v22(payload: &[u8]) -> Result<Self, SettingsError> {
+        if payload.len() != V22_PAYLOAD_LEN {
+            return Err(SettingsError::Malformed);
+        }
+        let (v21, &[pacing]) = payload.split_at(V21_PAYLOAD_LEN) else {
+            return Err(SettingsError::Malformed);
+        };
+        if pacing > 1 {
+            return Err(SettingsError::OutOfRange);
+        }
+        Ok(Self {
+            frame_pacing: pacing == 1,
+            ..Self::decode_v21(v21)?
+        })
+    }
+
+    fn decode_v23(payload: &[u8]) -> Result<Self, SettingsError> {
+        if payload.len() != V23_PAYLOAD_LEN {
+            return Err(SettingsError::Malformed);
+        }
+        let (v22, auto) = payload.split_at(V22_PAYLOAD_LEN);
+        Ok(Self {
+            auto_brightness: AutoBrightnessConfig::decode(auto).ok_or(SettingsError::OutOfRange)?,
+            ..Self::decode_v22(v22)?
+        })
+    }
+
+    fn decode_v24(payload: &[u8]) -> Result<Self, SettingsError> {
+        if payload.len() != V24_PAYLOAD_LEN {
+            return Err(SettingsError::Malformed);
+        }
+        let (v23, zone) = payload.split_at(V23_PAYLOAD_LEN);
+        Ok(Self {
+            timezone: TimeZone::decode(zone).ok_or(SettingsError::OutOfRange)?,
+            ..Self::decode_v23(v23)?
+        })
+    }
+
+    /// 编码成 base64 字符串，串口导出用
+    pub fn to_base64<'a>(&self, out: &'a mut [u8; BASE64_CAPACITY]) -> &'a str {
+        let mut blob = [0u8; BLOB_CAPACITY];
+        let len = self.encode(&mut blob);
+        // 容量是按 BLOB_CAPACITY 算的，不可能放不下
+        let written = STANDARD.encode_slice(&blob[..len], &mut out[..]).unwrap_or(0);
+        core::str::from_utf8(&out[..written]).unwrap_or("")
+    }
+
+    /// 从 base64 字符串解析，串口导入用
+    pub fn from_base64(text: &str) -> Result<Self, SettingsError> {
+        let mut blob = [0u8; BLOB_CAPACITY];
+        let len = STANDARD
+            .decode_slice(text.trim(), &mut blob)
+            .map_err(|_| SettingsError::Encoding)?;
+        Self::decode(&blob[..len])
+    }
+
+    /// 从 flash 读设置。没保存过(扇区是擦除状态)或者数据坏了都会返回错误，调用方用默认值就行
+    pub fn load() -> Result<Self, SettingsError> {
+        Self::decode(flash::read(flash::SETTINGS_OFFSET, BLOB_CAPACITY))
+    }
+
+    /// 写进 flash。擦写一次大概几十毫秒，期间关中断，CPU 只能跑 RAM 里的代码。
+    /// 同一个扇区里的屏幕累计记录会带过去(见 `panel_care`)，第 0 页设置后面的 Wi-Fi 账号原样写回(见 `wifi_config`)。
+    /// 电压低的时候不写，只记下有设置等着存，电压回来以后主循环再存一次(见 `low_voltage`)
+    pub fn store(&self) {
+        if !low_voltage::flash_writes_allowed() {
+            low_voltage::defer_save();
+            return;
+        }
+        let mut page = [0xFFu8; flash::PAGE_LEN];
+        let mut blob = [0u8; BLOB_CAPACITY];
+        let len = self.encode(&mut blob);
+        page[..len].copy_from_slice(&blob[..len]);
+        page[BLOB_CAPACITY..].copy_from_slice(flash::read(
+            flash::SETTINGS_OFFSET + BLOB_CAPACITY as u32,
+            flash::PAGE_LEN - BLOB_CAPACITY,
+        ));
+
+        panel_care::carry_over(|| {
+            flash::erase_sector(flash::SETTINGS_OFFSET);
+            flash::program_page(flash::SETTINGS_OFFSET, &page);
+        });
+        event_log::record(SystemEvent::SettingsSaved);
+    }
+
+    /// 把看得见的设置(旋转、亮度)应用到屏幕上。旋转之后显存要重画，下一帧会处理
+    pub fn apply<DI: WriteOnlyDataCommand>(
+        &self,
+        display: &mut Display<DI>,
+    ) -> Result<(), DisplayError> {
+        display.set_rotation(self.rotation())?;
+        display.set_contrast(self.contrast)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::burn_in::BurnInStrategy;
+    use crate::dimming::DimEntry;
+    use crate::timezone::DstRule;
+
+    /// 每个字段都换成不是默认值的合法值，解出来少了哪个一眼就看得出
+    fn custom() -> Settings {
+        let mut dim_schedule = DimSchedule::new();
+        dim_schedule.push(DimEntry::new(22, 30, 10));
+        dim_schedule.push(DimEntry::new(7, 0, 200));
+        dim_schedule.normalize();
+        Settings {
+            quarter_turns: 2,
+            contrast: 0x40,
+            muted: true,
+            logging: true,
+            log_interval_s: 30,
+            dim_schedule,
+            sea_level_pa: 100_900,
+            burn_in: BurnInConfig {
+                strategy: BurnInStrategy::PixelShift,
+                interval_min: 5,
+            },
+            screen_off_min: 15,
+            wake_mm: 300,
+            demo_auto: DemoAutoConfig {
+                idle_s: 120,
+                dwell_s: 10,
+            },
+            calibration: Calibration {
+                temp_offset_centi: -150,
+                ..Calibration::default()
+            },
+            thermal_derate: true,
+            splash_slot: Some(0),
+            low_voltage: LowVoltageConfig {
+                warn_mv: 3500,
+                critical_mv: 3300,
+            },
+            large_text: true,
+            display_profile: Some(0),
+            pulse: PulseConfig {
+                milli_per_pulse: 2500,
+                unit: 1,
+                min_width_ms: 10,
+            },
+            theme: 1,
+            frame_pacing: true,
+            auto_brightness: AutoBrightnessConfig {
+                dark_mv: 200,
+                bright_mv: 2500,
+                min_level: 1,
+                max_level: 255,
+            },
+            timezone: TimeZone::new(32, DstRule::Eu).unwrap(),
+            ..Settings::default()
+        }
+    }
+
+    /// 每个版本的数据段有多长
+    const PAYLOAD_LENS: [usize; SETTINGS_VERSION as usize] = [
+        V1_PAYLOAD_LEN,
+        V2_PAYLOAD_LEN,
+        V3_PAYLOAD_LEN,
+        V4_PAYLOAD_LEN,
+        V5_PAYLOAD_LEN,
+        V6_PAYLOAD_LEN,
+        V7_PAYLOAD_LEN,
+        V8_PAYLOAD_LEN,
+        V9_PAYLOAD_LEN,
+        V10_PAYLOAD_LEN,
+        V11_PAYLOAD_LEN,
+        V12_PAYLOAD_LEN,
+        V13_PAYLOAD_LEN,
+        V14_PAYLOAD_LEN,
+        V15_PAYLOAD_LEN,
+        V16_PAYLOAD_LEN,
+        V17_PAYLOAD_LEN,
+        V18_PAYLOAD_LEN,
+        V19_PAYLOAD_LEN,
+        V20_PAYLOAD_LEN,
+        V21_PAYLOAD_LEN,
+        V22_PAYLOAD_LEN,
+        V23_PAYLOAD_LEN,
+        V24_PAYLOAD_LEN,
+    ];
+
+    /// 老版本的 blob：新版本的数据段是在老版本后面接着加的，截掉后面就是老版本的布局
+    fn old_blob(settings: &Settings, version: u8) -> std::vec::Vec<u8> {
+        let mut current = [0u8; BLOB_CAPACITY];
+        settings.encode(&mut current);
+        let len = PAYLOAD_LENS[version as usize - 1];
+        let mut blob = std::vec![version, len as u8];
+        blob.extend_from_slice(&current[HEADER_LEN..HEADER_LEN + len]);
+        if version == 2 {
+            // 记录开关是 v3 才加的标志位
+            blob[4] &= !FLAG_LOGGING;
+        }
+        let crc = crc32(&blob);
+        blob.extend_from_slice(&crc.to_le_bytes());
+        blob
+    }
+
+    /// `version` 的时候已经有了的字段照 `settings`，后来才加的用默认值
+    fn known_in(settings: &Settings, version: u8) -> Settings {
+        let mut expected = Settings::default();
+        let s = settings;
+        let fields: [&mut dyn FnMut(&mut Settings); SETTINGS_VERSION as usize] = [
+            &mut |e| (e.quarter_turns, e.contrast) = (s.quarter_turns, s.contrast),
+            &mut |e| e.muted = s.muted,
+            &mut |e| (e.logging, e.log_interval_s) = (s.logging, s.log_interval_s),
+            &mut |e| e.dim_schedule = s.dim_schedule,
+            &mut |e| e.alarm_rules = s.alarm_rules,
+            &mut |e| e.sea_level_pa = s.sea_level_pa,
+            &mut |e| e.burn_in = s.burn_in,
+            &mut |e| (e.screen_off_min, e.wake_mm) = (s.screen_off_min, s.wake_mm),
+            &mut |e| e.carousel = s.carousel,
+            &mut |e| e.demo_auto = s.demo_auto,
+            &mut |e| e.calibration = s.calibration,
+            &mut |e| e.watch_face = s.watch_face,
+            &mut |e| e.thermal_derate = s.thermal_derate,
+            &mut |e| e.splash_slot = s.splash_slot,
+            &mut |e| e.low_voltage = s.low_voltage,
+            &mut |e| e.large_text = s.large_text,
+            &mut |e| e.wake_alarms = s.wake_alarms,
+            &mut |e| e.display_profile = s.display_profile,
+            &mut |e| e.pulse = s.pulse,
+            &mut |e| e.theme = s.theme,
+            &mut |e| e.input_bindings = s.input_bindings,
+            &mut |e| e.frame_pacing = s.frame_pacing,
+            &mut |e| e.auto_brightness = s.auto_brightness,
+            &mut |e| e.timezone = s.timezone,
+        ];
+        for field in fields.into_iter().take(version as usize) {
+            field(&mut expected);
+        }
+        expected
+    }
+
+    #[test]
+    fn round_trips_through_binary_and_base64() {
+        for settings in [Settings::default(), custom()] {
+            let mut blob = [0u8; BLOB_CAPACITY];
+            let len = settings.encode(&mut blob);
+            assert_eq!(len, HEADER_LEN + V24_PAYLOAD_LEN + CRC_LEN);
+            assert_eq!(Settings::decode(&blob[..len]), Ok(settings));
+            // flash 里后面没擦的 0xFF 不管
+            blob[len..].fill(0xFF);
+            assert_eq!(Settings::decode(&blob), Ok(settings));
+
+            let mut text = [0u8; BASE64_CAPACITY];
+            let text = settings.to_base64(&mut text);
+            assert_eq!(Settings::from_base64(text), Ok(settings));
+        }
+    }
+
+    #[test]
+    fn older_versions_fill_newer_fields_with_defaults() {
+        let settings = custom();
+        for version in 1..=SETTINGS_VERSION {
+            let blob = old_blob(&settings, version);
+            assert_eq!(
+                Settings::decode(&blob),
+                Ok(known_in(&settings, version)),
+                "v{version}"
+            );
+        }
+        // 最新版本切出来的和 encode 出来的是同一个东西
+        let mut current = [0u8; BLOB_CAPACITY];
+        let len = settings.encode(&mut current);
+        assert_eq!(old_blob(&settings, SETTINGS_VERSION), &current[..len]);
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let mut blob = old_blob(&custom(), SETTINGS_VERSION);
+        blob[0] = SETTINGS_VERSION + 1;
+        let body = blob.len() - CRC_LEN;
+        let crc = crc32(&blob[..body]);
+        blob[body..].copy_from_slice(&crc.to_le_bytes());
+        assert_eq!(
+            Settings::decode(&blob),
+            Err(SettingsError::NewerVersion(SETTINGS_VERSION + 1))
+        );
+    }
+
+    #[test]
+    fn rejects_corrupted_blobs() {
+        let blob = old_blob(&custom(), SETTINGS_VERSION);
+        for index in [0, 1, 2, HEADER_LEN + V12_PAYLOAD_LEN, blob.len() - 1] {
+            let mut corrupted = blob.clone();
+            corrupted[index] ^= 0x10;
+            let err = Settings::decode(&corrupted).unwrap_err();
+            // 改了长度字节的话读 CRC 的位置也跟着变，可能先发现长度不够
+            assert!(
+                matches!(err, SettingsError::BadCrc | SettingsError::Malformed),
+                "byte {index}: {err:?}"
+            );
+        }
+        let mut corrupted = blob.clone();
+        corrupted[HEADER_LEN] ^= 1;
+        assert_eq!(Settings::decode(&corrupted), Err(SettingsError::BadCrc));
+    }
+
+    #[test]
+    fn rejects_truncated_blobs_and_bad_base64() {
+        let blob = old_blob(&custom(), SETTINGS_VERSION);
+        for len in 0..blob.len() {
+            assert_eq!(
+                Settings::decode(&blob[..len]),
+                Err(SettingsError::Malformed),
+                "{len} bytes"
+            );
+        }
+        assert_eq!(
+            Settings::from_base64("not base64!"),
+            Err(SettingsError::Encoding)
+        );
+        // base64 本身没问题，解出来的东西太短
+        assert_eq!(Settings::from_base64("GA=="), Err(SettingsError::Malformed));
+        // 比最长的 blob 还长
+        let long = "A".repeat(BASE64_CAPACITY + 4);
+        assert_eq!(Settings::from_base64(&long), Err(SettingsError::Encoding));
+    }
+}
@@ -0,0 +1,130 @@
+//! 设置菜单：几个设置页面的入口
+//!
+//! 从诊断页面按 Down 进来。Up/Down 选，Select 打开，Back 回去。除了设置页面也放了 "perf" 这种调试用的页面。
+//! 菜单里有哪些页面、标题叫什么由 main.rs 决定，这里只管列出来。开机的演示菜单也是它。
+//!
+//! 页面后面还可以跟几项直接动手的操作(`with_actions`，比如清零)，选了先弹确认框(见 `confirm`)，
+//! 选 "yes" 才把动作交给主循环。
+
+use core::convert::Infallible;
+use core::fmt::Write;
+
+use embedded_graphics::geometry::Point;
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::text::Text;
+use embedded_graphics::Drawable;
+use heapless::String;
+
+use crate::app::{Action, Canvas, Confirm, Event, Page, PageId, Transition};
+use crate::input::{Button, ButtonEvent};
+use crate::large_text::draw_large_readings;
+use crate::widgets::draw_menu;
+
+/// 标题行基线
+const TITLE_Y: i32 = 8;
+
+/// 菜单从哪一行开始
+const MENU_TOP: i32 = 12;
+
+/// 设置菜单：`N` 个页面，后面跟 `M` 项要确认的操作
+#[derive(Debug)]
+pub struct SettingsMenu<const N: usize, const M: usize = 0> {
+    title: &'static str,
+    items: [(&'static str, PageId); N],
+    actions: [(&'static str, Confirm); M],
+    selected: usize,
+    pending: Option<Action>,
+}
+
+impl<const N: usize> SettingsMenu<N> {
+    pub const fn new(title: &'static str, items: [(&'static str, PageId); N]) -> Self {
+        Self {
+            title,
+            items,
+            actions: [],
+            selected: 0,
+            pending: None,
+        }
+    }
+
+    /// 页面后面加几项操作
+    pub fn with_actions<const M: usize>(
+        self,
+        actions: [(&'static str, Confirm); M],
+    ) -> SettingsMenu<N, M> {
+        SettingsMenu {
+            title: self.title,
+            items: self.items,
+            actions,
+            selected: 0,
+            pending: None,
+        }
+    }
+}
+
+impl<const N: usize, const M: usize> SettingsMenu<N, M> {
+    const ROWS: usize = N + M;
+
+    /// 第 `row` 行的名字
+    fn label(&self, row: usize) -> Option<&'static str> {
+        match self.items.get(row) {
+            Some(&(name, _)) => Some(name),
+            None => self.actions.get(row - N).map(|&(name, _)| name),
+        }
+    }
+}
+
+impl<const N: usize, const M: usize> Page for SettingsMenu<N, M> {
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        let rows = Self::ROWS;
+        match event {
+            Event::Button(ButtonEvent::Pressed(Button::Back)) => return Transition::Pop,
+            Event::Button(ButtonEvent::Pressed(Button::Up)) if rows > 0 => {
+                self.selected = (self.selected + rows - 1) % rows;
+            }
+            Event::Button(ButtonEvent::Pressed(Button::Down)) if rows > 0 => {
+                self.selected = (self.selected + 1) % rows;
+            }
+            Event::Button(ButtonEvent::Pressed(Button::Select)) => {
+                if let Some(&(_, page)) = self.items.get(self.selected) {
+                    return Transition::Push(page);
+                }
+                if let Some(&(_, confirm)) = self.actions.get(self.selected - N) {
+                    return Transition::Confirm(confirm);
+                }
+            }
+            Event::Confirmed { request, yes: true } => self.pending = Some(request.action),
+            _ => {}
+        }
+        Transition::None
+    }
+
+    fn take_action(&mut self) -> Option<Action> {
+        self.pending.take()
+    }
+
+    /// 只有按键的时候才变
+    fn desired_fps(&self) -> u16 {
+        1
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<(), Infallible> {
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        Text::new(self.title, Point::new(0, TITLE_Y), style).draw(canvas)?;
+        let items = (0..Self::ROWS).filter_map(|row| self.label(row).map(|name| (name, "")));
+        draw_menu(canvas, MENU_TOP, items, self.selected)
+    }
+
+    /// 大字版：一屏只放选中的那一项，上面一行是第几项
+    fn render_large(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<bool, Infallible> {
+        let Some(name) = self.label(self.selected) else {
+            return Ok(false);
+        };
+        let mut position: String<8> = String::new();
+        let _ = write!(position, "{}/{}", self.selected + 1, Self::ROWS);
+        draw_large_readings(canvas, &[(&position, name)])?;
+        Ok(true)
+    }
+}
@@ -0,0 +1,130 @@
+//! 最后一帧的影子：panic 的时候把最后一次完整发出去的画面重新发一遍，右上角标个 FAULT
+//!
+//! 渲染到一半 panic 的话，显存里是半张画面；默认的死机画面(见 `panic_screen`)会整屏换成 panic 消息。
+//! 打开 `panic-restore` feature 以后改成这样：`Display` 每次 flush 成功，就把发出去的那块区域的字节
+//! 抄进这里的静态缓冲区(多占 1K 内存)，所以这里存的总是屏幕上最后一张发完整了的画面，不会有画了一半的。
+//! panic 的时候把它原样发回屏幕，再盖一个小小的 FAULT 标记，看得出死机前停在哪一页。
+//!
+//! 什么时候算"发完整了"：
+//!
+//! - 阻塞 flush：`flush_region` 每一页都发出去了才抄
+//! - DMA flush：`start_flush` 只记下区域，等 `poll_flush` 报告 `Complete` 才从 DMA 暂存区抄过来，
+//!   这时候显存可能已经在画下一帧了，所以不能从显存抄。中途出错(`Abort`)的那一帧不算
+//!
+//! 影子里是 GDDRAM 布局的字节(已经转过方向、合成过叠加层)，还要知道是按什么方向和镜像发的才能原样发回去，
+//! 所以一起记下来。换方向或者镜像以后影子先作废，等下一次整屏 flush(换完会整屏标脏)才重新有效；
+//! 开机后第一次整屏 flush 之前也是无效的，这时候 panic 还是画普通的死机画面。
+//!
+//! 影子是一个 `Shared`(见 `shared`)，在锁里写，中断里 panic 也不会读到抄了一半的影子。
+
+use display_interface::{DisplayError, WriteOnlyDataCommand};
+use embedded_graphics::geometry::{OriginDimensions, Point, Size};
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::primitives::{Primitive, PrimitiveStyle, Rectangle};
+use embedded_graphics::text::{Baseline, Text};
+use embedded_graphics::Drawable;
+use ssd1306::prelude::DisplayRotation;
+
+use crate::display::{Display, DEFAULT_CONTRAST};
+use crate::framebuffer::{DirtyRegion, BUFFER_LEN, WIDTH};
+use crate::shared::Shared;
+
+/// 标记上的字
+const FAULT_TEXT: &str = "FAULT";
+
+/// 标记框的大小：5 个字加左右各 1 像素
+const MARKER_SIZE: Size = Size::new(5 * 6 + 2, 11);
+
+/// 屏幕最后一次完整发出去的画面
+#[derive(Clone)]
+pub struct ShadowFrame {
+    bytes: [u8; BUFFER_LEN],
+    rotation: DisplayRotation,
+    mirror: (bool, bool),
+    contrast: u8,
+    /// 从上一次换方向以后整屏发过没有，没有的话 `bytes` 里拼不出一张完整的画面
+    valid: bool,
+}
+
+static SHADOW: Shared<ShadowFrame> = Shared::new(ShadowFrame {
+    bytes: [0; BUFFER_LEN],
+    rotation: DisplayRotation::Rotate0,
+    mirror: (false, false),
+    contrast: DEFAULT_CONTRAST,
+    valid: false,
+});
+
+/// 记下屏幕上 `region` 这块现在是 `bytes`(按 flush 的顺序)。整屏的才会让影子变成有效的
+pub(crate) fn commit(
+    region: DirtyRegion,
+    bytes: impl Iterator<Item = u8>,
+    rotation: DisplayRotation,
+    mirror: (bool, bool),
+    contrast: u8,
+) {
+    SHADOW.lock(|shadow| {
+        let columns = region.columns();
+        for (i, byte) in bytes.take(region.byte_count()).enumerate() {
+            let page = region.first_page as usize + i / columns;
+            let col = region.first_col as usize + i % columns;
+            shadow.bytes[page * WIDTH + col] = byte;
+        }
+        shadow.rotation = rotation;
+        shadow.mirror = mirror;
+        shadow.contrast = contrast;
+        if region == DirtyRegion::FULL {
+            shadow.valid = true;
+        }
+    });
+}
+
+/// 作废(换了方向或者镜像)，等下一次整屏 flush
+pub(crate) fn invalidate() {
+    SHADOW.lock(|shadow| shadow.valid = false);
+}
+
+/// 抄一份出来，还没有完整的一帧返回 None。panic 的时候用，抄出来以后就不用再管中断了
+pub fn last_good() -> Option<ShadowFrame> {
+    SHADOW.lock(|shadow| shadow.valid.then(|| shadow.clone()))
+}
+
+impl ShadowFrame {
+    /// 把这一帧重新发到屏幕上，右上角盖一个反色的 FAULT
+    ///
+    /// panic 的时候原来的 `Display` 拿不到了，屏幕的状态也不可信(可能发到一半停下的，还在窗口中间)，
+    /// 所以传进来的是一个新建的、没初始化过的传输接口，这里做最少的重新初始化：
+    /// 整套上电命令(`init` 会把亮度、寻址模式、窗口都重设一遍)，再按原来的亮度和镜像设好，然后整屏发一遍。
+    /// 只靠阻塞写，不用 DMA、定时器和中断
+    pub fn restore<DI: WriteOnlyDataCommand>(&self, interface: DI) -> Result<(), DisplayError> {
+        let mut display = Display::new(interface, self.rotation);
+        display.init()?;
+        display.set_contrast(self.contrast)?;
+        let (horizontal, vertical) = self.mirror;
+        if self.mirror != (false, false) {
+            display.set_mirror(horizontal, vertical)?;
+        }
+        display.framebuffer_mut().load_bytes(&self.bytes);
+        draw_fault_marker(&mut display)?;
+        // init 已经整屏标脏了，这里会整屏重发
+        display.flush()
+    }
+}
+
+/// 右上角的反色 FAULT。画在逻辑坐标里，转了方向也在看到的右上角
+fn draw_fault_marker<DI>(display: &mut Display<DI>) -> Result<(), DisplayError> {
+    let width = display.size().width;
+    let top_left = Point::new((width - MARKER_SIZE.width) as i32, 0);
+    Rectangle::new(top_left, MARKER_SIZE)
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(display)?;
+    Text::with_baseline(
+        FAULT_TEXT,
+        top_left + Point::new(1, 1),
+        MonoTextStyle::new(&FONT_6X10, BinaryColor::Off),
+        Baseline::Top,
+    )
+    .draw(display)?;
+    Ok(())
+}
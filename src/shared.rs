@@ -0,0 +1,54 @@
+//! 中断和主循环共用的状态：统一用 `critical_section::Mutex<RefCell<..>>` 包起来，不用 `static mut`
+//!
+//! rp2040-hal 打开了 `critical-section-impl`，`critical_section::with` 在 RP2040 上是关中断再加一把硬件自旋锁，
+//! 两个核、中断里都能用。这里包了一层 `Shared<T>`，用的时候只有 `lock` 一个入口。
+//!
+//! 上锁的规矩：
+//!
+//! - 中断和主循环(或者两个核)都要碰的东西放在 `static Shared<T>` 里，不要写 `static mut` 再 `unsafe` 去读写
+//! - 只在 `lock` 的闭包里碰，闭包要短：拷进拷出、往队列里塞一个、改几个计数。不要在里面写 flash、发 I2C、
+//!   等定时器或者打日志，关着中断的时间就是别的中断被耽误的时间
+//! - 闭包里不要再锁同一个 `Shared`(`RefCell` 会 panic)，也不要把里面的引用带出来
+//! - 只是一个开关或者一个计数的，用 `core::sync::atomic` 就行(见 `low_voltage`)。thumbv6m 只有原子读和写，
+//!   没有交换和比较交换，要"读了再改"的还是得上锁
+//!
+//! 这里放了最常用的定时器：`install_timer` 以后哪里都能 `now_us`/`now_ms`，不用把 `Timer` 一路传下去，
+//! 也不用去读裸寄存器。按键不经过这里：消抖、长按都是主循环轮询出来的(`ButtonPad::poll`)，
+//! GPIO 中断只负责把睡着的主循环早点叫醒(见 main.rs 的 `idle_until_input`)
+
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+use rp2040_hal::Timer;
+
+/// 中断和主循环共用的一份 `T`
+pub struct Shared<T>(Mutex<RefCell<T>>);
+
+impl<T> Shared<T> {
+    pub const fn new(value: T) -> Self {
+        Self(Mutex::new(RefCell::new(value)))
+    }
+
+    /// 关着中断借出来用，闭包返回的东西带出去
+    pub fn lock<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        critical_section::with(|cs| f(&mut self.0.borrow_ref_mut(cs)))
+    }
+}
+
+/// 装好的定时器，`Timer` 可以复制，只读计数器的话和主循环手里那个不冲突
+static TIMER: Shared<Option<Timer>> = Shared::new(None);
+
+/// 装上定时器，`Timer::new` 之后马上调
+pub fn install_timer(timer: Timer) {
+    TIMER.lock(|slot| *slot = Some(timer));
+}
+
+/// 开机以来的微秒数，还没装定时器返回 0
+pub fn now_us() -> u64 {
+    TIMER.lock(|slot| slot.as_ref().map_or(0, |timer| timer.get_counter().ticks()))
+}
+
+/// 开机以来的毫秒数，还没装定时器返回 0
+pub fn now_ms() -> u64 {
+    now_us() / 1000
+}
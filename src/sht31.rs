@@ -0,0 +1,77 @@
+//! SHT31 温湿度传感器(Sensirion)
+//!
+//! 用单次测量命令(高重复性、不拉长时钟)：发 0x2400，等 15ms 再读 6 字节，温度和湿度各 2 字节数据 + 1 字节 CRC-8。
+//! 不用时钟拉长是因为总线和屏幕共用，传感器在测量的时候扣住 SCL 会把屏幕的 DMA 也卡住。
+//! 测完之前去读芯片会 NACK，算一次总线错误。
+
+use embedded_hal::i2c::I2c;
+
+use crate::crc::crc8;
+use crate::humidity::{HumidityError, HumidityReading, HumiditySensor};
+
+/// ADDR 脚接地是 0x44，接 VDD 是 0x45
+pub const ADDRESSES: [u8; 2] = [0x44, 0x45];
+
+/// 读状态寄存器，探测的时候用(回 2 字节 + CRC)
+const CMD_READ_STATUS: [u8; 2] = [0xF3, 0x2D];
+
+/// 单次测量，高重复性，不拉长时钟
+const CMD_MEASURE: [u8; 2] = [0x24, 0x00];
+
+/// 高重复性的测量手册上最长 15ms，多等 1ms
+const MEASUREMENT_MS: u32 = 16;
+
+/// 一个 SHT31
+#[derive(Debug, Clone, Copy)]
+pub struct Sht31 {
+    address: u8,
+}
+
+/// 2 字节数据 + CRC，校验对了返回数据
+fn checked_word(bytes: &[u8]) -> Result<u16, HumidityError> {
+    if crc8(&bytes[..2]) != bytes[2] {
+        return Err(HumidityError::Crc);
+    }
+    Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+impl Sht31 {
+    /// 在 `ADDRESSES` 上找芯片：能读出状态寄存器并且 CRC 对得上才算
+    pub fn probe<I: I2c>(i2c: &mut I) -> Option<Self> {
+        ADDRESSES.iter().find_map(|&address| {
+            let mut status = [0u8; 3];
+            i2c.write_read(address, &CMD_READ_STATUS, &mut status)
+                .ok()?;
+            checked_word(&status).ok()?;
+            Some(Self { address })
+        })
+    }
+}
+
+impl HumiditySensor for Sht31 {
+    fn name(&self) -> &'static str {
+        "SHT31"
+    }
+
+    fn measurement_ms(&self) -> u32 {
+        MEASUREMENT_MS
+    }
+
+    fn start<I: I2c>(&mut self, i2c: &mut I) -> Result<(), HumidityError> {
+        i2c.write(self.address, &CMD_MEASURE)
+            .map_err(|_| HumidityError::Bus)
+    }
+
+    fn read<I: I2c>(&mut self, i2c: &mut I) -> Result<HumidityReading, HumidityError> {
+        let mut data = [0u8; 6];
+        i2c.read(self.address, &mut data)
+            .map_err(|_| HumidityError::Bus)?;
+        let raw_t = checked_word(&data[..3])? as i32;
+        let raw_rh = checked_word(&data[3..])? as i32;
+        // 手册 4.13：T = -45 + 175 * raw / 65535，RH = 100 * raw / 65535
+        Ok(HumidityReading {
+            temp_centi: -4500 + 17500 * raw_t / 65535,
+            humidity_centi: 10000 * raw_rh / 65535,
+        })
+    }
+}
@@ -0,0 +1,186 @@
+//! 床头钟模式：大字显示 HH:MM，尽量少用电
+//!
+//! 这个模式不走 `Scheduler`，主循环整个停下来，由 main.rs 的睡眠循环接管(USB、遥测、告警都暂停)：
+//!
+//! - 对比度压到最低
+//! - 数字一分钟才变一次，只重画变了的那一位，每一位单独 flush，一位 24 列 x 5 页 = 120 字节
+//! - 秒点是一个像素，每秒亮灭一次，只 flush 它所在的那一个字节
+//! - 其余时间 CPU 在 WFI 里睡觉，靠定时器闹钟每秒叫醒一次
+//!
+//! 这样平时每秒只发 1 个字节的显存数据，每分钟再多几百字节，平均每秒不到 40 字节。
+//! 可以打开 defmt 的 trace 级别看每一次 flush 的字节数，或者看睡眠循环每分钟打的统计。
+//!
+//! 板子没有电池供电的实时时钟，时间是开机时长加上一个偏移，用串口命令 `CLOCK SET HH:MM` 对时，
+//! 没对过时的话从开机那一刻的 00:00 开始走。总线上接了 DS3231 的话主循环从它那里对时(见 ds3231.rs)。
+//!
+//! 时间全靠定时器，定时器的准确度就是晶振的准确度。打开 `no-xosc` 的板子上定时器跑在 ROSC 上，
+//! 一天可能差出好几个小时，床头钟基本没法用(见 rosc_clock.rs)。
+
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::primitives::{Primitive, PrimitiveStyle, Rectangle};
+use embedded_graphics::Drawable;
+
+use crate::display::BufferedDisplay;
+use crate::widgets::draw_big_digit;
+
+/// 一天多少毫秒
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// 数字的宽高和笔画粗细。顶边对齐到第 1 页，高度正好 5 页，flush 的时候不会多带半页
+const DIGIT_WIDTH: i32 = 24;
+const DIGIT_HEIGHT: i32 = 40;
+const DIGIT_TOP: i32 = 8;
+const STROKE: i32 = 4;
+
+/// 四位数字的左边 x 坐标，中间给冒号留了空
+const DIGIT_X: [i32; 4] = [4, 32, 70, 98];
+
+/// 冒号的两个点
+const COLON_X: i32 = 62;
+const COLON_Y: [i32; 2] = [DIGIT_TOP + 12, DIGIT_TOP + 24];
+
+/// 秒点的位置，冒号正下方
+const SECOND_DOT: Point = Point::new(63, 58);
+
+/// 墙上时间：开机时长加一个偏移
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct WallClock {
+    offset_ms: u64,
+    /// 对过时没有
+    set: bool,
+}
+
+impl WallClock {
+    pub const fn new() -> Self {
+        Self {
+            offset_ms: 0,
+            set: false,
+        }
+    }
+
+    /// 对过时了才知道现在真正是几点
+    pub fn is_set(&self) -> bool {
+        self.set
+    }
+
+    /// 一天里的第几分钟，没对过时返回 None
+    pub fn minute_of_day(&self, now_ms: u64) -> Option<u16> {
+        let (hour, minute, _) = self.time(now_ms);
+        self.set.then_some(hour as u16 * 60 + minute as u16)
+    }
+
+    /// 对时：现在是 `hour:minute`，超出范围的按一天取模
+    pub fn set(&mut self, hour: u8, minute: u8, now_ms: u64) {
+        self.set_time(hour, minute, 0, now_ms);
+    }
+
+    /// 对时，精确到秒(从 DS3231 读出来的时间)
+    pub fn set_time(&mut self, hour: u8, minute: u8, second: u8, now_ms: u64) {
+        let target = ((hour as u64 * 60 + minute as u64) * 60 + second as u64) * 1000 % DAY_MS;
+        self.offset_ms = (target + DAY_MS - now_ms % DAY_MS) % DAY_MS;
+        self.set = true;
+    }
+
+    /// 现在的 (时, 分, 秒)
+    pub fn time(&self, now_ms: u64) -> (u8, u8, u8) {
+        let seconds = (now_ms + self.offset_ms) % DAY_MS / 1000;
+        (
+            (seconds / 3600) as u8,
+            (seconds / 60 % 60) as u8,
+            (seconds % 60) as u8,
+        )
+    }
+}
+
+/// 解析 `HH:MM`
+pub fn parse_hh_mm(text: &str) -> Option<(u8, u8)> {
+    let (hour, minute) = text.split_once(':')?;
+    let hour: u8 = hour.parse().ok()?;
+    let minute: u8 = minute.parse().ok()?;
+    (hour < 24 && minute < 60).then_some((hour, minute))
+}
+
+/// 钟面：记着屏幕上现在显示的是什么，只重画变了的部分
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ClockFace {
+    shown: [Option<u8>; 4],
+    dot: Option<bool>,
+}
+
+impl ClockFace {
+    pub const fn new() -> Self {
+        Self {
+            shown: [None; 4],
+            dot: None,
+        }
+    }
+
+    /// 忘掉屏幕上的内容，下一次 `update` 整屏重画
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// 更新到 `hour:minute`，秒点按 `second` 的奇偶亮灭。每一处改动单独 flush，
+    /// 这样两处改动之间的区域不会被带上
+    pub fn update<D>(
+        &mut self,
+        display: &mut D,
+        hour: u8,
+        minute: u8,
+        second: u8,
+    ) -> Result<(), D::Error>
+    where
+        D: BufferedDisplay,
+    {
+        if self.shown == [None; 4] {
+            display.clear_buffer();
+            for y in COLON_Y {
+                Rectangle::new(
+                    Point::new(COLON_X, y),
+                    Size::new(STROKE as u32, STROKE as u32),
+                )
+                .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+                .draw(display)?;
+            }
+            display.flush()?;
+        }
+
+        let digits = [hour / 10, hour % 10, minute / 10, minute % 10];
+        for (cell, digit) in digits.into_iter().enumerate() {
+            if self.shown[cell] == Some(digit) {
+                continue;
+            }
+            draw_digit(display, DIGIT_X[cell], digit)?;
+            display.flush()?;
+            self.shown[cell] = Some(digit);
+        }
+
+        let dot = second.is_multiple_of(2);
+        if self.dot != Some(dot) {
+            let color = if dot {
+                BinaryColor::On
+            } else {
+                BinaryColor::Off
+            };
+            embedded_graphics::Pixel(SECOND_DOT, color).draw(display)?;
+            display.flush()?;
+            self.dot = Some(dot);
+        }
+        Ok(())
+    }
+}
+
+/// 在 `x` 处画一位七段数字，先把整个格子擦掉
+fn draw_digit<D>(display: &mut D, x: i32, digit: u8) -> Result<(), D::Error>
+where
+    D: BufferedDisplay,
+{
+    let cell = Rectangle::new(
+        Point::new(x, DIGIT_TOP),
+        Size::new(DIGIT_WIDTH as u32, DIGIT_HEIGHT as u32),
+    );
+    cell.into_styled(PrimitiveStyle::with_fill(BinaryColor::Off))
+        .draw(display)?;
+    draw_big_digit(display, cell, STROKE, digit)
+}
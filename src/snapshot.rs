@@ -0,0 +1,103 @@
+//! 显存快照：把 1024 字节的显存原样用十六进制导出来，电脑端和"标准答案"逐字节比对
+//!
+//! 用来在真机上查渲染回归：电脑端脚本发 `FB`(串口)或者 `FB DEFMT`(走 `dump_framebuffer`，打到 defmt 日志)，
+//! 把输出存下来，和之前确认过没问题的快照比。两种输出内容一样，都是一行一行的文本(defmt 里数据是 `[00, ff, ...]` 这样的列表)：
+//!
+//! ```text
+//! FB 128x64 crc=1a2b3c4d
+//! FB 00 0000ff81818181ff00...      一行 32 字节，64 个十六进制字符
+//! ...
+//! FB 1f ...
+//! OK                               只有串口有这一行
+//! ```
+//!
+//! 数据行的编号是 00..1f，第 `n` 行是显存的 `n * 32 .. n * 32 + 32` 字节。显存布局和 SSD1306 的 GDDRAM 一样
+//! (见 framebuffer.rs)：
+//!
+//! - 一共 8 页，每页 128 字节，一页正好是 4 行。第 `n` 行是第 `n / 4` 页、第 `(n % 4) * 32` 列开始的 32 列
+//! - 每个字节是一列里的 8 个像素，bit0 在上，bit7 在下；1 是亮
+//! - 用的是物理坐标：旋转、镜像、防烧屏平移都已经画进去了，导出来的就是屏幕上看到的样子(反色除外，反色是屏幕做的)
+//! - 打开了 `overlay-layer` 的话导出的是和叠加层合成以后的，和发给屏幕的一样
+//!
+//! `crc` 是导出来这 1024 字节的 CRC-32，电脑端拼好以后算一遍，对不上就是传丢了。
+//!
+//! 串口一次发不完，所以先 `capture` 拷一份快照，主循环按发送缓冲区的空闲分几圈发完，
+//! 中间页面接着刷新也不会让快照前后对不上。
+
+use core::fmt::Write;
+
+use display_interface::WriteOnlyDataCommand;
+
+use crate::crc::{crc32, Crc32};
+use crate::display::Display;
+use crate::framebuffer::{DirtyRegion, FrameBuffer, BUFFER_LEN, HEIGHT, WIDTH};
+
+/// 一行多少字节
+pub const BYTES_PER_LINE: usize = 32;
+
+/// 一共几行数据(不算头)
+pub const LINES: usize = BUFFER_LEN / BYTES_PER_LINE;
+
+/// 串口一行最长多少字节
+pub const LINE_MAX: usize = 80;
+
+/// 拷出来的一份显存
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    bytes: [u8; BUFFER_LEN],
+    crc: u32,
+}
+
+impl Snapshot {
+    pub fn capture(fb: &FrameBuffer) -> Self {
+        let bytes = fb.composited();
+        Self {
+            crc: crc32(&bytes),
+            bytes,
+        }
+    }
+
+    pub fn bytes(&self) -> &[u8; BUFFER_LEN] {
+        &self.bytes
+    }
+
+    /// 第一行：尺寸和校验
+    pub fn write_header<W: Write>(&self, out: &mut W) -> core::fmt::Result {
+        write!(out, "FB {}x{} crc={:08x}\r\n", WIDTH, HEIGHT, self.crc)
+    }
+
+    /// 第 `line` 行数据，`line` 是 `0..LINES`
+    pub fn write_line<W: Write>(&self, line: usize, out: &mut W) -> core::fmt::Result {
+        write!(out, "FB {:02x} ", line)?;
+        for byte in self.line(line) {
+            write!(out, "{:02x}", byte)?;
+        }
+        out.write_str("\r\n")
+    }
+
+    fn line(&self, line: usize) -> &[u8] {
+        &self.bytes[line * BYTES_PER_LINE..][..BYTES_PER_LINE]
+    }
+}
+
+/// 把现在的显存按上面的格式打到 defmt 日志里。一次打完，一行一行现拷，不用整块拷贝
+pub fn dump_framebuffer<DI: WriteOnlyDataCommand>(display: &Display<DI>) {
+    let fb = display.framebuffer();
+    let mut crc = Crc32::new();
+    fb.region_bytes(DirtyRegion::FULL)
+        .for_each(|byte| crc.update(byte));
+    defmt::info!(
+        "FB {=usize}x{=usize} crc={=u32:08x}",
+        WIDTH,
+        HEIGHT,
+        crc.finish()
+    );
+    let mut bytes = fb.region_bytes(DirtyRegion::FULL);
+    for line in 0..LINES {
+        let mut chunk = [0u8; BYTES_PER_LINE];
+        for (dst, byte) in chunk.iter_mut().zip(&mut bytes) {
+            *dst = byte;
+        }
+        defmt::info!("FB {=usize:02x} {=[u8]:02x}", line, chunk);
+    }
+}
@@ -0,0 +1,130 @@
+//! 话筒频谱页面：最近 `FFT_LEN` 个样本做 FFT，画成 `BARS` 根柱子(feature `mic-vu` 才有数据)
+//!
+//! 演示菜单里进来，Back 回去。每来一个新窗口(20ms)重算一次，最快 `FPS` 帧。
+//! 左边是低频，一根柱子两个频点(8kHz 采样率下 125Hz)，一直到 4kHz，直流那一点不画。
+//! 高度按 dB 算(`vu_meter::dbfs10`，到 `MIN_DB` 为止)，比线性的更看得出小声音；
+//! 柱子往上跳得快、往下落得慢(每次最多落 `FALL_PX`)，看起来不那么闪。
+//!
+//! 柱子直接用 `FrameBuffer::fill_rect` 按字节填，一帧几十根柱子不走逐像素的 `Drawable`。
+
+use core::cell::RefCell;
+use core::convert::Infallible;
+
+use embedded_graphics::geometry::{OriginDimensions, Point, Size};
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::text::{Baseline, Text};
+use embedded_graphics::Drawable;
+
+use crate::app::{Canvas, Event, Page, Transition};
+use crate::fft::{magnitudes, BINS};
+use crate::input::{Button, ButtonEvent};
+use crate::vu_meter::{dbfs10, VuMeter, MIN_DB};
+
+/// 几根柱子
+pub const BARS: usize = 32;
+
+/// 一根柱子几个频点
+const BINS_PER_BAR: usize = BINS / BARS;
+
+/// 最快每秒几帧
+const FPS: u16 = 25;
+
+/// 柱子从哪一行开始往下，上面是标题
+const BARS_TOP: i32 = 12;
+
+/// 每次最多落几像素
+const FALL_PX: u8 = 2;
+
+/// 话筒频谱页面
+pub struct SpectrumPage<'a> {
+    meter: &'a RefCell<VuMeter>,
+    /// 上次算的时候喂到第几个窗口了
+    seen: u32,
+    /// 每根柱子现在多高(像素)
+    heights: [u8; BARS],
+}
+
+impl<'a> SpectrumPage<'a> {
+    pub const fn new(meter: &'a RefCell<VuMeter>) -> Self {
+        Self {
+            meter,
+            seen: 0,
+            heights: [0; BARS],
+        }
+    }
+
+    /// 拿最新的样本重算柱子高度，`max_height` 是柱子区有多高
+    fn update(&mut self, meter: &VuMeter, max_height: u32) {
+        let mut spectrum = [0u16; BINS];
+        magnitudes(meter.samples(), &mut spectrum);
+        spectrum[0] = 0;
+        let range = -(MIN_DB as i32) * 10;
+        for (bar, height) in self.heights.iter_mut().enumerate() {
+            let bins = &spectrum[bar * BINS_PER_BAR..(bar + 1) * BINS_PER_BAR];
+            let peak = bins.iter().copied().max().unwrap_or(0);
+            let target = ((dbfs10(peak) as i32 + range) * max_height as i32 / range) as u8;
+            *height = target.max(height.saturating_sub(FALL_PX));
+        }
+    }
+}
+
+impl Page for SpectrumPage<'_> {
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        match event {
+            Event::Button(ButtonEvent::Pressed(Button::Back)) => Transition::Pop,
+            _ => Transition::None,
+        }
+    }
+
+    fn tick(&mut self, _now_ms: u64) -> bool {
+        self.meter.borrow().windows() != self.seen
+    }
+
+    fn desired_fps(&self) -> u16 {
+        FPS
+    }
+
+    fn has_data(&self) -> bool {
+        self.meter.borrow().windows() > 0
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<(), Infallible> {
+        let meter = self.meter.borrow();
+        let size = canvas.size();
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        if meter.windows() == 0 {
+            let hint = if cfg!(feature = "mic-vu") {
+                "waiting for mic"
+            } else {
+                "mic-vu disabled"
+            };
+            Text::with_baseline("FFT", Point::zero(), style, Baseline::Top).draw(canvas)?;
+            Text::with_baseline(hint, Point::new(0, 24), style, Baseline::Top).draw(canvas)?;
+            return Ok(());
+        }
+
+        let max_height = size.height.saturating_sub(BARS_TOP as u32);
+        if meter.windows() != self.seen {
+            self.seen = meter.windows();
+            self.update(&meter, max_height);
+        }
+        Text::with_baseline("FFT 0-4kHz", Point::zero(), style, Baseline::Top).draw(canvas)?;
+
+        // 屏幕竖着放的时候柱子窄一点，至少一像素
+        let pitch = (size.width / BARS as u32).max(1);
+        let width = pitch.saturating_sub(1).max(1);
+        let bottom = size.height as i32;
+        for (bar, &height) in self.heights.iter().enumerate() {
+            let height = (height as u32).min(max_height);
+            if height == 0 {
+                continue;
+            }
+            let top_left = Point::new(bar as i32 * pitch as i32, bottom - height as i32);
+            canvas.fill_rect(Rectangle::new(top_left, Size::new(width, height)), true);
+        }
+        Ok(())
+    }
+}
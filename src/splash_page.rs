@@ -0,0 +1,122 @@
+//! 开机画面的设置页面：选一个图片槽当开机画面，或者 "none" 用编译进去的 logo(没有的话原来的版本号画面)
+//!
+//! 从设置菜单进来。第一行是 "none"，后面每行一个槽，值是槽里有什么(`empty`、`corrupt`、宽x高)，
+//! 现在用着的那一行前面有个 `*`。Up/Down 选行，Select 选定并保存(`Action::SaveSplash`)，Back 回去。
+//! 空的、坏的槽也能选：开机的时候读不出图就还是显示版本号，等以后往那个槽传了图就会用上。
+
+use core::convert::Infallible;
+use core::fmt::Write;
+
+use embedded_graphics::geometry::Point;
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::text::Text;
+use embedded_graphics::Drawable;
+use heapless::String;
+
+use crate::app::{Action, Canvas, Event, Page, Transition};
+use crate::image_slots::{self, SLOTS};
+use crate::input::{Button, ButtonEvent};
+use crate::widgets::draw_menu;
+
+/// 标题行基线
+const TITLE_Y: i32 = 8;
+
+/// 菜单从哪一行开始
+const MENU_TOP: i32 = 12;
+
+/// "none" 加每个槽一行
+const ROWS: usize = SLOTS + 1;
+
+/// 开机画面设置页面
+#[derive(Debug)]
+pub struct SplashPage {
+    /// 现在用着的槽(从 0 数)
+    current: Option<u8>,
+    selected: usize,
+    pending: Option<Action>,
+}
+
+impl SplashPage {
+    /// `current` 是设置里存的开机画面槽
+    pub const fn new(current: Option<u8>) -> Self {
+        Self {
+            current,
+            selected: 0,
+            pending: None,
+        }
+    }
+
+    fn row_slot(row: usize) -> Option<u8> {
+        row.checked_sub(1).map(|slot| slot as u8)
+    }
+}
+
+impl Page for SplashPage {
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        let Event::Button(ButtonEvent::Pressed(button)) = event else {
+            return Transition::None;
+        };
+        match button {
+            Button::Back => return Transition::Pop,
+            Button::Up => self.selected = (self.selected + ROWS - 1) % ROWS,
+            Button::Down => self.selected = (self.selected + 1) % ROWS,
+            Button::Select => {
+                self.current = Self::row_slot(self.selected);
+                self.pending = Some(Action::SaveSplash(self.current));
+            }
+        }
+        Transition::None
+    }
+
+    /// 槽里的图是串口传的，一秒看一次就够了
+    fn desired_fps(&self) -> u16 {
+        1
+    }
+
+    fn take_action(&mut self) -> Option<Action> {
+        self.pending.take()
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<(), Infallible> {
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        Text::new("splash", Point::new(0, TITLE_Y), style).draw(canvas)?;
+
+        // 先把每一行拼好，菜单只借用字符串
+        let labels: [String<8>; ROWS] = core::array::from_fn(|row| {
+            let mut label = String::new();
+            let mark = if Self::row_slot(row) == self.current {
+                '*'
+            } else {
+                ' '
+            };
+            let _ = label.push(mark);
+            match Self::row_slot(row) {
+                Some(slot) => {
+                    let _ = write!(label, "slot {}", slot + 1);
+                }
+                None => {
+                    let _ = label.push_str("none");
+                }
+            }
+            label
+        });
+        let values: [String<8>; ROWS] = core::array::from_fn(|row| {
+            let mut value = String::new();
+            if let Some(slot) = Self::row_slot(row) {
+                let _ = write!(value, "{}", image_slots::state(slot as usize));
+            }
+            value
+        });
+        draw_menu(
+            canvas,
+            MENU_TOP,
+            labels
+                .iter()
+                .zip(values.iter())
+                .map(|(label, value)| (label.as_str(), value.as_str())),
+            self.selected,
+        )
+    }
+}
@@ -0,0 +1,149 @@
+//! 小动画图标(精灵)：几帧同样大小的 1bpp 小图，按固定的节奏一帧一帧换
+//!
+//! 每一帧的格式和 `image_slots` 一样，就是 embedded-graphics 的 `ImageRaw<BinaryColor>`：
+//! 一行一行从上往下，一个字节 8 个像素、高位在左，1 是亮，每行凑整到字节。
+//! 帧的长度在 `Sprite::new` 里检查，内置的几个是 `static`，写错了编译不过。
+//!
+//! 画的时候亮的、灭的像素都画，新的一帧直接盖掉上一帧；画出屏幕的部分由画布自己丢掉。
+//! 帧号是取模的，随便传多大都行，`frame_at` 按开机以来的毫秒数算出现在该是第几帧。
+//! 页面里一般放一个 `SpriteAnimation`：它数逻辑 tick(见 `timestep`)，页面的 `step` 里让它走一步、
+//! 问它帧变没变(变了才重画)，`render` 里画。
+
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::image::{Image, ImageRaw};
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::Drawable;
+
+use crate::timestep::TICK_MS;
+use crate::typewriter::CURSOR_BLINK_MS;
+
+/// 一个精灵：`frames` 里每一帧都是 `width` x `height`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sprite {
+    width: u32,
+    height: u32,
+    /// 一帧停多久(毫秒)
+    frame_ms: u32,
+    frames: &'static [&'static [u8]],
+}
+
+impl Sprite {
+    /// 至少要有一帧，每一帧的长度要正好是 `ceil(width/8) * height`，`frame_ms` 不能是 0
+    pub const fn new(
+        width: u32,
+        height: u32,
+        frame_ms: u32,
+        frames: &'static [&'static [u8]],
+    ) -> Self {
+        assert!(!frames.is_empty() && frame_ms > 0);
+        let frame_len = width.div_ceil(8) as usize * height as usize;
+        let mut i = 0;
+        while i < frames.len() {
+            assert!(frames[i].len() == frame_len);
+            i += 1;
+        }
+        Self {
+            width,
+            height,
+            frame_ms,
+            frames,
+        }
+    }
+
+    pub fn size(&self) -> Size {
+        Size::new(self.width, self.height)
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// 开机 `now_ms` 毫秒的时候该画第几帧
+    pub fn frame_at(&self, now_ms: u64) -> usize {
+        (now_ms / self.frame_ms as u64 % self.frames.len() as u64) as usize
+    }
+
+    /// 第 `frame` 帧(取模)左上角放在 `origin` 画出来
+    pub fn draw<D>(&self, display: &mut D, origin: Point, frame: usize) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        let raw = ImageRaw::<BinaryColor>::new(self.frames[frame % self.frames.len()], self.width);
+        Image::new(&raw, origin).draw(display)
+    }
+}
+
+/// 跟着逻辑 tick 走的精灵，记着走了几个 tick、上次画的是哪一帧
+#[derive(Debug, Clone, Copy)]
+pub struct SpriteAnimation {
+    sprite: &'static Sprite,
+    ticks: u32,
+    shown: Option<usize>,
+}
+
+impl SpriteAnimation {
+    pub const fn new(sprite: &'static Sprite) -> Self {
+        Self {
+            sprite,
+            ticks: 0,
+            shown: None,
+        }
+    }
+
+    /// 现在该画第几帧
+    fn frame(&self) -> usize {
+        self.sprite.frame_at(self.ticks as u64 * TICK_MS)
+    }
+
+    /// 走一个逻辑 tick，换帧了(或者还没画过)返回 true，页面的 `step` 里用
+    pub fn step(&mut self) -> bool {
+        self.ticks = self.ticks.wrapping_add(1);
+        let frame = self.frame();
+        let changed = self.shown != Some(frame);
+        self.shown = Some(frame);
+        changed
+    }
+
+    /// 下次 `step` 一定返回 true，比如页面被盖住过、要整个重画的时候
+    pub fn reset(&mut self) {
+        self.shown = None;
+    }
+
+    /// 画现在的那一帧
+    pub fn draw<D>(&self, display: &mut D, origin: Point) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        self.sprite.draw(display, origin, self.frame())
+    }
+}
+
+/// 转圈的小棒：| / - \，8x8
+pub static SPINNER: Sprite = Sprite::new(
+    8,
+    8,
+    120,
+    &[
+        &[0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18],
+        &[0x03, 0x07, 0x0E, 0x1C, 0x38, 0x70, 0xE0, 0xC0],
+        &[0x00, 0x00, 0x00, 0xFF, 0xFF, 0x00, 0x00, 0x00],
+        &[0xC0, 0xE0, 0x70, 0x38, 0x1C, 0x0E, 0x07, 0x03],
+    ],
+);
+
+/// 信号格：一格一格涨到四格再从一格开始，8x8，等连接的时候用
+pub static SIGNAL: Sprite = Sprite::new(
+    8,
+    8,
+    300,
+    &[
+        &[0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80, 0x80],
+        &[0x00, 0x00, 0x00, 0x00, 0x20, 0x20, 0xA0, 0xA0],
+        &[0x00, 0x00, 0x08, 0x08, 0x28, 0x28, 0xA8, 0xA8],
+        &[0x02, 0x02, 0x0A, 0x0A, 0x2A, 0x2A, 0xAA, 0xAA],
+    ],
+);
+
+/// 闪烁的方块光标，6x10(和 FONT_6X10 一个字一样大)，亮灭的节奏和打字机的光标一样
+pub static CURSOR: Sprite = Sprite::new(6, 10, CURSOR_BLINK_MS, &[&[0xFC; 10], &[0x00; 10]]);
@@ -0,0 +1,109 @@
+//! 栈用了多少：开机把栈下面没用到的内存刷成一个固定的数，之后看刷的数被冲掉到哪里了
+//!
+//! 内存布局的假设(cortex-m-rt 的链接脚本 + memory.x)：
+//!
+//! - RAM 是 0x2000_0000 开始的 256K(SRAM0-3)，SRAM4/5 不在里面(preflight.rs 拿来测读写)
+//! - 从低到高是 .data、.bss、.uninit，结束的地方是 `__sheap`；堆(`alloc` feature)和 core1 的栈
+//!   都是 `singleton!` 出来的静态变量，在 .bss 里，不在下面说的这一段
+//! - core0 的栈(中断也用它，只有 MSP)从 `_stack_start`(RAM 的最顶上)往下长，一直长到 `__sheap` 都不会有人拦，
+//!   再往下就踩到静态变量了，所以 `__sheap` 到 `_stack_start` 这一段就是栈最多能用多少(`system_info::free_ram_bytes`)
+//!
+//! `paint` 在开机早一点的地方调一次，把 `__sheap` 到当前栈指针往下 `MARGIN` 这一段按字刷成 `PATTERN`。
+//! 之后 `stack_watermark` 从 `__sheap` 往上找第一个不是 `PATTERN` 的字，那里往上就是栈最深到过的地方。
+//! 刷之前已经用掉的栈(`paint` 调用时栈指针往上)一律算用过。找的时候要读一遍没用过的内存(200 多 K，
+//! 125MHz 下大约 1ms)，所以只是主循环里定时调一次，结果存起来给诊断页面用(`usage`)。
+//!
+//! 栈上某个大数组恰好有一段没写过、或者写进去的正好是 `PATTERN` 的话会少算，只能当个大概。
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+/// 刷进去的数
+const PATTERN: u32 = 0xC5C5_C5C5;
+
+/// `paint` 在自己的栈指针下面留多少字节不刷，给它自己调用的东西用
+const MARGIN: u32 = 256;
+
+/// 刷到哪里(不含)，0 是还没刷
+static PAINTED_TOP: AtomicU32 = AtomicU32::new(0);
+
+/// 目前找到的栈最深到过的地址
+static LOWEST: AtomicU32 = AtomicU32::new(0);
+
+/// 栈的使用情况
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct StackUsage {
+    /// 开机以来最多用过多少字节
+    pub peak: usize,
+    /// 栈最多能有多大(`__sheap` 到 `_stack_start`)
+    pub size: usize,
+}
+
+impl StackUsage {
+    /// 还没用过的字节数
+    pub fn free(&self) -> usize {
+        self.size.saturating_sub(self.peak)
+    }
+}
+
+/// 栈能用的那一段：(最低，最高)，都按字对齐
+fn bounds() -> (u32, u32) {
+    extern "C" {
+        static __sheap: u8;
+        static _stack_start: u8;
+    }
+    // 只取地址，不读内容
+    let bottom = core::ptr::addr_of!(__sheap) as u32;
+    let top = core::ptr::addr_of!(_stack_start) as u32;
+    ((bottom + 3) & !3, top & !3)
+}
+
+/// 开机刷一遍，要在栈还浅的时候调，重复调用没有效果
+pub fn paint() {
+    if PAINTED_TOP.load(Ordering::Relaxed) != 0 {
+        return;
+    }
+    let (bottom, _) = bounds();
+    let sp = cortex_m::register::msp::read();
+    let end = sp.saturating_sub(MARGIN) & !3;
+    let mut address = bottom;
+    while address < end {
+        // 安全性：`__sheap` 往上没有静态变量，栈指针往下 MARGIN 以外现在没人用
+        unsafe { (address as *mut u32).write_volatile(PATTERN) };
+        address += 4;
+    }
+    LOWEST.store(end, Ordering::Relaxed);
+    PAINTED_TOP.store(end.max(bottom), Ordering::Relaxed);
+}
+
+/// 重新找一遍，返回开机以来栈最多用过多少字节。没刷过的话是 0
+pub fn stack_watermark() -> usize {
+    let painted = PAINTED_TOP.load(Ordering::Relaxed);
+    if painted == 0 {
+        return 0;
+    }
+    let (bottom, top) = bounds();
+    // 已经知道冲到过的地方往上不用再看
+    let end = LOWEST.load(Ordering::Relaxed).min(painted);
+    let mut address = bottom;
+    while address < end {
+        // 安全性：只读，这一段都在 RAM 里
+        if unsafe { (address as *const u32).read_volatile() } != PATTERN {
+            break;
+        }
+        address += 4;
+    }
+    LOWEST.store(address, Ordering::Relaxed);
+    top.saturating_sub(address) as usize
+}
+
+/// 上一次 `stack_watermark` 找到的结果，不重新找。没刷过是 None
+pub fn usage() -> Option<StackUsage> {
+    if PAINTED_TOP.load(Ordering::Relaxed) == 0 {
+        return None;
+    }
+    let (bottom, top) = bounds();
+    Some(StackUsage {
+        peak: top.saturating_sub(LOWEST.load(Ordering::Relaxed)) as usize,
+        size: top.saturating_sub(bottom) as usize,
+    })
+}
@@ -0,0 +1,165 @@
+//! 星空屏保：一群点从屏幕中间往四周飞，飞出去了再从中间随机冒出来
+//!
+//! 方向和速度都用 `rng` 随机取，方向换成 x/y 分量用的是 `trig` 的定点正弦表。
+//! 画面一直在变，不会在 OLED 上留残影，适合放进自动轮播。
+//! 星每 `TICKS_PER_STEP` 个逻辑 tick 飞一步(见 `timestep`)，画得慢不会飞得慢。
+//! 弹球演示里按 Select 过来，再按 Select 回弹球，Back 回去。
+
+use core::convert::Infallible;
+
+use embedded_graphics::geometry::{OriginDimensions, Point, Size};
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::primitives::{Primitive, PrimitiveStyle, Rectangle};
+use embedded_graphics::Drawable;
+
+use crate::app::{Canvas, Event, Page, PageId, Transition};
+use crate::input::{Button, ButtonEvent};
+use crate::rng::Rng;
+use crate::trig::{cos_q15, sin_q15};
+
+/// 一共几颗星
+const STAR_COUNT: usize = 32;
+
+/// 位置和速度的小数位数(8 位小数，1 像素是 256)
+const FRACTION_BITS: u32 = 8;
+
+/// 几个逻辑 tick 飞一步，50Hz 下一秒 25 步
+const TICKS_PER_STEP: u32 = 2;
+
+/// 每步最慢、最快飞多少(1/256 像素)
+const MIN_SPEED: u32 = 96;
+const MAX_SPEED: u32 = 640;
+
+/// 每步速度乘多少(Q8)，越往外飞得越快，看起来像往前冲
+const ACCELERATION: i32 = 266;
+
+/// 速度超过这个(1/256 像素每步)就画成 2x2 的点，离得近的星大一点
+const BIG_STAR_SPEED: i32 = 900;
+
+/// 刚冒出来的时候离中心最远多少像素
+const SPAWN_SPREAD: u32 = 8;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Star {
+    x: i32,
+    y: i32,
+    vx: i32,
+    vy: i32,
+}
+
+/// 星空页面
+#[derive(Debug)]
+pub struct StarfieldPage {
+    rng: Rng,
+    stars: [Star; STAR_COUNT],
+    /// 按 Select 切去哪个演示
+    other_demo: PageId,
+    /// 还没按屏幕大小撒过星
+    seeded: bool,
+    /// 上一次画的时候屏幕多大，星飞出去就重新冒
+    size: Size,
+}
+
+impl StarfieldPage {
+    pub const fn new(rng: Rng, other_demo: PageId) -> Self {
+        Self {
+            rng,
+            stars: [Star {
+                x: 0,
+                y: 0,
+                vx: 0,
+                vy: 0,
+            }; STAR_COUNT],
+            other_demo,
+            seeded: false,
+            size: Size::zero(),
+        }
+    }
+
+    /// 在屏幕中间附近冒一颗新的，方向随机
+    fn spawn(&mut self, size: Size) -> Star {
+        let angle = self.rng.next_u32() as u16;
+        let speed = (MIN_SPEED + self.rng.next_range(MAX_SPEED - MIN_SPEED)) as i32;
+        let spread = self.rng.next_range(SPAWN_SPREAD) as i32;
+        let (sin, cos) = (sin_q15(angle) as i32, cos_q15(angle) as i32);
+        let center_x = (size.width as i32 / 2) << FRACTION_BITS;
+        let center_y = (size.height as i32 / 2) << FRACTION_BITS;
+        Star {
+            x: center_x + ((spread * cos) >> (15 - FRACTION_BITS)),
+            y: center_y + ((spread * sin) >> (15 - FRACTION_BITS)),
+            vx: (speed * cos) >> 15,
+            vy: (speed * sin) >> 15,
+        }
+    }
+
+    /// 所有星飞一步
+    fn fly(&mut self, size: Size) {
+        let width = (size.width as i32) << FRACTION_BITS;
+        let height = (size.height as i32) << FRACTION_BITS;
+        for i in 0..STAR_COUNT {
+            let mut star = self.stars[i];
+            star.x += star.vx;
+            star.y += star.vy;
+            star.vx = (star.vx * ACCELERATION) >> 8;
+            star.vy = (star.vy * ACCELERATION) >> 8;
+            let outside = star.x < 0 || star.y < 0 || star.x >= width || star.y >= height;
+            self.stars[i] = if outside { self.spawn(size) } else { star };
+        }
+    }
+}
+
+impl Page for StarfieldPage {
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        match event {
+            Event::Button(ButtonEvent::Pressed(Button::Back)) => Transition::Pop,
+            Event::Button(ButtonEvent::Pressed(Button::Select)) => {
+                Transition::Replace(self.other_demo)
+            }
+            _ => Transition::None,
+        }
+    }
+
+    fn desired_fps(&self) -> u16 {
+        25
+    }
+
+    /// 第一次画之前不知道屏幕多大，不飞
+    fn step(&mut self, tick: u32) -> bool {
+        if !self.seeded || !tick.is_multiple_of(TICKS_PER_STEP) {
+            return false;
+        }
+        self.fly(self.size);
+        true
+    }
+
+    /// 星星是从旧的中心飞出来的，按新尺寸重新铺一遍
+    fn on_rotation_changed(&mut self, size: Size) {
+        self.size = size;
+        self.seeded = false;
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<(), Infallible> {
+        let size = canvas.size();
+        self.size = size;
+        if !self.seeded {
+            // 一开始就铺满整个屏幕，不然头几秒所有星都挤在中间
+            self.seeded = true;
+            for i in 0..STAR_COUNT {
+                let mut star = self.spawn(size);
+                let frames = self.rng.next_range(40) as i32;
+                star.x += star.vx * frames;
+                star.y += star.vy * frames;
+                self.stars[i] = star;
+            }
+        }
+        for star in &self.stars {
+            let position = Point::new(star.x >> FRACTION_BITS, star.y >> FRACTION_BITS);
+            let fast = star.vx.abs().max(star.vy.abs()) > BIG_STAR_SPEED;
+            let side = if fast { 2 } else { 1 };
+            Rectangle::new(position, Size::new(side, side))
+                .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+                .draw(canvas)?;
+        }
+        Ok(())
+    }
+}
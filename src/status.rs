@@ -0,0 +1,91 @@
+//! 状态行：一行 `键=值`，电脑上的脚本不用调试器就能看板子现在什么样
+//!
+//! `STATUS` 打一行，`STATUS ON <秒>` 每隔几秒打一行，`STATUS OFF` 停。格式是
+//!
+//! ```text
+//! up=123 temp=24.1 batt=87 scr=dashboard
+//! ```
+//!
+//! 空格分隔，键和值之间是 `=`，值里面没有空格，按空格切开再按第一个 `=` 切开就能解析，人看也看得懂。
+//! `up` 是开机以来的秒数，`temp` 是片内温度(°C，一位小数)，`batt` 是估算的电量(%)，`scr` 是当前页面的名字
+//! (和 `PERF` 里的一样)。读不到的值整个键都不写，新加的键只往后加。格式化不用堆，拼在调用方给的 `String` 里。
+
+use core::fmt::{self, Write};
+
+use heapless::String;
+
+use crate::sensors::ChannelInfo;
+
+/// 一行最长多少字节(含换行)
+pub const LINE_MAX: usize = 64;
+
+/// 温度按一位小数写
+const TEMP: ChannelInfo = ChannelInfo::new("temp", "C", 10);
+
+/// 要写进状态行的东西
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct State<'a> {
+    pub uptime_s: u64,
+    /// 片内温度，单位 0.01°C
+    pub temp_centi: Option<i32>,
+    pub battery_percent: Option<i32>,
+    /// 当前页面的名字
+    pub screen: &'a str,
+}
+
+/// 把状态拼成一行追加到 `buf` 后面，不带换行。放不下返回错误，`buf` 里是写了一半的内容
+pub fn serialize_status<const N: usize>(buf: &mut String<N>, state: &State) -> fmt::Result {
+    write!(buf, "up={}", state.uptime_s)?;
+    if let Some(centi) = state.temp_centi {
+        // 0.01°C 四舍五入到 0.1°C
+        let tenths = (centi + 5 * centi.signum()) / 10;
+        buf.write_str(" temp=")?;
+        TEMP.write_number(tenths, buf)?;
+    }
+    if let Some(percent) = state.battery_percent {
+        write!(buf, " batt={}", percent)?;
+    }
+    write!(buf, " scr={}", state.screen)
+}
+
+/// `STATUS ON` 的节奏
+#[derive(Debug, Default)]
+pub struct StatusReporter {
+    /// 关着的时候是 None
+    interval_ms: Option<u64>,
+    next_ms: u64,
+}
+
+impl StatusReporter {
+    pub const fn new() -> Self {
+        Self {
+            interval_ms: None,
+            next_ms: 0,
+        }
+    }
+
+    /// 每 `seconds` 秒一行，下一次 `is_due` 马上就是 true
+    pub fn start(&mut self, seconds: u16, now_ms: u64) {
+        self.interval_ms = Some(seconds.max(1) as u64 * 1000);
+        self.next_ms = now_ms;
+    }
+
+    pub fn stop(&mut self) {
+        self.interval_ms = None;
+    }
+
+    /// 到时间该打一行了。和遥测一样，主循环卡了不补打
+    pub fn is_due(&mut self, now_ms: u64) -> bool {
+        let Some(interval) = self.interval_ms else {
+            return false;
+        };
+        if now_ms < self.next_ms {
+            return false;
+        }
+        self.next_ms += interval;
+        if self.next_ms <= now_ms {
+            self.next_ms = now_ms + interval;
+        }
+        true
+    }
+}
@@ -0,0 +1,29 @@
+//! 板载状态灯：闪烁码(见 `blink_code`)只算什么时候该亮，灯怎么点亮交给 `StatusLed`
+//!
+//! Pico 和自己画的板子上灯接在 GP25，直接用 GPIO 推挽输出(`BoardLed`)。
+//!
+//! Pico W 的板载灯接在无线芯片 CYW43439 的 GPIO 0 上，要先把无线芯片的固件下载进去，再发控制命令才能点亮，
+//! 打开 `pico-w` feature 以后是 `Cyw43Led`(见 wireless.rs)。只开 `board-pico-w` 不开 `pico-w` 的话没有灯，
+//! 也不带固件。两种板子上闪烁码和主循环都一样，只是 `BoardLed` 换了。
+
+use embedded_hal::digital::OutputPin as _;
+
+use crate::outputs::OutputPin;
+
+/// 一个能开关的状态灯
+pub trait StatusLed {
+    fn set_on(&mut self, on: bool);
+}
+
+/// GPIO 直接驱动的灯，高电平亮
+impl StatusLed for OutputPin {
+    fn set_on(&mut self, on: bool) {
+        let Ok(()) = self.set_state(on.into());
+    }
+}
+
+/// 这块板子上的状态灯类型，`status_led_pin!` 返回的就是它
+#[cfg(any(not(feature = "pico-w"), feature = "board-custom"))]
+pub type BoardLed = OutputPin;
+#[cfg(all(feature = "pico-w", not(feature = "board-custom")))]
+pub type BoardLed = crate::wireless::Cyw43Led;
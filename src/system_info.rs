@@ -0,0 +1,204 @@
+//! 开机硬件小结：时钟、I2C、屏幕、探测到的传感器、USB、还剩多少内存
+//!
+//! 开机横幅和进度条走完以后在屏幕上停 `SCREEN_MS`(按任意键跳过)，同时用 defmt 打一整块(`log`)，
+//! 串口 `SYSINFO` 命令也打同样的几行(`write_line`)。三个地方都从同一个 `SystemInfo` 出来，
+//! 内容是 main.rs 初始化完填的，USB 连没连上会变，串口打之前 main.rs 会更新一下。
+//!
+//! 每一行开头 `OK` 是在、正常，`--` 是没有(没接、没编译进来)。
+
+use core::fmt::{self, Write};
+
+use embedded_graphics::geometry::Point;
+use embedded_graphics::mono_font::ascii::FONT_4X6;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::text::Text;
+use embedded_graphics::Drawable;
+use heapless::String;
+
+use crate::display::BufferedDisplay;
+
+/// 开机画面停多久(毫秒)
+pub const SCREEN_MS: u32 = 3000;
+
+/// 一共几行
+pub const LINES: usize = 10;
+
+/// 串口一行最长多少字节(带 `\r\n`)
+pub const LINE_MAX: usize = 40;
+
+/// 屏幕上的行距，4x6 的字 10 行正好放得下 64 行的屏
+const ROW_PITCH: i32 = 6;
+
+/// USB 的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum UsbState {
+    /// 这次启动没打开 USB
+    Off,
+    /// 打开了，电脑还没配置好
+    Waiting,
+    /// 电脑配置好了，串口能用
+    Configured,
+}
+
+impl UsbState {
+    pub fn name(&self) -> &'static str {
+        match self {
+            UsbState::Off => "off",
+            UsbState::Waiting => "waiting",
+            UsbState::Configured => "configured",
+        }
+    }
+}
+
+/// 硬件小结
+#[derive(Debug, Clone, Copy, defmt::Format)]
+pub struct SystemInfo {
+    /// 板子型号(见 boards.rs)
+    pub board: &'static str,
+    /// 晶振频率，`no-xosc` 编译的是 None
+    pub xosc_hz: Option<u32>,
+    pub sys_hz: u32,
+    pub peri_hz: u32,
+    /// 屏幕接在哪个 I2C 块、哪两个引脚，总线多快
+    pub i2c_block: u8,
+    pub i2c_sda: u8,
+    pub i2c_scl: u8,
+    pub i2c_hz: u32,
+    /// 屏幕的地址、控制器和分辨率，初始化成功没有
+    pub display_address: u8,
+    pub controller: &'static str,
+    pub panel: &'static str,
+    pub display_ok: bool,
+    /// 探测到的传感器型号，没接是 None
+    pub baro: Option<&'static str>,
+    pub humidity: Option<&'static str>,
+    pub distance: Option<&'static str>,
+    pub usb: UsbState,
+    /// 静态变量以外还剩多少内存(字节)，栈也在这里面，见 `free_ram_bytes`
+    pub free_ram: u32,
+}
+
+/// 内存里静态变量后面到栈顶的字节数，栈是从上往下长的，所以这是栈加上没用到的内存一共多少
+pub fn free_ram_bytes() -> u32 {
+    extern "C" {
+        // cortex-m-rt 的链接脚本给的：静态变量(.data、.bss、.uninit)结束的地方和栈顶
+        static __sheap: u8;
+        static _stack_start: u8;
+    }
+    // 只取地址，不读内容
+    let start = core::ptr::addr_of!(__sheap) as u32;
+    let end = core::ptr::addr_of!(_stack_start) as u32;
+    end.saturating_sub(start)
+}
+
+impl SystemInfo {
+    /// 第 `index` 行：在不在、名字、内容
+    fn line(&self, index: usize) -> Option<(bool, &'static str, String<32>)> {
+        let mut value = String::new();
+        let ok = match index {
+            0 => {
+                let _ = value.push_str(self.board);
+                true
+            }
+            1 => match self.xosc_hz {
+                Some(hz) => {
+                    let _ = write!(value, "{} MHz", hz / 1_000_000);
+                    true
+                }
+                None => {
+                    let _ = value.push_str("none (ROSC)");
+                    false
+                }
+            },
+            2 => {
+                let _ = write!(
+                    value,
+                    "sys {} / peri {} MHz",
+                    self.sys_hz / 1_000_000,
+                    self.peri_hz / 1_000_000
+                );
+                true
+            }
+            3 => {
+                let _ = write!(
+                    value,
+                    "I2C{} GP{}/GP{} {} kHz",
+                    self.i2c_block,
+                    self.i2c_sda,
+                    self.i2c_scl,
+                    self.i2c_hz / 1000
+                );
+                true
+            }
+            4 => {
+                let _ = write!(
+                    value,
+                    "{:#04x} {} {}",
+                    self.display_address, self.controller, self.panel
+                );
+                self.display_ok
+            }
+            5..=7 => {
+                let sensor = [self.baro, self.humidity, self.distance][index - 5];
+                let _ = value.push_str(sensor.unwrap_or("none"));
+                sensor.is_some()
+            }
+            8 => {
+                let _ = value.push_str(self.usb.name());
+                self.usb != UsbState::Off
+            }
+            9 => {
+                let _ = write!(value, "{} K free", self.free_ram / 1024);
+                true
+            }
+            _ => return None,
+        };
+        let label = [
+            "board", "xosc", "clocks", "bus", "oled", "baro", "humid", "dist", "usb", "ram",
+        ][index];
+        Some((ok, label, value))
+    }
+
+    /// 第 `index` 行(带 `\r\n`)
+    pub fn write_line<W: fmt::Write>(&self, index: usize, out: &mut W) -> fmt::Result {
+        let Some((ok, label, value)) = self.line(index) else {
+            return Ok(());
+        };
+        write!(out, "{} {:<6} {}\r\n", marker(ok), label, value)
+    }
+
+    /// 用 defmt 打一整块，开机的时候调
+    pub fn log(&self) {
+        defmt::info!("system info:");
+        for index in 0..LINES {
+            if let Some((ok, label, value)) = self.line(index) {
+                defmt::info!("  {=str} {=str} {=str}", marker(ok), label, value.as_str());
+            }
+        }
+    }
+
+    /// 开机画面，画完立刻 flush
+    pub fn draw<D: BufferedDisplay>(&self, display: &mut D) -> Result<(), D::Error> {
+        display.clear_buffer();
+        let style = MonoTextStyle::new(&FONT_4X6, BinaryColor::On);
+        for index in 0..LINES {
+            let Some((ok, label, value)) = self.line(index) else {
+                continue;
+            };
+            let mut text: String<40> = String::new();
+            let _ = write!(text, "{} {:<6} {}", marker(ok), label, value);
+            let y = 5 + index as i32 * ROW_PITCH;
+            Text::new(&text, Point::new(0, y), style).draw(display)?;
+        }
+        display.flush()
+    }
+}
+
+fn marker(ok: bool) -> &'static str {
+    if ok {
+        "OK"
+    } else {
+        "--"
+    }
+}
@@ -0,0 +1,172 @@
+//! 串口遥测：按固定频率往 CDC 串口打 CSV，电脑上用现成的串口绘图工具就能画曲线
+//!
+//! `TELEM ON 10` 打开(每秒 10 行)，`TELEM OFF` 关掉，`TELEM FIELDS temp,vsys` 选要哪几列。
+//! 第一列永远是开机以来的毫秒数，后面是传感器注册表(`sensors`)里的通道，最后可以加一列主循环耗时(`frame`)，
+//! 打开或者改列的时候先打一行表头。打的是注册表里的最新读数，每个通道多久更新一次由注册的时候定。
+//!
+//! 遥测绝不能卡住界面：串口发送缓冲区放不下一整行就把这一行扔掉，扔掉的行数显示在诊断页面上。
+//! 解析和格式化都不用堆。
+
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::sensors::{ChannelId, SensorRegistry};
+
+/// 最高每秒几行，再快 USB 全速也跟不上了
+pub const MAX_RATE_HZ: u16 = 100;
+
+/// 一行最长多少字节(含换行)
+pub const LINE_MAX: usize = 128;
+
+/// 因为发送缓冲区满了扔掉的行数，诊断页面要看，所以放在静态区
+static DROPPED: AtomicU32 = AtomicU32::new(0);
+
+/// 一共扔掉了几行
+pub fn dropped_lines() -> u32 {
+    DROPPED.load(Ordering::Relaxed)
+}
+
+/// 记一次扔行。只在主循环里调用，读和写之间不会被打断(M0+ 没有原子加)
+pub fn record_drop() {
+    DROPPED.store(dropped_lines().wrapping_add(1), Ordering::Relaxed);
+}
+
+/// 选中的列(位图)：第 i 位是注册表里的第 i 个通道，最高位是主循环耗时
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Fields(u16);
+
+impl Default for Fields {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl Fields {
+    pub const ALL: Fields = Fields(u16::MAX);
+
+    /// 主循环最长一圈用了多少微秒(上一行到这一行之间)，命令里叫 "frame"
+    const FRAME: u16 = 1 << 15;
+
+    pub const fn contains(self, channel: ChannelId) -> bool {
+        self.0 & (1 << channel.0) != 0
+    }
+
+    pub const fn has_frame(self) -> bool {
+        self.0 & Self::FRAME != 0
+    }
+
+    /// 解析 `temp,vsys,frame` 这种逗号分隔的列表，不区分大小写，通道名用 `find` 查。
+    /// 有不认识的名字或者一个都没选返回 None
+    pub fn parse(list: &str, find: impl Fn(&str) -> Option<ChannelId>) -> Option<Self> {
+        let mut bits = 0;
+        for name in list.split(',').map(str::trim) {
+            bits |= if name.eq_ignore_ascii_case("frame") {
+                Self::FRAME
+            } else {
+                1 << find(name)?.0
+            };
+        }
+        (bits != 0).then_some(Fields(bits))
+    }
+}
+
+/// 写表头(带 `\r\n`)，通道的列名是 "名字_单位"
+pub fn write_header<S, W: Write>(
+    fields: Fields,
+    sensors: &SensorRegistry<S>,
+    out: &mut W,
+) -> fmt::Result {
+    out.write_str("time_ms")?;
+    for (i, channel) in sensors.channels().iter().enumerate() {
+        if fields.contains(ChannelId(i as u8)) {
+            write!(out, ",{}_{}", channel.info().name, channel.info().unit)?;
+        }
+    }
+    if fields.has_frame() {
+        out.write_str(",frame_us")?;
+    }
+    out.write_str("\r\n")
+}
+
+/// 写一行数据(带 `\r\n`)，用的是注册表里的最新读数，不可用的通道那一列是空的
+pub fn write_line<S, W: Write>(
+    fields: Fields,
+    sensors: &SensorRegistry<S>,
+    now_ms: u64,
+    frame_us: u32,
+    out: &mut W,
+) -> fmt::Result {
+    write!(out, "{}", now_ms)?;
+    for (i, channel) in sensors.channels().iter().enumerate() {
+        if !fields.contains(ChannelId(i as u8)) {
+            continue;
+        }
+        out.write_char(',')?;
+        if let Some(value) = channel.value() {
+            channel.info().write_number(value, out)?;
+        }
+    }
+    if fields.has_frame() {
+        write!(out, ",{}", frame_us)?;
+    }
+    out.write_str("\r\n")
+}
+
+/// 遥测的开关和节奏
+#[derive(Debug, Default)]
+pub struct Telemetry {
+    /// 关着的时候是 None
+    interval_ms: Option<u64>,
+    fields: Fields,
+    next_ms: u64,
+}
+
+impl Telemetry {
+    pub const fn new() -> Self {
+        Self {
+            interval_ms: None,
+            fields: Fields::ALL,
+            next_ms: 0,
+        }
+    }
+
+    /// 按每秒 `rate_hz` 行打开(超过 `MAX_RATE_HZ` 按最高算)，下一次 `is_due` 马上就是 true
+    pub fn start(&mut self, rate_hz: u16, now_ms: u64) {
+        let rate = rate_hz.clamp(1, MAX_RATE_HZ) as u64;
+        self.interval_ms = Some(1000 / rate);
+        self.next_ms = now_ms;
+    }
+
+    pub fn stop(&mut self) {
+        self.interval_ms = None;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.interval_ms.is_some()
+    }
+
+    pub fn fields(&self) -> Fields {
+        self.fields
+    }
+
+    pub fn set_fields(&mut self, fields: Fields) {
+        self.fields = fields;
+    }
+
+    /// 到时间该打一行了。返回 true 的同时定好下一次的时间
+    ///
+    /// 主循环卡了一会儿的话不补打，直接从现在开始重新算
+    pub fn is_due(&mut self, now_ms: u64) -> bool {
+        let Some(interval) = self.interval_ms else {
+            return false;
+        };
+        if now_ms < self.next_ms {
+            return false;
+        }
+        self.next_ms += interval;
+        if self.next_ms <= now_ms {
+            self.next_ms = now_ms + interval;
+        }
+        true
+    }
+}
@@ -0,0 +1,482 @@
+//! 文字排版相关的小工具
+//!
+//! embedded-graphics 的 MonoFont 是等宽字体，所以一段文字的像素宽度可以直接算出来，
+//! 居中、右对齐、截断、自动换行这些操作都基于 `text_pixel_width`。竖排文字(`draw_vertical`)按字高往下排。
+//!
+//! 字体只有 ASCII，别的字(中文、全角符号)画出来是乱的或者干脆没有，控制字符(串口里混进来的 `\r`、`\x1b`)也一样。
+//! 所以这里画字的函数都先过一遍 `sanitize`：认识的字原样留着，不认识的换成 `PLACEHOLDER`，控制字符扔掉。
+//! 别的地方直接用 `Text` 画外面来的字符串(串口、电脑发来的)也应该先过一遍。
+
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::mono_font::{MonoFont, MonoTextStyle};
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::text::{Baseline, Text};
+use embedded_graphics::Drawable;
+use heapless::String;
+
+use crate::clip::with_clip;
+
+/// 文字被截断时在末尾补的省略号
+pub const ELLIPSIS: &str = "..";
+
+/// 字体里没有的字换成这个
+pub const PLACEHOLDER: char = '?';
+
+/// 画字的函数一次最多过滤多少字节，再长的截掉。一行字怎么也放不下这么多
+pub const SANITIZE_CAPACITY: usize = 128;
+
+/// 字体里有的字：可见的 ASCII 和空格
+pub fn is_printable(c: char) -> bool {
+    c == ' ' || c.is_ascii_graphic()
+}
+
+/// 按 `is_printable` 过滤一段文字，写进 `buf` 里返回，见 `sanitize_with`。`\n` 留着，换行要用
+pub fn sanitize<'a, const N: usize>(input: &str, buf: &'a mut String<N>) -> &'a str {
+    sanitize_with(input, buf, |c| c == '\n' || is_printable(c), PLACEHOLDER)
+}
+
+/// 按自己的白名单过滤：`allowed` 的字原样留着，别的控制字符扔掉，剩下的换成 `placeholder`。
+/// `buf` 先清空，放不下的截掉。原来有字、过滤完一个都不剩(全是控制字符)的话返回一个 `placeholder`，
+/// 免得看起来像什么都没收到
+pub fn sanitize_with<'a, const N: usize>(
+    input: &str,
+    buf: &'a mut String<N>,
+    allowed: impl Fn(char) -> bool,
+    placeholder: char,
+) -> &'a str {
+    buf.clear();
+    for c in input.chars() {
+        let kept = if allowed(c) {
+            c
+        } else if c.is_control() {
+            continue;
+        } else {
+            placeholder
+        };
+        if buf.push(kept).is_err() {
+            break;
+        }
+    }
+    if buf.is_empty() && !input.is_empty() {
+        let _ = buf.push(placeholder);
+    }
+    buf.as_str()
+}
+
+/// 计算一段文字用指定字体画出来有多宽(像素)
+///
+/// 宽度 = 字符数 * 字宽 + (字符数 - 1) * 字间距，空字符串宽度为 0
+pub fn text_pixel_width(text: &str, font: &MonoFont) -> u32 {
+    let count = text.chars().count() as u32;
+    if count == 0 {
+        return 0;
+    }
+    count * font.character_size.width + (count - 1) * font.character_spacing
+}
+
+/// 返回 `text` 在 `max_width` 像素以内能放下的最长前缀(按字符边界切，不会切坏 UTF-8)
+pub fn fit_prefix<'a>(text: &'a str, font: &MonoFont, max_width: u32) -> &'a str {
+    let mut end = 0;
+    for (idx, c) in text.char_indices() {
+        let next = idx + c.len_utf8();
+        if text_pixel_width(&text[..next], font) > max_width {
+            break;
+        }
+        end = next;
+    }
+    &text[..end]
+}
+
+/// 让文字在 `area_width` 宽的区域里水平居中时的起始 x 坐标
+///
+/// 文字比区域还宽的时候返回 0，也就是靠左画，超出部分交给调用方截断
+pub fn centered_x(text: &str, font: &MonoFont, area_width: u32) -> i32 {
+    let width = text_pixel_width(text, font);
+    (area_width.saturating_sub(width) / 2) as i32
+}
+
+/// 在第 `y` 行(基线坐标)水平居中画一段文字
+///
+/// 放不下的话会截断并在末尾补 `ELLIPSIS`，保证不会画出屏幕右边
+pub fn draw_centered<D>(
+    display: &mut D,
+    text: &str,
+    y: i32,
+    style: MonoTextStyle<'_, BinaryColor>,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let mut buf: String<SANITIZE_CAPACITY> = String::new();
+    let text = sanitize(text, &mut buf);
+    let width = display.bounding_box().size.width;
+    let font = style.font;
+
+    if text_pixel_width(text, font) <= width {
+        let x = centered_x(text, font, width);
+        Text::new(text, Point::new(x, y), style).draw(display)?;
+        return Ok(());
+    }
+
+    // 放不下：先留出省略号的位置，再画能放下的那部分
+    let ellipsis_width = text_pixel_width(ELLIPSIS, font) + font.character_spacing;
+    let prefix = fit_prefix(text, font, width.saturating_sub(ellipsis_width));
+    let next = Text::new(prefix, Point::new(0, y), style).draw(display)?;
+    Text::new(ELLIPSIS, next, style).draw(display)?;
+    Ok(())
+}
+
+/// 右对齐画一段文字：最后一个字的最右一列像素在 `right_x`，`y` 是基线坐标
+///
+/// 比 `right_x` 左边的空间还宽的话左边会画出屏幕，不截断
+pub fn draw_right_aligned<D>(
+    display: &mut D,
+    text: &str,
+    right_x: i32,
+    y: i32,
+    style: MonoTextStyle<'_, BinaryColor>,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let mut buf: String<SANITIZE_CAPACITY> = String::new();
+    let text = sanitize(text, &mut buf);
+    let x = right_x + 1 - text_pixel_width(text, style.font) as i32;
+    Text::new(text, Point::new(x, y), style).draw(display)?;
+    Ok(())
+}
+
+/// 竖排：一个字一个字往下排，字本身不转，每个字比上一个低一个字高。`origin` 是第一个字的左上角
+///
+/// 放不下的字(下边超出屏幕底部)和后面的都不画，返回画了几个字。`\n` 当成普通字符，不换列
+pub fn draw_vertical<D>(
+    display: &mut D,
+    text: &str,
+    origin: Point,
+    style: MonoTextStyle<'_, BinaryColor>,
+) -> Result<u32, D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let mut sanitized: String<SANITIZE_CAPACITY> = String::new();
+    let text = sanitize(text, &mut sanitized);
+    let line_height = style.font.character_size.height as i32;
+    let bottom = display.bounding_box().size.height as i32;
+    let mut count = 0;
+    let mut buf = [0u8; 4];
+    for c in text.chars() {
+        let position = origin + Point::new(0, count as i32 * line_height);
+        if position.y + line_height > bottom {
+            break;
+        }
+        let glyph: &str = c.encode_utf8(&mut buf);
+        Text::with_baseline(glyph, position, style, Baseline::Top).draw(display)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// 把一段文字按像素宽度切成多行，见 `wrap_lines`
+#[derive(Debug, Clone)]
+pub struct WrapLines<'a, 'f> {
+    rest: &'a str,
+    font: &'f MonoFont<'f>,
+    max_width: u32,
+}
+
+impl<'a> Iterator for WrapLines<'a, '_> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        // 行首的空格没有意义，直接跳过；`\n` 只作为换行符，本身不画
+        let rest = self.rest.trim_start_matches(' ');
+        if rest.is_empty() {
+            self.rest = rest;
+            return None;
+        }
+        if let Some(after) = rest.strip_prefix('\n') {
+            self.rest = after;
+            return Some("");
+        }
+
+        let prefix = fit_prefix(rest, self.font, self.max_width);
+        let end = if let Some(idx) = prefix.find('\n') {
+            // 放得下的部分里有换行符，就在那里断开
+            idx
+        } else if prefix.len() == rest.len() || rest[prefix.len()..].starts_with([' ', '\n']) {
+            // 剩下的全放得下，或者正好断在单词边界上
+            prefix.len()
+        } else if let Some(space) = prefix.rfind(' ') {
+            // 断在最后一个空格，下一个单词放到下一行
+            space
+        } else if prefix.is_empty() {
+            // 一个字都放不下(区域比一个字还窄)，至少画一个字，不然永远走不完
+            rest.chars().next().map_or(rest.len(), char::len_utf8)
+        } else {
+            // 单词比一整行还长，只能硬切
+            prefix.len()
+        };
+
+        // 断行处的空格和一个换行符都算这一行的，不然下一行会多出一个空行
+        let tail = rest[end..].trim_start_matches(' ');
+        self.rest = tail.strip_prefix('\n').unwrap_or(tail);
+        Some(rest[..end].trim_end_matches(' '))
+    }
+}
+
+/// 按空格自动换行：一个单词一个单词往行里放，再放一个就超过 `max_width` 的时候换行；
+/// 比一整行还长的单词会被硬切开。文字里的 `\n` 会强制换行
+pub fn wrap_lines<'a, 'f>(
+    text: &'a str,
+    font: &'f MonoFont<'f>,
+    max_width: u32,
+) -> WrapLines<'a, 'f> {
+    WrapLines {
+        rest: text,
+        font,
+        max_width,
+    }
+}
+
+/// 在 `max_width` 宽的范围里自动换行画一段文字，`origin` 是第一行的左上角，行高就是字高
+///
+/// 返回画了几行，调用方可以用 `origin.y + 行数 * 字高` 判断有没有超出屏幕底部。
+/// 只画在 `origin` 往右 `max_width`、往下到屏幕底的范围里，不会压到旁边的东西。
+/// 文字可能很长(比如更新说明)，所以是切好行以后一行一行地 `sanitize`
+pub fn draw_wrapped<D>(
+    display: &mut D,
+    text: &str,
+    origin: Point,
+    max_width: u32,
+    style: MonoTextStyle<'_, BinaryColor>,
+) -> Result<u32, D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let line_height = style.font.character_size.height as i32;
+    let bottom = display
+        .bounding_box()
+        .size
+        .height
+        .saturating_sub(origin.y.max(0) as u32);
+    let area = Rectangle::new(origin, Size::new(max_width, bottom));
+    let mut lines = 0;
+    with_clip(display, area, |display| {
+        let mut buf: String<SANITIZE_CAPACITY> = String::new();
+        for line in wrap_lines(text, style.font, max_width) {
+            let position = origin + Point::new(0, lines as i32 * line_height);
+            let line = sanitize(line, &mut buf);
+            Text::with_baseline(line, position, style, Baseline::Top).draw(display)?;
+            lines += 1;
+        }
+        Ok(lines)
+    })
+}
+
+/// 堆上的版本(`alloc` feature)：结果要长期保存(比如页面标题、歌名)，长度又没法事先确定的时候用
+#[cfg(feature = "alloc")]
+pub mod owned {
+    use alloc::borrow::Cow;
+    use alloc::boxed::Box;
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    use embedded_graphics::mono_font::MonoFont;
+
+    use super::{fit_prefix, text_pixel_width, wrap_lines, ELLIPSIS};
+
+    /// 放得下就原样返回，放不下就截断并补上省略号，和 `draw_centered` 的截断规则一样
+    pub fn ellipsize<'a>(text: &'a str, font: &MonoFont, max_width: u32) -> Cow<'a, str> {
+        if text_pixel_width(text, font) <= max_width {
+            return Cow::Borrowed(text);
+        }
+        let ellipsis_width = text_pixel_width(ELLIPSIS, font) + font.character_spacing;
+        let mut out = String::from(fit_prefix(
+            text,
+            font,
+            max_width.saturating_sub(ellipsis_width),
+        ));
+        out.push_str(ELLIPSIS);
+        Cow::Owned(out)
+    }
+
+    /// 换行之后每一行单独存一份，适合排版一次、画很多次的长文本
+    pub fn wrap_owned(text: &str, font: &MonoFont, max_width: u32) -> Vec<Box<str>> {
+        wrap_lines(text, font, max_width).map(Box::from).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_graphics::mock_display::MockDisplay;
+    use embedded_graphics::mono_font::ascii::FONT_6X10;
+    use embedded_graphics::mono_font::MonoTextStyleBuilder;
+    use ssd1306::prelude::DisplayRotation;
+
+    use crate::framebuffer::{FrameBuffer, WIDTH};
+    use crate::recording::{DrawOp, RecordingTarget};
+
+    /// FONT_6X10 画出来的 "A"、"g" 和非 ASCII 换成的 "?"，一个字 6x10，`#` 亮 `.` 灭。
+    /// 字体或者坐标换算一改这里就对不上
+    const GLYPHS: [&str; 10] = [
+        "..................",
+        "..#..........###..",
+        ".#.#........#...#.",
+        "#...#..####....#..",
+        "#...#.#...#...#...",
+        "#####.#...#...#...",
+        "#...#..####.......",
+        "#...#.....#...#...",
+        "......#...#.......",
+        ".......###........",
+    ];
+
+    fn style() -> MonoTextStyle<'static, BinaryColor> {
+        MonoTextStyle::new(&FONT_6X10, BinaryColor::On)
+    }
+
+    #[test]
+    fn sanitize_passes_printable_ascii_through() {
+        let mut buf: String<SANITIZE_CAPACITY> = String::new();
+        let text = "Hello, World! 0123 ~{}";
+        assert_eq!(sanitize(text, &mut buf), text);
+        assert_eq!(sanitize("two\nlines", &mut buf), "two\nlines");
+        assert_eq!(sanitize("", &mut buf), "");
+    }
+
+    #[test]
+    fn sanitize_replaces_non_ascii_and_drops_controls() {
+        let mut buf: String<SANITIZE_CAPACITY> = String::new();
+        assert_eq!(sanitize("温度 25°C", &mut buf), "?? 25?C");
+        assert_eq!(sanitize("abc\r\x1b[2J\t", &mut buf), "abc[2J");
+        // 全是控制字符的也要看得出来收到过东西
+        assert_eq!(sanitize("\r\x07", &mut buf), "?");
+        assert_eq!(
+            sanitize_with("a-b c", &mut buf, |c| c.is_ascii_alphabetic(), '_'),
+            "a_b_c"
+        );
+    }
+
+    #[test]
+    fn sanitize_truncates_at_capacity() {
+        let long = "x".repeat(SANITIZE_CAPACITY + 10);
+        let mut buf: String<SANITIZE_CAPACITY> = String::new();
+        assert_eq!(sanitize(&long, &mut buf).len(), SANITIZE_CAPACITY);
+
+        // 按字节算容量：放不下的多字节字符整个不要，换成的占位符是一个字节
+        let mut small: String<4> = String::new();
+        assert_eq!(sanitize("abcdef", &mut small), "abcd");
+        assert_eq!(sanitize("ab\r\ncd", &mut small), "ab\nc");
+        assert_eq!(
+            sanitize_with("aaaé", &mut small, |c| c == 'a' || c == 'é', '?'),
+            "aaa"
+        );
+    }
+
+    #[test]
+    fn pixel_width_counts_characters_not_bytes() {
+        assert_eq!(text_pixel_width("", &FONT_6X10), 0);
+        assert_eq!(text_pixel_width("v0.1.0", &FONT_6X10), 36);
+        assert_eq!(text_pixel_width("é", &FONT_6X10), 6);
+    }
+
+    #[test]
+    fn fit_prefix_stops_at_the_last_whole_character() {
+        assert_eq!(fit_prefix("ABCDEF", &FONT_6X10, 24), "ABCD");
+        assert_eq!(fit_prefix("ABCDEF", &FONT_6X10, 23), "ABC");
+        assert_eq!(fit_prefix("ABCDEF", &FONT_6X10, 100), "ABCDEF");
+        assert_eq!(fit_prefix("ééé", &FONT_6X10, 12), "éé");
+        assert_eq!(fit_prefix("ABC", &FONT_6X10, 0), "");
+    }
+
+    #[test]
+    fn centered_x_splits_the_spare_width() {
+        assert_eq!(centered_x("ABCD", &FONT_6X10, 128), 52);
+        assert_eq!(centered_x("ABC", &FONT_6X10, 20), 1);
+        assert_eq!(centered_x("ABCDEFGHIJKLMNOPQRSTUVWXYZ", &FONT_6X10, 64), 0);
+    }
+
+    #[test]
+    fn draw_wrapped_renders_known_glyphs() {
+        // 背景也画上，没亮的点也能对
+        let style = MonoTextStyleBuilder::new()
+            .font(&FONT_6X10)
+            .text_color(BinaryColor::On)
+            .background_color(BinaryColor::Off)
+            .build();
+        let mut display = MockDisplay::new();
+        assert_eq!(
+            draw_wrapped(&mut display, "Ag\u{e9}", Point::zero(), 60, style),
+            Ok(1)
+        );
+        display.assert_pattern(&GLYPHS);
+    }
+
+    #[test]
+    fn glyph_lands_on_the_right_physical_pixels() {
+        let origin = Point::new(10, 20);
+        for rotation in [DisplayRotation::Rotate0, DisplayRotation::Rotate90] {
+            let mut fb = FrameBuffer::new(rotation);
+            draw_wrapped(&mut fb, "A", origin, 60, style()).unwrap();
+            for (dy, row) in GLYPHS.iter().enumerate() {
+                for (dx, pixel) in row.bytes().take(6).enumerate() {
+                    let (x, y) = (origin.x as usize + dx, origin.y as usize + dy);
+                    // 显存按屏幕物理方向存，转 90 度的时候逻辑上的行是物理上的列
+                    let (col, row) = match rotation {
+                        DisplayRotation::Rotate90 => (y, x),
+                        _ => (x, y),
+                    };
+                    let byte = fb.as_bytes()[row / 8 * WIDTH + col];
+                    assert_eq!(
+                        byte & (1 << (row % 8)) != 0,
+                        pixel == b'#',
+                        "{rotation:?} glyph pixel ({dx}, {dy})"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn glyph_draws_only_its_lit_pixels() {
+        // 没有背景色的字只发亮的点，而且不出这个字的格子
+        let mut target: RecordingTarget<4> = RecordingTarget::new(Size::new(128, 64));
+        draw_wrapped(&mut target, "A", Point::new(6, 0), 60, style()).unwrap();
+        assert_eq!(
+            target.ops(),
+            [DrawOp::Pixels {
+                bounds: Rectangle::new(Point::new(6, 1), Size::new(5, 7)),
+                count: 16,
+                lit: 16,
+            }]
+        );
+    }
+
+    #[test]
+    fn draw_centered_centers_text_that_fits() {
+        let mut display = MockDisplay::new();
+        draw_centered(&mut display, "ABCD", 20, style()).unwrap();
+
+        let mut expected = MockDisplay::new();
+        Text::new("ABCD", Point::new(20, 20), style())
+            .draw(&mut expected)
+            .unwrap();
+        display.assert_eq(&expected);
+    }
+
+    #[test]
+    fn draw_centered_truncates_with_ellipsis_inside_the_screen() {
+        // MockDisplay 是 64 宽，画出去会 panic
+        let mut display = MockDisplay::new();
+        draw_centered(&mut display, "rp2040-i2c-oled-rust", 20, style()).unwrap();
+
+        let mut expected = MockDisplay::new();
+        Text::new("rp2040-i..", Point::new(0, 20), style())
+            .draw(&mut expected)
+            .unwrap();
+        display.assert_eq(&expected);
+    }
+}
@@ -0,0 +1,149 @@
+//! 只重画变了的字：给刷新很勤、每次只变一两个字的文字行用(计数器、状态行)
+//!
+//! 整行重画的话，哪怕只有最后一位数变了，从第一个变了的字到最后一个变了的字之间都会被带进脏区域。
+//! `TrackedText` 记着上次画的是什么，新字符串按字符一个格子一个格子地比：只有变了的格子先擦掉再画，
+//! 返回的矩形刚好盖住这些格子，配合按脏区域 flush(见 `framebuffer`)，一个 10Hz 的计数器每次只要发几个字节，
+//! 开 trace 日志看 `flush N bytes` 就能看到。
+//!
+//! 只支持等宽字体(`MonoFont`)，格子宽度是字宽加字间距。字符串变短了，多出来的尾巴会擦掉；
+//! 换了字体整个重画(先按老字体擦掉老的那一段)。
+//!
+//! 这个类型不管屏幕上别的东西：画它的地方不能每帧先清屏(页面的 `render` 之前调度器会清屏，
+//! 在那里用就和直接画一样了)，适合床头钟这种自己管屏幕、只改局部的地方。屏幕被别人清过以后调 `invalidate`。
+
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::mono_font::{MonoFont, MonoTextStyle};
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::primitives::{Primitive, PrimitiveStyle, Rectangle};
+use embedded_graphics::text::{Baseline, Text};
+use embedded_graphics::Drawable;
+use heapless::String;
+
+/// 一行记着上次内容的文字，最多 `N` 字节，多的截掉
+pub struct TrackedText<const N: usize> {
+    /// 第一个格子的左上角
+    origin: Point,
+    font: &'static MonoFont<'static>,
+    /// 屏幕上现在是什么，None 表示不知道(下次整个重画)
+    shown: Option<String<N>>,
+    /// 换字体之前老的那一段占的地方，下次 `update` 先擦掉
+    stale: Option<Rectangle>,
+}
+
+impl<const N: usize> TrackedText<N> {
+    /// `origin` 是第一个字的左上角
+    pub const fn new(origin: Point, font: &'static MonoFont<'static>) -> Self {
+        Self {
+            origin,
+            font,
+            shown: None,
+            stale: None,
+        }
+    }
+
+    /// 一个格子多宽(字宽 + 字间距)
+    fn cell_width(&self) -> u32 {
+        self.font.character_size.width + self.font.character_spacing
+    }
+
+    /// 从第 `first` 个格子开始的 `count` 个格子
+    fn cells(&self, first: usize, count: usize) -> Rectangle {
+        let width = self.cell_width();
+        Rectangle::new(
+            self.origin + Point::new((first as u32 * width) as i32, 0),
+            Size::new(count as u32 * width, self.font.character_size.height),
+        )
+    }
+
+    /// 换字体。和现在的一样就什么都不做，不然下次 `update` 整个重画
+    pub fn set_font(&mut self, font: &'static MonoFont<'static>) {
+        if core::ptr::eq(font, self.font) {
+            return;
+        }
+        if let Some(shown) = self.shown.take() {
+            let old = self.cells(0, shown.chars().count());
+            self.stale = Some(match self.stale {
+                Some(stale) => bounding(stale, old),
+                None => old,
+            });
+        }
+        self.font = font;
+    }
+
+    /// 忘掉屏幕上画过什么(比如整屏清过了)，下次 `update` 整个重画
+    pub fn invalidate(&mut self) {
+        self.shown = None;
+    }
+
+    /// 更新成 `text`：只擦掉、重画和上次不一样的格子。返回改过的区域，一个格子都没变返回 None
+    pub fn update<D>(&mut self, target: &mut D, text: &str) -> Result<Option<Rectangle>, D::Error>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        let mut next: String<N> = String::new();
+        for c in text.chars() {
+            if next.push(c).is_err() {
+                break;
+            }
+        }
+
+        let mut dirty = None;
+        if let Some(stale) = self.stale.take() {
+            stale
+                .into_styled(PrimitiveStyle::with_fill(BinaryColor::Off))
+                .draw(target)?;
+            dirty = Some(stale);
+        }
+
+        let style = MonoTextStyle::new(self.font, BinaryColor::On);
+        let shown = self.shown.take();
+        let count = next
+            .chars()
+            .count()
+            .max(shown.as_ref().map_or(0, |shown| shown.chars().count()));
+        let mut before = shown.as_ref().map(|shown| shown.chars());
+        let mut after = next.chars();
+        // 改过的第一个和最后一个格子
+        let mut changed: Option<(usize, usize)> = None;
+        for index in 0..count {
+            let old = before.as_mut().and_then(|chars| chars.next());
+            let new = after.next();
+            // 不知道屏幕上是什么的时候每个格子都重画
+            if shown.is_some() && old == new {
+                continue;
+            }
+            let cell = self.cells(index, 1);
+            cell.into_styled(PrimitiveStyle::with_fill(BinaryColor::Off))
+                .draw(target)?;
+            if let Some(c) = new {
+                let mut buf = [0u8; 4];
+                Text::with_baseline(c.encode_utf8(&mut buf), cell.top_left, style, Baseline::Top)
+                    .draw(target)?;
+            }
+            changed = Some(changed.map_or((index, index), |(first, _)| (first, index)));
+        }
+        self.shown = Some(next);
+
+        let cells = changed.map(|(first, last)| self.cells(first, last - first + 1));
+        Ok(match (dirty, cells) {
+            (Some(a), Some(b)) => Some(bounding(a, b)),
+            (a, b) => a.or(b),
+        })
+    }
+}
+
+/// 同时盖住两个矩形的最小矩形
+fn bounding(a: Rectangle, b: Rectangle) -> Rectangle {
+    let top_left = Point::new(
+        a.top_left.x.min(b.top_left.x),
+        a.top_left.y.min(b.top_left.y),
+    );
+    let bottom_right = |r: Rectangle| r.top_left + r.size;
+    let (a_end, b_end) = (bottom_right(a), bottom_right(b));
+    let end = Point::new(a_end.x.max(b_end.x), a_end.y.max(b_end.y));
+    Rectangle::new(
+        top_left,
+        Size::new((end.x - top_left.x) as u32, (end.y - top_left.y) as u32),
+    )
+}
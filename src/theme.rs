@@ -0,0 +1,268 @@
+//! 主题：每种控件的边框、填充、黑白用哪一套，换一个主题所有页面一起变
+//!
+//! 一套样式(`Style`)是三样：
+//!
+//! - 边框：没有、细(1 像素)、粗(2 像素)、圆角(细边框去掉四个角上的点，小屏上看着就是圆的)
+//! - 填充：进度条、电平条、柱子、确认框选中的那一项怎么涂：实心、不涂(只描边)、50% 棋盘格、隔行横线
+//! - 黑白：正常是黑底白字；反过来是白底黑字，整块控件先涂白再用黑色画
+//!
+//! 主题(`Theme`)给每种控件配一套：进度条、电平条、柱状图、状态栏、提示条、确认框。
+//! 内置两个：`DEFAULT` 就是原来的样子，`HIGH_CONTRAST` 边框加粗、提示条和确认框反白，太阳底下也看得清。
+//! 在设置菜单的 "theme" 里看效果、选定(见 `theme_page`)，或者串口 `SETTINGS THEME DEFAULT|HIGH-CONTRAST`，
+//! 存在设置里(`Settings::theme`)。
+//!
+//! 现在用的主题放在静态变量里(`active`)，调度器换主题(`Scheduler::set_theme`)的时候顺便整屏重画，
+//! 页面画控件的时候照 `active()` 里那一套画，不用每个页面自己记。没有样式参数的老函数
+//! (`draw_progress_bar` 这些)照默认主题画，开机进度条这种还没读设置的地方用。
+//!
+//! 棋盘格、横线在显存(`FrameBuffer`)上按字节填，一页 8 行一次写完(见 `PatternTarget`)；
+//! 别的画布退回一个点一个点画。花纹是对齐屏幕的，相邻两块花纹接得上。
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use embedded_graphics::draw_target::{Clipped, Cropped, DrawTarget, Translated};
+use embedded_graphics::geometry::Point;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::primitives::{
+    PointsIter, Primitive, PrimitiveStyle, PrimitiveStyleBuilder, Rectangle, StrokeAlignment,
+};
+use embedded_graphics::{Drawable, Pixel};
+
+use crate::display::BufferedDisplay;
+
+/// 边框
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Border {
+    None,
+    /// 1 像素
+    Thin,
+    /// 2 像素
+    Thick,
+    /// 1 像素，四个角上的点不画
+    Rounded,
+}
+
+impl Border {
+    /// 边框占几个像素宽
+    pub const fn width(self) -> u32 {
+        match self {
+            Border::None => 0,
+            Border::Thin | Border::Rounded => 1,
+            Border::Thick => 2,
+        }
+    }
+}
+
+/// 填充
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Fill {
+    /// 不涂，只描一圈边
+    None,
+    Solid,
+    /// 50% 棋盘格，点亮的和不亮的像素隔一个换一个
+    Checker,
+    /// 隔一行点亮一行
+    HLines,
+}
+
+impl Fill {
+    /// 花纹在逻辑坐标 `point` 上亮不亮(`None` 不算花纹，当作不亮)
+    pub fn is_lit(self, point: Point) -> bool {
+        match self {
+            Fill::None => false,
+            Fill::Solid => true,
+            Fill::Checker => (point.x + point.y) % 2 == 0,
+            Fill::HLines => point.y % 2 == 0,
+        }
+    }
+}
+
+/// 黑白
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Polarity {
+    /// 黑底白字
+    Normal,
+    /// 白底黑字
+    Inverted,
+}
+
+/// 一种控件的样式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Style {
+    pub border: Border,
+    pub fill: Fill,
+    pub text: Polarity,
+}
+
+impl Style {
+    pub const fn new(border: Border, fill: Fill, text: Polarity) -> Self {
+        Self { border, fill, text }
+    }
+
+    /// 画线、写字、填充用的颜色
+    pub fn ink(&self) -> BinaryColor {
+        match self.text {
+            Polarity::Normal => BinaryColor::On,
+            Polarity::Inverted => BinaryColor::Off,
+        }
+    }
+
+    /// 底色
+    pub fn paper(&self) -> BinaryColor {
+        self.ink().invert()
+    }
+
+    /// 画底色和边框，返回边框里面的区域。`clear` 是黑底的时候也要先把底涂黑(盖住下面的页面)，
+    /// 白底的时候总是先涂白
+    pub fn draw_frame<D>(
+        &self,
+        display: &mut D,
+        area: Rectangle,
+        clear: bool,
+    ) -> Result<Rectangle, D::Error>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        if clear || self.text == Polarity::Inverted {
+            display.fill_solid(&area, self.paper())?;
+        }
+        let width = self.border.width();
+        if width > 0 {
+            let stroke = PrimitiveStyleBuilder::new()
+                .stroke_color(self.ink())
+                .stroke_width(width)
+                .stroke_alignment(StrokeAlignment::Inside)
+                .build();
+            area.into_styled(stroke).draw(display)?;
+        }
+        if self.border == Border::Rounded {
+            if let Some(bottom_right) = area.bottom_right() {
+                let top_left = area.top_left;
+                let corners = [
+                    top_left,
+                    Point::new(bottom_right.x, top_left.y),
+                    Point::new(top_left.x, bottom_right.y),
+                    bottom_right,
+                ];
+                let paper = self.paper();
+                display.draw_iter(corners.into_iter().map(|point| Pixel(point, paper)))?;
+            }
+        }
+        Ok(area.offset(-(width as i32)))
+    }
+
+    /// 按填充方式涂一块(进度条填满的那一段、一根柱子)
+    pub fn fill_area<D>(&self, display: &mut D, area: Rectangle) -> Result<(), D::Error>
+    where
+        D: PatternTarget,
+    {
+        match self.fill {
+            Fill::None => area
+                .into_styled(PrimitiveStyle::with_stroke(self.ink(), 1))
+                .draw(display),
+            fill => display.fill_pattern(&area, fill, self.ink()),
+        }
+    }
+}
+
+/// 能画花纹填充的画布。默认一个点一个点画，显存按字节填
+pub trait PatternTarget: DrawTarget<Color = BinaryColor> {
+    /// 花纹亮的点画 `color`，不亮的点画反色，整块都会被盖掉。`Fill::None` 什么都不画
+    fn fill_pattern(
+        &mut self,
+        area: &Rectangle,
+        fill: Fill,
+        color: BinaryColor,
+    ) -> Result<(), Self::Error> {
+        match fill {
+            Fill::None => Ok(()),
+            Fill::Solid => self.fill_solid(area, color),
+            fill => self.fill_contiguous(
+                area,
+                area.points().map(|point| {
+                    if fill.is_lit(point) {
+                        color
+                    } else {
+                        color.invert()
+                    }
+                }),
+            ),
+        }
+    }
+}
+
+/// 屏幕自己的缓冲区(开机进度条画在这上面)一个点一个点画
+impl<T: BufferedDisplay> PatternTarget for T {}
+
+/// 裁剪、平移过的画布(表盘的格子)也一个点一个点画
+impl<T: PatternTarget> PatternTarget for Clipped<'_, T> {}
+impl<T: PatternTarget> PatternTarget for Cropped<'_, T> {}
+impl<T: PatternTarget> PatternTarget for Translated<'_, T> {}
+
+/// 一个主题：每种控件一套样式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Theme {
+    pub name: &'static str,
+    pub progress_bar: Style,
+    pub gauge: Style,
+    pub bar_chart: Style,
+    pub status_bar: Style,
+    pub toast: Style,
+    pub dialog: Style,
+}
+
+/// 默认主题，和加主题之前一样
+pub const DEFAULT: Theme = Theme {
+    name: "default",
+    progress_bar: Style::new(Border::Thin, Fill::Solid, Polarity::Normal),
+    gauge: Style::new(Border::Thin, Fill::Solid, Polarity::Normal),
+    bar_chart: Style::new(Border::None, Fill::Solid, Polarity::Normal),
+    status_bar: Style::new(Border::None, Fill::Solid, Polarity::Inverted),
+    toast: Style::new(Border::Thin, Fill::None, Polarity::Normal),
+    dialog: Style::new(Border::Thin, Fill::Solid, Polarity::Normal),
+};
+
+/// 高对比度：边框加粗，提示条、确认框白底黑字
+pub const HIGH_CONTRAST: Theme = Theme {
+    name: "high-contrast",
+    progress_bar: Style::new(Border::Thick, Fill::Solid, Polarity::Normal),
+    gauge: Style::new(Border::Thick, Fill::Solid, Polarity::Normal),
+    bar_chart: Style::new(Border::Thin, Fill::Solid, Polarity::Normal),
+    status_bar: Style::new(Border::Thick, Fill::Solid, Polarity::Inverted),
+    toast: Style::new(Border::Rounded, Fill::None, Polarity::Inverted),
+    dialog: Style::new(Border::Thick, Fill::Solid, Polarity::Inverted),
+};
+
+/// 所有主题，设置里按这个顺序存编号，所以只能往后加
+pub const THEMES: [Theme; 2] = [DEFAULT, HIGH_CONTRAST];
+
+/// 现在用的主题的编号
+static ACTIVE: AtomicU8 = AtomicU8::new(0);
+
+/// 按名字找(不区分大小写)，返回编号
+pub fn find(name: &str) -> Option<u8> {
+    THEMES
+        .iter()
+        .position(|theme| theme.name.eq_ignore_ascii_case(name))
+        .map(|index| index as u8)
+}
+
+/// 第几个主题，编号不对返回 None
+pub fn get(index: u8) -> Option<&'static Theme> {
+    THEMES.get(index as usize)
+}
+
+/// 现在用的主题
+pub fn active() -> &'static Theme {
+    get(active_index()).unwrap_or(&DEFAULT)
+}
+
+pub fn active_index() -> u8 {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+/// 换主题，编号不对的话用默认主题。只换静态变量，重画由调用方管(`Scheduler::set_theme`)
+pub fn set_active(index: u8) {
+    let index = if get(index).is_some() { index } else { 0 };
+    ACTIVE.store(index, Ordering::Relaxed);
+}
@@ -0,0 +1,130 @@
+//! 主题的设置页面：每种控件都画一个样子看效果，当场换主题(见 `theme`)
+//!
+//! 从设置菜单的 "theme" 进来。顶上是状态栏(主题名、第几个)，下面是进度条、柱状图、电平条，
+//! 最下面一排是四种填充各一块。Up/Down 换成上一个/下一个主题并保存(`Action::SaveTheme`)，
+//! 换完主循环弹的提示条就是新主题的样子；Select 弹一个确认框看样子，选什么都不做。Back 回去。
+
+use core::convert::Infallible;
+use core::fmt::Write;
+
+use embedded_graphics::geometry::{Dimensions, Point, Size};
+use embedded_graphics::primitives::Rectangle;
+use heapless::String;
+
+use crate::app::{Action, Canvas, Confirm, Event, Page, Transition};
+use crate::input::{Button, ButtonEvent};
+use crate::theme::{self, Fill, Style, THEMES};
+use crate::widgets::{
+    draw_bar_chart_styled, draw_level_gauge_styled, draw_progress_bar_styled, draw_status_bar,
+    STATUS_BAR_HEIGHT,
+};
+
+/// 柱状图画的一组固定数据
+const CHART_VALUES: [u8; 10] = [3, 5, 8, 6, 9, 4, 7, 10, 6, 8];
+
+/// 填充样例的边长
+const SWATCH: u32 = 12;
+
+/// 四种填充，最下面一排从左到右
+const FILLS: [Fill; 4] = [Fill::Solid, Fill::None, Fill::Checker, Fill::HLines];
+
+/// 主题设置页面
+#[derive(Debug, Default)]
+pub struct ThemePage {
+    pending: Option<Action>,
+}
+
+impl ThemePage {
+    pub const fn new() -> Self {
+        Self { pending: None }
+    }
+
+    /// 往后(`step` = 1)或者往前(`step` = THEMES.len() - 1)换一个主题
+    fn switch(&mut self, step: usize) {
+        let index = (theme::active_index() as usize + step) % THEMES.len();
+        self.pending = Some(Action::SaveTheme(index as u8));
+    }
+}
+
+impl Page for ThemePage {
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        match event {
+            Event::Button(ButtonEvent::Pressed(Button::Back)) => Transition::Pop,
+            Event::Button(ButtonEvent::Pressed(Button::Up)) => {
+                self.switch(THEMES.len() - 1);
+                Transition::None
+            }
+            Event::Button(ButtonEvent::Pressed(Button::Down)) => {
+                self.switch(1);
+                Transition::None
+            }
+            // 只是看看确认框的样子，选完了 `Event::Confirmed` 不管
+            Event::Button(ButtonEvent::Pressed(Button::Select)) => Transition::Confirm(Confirm {
+                prompt: "sample dialog",
+                action: Action::SaveTheme(theme::active_index()),
+            }),
+            _ => Transition::None,
+        }
+    }
+
+    fn take_action(&mut self) -> Option<Action> {
+        self.pending.take()
+    }
+
+    /// 换了主题调度器会整屏重画，平时不用动
+    fn desired_fps(&self) -> u16 {
+        1
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<(), Infallible> {
+        let theme = theme::active();
+        let width = canvas.bounding_box().size.width;
+
+        let mut position: String<8> = String::new();
+        let _ = write!(position, "{}/{}", theme::active_index() + 1, THEMES.len());
+        draw_status_bar(
+            canvas,
+            Rectangle::new(Point::zero(), Size::new(width, STATUS_BAR_HEIGHT)),
+            theme.name,
+            &position,
+            &theme.status_bar,
+        )?;
+
+        // 左边进度条在上、柱状图在下，右边一根电平条
+        let left_width = width.saturating_sub(24);
+        draw_progress_bar_styled(
+            canvas,
+            Rectangle::new(Point::new(0, 15), Size::new(left_width, 8)),
+            60,
+            &theme.progress_bar,
+        )?;
+        draw_bar_chart_styled(
+            canvas,
+            Rectangle::new(Point::new(0, 26), Size::new(left_width, 20)),
+            CHART_VALUES.iter().copied(),
+            10,
+            &theme.bar_chart,
+        )?;
+        draw_level_gauge_styled(
+            canvas,
+            Rectangle::new(Point::new(width as i32 - 12, 15), Size::new(10, 31)),
+            70,
+            &theme.gauge,
+        )?;
+
+        // 四种填充，边框和黑白跟着进度条
+        for (index, &fill) in FILLS.iter().enumerate() {
+            let style = Style {
+                fill,
+                ..theme.progress_bar
+            };
+            let area = Rectangle::new(
+                Point::new(index as i32 * (SWATCH as i32 + 4), 50),
+                Size::new(SWATCH, SWATCH),
+            );
+            let inner = style.draw_frame(canvas, area, true)?;
+            style.fill_area(canvas, inner.offset(-1))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+//! 固定步长：动画、游戏的逻辑按固定的节奏(`TICK_HZ`)往前走，和画得多快分开
+//!
+//! 以前动画是画一帧走一步，一次 flush 慢了(总线上有别的设备、整屏都脏了)或者页面复杂了画得慢，
+//! 弹球、生命游戏就跟着变慢。现在逻辑和画图分成两个节奏：
+//!
+//! - 逻辑 tick：每 `TICK_MS` 毫秒一次，雷打不动，调度器给栈里的页面调 `Page::step`。
+//!   主循环每一圈都被定时器闹钟叫醒(见 main.rs 的 `idle_for`)，到点了几个就补几个
+//! - 画图：还是按页面要的帧率(`Page::desired_fps`)，画的是最近一次 tick 以后的样子，不做插值
+//!
+//! 落后了(一圈里该走不止一步)先把逻辑补上，这一帧不画，留出时间给后面的 tick；但不会连着两帧都不画，
+//! 不然画得太慢的时候屏幕就一直不动了。一圈最多补 `MAX_CATCH_UP` 步，再多的直接丢掉、从现在重新算，
+//! 不会越补越落后(比如关屏的时候主循环不画，亮屏以后不会一口气快进几分钟)。
+//!
+//! tick 一个一个编号(`Steps::first`)，页面拿编号取模就能每几个 tick 走一步，同样的编号顺序总是走出同样的结果。
+//! 走了几步、丢了几步、跳过了几帧记在 `perf` 里，perf 页面和串口 `PERF` 能看出两个节奏是分开的。
+
+/// 逻辑 tick 每秒几次
+pub const TICK_HZ: u32 = 50;
+
+/// 两个 tick 之间多少毫秒
+pub const TICK_MS: u64 = 1000 / TICK_HZ as u64;
+
+/// 一圈最多补几步
+pub const MAX_CATCH_UP: u32 = 5;
+
+/// 这一圈该走的步
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Steps {
+    /// 第一步的编号，后面的依次加一
+    pub first: u32,
+    /// 走几步，最多 `MAX_CATCH_UP`
+    pub count: u32,
+    /// 补不上丢掉的步数
+    pub dropped: u32,
+    /// 落后了，这一帧不画
+    pub skip_render: bool,
+}
+
+impl Steps {
+    /// 这一圈要走的每一步的编号
+    pub fn ticks(&self) -> impl Iterator<Item = u32> {
+        let first = self.first;
+        (0..self.count).map(move |i| first.wrapping_add(i))
+    }
+}
+
+/// 固定步长的时钟
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FixedStep {
+    /// 下一个 tick 该在什么时候走，None 是还没开始
+    next_ms: Option<u64>,
+    /// 下一个 tick 的编号
+    tick: u32,
+    /// 上一帧是不是因为落后没画
+    skipped: bool,
+}
+
+impl FixedStep {
+    pub const fn new() -> Self {
+        Self {
+            next_ms: None,
+            tick: 0,
+            skipped: false,
+        }
+    }
+
+    /// 到 `now_ms` 为止该走几步。第一次调用从这一刻开始算，不走
+    pub fn advance(&mut self, now_ms: u64) -> Steps {
+        let first = self.tick;
+        let Some(mut next_ms) = self.next_ms else {
+            self.next_ms = Some(now_ms + TICK_MS);
+            return Steps {
+                first,
+                ..Steps::default()
+            };
+        };
+        let mut count = 0;
+        while now_ms >= next_ms && count < MAX_CATCH_UP {
+            next_ms += TICK_MS;
+            count += 1;
+        }
+        let mut dropped = 0;
+        if now_ms >= next_ms {
+            let behind = (now_ms - next_ms) / TICK_MS + 1;
+            next_ms += behind * TICK_MS;
+            dropped = behind.min(u32::MAX as u64) as u32;
+        }
+        self.next_ms = Some(next_ms);
+        self.tick = self.tick.wrapping_add(count);
+        // 补了不止一步就是落后了，上一帧已经没画的话这一帧照画
+        let skip_render = count > 1 && !self.skipped;
+        self.skipped = skip_render;
+        Steps {
+            first,
+            count,
+            dropped,
+            skip_render,
+        }
+    }
+
+    /// 下一个 tick 的编号，也就是一共走了几步
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+}
@@ -0,0 +1,491 @@
+//! 时区和夏令时：DS3231 里一直存 UTC，屏幕上显示当地时间
+//!
+//! 时区是相对 UTC 的偏移，按 15 分钟一档，-12:00 到 +14:00(尼泊尔 +05:45 这种也能设)。夏令时四种：
+//!
+//! | 规则 | 什么时候多 1 小时 |
+//! |------|-------------------|
+//! | `off` | 不用夏令时 |
+//! | `on` | 一直多 1 小时，自己到季节了手动开关 |
+//! | `EU` | 3 月最后一个星期天 01:00 UTC 到 10 月最后一个星期天 01:00 UTC(欧盟各国同时切换) |
+//! | `US` | 3 月第二个星期天当地 02:00 到 11 月第一个星期天当地 02:00(夏令时的 02:00，也就是标准时间的 01:00) |
+//!
+//! 只支持北半球的这两种，南半球(夏令时跨年)用 `on`/`off` 手动切。
+//!
+//! 用的地方(见 main.rs)：从 DS3231 对时的时候(开机一次，之后每分钟一次)把 UTC 换成当地时间交给软件时钟 `WallClock`，
+//! 所有表盘、床头钟、闹钟、按时间调亮度看的都是它；`CLOCK SET` 输入的是当地时间，写进 DS3231 之前换回 UTC。
+//! 夏令时切换那一刻最多晚一分钟生效(下一次对时)。没接 DS3231 就没有日期，`EU`/`US` 判断不了，按标准时间算。
+//!
+//! 当地时间换回 UTC(`to_utc`)的时候，切换那天多出来的那一小时(秋天 01:00~02:00 走两遍)按夏令时算，
+//! 跳过去的那一小时(春天)按标准时间算，都不报错。
+//!
+//! 日期按公历算，年份在 DS3231 能存的 2000~2199 里，算出界了贴边。
+
+use core::fmt::{self, Write};
+
+use crate::ds3231::Time;
+
+/// 偏移一档多少分钟
+pub const OFFSET_STEP_MIN: i16 = 15;
+
+/// 偏移最小、最大多少档：-12:00、+14:00
+pub const MIN_OFFSET_QUARTERS: i8 = -48;
+pub const MAX_OFFSET_QUARTERS: i8 = 56;
+
+/// 存进设置的长度：偏移 1 字节(有符号，档数)、夏令时规则 1 字节
+pub const ENCODED_LEN: usize = 2;
+
+/// 1970-01-01 到 2000-01-01 多少天
+const DAYS_1970_TO_2000: i64 = 10_957;
+
+const DAY_S: i64 = 24 * 60 * 60;
+
+/// 夏令时规则
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, defmt::Format)]
+pub enum DstRule {
+    #[default]
+    Off,
+    /// 手动打开，一直多 1 小时
+    On,
+    Eu,
+    Us,
+}
+
+impl DstRule {
+    /// 设置页面里按这个顺序轮着选
+    pub const ALL: [DstRule; 4] = [DstRule::Off, DstRule::On, DstRule::Eu, DstRule::Us];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DstRule::Off => "off",
+            DstRule::On => "on",
+            DstRule::Eu => "EU",
+            DstRule::Us => "US",
+        }
+    }
+
+    /// 串口上的写法，不区分大小写
+    pub fn parse(text: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|rule| rule.name().eq_ignore_ascii_case(text))
+    }
+
+    /// 下一种(`forward`)或者上一种，到头了绕回来
+    pub fn step(self, forward: bool) -> Self {
+        let index = Self::ALL.iter().position(|&rule| rule == self).unwrap_or(0);
+        let len = Self::ALL.len();
+        Self::ALL[if forward { index + 1 } else { index + len - 1 } % len]
+    }
+
+    fn code(self) -> u8 {
+        self as u8
+    }
+
+    fn from_code(code: u8) -> Option<Self> {
+        Self::ALL.get(code as usize).copied()
+    }
+}
+
+/// 时区：标准时间的偏移和夏令时规则
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, defmt::Format)]
+pub struct TimeZone {
+    /// 相对 UTC 偏几档(15 分钟一档)，东边是正的
+    offset_quarters: i8,
+    dst: DstRule,
+}
+
+impl TimeZone {
+    /// 默认就是 UTC，不用夏令时：DS3231 里存的直接显示，和没有时区设置的时候一样
+    pub const UTC: TimeZone = TimeZone {
+        offset_quarters: 0,
+        dst: DstRule::Off,
+    };
+
+    /// 偏移超出 -12:00~+14:00 返回 None
+    pub fn new(offset_quarters: i8, dst: DstRule) -> Option<Self> {
+        (MIN_OFFSET_QUARTERS..=MAX_OFFSET_QUARTERS)
+            .contains(&offset_quarters)
+            .then_some(Self {
+                offset_quarters,
+                dst,
+            })
+    }
+
+    pub fn offset_quarters(&self) -> i8 {
+        self.offset_quarters
+    }
+
+    pub fn dst(&self) -> DstRule {
+        self.dst
+    }
+
+    /// 偏移调一档，到头了不动
+    pub fn step_offset(self, forward: bool) -> Self {
+        let quarters = if forward {
+            self.offset_quarters.saturating_add(1)
+        } else {
+            self.offset_quarters.saturating_sub(1)
+        };
+        Self::new(quarters, self.dst).unwrap_or(self)
+    }
+
+    pub fn with_dst(self, dst: DstRule) -> Self {
+        Self { dst, ..self }
+    }
+
+    /// 标准时间的偏移(分钟)
+    pub fn standard_offset_min(&self) -> i16 {
+        self.offset_quarters as i16 * OFFSET_STEP_MIN
+    }
+
+    /// UTC 的 `utc` 这一刻在不在夏令时里
+    pub fn is_dst(&self, utc: &Time) -> bool {
+        let now = to_seconds(utc);
+        let standard = self.standard_offset_min() as i64 * 60;
+        let year = utc.year;
+        let (start, end) = match self.dst {
+            DstRule::Off => return false,
+            DstRule::On => return true,
+            DstRule::Eu => (
+                at(year, 3, last_sunday(year, 3), 1),
+                at(year, 10, last_sunday(year, 10), 1),
+            ),
+            // 当地标准时间 02:00 开始，夏令时 02:00(标准时间 01:00)结束
+            DstRule::Us => (
+                at(year, 3, nth_sunday(year, 3, 2), 2) - standard,
+                at(year, 11, nth_sunday(year, 11, 1), 1) - standard,
+            ),
+        };
+        (start..end).contains(&now)
+    }
+
+    /// `utc` 这一刻当地比 UTC 快多少分钟(算上夏令时)
+    pub fn offset_min(&self, utc: &Time) -> i16 {
+        self.standard_offset_min() + if self.is_dst(utc) { 60 } else { 0 }
+    }
+
+    /// UTC 换成当地时间
+    pub fn to_local(&self, utc: &Time) -> Time {
+        shift(utc, self.offset_min(utc) as i64 * 60)
+    }
+
+    /// 当地时间换回 UTC，切换那天多出来、跳过去的那一小时见开头的注释
+    pub fn to_utc(&self, local: &Time) -> Time {
+        let standard = shift(local, -(self.standard_offset_min() as i64) * 60);
+        let daylight = shift(&standard, -60 * 60);
+        if self.is_dst(&daylight) {
+            daylight
+        } else {
+            standard
+        }
+    }
+
+    /// 串口、设置页面上的写法：`+08:00`、`-03:30`、`+00:00`
+    pub fn write_offset<W: Write>(&self, out: &mut W) -> fmt::Result {
+        let minutes = self.standard_offset_min();
+        let sign = if minutes < 0 { '-' } else { '+' };
+        let minutes = minutes.unsigned_abs();
+        write!(out, "{}{:02}:{:02}", sign, minutes / 60, minutes % 60)
+    }
+
+    pub fn encode(&self) -> [u8; ENCODED_LEN] {
+        [self.offset_quarters as u8, self.dst.code()]
+    }
+
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let &[offset, dst] = bytes else {
+            return None;
+        };
+        Self::new(offset as i8, DstRule::from_code(dst)?)
+    }
+}
+
+/// 解析偏移：`+08:00`、`-3:30`、`+5`、`0`，分钟必须是 15 的倍数，返回档数
+pub fn parse_offset(text: &str) -> Option<i8> {
+    let (negative, rest) = match text.as_bytes().first()? {
+        b'+' => (false, &text[1..]),
+        b'-' => (true, &text[1..]),
+        _ => (false, text),
+    };
+    let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+    let hours: i16 = hours.parse().ok()?;
+    let minutes: i16 = minutes.parse().ok()?;
+    if !(0..60).contains(&minutes) || minutes % OFFSET_STEP_MIN != 0 || hours > 14 {
+        return None;
+    }
+    let quarters = (hours * 60 + minutes) / OFFSET_STEP_MIN;
+    let quarters = if negative { -quarters } else { quarters };
+    TimeZone::new(quarters as i8, DstRule::Off).map(|zone| zone.offset_quarters)
+}
+
+pub fn is_leap_year(year: u16) -> bool {
+    (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400)
+}
+
+pub fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// 1970-01-01 以来第几天
+fn days_from_civil(year: u16, month: u8, day: u8) -> i64 {
+    // 把 1、2 月算到上一年的最后，闰日就在一年的最后一天
+    let year = year as i64 - (month <= 2) as i64;
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_from_march = (month as i64 + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// `days_from_civil` 反过来
+fn civil_from_days(days: i64) -> (u16, u8, u8) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    (year as u16, month as u8, day as u8)
+}
+
+/// 星期几，1 是星期一，7 是星期天(和 DS3231 的星期寄存器一样)
+pub fn weekday(year: u16, month: u8, day: u8) -> u8 {
+    // 1970-01-01 是星期四
+    ((days_from_civil(year, month, day) + 3).rem_euclid(7) + 1) as u8
+}
+
+/// 这个月最后一个星期天是几号
+pub fn last_sunday(year: u16, month: u8) -> u8 {
+    let last = days_in_month(year, month);
+    last - weekday(year, month, last) % 7
+}
+
+/// 这个月第 `n` 个星期天是几号(`n` 从 1 数)
+pub fn nth_sunday(year: u16, month: u8, n: u8) -> u8 {
+    let first = 1 + (7 - weekday(year, month, 1)) % 7;
+    first + 7 * (n - 1)
+}
+
+/// 1970 年以来的秒数
+fn to_seconds(time: &Time) -> i64 {
+    days_from_civil(time.year, time.month, time.day) * DAY_S
+        + time.hour as i64 * 3600
+        + time.minute as i64 * 60
+        + time.second as i64
+}
+
+/// UTC 的某一天某个整点
+fn at(year: u16, month: u8, day: u8, hour: u8) -> i64 {
+    days_from_civil(year, month, day) * DAY_S + hour as i64 * 3600
+}
+
+/// 往后(负数往前)挪 `seconds` 秒，跨天、跨月、跨年都算好，星期也重算。超出 2000~2199 贴边
+pub fn shift(time: &Time, seconds: i64) -> Time {
+    let first = DAYS_1970_TO_2000 * DAY_S;
+    let last = days_from_civil(2199, 12, 31) * DAY_S + DAY_S - 1;
+    let total = (to_seconds(time) + seconds).clamp(first, last);
+    let days = total.div_euclid(DAY_S);
+    let of_day = total.rem_euclid(DAY_S);
+    let (year, month, day) = civil_from_days(days);
+    Time {
+        year,
+        month,
+        day,
+        weekday: weekday(year, month, day),
+        hour: (of_day / 3600) as u8,
+        minute: (of_day / 60 % 60) as u8,
+        second: (of_day % 60) as u8,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(year: u16, month: u8, day: u8, hour: u8, minute: u8) -> Time {
+        Time {
+            year,
+            month,
+            day,
+            weekday: weekday(year, month, day),
+            hour,
+            minute,
+            second: 0,
+        }
+    }
+
+    fn zone(offset: &str, dst: DstRule) -> TimeZone {
+        TimeZone::new(parse_offset(offset).unwrap(), dst).unwrap()
+    }
+
+    #[test]
+    fn calendar() {
+        // 2000-01-01 是星期六，和 DS3231 的默认时间一样
+        assert_eq!(weekday(2000, 1, 1), Time::EPOCH.weekday);
+        assert_eq!(weekday(2026, 10, 14), 3);
+        assert_eq!(weekday(2024, 2, 29), 4);
+        assert!(is_leap_year(2000) && is_leap_year(2024));
+        assert!(!is_leap_year(2100) && !is_leap_year(2026));
+        assert_eq!(days_in_month(2100, 2), 28);
+        for days in [0, 59, 60, 365, 36_524, 73_049] {
+            let days = DAYS_1970_TO_2000 + days;
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+
+    #[test]
+    fn sunday_rules() {
+        assert_eq!(last_sunday(2026, 3), 29);
+        assert_eq!(last_sunday(2026, 10), 25);
+        // 最后一天就是星期天
+        assert_eq!(last_sunday(2020, 5), 31);
+        assert_eq!(nth_sunday(2026, 3, 2), 8);
+        assert_eq!(nth_sunday(2026, 11, 1), 1);
+        assert_eq!(nth_sunday(2027, 3, 2), 14);
+    }
+
+    #[test]
+    fn parses_and_prints_offsets() {
+        assert_eq!(parse_offset("+08:00"), Some(32));
+        assert_eq!(parse_offset("-3:30"), Some(-14));
+        assert_eq!(parse_offset("5:45"), Some(23));
+        assert_eq!(parse_offset("0"), Some(0));
+        assert_eq!(parse_offset("-12"), Some(-48));
+        assert_eq!(parse_offset("+14:00"), Some(56));
+        assert_eq!(parse_offset("+14:15"), None);
+        assert_eq!(parse_offset("-12:15"), None);
+        assert_eq!(parse_offset("+1:20"), None);
+        assert_eq!(parse_offset(""), None);
+        let mut text: heapless::String<8> = heapless::String::new();
+        zone("-3:30", DstRule::Off).write_offset(&mut text).unwrap();
+        assert_eq!(text, "-03:30");
+    }
+
+    #[test]
+    fn offset_rolls_the_date_over() {
+        // 东八区：UTC 前一天晚上 20:30 是当地第二天凌晨，跨月跨年
+        let east = zone("+08:00", DstRule::Off);
+        assert_eq!(
+            east.to_local(&time(2026, 12, 31, 20, 30)),
+            time(2027, 1, 1, 4, 30)
+        );
+        // 西边的反过来，闰年 3 月 1 日退回 2 月 29 日
+        let west = zone("-05:00", DstRule::Off);
+        assert_eq!(
+            west.to_local(&time(2024, 3, 1, 2, 0)),
+            time(2024, 2, 29, 21, 0)
+        );
+        assert_eq!(
+            west.to_utc(&time(2024, 2, 29, 21, 0)),
+            time(2024, 3, 1, 2, 0)
+        );
+        // 贴着 DS3231 能存的边
+        assert_eq!(west.to_local(&Time::EPOCH), Time::EPOCH);
+    }
+
+    #[test]
+    fn eu_transitions_at_one_utc() {
+        let berlin = zone("+01:00", DstRule::Eu);
+        assert!(!berlin.is_dst(&time(2026, 3, 29, 0, 59)));
+        assert!(berlin.is_dst(&time(2026, 3, 29, 1, 0)));
+        // 当地 01:59 下一分钟就是 03:00
+        assert_eq!(
+            berlin.to_local(&time(2026, 3, 29, 0, 59)),
+            time(2026, 3, 29, 1, 59)
+        );
+        assert_eq!(
+            berlin.to_local(&time(2026, 3, 29, 1, 0)),
+            time(2026, 3, 29, 3, 0)
+        );
+        assert!(berlin.is_dst(&time(2026, 10, 25, 0, 59)));
+        assert!(!berlin.is_dst(&time(2026, 10, 25, 1, 0)));
+        assert_eq!(
+            berlin.to_local(&time(2026, 10, 25, 1, 0)),
+            time(2026, 10, 25, 2, 0)
+        );
+        // 所有时区同时切换，不管偏移
+        let lisbon = zone("0", DstRule::Eu);
+        assert!(lisbon.is_dst(&time(2026, 3, 29, 1, 0)));
+    }
+
+    #[test]
+    fn us_transitions_at_two_local() {
+        let new_york = zone("-05:00", DstRule::Us);
+        // 3 月 8 日当地 02:00 = 07:00 UTC
+        assert!(!new_york.is_dst(&time(2026, 3, 8, 6, 59)));
+        assert!(new_york.is_dst(&time(2026, 3, 8, 7, 0)));
+        // 11 月 1 日当地夏令时 02:00 = 06:00 UTC，之后当地又从 01:00 走一遍
+        assert!(new_york.is_dst(&time(2026, 11, 1, 5, 59)));
+        assert!(!new_york.is_dst(&time(2026, 11, 1, 6, 0)));
+        assert_eq!(
+            new_york.to_local(&time(2026, 11, 1, 6, 0)),
+            time(2026, 11, 1, 1, 0)
+        );
+        // 夏令时把当地时间推过了午夜
+        let la = zone("-08:00", DstRule::Us);
+        assert_eq!(
+            la.to_local(&time(2026, 7, 1, 6, 30)),
+            time(2026, 6, 30, 23, 30)
+        );
+    }
+
+    #[test]
+    fn local_back_to_utc() {
+        let berlin = zone("+01:00", DstRule::Eu);
+        for utc in [
+            time(2026, 1, 15, 12, 0),
+            time(2026, 7, 15, 23, 30),
+            time(2026, 3, 29, 1, 0),
+        ] {
+            assert_eq!(berlin.to_utc(&berlin.to_local(&utc)), utc);
+        }
+        // 秋天当地 02:30 走两遍，按夏令时那一遍算
+        assert_eq!(
+            berlin.to_utc(&time(2026, 10, 25, 2, 30)),
+            time(2026, 10, 25, 0, 30)
+        );
+        // 春天跳过去的当地 02:30，按标准时间算
+        assert_eq!(
+            berlin.to_utc(&time(2026, 3, 29, 2, 30)),
+            time(2026, 3, 29, 1, 30)
+        );
+        let manual = zone("+00:00", DstRule::On);
+        assert_eq!(
+            manual.to_utc(&time(2026, 1, 1, 0, 30)),
+            time(2025, 12, 31, 23, 30)
+        );
+    }
+
+    #[test]
+    fn encode_round_trips() {
+        let zone = zone("-09:30", DstRule::Us);
+        assert_eq!(TimeZone::decode(&zone.encode()), Some(zone));
+        assert_eq!(
+            TimeZone::decode(&TimeZone::default().encode()),
+            Some(TimeZone::UTC)
+        );
+        assert_eq!(TimeZone::decode(&[57, 0]), None);
+        assert_eq!(TimeZone::decode(&[0, 4]), None);
+        assert_eq!(TimeZone::decode(&[0]), None);
+        assert_eq!(TimeZone::UTC.step_offset(false).offset_quarters(), -1);
+        let east = TimeZone::new(MAX_OFFSET_QUARTERS, DstRule::Off).unwrap();
+        assert_eq!(east.step_offset(true), east);
+        assert_eq!(DstRule::Off.step(false), DstRule::Us);
+        assert_eq!(DstRule::parse("eu"), Some(DstRule::Eu));
+    }
+}
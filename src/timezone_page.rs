@@ -0,0 +1,175 @@
+//! 时区的设置页面：改偏移和夏令时规则，下面对着看 UTC 和当地时间(见 `timezone`)
+//!
+//! 从设置菜单的 "time zone" 进来。两行：偏移(`+08:00`)和夏令时(`off`/`on`/`EU`/`US`)。
+//!
+//! - Up/Down 选行，Select 开始改这一行：偏移每按一下 15 分钟，夏令时轮着换。再按 Select 保存
+//!   (`Action::SaveTimeZone`，主循环存好马上按新时区对一次时)，Back 放弃这次修改
+//! - Back 回去
+//!
+//! 下面两行是 DS3231 里的 UTC 和按时区换算出来的当地时间，改的时候当地时间跟着预览，
+//! 切换夏令时那天可以对着检查。时间是主循环对时的时候广播的(`Event::RtcTime`)，两次对时之间自己往前走；
+//! 没接 DS3231 显示 "no RTC"，这时 `CLOCK SET` 设的就是当地时间，时区不起作用。
+//! 128x32 的屏放不下这两行，只看得到正在选的那一行。
+
+use core::convert::Infallible;
+use core::fmt::Write;
+
+use embedded_graphics::geometry::Point;
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::text::Text;
+use embedded_graphics::Drawable;
+use heapless::String;
+
+use crate::app::{Action, Canvas, Event, Page, Transition};
+use crate::ds3231::Time;
+use crate::input::{Button, ButtonEvent};
+use crate::timezone::{self, TimeZone};
+use crate::widgets::{draw_menu, MENU_ROW_HEIGHT};
+
+/// 标题行基线
+const TITLE_Y: i32 = 8;
+
+/// 菜单从哪一行开始
+const MENU_TOP: i32 = 12;
+
+/// 偏移、夏令时两行
+const ROWS: usize = 2;
+
+/// UTC 那一行的基线，当地时间在下面一行
+const UTC_Y: i32 = MENU_TOP + ROWS as i32 * MENU_ROW_HEIGHT + 10;
+
+/// 时区设置页面
+#[derive(Debug)]
+pub struct TimeZonePage {
+    zone: TimeZone,
+    selected: usize,
+    /// 正在改的时区(保存了才放回 `zone`)
+    editing: Option<TimeZone>,
+    /// 最近一次对时读到的 UTC 和那时候的 `now_ms`
+    utc: Option<(Time, u64)>,
+    pending: Option<Action>,
+}
+
+impl TimeZonePage {
+    /// `zone` 是设置里存的
+    pub const fn new(zone: TimeZone) -> Self {
+        Self {
+            zone,
+            selected: 0,
+            editing: None,
+            utc: None,
+            pending: None,
+        }
+    }
+
+    fn on_edit(&mut self, button: Button, zone: TimeZone) {
+        let forward = match button {
+            Button::Up => true,
+            Button::Down => false,
+            Button::Back => {
+                self.editing = None;
+                return;
+            }
+            Button::Select => {
+                self.zone = zone;
+                self.editing = None;
+                self.pending = Some(Action::SaveTimeZone(zone));
+                return;
+            }
+        };
+        self.editing = Some(if self.selected == 0 {
+            zone.step_offset(forward)
+        } else {
+            zone.with_dst(zone.dst().step(forward))
+        });
+    }
+
+    /// "utc 2026-10-14 08:30" 这样的一行
+    fn time_line(label: &str, time: &Time) -> String<24> {
+        let mut text = String::new();
+        let _ = write!(
+            text,
+            "{} {}-{:02}-{:02} {:02}:{:02}",
+            label, time.year, time.month, time.day, time.hour, time.minute
+        );
+        text
+    }
+}
+
+impl Page for TimeZonePage {
+    fn on_event(&mut self, event: &Event, now_ms: u64) -> Transition {
+        let button = match event {
+            Event::RtcTime(time) => {
+                self.utc = Some((*time, now_ms));
+                return Transition::None;
+            }
+            // 串口改了或者导入了设置
+            Event::TimeZone(zone) => {
+                self.zone = *zone;
+                self.editing = None;
+                return Transition::None;
+            }
+            Event::Button(ButtonEvent::Pressed(button)) => *button,
+            _ => return Transition::None,
+        };
+        if let Some(zone) = self.editing {
+            self.on_edit(button, zone);
+            return Transition::None;
+        }
+        match button {
+            Button::Back => return Transition::Pop,
+            Button::Up | Button::Down => self.selected = (self.selected + 1) % ROWS,
+            Button::Select => self.editing = Some(self.zone),
+        }
+        Transition::None
+    }
+
+    fn desired_fps(&self) -> u16 {
+        1
+    }
+
+    fn take_action(&mut self) -> Option<Action> {
+        self.pending.take()
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, now_ms: u64) -> Result<(), Infallible> {
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        Text::new("time zone", Point::new(0, TITLE_Y), style).draw(canvas)?;
+
+        let zone = self.editing.unwrap_or(self.zone);
+        let editing = |row: usize| self.editing.is_some() && row == self.selected;
+        let mut offset: String<10> = String::new();
+        if editing(0) {
+            let _ = offset.push('[');
+        }
+        let _ = zone.write_offset(&mut offset);
+        if editing(0) {
+            let _ = offset.push(']');
+        }
+        let mut dst: String<6> = String::new();
+        let _ = if editing(1) {
+            write!(dst, "[{}]", zone.dst().name())
+        } else {
+            write!(dst, "{}", zone.dst().name())
+        };
+        draw_menu(
+            canvas,
+            MENU_TOP,
+            [("offset", offset.as_str()), ("dst", dst.as_str())].into_iter(),
+            self.selected,
+        )?;
+
+        let Some((time, at_ms)) = self.utc else {
+            Text::new("no RTC", Point::new(0, UTC_Y), style).draw(canvas)?;
+            return Ok(());
+        };
+        let utc = timezone::shift(&time, (now_ms.saturating_sub(at_ms) / 1000) as i64);
+        let local = zone.to_local(&utc);
+        Text::new(&Self::time_line("utc", &utc), Point::new(0, UTC_Y), style).draw(canvas)?;
+        let local_line = Self::time_line("loc", &local);
+        Text::new(&local_line, Point::new(0, UTC_Y + 12), style).draw(canvas)?;
+        Ok(())
+    }
+}
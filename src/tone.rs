@@ -0,0 +1,219 @@
+//! 声音：几段内置的旋律 + 一个不阻塞的播放器
+//!
+//! 旋律就是 (频率, 时长) 的常量表，频率 0 是休止符。`ToneEngine` 不会自己 delay，
+//! 主循环每一圈调一次 `tick`，到时间了它才换下一个音，所以放声音的时候屏幕和 USB 照常工作。
+//! 真正发声的是 `ToneOutput`(见 `buzzer` 模块的 PWM 蜂鸣器)，播放器本身不碰硬件。
+//!
+//! 一个音符就是一次 `buzzer::beep`，只是不 delay：`ToneEngine` 是 `beep` 的不阻塞版本。
+//! 菜单导航响一声 `ToneEngine::beep`(`NAV_BEEP`)，告警、闹钟是几声 beep 排成的旋律(`ALERT`、`WAKE_ALARM`)。
+//!
+//! 同时来好几个声音会排队，队列按优先级排好：告警插到按键音前面，正在放的声音优先级低的话直接被打断。
+
+use heapless::Vec;
+
+/// 最多排几个声音，再多的话优先级最低的那个会被丢掉
+pub const QUEUE_LEN: usize = 4;
+
+/// 一个音符
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, defmt::Format)]
+pub struct Note {
+    /// 频率(Hz)，0 表示休止
+    pub freq_hz: u16,
+    /// 时长(毫秒)
+    pub duration_ms: u16,
+}
+
+/// 音符
+pub const fn note(freq_hz: u16, duration_ms: u16) -> Note {
+    Note {
+        freq_hz,
+        duration_ms,
+    }
+}
+
+/// 休止符
+pub const fn rest(duration_ms: u16) -> Note {
+    note(0, duration_ms)
+}
+
+/// 菜单导航的按键音：很短的一声"嗒"
+pub const NAV_BEEP: Note = note(4000, 8);
+
+/// 告警：三声急促的高音
+pub const ALERT: &[Note] = &[
+    note(2000, 150),
+    rest(50),
+    note(2000, 150),
+    rest(50),
+    note(2000, 150),
+];
+
+/// 倒计时结束：C6 E6 G6 上行
+pub const COUNTDOWN_DONE: &[Note] = &[
+    note(1047, 120),
+    rest(40),
+    note(1319, 120),
+    rest(40),
+    note(1568, 240),
+];
+
+/// 闹钟：两声一组，后面停一会儿，响着的时候放完一遍接着放
+pub const WAKE_ALARM: &[Note] = &[note(2600, 100), rest(80), note(2600, 100), rest(600)];
+
+/// 开机：C5 E5 G5
+pub const BOOT_CHIME: &[Note] = &[note(523, 100), note(659, 100), note(784, 160)];
+
+/// 内置的声音，按优先级从低到高排
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, defmt::Format)]
+pub enum Sound {
+    /// 单独一声(`ToneEngine::beep`)
+    Beep(Note),
+    BootChime,
+    CountdownDone,
+    Alert,
+    WakeAlarm,
+}
+
+impl Sound {
+    /// 第 `index` 个音符，放完了是 None
+    pub fn note(self, index: usize) -> Option<Note> {
+        let melody = match self {
+            Sound::Beep(note) => return (index == 0).then_some(note),
+            Sound::BootChime => BOOT_CHIME,
+            Sound::CountdownDone => COUNTDOWN_DONE,
+            Sound::Alert => ALERT,
+            Sound::WakeAlarm => WAKE_ALARM,
+        };
+        melody.get(index).copied()
+    }
+}
+
+/// 能发声的东西
+pub trait ToneOutput {
+    /// `Some(频率)` 开始发这个频率的方波，`None` 静音
+    fn set_tone(&mut self, freq_hz: Option<u32>);
+}
+
+/// 正在放的声音
+#[derive(Debug, Clone, Copy)]
+struct Playing {
+    sound: Sound,
+    /// 当前是第几个音符
+    index: usize,
+    /// 当前音符什么时候结束
+    note_until_ms: u64,
+}
+
+/// 不阻塞的旋律播放器
+#[derive(Debug, Default)]
+pub struct ToneEngine {
+    /// 排队的声音，优先级高的在前面
+    queue: Vec<Sound, QUEUE_LEN>,
+    playing: Option<Playing>,
+    /// 输出现在是不是在响，避免每一圈都重复发静音
+    sounding: bool,
+    muted: bool,
+}
+
+impl ToneEngine {
+    pub const fn new() -> Self {
+        Self {
+            queue: Vec::new(),
+            playing: None,
+            sounding: false,
+            muted: false,
+        }
+    }
+
+    /// 静音。打开静音的时候正在放的和排队的声音都会被丢掉，下一次 `tick` 停止发声
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+        if muted {
+            self.queue.clear();
+            self.playing = None;
+        }
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    /// 没有在放也没有在排队
+    pub fn is_idle(&self) -> bool {
+        self.playing.is_none() && self.queue.is_empty()
+    }
+
+    /// 不阻塞的 `buzzer::beep`：响一声 `freq_hz`，`duration_ms` 之后由 `tick` 静音。
+    /// 优先级最低，有别的声音在放就排在后面
+    pub fn beep(&mut self, freq_hz: u16, duration_ms: u16) {
+        self.play(Sound::Beep(note(freq_hz, duration_ms)));
+    }
+
+    /// 放一个声音：比正在放的优先级高就打断它，否则排队。队列满了丢优先级最低的
+    pub fn play(&mut self, sound: Sound) {
+        if self.muted {
+            return;
+        }
+        if matches!(self.playing, Some(playing) if sound > playing.sound) {
+            self.playing = None;
+        }
+        // 同优先级的排在后面，先来先放
+        let index = self
+            .queue
+            .iter()
+            .position(|&queued| queued < sound)
+            .unwrap_or(self.queue.len());
+        if self.queue.is_full() {
+            if index == self.queue.len() {
+                return;
+            }
+            self.queue.pop();
+        }
+        let _ = self.queue.insert(index, sound);
+    }
+
+    /// 立刻停下：丢掉正在放的和排队的声音，输出马上静音
+    pub fn stop<T: ToneOutput>(&mut self, output: &mut T) {
+        self.queue.clear();
+        self.playing = None;
+        output.set_tone(None);
+        self.sounding = false;
+    }
+
+    /// 主循环每一圈调一次：当前音符到时间了就换下一个，放完了就从队列里取下一个声音
+    pub fn tick<T: ToneOutput>(&mut self, now_ms: u64, output: &mut T) {
+        if let Some(playing) = &mut self.playing {
+            if now_ms < playing.note_until_ms {
+                return;
+            }
+            playing.index += 1;
+            if playing.sound.note(playing.index).is_none() {
+                self.playing = None;
+            }
+        }
+        if self.playing.is_none() && !self.queue.is_empty() {
+            self.playing = Some(Playing {
+                sound: self.queue.remove(0),
+                index: 0,
+                note_until_ms: 0,
+            });
+        }
+
+        match &mut self.playing {
+            Some(playing) => {
+                let Some(note) = playing.sound.note(playing.index) else {
+                    return;
+                };
+                playing.note_until_ms = now_ms + note.duration_ms as u64;
+                let freq = (note.freq_hz != 0).then_some(note.freq_hz as u32);
+                output.set_tone(freq);
+                self.sounding = freq.is_some();
+            }
+            None if self.sounding => {
+                output.set_tone(None);
+                self.sounding = false;
+            }
+            None => {}
+        }
+    }
+}
@@ -0,0 +1,125 @@
+//! 换页动画：新页面从右边推进来(`SlideLeft`)，或者从上往下盖过去(`WipeDown`)
+//!
+//! 名字叫 `TransitionEffect` 是因为 `app::Transition` 已经是页面栈的切换请求了。
+//!
+//! 做法是两块整屏的显存字节互相插值：换页之前先把屏幕上现在的样子存一份(`FrameBuffer::composited`)，
+//! 调度器照常把新页面画进显存，再存一份，然后 `transition` 一帧一帧拼出中间的画面，阻塞 flush 出去。
+//! 拼的时候直接按 SSD1306 的页布局操作字节：推进来是整列挪，一列就是一个字节，
+//! 往下盖是每一页按行数算一个掩码，两边各取一半的位，都不用一个像素一个像素地画。
+//!
+//! 动画是按物理屏幕的方向做的，屏幕转了 90 度的话"往左推"看起来是往上推。
+//! 整个过程大约 `DURATION_MS` 加上每帧 flush 的时间(推进来每帧都是整屏，400kHz 下一帧 20 多毫秒)，
+//! 这段时间主循环停着，所以步数不多。
+
+use display_interface::{DisplayError, WriteOnlyDataCommand};
+use embedded_hal::delay::DelayNs;
+
+use crate::display::Display;
+use crate::framebuffer::{BUFFER_LEN, HEIGHT, PAGES, WIDTH};
+
+/// 一共几帧
+pub const STEPS: u32 = 6;
+
+/// 除去 flush 的时间，整个动画停多久(毫秒)
+pub const DURATION_MS: u32 = 90;
+
+/// 换页动画
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum TransitionEffect {
+    /// 直接换
+    None,
+    /// 旧的往左推出去，新的从右边跟进来
+    SlideLeft,
+    /// 新的从上往下一行一行盖下来
+    WipeDown,
+}
+
+/// 从 `from` 动画到 `to`，两个都是整屏字节(布局见 framebuffer.rs)，一般 `to` 就是显存合成以后的样子
+///
+/// 中间的画面借显存拼，结束的时候显存恢复原样，屏幕上最后停在 `to`。flush 失败的话直接返回，
+/// 显存已经恢复了，整屏标成脏的，下一次正常 flush 会补上
+pub fn transition<DI, T>(
+    display: &mut Display<DI>,
+    from: &[u8; BUFFER_LEN],
+    to: &[u8; BUFFER_LEN],
+    kind: TransitionEffect,
+    timer: &mut T,
+) -> Result<(), DisplayError>
+where
+    DI: WriteOnlyDataCommand,
+    T: DelayNs,
+{
+    if kind == TransitionEffect::None {
+        return Ok(());
+    }
+    let original = *display.framebuffer().as_bytes();
+    let mut frame = [0u8; BUFFER_LEN];
+    let mut result = Ok(());
+    for step in 1..=STEPS {
+        let progress = ease_out(step);
+        match kind {
+            TransitionEffect::None => {}
+            TransitionEffect::SlideLeft => slide_left(from, to, progress, &mut frame),
+            TransitionEffect::WipeDown => wipe_down(from, to, progress, &mut frame),
+        }
+        display.framebuffer_mut().load_bytes(&frame);
+        result = display.flush();
+        if result.is_err() {
+            break;
+        }
+        timer.delay_ms(DURATION_MS / STEPS);
+    }
+    let fb = display.framebuffer_mut();
+    fb.load_bytes(&original);
+    if result.is_err() {
+        fb.mark_all_dirty();
+    }
+    result
+}
+
+/// 第 `step` 帧走到哪了，0..=256。先快后慢，最后一帧正好是 256
+fn ease_out(step: u32) -> u32 {
+    let remaining = 256 - step.min(STEPS) * 256 / STEPS;
+    256 - remaining * remaining / 256
+}
+
+/// 往左推了 `progress`/256 屏宽：屏幕第 `col` 列是旧画面的 `col + 推过的列数`，推出头了就是新画面的
+fn slide_left(
+    from: &[u8; BUFFER_LEN],
+    to: &[u8; BUFFER_LEN],
+    progress: u32,
+    out: &mut [u8; BUFFER_LEN],
+) {
+    let shifted = (WIDTH as u32 * progress / 256) as usize;
+    for page in 0..PAGES {
+        let row = page * WIDTH;
+        for col in 0..WIDTH {
+            let source = col + shifted;
+            out[row + col] = if source < WIDTH {
+                from[row + source]
+            } else {
+                to[row + source - WIDTH]
+            };
+        }
+    }
+}
+
+/// 上面 `progress`/256 屏高是新画面，下面是旧画面。分界线在一页中间的时候那一页按位拼
+fn wipe_down(
+    from: &[u8; BUFFER_LEN],
+    to: &[u8; BUFFER_LEN],
+    progress: u32,
+    out: &mut [u8; BUFFER_LEN],
+) {
+    let edge = (HEIGHT as u32 * progress / 256) as usize;
+    for page in 0..PAGES {
+        // 这一页里分界线以上的行(bit0 是最上面一行)
+        let covered = edge.saturating_sub(page * 8).min(8);
+        let mask = (0xFFu16 >> (8 - covered)) as u8;
+        let row = page * WIDTH;
+        for col in 0..WIDTH {
+            let i = row + col;
+            out[i] = (to[i] & mask) | (from[i] & !mask);
+        }
+    }
+}
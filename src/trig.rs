@@ -0,0 +1,131 @@
+//! 定点数的正弦/余弦，画波形、仪表盘指针、转圈动画用
+//!
+//! 固件里不想为了几根线把浮点的 libm 拉进来(M0+ 没有 FPU，软浮点又大又慢)，
+//! 所以三角函数都走这里：
+//!
+//! - 角度用 u16 表示一整圈，0x4000 是 90°，0x8000 是 180°，加减自然回绕，不用取模
+//! - 结果是 Q15 定点数：32767 约等于 1.0，-32768 是 -1.0(正弦最小只到 -32767)
+//! - 只存四分之一个周期(0°~90°，257 项)，其他象限用对称性翻过来；表项之间线性插值，
+//!   和浮点算的比误差在 2 个 LSB 以内(约 0.00006)
+//!
+//! 表是编译期用 `const fn` 算出来的，浮点只在编译的时候用，运行时只有整数运算。
+
+/// 一整圈是多少(角度的单位是 1/65536 圈)
+pub const FULL_TURN: u32 = 1 << 16;
+
+/// 90° 是多少
+pub const QUARTER_TURN: u16 = 1 << 14;
+
+/// Q15 的 1.0(能表示的最大值)
+pub const Q15_ONE: i16 = i16::MAX;
+
+/// 四分之一周期分成多少段
+pub(crate) const TABLE_STEPS: usize = 256;
+
+/// 每一段里有多少个角度单位
+const STEP: u32 = QUARTER_TURN as u32 / TABLE_STEPS as u32;
+
+/// 0°~90° 的正弦，两头都包括
+static QUARTER_SINE: [i16; TABLE_STEPS + 1] = quarter_sine_table();
+
+/// 编译期算正弦表：泰勒级数，x 不超过 π/2 的时候加到 x^21 已经比 Q15 精度高得多
+pub(crate) const fn quarter_sine_table() -> [i16; TABLE_STEPS + 1] {
+    let mut table = [0i16; TABLE_STEPS + 1];
+    let mut i = 0;
+    while i <= TABLE_STEPS {
+        let x = core::f64::consts::FRAC_PI_2 * i as f64 / TABLE_STEPS as f64;
+        let mut term = x;
+        let mut sum = x;
+        let mut n = 1;
+        while n < 11 {
+            term = -term * x * x / ((2 * n) as f64 * (2 * n + 1) as f64);
+            sum += term;
+            n += 1;
+        }
+        let value = (sum * 32768.0 + 0.5) as i32;
+        table[i] = if value > i16::MAX as i32 {
+            i16::MAX
+        } else {
+            value as i16
+        };
+        i += 1;
+    }
+    table
+}
+
+/// 第一象限里的正弦，`angle` 是 0..=QUARTER_TURN
+fn quarter(angle: u32) -> i16 {
+    let index = (angle / STEP) as usize;
+    let frac = (angle % STEP) as i32;
+    let low = QUARTER_SINE[index] as i32;
+    let Some(&high) = QUARTER_SINE.get(index + 1) else {
+        return low as i16;
+    };
+    (low + (high as i32 - low) * frac / STEP as i32) as i16
+}
+
+/// 正弦，Q15
+pub fn sin_q15(angle: u16) -> i16 {
+    let within = (angle % QUARTER_TURN) as u32;
+    match angle / QUARTER_TURN {
+        0 => quarter(within),
+        1 => quarter(QUARTER_TURN as u32 - within),
+        2 => -quarter(within),
+        _ => -quarter(QUARTER_TURN as u32 - within),
+    }
+}
+
+/// 余弦，Q15
+pub fn cos_q15(angle: u16) -> i16 {
+    sin_q15(angle.wrapping_add(QUARTER_TURN))
+}
+
+/// 角度(度)换成 1/65536 圈，超过 360 的自动绕回来
+pub const fn degrees(deg: u32) -> u16 {
+    ((deg % 360) * FULL_TURN / 360) as u16
+}
+
+/// `value` 乘一个 Q15 的系数，四舍五入。画图的时候拿半径乘 `sin_q15`/`cos_q15` 用
+pub fn mul_q15(value: i32, q15: i16) -> i32 {
+    (value * q15 as i32 + (1 << 14)) >> 15
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 整圈每一个角度都和浮点比，误差在文档说的 2 个 LSB 以内
+    #[test]
+    fn matches_floating_point_over_the_whole_turn() {
+        for angle in 0..=u16::MAX {
+            let radians = angle as f64 / FULL_TURN as f64 * core::f64::consts::TAU;
+            for (fixed, exact) in [
+                (sin_q15(angle), radians.sin()),
+                (cos_q15(angle), radians.cos()),
+            ] {
+                let error = (fixed as f64 - exact * 32768.0).abs();
+                assert!(error <= 2.0, "angle {angle:#06x}: {fixed} vs {exact}");
+            }
+        }
+    }
+
+    #[test]
+    fn exact_at_the_quadrants() {
+        let quadrants = [0x0000, 0x4000, 0x8000, 0xC000];
+        let sines: [i16; 4] = quadrants.map(sin_q15);
+        let cosines: [i16; 4] = quadrants.map(cos_q15);
+        assert_eq!(sines, [0, Q15_ONE, 0, -Q15_ONE]);
+        assert_eq!(cosines, [Q15_ONE, 0, -Q15_ONE, 0]);
+    }
+
+    #[test]
+    fn degrees_wrap_and_scale() {
+        assert_eq!(degrees(0), 0);
+        assert_eq!(degrees(90), QUARTER_TURN);
+        assert_eq!(degrees(180), 0x8000);
+        assert_eq!(degrees(450), QUARTER_TURN);
+        assert_eq!(mul_q15(100, Q15_ONE), 100);
+        assert_eq!(mul_q15(100, -Q15_ONE), -100);
+        assert_eq!(mul_q15(100, sin_q15(degrees(30))), 50);
+    }
+}
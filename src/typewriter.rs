@@ -0,0 +1,151 @@
+//! 打字机效果：一段文字一个字一个字地出来，最后一个字后面跟着一个方块光标
+//!
+//! `type_text` 是阻塞的：每打一个字 flush 一次，然后等 `char_delay_ms`，等的时候每 10ms 问一次 `skip`，
+//! 返回 true(一般是有人按了键)就把剩下的字一次画完、flush，马上返回。画之前不清屏，要清的话调用方先清。
+//!
+//! 一行放不下的按 `wrap_lines` 的规矩换行(按空格断，太长的单词硬切)，从 `origin` 画到屏幕右边，
+//! 往下超出屏幕的行不画。断行处的空格不算字，不占时间。
+//!
+//! 打完以后光标擦掉，返回光标最后在哪里；想让它在结尾闪几下就接着调 `blink_cursor`。
+
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::primitives::{Primitive, PrimitiveStyle, Rectangle};
+use embedded_graphics::text::{Baseline, Text};
+use embedded_graphics::Drawable;
+use embedded_hal::delay::DelayNs;
+
+use crate::display::BufferedDisplay;
+use crate::text::wrap_lines;
+
+/// 等的时候隔多久问一次要不要跳过(毫秒)
+const SKIP_POLL_MS: u32 = 10;
+
+/// 光标亮、灭各多久(毫秒)
+pub const CURSOR_BLINK_MS: u32 = 400;
+
+/// `type_text` 打完了停在哪
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Typed {
+    /// 最后一个字后面、光标的左上角
+    pub cursor: Point,
+    /// 是不是被 `skip` 打断、一次画完的
+    pub skipped: bool,
+}
+
+/// 等 `ms` 毫秒，期间 `skip` 返回 true 就提前结束并返回 true
+fn wait<T: DelayNs>(timer: &mut T, ms: u32, skip: &mut impl FnMut() -> bool) -> bool {
+    let mut waited = 0;
+    while waited < ms {
+        if skip() {
+            return true;
+        }
+        let step = SKIP_POLL_MS.min(ms - waited);
+        timer.delay_ms(step);
+        waited += step;
+    }
+    false
+}
+
+/// 左上角在 `at`、一个字大小的光标，`on` 是画出来还是擦掉
+fn draw_cursor<D: BufferedDisplay>(
+    display: &mut D,
+    at: Point,
+    style: &MonoTextStyle<'_, BinaryColor>,
+    on: bool,
+) -> Result<(), D::Error> {
+    let color = if on {
+        BinaryColor::On
+    } else {
+        BinaryColor::Off
+    };
+    Rectangle::new(at, style.font.character_size)
+        .into_styled(PrimitiveStyle::with_fill(color))
+        .draw(display)
+}
+
+/// 一个字一个字地把 `text` 打出来，`origin` 是第一行的左上角，每个字之间等 `char_delay_ms`
+///
+/// `skip` 返回 true 就不再等，剩下的一次画完。flush 失败直接返回错误，屏幕上停在打到的那个字
+pub fn type_text<D, T>(
+    display: &mut D,
+    text: &str,
+    style: MonoTextStyle<'_, BinaryColor>,
+    origin: Point,
+    char_delay_ms: u32,
+    timer: &mut T,
+    mut skip: impl FnMut() -> bool,
+) -> Result<Typed, D::Error>
+where
+    D: BufferedDisplay,
+    T: DelayNs,
+{
+    let font = style.font;
+    let advance = (font.character_size.width + font.character_spacing) as i32;
+    let line_height = font.character_size.height as i32;
+    let Size { width, height } = display.bounding_box().size;
+    let max_width = width.saturating_sub(origin.x.max(0) as u32);
+
+    let mut cursor = origin;
+    let mut skipped = false;
+    for (row, line) in wrap_lines(text, font, max_width).enumerate() {
+        let top = origin.y + row as i32 * line_height;
+        if top >= height as i32 {
+            break;
+        }
+        if row > 0 && !skipped {
+            // 换行了：上一行末尾的光标擦掉，挪到这一行开头
+            draw_cursor(display, cursor, &style, false)?;
+        }
+        cursor = Point::new(origin.x, top);
+        for (at, ch) in line.char_indices() {
+            let glyph = &line[at..at + ch.len_utf8()];
+            if !skipped {
+                draw_cursor(display, cursor, &style, false)?;
+            }
+            Text::with_baseline(glyph, cursor, style, Baseline::Top).draw(display)?;
+            cursor.x += advance;
+            if skipped {
+                continue;
+            }
+            draw_cursor(display, cursor, &style, true)?;
+            display.flush()?;
+            skipped = wait(timer, char_delay_ms, &mut skip);
+        }
+    }
+    draw_cursor(display, cursor, &style, false)?;
+    display.flush()?;
+    Ok(Typed { cursor, skipped })
+}
+
+/// 在 `at`(一般是 `Typed::cursor`)让光标闪 `duration_ms` 毫秒，结束的时候是擦掉的
+///
+/// `skip` 返回 true 就提前结束，返回是不是提前结束的
+pub fn blink_cursor<D, T>(
+    display: &mut D,
+    at: Point,
+    style: MonoTextStyle<'_, BinaryColor>,
+    duration_ms: u32,
+    timer: &mut T,
+    mut skip: impl FnMut() -> bool,
+) -> Result<bool, D::Error>
+where
+    D: BufferedDisplay,
+    T: DelayNs,
+{
+    let mut elapsed = 0;
+    let mut on = true;
+    let mut skipped = false;
+    while elapsed < duration_ms && !skipped {
+        draw_cursor(display, at, &style, on)?;
+        display.flush()?;
+        let step = CURSOR_BLINK_MS.min(duration_ms - elapsed);
+        skipped = wait(timer, step, &mut skip);
+        elapsed += step;
+        on = !on;
+    }
+    draw_cursor(display, at, &style, false)?;
+    display.flush()?;
+    Ok(skipped)
+}
@@ -0,0 +1,137 @@
+//! USB 复合设备：一个 CDC 串口 + 一个厂商自定义的 HID 接口
+//!
+//! USB 协议栈没有中断驱动，需要主循环不停地调用 `poll`，
+//! 两次 poll 之间间隔太久(几十毫秒以上)电脑端会觉得设备反应慢，但不会掉线。
+
+use heapless::Deque;
+use rp2040_hal::usb::UsbBus;
+use usb_device::bus::UsbBusAllocator;
+use usb_device::device::{StringDescriptors, UsbDevice, UsbDeviceBuilder, UsbDeviceState, UsbVidPid};
+use usbd_hid::hid_class::HIDClass;
+use usbd_serial::SerialPort;
+
+use crate::event_log::{self, SystemEvent};
+use crate::host_status::{REPORT_DESCRIPTOR, REPORT_LEN};
+
+/// pid.codes 给开源项目测试用的 VID/PID，电脑端脚本按这个找设备
+pub const USB_VID: u16 = 0x16c0;
+pub const USB_PID: u16 = 0x27dd;
+
+/// HID 端点的轮询间隔(毫秒)
+const HID_POLL_MS: u8 = 10;
+
+/// 串口发送队列长度。电脑端没打开串口的时候发不出去，队列满了新数据直接丢掉
+const SERIAL_TX_CAPACITY: usize = 256;
+
+/// 设备上的所有 USB 功能
+pub struct UsbLink<'a> {
+    device: UsbDevice<'a, UsbBus>,
+    serial: SerialPort<'a, UsbBus>,
+    hid: HIDClass<'a, UsbBus>,
+    serial_tx: Deque<u8, SERIAL_TX_CAPACITY>,
+    /// 上一次 poll 的时候是不是枚举好了，变了就记一条事件
+    configured: bool,
+}
+
+impl<'a> UsbLink<'a> {
+    /// `bus` 必须活得和设备一样久，一般用 `cortex_m::singleton!` 放到静态区
+    pub fn new(bus: &'a UsbBusAllocator<UsbBus>) -> Self {
+        // 注意顺序：接口号按创建顺序分配，CDC 占 0/1，HID 是 2
+        let serial = SerialPort::new(bus);
+        let hid = HIDClass::new(bus, REPORT_DESCRIPTOR, HID_POLL_MS);
+
+        let device = UsbDeviceBuilder::new(bus, UsbVidPid(USB_VID, USB_PID))
+            .strings(&[StringDescriptors::default()
+                .manufacturer("xhz10")
+                .product("rp2040 oled")
+                .serial_number("0001")])
+            .unwrap()
+            // 多个接口要用 IAD 把 CDC 的两个接口绑在一起，不然 Windows 认不出来
+            .composite_with_iads()
+            .build();
+
+        Self {
+            device,
+            serial,
+            hid,
+            serial_tx: Deque::new(),
+            configured: false,
+        }
+    }
+
+    /// 处理 USB 总线事件，主循环里每一圈都要调
+    pub fn poll(&mut self) {
+        self.device.poll(&mut [&mut self.serial, &mut self.hid]);
+        let configured = self.is_configured();
+        if configured != self.configured {
+            self.configured = configured;
+            event_log::record(if configured {
+                SystemEvent::UsbConnected
+            } else {
+                SystemEvent::UsbDisconnected
+            });
+        }
+        self.pump_serial();
+    }
+
+    /// 读串口收到的数据，返回读到的字节数
+    pub fn read_serial(&mut self, buf: &mut [u8]) -> usize {
+        self.serial.read(buf).unwrap_or(0)
+    }
+
+    /// 往串口写数据。先进发送队列，能发多少发多少，剩下的在之后的 `poll` 里接着发
+    pub fn write_serial(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            if self.serial_tx.push_back(byte).is_err() {
+                break;
+            }
+        }
+        self.pump_serial();
+    }
+
+    /// 发送队列还能放多少字节，大段输出(比如导出数据记录)按这个分批写，免得被截掉
+    pub fn serial_tx_free(&self) -> usize {
+        self.serial_tx.capacity() - self.serial_tx.len()
+    }
+
+    /// 把发送队列里的数据尽量交给 CDC 端点
+    fn pump_serial(&mut self) {
+        while !self.serial_tx.is_empty() {
+            let (front, _) = self.serial_tx.as_slices();
+            match self.serial.write(front) {
+                Ok(n) if n > 0 => {
+                    for _ in 0..n {
+                        self.serial_tx.pop_front();
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// 是否已经被电脑枚举完成
+    pub fn is_configured(&self) -> bool {
+        self.device.state() == UsbDeviceState::Configured
+    }
+
+    /// 读一个电脑发来的 HID output report，没有的话返回 None
+    pub fn read_host_report(&mut self, buf: &mut [u8; REPORT_LEN]) -> Option<usize> {
+        match self.hid.pull_raw_output(buf) {
+            Ok(n) if n > 0 => Some(n),
+            _ => None,
+        }
+    }
+
+    /// 发一个 HID input report 给电脑，端点忙的时候直接丢掉(ack 丢了电脑端会超时重发)
+    pub fn send_host_report(&mut self, report: &[u8; REPORT_LEN]) {
+        let _ = self.hid.push_raw_input(report);
+    }
+}
+
+/// 方便用 `write!` 往串口打印格式化文本
+impl core::fmt::Write for UsbLink<'_> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.write_serial(s.as_bytes());
+        Ok(())
+    }
+}
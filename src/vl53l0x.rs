@@ -0,0 +1,442 @@
+//! VL53L0X 激光测距(ToF)传感器，和屏幕挂在同一条 I2C 总线上
+//!
+//! ST 没公开寄存器手册，只给了一个很大的 C 库。这里的初始化照着 Pololu 的 Arduino 库
+//! (它又是照着 ST 的 API 抄的)：
+//!
+//! 1. 数据初始化：IO 切到 2.8V，读出 "stop variable"(每次单次测量之前都要写回去)，关掉两个信号率检查
+//! 2. 静态初始化：按 NVM 里记的 SPAD 个数和类型配参考 SPAD，再写一大串 ST 给的默认调校寄存器(`TUNING`)
+//! 3. 中断配成"有新结果"，跑两次参考校准(VHV 和相位)
+//!
+//! 没做 ST 库里按测量时间预算重算各阶段超时那一步，用的是默认预算，一次测量 30ms 左右。
+//!
+//! 测量用单次模式，不用连续模式：连续测量一直开着激光要十几毫安，单次模式测完就停。
+//! 和温湿度传感器一样分成 `start` 和 `read` 两步，`DistanceMonitor` 在主循环里按节奏推。
+//! 看不到东西的时候芯片报 8190/8191 之类的数，或者量程状态不是"有效"，这些都当成没读到，不会显示成真的距离。
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+
+/// 出厂默认地址
+pub const ADDRESS: u8 = 0x29;
+
+/// 量程上限(mm)，默认模式下再远就不准了。超出量程的时候芯片会报 8190、8191，也在这里挡掉
+pub const MAX_RANGE_MM: u16 = 2000;
+
+/// 醒着和屏幕睡着的时候多久测一次(毫秒)。睡着的时候只用来等人靠近，2 次每秒够了
+pub const AWAKE_POLL_MS: u32 = 100;
+pub const ASLEEP_POLL_MS: u32 = 500;
+
+/// `start` 之后等多久再去看结果
+const MEASUREMENT_MS: u64 = 33;
+
+/// 结果还没出来的时候再等多久
+const BUSY_RETRY_MS: u64 = 5;
+
+/// 一次测量最多等多久，过了还没结果就当读失败
+const MEASUREMENT_TIMEOUT_MS: u64 = 100;
+
+/// 初始化里等芯片的时候最多等几毫秒
+const INIT_TIMEOUT_MS: u32 = 100;
+
+const REG_SYSRANGE_START: u8 = 0x00;
+const REG_SYSTEM_SEQUENCE_CONFIG: u8 = 0x01;
+const REG_SYSTEM_INTERRUPT_CONFIG_GPIO: u8 = 0x0A;
+const REG_SYSTEM_INTERRUPT_CLEAR: u8 = 0x0B;
+const REG_RESULT_INTERRUPT_STATUS: u8 = 0x13;
+const REG_RESULT_RANGE_STATUS: u8 = 0x14;
+const REG_FINAL_RANGE_MIN_COUNT_RATE: u8 = 0x44;
+const REG_MSRC_CONFIG_CONTROL: u8 = 0x60;
+const REG_GPIO_HV_MUX_ACTIVE_HIGH: u8 = 0x84;
+const REG_VHV_CONFIG_PAD_SCL_SDA_EXTSUP_HV: u8 = 0x89;
+const REG_GLOBAL_CONFIG_SPAD_ENABLES_REF_0: u8 = 0xB0;
+const REG_GLOBAL_CONFIG_REF_EN_START_SELECT: u8 = 0xB6;
+const REG_DYNAMIC_SPAD_NUM_REQUESTED_REF_SPAD: u8 = 0x4E;
+const REG_DYNAMIC_SPAD_REF_EN_START_OFFSET: u8 = 0x4F;
+const REG_MODEL_ID: u8 = 0xC0;
+
+/// 型号寄存器的值
+const MODEL_ID: u8 = 0xEE;
+
+/// 量程状态(结果里第一个字节的 bit6..3)里表示"有效"的值
+const RANGE_STATUS_VALID: u8 = 11;
+
+/// 最终测量的信号率下限 0.25 MCPS，格式是 9.7 定点
+const SIGNAL_RATE_LIMIT: u16 = 32;
+
+/// 访问 NVM 和 stop variable 的前后都要先写这一串、再反着写回去
+const PRIVATE_ENTER: [(u8, u8); 3] = [(0x80, 0x01), (0xFF, 0x01), (0x00, 0x00)];
+const PRIVATE_LEAVE: [(u8, u8); 3] = [(0x00, 0x01), (0xFF, 0x00), (0x80, 0x00)];
+
+/// ST API 里的 DefaultTuningSettings，原样照抄，意思没有公开
+const TUNING: [(u8, u8); 80] = [
+    (0xFF, 0x01),
+    (0x00, 0x00),
+    (0xFF, 0x00),
+    (0x09, 0x00),
+    (0x10, 0x00),
+    (0x11, 0x00),
+    (0x24, 0x01),
+    (0x25, 0xFF),
+    (0x75, 0x00),
+    (0xFF, 0x01),
+    (0x4E, 0x2C),
+    (0x48, 0x00),
+    (0x30, 0x20),
+    (0xFF, 0x00),
+    (0x30, 0x09),
+    (0x54, 0x00),
+    (0x31, 0x04),
+    (0x32, 0x03),
+    (0x40, 0x83),
+    (0x46, 0x25),
+    (0x60, 0x00),
+    (0x27, 0x00),
+    (0x50, 0x06),
+    (0x51, 0x00),
+    (0x52, 0x96),
+    (0x56, 0x08),
+    (0x57, 0x30),
+    (0x61, 0x00),
+    (0x62, 0x00),
+    (0x64, 0x00),
+    (0x65, 0x00),
+    (0x66, 0xA0),
+    (0xFF, 0x01),
+    (0x22, 0x32),
+    (0x47, 0x14),
+    (0x49, 0xFF),
+    (0x4A, 0x00),
+    (0xFF, 0x00),
+    (0x7A, 0x0A),
+    (0x7B, 0x00),
+    (0x78, 0x21),
+    (0xFF, 0x01),
+    (0x23, 0x34),
+    (0x42, 0x00),
+    (0x44, 0xFF),
+    (0x45, 0x26),
+    (0x46, 0x05),
+    (0x40, 0x40),
+    (0x0E, 0x06),
+    (0x20, 0x1A),
+    (0x43, 0x40),
+    (0xFF, 0x00),
+    (0x34, 0x03),
+    (0x35, 0x44),
+    (0xFF, 0x01),
+    (0x31, 0x04),
+    (0x4B, 0x09),
+    (0x4C, 0x05),
+    (0x4D, 0x04),
+    (0xFF, 0x00),
+    (0x44, 0x00),
+    (0x45, 0x20),
+    (0x47, 0x08),
+    (0x48, 0x28),
+    (0x67, 0x00),
+    (0x70, 0x04),
+    (0x71, 0x01),
+    (0x72, 0xFE),
+    (0x76, 0x00),
+    (0x77, 0x00),
+    (0xFF, 0x01),
+    (0x0D, 0x01),
+    (0xFF, 0x00),
+    (0x80, 0x01),
+    (0x01, 0xF8),
+    (0xFF, 0x01),
+    (0x8E, 0x01),
+    (0x00, 0x01),
+    (0xFF, 0x00),
+    (0x80, 0x00),
+];
+
+/// 初始化哪一步出的错，只用来打日志
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+enum InitError {
+    Bus,
+    SpadInfoTimeout,
+    CalibrationTimeout,
+}
+
+/// 一个初始化好的 VL53L0X
+#[derive(Debug, Clone, Copy)]
+pub struct Vl53l0x {
+    stop_variable: u8,
+}
+
+impl Vl53l0x {
+    /// 认型号，认出来就做完整的初始化(阻塞几十毫秒，只在开机的时候)。找不到或者初始化失败返回 None
+    pub fn probe<I: I2c, T: DelayNs>(i2c: &mut I, timer: &mut T) -> Option<Self> {
+        if read_reg(i2c, REG_MODEL_ID).ok()? != MODEL_ID {
+            return None;
+        }
+        match init(i2c, timer) {
+            Ok(sensor) => {
+                defmt::info!("VL53L0X ready");
+                Some(sensor)
+            }
+            Err(err) => {
+                defmt::warn!("VL53L0X init failed: {}", err);
+                None
+            }
+        }
+    }
+
+    /// 开始一次单次测量，马上返回
+    pub fn start<I: I2c>(&mut self, i2c: &mut I) -> Result<(), I::Error> {
+        write_regs(i2c, &PRIVATE_ENTER)?;
+        write_reg(i2c, 0x91, self.stop_variable)?;
+        write_regs(i2c, &PRIVATE_LEAVE)?;
+        write_reg(i2c, REG_SYSRANGE_START, 0x01)
+    }
+
+    /// 读测量结果。还没测完返回 Ok(None)，测完了返回 Ok(Some(距离))，看不到东西的时候距离是 None
+    pub fn read<I: I2c>(&mut self, i2c: &mut I) -> Result<Option<Option<u16>>, I::Error> {
+        if read_reg(i2c, REG_RESULT_INTERRUPT_STATUS)? & 0x07 == 0 {
+            return Ok(None);
+        }
+        let mut result = [0u8; 12];
+        i2c.write_read(ADDRESS, &[REG_RESULT_RANGE_STATUS], &mut result)?;
+        write_reg(i2c, REG_SYSTEM_INTERRUPT_CLEAR, 0x01)?;
+        let status = (result[0] >> 3) & 0x0F;
+        let raw = u16::from_be_bytes([result[10], result[11]]);
+        Ok(Some(filter_range(status, raw)))
+    }
+}
+
+/// 只留量程状态有效、在量程里的读数
+fn filter_range(status: u8, raw_mm: u16) -> Option<u16> {
+    (status == RANGE_STATUS_VALID && raw_mm <= MAX_RANGE_MM).then_some(raw_mm)
+}
+
+fn init<I: I2c, T: DelayNs>(i2c: &mut I, timer: &mut T) -> Result<Vl53l0x, InitError> {
+    let bus = |_| InitError::Bus;
+
+    // 1. 数据初始化
+    let pad = read_reg(i2c, REG_VHV_CONFIG_PAD_SCL_SDA_EXTSUP_HV).map_err(bus)?;
+    write_reg(i2c, REG_VHV_CONFIG_PAD_SCL_SDA_EXTSUP_HV, pad | 0x01).map_err(bus)?;
+    write_reg(i2c, 0x88, 0x00).map_err(bus)?;
+    write_regs(i2c, &PRIVATE_ENTER).map_err(bus)?;
+    let stop_variable = read_reg(i2c, 0x91).map_err(bus)?;
+    write_regs(i2c, &PRIVATE_LEAVE).map_err(bus)?;
+    let msrc = read_reg(i2c, REG_MSRC_CONFIG_CONTROL).map_err(bus)?;
+    write_reg(i2c, REG_MSRC_CONFIG_CONTROL, msrc | 0x12).map_err(bus)?;
+    let [hi, lo] = SIGNAL_RATE_LIMIT.to_be_bytes();
+    i2c.write(ADDRESS, &[REG_FINAL_RANGE_MIN_COUNT_RATE, hi, lo])
+        .map_err(bus)?;
+    write_reg(i2c, REG_SYSTEM_SEQUENCE_CONFIG, 0xFF).map_err(bus)?;
+
+    // 2. 静态初始化：参考 SPAD
+    let (spad_count, aperture) = spad_info(i2c, timer)?;
+    let mut spad_map = [0u8; 7];
+    spad_map[0] = REG_GLOBAL_CONFIG_SPAD_ENABLES_REF_0;
+    i2c.write_read(
+        ADDRESS,
+        &[REG_GLOBAL_CONFIG_SPAD_ENABLES_REF_0],
+        &mut spad_map[1..],
+    )
+    .map_err(bus)?;
+    write_regs(
+        i2c,
+        &[
+            (0xFF, 0x01),
+            (REG_DYNAMIC_SPAD_REF_EN_START_OFFSET, 0x00),
+            (REG_DYNAMIC_SPAD_NUM_REQUESTED_REF_SPAD, 0x2C),
+            (0xFF, 0x00),
+            (REG_GLOBAL_CONFIG_REF_EN_START_SELECT, 0xB4),
+        ],
+    )
+    .map_err(bus)?;
+    // 孔径型的 SPAD 从第 12 个开始，只留 NVM 里说的那么多个
+    let first = if aperture { 12 } else { 0 };
+    let mut enabled = 0;
+    for i in 0..48 {
+        let byte = &mut spad_map[1 + i / 8];
+        let bit = 1 << (i % 8);
+        if i < first || enabled == spad_count {
+            *byte &= !bit;
+        } else if *byte & bit != 0 {
+            enabled += 1;
+        }
+    }
+    i2c.write(ADDRESS, &spad_map).map_err(bus)?;
+
+    // 默认调校
+    write_regs(i2c, &TUNING).map_err(bus)?;
+
+    // 3. 中断：有新结果的时候置位(低电平有效)，然后做参考校准
+    write_reg(i2c, REG_SYSTEM_INTERRUPT_CONFIG_GPIO, 0x04).map_err(bus)?;
+    let mux = read_reg(i2c, REG_GPIO_HV_MUX_ACTIVE_HIGH).map_err(bus)?;
+    write_reg(i2c, REG_GPIO_HV_MUX_ACTIVE_HIGH, mux & !0x10).map_err(bus)?;
+    write_reg(i2c, REG_SYSTEM_INTERRUPT_CLEAR, 0x01).map_err(bus)?;
+
+    write_reg(i2c, REG_SYSTEM_SEQUENCE_CONFIG, 0x01).map_err(bus)?;
+    single_ref_calibration(i2c, timer, 0x40)?;
+    write_reg(i2c, REG_SYSTEM_SEQUENCE_CONFIG, 0x02).map_err(bus)?;
+    single_ref_calibration(i2c, timer, 0x00)?;
+    // 平时的测量不做 MSRC 和 TCC 两步
+    write_reg(i2c, REG_SYSTEM_SEQUENCE_CONFIG, 0xE8).map_err(bus)?;
+
+    Ok(Vl53l0x { stop_variable })
+}
+
+/// 从 NVM 里读参考 SPAD 的个数和是不是孔径型的
+fn spad_info<I: I2c, T: DelayNs>(i2c: &mut I, timer: &mut T) -> Result<(u8, bool), InitError> {
+    let bus = |_| InitError::Bus;
+    write_regs(i2c, &PRIVATE_ENTER).map_err(bus)?;
+    write_reg(i2c, 0xFF, 0x06).map_err(bus)?;
+    let reg = read_reg(i2c, 0x83).map_err(bus)?;
+    write_reg(i2c, 0x83, reg | 0x04).map_err(bus)?;
+    write_regs(
+        i2c,
+        &[
+            (0xFF, 0x07),
+            (0x81, 0x01),
+            (0x80, 0x01),
+            (0x94, 0x6B),
+            (0x83, 0x00),
+        ],
+    )
+    .map_err(bus)?;
+    wait_for(timer, InitError::SpadInfoTimeout, || {
+        read_reg(i2c, 0x83).map(|v| v != 0x00).map_err(bus)
+    })?;
+    write_reg(i2c, 0x83, 0x01).map_err(bus)?;
+    let info = read_reg(i2c, 0x92).map_err(bus)?;
+    write_regs(i2c, &[(0x81, 0x00), (0xFF, 0x06)]).map_err(bus)?;
+    let reg = read_reg(i2c, 0x83).map_err(bus)?;
+    write_reg(i2c, 0x83, reg & !0x04).map_err(bus)?;
+    write_reg(i2c, 0xFF, 0x01).map_err(bus)?;
+    write_regs(i2c, &PRIVATE_LEAVE).map_err(bus)?;
+    Ok((info & 0x7F, info & 0x80 != 0))
+}
+
+fn single_ref_calibration<I: I2c, T: DelayNs>(
+    i2c: &mut I,
+    timer: &mut T,
+    vhv_init: u8,
+) -> Result<(), InitError> {
+    let bus = |_| InitError::Bus;
+    write_reg(i2c, REG_SYSRANGE_START, 0x01 | vhv_init).map_err(bus)?;
+    wait_for(timer, InitError::CalibrationTimeout, || {
+        read_reg(i2c, REG_RESULT_INTERRUPT_STATUS)
+            .map(|v| v & 0x07 != 0)
+            .map_err(bus)
+    })?;
+    write_reg(i2c, REG_SYSTEM_INTERRUPT_CLEAR, 0x01).map_err(bus)?;
+    write_reg(i2c, REG_SYSRANGE_START, 0x00).map_err(bus)
+}
+
+/// 每毫秒问一次 `ready`，`INIT_TIMEOUT_MS` 之内没好返回 `timeout`
+fn wait_for<T: DelayNs>(
+    timer: &mut T,
+    timeout: InitError,
+    mut ready: impl FnMut() -> Result<bool, InitError>,
+) -> Result<(), InitError> {
+    for _ in 0..INIT_TIMEOUT_MS {
+        if ready()? {
+            return Ok(());
+        }
+        timer.delay_ms(1);
+    }
+    Err(timeout)
+}
+
+fn read_reg<I: I2c>(i2c: &mut I, reg: u8) -> Result<u8, I::Error> {
+    let mut value = [0u8];
+    i2c.write_read(ADDRESS, &[reg], &mut value)?;
+    Ok(value[0])
+}
+
+fn write_reg<I: I2c>(i2c: &mut I, reg: u8, value: u8) -> Result<(), I::Error> {
+    i2c.write(ADDRESS, &[reg, value])
+}
+
+fn write_regs<I: I2c>(i2c: &mut I, regs: &[(u8, u8)]) -> Result<(), I::Error> {
+    regs.iter()
+        .try_for_each(|&(reg, value)| write_reg(i2c, reg, value))
+}
+
+/// 按节奏测距，记住最近一次的结果
+#[derive(Debug, Clone, Copy)]
+pub struct DistanceMonitor {
+    sensor: Vl53l0x,
+    distance: Option<u16>,
+    /// 已经 `start` 了，在等结果
+    measuring: bool,
+    /// 下次什么时候动(开始测量或者看结果)
+    next_ms: u64,
+    /// 这次测量最晚什么时候出结果
+    deadline_ms: u64,
+    poll_ms: u32,
+}
+
+impl DistanceMonitor {
+    pub const fn new(sensor: Vl53l0x) -> Self {
+        Self {
+            sensor,
+            distance: None,
+            measuring: false,
+            next_ms: 0,
+            deadline_ms: 0,
+            poll_ms: AWAKE_POLL_MS,
+        }
+    }
+
+    /// 换测量间隔，下一次测量开始生效
+    pub fn set_poll_ms(&mut self, poll_ms: u32) {
+        self.poll_ms = poll_ms;
+    }
+
+    /// 到时间该动总线了
+    pub fn is_due(&self, now_ms: u64) -> bool {
+        now_ms >= self.next_ms
+    }
+
+    /// 到时间了就开始测量或者看结果，调用方保证总线这会儿是空的。返回 true 表示刚测完一次
+    pub fn service<I: I2c>(&mut self, i2c: &mut I, now_ms: u64) -> bool {
+        if !self.is_due(now_ms) {
+            return false;
+        }
+        if !self.measuring {
+            match self.sensor.start(i2c) {
+                Ok(()) => {
+                    self.measuring = true;
+                    self.next_ms = now_ms + MEASUREMENT_MS;
+                    self.deadline_ms = now_ms + MEASUREMENT_TIMEOUT_MS;
+                }
+                Err(_) => self.failed(now_ms),
+            }
+            return false;
+        }
+        match self.sensor.read(i2c) {
+            Ok(None) if now_ms < self.deadline_ms => {
+                self.next_ms = now_ms + BUSY_RETRY_MS;
+                false
+            }
+            Ok(Some(distance)) => {
+                self.distance = distance;
+                self.measuring = false;
+                self.next_ms = now_ms + self.poll_ms as u64;
+                true
+            }
+            Ok(None) | Err(_) => {
+                self.failed(now_ms);
+                false
+            }
+        }
+    }
+
+    fn failed(&mut self, now_ms: u64) {
+        defmt::warn!("VL53L0X measurement failed");
+        self.distance = None;
+        self.measuring = false;
+        self.next_ms = now_ms + self.poll_ms as u64;
+    }
+
+    /// 最近一次测到的距离(mm)，看不到东西或者读失败是 None
+    pub fn distance_mm(&self) -> Option<u16> {
+        self.distance
+    }
+}
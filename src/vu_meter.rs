@@ -0,0 +1,253 @@
+//! 话筒电平表的计算：一个窗口的样本算出峰值、RMS、削顶，再按最近一段时间的电平分布做直方图均衡
+//!
+//! 样本从哪来见 mic.rs(feature `mic-vu`)，这里只管算，全是整数。
+//!
+//! 普通的电平表按 dB 线性画，安静的房间里指针一直趴在左边，一说话又一下顶到头。
+//! 这里记一个电平(每窗口的 RMS，按 `BIN_DB` 分格)的直方图，条的长度是"比最近多少比例的窗口响"，
+//! 也就是累积分布。环境安静，刻度就往安静那头挤；环境吵，刻度就往吵那头挤，条总是在中间晃，看得出起伏。
+//! 直方图攒到 `HISTORY_LIMIT` 个窗口就全部减半，老的电平慢慢淡出去，换了环境一两分钟就适应。
+//! 具体多少 dB 另外写成数字。
+//!
+//! 峰值保持按峰值电平走：新的峰值更高就顶上去，保持 `HOLD_MS` 以后每个窗口落 1dB。
+//! 有一个样本离 0 或者满量程不到 `CLIP_MARGIN` 就算削顶，CLIP 标志亮 `CLIP_HOLD_MS`。
+
+use heapless::HistoryBuffer;
+
+use crate::fft::FFT_LEN;
+
+/// 12 位 ADC 的最大读数
+const ADC_MAX: u16 = 4095;
+
+/// 去掉直流以后的满量程幅度，0dBFS
+pub const FULL_SCALE: u16 = 2048;
+
+/// 原始读数离 0 或者满量程这么近就算削顶
+pub const CLIP_MARGIN: u16 = 16;
+
+/// 一个窗口在波形条上占几列
+pub const WAVE_COLUMNS: usize = 2;
+
+/// 波形条一共多少列，正好一屏宽
+pub const WAVE_LEN: usize = 128;
+
+/// 电平表最低到多少 dBFS，再小都算这么多
+pub const MIN_DB: i16 = -48;
+
+/// 直方图一格多少 dB
+pub const BIN_DB: i16 = 2;
+
+const BINS: usize = (-MIN_DB / BIN_DB) as usize;
+
+/// 直方图攒到这么多个窗口就全部减半(20ms 一个窗口的话大约一分钟)
+const HISTORY_LIMIT: u32 = 3000;
+
+/// 峰值保持多久(毫秒)
+pub const HOLD_MS: u64 = 1000;
+
+/// CLIP 标志亮多久(毫秒)
+pub const CLIP_HOLD_MS: u64 = 1000;
+
+/// 一个窗口的话筒样本算出来的东西，幅度都是去掉直流以后的，0..=`FULL_SCALE`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, defmt::Format)]
+pub struct MicWindow {
+    /// 直流偏置(原始读数的平均)
+    pub dc: u16,
+    pub peak: u16,
+    pub rms: u16,
+    pub clipped: bool,
+    /// 窗口分成几小段，每段的最大幅度，波形条用
+    pub wave: [u16; WAVE_COLUMNS],
+}
+
+impl MicWindow {
+    /// 从一个窗口的原始读数算。要遍历两遍(先求平均)，所以迭代器要能 clone
+    pub fn analyze<I>(samples: I) -> Self
+    where
+        I: Iterator<Item = u16> + Clone,
+    {
+        let (count, sum) = samples.clone().fold((0u32, 0u32), |(count, sum), raw| {
+            (count + 1, sum + raw as u32)
+        });
+        if count == 0 {
+            return Self::default();
+        }
+        let dc = sum / count;
+        let chunk = count.div_ceil(WAVE_COLUMNS as u32) as usize;
+        let mut window = Self {
+            dc: dc as u16,
+            ..Self::default()
+        };
+        let mut energy = 0u64;
+        for (i, raw) in samples.enumerate() {
+            if raw <= CLIP_MARGIN || raw >= ADC_MAX - CLIP_MARGIN {
+                window.clipped = true;
+            }
+            let amplitude = (raw as i32 - dc as i32)
+                .unsigned_abs()
+                .min(FULL_SCALE as u32) as u16;
+            window.peak = window.peak.max(amplitude);
+            energy += amplitude as u64 * amplitude as u64;
+            let column = &mut window.wave[i / chunk];
+            *column = (*column).max(amplitude);
+        }
+        window.rms = (energy / count as u64).isqrt() as u16;
+        window
+    }
+}
+
+/// 幅度换算成 dBFS 的十倍，最小 `MIN_DB`。对数用整数近似，误差不到 0.1dB
+pub fn dbfs10(amplitude: u16) -> i16 {
+    let floor = MIN_DB * 10;
+    if amplitude == 0 {
+        return floor;
+    }
+    // log2 的整数部分是最高位，小数部分拿下面 8 位线性插值，再加一项修正曲线
+    let msb = 15 - amplitude.leading_zeros() as i32;
+    let frac = ((((amplitude as u32) << (15 - msb)) >> 7) & 0xFF) as i32;
+    let log2_q8 = msb * 256 + frac + frac * (256 - frac) * 89 / 65536;
+    // 20*log10(a / 2048) = 6.0206 * (log2(a) - 11)
+    let full_scale_q8 = 11 * 256;
+    let db10 = (log2_q8 - full_scale_q8) * 60206 / 256_000;
+    db10.clamp(floor as i32, 0) as i16
+}
+
+/// 电平表的状态，窗口一个一个喂进来，页面来读
+#[derive(Debug)]
+pub struct VuMeter {
+    histogram: [u16; BINS],
+    total: u32,
+    /// 最近一个窗口的 RMS 和峰值(dBFS 的十倍)
+    level_db10: i16,
+    peak_db10: i16,
+    /// 峰值保持在哪，保持到什么时候
+    hold_db10: i16,
+    hold_until_ms: u64,
+    clip_until_ms: u64,
+    wave: HistoryBuffer<u16, WAVE_LEN>,
+    /// 最近一段去掉直流的样本，频谱页面拿去做 FFT
+    samples: [i16; FFT_LEN],
+    windows: u32,
+}
+
+impl Default for VuMeter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VuMeter {
+    pub const fn new() -> Self {
+        Self {
+            histogram: [0; BINS],
+            total: 0,
+            level_db10: MIN_DB * 10,
+            peak_db10: MIN_DB * 10,
+            hold_db10: MIN_DB * 10,
+            hold_until_ms: 0,
+            clip_until_ms: 0,
+            wave: HistoryBuffer::new(),
+            samples: [0; FFT_LEN],
+            windows: 0,
+        }
+    }
+
+    /// 喂一个窗口
+    pub fn push(&mut self, window: &MicWindow, now_ms: u64) {
+        self.level_db10 = dbfs10(window.rms);
+        self.peak_db10 = dbfs10(window.peak);
+
+        if self.total >= HISTORY_LIMIT {
+            for count in self.histogram.iter_mut() {
+                *count /= 2;
+            }
+            self.total = self.histogram.iter().map(|&count| count as u32).sum();
+        }
+        self.histogram[bin(self.level_db10)] += 1;
+        self.total += 1;
+
+        if self.peak_db10 >= self.hold_db10 {
+            self.hold_db10 = self.peak_db10;
+            self.hold_until_ms = now_ms + HOLD_MS;
+        } else if now_ms >= self.hold_until_ms {
+            self.hold_db10 = (self.hold_db10 - 10).max(self.peak_db10);
+        }
+
+        if window.clipped {
+            self.clip_until_ms = now_ms + CLIP_HOLD_MS;
+        }
+        self.wave.extend_from_slice(&window.wave);
+        self.windows = self.windows.wrapping_add(1);
+    }
+
+    /// 存下最近 `FFT_LEN` 个样本(去掉直流的，±2048)，不够的补 0。在 `push` 之前调，页面看到新窗口的时候样本也是新的
+    pub fn set_samples(&mut self, samples: impl Iterator<Item = i16>) {
+        self.samples = [0; FFT_LEN];
+        for (slot, sample) in self.samples.iter_mut().zip(samples) {
+            *slot = sample;
+        }
+    }
+
+    pub fn samples(&self) -> &[i16; FFT_LEN] {
+        &self.samples
+    }
+
+    /// 一共喂过几个窗口，页面拿来判断有没有新数据，0 是还没有话筒数据
+    pub fn windows(&self) -> u32 {
+        self.windows
+    }
+
+    /// 最近一个窗口的 RMS 电平(dBFS 的十倍)
+    pub fn level_db10(&self) -> i16 {
+        self.level_db10
+    }
+
+    /// 峰值保持(dBFS 的十倍)
+    pub fn hold_db10(&self) -> i16 {
+        self.hold_db10
+    }
+
+    /// 电平条多长，0..=256
+    pub fn level(&self) -> u32 {
+        self.equalize(self.level_db10)
+    }
+
+    /// 峰值保持的标记在哪，0..=256
+    pub fn hold(&self) -> u32 {
+        self.equalize(self.hold_db10)
+    }
+
+    /// CLIP 标志现在亮不亮
+    pub fn clipping(&self, now_ms: u64) -> bool {
+        now_ms < self.clip_until_ms
+    }
+
+    /// 波形条，从旧到新，每列一个幅度
+    pub fn wave(&self) -> impl Iterator<Item = u16> + '_ {
+        self.wave.oldest_ordered().copied()
+    }
+
+    /// 直方图均衡：有多少比例的窗口比 `db10` 安静(落在同一格的按位置插值)，0..=256。
+    /// 还没有历史的时候按 dB 线性，0dBFS 总是顶满
+    fn equalize(&self, db10: i16) -> u32 {
+        let offset = (db10 - MIN_DB * 10) as u32;
+        if db10 >= 0 {
+            return 256;
+        }
+        if self.total == 0 {
+            return offset * 256 / (-MIN_DB as u32 * 10);
+        }
+        let bin = bin(db10);
+        let below: u32 = self.histogram[..bin]
+            .iter()
+            .map(|&count| count as u32)
+            .sum();
+        let bin_width = BIN_DB as u32 * 10;
+        let inside = self.histogram[bin] as u32 * (offset % bin_width) / bin_width;
+        ((below + inside) * 256 / self.total).min(256)
+    }
+}
+
+/// 电平落在直方图的哪一格，0dBFS 算最后一格
+fn bin(db10: i16) -> usize {
+    (((db10 - MIN_DB * 10) / (BIN_DB * 10)) as usize).min(BINS - 1)
+}
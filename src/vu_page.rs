@@ -0,0 +1,168 @@
+//! 话筒电平表页面：一根均衡过的电平条带峰值保持标记，底下一条滚动的波形，削顶的时候右上角亮 CLIP
+//!
+//! 演示菜单里进来，Back 回去。数据是主循环喂进 `VuMeter` 的(见 mic.rs，feature `mic-vu`)，
+//! 这个页面只读，每个新窗口重画一次，最快 `FPS` 帧。没开 `mic-vu` 或者还没有数据的时候显示一行提示。
+//!
+//! 左上角是 RMS 电平(dBFS)。电平条的刻度不是固定的 dB，是按最近的电平分布均衡过的(见 vu_meter.rs)，
+//! 竖着的小标记是峰值保持。波形条每列是一小段样本的最大幅度，按 dB 画成上下对称的竖线，最新的在最右边。
+
+use core::cell::RefCell;
+use core::convert::Infallible;
+use core::fmt::Write;
+
+use embedded_graphics::geometry::{OriginDimensions, Point, Size};
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::primitives::{Line, Primitive, PrimitiveStyle, Rectangle};
+use embedded_graphics::text::{Baseline, Text};
+use embedded_graphics::Drawable;
+use heapless::String;
+
+use crate::app::{Canvas, Event, Page, Transition};
+use crate::input::{Button, ButtonEvent};
+use crate::text::draw_right_aligned;
+use crate::vu_meter::{dbfs10, VuMeter, MIN_DB};
+
+/// 最快每秒几帧，窗口比这个快的时候中间的窗口不单独画
+const FPS: u16 = 25;
+
+/// 电平条的上边和高度
+const BAR_TOP: i32 = 12;
+const BAR_HEIGHT: u32 = 10;
+
+/// 波形条从哪一行开始，一直到屏幕底边
+const WAVE_TOP: i32 = 26;
+
+/// 话筒电平表页面
+pub struct VuPage<'a> {
+    meter: &'a RefCell<VuMeter>,
+    /// 上次画的时候喂到第几个窗口了
+    seen: u32,
+    /// 上次画的时候 CLIP 亮没亮
+    clip_shown: bool,
+}
+
+impl<'a> VuPage<'a> {
+    pub const fn new(meter: &'a RefCell<VuMeter>) -> Self {
+        Self {
+            meter,
+            seen: 0,
+            clip_shown: false,
+        }
+    }
+}
+
+fn draw_bar(canvas: &mut Canvas, meter: &VuMeter, width: u32) -> Result<(), Infallible> {
+    let outline = PrimitiveStyle::with_stroke(BinaryColor::On, 1);
+    let fill = PrimitiveStyle::with_fill(BinaryColor::On);
+    Rectangle::new(Point::new(0, BAR_TOP), Size::new(width, BAR_HEIGHT))
+        .into_styled(outline)
+        .draw(canvas)?;
+    let inner = width - 4;
+    let level = inner * meter.level() / 256;
+    Rectangle::new(Point::new(2, BAR_TOP + 2), Size::new(level, BAR_HEIGHT - 4))
+        .into_styled(fill)
+        .draw(canvas)?;
+    // 峰值保持的标记比条高出上下各一像素
+    let hold = (2 + inner * meter.hold() / 256).min(width - 2) as i32;
+    Rectangle::new(
+        Point::new(hold - 1, BAR_TOP - 1),
+        Size::new(2, BAR_HEIGHT + 2),
+    )
+    .into_styled(fill)
+    .draw(canvas)
+}
+
+fn draw_wave(canvas: &mut Canvas, meter: &VuMeter, size: Size) -> Result<(), Infallible> {
+    let half = (size.height as i32 - WAVE_TOP) / 2 - 1;
+    if half <= 0 {
+        return Ok(());
+    }
+    let center = WAVE_TOP + half + 1;
+    let range = -(MIN_DB as i32) * 10;
+    let style = PrimitiveStyle::with_stroke(BinaryColor::On, 1);
+    // 屏幕比波形条窄(竖着放)的时候只画最新的那几列
+    let columns = meter.wave().count();
+    let skip = columns.saturating_sub(size.width as usize);
+    let left = size.width as i32 - (columns - skip) as i32;
+    for (i, amplitude) in meter.wave().skip(skip).enumerate() {
+        let height = (dbfs10(amplitude) as i32 + range) * half / range;
+        let x = left + i as i32;
+        Line::new(
+            Point::new(x, center - height),
+            Point::new(x, center + height),
+        )
+        .into_styled(style)
+        .draw(canvas)?;
+    }
+    Ok(())
+}
+
+impl Page for VuPage<'_> {
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        match event {
+            Event::Button(ButtonEvent::Pressed(Button::Back)) => Transition::Pop,
+            _ => Transition::None,
+        }
+    }
+
+    fn tick(&mut self, now_ms: u64) -> bool {
+        let meter = self.meter.borrow();
+        meter.windows() != self.seen || meter.clipping(now_ms) != self.clip_shown
+    }
+
+    fn desired_fps(&self) -> u16 {
+        FPS
+    }
+
+    fn has_data(&self) -> bool {
+        self.meter.borrow().windows() > 0
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, now_ms: u64) -> Result<(), Infallible> {
+        let meter = self.meter.borrow();
+        let size = canvas.size();
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        self.seen = meter.windows();
+        self.clip_shown = meter.clipping(now_ms);
+
+        if meter.windows() == 0 {
+            let hint = if cfg!(feature = "mic-vu") {
+                "waiting for mic"
+            } else {
+                "mic-vu disabled"
+            };
+            Text::with_baseline("VU", Point::zero(), style, Baseline::Top).draw(canvas)?;
+            Text::with_baseline(hint, Point::new(0, 24), style, Baseline::Top).draw(canvas)?;
+            return Ok(());
+        }
+
+        let mut label: String<16> = String::new();
+        let db10 = meter.level_db10();
+        let sign = if db10 < 0 { "-" } else { "" };
+        let _ = write!(
+            label,
+            "{}{}.{}dB",
+            sign,
+            db10.unsigned_abs() / 10,
+            db10.unsigned_abs() % 10
+        );
+        Text::with_baseline(&label, Point::zero(), style, Baseline::Top).draw(canvas)?;
+        if self.clip_shown {
+            // 反色的 CLIP，离远了也看得见
+            let width = 4 * 6 + 2;
+            Rectangle::new(
+                Point::new(size.width as i32 - width, 0),
+                Size::new(width as u32, 10),
+            )
+            .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+            .draw(canvas)?;
+            let inverted = MonoTextStyle::new(&FONT_6X10, BinaryColor::Off);
+            draw_right_aligned(canvas, "CLIP", size.width as i32 - 2, 8, inverted)?;
+        }
+
+        draw_bar(canvas, &meter, size.width)?;
+        draw_wave(canvas, &meter, size)
+    }
+}
@@ -0,0 +1,433 @@
+//! 闹钟：最多 `MAX_ALARMS` 个，每个是一天里的一个时间点，每天都响
+//!
+//! 时间来自 `sleep_clock::WallClock`，没对过时(`CLOCK SET`)就不知道现在几点，闹钟不响。
+//! 板子没有日历，所以没有"周几响"，打开的闹钟每天都响。
+//!
+//! `AlarmClock` 每秒被问一次(主循环的定时任务，床头钟模式下是它自己的睡眠循环)，
+//! 分钟变了才比较一次：正好走到闹钟的那一分钟就开始响。只在"走到"的那一刻比较，
+//! 所以关着机错过的闹钟开机以后不会补响(开机时间也是从 00:00 重新算的)。
+//!
+//! 响的时候：
+//!
+//! - 短按任意键贪睡 `snooze_min` 分钟(5 到 10)，一次响最多贪睡 `MAX_SNOOZES` 次，用完了短按不管用
+//! - 长按任意键关掉，明天这个时间照常响
+//! - 响了 `RING_TIMEOUT_MS` 还没人管就自己停，和长按一样
+//!
+//! 一个闹钟响着(或者贪睡着)的时候别的闹钟到点了不响。设置存在设置里(见 `settings`)，在设置页面里编辑，见 `wake_alarm_page`。
+
+use crate::dimming::MINUTES_PER_DAY;
+
+/// 最多几个闹钟
+pub const MAX_ALARMS: usize = 3;
+
+/// 贪睡多久(分钟)的范围和默认值
+pub const MIN_SNOOZE_MIN: u8 = 5;
+pub const MAX_SNOOZE_MIN: u8 = 10;
+pub const DEFAULT_SNOOZE_MIN: u8 = 9;
+
+/// 一次响最多贪睡几次
+pub const MAX_SNOOZES: u8 = 3;
+
+/// 响多久没人管自己停(毫秒)
+pub const RING_TIMEOUT_MS: u64 = 5 * 60 * 1000;
+
+/// 编码之后多少字节：贪睡分钟 + 每个闹钟(分钟 2 字节 + 标志 1 字节)
+pub const ENCODED_LEN: usize = 1 + MAX_ALARMS * 3;
+
+/// 标志位：打开
+const FLAG_ENABLED: u8 = 1 << 0;
+
+/// 一个闹钟：一天里的第 `minute` 分钟响
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, defmt::Format)]
+pub struct WakeAlarm {
+    pub minute: u16,
+    pub enabled: bool,
+}
+
+impl WakeAlarm {
+    /// 关着的闹钟
+    pub const fn new(hour: u8, minute: u8) -> Self {
+        Self {
+            minute: hour as u16 * 60 + minute as u16,
+            enabled: false,
+        }
+    }
+}
+
+/// 闹钟设置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct WakeAlarmConfig {
+    pub alarms: [WakeAlarm; MAX_ALARMS],
+    /// 贪睡多久(分钟)，`MIN_SNOOZE_MIN..=MAX_SNOOZE_MIN`
+    pub snooze_min: u8,
+}
+
+impl Default for WakeAlarmConfig {
+    /// 三个都是 07:00，都关着
+    fn default() -> Self {
+        Self {
+            alarms: [WakeAlarm::new(7, 0); MAX_ALARMS],
+            snooze_min: DEFAULT_SNOOZE_MIN,
+        }
+    }
+}
+
+impl WakeAlarmConfig {
+    pub fn encode(&self) -> [u8; ENCODED_LEN] {
+        let mut out = [0u8; ENCODED_LEN];
+        out[0] = self.snooze_min;
+        for (i, alarm) in self.alarms.iter().enumerate() {
+            out[1 + i * 3..3 + i * 3].copy_from_slice(&alarm.minute.to_le_bytes());
+            out[3 + i * 3] = if alarm.enabled { FLAG_ENABLED } else { 0 };
+        }
+        out
+    }
+
+    /// 解码。贪睡时间、闹钟时间超出范围或者有不认识的标志位返回 None
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != ENCODED_LEN || !(MIN_SNOOZE_MIN..=MAX_SNOOZE_MIN).contains(&bytes[0]) {
+            return None;
+        }
+        let mut config = Self {
+            snooze_min: bytes[0],
+            ..Self::default()
+        };
+        for (i, alarm) in config.alarms.iter_mut().enumerate() {
+            let minute = u16::from_le_bytes([bytes[1 + i * 3], bytes[2 + i * 3]]);
+            let flags = bytes[3 + i * 3];
+            if minute >= MINUTES_PER_DAY || flags & !FLAG_ENABLED != 0 {
+                return None;
+            }
+            *alarm = WakeAlarm {
+                minute,
+                enabled: flags & FLAG_ENABLED != 0,
+            };
+        }
+        Some(config)
+    }
+}
+
+/// 正在响的闹钟
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Ringing {
+    /// 第几个闹钟(从 0 数)
+    pub index: u8,
+    /// 闹钟设的时间(一天里的第几分钟)，贪睡以后也不变
+    pub minute: u16,
+    /// 已经贪睡了几次
+    pub snoozes: u8,
+}
+
+impl Ringing {
+    /// 还能不能贪睡
+    pub fn can_snooze(&self) -> bool {
+        self.snoozes < MAX_SNOOZES
+    }
+}
+
+/// `AlarmClock::update` 报的变化
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum AlarmEvent {
+    /// 开始响了(到点了或者贪睡完了)
+    Ring(Ringing),
+    /// 响太久没人管，停了
+    TimedOut,
+}
+
+/// 闹钟的运行状态
+#[derive(Debug, Default)]
+pub struct AlarmClock {
+    /// 上一次比较的是第几分钟
+    checked_minute: Option<u16>,
+    /// 正在响的闹钟和开始响的时刻
+    ringing: Option<(Ringing, u64)>,
+    /// 贪睡中的闹钟和到第几分钟再响
+    snoozed: Option<(Ringing, u16)>,
+}
+
+impl AlarmClock {
+    pub const fn new() -> Self {
+        Self {
+            checked_minute: None,
+            ringing: None,
+            snoozed: None,
+        }
+    }
+
+    /// 每秒调一次就够了。`minute` 是一天里的第几分钟，不知道现在几点就传 None
+    pub fn update(
+        &mut self,
+        config: &WakeAlarmConfig,
+        minute: Option<u16>,
+        now_ms: u64,
+    ) -> Option<AlarmEvent> {
+        if let Some((_, started_ms)) = self.ringing {
+            if now_ms >= started_ms + RING_TIMEOUT_MS {
+                self.dismiss();
+                return Some(AlarmEvent::TimedOut);
+            }
+        }
+        let minute = minute?;
+        if self.checked_minute == Some(minute) || self.ringing.is_some() {
+            self.checked_minute = Some(minute);
+            return None;
+        }
+        self.checked_minute = Some(minute);
+        let ring = match self.snoozed {
+            Some((ring, until)) if until == minute => {
+                self.snoozed = None;
+                Some(ring)
+            }
+            Some(_) => None,
+            None => config
+                .alarms
+                .iter()
+                .position(|alarm| alarm.enabled && alarm.minute == minute)
+                .map(|index| Ringing {
+                    index: index as u8,
+                    minute,
+                    snoozes: 0,
+                }),
+        }?;
+        self.ringing = Some((ring, now_ms));
+        Some(AlarmEvent::Ring(ring))
+    }
+
+    /// 正在响的闹钟
+    pub fn ringing(&self) -> Option<Ringing> {
+        self.ringing.map(|(ring, _)| ring)
+    }
+
+    /// 贪睡：停下来，`config.snooze_min` 分钟以后再响。贪睡次数用完了不管用，返回 false
+    pub fn snooze(&mut self, config: &WakeAlarmConfig) -> bool {
+        let (Some((ring, _)), Some(minute)) = (self.ringing, self.checked_minute) else {
+            return false;
+        };
+        if !ring.can_snooze() {
+            return false;
+        }
+        let until = (minute + config.snooze_min as u16) % MINUTES_PER_DAY;
+        self.ringing = None;
+        self.snoozed = Some((
+            Ringing {
+                snoozes: ring.snoozes + 1,
+                ..ring
+            },
+            until,
+        ));
+        true
+    }
+
+    /// 关掉正在响的或者贪睡中的闹钟，到明天这个时间再响
+    pub fn dismiss(&mut self) {
+        self.ringing = None;
+        self.snoozed = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: u64 = 1000;
+    const MINUTE: u64 = 60 * SECOND;
+
+    fn config(alarms: &[(u8, u8)]) -> WakeAlarmConfig {
+        let mut config = WakeAlarmConfig::default();
+        for (slot, &(hour, minute)) in config.alarms.iter_mut().zip(alarms) {
+            *slot = WakeAlarm {
+                enabled: true,
+                ..WakeAlarm::new(hour, minute)
+            };
+        }
+        config
+    }
+
+    /// 像主循环那样每秒问一次，从 `from` 分钟走到 `to` 分钟(不含)，返回报出来的事件和对应的分钟。
+    /// 开机时间按 `from * MINUTE` 起算，过了午夜接着往上加
+    fn run(
+        clock: &mut AlarmClock,
+        config: &WakeAlarmConfig,
+        from: u16,
+        to: u16,
+    ) -> std::vec::Vec<(u16, AlarmEvent)> {
+        let mut events = std::vec::Vec::new();
+        let mut minute = from;
+        let mut start_ms = from as u64 * MINUTE;
+        while minute != to {
+            for second in 0..60 {
+                let now_ms = start_ms + second * SECOND;
+                if let Some(event) = clock.update(config, Some(minute), now_ms) {
+                    events.push((minute, event));
+                }
+            }
+            minute = (minute + 1) % MINUTES_PER_DAY;
+            start_ms += MINUTE;
+        }
+        events
+    }
+
+    fn ring(index: u8, minute: u16, snoozes: u8) -> AlarmEvent {
+        AlarmEvent::Ring(Ringing {
+            index,
+            minute,
+            snoozes,
+        })
+    }
+
+    #[test]
+    fn rings_once_at_the_minute() {
+        let config = config(&[(7, 0)]);
+        let mut clock = AlarmClock::new();
+        assert_eq!(
+            run(&mut clock, &config, 6 * 60 + 58, 7 * 60 + 1),
+            [(420, ring(0, 420, 0))]
+        );
+        assert_eq!(clock.ringing().map(|ring| ring.index), Some(0));
+    }
+
+    #[test]
+    fn disabled_and_unknown_time_do_not_ring() {
+        let mut config = config(&[(7, 0)]);
+        config.alarms[0].enabled = false;
+        let mut clock = AlarmClock::new();
+        assert!(run(&mut clock, &config, 419, 422).is_empty());
+        let config = self::config(&[(7, 0)]);
+        let mut clock = AlarmClock::new();
+        assert_eq!(clock.update(&config, None, 0), None);
+        assert_eq!(clock.ringing(), None);
+    }
+
+    #[test]
+    fn no_catch_up_after_start() {
+        // 开机的时候已经过了闹钟那一分钟，不补响
+        let config = config(&[(7, 0)]);
+        let mut clock = AlarmClock::new();
+        assert!(run(&mut clock, &config, 421, 425).is_empty());
+        // 但是第一次问正好就是那一分钟的话要响
+        let mut clock = AlarmClock::new();
+        assert_eq!(clock.update(&config, Some(420), 0), Some(ring(0, 420, 0)));
+    }
+
+    #[test]
+    fn snooze_wraps_past_midnight() {
+        let config = WakeAlarmConfig {
+            snooze_min: 5,
+            ..config(&[(23, 58)])
+        };
+        let mut clock = AlarmClock::new();
+        let alarm = 23 * 60 + 58;
+        assert_eq!(
+            clock.update(&config, Some(alarm), alarm as u64 * MINUTE),
+            Some(ring(0, alarm, 0))
+        );
+        assert!(clock.snooze(&config));
+        assert_eq!(clock.ringing(), None);
+        assert_eq!(run(&mut clock, &config, alarm, 5), [(3, ring(0, alarm, 1))]);
+    }
+
+    #[test]
+    fn snooze_limit() {
+        let config = WakeAlarmConfig {
+            snooze_min: MIN_SNOOZE_MIN,
+            ..config(&[(6, 0)])
+        };
+        let mut clock = AlarmClock::new();
+        let mut minute = 360;
+        assert_eq!(
+            clock.update(&config, Some(minute), 360 * MINUTE),
+            Some(ring(0, 360, 0))
+        );
+        for snoozes in 1..=MAX_SNOOZES {
+            assert!(clock.snooze(&config));
+            let events = run(
+                &mut clock,
+                &config,
+                minute,
+                minute + MIN_SNOOZE_MIN as u16 + 1,
+            );
+            minute += MIN_SNOOZE_MIN as u16;
+            assert_eq!(events, [(minute, ring(0, 360, snoozes))]);
+        }
+        // 次数用完了，短按不管用，一直响着
+        assert!(!clock.ringing().unwrap().can_snooze());
+        assert!(!clock.snooze(&config));
+        assert!(clock.ringing().is_some());
+    }
+
+    #[test]
+    fn rings_time_out() {
+        let config = config(&[(7, 0)]);
+        let mut clock = AlarmClock::new();
+        let start_ms = 420 * MINUTE;
+        clock.update(&config, Some(420), start_ms);
+        assert_eq!(
+            clock.update(&config, Some(424), start_ms + RING_TIMEOUT_MS - 1),
+            None
+        );
+        assert_eq!(
+            clock.update(&config, Some(425), start_ms + RING_TIMEOUT_MS),
+            Some(AlarmEvent::TimedOut)
+        );
+        assert_eq!(clock.ringing(), None);
+        // 和长按关掉一样：不会再贪睡，这一天不再响
+        assert!(!clock.snooze(&config));
+        assert!(run(&mut clock, &config, 425, 419).is_empty());
+        assert_eq!(run(&mut clock, &config, 419, 421), [(420, ring(0, 420, 0))]);
+    }
+
+    #[test]
+    fn dismiss_cancels_snooze() {
+        let config = config(&[(7, 0)]);
+        let mut clock = AlarmClock::new();
+        clock.update(&config, Some(420), 420 * MINUTE);
+        assert!(clock.snooze(&config));
+        clock.dismiss();
+        assert!(run(&mut clock, &config, 420, 440).is_empty());
+    }
+
+    #[test]
+    fn other_alarms_wait_while_ringing_or_snoozed() {
+        let config = WakeAlarmConfig {
+            snooze_min: 10,
+            ..config(&[(7, 0), (7, 2), (7, 12)])
+        };
+        let mut clock = AlarmClock::new();
+        clock.update(&config, Some(420), 420 * MINUTE);
+        // 7:02 的时候第一个还在响
+        assert!(run(&mut clock, &config, 421, 423).is_empty());
+        assert!(clock.snooze(&config));
+        // 贪睡到 7:12 再响的是第一个，第三个 7:12 的不响
+        assert_eq!(run(&mut clock, &config, 423, 433), [(432, ring(0, 420, 1))]);
+        assert_eq!(clock.ringing().map(|ring| ring.index), Some(0));
+    }
+
+    #[test]
+    fn config_round_trips() {
+        let mut config = config(&[(0, 0), (23, 59)]);
+        config.snooze_min = MAX_SNOOZE_MIN;
+        assert_eq!(WakeAlarmConfig::decode(&config.encode()), Some(config));
+        let default = WakeAlarmConfig::default();
+        assert_eq!(WakeAlarmConfig::decode(&default.encode()), Some(default));
+    }
+
+    #[test]
+    fn decode_rejects_out_of_range() {
+        let good = config(&[(7, 0)]).encode();
+        for (index, value) in [(0, MIN_SNOOZE_MIN - 1), (0, MAX_SNOOZE_MIN + 1), (3, 2)] {
+            let mut bad = good;
+            bad[index] = value;
+            assert_eq!(
+                WakeAlarmConfig::decode(&bad),
+                None,
+                "byte {} = {}",
+                index,
+                value
+            );
+        }
+        let mut bad = good;
+        bad[1..3].copy_from_slice(&MINUTES_PER_DAY.to_le_bytes());
+        assert_eq!(WakeAlarmConfig::decode(&bad), None);
+        assert_eq!(WakeAlarmConfig::decode(&good[..ENCODED_LEN - 1]), None);
+    }
+}
@@ -0,0 +1,339 @@
+//! 闹钟的两个页面：设置菜单里的编辑页面，和响的时候盖满整屏的页面(见 `wake_alarm`)
+//!
+//! 编辑页面前三行是三个闹钟，最后一行是贪睡时间：
+//!
+//! - Up/Down 选行，Select 开始编辑。闹钟依次改 时 -> 分 -> 开/关，贪睡时间只有一项，
+//!   Up/Down 调数值，Select 进下一项，改完最后一项就保存；编辑的时候按 Back 放弃这次修改
+//! - Back 回去
+//!
+//! 保存是交给主循环做的(`Action::SaveWakeAlarms`)。没对过时的话标题上会提示，闹钟照样可以改，只是不会响。
+//!
+//! 响的页面整屏每半秒反色一次，中间是闹钟的时间。短按(松开的时候还没到长按)贪睡，长按关掉，
+//! 都是交给主循环做(`Action::SnoozeWakeAlarm`、`Action::DismissWakeAlarm`)。主循环做完广播 `Event::WakeAlarm(None)`，
+//! 页面收到就关掉(动作只从最上面的页面取，所以不能自己先关)。
+
+use core::convert::Infallible;
+use core::fmt::Write;
+
+use embedded_graphics::geometry::{Dimensions, Point};
+use embedded_graphics::mono_font::ascii::{FONT_10X20, FONT_6X10};
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::text::Text;
+use embedded_graphics::Drawable;
+use heapless::{String, Vec};
+
+use crate::app::{Action, Canvas, Event, Page, Transition};
+use crate::input::{Button, ButtonEvent};
+use crate::large_text::draw_large_readings;
+use crate::text::draw_centered;
+use crate::wake_alarm::{
+    Ringing, WakeAlarm, WakeAlarmConfig, MAX_ALARMS, MAX_SNOOZE_MIN, MIN_SNOOZE_MIN,
+};
+use crate::widgets::draw_menu;
+
+/// 标题行基线
+const TITLE_Y: i32 = 8;
+
+/// 菜单从哪一行开始
+const MENU_TOP: i32 = 12;
+
+/// 分钟每次调多少
+const MINUTE_STEP: u16 = 5;
+
+/// 响的时候多久反色一次(毫秒)
+const FLASH_MS: u64 = 500;
+
+/// 正在改哪一项
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Hour,
+    Minute,
+    Enabled,
+    Snooze,
+}
+
+/// 闹钟的编辑页面
+#[derive(Debug)]
+pub struct WakeAlarmPage {
+    config: WakeAlarmConfig,
+    selected: usize,
+    /// 正在编辑的设置(改完才放回 `config`)和正在改的字段
+    editing: Option<(WakeAlarmConfig, Field)>,
+    clock_known: bool,
+    pending: Option<Action>,
+}
+
+impl WakeAlarmPage {
+    /// `config` 是设置里存的
+    pub const fn new(config: WakeAlarmConfig) -> Self {
+        Self {
+            config,
+            selected: 0,
+            editing: None,
+            clock_known: false,
+            pending: None,
+        }
+    }
+
+    /// 三个闹钟加一行贪睡时间
+    const ROWS: usize = MAX_ALARMS + 1;
+
+    fn on_edit(&mut self, button: Button, mut config: WakeAlarmConfig, field: Field) {
+        let up = match button {
+            Button::Up => true,
+            Button::Down => false,
+            Button::Back => {
+                self.editing = None;
+                return;
+            }
+            Button::Select => {
+                self.editing = match field {
+                    Field::Hour => Some((config, Field::Minute)),
+                    Field::Minute => Some((config, Field::Enabled)),
+                    Field::Enabled | Field::Snooze => {
+                        self.config = config;
+                        self.pending = Some(Action::SaveWakeAlarms(config));
+                        None
+                    }
+                };
+                return;
+            }
+        };
+        if field == Field::Snooze {
+            config.snooze_min = if up {
+                (config.snooze_min + 1).min(MAX_SNOOZE_MIN)
+            } else {
+                (config.snooze_min - 1).max(MIN_SNOOZE_MIN)
+            };
+            self.editing = Some((config, field));
+            return;
+        }
+        let alarm = &mut config.alarms[self.selected];
+        let (hour, minute) = (alarm.minute / 60, alarm.minute % 60);
+        match field {
+            Field::Hour => {
+                let hour = if up {
+                    (hour + 1) % 24
+                } else {
+                    (hour + 23) % 24
+                };
+                alarm.minute = hour * 60 + minute;
+            }
+            Field::Minute => {
+                let step = if up { MINUTE_STEP } else { 60 - MINUTE_STEP };
+                // 只改分钟，不进位到小时
+                alarm.minute = hour * 60 + (minute + step) % 60;
+            }
+            Field::Enabled => alarm.enabled = !alarm.enabled,
+            Field::Snooze => {}
+        }
+        self.editing = Some((config, field));
+    }
+}
+
+impl Page for WakeAlarmPage {
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        let button = match event {
+            Event::ClockSet(_) => {
+                self.clock_known = true;
+                return Transition::None;
+            }
+            Event::Button(ButtonEvent::Pressed(button)) => *button,
+            _ => return Transition::None,
+        };
+        if let Some((config, field)) = self.editing {
+            self.on_edit(button, config, field);
+            return Transition::None;
+        }
+        match button {
+            Button::Back => return Transition::Pop,
+            Button::Up => self.selected = (self.selected + Self::ROWS - 1) % Self::ROWS,
+            Button::Down => self.selected = (self.selected + 1) % Self::ROWS,
+            Button::Select => {
+                let field = if self.selected < MAX_ALARMS {
+                    Field::Hour
+                } else {
+                    Field::Snooze
+                };
+                self.editing = Some((self.config, field));
+            }
+        }
+        Transition::None
+    }
+
+    fn desired_fps(&self) -> u16 {
+        1
+    }
+
+    fn take_action(&mut self) -> Option<Action> {
+        self.pending.take()
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<(), Infallible> {
+        let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+        let title = if self.clock_known {
+            "wake alarms"
+        } else {
+            "wake: no clock"
+        };
+        Text::new(title, Point::new(0, TITLE_Y), style).draw(canvas)?;
+
+        // 先把每一行的文字拼好，菜单只借用字符串
+        let mut rows: Vec<(String<12>, String<8>), { WakeAlarmPage::ROWS }> = Vec::new();
+        for row in 0..Self::ROWS {
+            let (mut label, mut value) = (String::new(), String::new());
+            let (config, field) = match self.editing {
+                Some((config, field)) if row == self.selected => (config, Some(field)),
+                _ => (self.config, None),
+            };
+            if let Some(&WakeAlarm { minute, enabled }) = config.alarms.get(row) {
+                let (hour, minute) = (minute / 60, minute % 60);
+                let _ = match field {
+                    Some(Field::Hour) => write!(label, "{} [{:02}]:{:02}", row + 1, hour, minute),
+                    Some(Field::Minute) => write!(label, "{} {:02}:[{:02}]", row + 1, hour, minute),
+                    _ => write!(label, "{} {:02}:{:02}", row + 1, hour, minute),
+                };
+                let state = if enabled { "on" } else { "off" };
+                let _ = if field == Some(Field::Enabled) {
+                    write!(value, "[{}]", state)
+                } else {
+                    write!(value, "{}", state)
+                };
+            } else {
+                let _ = label.push_str("snooze");
+                let _ = if field == Some(Field::Snooze) {
+                    write!(value, "[{}]m", config.snooze_min)
+                } else {
+                    write!(value, "{}m", config.snooze_min)
+                };
+            }
+            let _ = rows.push((label, value));
+        }
+        draw_menu(
+            canvas,
+            MENU_TOP,
+            rows.iter()
+                .map(|(label, value)| (label.as_str(), value.as_str())),
+            self.selected,
+        )
+    }
+}
+
+/// 闹钟响的时候的页面，主循环广播 `Event::WakeAlarm(Some(..))` 然后把它推到最上面
+#[derive(Debug, Default)]
+pub struct RingingPage {
+    ringing: Option<Ringing>,
+    /// 在这一页按下了、还没到长按，松开就是短按。响之前就按着的键松开不算
+    pressed: bool,
+    shown_phase: Option<bool>,
+    pending: Option<Action>,
+}
+
+impl RingingPage {
+    pub const fn new() -> Self {
+        Self {
+            ringing: None,
+            pressed: false,
+            shown_phase: None,
+            pending: None,
+        }
+    }
+
+    fn time_text(ring: &Ringing) -> String<8> {
+        let mut text = String::new();
+        let _ = write!(text, "{:02}:{:02}", ring.minute / 60, ring.minute % 60);
+        text
+    }
+}
+
+/// 这一刻是不是反色的那半秒
+fn flash_phase(now_ms: u64) -> bool {
+    now_ms / FLASH_MS % 2 == 1
+}
+
+impl Page for RingingPage {
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        match *event {
+            Event::WakeAlarm(ringing) => {
+                self.ringing = ringing;
+                self.pressed = false;
+                // 不响了(贪睡、关掉、响太久自己停了)，在最上面的话就关掉
+                if ringing.is_none() {
+                    return Transition::Pop;
+                }
+            }
+            Event::Button(ButtonEvent::Pressed(_)) => self.pressed = true,
+            Event::Button(ButtonEvent::LongPress(_)) if self.ringing.is_some() => {
+                self.pressed = false;
+                self.ringing = None;
+                self.pending = Some(Action::DismissWakeAlarm);
+            }
+            // 贪睡次数用完了短按不管用，只能长按关掉
+            Event::Button(ButtonEvent::Released(_))
+                if self.pressed && self.ringing.is_some_and(|ring| ring.can_snooze()) =>
+            {
+                self.ringing = None;
+                self.pending = Some(Action::SnoozeWakeAlarm);
+            }
+            _ => {}
+        }
+        Transition::None
+    }
+
+    fn tick(&mut self, now_ms: u64) -> bool {
+        let phase = flash_phase(now_ms);
+        let changed = self.shown_phase != Some(phase);
+        self.shown_phase = Some(phase);
+        changed
+    }
+
+    fn desired_fps(&self) -> u16 {
+        4
+    }
+
+    fn take_action(&mut self) -> Option<Action> {
+        self.pending.take()
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, now_ms: u64) -> Result<(), Infallible> {
+        let Some(ring) = self.ringing else {
+            return Ok(());
+        };
+        let inverted = flash_phase(now_ms);
+        if inverted {
+            let area = canvas.bounding_box();
+            canvas.fill_rect(area, true);
+        }
+        let color = if inverted {
+            BinaryColor::Off
+        } else {
+            BinaryColor::On
+        };
+        let small = MonoTextStyle::new(&FONT_6X10, color);
+        let mut title: String<12> = String::new();
+        let _ = write!(title, "ALARM {}", ring.index + 1);
+        draw_centered(canvas, &title, 10, small)?;
+        draw_centered(
+            canvas,
+            &Self::time_text(&ring),
+            36,
+            MonoTextStyle::new(&FONT_10X20, color),
+        )?;
+        let hint = if ring.can_snooze() {
+            "press: snooze"
+        } else {
+            "no more snooze"
+        };
+        draw_centered(canvas, hint, 50, small)?;
+        draw_centered(canvas, "hold: stop", 61, small)
+    }
+
+    fn render_large(&mut self, canvas: &mut Canvas, _now_ms: u64) -> Result<bool, Infallible> {
+        let Some(ring) = self.ringing else {
+            return Ok(false);
+        };
+        draw_large_readings(canvas, &[("ALARM", &Self::time_text(&ring))])?;
+        Ok(true)
+    }
+}
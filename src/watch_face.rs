@@ -0,0 +1,322 @@
+//! 表盘：自己拼的一屏，最多 `SLOTS` 个格子，每个格子放一个小部件
+//!
+//! 格子的位置和大小不能随便摆，只能从几种布局里选(`Layout`)，布局决定用前几个格子，后面的格子留着不显示，
+//! 换回格子多的布局的时候还在。小部件有这几种(`Widget`)：
+//!
+//! - 时钟：`HH:MM`，格子够宽带秒，没对过时显示 `--:--`
+//! - 读数：一个传感器通道的名字和读数
+//! - 进度条：一个传感器通道的读数画成条，单位是 % 的直接按百分比，别的按历史里的最小到最大
+//! - 电池、告警：图标加一段文字
+//!
+//! 通道是按注册表里的编号存的，和告警规则一样，所以换了传感器接法以后编号可能对不上，对不上的显示 "?"。
+//! 表盘在 watch_face_page.rs 里画和编辑，设置存在 flash 里(见 `settings`)，串口命令 `FACE` 也能改。
+
+use core::fmt::{self, Write};
+
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::primitives::Rectangle;
+use heapless::Vec;
+
+use crate::sensors::{ChannelId, MAX_CHANNELS};
+
+/// 最多几个格子
+pub const SLOTS: usize = 6;
+
+/// 编码之后多少字节：布局 1 字节 + 每个格子 1 字节(高 4 位是种类，低 4 位是通道编号)
+pub const ENCODED_LEN: usize = 1 + SLOTS;
+
+// 通道编号要放得进半个字节
+const _: () = assert!(MAX_CHANNELS <= 16);
+
+/// 布局
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Layout {
+    /// 2x2 四格
+    Grid,
+    /// 上面一个大格，下面三个小格
+    BigThree,
+    /// 一个格子占满整屏
+    Full,
+    /// 3x2 六格
+    Six,
+}
+
+impl Layout {
+    /// 按这个顺序轮换
+    pub const ALL: [Layout; 4] = [Layout::Grid, Layout::BigThree, Layout::Full, Layout::Six];
+
+    /// 串口命令和屏幕上用的名字
+    pub fn name(self) -> &'static str {
+        match self {
+            Layout::Grid => "grid",
+            Layout::BigThree => "big",
+            Layout::Full => "full",
+            Layout::Six => "six",
+        }
+    }
+
+    /// 按名字找，不区分大小写
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|layout| layout.name().eq_ignore_ascii_case(name))
+    }
+
+    /// 用前几个格子
+    pub fn slot_count(self) -> usize {
+        match self {
+            Layout::Grid => 4,
+            Layout::BigThree => 4,
+            Layout::Full => 1,
+            Layout::Six => 6,
+        }
+    }
+
+    /// 下一种布局(转一圈)
+    pub fn next(self) -> Self {
+        let index = Self::ALL
+            .iter()
+            .position(|&layout| layout == self)
+            .unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// 在 `area` 里每个格子的位置，按格子的顺序。除不尽的像素给最后一列
+    pub fn cells(self, area: Rectangle) -> Vec<Rectangle, SLOTS> {
+        let (width, height) = (area.size.width, area.size.height);
+        let half = height / 2;
+        let mut cells = Vec::new();
+        let mut row = |top: u32, rows: u32, columns: u32| {
+            let column_width = width / columns;
+            for column in 0..columns {
+                let left = column * column_width;
+                let cell_width = if column == columns - 1 {
+                    width - left
+                } else {
+                    column_width
+                };
+                let _ = cells.push(Rectangle::new(
+                    area.top_left + Point::new(left as i32, top as i32),
+                    Size::new(cell_width, rows),
+                ));
+            }
+        };
+        match self {
+            Layout::Grid => {
+                row(0, half, 2);
+                row(half, height - half, 2);
+            }
+            Layout::BigThree => {
+                row(0, half, 1);
+                row(half, height - half, 3);
+            }
+            Layout::Full => row(0, height, 1),
+            Layout::Six => {
+                row(0, half, 3);
+                row(half, height - half, 3);
+            }
+        }
+        cells
+    }
+}
+
+/// 小部件的种类，串口命令用
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum WidgetKind {
+    Empty,
+    Clock,
+    Value,
+    Bar,
+    Battery,
+    Alarms,
+}
+
+impl WidgetKind {
+    /// 按这个顺序轮换，下标就是编码里的种类
+    pub const ALL: [WidgetKind; 6] = [
+        WidgetKind::Empty,
+        WidgetKind::Clock,
+        WidgetKind::Value,
+        WidgetKind::Bar,
+        WidgetKind::Battery,
+        WidgetKind::Alarms,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            WidgetKind::Empty => "empty",
+            WidgetKind::Clock => "clock",
+            WidgetKind::Value => "value",
+            WidgetKind::Bar => "bar",
+            WidgetKind::Battery => "batt",
+            WidgetKind::Alarms => "alarms",
+        }
+    }
+
+    /// 按名字找，不区分大小写
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.name().eq_ignore_ascii_case(name))
+    }
+
+    /// 要不要绑一个传感器通道
+    pub fn needs_channel(self) -> bool {
+        matches!(self, WidgetKind::Value | WidgetKind::Bar)
+    }
+
+    fn index(self) -> u8 {
+        Self::ALL.iter().position(|&kind| kind == self).unwrap_or(0) as u8
+    }
+}
+
+/// 格子里放的东西
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub enum Widget {
+    /// 空着：平时什么都不画，编辑的时候画一个虚线框
+    Empty,
+    Clock,
+    /// 通道的名字和读数
+    Value(ChannelId),
+    /// 通道的读数画成进度条
+    Bar(ChannelId),
+    /// 电池图标和电量
+    Battery,
+    /// 告警图标和正在触发几条
+    Alarms,
+}
+
+impl Widget {
+    /// `kind` 种类的小部件，要绑通道的没给通道就绑第一个
+    pub fn new(kind: WidgetKind, channel: Option<ChannelId>) -> Self {
+        let channel = channel.unwrap_or(ChannelId(0));
+        match kind {
+            WidgetKind::Empty => Widget::Empty,
+            WidgetKind::Clock => Widget::Clock,
+            WidgetKind::Value => Widget::Value(channel),
+            WidgetKind::Bar => Widget::Bar(channel),
+            WidgetKind::Battery => Widget::Battery,
+            WidgetKind::Alarms => Widget::Alarms,
+        }
+    }
+
+    pub fn kind(self) -> WidgetKind {
+        match self {
+            Widget::Empty => WidgetKind::Empty,
+            Widget::Clock => WidgetKind::Clock,
+            Widget::Value(_) => WidgetKind::Value,
+            Widget::Bar(_) => WidgetKind::Bar,
+            Widget::Battery => WidgetKind::Battery,
+            Widget::Alarms => WidgetKind::Alarms,
+        }
+    }
+
+    /// 绑的通道，不用通道的是 None
+    pub fn channel(self) -> Option<ChannelId> {
+        match self {
+            Widget::Value(channel) | Widget::Bar(channel) => Some(channel),
+            _ => None,
+        }
+    }
+
+    /// 换成下一种，绑的通道留着
+    pub fn next_kind(self) -> Self {
+        let next = WidgetKind::ALL[(self.kind().index() as usize + 1) % WidgetKind::ALL.len()];
+        Self::new(next, self.channel())
+    }
+
+    /// 绑下一个通道(一共 `channels` 个，转一圈)，不用通道的不变
+    pub fn next_channel(self, channels: usize) -> Self {
+        let next = |ChannelId(id)| ChannelId(((id as usize + 1) % channels.max(1)) as u8);
+        match self {
+            Widget::Value(channel) => Widget::Value(next(channel)),
+            Widget::Bar(channel) => Widget::Bar(next(channel)),
+            other => other,
+        }
+    }
+
+    fn encode(self) -> u8 {
+        self.kind().index() << 4 | self.channel().map_or(0, |ChannelId(id)| id)
+    }
+
+    fn decode(byte: u8) -> Option<Self> {
+        let kind = *WidgetKind::ALL.get((byte >> 4) as usize)?;
+        let channel = byte & 0x0F;
+        if !kind.needs_channel() && channel != 0 {
+            return None;
+        }
+        Some(Self::new(kind, Some(ChannelId(channel))))
+    }
+}
+
+/// 表盘的设置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct WatchFaceConfig {
+    pub layout: Layout,
+    pub slots: [Widget; SLOTS],
+}
+
+impl Default for WatchFaceConfig {
+    /// 时钟、片内温度(第一个注册的通道)、电池、告警
+    fn default() -> Self {
+        let mut slots = [Widget::Empty; SLOTS];
+        slots[..4].copy_from_slice(&[
+            Widget::Clock,
+            Widget::Value(ChannelId(0)),
+            Widget::Battery,
+            Widget::Alarms,
+        ]);
+        Self {
+            layout: Layout::Grid,
+            slots,
+        }
+    }
+}
+
+impl WatchFaceConfig {
+    /// 现在的布局用到的格子
+    pub fn visible(&self) -> &[Widget] {
+        &self.slots[..self.layout.slot_count()]
+    }
+
+    /// 一行的摘要，比如 `grid 1=clock 2=value:temp 3=batt 4=alarms 5=empty 6=empty`，`name` 查通道的名字
+    pub fn write_summary<W: Write>(
+        &self,
+        out: &mut W,
+        name: impl Fn(ChannelId) -> &'static str,
+    ) -> fmt::Result {
+        out.write_str(self.layout.name())?;
+        for (slot, widget) in self.slots.iter().enumerate() {
+            write!(out, " {}={}", slot + 1, widget.kind().name())?;
+            if let Some(channel) = widget.channel() {
+                write!(out, ":{}", name(channel))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn encode(&self) -> [u8; ENCODED_LEN] {
+        let mut out = [0u8; ENCODED_LEN];
+        out[0] = Layout::ALL
+            .iter()
+            .position(|&layout| layout == self.layout)
+            .unwrap_or(0) as u8;
+        for (byte, widget) in out[1..].iter_mut().zip(self.slots) {
+            *byte = widget.encode();
+        }
+        out
+    }
+
+    /// 解码。布局或者种类不认识、不用通道的种类带了通道编号返回 None
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != ENCODED_LEN {
+            return None;
+        }
+        let layout = *Layout::ALL.get(bytes[0] as usize)?;
+        let mut slots = [Widget::Empty; SLOTS];
+        for (slot, &byte) in slots.iter_mut().zip(&bytes[1..]) {
+            *slot = Widget::decode(byte)?;
+        }
+        Some(Self { layout, slots })
+    }
+}
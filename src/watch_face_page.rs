@@ -0,0 +1,356 @@
+//! 表盘页面：按表盘设置(见 `watch_face`)把几个小部件拼成一屏，也在这里编辑
+//!
+//! 平时只显示，Back 回去。长按 Select 进编辑，选中的格子画实线框，空格子画虚线框：
+//!
+//! - Up/Down 选格子
+//! - 短按 Select 换小部件的种类，长按换绑的通道(读数、进度条才有)
+//! - 双击 Select 换布局
+//! - Back 保存(`Action::SaveWatchFace`)，回到平时的显示
+//!
+//! 串口命令 `FACE` 改了设置会广播 `Event::WatchFace` 过来，时钟要等 `Event::ClockSet` 带着对好的时间过来才走。
+
+use core::cell::RefCell;
+use core::convert::Infallible;
+use core::fmt::Write;
+
+use embedded_graphics::draw_target::{DrawTarget, DrawTargetExt};
+use embedded_graphics::geometry::{Dimensions, Point, Size};
+use embedded_graphics::mono_font::ascii::{FONT_10X20, FONT_6X10};
+use embedded_graphics::mono_font::{MonoFont, MonoTextStyle};
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::primitives::{Primitive, PrimitiveStyle, Rectangle};
+use embedded_graphics::text::{Baseline, Text};
+use embedded_graphics::{Drawable, Pixel};
+use heapless::String;
+
+use crate::app::{Action, Canvas, Event, Page, Transition};
+use crate::gesture::Gesture;
+use crate::input::{Button, ButtonEvent};
+use crate::sensors::{ChannelId, SensorRegistry};
+use crate::sleep_clock::WallClock;
+use crate::text::{centered_x, fit_prefix, text_pixel_width};
+use crate::theme::{self, PatternTarget};
+use crate::watch_face::{WatchFaceConfig, Widget};
+use crate::widgets::{
+    draw_alarm_icon_at, draw_battery_icon, draw_progress_bar_styled, ALARM_ICON_SIZE,
+    BATTERY_ICON_SIZE,
+};
+
+/// 格子里名字那一行的高度，读数、进度条画在下面
+const LABEL_HEIGHT: i32 = 10;
+
+/// 格子至少这么宽才显示秒
+const SECONDS_MIN_WIDTH: u32 = 90;
+
+/// 进度条最高多少像素
+const BAR_MAX_HEIGHT: u32 = 10;
+
+/// 图标右边的文字从哪开始
+const ICON_TEXT_X: i32 = BATTERY_ICON_SIZE.width as i32 + 5;
+
+/// 放得下就用大字：`room` 是能用的高度，`width` 是能用的宽度
+fn font_for(text: &str, width: u32, room: i32) -> &'static MonoFont<'static> {
+    let fits = |font: &MonoFont| {
+        room >= font.character_size.height as i32 + 2 && text_pixel_width(text, font) <= width
+    };
+    if fits(&FONT_10X20) {
+        &FONT_10X20
+    } else {
+        &FONT_6X10
+    }
+}
+
+/// 竖直中线在 `y`、从 `x` 开始画一段文字，右边放不下的截断
+fn draw_text_at<D>(target: &mut D, text: &str, x: i32, y: i32, room: i32) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let width = (target.bounding_box().size.width as i32 - x).max(0) as u32;
+    let font = font_for(text, width, room);
+    let style = MonoTextStyle::new(font, BinaryColor::On);
+    let text = fit_prefix(text, font, width);
+    Text::with_baseline(text, Point::new(x, y), style, Baseline::Middle).draw(target)?;
+    Ok(())
+}
+
+/// 水平居中、竖直中线在 `y` 画一段文字
+fn draw_text_centered<D>(target: &mut D, text: &str, y: i32, room: i32) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let width = target.bounding_box().size.width;
+    let font = font_for(text, width, room);
+    draw_text_at(target, text, centered_x(text, font, width), y, room)
+}
+
+/// 左上角的名字
+fn draw_label<D>(target: &mut D, label: &str) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+    Text::with_baseline(label, Point::new(2, 1), style, Baseline::Top).draw(target)?;
+    Ok(())
+}
+
+/// 空格子的虚线框，隔一个点画一个
+fn draw_dotted_outline<D>(target: &mut D, cell: Rectangle) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let Some(bottom_right) = cell.bottom_right() else {
+        return Ok(());
+    };
+    let top_left = cell.top_left;
+    let horizontal = (top_left.x..=bottom_right.x)
+        .step_by(2)
+        .flat_map(|x| [Point::new(x, top_left.y), Point::new(x, bottom_right.y)]);
+    let vertical = (top_left.y..=bottom_right.y)
+        .step_by(2)
+        .flat_map(|y| [Point::new(top_left.x, y), Point::new(bottom_right.x, y)]);
+    target.draw_iter(
+        horizontal
+            .chain(vertical)
+            .map(|point| Pixel(point, BinaryColor::On)),
+    )
+}
+
+/// 表盘页面
+pub struct WatchFacePage<'a, S> {
+    sensors: &'a RefCell<SensorRegistry<S>>,
+    battery: Option<ChannelId>,
+    config: WatchFaceConfig,
+    clock: WallClock,
+    /// 正在触发的告警，第 i 位是第 i 条
+    alarms: u8,
+    /// 编辑的时候选中的格子，不在编辑是 None
+    editing: Option<usize>,
+    pending: Option<Action>,
+    shown_generation: Option<u32>,
+    shown_second: Option<u64>,
+}
+
+impl<'a, S> WatchFacePage<'a, S> {
+    /// `battery` 是电池电量的通道，电池格子用
+    pub const fn new(
+        sensors: &'a RefCell<SensorRegistry<S>>,
+        battery: Option<ChannelId>,
+        config: WatchFaceConfig,
+    ) -> Self {
+        Self {
+            sensors,
+            battery,
+            config,
+            clock: WallClock::new(),
+            alarms: 0,
+            editing: None,
+            pending: None,
+            shown_generation: None,
+            shown_second: None,
+        }
+    }
+
+    /// 编辑的时候改选中的格子
+    fn edit(&mut self, change: impl FnOnce(Widget, usize) -> Widget) {
+        let Some(slot) = self.editing else {
+            return;
+        };
+        let channels = self.sensors.borrow().channels().len();
+        self.config.slots[slot] = change(self.config.slots[slot], channels);
+    }
+
+    /// 换了布局以后选中的格子可能不在了
+    fn clamp_selection(&mut self) {
+        let count = self.config.layout.slot_count();
+        if let Some(slot) = self.editing.as_mut() {
+            *slot = (*slot).min(count - 1);
+        }
+    }
+
+    fn shows_clock(&self) -> bool {
+        self.config.visible().contains(&Widget::Clock)
+    }
+
+    fn draw_widget<D>(&self, target: &mut D, widget: Widget, now_ms: u64) -> Result<(), D::Error>
+    where
+        D: PatternTarget,
+    {
+        let size = target.bounding_box().size;
+        let (width, height) = (size.width, size.height as i32);
+        let middle = height / 2;
+        let sensors = self.sensors.borrow();
+        let mut text: String<16> = String::new();
+        match widget {
+            Widget::Empty => Ok(()),
+            Widget::Clock => {
+                if self.clock.is_set() {
+                    let (hour, minute, second) = self.clock.time(now_ms);
+                    let _ = write!(text, "{:02}:{:02}", hour, minute);
+                    if width >= SECONDS_MIN_WIDTH {
+                        let _ = write!(text, ":{:02}", second);
+                    }
+                } else {
+                    let _ = text.push_str("--:--");
+                }
+                draw_text_centered(target, &text, middle, height)
+            }
+            Widget::Value(channel) => {
+                let info = sensors.get(channel).map(|channel| *channel.info());
+                draw_label(target, info.map_or("?", |info| info.name))?;
+                if let Some(info) = info {
+                    let _ = info.write_value(sensors.value(channel), &mut text);
+                } else {
+                    let _ = text.push_str("--");
+                }
+                let room = height - LABEL_HEIGHT;
+                draw_text_centered(target, &text, LABEL_HEIGHT + room / 2, room)
+            }
+            Widget::Bar(channel) => {
+                let Some(sensor) = sensors.get(channel) else {
+                    return draw_label(target, "?");
+                };
+                let info = sensor.info();
+                draw_label(target, info.name)?;
+                let value = sensor.value();
+                let _ = info.write_value(value, &mut text);
+                let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+                let x = width as i32 - 2 - text_pixel_width(&text, &FONT_6X10) as i32;
+                // 名字和读数挤不下就不写读数
+                if x > 2 + text_pixel_width(info.name, &FONT_6X10) as i32 {
+                    Text::with_baseline(&text, Point::new(x, 1), style, Baseline::Top)
+                        .draw(target)?;
+                }
+                // 单位是 % 的直接用，别的按历史里的最小到最大算
+                let percent = value.map_or(0, |value| {
+                    if info.unit == "%" {
+                        return (value / info.scale.max(1) as i32).clamp(0, 100) as u8;
+                    }
+                    let (min, max) = sensor
+                        .history()
+                        .fold((value, value), |(min, max), v| (min.min(v), max.max(v)));
+                    if max == min {
+                        50
+                    } else {
+                        ((value - min) as i64 * 100 / (max - min) as i64) as u8
+                    }
+                });
+                let bar_height = ((height - LABEL_HEIGHT - 4).max(3) as u32).min(BAR_MAX_HEIGHT);
+                let top = LABEL_HEIGHT + 1 + (height - LABEL_HEIGHT - 1 - bar_height as i32) / 2;
+                let area = Rectangle::new(
+                    Point::new(2, top),
+                    Size::new(width.saturating_sub(4), bar_height),
+                );
+                draw_progress_bar_styled(target, area, percent, &theme::active().progress_bar)
+            }
+            Widget::Battery => {
+                let percent = self.battery.and_then(|channel| sensors.value(channel));
+                let icon_top = middle - BATTERY_ICON_SIZE.height as i32 / 2;
+                draw_battery_icon(
+                    target,
+                    Point::new(2, icon_top),
+                    percent.unwrap_or(0).clamp(0, 100) as u8,
+                )?;
+                match percent {
+                    Some(percent) => {
+                        let _ = write!(text, "{}%", percent);
+                    }
+                    None => {
+                        let _ = text.push_str("--");
+                    }
+                }
+                draw_text_at(target, &text, ICON_TEXT_X, middle, height)
+            }
+            Widget::Alarms => {
+                let icon_top = middle - ALARM_ICON_SIZE as i32 / 2;
+                draw_alarm_icon_at(target, Point::new(2, icon_top))?;
+                match self.alarms.count_ones() {
+                    0 => {
+                        let _ = text.push_str("ok");
+                    }
+                    active => {
+                        let _ = write!(text, "{} on", active);
+                    }
+                }
+                draw_text_at(target, &text, ICON_TEXT_X, middle, height)
+            }
+        }
+    }
+}
+
+impl<S> Page for WatchFacePage<'_, S> {
+    fn on_event(&mut self, event: &Event, _now_ms: u64) -> Transition {
+        let editing = self.editing.is_some();
+        match event {
+            Event::ClockSet(clock) => self.clock = *clock,
+            Event::Alarms(active) => self.alarms = *active,
+            Event::WatchFace(config) => {
+                self.config = *config;
+                self.clamp_selection();
+            }
+            Event::Button(ButtonEvent::Pressed(Button::Back)) => {
+                if !editing {
+                    return Transition::Pop;
+                }
+                self.editing = None;
+                self.pending = Some(Action::SaveWatchFace(self.config));
+            }
+            Event::Button(ButtonEvent::Pressed(button @ (Button::Up | Button::Down))) => {
+                let count = self.config.layout.slot_count();
+                if let Some(slot) = self.editing.as_mut() {
+                    *slot = match button {
+                        Button::Up => (*slot + count - 1) % count,
+                        _ => (*slot + 1) % count,
+                    };
+                }
+            }
+            Event::Gesture(Gesture::Long(Button::Select)) if !editing => self.editing = Some(0),
+            Event::Gesture(Gesture::Long(Button::Select)) => {
+                self.edit(|widget, channels| widget.next_channel(channels))
+            }
+            Event::Gesture(Gesture::Short(Button::Select)) => {
+                self.edit(|widget, _| widget.next_kind())
+            }
+            Event::Gesture(Gesture::Double(Button::Select)) if editing => {
+                self.config.layout = self.config.layout.next();
+                self.clamp_selection();
+            }
+            _ => {}
+        }
+        Transition::None
+    }
+
+    fn desired_fps(&self) -> u16 {
+        2
+    }
+
+    fn tick(&mut self, now_ms: u64) -> bool {
+        let generation = self.sensors.borrow().generation();
+        let second = self.shows_clock().then_some(now_ms / 1000);
+        let changed = self.shown_generation != Some(generation) || self.shown_second != second;
+        self.shown_generation = Some(generation);
+        self.shown_second = second;
+        changed
+    }
+
+    fn take_action(&mut self) -> Option<Action> {
+        self.pending.take()
+    }
+
+    fn render(&mut self, canvas: &mut Canvas, now_ms: u64) -> Result<(), Infallible> {
+        let cells = self.config.layout.cells(canvas.bounding_box());
+        for (slot, (&cell, &widget)) in cells.iter().zip(self.config.visible()).enumerate() {
+            {
+                let mut translated = canvas.translated(cell.top_left);
+                let mut target = translated.clipped(&Rectangle::new(Point::zero(), cell.size));
+                self.draw_widget(&mut target, widget, now_ms)?;
+            }
+            if self.editing == Some(slot) {
+                cell.into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+                    .draw(canvas)?;
+            } else if self.editing.is_some() && widget == Widget::Empty {
+                draw_dotted_outline(canvas, cell)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,55 @@
+//! 长操作里喂看门狗：扫总线这种一口气要跑很久的循环，在安全的地方喂一次狗，别让看门狗在半路把板子复位了
+//!
+//! 主循环一圈喂一次狗就够了，但有的操作一圈里要跑几十上百毫秒甚至更久(每个地址都要等一次应答)，
+//! 超时设得紧一点就会在中间复位。办法不是把超时放宽，而是让操作自己在每一步做完的地方报一声：
+//!
+//! ```ignore
+//! let found = feed_during(&watchdog, |checkpoint| health::scan(i2c, checkpoint));
+//! ```
+//!
+//! `feed_during` 进去之前、出来以后各喂一次，中间操作每到一个检查点调一次 `Checkpoint::reached`。
+//! 检查点要放在"这一步肯定做完了"的地方(一个地址问完了、一批采样采完了)，不要放在可能卡住的等待里面：
+//! 总线被拉死、某一次传输永远不结束，检查点就到不了，看门狗照样会复位，真正的死机还是抓得住。
+//! 一步本身不能比看门狗超时还长，不然检查点也救不了。
+//!
+//! 看门狗在 main.rs 里时钟一配好就启动，超时是 `WATCHDOG_TIMEOUT_MS`(8 秒)，现在用到的几步都远远够：
+//!
+//! - `health::scan`：一个地址读一个字节，400kHz 下几十微秒，一百多个地址扫完也就十几毫秒
+//! - `i2c_lines::diagnose_i2c_lines`：空闲采 32 个、发一帧(1K 字节大约 25 毫秒)的时候采 256 个，一批不到 30 毫秒
+//!
+//! 所以一步做完了还没到检查点，多半就是真的卡死了。产线测试模式把看门狗关掉了(结果要一直停着)，
+//! 那里喂狗什么都不做，这些函数照常用。
+
+use rp2040_hal::watchdog::Watchdog;
+
+/// 长操作里的检查点，见模块文档
+pub struct Checkpoint<'a> {
+    watchdog: &'a Watchdog,
+    /// 报过几次，查问题用
+    reached: u32,
+}
+
+impl Checkpoint<'_> {
+    /// 这一步安全做完了，喂一次狗
+    pub fn reached(&mut self) {
+        self.watchdog.feed();
+        self.reached = self.reached.wrapping_add(1);
+    }
+
+    /// 到现在报过几次检查点
+    pub fn count(&self) -> u32 {
+        self.reached
+    }
+}
+
+/// 跑一个长操作，它在每个检查点喂一次狗；进去之前、出来以后也各喂一次
+pub fn feed_during<R>(watchdog: &Watchdog, op: impl FnOnce(&mut Checkpoint<'_>) -> R) -> R {
+    watchdog.feed();
+    let mut checkpoint = Checkpoint {
+        watchdog,
+        reached: 0,
+    };
+    let result = op(&mut checkpoint);
+    watchdog.feed();
+    result
+}
@@ -0,0 +1,893 @@
+//! 常用的小部件：滚动字幕、柱状图、折线图、音量条、进度条、状态栏、电池图标、七段大数字、菜单、提示条、圆角框、带标题的面板、告警弹窗、告警图标、网络图标、轮播进度条
+//!
+//! 这些都是只管"画"的函数，状态(比如滚动到哪了)由调用方自己保存，
+//! 所以同一个部件可以在不同页面复用。柱状图、音量条、进度条、状态栏、提示条有带 `Style` 的版本(`_styled`)，
+//! 页面照现在的主题画(见 `theme`)；不带的按默认主题画。
+
+use embedded_graphics::draw_target::{DrawTarget, DrawTargetExt};
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::mono_font::ascii::FONT_6X10;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::primitives::{
+    CornerRadii, Line, Primitive, PrimitiveStyle, PrimitiveStyleBuilder, Rectangle,
+    RoundedRectangle,
+};
+use embedded_graphics::text::{Baseline, Text};
+use embedded_graphics::Drawable;
+
+use heapless::String;
+
+use crate::clip::with_clip;
+use crate::net_console::NetState;
+use crate::text::{
+    centered_x, draw_centered, sanitize, text_pixel_width, wrap_lines, SANITIZE_CAPACITY,
+};
+use crate::theme::{self, PatternTarget, Style};
+
+/// 滚动字幕两次重复之间空出来的像素
+const MARQUEE_GAP: u32 = 24;
+
+/// 滚动字幕(跑马灯)：文字比区域宽的时候从右往左循环滚动
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Marquee {
+    offset: u32,
+}
+
+impl Marquee {
+    pub const fn new() -> Self {
+        Self { offset: 0 }
+    }
+
+    /// 回到开头，换了新文字的时候调用
+    pub fn reset(&mut self) {
+        self.offset = 0;
+    }
+
+    /// 往前滚 `step` 个像素
+    pub fn advance(&mut self, step: u32) {
+        self.offset = self.offset.wrapping_add(step);
+    }
+
+    /// 在 `area` 这条横向区域里画字幕，`baseline_y` 是文字基线
+    ///
+    /// 放得下就靠左静止显示；放不下就画两份首尾相接，看起来是无缝循环
+    pub fn draw<D>(
+        &self,
+        display: &mut D,
+        text: &str,
+        area: Rectangle,
+        baseline_y: i32,
+        style: MonoTextStyle<'_, BinaryColor>,
+    ) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        area.into_styled(PrimitiveStyle::with_fill(BinaryColor::Off))
+            .draw(display)?;
+
+        let mut buf: String<SANITIZE_CAPACITY> = String::new();
+        let text = sanitize(text, &mut buf);
+        let width = text_pixel_width(text, style.font);
+        let left = area.top_left.x;
+        if width <= area.size.width {
+            Text::new(text, Point::new(left, baseline_y), style).draw(display)?;
+            return Ok(());
+        }
+
+        // 一个循环周期是文字宽度加上间隔
+        let period = width + MARQUEE_GAP;
+        let shift = (self.offset % period) as i32;
+        let mut clipped = display.clipped(&area);
+        Text::new(text, Point::new(left - shift, baseline_y), style).draw(&mut clipped)?;
+        Text::new(text, Point::new(left - shift + period as i32, baseline_y), style)
+            .draw(&mut clipped)?;
+        Ok(())
+    }
+}
+
+/// 柱状图：`values` 里每个值占一根柱子，取值范围 0..=`max`，从左往右画
+///
+/// 柱子宽度根据区域宽度和数量自动算，放不下的时候只画最新的那一部分(靠右对齐)
+pub fn draw_bar_chart<D>(
+    display: &mut D,
+    area: Rectangle,
+    values: impl ExactSizeIterator<Item = u8>,
+    max: u8,
+) -> Result<(), D::Error>
+where
+    D: PatternTarget,
+{
+    draw_bar_chart_styled(display, area, values, max, &theme::DEFAULT.bar_chart)
+}
+
+/// 按 `style` 画柱状图(见 `theme`)，有边框的话柱子画在边框里面
+pub fn draw_bar_chart_styled<D>(
+    display: &mut D,
+    area: Rectangle,
+    values: impl ExactSizeIterator<Item = u8>,
+    max: u8,
+    style: &Style,
+) -> Result<(), D::Error>
+where
+    D: PatternTarget,
+{
+    let area = style.draw_frame(display, area, false)?;
+    let count = values.len() as u32;
+    if count == 0 || max == 0 || area.size.height == 0 {
+        return Ok(());
+    }
+
+    // 每根柱子至少 1 像素宽，柱子之间空 1 像素
+    let slot = (area.size.width / count).max(2);
+    let bar_width = slot - 1;
+    let visible = (area.size.width / slot).min(count);
+    let skip = (count - visible) as usize;
+    let bottom = area.top_left.y + area.size.height as i32;
+
+    for (i, value) in values.skip(skip).enumerate() {
+        let value = value.min(max) as u32;
+        let height = value * area.size.height / max as u32;
+        if height == 0 {
+            continue;
+        }
+        let x = area.top_left.x + (i as u32 * slot) as i32;
+        let bar = Rectangle::new(
+            Point::new(x, bottom - height as i32),
+            Size::new(bar_width, height),
+        );
+        style.fill_area(display, bar)?;
+    }
+    Ok(())
+}
+
+/// 折线图：`values` 从左往右每个值占一列像素，取值范围 `min..=max`，超出范围的贴边画
+///
+/// `None` 是缺数据，折线在这里断开。值比区域宽度多的时候只画前面放得下的部分。不会画出 `area`
+pub fn draw_line_graph<D>(
+    display: &mut D,
+    area: Rectangle,
+    values: impl Iterator<Item = Option<i32>>,
+    min: i32,
+    max: i32,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    if area.size.height == 0 || max <= min {
+        return Ok(());
+    }
+    let bottom = area.top_left.y + area.size.height as i32 - 1;
+    let span = (area.size.height - 1) as i32;
+    let to_point = |column: i32, value: i32| {
+        let value = value.clamp(min, max);
+        let y = bottom - (value - min) * span / (max - min);
+        Point::new(area.top_left.x + column, y)
+    };
+
+    let stroke = PrimitiveStyle::with_stroke(BinaryColor::On, 1);
+    let mut previous: Option<Point> = None;
+    with_clip(display, area, |display| {
+        for (column, value) in values.take(area.size.width as usize).enumerate() {
+            let Some(value) = value else {
+                previous = None;
+                continue;
+            };
+            let point = to_point(column as i32, value);
+            Line::new(previous.unwrap_or(point), point)
+                .into_styled(stroke)
+                .draw(display)?;
+            previous = Some(point);
+        }
+        Ok(())
+    })
+}
+
+/// 竖向的电平条(音量条)：外面一个框，里面按 `percent` 从下往上填充，旁边每 25% 一个刻度
+pub fn draw_level_gauge<D>(display: &mut D, area: Rectangle, percent: u8) -> Result<(), D::Error>
+where
+    D: PatternTarget,
+{
+    draw_level_gauge_styled(display, area, percent, &theme::DEFAULT.gauge)
+}
+
+/// 按 `style` 画电平条(见 `theme`)，刻度在框外面，总是白的
+pub fn draw_level_gauge_styled<D>(
+    display: &mut D,
+    area: Rectangle,
+    percent: u8,
+    style: &Style,
+) -> Result<(), D::Error>
+where
+    D: PatternTarget,
+{
+    let border = style.border.width() * 2;
+    if area.size.width <= border || area.size.height <= border {
+        return Ok(());
+    }
+
+    let inner = style.draw_frame(display, area, false)?;
+    let inner_height = inner.size.height;
+    let filled = percent.min(100) as u32 * inner_height / 100;
+    if filled > 0 {
+        let bar = Rectangle::new(
+            Point::new(
+                inner.top_left.x,
+                inner.top_left.y + (inner_height - filled) as i32,
+            ),
+            Size::new(inner.size.width, filled),
+        );
+        style.fill_area(display, bar)?;
+    }
+
+    // 刻度画在框的左边外侧，2 像素长
+    for quarter in 1..4 {
+        let y = inner.top_left.y + (inner_height * quarter / 4) as i32;
+        Rectangle::new(Point::new(area.top_left.x - 3, y), Size::new(2, 1))
+            .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+            .draw(display)?;
+    }
+    Ok(())
+}
+
+/// 横向的进度条：外面一个框，里面按 `percent` 从左往右填充
+pub fn draw_progress_bar<D>(display: &mut D, area: Rectangle, percent: u8) -> Result<(), D::Error>
+where
+    D: PatternTarget,
+{
+    draw_progress_bar_styled(display, area, percent, &theme::DEFAULT.progress_bar)
+}
+
+/// 按 `style` 画进度条(见 `theme`)
+pub fn draw_progress_bar_styled<D>(
+    display: &mut D,
+    area: Rectangle,
+    percent: u8,
+    style: &Style,
+) -> Result<(), D::Error>
+where
+    D: PatternTarget,
+{
+    let border = style.border.width() * 2;
+    if area.size.width <= border || area.size.height <= border {
+        return Ok(());
+    }
+
+    let inner = style.draw_frame(display, area, false)?;
+    let filled = percent.min(100) as u32 * inner.size.width / 100;
+    if filled > 0 {
+        let bar = Rectangle::new(inner.top_left, Size::new(filled, inner.size.height));
+        style.fill_area(display, bar)?;
+    }
+    Ok(())
+}
+
+/// 状态栏的高度，正好放一行字
+pub const STATUS_BAR_HEIGHT: u32 = 12;
+
+/// 状态栏：一条盖住下面内容的横条，左边、右边各一段字，右边的靠右。填充方式不用
+pub fn draw_status_bar<D>(
+    display: &mut D,
+    area: Rectangle,
+    left: &str,
+    right: &str,
+    style: &Style,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let inner = style.draw_frame(display, area, true)?;
+    let font = &FONT_6X10;
+    let text = MonoTextStyle::new(font, style.ink());
+    let y = inner.top_left.y + inner.size.height as i32 / 2;
+    let mut clipped = display.clipped(&inner);
+    Text::with_baseline(
+        left,
+        Point::new(inner.top_left.x + 1, y),
+        text,
+        Baseline::Middle,
+    )
+    .draw(&mut clipped)?;
+    let right_x =
+        inner.top_left.x + inner.size.width as i32 - 1 - text_pixel_width(right, font) as i32;
+    Text::with_baseline(right, Point::new(right_x, y), text, Baseline::Middle)
+        .draw(&mut clipped)?;
+    Ok(())
+}
+
+/// 电池图标的大小(不算右边的正极小凸起)
+pub const BATTERY_ICON_SIZE: Size = Size::new(13, 7);
+
+/// 电池图标：左上角在 `top_left`，外框加右边一个正极凸起，里面按 `percent` 从左往右填充。
+/// 有电就至少填一列，不然 1%~7% 看起来和没电一样
+pub fn draw_battery_icon<D>(display: &mut D, top_left: Point, percent: u8) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let body = Rectangle::new(top_left, BATTERY_ICON_SIZE);
+    body.into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+        .draw(display)?;
+    let nub_height = BATTERY_ICON_SIZE.height - 4;
+    Rectangle::new(
+        top_left + Point::new(BATTERY_ICON_SIZE.width as i32, 2),
+        Size::new(1, nub_height),
+    )
+    .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+    .draw(display)?;
+
+    // 框里面再留一圈空，填充的部分和外框分得开
+    let inner_width = BATTERY_ICON_SIZE.width - 4;
+    let mut filled = percent.min(100) as u32 * inner_width / 100;
+    if percent > 0 {
+        filled = filled.max(1);
+    }
+    if filled > 0 {
+        Rectangle::new(
+            top_left + Point::new(2, 2),
+            Size::new(filled, BATTERY_ICON_SIZE.height - 4),
+        )
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(display)?;
+    }
+    Ok(())
+}
+
+/// 七段数码管每个数字亮哪几段，bit0..bit6 对应 a..g(a 在上面，顺时针，g 在中间)
+const SEGMENTS: [u8; 10] = [
+    0b011_1111, 0b000_0110, 0b101_1011, 0b100_1111, 0b110_0110, 0b110_1101, 0b111_1101, 0b000_0111,
+    0b111_1111, 0b110_1111,
+];
+
+/// 在 `cell` 里画一位七段数字，笔画粗 `stroke` 像素。只画亮的段，格子要调用方自己先擦
+pub fn draw_big_digit<D>(
+    display: &mut D,
+    cell: Rectangle,
+    stroke: i32,
+    digit: u8,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let (x, top) = (cell.top_left.x, cell.top_left.y);
+    let (width, height) = (cell.size.width as i32, cell.size.height as i32);
+    let mid = top + (height - stroke) / 2;
+    let bottom = top + height - stroke;
+    let inner = width - 2 * stroke;
+    let upper = mid - top - stroke;
+    let lower = bottom - mid - stroke;
+    let right = x + width - stroke;
+    // (左上角, 宽, 高)，顺序是 a..g
+    let segments = [
+        (Point::new(x + stroke, top), inner, stroke),
+        (Point::new(right, top + stroke), stroke, upper),
+        (Point::new(right, mid + stroke), stroke, lower),
+        (Point::new(x + stroke, bottom), inner, stroke),
+        (Point::new(x, mid + stroke), stroke, lower),
+        (Point::new(x, top + stroke), stroke, upper),
+        (Point::new(x + stroke, mid), inner, stroke),
+    ];
+    let lit = SEGMENTS[digit as usize % 10];
+    for (i, (top_left, width, height)) in segments.into_iter().enumerate() {
+        if lit & (1 << i) != 0 {
+            Rectangle::new(top_left, Size::new(width as u32, height as u32))
+                .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+                .draw(display)?;
+        }
+    }
+    Ok(())
+}
+
+/// 菜单每一行的高度
+pub const MENU_ROW_HEIGHT: i32 = 12;
+
+/// 菜单列表：从 `top` 开始往下一行一项，左边是名字、右边是值(比如开关状态)，选中的那一行反色
+///
+/// 放不下的时候会往下滚，保证选中的那一行能看到。只画在 `top` 以下，不会压到上面的标题
+pub fn draw_menu<'a, D>(
+    display: &mut D,
+    top: i32,
+    items: impl Iterator<Item = (&'a str, &'a str)>,
+    selected: usize,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let screen = display.bounding_box();
+    let rows = ((screen.size.height as i32 - top) / MENU_ROW_HEIGHT).max(1) as usize;
+    let first = selected.saturating_sub(rows - 1);
+    let area = Rectangle::with_corners(
+        Point::new(screen.top_left.x, top),
+        screen.bottom_right().unwrap_or(screen.top_left),
+    );
+    with_clip(display, area, |display| {
+        for (row, (label, value)) in items.skip(first).take(rows).enumerate() {
+            let y = top + row as i32 * MENU_ROW_HEIGHT;
+            draw_menu_row(display, y, label, value, first + row == selected)?;
+        }
+        Ok(())
+    })
+}
+
+/// 菜单里的一行(顶边在 `y`)，不是菜单的页面想要同样的"名字 ... 值"排版也可以直接用
+pub fn draw_menu_row<D>(
+    display: &mut D,
+    y: i32,
+    label: &str,
+    value: &str,
+    selected: bool,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let width = display.bounding_box().size.width;
+    let color = if selected {
+        Rectangle::new(Point::new(0, y), Size::new(width, MENU_ROW_HEIGHT as u32))
+            .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+            .draw(display)?;
+        BinaryColor::Off
+    } else {
+        BinaryColor::On
+    };
+    let style = MonoTextStyle::new(&FONT_6X10, color);
+    // 文字基线在行底往上 3 像素，和 6x10 字体的下伸部分对齐
+    let baseline = y + MENU_ROW_HEIGHT - 3;
+    Text::new(label, Point::new(2, baseline), style).draw(display)?;
+    let value_x = width as i32 - 2 - text_pixel_width(value, &FONT_6X10) as i32;
+    Text::new(value, Point::new(value_x, baseline), style).draw(display)?;
+    Ok(())
+}
+
+/// 告警图标的边长
+pub const ALARM_ICON_SIZE: u32 = 9;
+
+/// 告警图标：右上角一个白底的 "!"，有告警规则在触发的时候盖在页面上面
+pub fn draw_alarm_icon<D>(display: &mut D) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let left = display.bounding_box().size.width as i32 - ALARM_ICON_SIZE as i32;
+    draw_alarm_icon_at(display, Point::new(left, 0))
+}
+
+/// 同一个告警图标，左上角在 `top_left`(表盘的告警格子用)
+pub fn draw_alarm_icon_at<D>(display: &mut D, top_left: Point) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    Rectangle::new(top_left, Size::new(ALARM_ICON_SIZE, ALARM_ICON_SIZE))
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(display)?;
+    let x = top_left.x + ALARM_ICON_SIZE as i32 / 2;
+    let ink = PrimitiveStyle::with_stroke(BinaryColor::Off, 1);
+    Line::new(Point::new(x, top_left.y + 1), Point::new(x, top_left.y + 5))
+        .into_styled(ink)
+        .draw(display)?;
+    Line::new(Point::new(x, top_left.y + 7), Point::new(x, top_left.y + 7))
+        .into_styled(ink)
+        .draw(display)
+}
+
+/// 低电量图标：告警图标左边一个只剩一格电的电池，电压低、不写 flash 的时候盖在页面上面(见 `low_voltage`)
+///
+/// `save_pending` 的话电池左边再画一个小方块，表示有设置等着电压回来再存。先把底下擦黑，页面上的东西不会和它混在一起
+pub fn draw_low_battery_indicator<D>(display: &mut D, save_pending: bool) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let width = display.bounding_box().size.width as i32;
+    let right = width - ALARM_ICON_SIZE as i32 - 2;
+    let top_left = Point::new(right - BATTERY_ICON_SIZE.width as i32 - 1, 1);
+    Rectangle::new(
+        top_left - Point::new(6, 1),
+        BATTERY_ICON_SIZE + Size::new(8, 2),
+    )
+    .into_styled(PrimitiveStyle::with_fill(BinaryColor::Off))
+    .draw(display)?;
+    draw_battery_icon(display, top_left, 1)?;
+    if save_pending {
+        Rectangle::new(top_left + Point::new(-5, 2), Size::new(3, 3))
+            .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+            .draw(display)?;
+    }
+    Ok(())
+}
+
+/// 网络图标的边长
+pub const NETWORK_ICON_SIZE: u32 = 9;
+
+/// 网络图标：低电量图标再左边三根从矮到高的竖条(见 `net_console`)。
+/// 在连 Wi-Fi 亮一根，拿到 IP 两根，有人连着三根；`Off` 什么都不画。先把底下擦黑
+pub fn draw_network_indicator<D>(display: &mut D, state: NetState) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let bars = match state {
+        NetState::Off => return Ok(()),
+        NetState::Joining => 1,
+        NetState::Online(_) => 2,
+        NetState::Client(_) => 3,
+    };
+    let width = display.bounding_box().size.width as i32;
+    // 低电量图标连同它擦黑的那一块一共占到右边 31 像素，再空 2 像素
+    let left = width - NETWORK_ICON_SIZE as i32 - 33;
+    let size = Size::new(NETWORK_ICON_SIZE, NETWORK_ICON_SIZE);
+    Rectangle::new(Point::new(left, 0), size)
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::Off))
+        .draw(display)?;
+    let bottom = NETWORK_ICON_SIZE as i32 - 1;
+    for bar in 0..bars {
+        let height = 3 + 2 * bar as u32;
+        Rectangle::new(
+            Point::new(left + 1 + 3 * bar, bottom - height as i32),
+            Size::new(2, height),
+        )
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(display)?;
+    }
+    Ok(())
+}
+
+/// 轮播进度条：底边一像素高的线，从左往右长到 `percent`。先把这一行擦掉，页面贴底画的东西不会跟它混在一起
+pub fn draw_carousel_progress<D>(display: &mut D, percent: u8) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let size = display.bounding_box().size;
+    let y = size.height as i32 - 1;
+    let filled = size.width * percent.min(100) as u32 / 100;
+    Rectangle::new(Point::new(0, y), Size::new(size.width, 1))
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::Off))
+        .draw(display)?;
+    Rectangle::new(Point::new(0, y), Size::new(filled, 1))
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(display)
+}
+
+/// 面板圆角的半径
+const PANEL_RADIUS: u32 = 3;
+
+/// 面板标题栏的高度
+const PANEL_TITLE_HEIGHT: u32 = 12;
+
+/// 圆角矩形，`radius` 超过短边的一半就按一半算(两头是半圆)
+pub fn draw_rounded_rect<D>(
+    display: &mut D,
+    rect: Rectangle,
+    radius: u32,
+    style: PrimitiveStyle<BinaryColor>,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let radius = radius.min(rect.size.width.min(rect.size.height) / 2);
+    RoundedRectangle::new(rect, CornerRadii::new(Size::new(radius, radius)))
+        .into_styled(style)
+        .draw(display)
+}
+
+/// 带标题的面板：圆角边框，顶上一条白底黑字的标题栏，标题居中，太长的截掉
+///
+/// 框里面先涂黑，盖住下面的东西。返回标题栏下面、边框里面能画内容的区域
+pub fn draw_panel<D>(display: &mut D, rect: Rectangle, title: &str) -> Result<Rectangle, D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let frame = PrimitiveStyleBuilder::new()
+        .stroke_color(BinaryColor::On)
+        .stroke_width(1)
+        .fill_color(BinaryColor::Off)
+        .build();
+    draw_rounded_rect(display, rect, PANEL_RADIUS, frame)?;
+    let title_height = PANEL_TITLE_HEIGHT.min(rect.size.height);
+    let bar = Rectangle::new(rect.top_left, Size::new(rect.size.width, title_height));
+    // 标题栏和边框一起圆角，下面两个角靠裁剪切成直的
+    with_clip(display, bar, |display| {
+        draw_rounded_rect(
+            display,
+            rect,
+            PANEL_RADIUS,
+            PrimitiveStyle::with_fill(BinaryColor::On),
+        )
+    })?;
+    // 在标题栏里面居中，放不下的时候 `draw_centered` 会截断加省略号
+    let font = &FONT_6X10;
+    let style = MonoTextStyle::new(font, BinaryColor::Off);
+    let top = title_height.saturating_sub(font.character_size.height) / 2;
+    let baseline = (top + font.baseline) as i32;
+    draw_centered(
+        &mut display.cropped(&bar.offset(-1)),
+        title,
+        baseline - 1,
+        style,
+    )?;
+    Ok(Rectangle::new(
+        rect.top_left + Point::new(1, title_height as i32),
+        Size::new(
+            rect.size.width.saturating_sub(2),
+            rect.size.height.saturating_sub(title_height + 1),
+        ),
+    ))
+}
+
+/// 弹窗离屏幕边的距离
+const ALERT_MARGIN: u32 = 6;
+
+/// 弹窗里文字离边框的距离
+const ALERT_PADDING: u32 = 5;
+
+/// 弹窗圆角的半径
+const ALERT_RADIUS: u32 = 4;
+
+/// 告警弹窗：屏幕中间一个白底圆角框，里面是反色(黑字)的消息，盖在页面上面
+///
+/// 消息按框的宽度自动换行，行数按内容定，放不下的行不画。只管画，弹出、关掉由调用方管：
+/// 画在显存上的只是这一帧，关掉之后把下面的页面重画一遍就恢复了(见 `popup`)
+pub fn draw_alert<D>(display: &mut D, message: &str) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let size = display.bounding_box().size;
+    let font = &FONT_6X10;
+    let line_height = font.character_size.height;
+    let box_width = size.width.saturating_sub(2 * ALERT_MARGIN);
+    let text_width = box_width.saturating_sub(2 * ALERT_PADDING);
+    let max_lines = (size
+        .height
+        .saturating_sub(2 * (ALERT_MARGIN + ALERT_PADDING))
+        / line_height)
+        .max(1) as usize;
+    let lines = wrap_lines(message, font, text_width)
+        .take(max_lines)
+        .count()
+        .max(1);
+    let box_height = lines as u32 * line_height + 2 * ALERT_PADDING;
+
+    let top_left = Point::new(
+        ALERT_MARGIN as i32,
+        (size.height.saturating_sub(box_height) / 2) as i32,
+    );
+    let area = Rectangle::new(top_left, Size::new(box_width, box_height));
+    draw_rounded_rect(
+        display,
+        area,
+        ALERT_RADIUS,
+        PrimitiveStyle::with_fill(BinaryColor::On),
+    )?;
+    // 白底里面再描一圈黑线，和下面页面的白色内容分得开
+    draw_rounded_rect(
+        display,
+        area.offset(-2),
+        ALERT_RADIUS - 2,
+        PrimitiveStyle::with_stroke(BinaryColor::Off, 1),
+    )?;
+
+    let style = MonoTextStyle::new(font, BinaryColor::Off);
+    let text_top = top_left.y + ALERT_PADDING as i32;
+    let mut buf: String<SANITIZE_CAPACITY> = String::new();
+    for (row, line) in wrap_lines(message, font, text_width)
+        .take(lines)
+        .enumerate()
+    {
+        let line = sanitize(line, &mut buf);
+        let x = top_left.x + ALERT_PADDING as i32 + centered_x(line, font, text_width);
+        let y = text_top + row as i32 * line_height as i32;
+        Text::with_baseline(line, Point::new(x, y), style, Baseline::Top).draw(display)?;
+    }
+    Ok(())
+}
+
+/// 提示条最多放多少字节
+pub const TOAST_CAPACITY: usize = 24;
+
+/// 提示条默认显示多久(毫秒)
+pub const TOAST_DURATION_MS: u64 = 2000;
+
+/// 提示条的高度，贴着屏幕底部
+const TOAST_HEIGHT: u32 = 14;
+
+/// 提示条(toast)：在屏幕底部盖一条带框的文字，过几秒自己消失
+///
+/// 要在页面内容画完之后再画，这样才会盖在最上面
+#[derive(Debug, Default, Clone)]
+pub struct Toast {
+    message: String<TOAST_CAPACITY>,
+    until_ms: u64,
+}
+
+impl Toast {
+    pub const fn new() -> Self {
+        Self {
+            message: String::new(),
+            until_ms: 0,
+        }
+    }
+
+    /// 显示一条提示，会顶掉正在显示的那条。太长的部分会被截掉
+    pub fn show(&mut self, message: &str, now_ms: u64) {
+        self.message.clear();
+        for c in message.chars() {
+            if self.message.push(c).is_err() {
+                break;
+            }
+        }
+        self.until_ms = now_ms + TOAST_DURATION_MS;
+    }
+
+    pub fn is_visible(&self, now_ms: u64) -> bool {
+        now_ms < self.until_ms
+    }
+
+    /// 画提示条，已经过期的话什么都不画
+    pub fn draw<D>(&self, display: &mut D, now_ms: u64) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        self.draw_styled(display, now_ms, &theme::DEFAULT.toast)
+    }
+
+    /// 按 `style` 画提示条(见 `theme`)，先涂底色把下面的页面内容盖住。填充方式不用
+    pub fn draw_styled<D>(
+        &self,
+        display: &mut D,
+        now_ms: u64,
+        style: &Style,
+    ) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        if !self.is_visible(now_ms) {
+            return Ok(());
+        }
+        let size = display.bounding_box().size;
+        let area = Rectangle::new(
+            Point::new(0, (size.height - TOAST_HEIGHT) as i32),
+            Size::new(size.width, TOAST_HEIGHT),
+        );
+        style.draw_frame(display, area, true)?;
+        let text = MonoTextStyle::new(&FONT_6X10, style.ink());
+        draw_centered(display, &self.message, area.top_left.y + 10, text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recording::{DrawOp, RecordingTarget};
+
+    type Target = RecordingTarget<64>;
+
+    fn recorder() -> Target {
+        RecordingTarget::new(Size::new(128, 64))
+    }
+
+    fn rect(x: i32, y: i32, width: u32, height: u32) -> Rectangle {
+        Rectangle::new(Point::new(x, y), Size::new(width, height))
+    }
+
+    fn fill(x: i32, y: i32, width: u32, height: u32) -> DrawOp {
+        DrawOp::Fill {
+            area: rect(x, y, width, height),
+            color: BinaryColor::On,
+        }
+    }
+
+    /// 默认主题 1 像素边框：上、下、左、右四条
+    fn frame(x: i32, y: i32, width: u32, height: u32) -> [DrawOp; 4] {
+        [
+            fill(x, y, width, 1),
+            fill(x, y + height as i32 - 1, width, 1),
+            fill(x, y + 1, 1, height - 2),
+            fill(x + width as i32 - 1, y + 1, 1, height - 2),
+        ]
+    }
+
+    #[test]
+    fn progress_bar_plan() {
+        let area = rect(10, 40, 100, 10);
+        let mut target = recorder();
+        draw_progress_bar(&mut target, area, 50).unwrap();
+        let mut expected = frame(10, 40, 100, 10).to_vec();
+        // 框里面 98 像素宽，一半是 49
+        expected.push(fill(11, 41, 49, 8));
+        assert_eq!(target.ops(), expected);
+
+        for (percent, width) in [(100, 98), (255, 98), (1, 0)] {
+            let mut target = recorder();
+            draw_progress_bar(&mut target, area, percent).unwrap();
+            let bar = target.ops().get(4).copied();
+            assert_eq!(
+                bar,
+                (width > 0).then(|| fill(11, 41, width, 8)),
+                "{percent}%"
+            );
+        }
+    }
+
+    #[test]
+    fn too_small_draws_nothing() {
+        let mut target = recorder();
+        draw_progress_bar(&mut target, rect(0, 0, 2, 10), 50).unwrap();
+        draw_level_gauge(&mut target, rect(0, 0, 10, 2), 50).unwrap();
+        assert!(target.ops().is_empty());
+    }
+
+    #[test]
+    fn level_gauge_fills_from_the_bottom_with_ticks() {
+        let mut target = recorder();
+        draw_level_gauge(&mut target, rect(10, 10, 10, 42), 25).unwrap();
+        let mut expected = frame(10, 10, 10, 42).to_vec();
+        // 里面 40 行，25% 是最下面 10 行；刻度在框左边外侧，每 25% 一个
+        expected.push(fill(11, 41, 8, 10));
+        expected.extend([21, 31, 41].map(|y| fill(7, y, 2, 1)));
+        assert_eq!(target.ops(), expected);
+    }
+
+    #[test]
+    fn battery_icon_fills_at_least_one_column() {
+        let top_left = Point::new(100, 2);
+        let fill_width = |percent| {
+            let mut target = recorder();
+            draw_battery_icon(&mut target, top_left, percent).unwrap();
+            target.ops().iter().find_map(|op| match *op {
+                DrawOp::Fill { area, .. } if area.top_left == top_left + Point::new(2, 2) => {
+                    Some(area.size.width)
+                }
+                _ => None,
+            })
+        };
+        assert_eq!(fill_width(0), None);
+        assert_eq!(fill_width(1), Some(1));
+        assert_eq!(fill_width(50), Some(4));
+        assert_eq!(fill_width(100), Some(BATTERY_ICON_SIZE.width - 4));
+        // 正极凸起在框右边外面
+        let mut target = recorder();
+        draw_battery_icon(&mut target, top_left, 0).unwrap();
+        assert!(target.ops().contains(&fill(113, 4, 1, 3)));
+    }
+
+    #[test]
+    fn network_indicator_plan() {
+        let bars = |state| {
+            let mut target = recorder();
+            draw_network_indicator(&mut target, state).unwrap();
+            target.ops().to_vec()
+        };
+        assert!(bars(NetState::Off).is_empty());
+        let background = DrawOp::Fill {
+            area: rect(86, 0, 9, 9),
+            color: BinaryColor::Off,
+        };
+        assert_eq!(
+            bars(NetState::Online([10, 0, 0, 2])),
+            [background, fill(87, 5, 2, 3), fill(90, 3, 2, 5)]
+        );
+        assert_eq!(bars(NetState::Joining).len(), 2);
+        // 最高的一根顶到图标上边，不碰到右边的低电量图标
+        let mut target = recorder();
+        draw_network_indicator(&mut target, NetState::Client([10, 0, 0, 2])).unwrap();
+        assert_eq!(target.lit_bounds(), Some(rect(87, 1, 8, 7)));
+    }
+
+    #[test]
+    fn big_digit_segments() {
+        let cell = rect(52, 14, 24, 40);
+        let segments = |digit| {
+            let mut target = recorder();
+            draw_big_digit(&mut target, cell, 4, digit).unwrap();
+            target.ops().to_vec()
+        };
+        // 1 只有右边两段
+        assert_eq!(segments(1), [fill(72, 18, 4, 14), fill(72, 36, 4, 14)]);
+        for digit in 0..10 {
+            assert_eq!(
+                segments(digit).len(),
+                SEGMENTS[digit as usize].count_ones() as usize
+            );
+            let mut target = recorder();
+            draw_big_digit(&mut target, cell, 4, digit).unwrap();
+            // 都画在格子里面
+            let bounds = target.lit_bounds().unwrap();
+            assert_eq!(bounds.intersection(&cell), bounds, "{digit}");
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//! Pico W 连哪个 Wi-Fi：名字和密码，存在设置扇区第 0 页设置后面的空位里
+//!
+//! 两个来源，串口设的优先：
+//!
+//! - 编译的时候的环境变量 `WIFI_SSID`、`WIFI_PASSWORD`(没有密码就是开放网络)，烧进固件里
+//! - 串口命令 `SETTINGS WIFI <名字> [密码]`，存进 flash；`SETTINGS WIFI OFF` 也存一条"不连"，
+//!   这样编译进去的那个也不会再连
+//!
+//! 不放进设置的二进制格式(见 `settings`)：那里一行 base64 放不下，而且 `SETTINGS DUMP` 不该把密码打出来。
+//! 保存设置、屏幕累计记录擦扇区的时候这一段跟着第 0 页一起写回去。
+//!
+//! flash 里的格式：版本 1 字节、名字长度 1 字节、名字 32 字节、密码长度 1 字节、密码 63 字节、
+//! 前面所有字节的 CRC-32 4 字节(小端)。名字长度是 0 表示不连。名字里不能有空格(串口命令按空格拆参数)，
+//! 密码是 WPA2 的 8~63 个可打印字符，不支持 64 位十六进制的 PSK。
+
+use heapless::String;
+
+use crate::crc::crc32;
+use crate::flash;
+use crate::low_voltage;
+use crate::panel_care;
+use crate::settings::BLOB_CAPACITY;
+
+/// 名字最长多少字节(802.11 的上限)
+pub const SSID_MAX: usize = 32;
+
+/// 密码最短、最长多少字节(WPA2 的口令)
+pub const PASSWORD_MIN: usize = 8;
+pub const PASSWORD_MAX: usize = 63;
+
+/// 编码之后多少字节
+pub const ENCODED_LEN: usize = 1 + 1 + SSID_MAX + 1 + PASSWORD_MAX + 4;
+
+/// 格式版本
+const VERSION: u8 = 1;
+
+/// 存在哪里：设置扇区第 0 页，设置后面
+const OFFSET: u32 = flash::SETTINGS_OFFSET + BLOB_CAPACITY as u32;
+
+const _: () = assert!(BLOB_CAPACITY + ENCODED_LEN <= flash::PAGE_LEN);
+
+/// 一个 Wi-Fi 的名字和密码
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WifiCredentials {
+    ssid: String<SSID_MAX>,
+    /// 空的是开放网络
+    password: String<PASSWORD_MAX>,
+}
+
+impl WifiCredentials {
+    /// 名字 1~32 字节、不带空白，密码是空的或者 8~63 个可打印 ASCII 字符，不对返回 None
+    pub fn new(ssid: &str, password: &str) -> Option<Self> {
+        let ssid_ok = !ssid.is_empty() && !ssid.contains(char::is_whitespace);
+        let password_ok = password.is_empty()
+            || ((PASSWORD_MIN..=PASSWORD_MAX).contains(&password.len())
+                && password.bytes().all(|byte| (b' '..=b'~').contains(&byte)));
+        if !(ssid_ok && password_ok) {
+            return None;
+        }
+        Some(Self {
+            ssid: String::try_from(ssid).ok()?,
+            password: String::try_from(password).ok()?,
+        })
+    }
+
+    /// 编译的时候给的(`WIFI_SSID`、`WIFI_PASSWORD`)，没给或者不对是 None
+    pub fn from_build_env() -> Option<Self> {
+        Self::new(
+            option_env!("WIFI_SSID")?,
+            option_env!("WIFI_PASSWORD").unwrap_or(""),
+        )
+    }
+
+    pub fn ssid(&self) -> &str {
+        &self.ssid
+    }
+
+    pub fn password(&self) -> &str {
+        &self.password
+    }
+}
+
+/// 编码，None 是"不连"
+pub fn encode(credentials: Option<&WifiCredentials>) -> [u8; ENCODED_LEN] {
+    let mut out = [0u8; ENCODED_LEN];
+    out[0] = VERSION;
+    if let Some(credentials) = credentials {
+        let (ssid, password) = (credentials.ssid.as_bytes(), credentials.password.as_bytes());
+        out[1] = ssid.len() as u8;
+        out[2..2 + ssid.len()].copy_from_slice(ssid);
+        out[2 + SSID_MAX] = password.len() as u8;
+        out[3 + SSID_MAX..3 + SSID_MAX + password.len()].copy_from_slice(password);
+    }
+    let crc = crc32(&out[..ENCODED_LEN - 4]);
+    out[ENCODED_LEN - 4..].copy_from_slice(&crc.to_le_bytes());
+    out
+}
+
+/// 解码：Some(None) 是存过"不连"，None 是没存过(擦除状态)或者坏了
+pub fn decode(bytes: &[u8]) -> Option<Option<WifiCredentials>> {
+    if bytes.len() != ENCODED_LEN || bytes[0] != VERSION {
+        return None;
+    }
+    let (body, crc) = bytes.split_at(ENCODED_LEN - 4);
+    if crc32(body).to_le_bytes() != crc {
+        return None;
+    }
+    let ssid_len = body[1] as usize;
+    if ssid_len == 0 {
+        return Some(None);
+    }
+    let password_len = body[2 + SSID_MAX] as usize;
+    if ssid_len > SSID_MAX || password_len > PASSWORD_MAX {
+        return None;
+    }
+    let ssid = core::str::from_utf8(&body[2..2 + ssid_len]).ok()?;
+    let password = core::str::from_utf8(&body[3 + SSID_MAX..3 + SSID_MAX + password_len]).ok()?;
+    WifiCredentials::new(ssid, password).map(Some)
+}
+
+/// 该连哪个：串口存过的优先(包括存过"不连")，没存过用编译进去的
+pub fn load() -> Option<WifiCredentials> {
+    decode(flash::read(OFFSET, ENCODED_LEN)).unwrap_or_else(WifiCredentials::from_build_env)
+}
+
+/// 存进 flash，None 是"不连"。要擦整个设置扇区，设置和屏幕累计记录会写回去。
+/// 电压低的时候不写，返回 false
+pub fn store(credentials: Option<&WifiCredentials>) -> bool {
+    if !low_voltage::flash_writes_allowed() {
+        return false;
+    }
+    let mut page = [0u8; flash::PAGE_LEN];
+    page.copy_from_slice(flash::read(flash::SETTINGS_OFFSET, flash::PAGE_LEN));
+    let at = BLOB_CAPACITY;
+    page[at..at + ENCODED_LEN].copy_from_slice(&encode(credentials));
+    panel_care::carry_over(|| {
+        flash::erase_sector(flash::SETTINGS_OFFSET);
+        flash::program_page(flash::SETTINGS_OFFSET, &page);
+    });
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_ssid_and_password() {
+        assert!(WifiCredentials::new("home", "").is_some());
+        assert!(WifiCredentials::new("home", "12345678").is_some());
+        assert!(WifiCredentials::new("", "12345678").is_none());
+        assert!(WifiCredentials::new("my home", "12345678").is_none());
+        // WPA2 口令 8~63 个字符
+        assert!(WifiCredentials::new("home", "1234567").is_none());
+        assert!(WifiCredentials::new("home", &"x".repeat(63)).is_some());
+        assert!(WifiCredentials::new("home", &"x".repeat(64)).is_none());
+        assert!(WifiCredentials::new(&"s".repeat(33), "").is_none());
+        assert!(WifiCredentials::new("home", "pass\tword").is_none());
+    }
+
+    #[test]
+    fn round_trips() {
+        let credentials = WifiCredentials::new("home-2G", "correct horse").unwrap();
+        let encoded = encode(Some(&credentials));
+        assert_eq!(decode(&encoded), Some(Some(credentials)));
+        assert_eq!(decode(&encode(None)), Some(None));
+        let open = WifiCredentials::new(&"s".repeat(SSID_MAX), "").unwrap();
+        assert_eq!(decode(&encode(Some(&open))), Some(Some(open)));
+    }
+
+    #[test]
+    fn rejects_blank_or_corrupt() {
+        assert_eq!(decode(&[0xFF; ENCODED_LEN]), None);
+        let mut encoded = encode(Some(&WifiCredentials::new("home", "12345678").unwrap()));
+        encoded[3] ^= 1;
+        assert_eq!(decode(&encoded), None);
+        assert_eq!(decode(&encoded[1..]), None);
+    }
+}
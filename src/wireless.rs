@@ -0,0 +1,515 @@
+//! Pico W 的无线芯片 CYW43439：起驱动、点板载 LED、连 Wi-Fi
+//!
+//! Pico W 的板载 LED 接在无线芯片的 GPIO 0 上，CPU 碰不到。要点亮得先把芯片的固件(两百多 K，在 `cyw43-firmware` 里)
+//! 下载进去，跑起来以后再发一条设 GPIO 的控制命令。协议由 `cyw43` 驱动负责，这里只做三件事：
+//!
+//! - 总线：芯片和 RP2040 之间是一根数据线来回用的半双工 SPI(gSPI)，`GSpi` 用 GPIO 翻转模拟，
+//!   时序和 `cyw43-pio` 的 PIO 程序一样：写的时候时钟低电平放数据、上升沿芯片采样；读的时候上升沿之后采样。
+//!   CPU 翻转大概几 MHz，比 PIO 慢，但是不占状态机和 DMA，下载一遍固件也就零点几秒
+//! - 运行时：`cyw43` 是 embassy 的异步驱动，要有人一直 poll 它的 `Runner`。这里放一个 embassy
+//!   的裸执行器(`raw::Executor`)，谁调 `poll` 谁驱动它，主循环不用改成异步的。定时器(`embassy-time`)
+//!   换算到 rp2040-hal 的定时器上(`shared::now_us`)，到没到点每次 poll 都看一遍，不用中断叫醒
+//! - LED：`Cyw43Led` 实现 `StatusLed`，闪烁码和主循环和普通 Pico 上一样
+//! - 网络：`Cyw43Led::start_network` 再起 embassy-net 协议栈(DHCP)和一个听 TCP 端口的任务，
+//!   收发的字节和状态交给 net_console.rs，主循环拿到的是 `NetLink`
+//!
+//! ## 网络任务
+//!
+//! | 任务 | 干什么 |
+//! | ---- | ---- |
+//! | `control_task` | 设灯；按 `NetLink::join` 给的账号连 Wi-Fi，连不上或者断了隔 `JOIN_RETRY_MS` 再连 |
+//! | `net_task` | 协议栈本身(收发包、DHCP) |
+//! | `status_task` | 看链路、IP、有没有人连着，算出 `NetState` |
+//! | `tcp_task` | 在 `net_console::PORT` 上等人连，连上以后在连接和两条队列之间搬字节 |
+//!
+//! 连 Wi-Fi 要等芯片好几秒，这段时间在 `control_task` 里等着，主循环照常跑，只是灯要等连完了才跟着变。
+//!
+//! ## 引脚
+//!
+//! | 引脚 | 用途 |
+//! | ---- | ---- |
+//! | GP23 | WL_ON，无线芯片的电源，高电平开 |
+//! | GP24 | 数据线(写、读、空闲时拉高表示有事件) |
+//! | GP25 | 片选，低电平有效 |
+//! | GP29 | 时钟，和测 VSYS 的 ADC3 共用 |
+//!
+//! GP29 归 `vsys_adc_pin!` 管，这里不拿它：每次传输拉低片选以后才把它临时切成输出当时钟，传完马上恢复成 ADC 输入，
+//! 片选拉高的时候芯片不看时钟线，所以读 VSYS 不受影响。打开 `mic-vu` 的时候 ADC 一直在转，
+//! 碰巧在传输中间采到的那一个 VSYS 样本不准。
+//!
+//! ## 限制
+//!
+//! - 起驱动(上电、下载固件、初始化)在开机的时候阻塞着做，最多等 `BRING_UP_TIMEOUT_MS`，比看门狗短；
+//!   起不来(比如板子其实不是 Pico W)就当没有灯，打一条警告
+//! - 灯变了的时候 `set_on` 要等芯片回应，最多 `LED_TIMEOUT_MS`(正在连 Wi-Fi 的话不等)；没变的话只 poll 一次执行器，很快
+//! - 执行器只在主循环里 poll(`set_on`、`NetLink::poll`)，主循环停下来的地方(写 flash、关屏等按键)网络也停，
+//!   TCP 那边就是卡一下，不会断
+//! - 整个模块在 `pico-w` feature 后面，不开就不编译，也不带固件
+
+use core::future::{pending, poll_fn, Future};
+use core::pin::pin;
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicU8, Ordering};
+use core::task::{Context, Poll, Waker};
+
+use cyw43::{JoinOptions, SpiBusCyw43};
+use embassy_executor::raw::Executor;
+use embassy_futures::select::{select, select3, Either3};
+use embassy_futures::yield_now;
+use embassy_net::tcp::TcpSocket;
+use embassy_net::{Stack, StackResources};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Timer};
+use rp2040_hal::pac;
+
+use crate::net_console::{NetLink, NetState, PIPE, PORT};
+use crate::outputs::OutputPin;
+use crate::shared;
+use crate::status_led::StatusLed;
+use crate::wifi_config::WifiCredentials;
+
+/// 起驱动最多等多久(毫秒)，要比看门狗超时短
+pub const BRING_UP_TIMEOUT_MS: u64 = 5_000;
+
+/// 灯变了以后最多等芯片多久(毫秒)
+const LED_TIMEOUT_MS: u64 = 50;
+
+/// 灯接在无线芯片的第几个 GPIO
+const LED_GPIO: u8 = 0;
+
+/// 连不上或者断了以后隔多久再连(毫秒)
+const JOIN_RETRY_MS: u64 = 10_000;
+
+/// 连着的人多久没动静就断开，好让别人连上来
+const IDLE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// 多久发一次 TCP keep-alive，对面不在了(电脑睡了、Wi-Fi 断了)超时会早一点发现
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+
+/// 断开的时候最多等多久把没发完的发出去
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// 协议栈里的 socket 有几个：DHCP 一个、TCP 一个
+const SOCKETS: usize = 2;
+
+/// TCP 连接自己的收发缓冲
+const TCP_RX_LEN: usize = 512;
+const TCP_TX_LEN: usize = 1024;
+
+/// 数据线、片选、时钟的 GPIO 编号
+const DIO: u32 = 24;
+const CS: u32 = 25;
+const CLK: u32 = 29;
+
+/// GPIO 的 FUNCSEL 选 SIO
+const FUNCSEL_SIO: u8 = 5;
+
+/// 控制任务 → `set_on`：芯片现在的灯是什么状态
+const LED_UNKNOWN: u8 = 2;
+
+/// 控制任务初始化完了
+static READY: AtomicBool = AtomicBool::new(false);
+
+/// 芯片上的灯现在是亮(1)、灭(0)还是还不知道(`LED_UNKNOWN`)
+static LED_STATE: AtomicU8 = AtomicU8::new(LED_UNKNOWN);
+
+/// `set_on` → 控制任务：灯要变成什么
+static LED_REQUEST: Signal<CriticalSectionRawMutex, bool> = Signal::new();
+
+/// 控制任务正在连 Wi-Fi，这时候设灯要等连完
+static JOINING: AtomicBool = AtomicBool::new(false);
+
+/// 配了 Wi-Fi 要连(不管连没连上)
+static WANTED: AtomicBool = AtomicBool::new(false);
+
+/// 链路是通的(`status_task` 看协议栈得出来的)
+static LINK_UP: AtomicBool = AtomicBool::new(false);
+
+/// 有人连在 TCP 端口上
+static CLIENT: AtomicBool = AtomicBool::new(false);
+
+/// 执行器，`start` 以前是空的
+static EXECUTOR: AtomicPtr<Executor> = AtomicPtr::new(core::ptr::null_mut());
+
+/// `embassy-time` 的时钟：rp2040-hal 的定时器本来就是 64 位微秒
+struct HalTimeDriver;
+
+impl embassy_time_driver::Driver for HalTimeDriver {
+    fn now(&self) -> u64 {
+        shared::now_us()
+    }
+
+    /// 不排队：执行器每次 poll 都会轮到等定时器的任务，马上叫醒就是下一次 poll 再看到没到点
+    fn schedule_wake(&self, _at: u64, waker: &Waker) {
+        waker.wake_by_ref();
+    }
+}
+
+embassy_time_driver::time_driver_impl!(static TIME_DRIVER: HalTimeDriver = HalTimeDriver);
+
+/// 裸执行器叫醒任务的时候会调这个，什么都不用做：主循环反正每一圈都会 poll
+#[export_name = "__pender"]
+fn pender(_context: *mut ()) {}
+
+/// 用 GPIO 模拟的 gSPI，见模块文档
+pub struct GSpi {
+    /// 片选和数据线的引脚对象只是占着，翻转走 SIO 寄存器，比 embedded-hal 的接口快
+    _cs: OutputPin,
+    _dio: OutputPin,
+}
+
+impl GSpi {
+    fn new(cs: OutputPin, mut dio: OutputPin) -> Self {
+        dio.set_input_enable(true);
+        dio.set_schmitt_enabled(true);
+        // 安全性：这两个引脚归这里独占
+        let sio = unsafe { &*pac::SIO::ptr() };
+        sio.gpio_out_set().write(|w| unsafe { w.bits(1 << CS) });
+        sio.gpio_out_clr().write(|w| unsafe { w.bits(1 << DIO) });
+        Self { _cs: cs, _dio: dio }
+    }
+
+    /// 一次传输：片选拉低、临时接管 GP29 当时钟，写 `write`，读 `read`，再读一个状态字，最后全部恢复
+    fn transfer(&mut self, write: &[u32], read: &mut [u32]) -> u32 {
+        // 安全性：GP29 只在片选拉低的这一段借用，结束以前把寄存器原样写回去；SIO 的置位/清零寄存器不影响别的引脚
+        let (sio, pads, bank) = unsafe { (&*pac::SIO::ptr(), &*pac::PADS_BANK0::ptr(), &*pac::IO_BANK0::ptr()) };
+        let saved_pad = pads.gpio(CLK as usize).read().bits();
+        let saved_func = bank.gpio(CLK as usize).gpio_ctrl().read().funcsel().bits();
+        let set = |mask: u32| sio.gpio_out_set().write(|w| unsafe { w.bits(mask) });
+        let clear = |mask: u32| sio.gpio_out_clr().write(|w| unsafe { w.bits(mask) });
+
+        clear(1 << CLK);
+        pads.gpio(CLK as usize).modify(|_, w| w.od().clear_bit());
+        bank.gpio(CLK as usize).gpio_ctrl().modify(|_, w| unsafe { w.funcsel().bits(FUNCSEL_SIO) });
+        sio.gpio_oe_set().write(|w| unsafe { w.bits(1 << CLK | 1 << DIO) });
+        clear(1 << CS);
+
+        for word in write {
+            for bit in (0..32).rev() {
+                if word >> bit & 1 != 0 {
+                    set(1 << DIO);
+                } else {
+                    clear(1 << DIO);
+                }
+                set(1 << CLK);
+                clear(1 << CLK);
+            }
+        }
+        // 数据线交给芯片，时钟低着等一个周期再读
+        sio.gpio_oe_clr().write(|w| unsafe { w.bits(1 << DIO) });
+        cortex_m::asm::delay(8);
+        let read_word = || {
+            let mut word = 0u32;
+            for _ in 0..32 {
+                set(1 << CLK);
+                word = word << 1 | (sio.gpio_in().read().bits() >> DIO & 1);
+                clear(1 << CLK);
+            }
+            word
+        };
+        for word in read.iter_mut() {
+            *word = read_word();
+        }
+        let status = read_word();
+
+        set(1 << CS);
+        sio.gpio_oe_clr().write(|w| unsafe { w.bits(1 << CLK) });
+        bank.gpio(CLK as usize).gpio_ctrl().modify(|_, w| unsafe { w.funcsel().bits(saved_func) });
+        pads.gpio(CLK as usize).write(|w| unsafe { w.bits(saved_pad) });
+        status
+    }
+
+    /// 片选拉高的时候芯片把数据线拉高表示有事情要处理
+    fn event_pending(&self) -> bool {
+        // 安全性：只读
+        let sio = unsafe { &*pac::SIO::ptr() };
+        sio.gpio_in().read().bits() >> DIO & 1 != 0
+    }
+}
+
+impl SpiBusCyw43 for GSpi {
+    async fn cmd_write(&mut self, write: &[u32]) -> u32 {
+        self.transfer(write, &mut [])
+    }
+
+    async fn cmd_read(&mut self, write: u32, read: &mut [u32]) -> u32 {
+        self.transfer(&[write], read)
+    }
+
+    async fn wait_for_event(&mut self) {
+        core::future::poll_fn(|cx| {
+            if self.event_pending() {
+                Poll::Ready(())
+            } else {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        })
+        .await
+    }
+}
+
+/// 驱动的 `Runner`：收发都靠它，一直跑
+#[embassy_executor::task]
+async fn runner_task(runner: cyw43::Runner<'static, OutputPin, GSpi>) -> ! {
+    runner.run().await
+}
+
+/// 控制任务：初始化完成以后设芯片的 GPIO(灯)、连 Wi-Fi
+#[embassy_executor::task]
+async fn control_task(mut control: cyw43::Control<'static>) -> ! {
+    control.init(cyw43_firmware::CYW43_43439A0_CLM).await;
+    READY.store(true, Ordering::Relaxed);
+    let mut wanted: Option<WifiCredentials> = None;
+    let mut retry_at = 0;
+    loop {
+        let rejoin = async {
+            match wanted {
+                Some(_) => rejoin_due(retry_at).await,
+                None => pending().await,
+            }
+        };
+        match select3(LED_REQUEST.wait(), next_join_request(), rejoin).await {
+            Either3::First(on) => {
+                control.gpio_set(LED_GPIO, on).await;
+                LED_STATE.store(on as u8, Ordering::Relaxed);
+            }
+            Either3::Second(credentials) => {
+                if wanted.is_some() {
+                    control.leave().await;
+                }
+                WANTED.store(credentials.is_some(), Ordering::Relaxed);
+                wanted = credentials;
+                retry_at = 0;
+            }
+            Either3::Third(()) => {
+                let Some(credentials) = wanted.as_ref() else {
+                    continue;
+                };
+                JOINING.store(true, Ordering::Relaxed);
+                let options = match credentials.password() {
+                    "" => JoinOptions::new_open(),
+                    password => JoinOptions::new(password.as_bytes()),
+                };
+                match control.join(credentials.ssid(), options).await {
+                    Ok(()) => defmt::info!("wifi: joined {=str}", credentials.ssid()),
+                    Err(err) => defmt::warn!("wifi: join {=str} failed, status {}", credentials.ssid(), err.status),
+                }
+                JOINING.store(false, Ordering::Relaxed);
+                // 连上了也隔一会儿再看链路，协议栈看到链路起来要一点时间
+                retry_at = shared::now_ms() + JOIN_RETRY_MS;
+            }
+        }
+    }
+}
+
+/// 等 `NetLink::join` 给的新账号
+async fn next_join_request() -> Option<WifiCredentials> {
+    poll_fn(|cx| match PIPE.lock(|pipe| pipe.take_join()) {
+        Some(credentials) => Poll::Ready(credentials),
+        None => {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+/// 等到该(重新)连的时候：链路不通，而且到了 `retry_at`
+async fn rejoin_due(retry_at: u64) {
+    poll_fn(|cx| {
+        if !LINK_UP.load(Ordering::Relaxed) && shared::now_ms() >= retry_at {
+            Poll::Ready(())
+        } else {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+/// 协议栈
+#[embassy_executor::task]
+async fn net_task(mut runner: embassy_net::Runner<'static, cyw43::NetDriver<'static>>) -> ! {
+    runner.run().await
+}
+
+/// 算 `NetState` 交给主循环，每次 poll 看一遍
+#[embassy_executor::task]
+async fn status_task(stack: Stack<'static>) -> ! {
+    loop {
+        let link_up = stack.is_link_up();
+        LINK_UP.store(link_up, Ordering::Relaxed);
+        let ip = stack.config_v4().filter(|_| link_up).map(|config| config.address.address().octets());
+        let state = match ip {
+            _ if !WANTED.load(Ordering::Relaxed) => NetState::Off,
+            None => NetState::Joining,
+            Some(ip) if CLIENT.load(Ordering::Relaxed) => NetState::Client(ip),
+            Some(ip) => NetState::Online(ip),
+        };
+        PIPE.lock(|pipe| pipe.set_state(state));
+        yield_now().await;
+    }
+}
+
+/// 在 `PORT` 上一次接一个连接
+#[embassy_executor::task]
+async fn tcp_task(stack: Stack<'static>) -> ! {
+    let mut rx_buffer = [0u8; TCP_RX_LEN];
+    let mut tx_buffer = [0u8; TCP_TX_LEN];
+    loop {
+        let mut socket = TcpSocket::new(stack, &mut rx_buffer, &mut tx_buffer);
+        socket.set_timeout(Some(IDLE_TIMEOUT));
+        socket.set_keep_alive(Some(KEEP_ALIVE));
+        if socket.accept(PORT).await.is_err() {
+            continue;
+        }
+        defmt::info!("net: client connected");
+        CLIENT.store(true, Ordering::Relaxed);
+        serve(&mut socket).await;
+        CLIENT.store(false, Ordering::Relaxed);
+        socket.close();
+        let _ = select(socket.flush(), Timer::after(CLOSE_TIMEOUT)).await;
+        defmt::info!("net: client gone");
+    }
+}
+
+/// 在连接和 net_console 的两条队列之间搬字节，对面关了、超时了就返回
+async fn serve(socket: &mut TcpSocket<'_>) {
+    loop {
+        if socket.can_recv() {
+            let free = PIPE.lock(|pipe| pipe.rx_free());
+            if free > 0 {
+                let received = socket.read_with(|data| {
+                    let len = data.len().min(free);
+                    PIPE.lock(|pipe| pipe.push_received(&data[..len]));
+                    (len, ())
+                });
+                if received.await.is_err() {
+                    return;
+                }
+            }
+        } else if !socket.may_recv() {
+            return;
+        }
+        if socket.can_send() {
+            let sent = socket.write_with(|room| (PIPE.lock(|pipe| pipe.take_tx(room)), ()));
+            if sent.await.is_err() {
+                return;
+            }
+        }
+        yield_now().await;
+    }
+}
+
+/// 协议栈要的随机种子(TCP 的初始序号用)：攒 64 个 ROSC 的随机位。ROSC 开机以后一直在跑
+fn random_seed() -> u64 {
+    // 安全性：只读
+    let rosc = unsafe { &*pac::ROSC::ptr() };
+    (0..64).fold(0, |seed, _| seed << 1 | rosc.randombit().read().randombit().bit() as u64)
+}
+
+/// 跑一遍执行器里该跑的任务，还没起来就什么都不做
+fn poll_executor() {
+    let executor = EXECUTOR.load(Ordering::Relaxed);
+    if executor.is_null() {
+        return;
+    }
+    // 安全性：指针是 `start` 里 singleton 出来的，一直有效；执行器只在主循环里 poll，不会重入
+    unsafe { (*executor).poll() };
+}
+
+/// 不停地 poll 一个 future，超时返回 None。只用在开机等驱动起来的时候
+fn block_on_for<F: Future>(future: F, timeout_ms: u64) -> Option<F::Output> {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    let deadline = shared::now_ms() + timeout_ms;
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return Some(output);
+        }
+        if shared::now_ms() >= deadline {
+            return None;
+        }
+    }
+}
+
+/// 无线芯片上的板载 LED，网络也从这里起(`start_network`)
+pub struct Cyw43Led {
+    /// 上一次要的状态，没变就不发命令
+    wanted: Option<bool>,
+    /// 驱动给协议栈的网卡，`start_network` 拿走
+    net: Option<cyw43::NetDriver<'static>>,
+}
+
+impl Cyw43Led {
+    /// 给无线芯片上电、下载固件、初始化，最多等 `BRING_UP_TIMEOUT_MS`，起不来返回 None。
+    /// `pwr`、`cs`、`dio` 是 GP23、GP25、GP24，都配成推挽输出；只能调一次
+    pub fn start(pwr: OutputPin, cs: OutputPin, dio: OutputPin) -> Option<Self> {
+        let started = shared::now_ms();
+        let state = cortex_m::singleton!(: cyw43::State = cyw43::State::new())?;
+        let executor: &'static Executor = cortex_m::singleton!(: Executor = Executor::new(core::ptr::null_mut()))?;
+        let spi = GSpi::new(cs, dio);
+        let Some((net, control, runner)) = block_on_for(
+            cyw43::new(state, pwr, spi, cyw43_firmware::CYW43_43439A0),
+            BRING_UP_TIMEOUT_MS,
+        ) else {
+            defmt::warn!("cyw43: no answer from the wireless chip, onboard LED disabled");
+            return None;
+        };
+        let spawner = executor.spawner();
+        if spawner.spawn(runner_task(runner)).is_err() || spawner.spawn(control_task(control)).is_err() {
+            return None;
+        }
+        EXECUTOR.store(executor as *const Executor as *mut Executor, Ordering::Relaxed);
+        let deadline = started + BRING_UP_TIMEOUT_MS;
+        while !READY.load(Ordering::Relaxed) {
+            if shared::now_ms() >= deadline {
+                defmt::warn!("cyw43: init timed out, onboard LED disabled");
+                return None;
+            }
+            poll_executor();
+        }
+        defmt::info!("cyw43: up after {} ms", shared::now_ms() - started);
+        Some(Self {
+            wanted: None,
+            net: Some(net),
+        })
+    }
+
+    /// 驱动一下无线芯片：跑一遍该跑的任务就返回
+    pub fn poll(&mut self) {
+        poll_executor();
+    }
+
+    /// 起协议栈和 TCP 端口，不连 Wi-Fi(账号用 `NetLink::join` 给)。只能调一次，第二次返回 None
+    pub fn start_network(&mut self) -> Option<NetLink> {
+        let device = self.net.take()?;
+        let resources = cortex_m::singleton!(: StackResources<SOCKETS> = StackResources::new())?;
+        let config = embassy_net::Config::dhcpv4(Default::default());
+        let (stack, runner) = embassy_net::new(device, config, resources, random_seed());
+        // 安全性：`start` 里存进去的，不是空的
+        let spawner = unsafe { &*EXECUTOR.load(Ordering::Relaxed) }.spawner();
+        spawner.spawn(net_task(runner)).ok()?;
+        spawner.spawn(status_task(stack)).ok()?;
+        spawner.spawn(tcp_task(stack)).ok()?;
+        Some(NetLink::new(poll_executor))
+    }
+}
+
+impl StatusLed for Cyw43Led {
+    fn set_on(&mut self, on: bool) {
+        if self.wanted != Some(on) {
+            self.wanted = Some(on);
+            LED_REQUEST.signal(on);
+            let deadline = shared::now_ms() + LED_TIMEOUT_MS;
+            while !JOINING.load(Ordering::Relaxed)
+                && LED_STATE.load(Ordering::Relaxed) != on as u8
+                && shared::now_ms() < deadline
+            {
+                self.poll();
+            }
+        }
+        self.poll();
+    }
+}
@@ -0,0 +1,254 @@
+//! WS2812 灯带：一个 PIO 状态机产生 800kHz 的波形，DMA 把颜色数据喂进 PIO 的 FIFO
+//!
+//! WS2812 每一位都是"先高后低"，高电平的长短区分 0 和 1，时序要求到几百纳秒，
+//! CPU 用 GPIO 翻转很难保证(中断一来就乱了)，所以交给 PIO：每位 10 个 PIO 周期，
+//! 起始 2 个周期高电平，数据 5 个周期(1 为高，0 为低)，结束 3 个周期低电平。
+//! 程序和 rp2040-hal 的 ws2812 例子一样，只是数据改成由 DMA 搬运，刷新灯带的时候 CPU 不用等。
+//!
+//! 这个模块要占一个 PIO 状态机和一个 DMA 通道，所以放在 `ws2812` feature 后面，不开就完全不编译。
+
+use rp2040_hal::dma::single_buffer::{Config, Transfer};
+use rp2040_hal::dma::SingleChannel;
+use rp2040_hal::gpio::{DynPinId, Function, Pin, PinId, PullNone, PullType, ValidFunction};
+use rp2040_hal::pio::{
+    Buffers, PIOBuilder, PIOExt, PinDir, Running, ShiftDirection, StateMachine, StateMachineIndex,
+    Tx, UninitStateMachine, PIO,
+};
+
+use crate::alert::{AlertLevel, Alerts};
+
+/// WS2812 的位速率
+const BIT_RATE_HZ: u32 = 800_000;
+
+/// 每一位的三段各占几个 PIO 周期
+const T1: u8 = 2;
+const T2: u8 = 5;
+const T3: u8 = 3;
+const CYCLES_PER_BIT: u32 = (T1 + T2 + T3) as u32;
+
+/// 灯带最快多久刷新一次(毫秒)，动画 50Hz 足够了
+const REFRESH_INTERVAL_MS: u64 = 20;
+
+/// 呼吸灯一个周期(毫秒)
+const BREATHE_PERIOD_MS: u64 = 2000;
+
+/// 绿灯脉冲的周期和每次亮多久(毫秒)
+const PULSE_PERIOD_MS: u64 = 1000;
+const PULSE_ON_MS: u64 = 150;
+
+/// 颜色
+#[derive(Debug, Clone, Copy, PartialEq, Eq, defmt::Format)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    pub const OFF: Rgb = Rgb::new(0, 0, 0);
+    pub const RED: Rgb = Rgb::new(255, 0, 0);
+    pub const AMBER: Rgb = Rgb::new(255, 100, 0);
+    pub const GREEN: Rgb = Rgb::new(0, 255, 0);
+
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    /// 按亮度缩放，255 不变，0 全灭
+    pub const fn scale(self, level: u8) -> Self {
+        let l = level as u16;
+        Self::new(
+            ((self.r as u16 * l) / 255) as u8,
+            ((self.g as u16 * l) / 255) as u8,
+            ((self.b as u16 * l) / 255) as u8,
+        )
+    }
+
+    /// FIFO 里的格式：WS2812 要求 GRB 顺序、高位先发，PIO 从 32 位字的最高位开始移出 24 位
+    const fn fifo_word(self) -> u32 {
+        ((self.g as u32) << 24) | ((self.r as u32) << 16) | ((self.b as u32) << 8)
+    }
+}
+
+/// DMA 用的颜色缓冲，要放在静态区(`cortex_m::singleton!`)
+pub type StripBuffer<const N: usize> = [u32; N];
+
+enum State<P: PIOExt, SM: StateMachineIndex, CH: SingleChannel, const N: usize> {
+    Idle(CH, &'static mut StripBuffer<N>, Tx<(P, SM)>),
+    /// DMA 还在往 FIFO 里搬数据
+    Sending(Transfer<CH, &'static mut StripBuffer<N>, Tx<(P, SM)>>),
+}
+
+/// `N` 颗灯珠的 WS2812 灯带
+pub struct Ws2812<P: PIOExt, SM: StateMachineIndex, CH: SingleChannel, const N: usize> {
+    // 只有在状态切换的一瞬间是 None
+    state: Option<State<P, SM, CH, N>>,
+    _sm: StateMachine<(P, SM), Running>,
+    _pin: Pin<DynPinId, P::PinFunction, PullNone>,
+}
+
+impl<P, SM, CH, const N: usize> Ws2812<P, SM, CH, N>
+where
+    P: PIOExt,
+    SM: StateMachineIndex,
+    CH: SingleChannel,
+{
+    /// 装好 PIO 程序、配好状态机并启动，灯带默认全灭
+    ///
+    /// `system_clock_hz` 用来算 PIO 的分频，超频或者换了时钟源的话要传实际的频率
+    pub fn new<I, F, PT>(
+        pin: Pin<I, F, PT>,
+        pio: &mut PIO<P>,
+        sm: UninitStateMachine<(P, SM)>,
+        channel: CH,
+        buffer: &'static mut StripBuffer<N>,
+        system_clock_hz: u32,
+    ) -> Self
+    where
+        I: PinId + ValidFunction<P::PinFunction>,
+        F: Function,
+        PT: PullType,
+    {
+        let pin = pin
+            .into_function::<P::PinFunction>()
+            .into_pull_type::<PullNone>()
+            .into_dyn_pin();
+        let pin_num = pin.id().num;
+
+        let side_set = pio::SideSet::new(false, 1, false);
+        let mut a = pio::Assembler::new_with_side_set(side_set);
+        let mut wrap_target = a.label();
+        let mut wrap_source = a.label();
+        let mut do_zero = a.label();
+        a.bind(&mut wrap_target);
+        // 结束段：低电平，同时取下一位
+        a.out_with_delay_and_side_set(pio::OutDestination::X, 1, T3 - 1, 0);
+        // 起始段：高电平
+        a.jmp_with_delay_and_side_set(pio::JmpCondition::XIsZero, &mut do_zero, T1 - 1, 1);
+        // 数据位 1：继续高电平
+        a.jmp_with_delay_and_side_set(pio::JmpCondition::Always, &mut wrap_target, T2 - 1, 1);
+        a.bind(&mut do_zero);
+        // 数据位 0：低电平
+        a.nop_with_delay_and_side_set(T2 - 1, 0);
+        a.bind(&mut wrap_source);
+        let program = a.assemble_with_wrap(wrap_source, wrap_target);
+        // 程序只有 4 条指令，PIO 的 32 条指令内存只要没被别的程序占满就装得下
+        let installed = pio.install(&program).unwrap();
+
+        // 分频 = 系统时钟 / (位速率 * 每位周期数)，小数部分是 1/256 为单位的定点数
+        let bit_freq = BIT_RATE_HZ * CYCLES_PER_BIT;
+        let int = system_clock_hz / bit_freq;
+        let frac = ((system_clock_hz % bit_freq) * 256) / bit_freq;
+
+        let (mut sm, _rx, tx) = PIOBuilder::from_installed_program(installed)
+            .buffers(Buffers::OnlyTx)
+            .side_set_pin_base(pin_num)
+            .out_shift_direction(ShiftDirection::Left)
+            .autopull(true)
+            .pull_threshold(24)
+            .clock_divisor_fixed_point(int as u16, frac as u8)
+            .build(sm);
+        sm.set_pindirs([(pin_num, PinDir::Output)]);
+
+        let mut strip = Self {
+            state: Some(State::Idle(channel, buffer, tx)),
+            _sm: sm.start(),
+            _pin: pin,
+        };
+        strip.write(&[Rgb::OFF; N]);
+        strip
+    }
+
+    /// DMA 是否空闲(上一次的数据已经全部进了 FIFO)
+    pub fn is_idle(&mut self) -> bool {
+        if let Some(State::Sending(transfer)) = &self.state {
+            if !transfer.is_done() {
+                return false;
+            }
+        }
+        if let Some(State::Sending(transfer)) = self.state.take() {
+            let (channel, buffer, tx) = transfer.wait();
+            self.state = Some(State::Idle(channel, buffer, tx));
+        }
+        true
+    }
+
+    /// 把颜色交给 DMA 发出去，立刻返回。上一次还没发完会返回 false，这一帧就丢掉了
+    pub fn write(&mut self, colors: &[Rgb; N]) -> bool {
+        if !self.is_idle() {
+            return false;
+        }
+        let Some(State::Idle(channel, buffer, tx)) = self.state.take() else {
+            return false;
+        };
+        for (word, color) in buffer.iter_mut().zip(colors) {
+            *word = color.fifo_word();
+        }
+        let transfer = Config::new(channel, buffer, tx).start();
+        self.state = Some(State::Sending(transfer));
+        true
+    }
+}
+
+/// 告警级别对应的灯效：严重是红色常亮，警告是琥珀色呼吸，提示是绿色脉冲
+pub fn alert_color(level: AlertLevel, elapsed_ms: u64) -> Rgb {
+    match level {
+        AlertLevel::None => Rgb::OFF,
+        AlertLevel::Notice => {
+            if elapsed_ms % PULSE_PERIOD_MS < PULSE_ON_MS {
+                Rgb::GREEN
+            } else {
+                Rgb::OFF
+            }
+        }
+        AlertLevel::Warning => {
+            // 三角波，最暗的时候也留一点亮度，看得出灯没坏
+            let phase = elapsed_ms % BREATHE_PERIOD_MS;
+            let half = BREATHE_PERIOD_MS / 2;
+            let ramp = if phase < half {
+                phase
+            } else {
+                BREATHE_PERIOD_MS - phase
+            };
+            Rgb::AMBER.scale((16 + ramp * (255 - 16) / half) as u8)
+        }
+        AlertLevel::Critical => Rgb::RED,
+    }
+}
+
+/// 告警桥：把 `Alerts` 的状态画到灯带上，整条灯带同一个颜色
+pub struct AlertStrip<P: PIOExt, SM: StateMachineIndex, CH: SingleChannel, const N: usize> {
+    strip: Ws2812<P, SM, CH, N>,
+    last_color: Rgb,
+    last_refresh_ms: u64,
+}
+
+impl<P, SM, CH, const N: usize> AlertStrip<P, SM, CH, N>
+where
+    P: PIOExt,
+    SM: StateMachineIndex,
+    CH: SingleChannel,
+{
+    pub fn new(strip: Ws2812<P, SM, CH, N>) -> Self {
+        Self {
+            strip,
+            last_color: Rgb::OFF,
+            last_refresh_ms: 0,
+        }
+    }
+
+    /// 主循环每一圈调一次，颜色变了才会真正发数据，最多 50Hz
+    pub fn update(&mut self, alerts: &mut Alerts, now_ms: u64) {
+        if now_ms.saturating_sub(self.last_refresh_ms) < REFRESH_INTERVAL_MS {
+            return;
+        }
+        let color = alert_color(alerts.level(now_ms), alerts.elapsed_ms(now_ms));
+        if color == self.last_color {
+            return;
+        }
+        if self.strip.write(&[color; N]) {
+            self.last_color = color;
+            self.last_refresh_ms = now_ms;
+        }
+    }
+}
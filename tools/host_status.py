@@ -0,0 +1,73 @@
+#!/usr/bin/env python3
+"""往 rp2040 OLED 的 HID 接口发一帧电脑状态，并等设备回 ack
+
+依赖 hidapi 的 python 绑定: pip install hidapi
+报文布局见固件的 src/host_status.rs
+
+用法:
+    python3 tools/host_status.py --title "Never Gonna Give You Up" --cpu 42 --volume 70 --caps
+"""
+import argparse
+import sys
+
+import hid
+
+USB_VID = 0x16C0
+USB_PID = 0x27DD
+REPORT_LEN = 32
+PACKET_VERSION = 1
+HEADER_LEN = 6
+
+FLAG_NUM_LOCK = 1 << 0
+FLAG_CAPS_LOCK = 1 << 1
+FLAG_SCROLL_LOCK = 1 << 2
+FLAG_MUTED = 1 << 3
+
+ACK_CODES = {0: "ok", 1: "unsupported version", 2: "malformed"}
+
+
+def encode_packet(seq, cpu, volume, flags, title):
+    title_bytes = title.encode("ascii", "replace")[: REPORT_LEN - HEADER_LEN]
+    packet = bytes([PACKET_VERSION, seq & 0xFF, cpu, volume, flags, len(title_bytes)]) + title_bytes
+    return packet.ljust(REPORT_LEN, b"\0")
+
+
+def main():
+    parser = argparse.ArgumentParser(description=__doc__, formatter_class=argparse.RawDescriptionHelpFormatter)
+    parser.add_argument("--title", default="")
+    parser.add_argument("--cpu", type=int, default=0)
+    parser.add_argument("--volume", type=int, default=50)
+    parser.add_argument("--seq", type=int, default=1)
+    parser.add_argument("--num", action="store_true")
+    parser.add_argument("--caps", action="store_true")
+    parser.add_argument("--scroll", action="store_true")
+    parser.add_argument("--muted", action="store_true")
+    args = parser.parse_args()
+
+    flags = 0
+    flags |= FLAG_NUM_LOCK if args.num else 0
+    flags |= FLAG_CAPS_LOCK if args.caps else 0
+    flags |= FLAG_SCROLL_LOCK if args.scroll else 0
+    flags |= FLAG_MUTED if args.muted else 0
+
+    packet = encode_packet(args.seq, args.cpu, args.volume, flags, args.title)
+
+    dev = hid.device()
+    dev.open(USB_VID, USB_PID)
+    try:
+        # 设备不用 report id，hidapi 要求第一个字节写 0
+        dev.write(b"\0" + packet)
+        ack = dev.read(REPORT_LEN, timeout_ms=1000)
+    finally:
+        dev.close()
+
+    if not ack:
+        print("no ack from device", file=sys.stderr)
+        return 1
+    version, seq, code = ack[0], ack[1], ack[2]
+    print(f"ack: version={version} seq={seq} result={ACK_CODES.get(code, code)}")
+    return 0 if code == 0 and seq == args.seq & 0xFF else 1
+
+
+if __name__ == "__main__":
+    sys.exit(main())
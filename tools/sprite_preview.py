@@ -0,0 +1,124 @@
+#!/usr/bin/env python3
+"""把一张 PNG 转成精灵的字节格式，通过串口 PREVIEW 命令推到屏幕上直接看(固件要带 devtools feature 编译)
+
+依赖 pyserial 和 Pillow: pip install pyserial pillow
+字节格式见固件的 src/devtools.rs：一行一行，一个字节 8 个像素，高位在左，每行补齐到整字节。
+比 128x64 大的图固件不收。亮度超过阈值的像素算亮，PNG 的透明部分算暗。
+
+用法:
+    python3 tools/sprite_preview.py /dev/ttyACM0 icon.png --at 40 16 --grid
+    python3 tools/sprite_preview.py /dev/ttyACM0 icon.png --watch     # 文件一变就重新推
+    python3 tools/sprite_preview.py /dev/ttyACM0 --off                # 关掉预览
+    python3 tools/sprite_preview.py - icon.png --rust                 # 不发，打印成 Rust 数组贴进 sprite.rs
+"""
+import argparse
+import base64
+import os
+import sys
+import time
+
+from PIL import Image
+
+MAX_WIDTH = 128
+MAX_HEIGHT = 64
+# 一行命令最多带 120 字节(160 个 base64 字)，见 src/devtools.rs 的 CHUNK_BYTES
+CHUNK_BYTES = 120
+REPLY_TIMEOUT_S = 2.0
+
+
+def to_sprite(path, threshold):
+    """返回 (宽, 高, 字节)"""
+    image = Image.open(path).convert("RGBA")
+    width, height = image.size
+    if width > MAX_WIDTH or height > MAX_HEIGHT:
+        sys.exit(f"{path}: {width}x{height} is larger than {MAX_WIDTH}x{MAX_HEIGHT}")
+    stride = (width + 7) // 8
+    data = bytearray(stride * height)
+    for y in range(height):
+        for x in range(width):
+            r, g, b, a = image.getpixel((x, y))
+            luma = (r * 299 + g * 587 + b * 114) // 1000
+            if a >= 128 and luma >= threshold:
+                data[y * stride + x // 8] |= 0x80 >> (x % 8)
+    return width, height, bytes(data)
+
+
+def command(port, line):
+    """发一行命令，等 OK/ERR 那一行"""
+    port.write((line + "\r\n").encode("ascii"))
+    deadline = time.monotonic() + REPLY_TIMEOUT_S
+    while time.monotonic() < deadline:
+        reply = port.readline().decode("ascii", "replace").strip()
+        if reply.startswith("OK"):
+            return reply
+        if reply.startswith("ERR"):
+            sys.exit(f"{line.split(' ')[0]}: {reply}")
+    sys.exit("no reply from the board (is the firmware built with --features devtools?)")
+
+
+def push(port, width, height, data):
+    reply = ""
+    for start in range(0, len(data), CHUNK_BYTES):
+        chunk = base64.b64encode(data[start : start + CHUNK_BYTES]).decode("ascii")
+        reply = command(port, f"PREVIEW {width} {height} {chunk}")
+    return reply
+
+
+def print_rust(path, width, height, data):
+    name = os.path.splitext(os.path.basename(path))[0].upper().replace("-", "_")
+    stride = (width + 7) // 8
+    print(f"/// {os.path.basename(path)}，{width}x{height}")
+    print(f"pub const {name}: [u8; {len(data)}] = [")
+    for row in range(height):
+        line = data[row * stride : (row + 1) * stride]
+        print("    " + ", ".join(f"0b{byte:08b}" for byte in line) + ",")
+    print("];")
+
+
+def main():
+    parser = argparse.ArgumentParser(description=__doc__, formatter_class=argparse.RawDescriptionHelpFormatter)
+    parser.add_argument("port", help="串口，比如 /dev/ttyACM0；只打印 --rust 的话写 -")
+    parser.add_argument("png", nargs="?")
+    parser.add_argument("--at", nargs=2, type=int, metavar=("X", "Y"), help="图的左上角放在哪")
+    parser.add_argument("--grid", choices=["on", "off"], help="叠 8 像素网格")
+    parser.add_argument("--threshold", type=int, default=128, help="亮度多少以上算亮，0-255")
+    parser.add_argument("--watch", action="store_true", help="文件一变就重新推，Ctrl-C 退出")
+    parser.add_argument("--off", action="store_true", help="关掉预览，回到原来的页面")
+    parser.add_argument("--rust", action="store_true", help="不发，打印成 Rust 数组")
+    args = parser.parse_args()
+
+    if args.rust:
+        if not args.png:
+            parser.error("--rust needs a png")
+        print_rust(args.png, *to_sprite(args.png, args.threshold))
+        return
+
+    import serial
+
+    with serial.Serial(args.port, 115200, timeout=0.2) as port:
+        if args.off:
+            command(port, "PREVIEW OFF")
+            return
+        if args.at:
+            command(port, f"PREVIEW AT {args.at[0]} {args.at[1]}")
+        if args.grid:
+            command(port, f"GRID {args.grid.upper()}")
+        if not args.png:
+            return
+        mtime = None
+        while True:
+            current = os.stat(args.png).st_mtime
+            if current != mtime:
+                mtime = current
+                width, height, data = to_sprite(args.png, args.threshold)
+                print(f"{args.png}: {width}x{height}, {len(data)} bytes, {push(port, width, height, data)}")
+            if not args.watch:
+                break
+            time.sleep(0.5)
+
+
+if __name__ == "__main__":
+    try:
+        main()
+    except KeyboardInterrupt:
+        pass